@@ -0,0 +1,160 @@
+//! The `#[retry]` attribute macro of the `retry` crate, which re-exports it with its `"macros"`
+//! feature. Depend on `retry` rather than on this crate: the code the macro generates refers to
+//! `::retry`.
+
+#![deny(missing_debug_implementations, missing_docs, warnings)]
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{quote, quote_spanned};
+use syn::{
+    meta::ParseNestedMeta, parse_macro_input, parse_quote, spanned::Spanned, Expr, FnArg,
+    GenericArgument, ItemFn, LitInt, LitStr, Pat, PathArguments, ReturnType, Type,
+};
+
+/// Retry the body of a function returning `Result<T, E>` according to a policy, changing the
+/// function to return `Result<T, retry::Error<E>>`.
+///
+/// The macro takes the policy as an expression in a string, evaluated in the scope of the function
+/// each time it is called, and optionally the maximum number of attempts, which overrides the one
+/// of the policy:
+///
+/// ```rust,ignore
+/// use retry::{presets, retry};
+///
+/// #[retry(policy = "presets::db_transaction()", max_attempts = 3)]
+/// fn transfer(client: &mut postgres::Client, amount: i64) -> Result<(), postgres::Error> {
+///     client.execute("UPDATE accounts SET balance = balance - $1 WHERE id = 1", &[&amount])?;
+///     client.execute("UPDATE accounts SET balance = balance + $1 WHERE id = 2", &[&amount])?;
+///     Ok(())
+/// }
+/// ```
+///
+/// The body of a synchronous function is retried with `Policy::retry`, so it may borrow its
+/// arguments, but not move them out. The body of an `async` function is retried with
+/// `Policy::retry_async`, which needs the `"asynchronous"` feature of `retry`, and each attempt
+/// runs with its own clones of the arguments, so they must be `Clone`. References are cloned as
+/// references, but `&mut` arguments cannot be given to an `async` function.
+///
+/// The error type must be spelled out in the return type, as in `Result<T, io::Error>` rather than
+/// `io::Result<T>`, and be `Debug`. A `return` in the body ends the attempt, not the call.
+#[proc_macro_attribute]
+pub fn retry(args: TokenStream, item: TokenStream) -> TokenStream {
+    let mut settings = Settings::default();
+    let parser = syn::meta::parser(|meta| settings.parse(meta));
+    parse_macro_input!(args with parser);
+    let function = parse_macro_input!(item as ItemFn);
+    expand(settings, function)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// The arguments of the attribute.
+#[derive(Default)]
+struct Settings {
+    policy: Option<Expr>,
+    max_attempts: Option<LitInt>,
+}
+
+impl Settings {
+    fn parse(&mut self, meta: ParseNestedMeta<'_>) -> syn::Result<()> {
+        if meta.path.is_ident("policy") {
+            self.policy = Some(meta.value()?.parse::<LitStr>()?.parse()?);
+            Ok(())
+        } else if meta.path.is_ident("max_attempts") {
+            self.max_attempts = Some(meta.value()?.parse()?);
+            Ok(())
+        } else {
+            Err(meta.error("expected `policy` or `max_attempts`"))
+        }
+    }
+}
+
+fn expand(settings: Settings, mut function: ItemFn) -> syn::Result<TokenStream2> {
+    let policy = settings.policy.ok_or_else(|| {
+        syn::Error::new(
+            function.sig.fn_token.span(),
+            "#[retry] needs a policy, such as `#[retry(policy = \"presets::grpc_default()\")]`",
+        )
+    })?;
+    let policy = match settings.max_attempts {
+        Some(max_attempts) => quote!((#policy).with_max_attempts(#max_attempts)),
+        None => quote!(#policy),
+    };
+
+    let output = match function.sig.output {
+        ReturnType::Type(_, ref output) => (**output).clone(),
+        ReturnType::Default => {
+            return Err(syn::Error::new(
+                function.sig.span(),
+                "#[retry] functions must return `Result<T, E>`",
+            ))
+        }
+    };
+    let (value, error) = result_types(&output)?;
+    function.sig.output = parse_quote!(-> ::core::result::Result<#value, ::retry::Error<#error>>);
+
+    let body = &function.block;
+    let retrying = if function.sig.asyncness.is_some() {
+        let clones = function.sig.inputs.iter().filter_map(|input| match input {
+            FnArg::Typed(argument) => match *argument.pat {
+                Pat::Ident(ref pat) => {
+                    let name = &pat.ident;
+                    Some(quote_spanned! {argument.span()=>
+                        let #name = ::core::clone::Clone::clone(&#name);
+                    })
+                }
+                _ => None,
+            },
+            FnArg::Receiver(_) => None,
+        });
+        let clones: Vec<_> = clones.collect();
+        quote! {
+            ::retry::Policy::retry_async(&__retry_policy, || {
+                #(#clones)*
+                async move {
+                    let __retry_result: #output = async move #body.await;
+                    __retry_result
+                }
+            })
+            .await
+        }
+    } else {
+        quote! {
+            ::retry::Policy::retry(&__retry_policy, || -> #output #body)
+        }
+    };
+    function.block = parse_quote!({
+        let __retry_policy = #policy;
+        #retrying
+    });
+    Ok(quote!(#function))
+}
+
+/// The value and error types of a `Result<T, E>`.
+fn result_types(output: &Type) -> syn::Result<(Type, Type)> {
+    let unsupported = || {
+        syn::Error::new(
+            output.span(),
+            "#[retry] functions must return `Result<T, E>`, with the error type spelled out",
+        )
+    };
+    let segment = match *output {
+        Type::Path(ref path) if path.qself.is_none() => path.path.segments.last(),
+        _ => None,
+    }
+    .filter(|segment| segment.ident == "Result")
+    .ok_or_else(unsupported)?;
+    let arguments = match segment.arguments {
+        PathArguments::AngleBracketed(ref arguments) => &arguments.args,
+        _ => return Err(unsupported()),
+    };
+    let mut types = arguments.iter().filter_map(|argument| match argument {
+        GenericArgument::Type(argument) => Some(argument.clone()),
+        _ => None,
+    });
+    match (types.next(), types.next(), types.next()) {
+        (Some(value), Some(error), None) => Ok((value, error)),
+        _ => Err(unsupported()),
+    }
+}
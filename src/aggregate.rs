@@ -0,0 +1,315 @@
+//! Strategies that turn the errors of the attempts of a call into the error it gives up with.
+//!
+//! `Policy::retry` gives up with the error of the last attempt and drops the others. With
+//! `Policy::retry_aggregated`, an `ErrorAggregator` receives the error of every attempt instead,
+//! and decides what the call gives up with:
+//!
+//! ```rust
+//! # use retry::delay::NoDelay;
+//! use retry::{aggregate::Deduplicated, Policy};
+//!
+//! let policy = Policy::new(NoDelay.take(3));
+//! let mut errors = vec!["timeout", "refused", "timeout", "refused"].into_iter();
+//!
+//! let error = policy
+//!     .retry_aggregated(Deduplicated::new(), || Err::<(), _>(errors.next().unwrap()))
+//!     .unwrap_err();
+//!
+//! assert_eq!(error.into_last_error(), Some(vec!["timeout", "refused"]));
+//! ```
+//!
+//! `Last`, `All`, `Deduplicated` and `Fold` cover the common cases, `Timestamped` records when each
+//! error occurred, and any other can implement the trait. Calls that give up without an error of
+//! an attempt, such as those that time out, drop the errors they were given.
+
+use std::{
+    collections::HashSet,
+    fmt,
+    time::{Duration, SystemTime},
+};
+
+use crate::{CorrelationId, Error, OperationResult, Policy};
+
+/// Turns the errors of the attempts of a call into the error it gives up with.
+///
+/// An aggregator is given to a single call, so it can accumulate the errors in its own state.
+pub trait ErrorAggregator<E> {
+    /// The error the call gives up with.
+    type Output;
+
+    /// Take the error of an attempt that is about to be retried.
+    fn aggregate(&mut self, error: E);
+
+    /// Produce the error the call gives up with, from the error of its last attempt.
+    fn finish(self, last: E) -> Self::Output;
+}
+
+/// Gives up with the error of the last attempt, as `Policy::retry` does.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Last;
+
+impl<E> ErrorAggregator<E> for Last {
+    type Output = E;
+
+    fn aggregate(&mut self, _: E) {}
+
+    fn finish(self, last: E) -> E {
+        last
+    }
+}
+
+/// Gives up with the errors of every attempt, in the order they occurred.
+#[derive(Clone, Debug)]
+pub struct All<E> {
+    errors: Vec<E>,
+}
+
+impl<E> All<E> {
+    /// Create an aggregator that keeps every error.
+    pub fn new() -> Self {
+        All { errors: Vec::new() }
+    }
+}
+
+impl<E> Default for All<E> {
+    fn default() -> Self {
+        All::new()
+    }
+}
+
+impl<E> ErrorAggregator<E> for All<E> {
+    type Output = Vec<E>;
+
+    fn aggregate(&mut self, error: E) {
+        self.errors.push(error);
+    }
+
+    fn finish(mut self, last: E) -> Vec<E> {
+        self.errors.push(last);
+        self.errors
+    }
+}
+
+/// Gives up with the first error of each distinct message, in the order the messages first
+/// occurred, so that an error repeated by every attempt is reported once.
+#[derive(Clone, Debug)]
+pub struct Deduplicated<E> {
+    errors: Vec<E>,
+    messages: HashSet<String>,
+}
+
+impl<E> Deduplicated<E> {
+    /// Create an aggregator that keeps one error per message.
+    pub fn new() -> Self {
+        Deduplicated {
+            errors: Vec::new(),
+            messages: HashSet::new(),
+        }
+    }
+}
+
+impl<E> Default for Deduplicated<E> {
+    fn default() -> Self {
+        Deduplicated::new()
+    }
+}
+
+impl<E> ErrorAggregator<E> for Deduplicated<E>
+where
+    E: fmt::Display,
+{
+    type Output = Vec<E>;
+
+    fn aggregate(&mut self, error: E) {
+        if self.messages.insert(error.to_string()) {
+            self.errors.push(error);
+        }
+    }
+
+    fn finish(mut self, last: E) -> Vec<E> {
+        self.aggregate(last);
+        self.errors
+    }
+}
+
+/// Gives up with an accumulator that the errors of every attempt were folded into.
+///
+/// ```rust
+/// # use retry::delay::NoDelay;
+/// use retry::{aggregate::Fold, Policy};
+///
+/// let policy = Policy::new(NoDelay.take(2));
+/// let counts = Fold::new((0, 0), |(timeouts, others): &mut (u64, u64), error: &str| {
+///     match error {
+///         "timeout" => *timeouts += 1,
+///         _ => *others += 1,
+///     }
+/// });
+/// let mut errors = vec!["timeout", "refused", "timeout"].into_iter();
+///
+/// let error = policy
+///     .retry_aggregated(counts, || Err::<(), _>(errors.next().unwrap()))
+///     .unwrap_err();
+///
+/// assert_eq!(error.into_last_error(), Some((2, 1)));
+/// ```
+pub struct Fold<A, F> {
+    accumulator: A,
+    fold: F,
+}
+
+impl<A, F> Fold<A, F> {
+    /// Create an aggregator that starts from `initial` and folds every error into it with `fold`.
+    pub fn new<E>(initial: A, fold: F) -> Self
+    where
+        F: FnMut(&mut A, E),
+    {
+        Fold {
+            accumulator: initial,
+            fold,
+        }
+    }
+}
+
+impl<A, F> fmt::Debug for Fold<A, F>
+where
+    A: fmt::Debug,
+{
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter
+            .debug_struct("Fold")
+            .field("accumulator", &self.accumulator)
+            .finish()
+    }
+}
+
+impl<A, E, F> ErrorAggregator<E> for Fold<A, F>
+where
+    F: FnMut(&mut A, E),
+{
+    type Output = A;
+
+    fn aggregate(&mut self, error: E) {
+        (self.fold)(&mut self.accumulator, error);
+    }
+
+    fn finish(mut self, last: E) -> A {
+        self.aggregate(last);
+        self.accumulator
+    }
+}
+
+/// Gives every error to another aggregator together with the time it occurred, when its attempt
+/// ended, as read from the system clock, so that the error the call gives up with tells when each
+/// failure happened.
+///
+/// ```rust
+/// # use std::time::{Duration, SystemTime};
+/// # use retry::delay::Fixed;
+/// use retry::{
+///     aggregate::{All, Timestamped},
+///     Policy,
+/// };
+///
+/// let policy = Policy::new(Fixed::from_millis(10).take(1));
+///
+/// let error = policy
+///     .retry_aggregated(Timestamped::new(All::new()), || Err::<(), _>("refused"))
+///     .unwrap_err();
+///
+/// let failures = error.into_last_error().unwrap();
+/// let (first, second) = (failures[0].0, failures[1].0);
+/// assert!(second.duration_since(first).unwrap() >= Duration::from_millis(10));
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct Timestamped<A> {
+    inner: A,
+}
+
+impl<A> Timestamped<A> {
+    /// Give the errors, with the time each occurred, to `inner`.
+    pub fn new(inner: A) -> Self {
+        Timestamped { inner }
+    }
+}
+
+impl<A, E> ErrorAggregator<E> for Timestamped<A>
+where
+    A: ErrorAggregator<(SystemTime, E)>,
+{
+    type Output = A::Output;
+
+    fn aggregate(&mut self, error: E) {
+        self.inner.aggregate((SystemTime::now(), error));
+    }
+
+    fn finish(self, last: E) -> A::Output {
+        self.inner.finish((SystemTime::now(), last))
+    }
+}
+
+impl<D> Policy<D>
+where
+    D: IntoIterator<Item = Duration> + Clone,
+{
+    /// Retry the given operation synchronously according to this policy, giving every error of
+    /// its attempts to `aggregator`, which produces the error the call gives up with.
+    pub fn retry_aggregated<O, R, E, OR, A>(
+        &self,
+        aggregator: A,
+        mut operation: O,
+    ) -> Result<R, Error<A::Output>>
+    where
+        O: FnMut() -> OR,
+        OR: Into<OperationResult<R, E>>,
+        E: fmt::Debug,
+        A: ErrorAggregator<E>,
+    {
+        self.execute_aggregated(CorrelationId::generate(), aggregator, None, |_, _| {
+            let result: OperationResult<R, E> = operation().into();
+            result.into()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{All, Last};
+    use crate::{delay::NoDelay, Error, OperationResult, Policy};
+
+    #[test]
+    fn gives_up_with_the_aggregated_errors() {
+        let policy = Policy::new(NoDelay.take(2));
+        let mut attempts = 0;
+
+        let result = policy.retry_aggregated(All::new(), || {
+            attempts += 1;
+            Err::<(), _>(attempts)
+        });
+        assert_eq!(
+            result,
+            Err(Error::Operation {
+                error: vec![1, 2, 3],
+                total_delay: Duration::default(),
+                tries: 3,
+            })
+        );
+
+        let result = policy.retry_aggregated(All::new(), || OperationResult::<(), _>::Err("bad"));
+        assert_eq!(result.unwrap_err().into_last_error(), Some(vec!["bad"]));
+
+        let result = policy
+            .with_max_attempts(2)
+            .retry_aggregated(Last, || Err::<(), _>("down"));
+        assert!(matches!(
+            result,
+            Err(Error::MaxAttempts {
+                error: "down",
+                tries: 2,
+                ..
+            })
+        ));
+    }
+}
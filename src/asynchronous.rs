@@ -1,34 +1,264 @@
 //! Asynchronous implementation of `retry` and `retry_with_index`. This module is enabled with the
 //! `"asynchronous"` feature.
+//!
+//! Delays between tries are awaited with an `AsyncSleeper`. The functions in this module use
+//! `DefaultSleeper`: a timer built on `futures-timer`, which works with any executor, or Tokio's
+//! timer with the `"tokio"` feature, which is cheaper and follows `tokio::time::pause` in tests.
+//! Use `retry_with_sleeper` to plug in a different runtime's timer.
 
-use crate::{Error, OperationResult};
-use std::{future::Future, time::Duration};
-use tokio::time;
+#[cfg(not(feature = "tokio"))]
+use crate::clock::SystemClock;
+#[cfg(feature = "tokio")]
+use crate::clock::TokioClock;
+use crate::{
+    classified::Outcome, context::AttemptContext, delay::FiniteDelay, policy::Waiting, Classified,
+    ConditionTimeout, CorrelationId, Error, IdempotencyKey, OperationResult, Policy,
+    RetryAfterHint,
+};
+use futures_util::stream::{self, Stream, StreamExt};
+use std::{
+    fmt::Debug,
+    future::Future,
+    pin::Pin,
+    sync::Mutex,
+    task::{Context, Poll},
+    time::Duration,
+};
+#[cfg(feature = "tokio")]
+use tokio::{runtime::Handle, time};
+
+/// A timer that asynchronous retries use to wait between tries.
+///
+/// The associated `Sleep` future lets runtime backends plug in their own timer without boxing a
+/// future for every delay. This is implemented for all closures of the form
+/// `Fn(Duration) -> impl Future<Output = ()>`.
+pub trait AsyncSleeper {
+    /// The future returned by `sleep`.
+    type Sleep: Future<Output = ()>;
+
+    /// Returns a future that completes after `duration` has elapsed.
+    fn sleep(&self, duration: Duration) -> Self::Sleep;
+}
+
+impl<F, S> AsyncSleeper for F
+where
+    F: Fn(Duration) -> S,
+    S: Future<Output = ()>,
+{
+    type Sleep = S;
+
+    fn sleep(&self, duration: Duration) -> S {
+        self(duration)
+    }
+}
+
+/// An `AsyncSleeper` backed by `futures_timer::Delay`, whose timer runs on a thread of its own, so
+/// that it works with any executor, or none.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FuturesTimerSleeper;
+
+impl AsyncSleeper for FuturesTimerSleeper {
+    type Sleep = futures_timer::Delay;
+
+    fn sleep(&self, duration: Duration) -> futures_timer::Delay {
+        futures_timer::Delay::new(duration)
+    }
+}
+
+/// An `AsyncSleeper` backed by `tokio::time::sleep`. This sleeper is enabled with the `"tokio"`
+/// feature.
+#[cfg(feature = "tokio")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TokioSleeper;
+
+#[cfg(feature = "tokio")]
+impl AsyncSleeper for TokioSleeper {
+    type Sleep = time::Sleep;
+
+    fn sleep(&self, duration: Duration) -> time::Sleep {
+        time::sleep(duration)
+    }
+}
+
+/// The sleeper that asynchronous retries wait with unless they are given another one:
+/// `TokioSleeper` with the `"tokio"` feature, and `FuturesTimerSleeper` otherwise.
+#[cfg(feature = "tokio")]
+pub type DefaultSleeper = TokioSleeper;
+
+/// The sleeper that asynchronous retries wait with unless they are given another one:
+/// `TokioSleeper` with the `"tokio"` feature, and `FuturesTimerSleeper` otherwise.
+#[cfg(not(feature = "tokio"))]
+pub type DefaultSleeper = FuturesTimerSleeper;
+
+/// The clock that asynchronous calls read unless the policy has another one, matching the
+/// default sleeper.
+#[cfg(feature = "tokio")]
+static DEFAULT_CLOCK: TokioClock = TokioClock;
+#[cfg(not(feature = "tokio"))]
+static DEFAULT_CLOCK: SystemClock = SystemClock;
+
+/// Wait `delay` with `sleeper`, in the intervals of the health check and suspend detection of
+/// `waiting`, and return how long was waited.
+async fn wait<S>(sleeper: &S, mut waiting: Waiting<'_>) -> Duration
+where
+    S: AsyncSleeper,
+{
+    while let Some(interval) = waiting.next_interval() {
+        sleeper.sleep(interval).await;
+        waiting.slept(interval);
+    }
+    waiting.waited()
+}
 
 /// Retry the given asynchronous operation until it succeeds, or until the given `Duration`
 /// iterator ends.
-pub async fn retry<I, O, R, E, OR, F>(iterable: I, mut operation: O) -> Result<R, Error<E>>
+pub async fn retry<I, O, R, E, OR, F>(iterable: I, operation: O) -> Result<R, Error<E>>
 where
     I: IntoIterator<Item = Duration>,
+    I::IntoIter: FiniteDelay,
     O: FnMut() -> F,
     OR: Into<OperationResult<R, E>>,
     F: Future<Output = OR>,
 {
-    retry_with_index(iterable, |_| operation()).await
+    retry_forever(iterable, operation).await
+}
+
+/// Retry the given asynchronous operation like `retry`, with an iterator that may never end, in
+/// which case the call only returns once the operation succeeds or returns
+/// `OperationResult::Err`.
+pub async fn retry_forever<I, O, R, E, OR, F>(iterable: I, mut operation: O) -> Result<R, Error<E>>
+where
+    I: IntoIterator<Item = Duration>,
+    O: FnMut() -> F,
+    OR: Into<OperationResult<R, E>>,
+    F: Future<Output = OR>,
+{
+    retry_notify_with_index(
+        DefaultSleeper::default(),
+        iterable,
+        |_| operation(),
+        |_, _| std::future::ready(()),
+    )
+    .await
+}
+
+/// Check the given asynchronous probe until it resolves to `true`, waiting between checks according
+/// to the given `Duration` iterator.
+pub async fn await_condition<I, P, F>(iterable: I, mut probe: P) -> Result<(), ConditionTimeout>
+where
+    I: IntoIterator<Item = Duration>,
+    I::IntoIter: FiniteDelay,
+    P: FnMut() -> F,
+    F: Future<Output = bool>,
+{
+    retry(iterable, || {
+        let check = probe();
+        async move {
+            if check.await {
+                Ok(())
+            } else {
+                Err(())
+            }
+        }
+    })
+    .await
+    .map_err(ConditionTimeout::from)
+}
+
+/// Retry the given asynchronous operation while it returns `None`, until it returns `Some` or
+/// until the given `Duration` iterator ends. The error of the operation is `()`.
+pub async fn retry_until_some<I, O, T, F>(iterable: I, mut operation: O) -> Result<T, Error<()>>
+where
+    I: IntoIterator<Item = Duration>,
+    I::IntoIter: FiniteDelay,
+    O: FnMut() -> F,
+    F: Future<Output = Option<T>>,
+{
+    retry(iterable, || {
+        let attempt = operation();
+        async move { attempt.await.ok_or(()) }
+    })
+    .await
 }
 
 /// Retry the given asynchronous operation until it succeeds, or until the given `Duration`
 /// iterator ends, with each iteration of the operation receiving the number of the attempt as an
 /// argument.
-pub async fn retry_with_index<I, O, R, E, OR, F>(
+pub async fn retry_with_index<I, O, R, E, OR, F>(iterable: I, operation: O) -> Result<R, Error<E>>
+where
+    I: IntoIterator<Item = Duration>,
+    I::IntoIter: FiniteDelay,
+    O: FnMut(u64) -> F,
+    OR: Into<OperationResult<R, E>>,
+    F: Future<Output = OR>,
+{
+    retry_notify_with_index(DefaultSleeper::default(), iterable, operation, |_, _| {
+        std::future::ready(())
+    })
+    .await
+}
+
+/// Retry the given asynchronous operation until it succeeds, or until the given `Duration`
+/// iterator ends, waiting between tries with the given `AsyncSleeper`.
+pub async fn retry_with_sleeper<S, I, O, R, E, OR, F>(
+    sleeper: S,
+    iterable: I,
+    mut operation: O,
+) -> Result<R, Error<E>>
+where
+    S: AsyncSleeper,
+    I: IntoIterator<Item = Duration>,
+    I::IntoIter: FiniteDelay,
+    O: FnMut() -> F,
+    OR: Into<OperationResult<R, E>>,
+    F: Future<Output = OR>,
+{
+    retry_notify_with_index(
+        sleeper,
+        iterable,
+        |_| operation(),
+        |_, _| std::future::ready(()),
+    )
+    .await
+}
+
+/// Retry the given asynchronous operation until it succeeds, or until the given `Duration`
+/// iterator ends, awaiting the asynchronous `notify` callback before each delay.
+///
+/// `notify` receives the error of the failed try and the delay before the next one. The future it
+/// returns is awaited before sleeping, so it can write to asynchronous sinks such as log shippers or
+/// metrics clients. The future cannot borrow the error; format or clone what it needs first.
+pub async fn retry_notify<I, O, R, E, OR, F, N, NF>(
+    iterable: I,
+    mut operation: O,
+    notify: N,
+) -> Result<R, Error<E>>
+where
+    I: IntoIterator<Item = Duration>,
+    I::IntoIter: FiniteDelay,
+    O: FnMut() -> F,
+    OR: Into<OperationResult<R, E>>,
+    F: Future<Output = OR>,
+    N: FnMut(&E, Duration) -> NF,
+    NF: Future<Output = ()>,
+{
+    retry_notify_with_index(DefaultSleeper::default(), iterable, |_| operation(), notify).await
+}
+
+async fn retry_notify_with_index<S, I, O, R, E, OR, F, N, NF>(
+    sleeper: S,
     iterable: I,
     mut operation: O,
+    mut notify: N,
 ) -> Result<R, Error<E>>
 where
     I: IntoIterator<Item = Duration>,
     O: FnMut(u64) -> F,
     OR: Into<OperationResult<R, E>>,
     F: Future<Output = OR>,
+    S: AsyncSleeper,
+    N: FnMut(&E, Duration) -> NF,
+    NF: Future<Output = ()>,
 {
     let mut iterator = iterable.into_iter();
     let mut current_try = 1;
@@ -39,7 +269,8 @@ where
             OperationResult::Ok(value) => return Ok(value),
             OperationResult::Retry(error) => {
                 if let Some(delay) = iterator.next() {
-                    time::delay_for(delay).await;
+                    notify(&error, delay).await;
+                    sleeper.sleep(delay).await;
                     current_try += 1;
                     total_delay += delay;
                 } else {
@@ -61,6 +292,620 @@ where
     }
 }
 
+impl<D> Policy<D>
+where
+    D: IntoIterator<Item = Duration> + Clone,
+{
+    /// Retry the given asynchronous operation according to this policy.
+    ///
+    /// If the policy has an attempt timeout, each try that does not finish in time is dropped and
+    /// counted as a retryable failure. If the last try times out, `Error::TimedOut` is returned.
+    pub async fn retry_async<O, R, E, OR, F>(&self, mut operation: O) -> Result<R, Error<E>>
+    where
+        O: FnMut() -> F,
+        OR: Into<OperationResult<R, E>>,
+        F: Future<Output = OR>,
+        E: Debug,
+    {
+        self.retry_async_with_index(|_| operation()).await
+    }
+
+    /// Retry the given asynchronous operation according to this policy, with each iteration of
+    /// the operation receiving the number of the attempt as an argument.
+    pub async fn retry_async_with_index<O, R, E, OR, F>(
+        &self,
+        mut operation: O,
+    ) -> Result<R, Error<E>>
+    where
+        O: FnMut(u64) -> F,
+        OR: Into<OperationResult<R, E>>,
+        F: Future<Output = OR>,
+        E: Debug,
+    {
+        self.retry_async_with_correlation_id(CorrelationId::generate(), |current_try, _| {
+            operation(current_try)
+        })
+        .await
+    }
+
+    /// Retry the given asynchronous operation according to this policy, identifying the call with
+    /// the given correlation ID instead of a generated one. Each iteration of the operation
+    /// receives the number of the attempt and a clone of the ID as arguments.
+    pub async fn retry_async_with_correlation_id<O, R, E, OR, F>(
+        &self,
+        id: CorrelationId,
+        mut operation: O,
+    ) -> Result<R, Error<E>>
+    where
+        O: FnMut(u64, CorrelationId) -> F,
+        OR: Into<OperationResult<R, E>>,
+        F: Future<Output = OR>,
+        E: Debug,
+    {
+        self.execute_async(id, None, |current_try, id| {
+            let attempt = operation(current_try, id);
+            async move {
+                let result: OperationResult<R, E> = attempt.await.into();
+                Outcome::from(result)
+            }
+        })
+        .await
+    }
+
+    /// Retry the given asynchronous operation according to this policy, passing every attempt a
+    /// clone of the same newly generated idempotency key.
+    pub async fn retry_async_idempotent<O, R, E, OR, F>(&self, operation: O) -> Result<R, Error<E>>
+    where
+        O: FnMut(IdempotencyKey) -> F,
+        OR: Into<OperationResult<R, E>>,
+        F: Future<Output = OR>,
+        E: Debug,
+    {
+        self.retry_async_with_idempotency_key(IdempotencyKey::generate(), operation)
+            .await
+    }
+
+    /// Retry the given asynchronous operation according to this policy, passing every attempt a
+    /// clone of the given idempotency key.
+    pub async fn retry_async_with_idempotency_key<O, R, E, OR, F>(
+        &self,
+        key: IdempotencyKey,
+        mut operation: O,
+    ) -> Result<R, Error<E>>
+    where
+        O: FnMut(IdempotencyKey) -> F,
+        OR: Into<OperationResult<R, E>>,
+        F: Future<Output = OR>,
+        E: Debug,
+    {
+        self.retry_async(|| operation(key.clone())).await
+    }
+
+    /// Retry the given asynchronous operation according to this policy, retrying the errors it
+    /// classifies as `Transient` and returning those it classifies as `Permanent` immediately.
+    pub async fn retry_async_classified<O, R, E, F>(&self, mut operation: O) -> Result<R, Error<E>>
+    where
+        O: FnMut() -> F,
+        F: Future<Output = Result<R, Classified<E>>>,
+        E: Debug,
+    {
+        self.execute_async(CorrelationId::generate(), None, |_, _| {
+            let attempt = operation();
+            async move { Outcome::from(attempt.await) }
+        })
+        .await
+    }
+
+    /// Retry the given asynchronous operation according to this policy, waiting the delay hinted
+    /// by each retried error, if any, instead of the strategy's next delay.
+    pub async fn retry_async_hinted<O, R, E, OR, F>(&self, mut operation: O) -> Result<R, Error<E>>
+    where
+        O: FnMut() -> F,
+        F: Future<Output = OR>,
+        OR: Into<OperationResult<R, E>>,
+        E: Debug + RetryAfterHint,
+    {
+        self.execute_async(CorrelationId::generate(), None, |_, _| {
+            let attempt = operation();
+            async move { Outcome::hinted(attempt.await.into()) }
+        })
+        .await
+    }
+
+    /// Retry the given blocking operation from asynchronous code according to this policy,
+    /// running each try on Tokio's blocking thread pool with `spawn_blocking` while the delays,
+    /// attempt timeout and listeners stay on the calling task.
+    ///
+    /// A try that times out is left running on its blocking thread, since blocking code cannot be
+    /// interrupted, and its result is ignored. A panic in a try is resumed on the calling task.
+    ///
+    /// # Panics
+    ///
+    /// Panics if not called from within a Tokio runtime, or if the runtime shuts down during a
+    /// try.
+    ///
+    /// This method is enabled with the `"tokio"` feature.
+    #[cfg(feature = "tokio")]
+    pub async fn retry_blocking<O, R, E, OR>(&self, operation: O) -> Result<R, Error<E>>
+    where
+        O: Fn() -> OR + Send + Sync + 'static,
+        OR: Into<OperationResult<R, E>> + Send + 'static,
+        E: Debug,
+    {
+        let operation = std::sync::Arc::new(operation);
+        self.retry_async(|| {
+            let operation = std::sync::Arc::clone(&operation);
+            async move {
+                match tokio::task::spawn_blocking(move || operation()).await {
+                    Ok(result) => result,
+                    Err(error) if error.is_panic() => std::panic::resume_unwind(error.into_panic()),
+                    Err(_) => panic!("the runtime shut down during a blocking try"),
+                }
+            }
+        })
+        .await
+    }
+
+    /// Retry the given asynchronous operation according to this policy while it returns `None`,
+    /// returning the value of the first `Some`. The error of the operation is `()`.
+    pub async fn retry_async_until_some<O, T, F>(&self, mut operation: O) -> Result<T, Error<()>>
+    where
+        O: FnMut() -> F,
+        F: Future<Output = Option<T>>,
+    {
+        self.retry_async(|| {
+            let attempt = operation();
+            async move { attempt.await.ok_or(()) }
+        })
+        .await
+    }
+
+    /// Retry the given asynchronous operation according to this policy, lending every attempt
+    /// mutable access to `state`.
+    ///
+    /// The future of an attempt can borrow the state, such as a connection or a cursor, for as long
+    /// as the attempt runs, so the operation does not need to clone it or share it behind a lock.
+    /// The operation is an async closure, or any closure that returns a future.
+    ///
+    /// ```rust
+    /// # use retry::delay::NoDelay;
+    /// use retry::Policy;
+    ///
+    /// struct Connection {
+    ///     sent: Vec<&'static str>,
+    ///     drops: u32,
+    /// }
+    ///
+    /// impl Connection {
+    ///     async fn send(&mut self, message: &'static str) -> Result<(), &'static str> {
+    ///         if self.drops > 0 {
+    ///             self.drops -= 1;
+    ///             return Err("connection dropped");
+    ///         }
+    ///         self.sent.push(message);
+    ///         Ok(())
+    ///     }
+    /// }
+    ///
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// let mut connection = Connection { sent: Vec::new(), drops: 2 };
+    /// let policy = Policy::new(NoDelay.take(2));
+    ///
+    /// let result = policy
+    ///     .retry_async_with_state(&mut connection, async |connection: &mut Connection| {
+    ///         connection.send("hello").await
+    ///     })
+    ///     .await;
+    ///
+    /// assert_eq!(result, Ok(()));
+    /// assert_eq!(connection.sent, ["hello"]);
+    /// # }
+    /// ```
+    pub async fn retry_async_with_state<S, O, R, E, OR>(
+        &self,
+        state: &mut S,
+        mut operation: O,
+    ) -> Result<R, Error<E>>
+    where
+        O: AsyncFnMut(&mut S) -> OR,
+        OR: Into<OperationResult<R, E>>,
+        E: Debug,
+    {
+        let slot = Mutex::new(Some((state, &mut operation)));
+        let slot = &slot;
+        self.execute_async(CorrelationId::generate(), None, |_, _| async move {
+            let mut lent = Lent::take(slot);
+            let (state, operation) = lent.get();
+            let result: OperationResult<R, E> = operation(state).await.into();
+            Outcome::from(result)
+        })
+        .await
+    }
+
+    /// Run the attempts of an asynchronous call. If `context` is given, it is set to the context
+    /// of each attempt before the attempt is made.
+    pub(crate) async fn execute_async<O, R, E, F>(
+        &self,
+        id: CorrelationId,
+        context: Option<&Mutex<AttemptContext>>,
+        mut operation: O,
+    ) -> Result<R, Error<E>>
+    where
+        O: FnMut(u64, CorrelationId) -> F,
+        F: Future<Output = Outcome<R, E>>,
+        E: Debug,
+    {
+        let sleeper = DefaultSleeper::default();
+        let mut session = self.session(id, &DEFAULT_CLOCK)?;
+        #[cfg(feature = "tracing")]
+        let span = session.span().clone();
+
+        let attempts = async move {
+            loop {
+                if self.cancelled() {
+                    return Err(session.cancel());
+                }
+                let current_try = session.start_attempt();
+                if let Some(context) = context {
+                    *context
+                        .lock()
+                        .unwrap_or_else(|poisoned| poisoned.into_inner()) = session.context();
+                }
+                let attempt = operation(current_try, session.correlation_id().clone());
+
+                let result = match self.attempt_timeout() {
+                    Some(timeout) => {
+                        let elapsed = std::pin::pin!(sleeper.sleep(timeout));
+                        race(attempt, elapsed).await.ok_or(timeout)
+                    }
+                    None => Ok(attempt.await),
+                };
+
+                let delay = match result {
+                    Ok(Outcome::Ok(value)) => {
+                        session.succeed();
+                        return Ok(value);
+                    }
+                    Ok(Outcome::Retry(error, retry_after)) => {
+                        match session.retry(&error, retry_after) {
+                            Some(delay) => delay,
+                            None => return Err(session.give_up(error)),
+                        }
+                    }
+                    Ok(Outcome::Err(error)) => return Err(session.give_up(error)),
+                    Err(timeout) => match session.retry_timed_out(timeout) {
+                        Some(delay) => delay,
+                        None => return Err(session.give_up_timed_out(timeout)),
+                    },
+                };
+
+                let waited = wait(&sleeper, self.waiting(delay)).await;
+                session.waited(waited);
+            }
+        };
+
+        #[cfg(feature = "tracing")]
+        let attempts = tracing::Instrument::instrument(attempts, span);
+
+        attempts.await
+    }
+}
+
+/// A value lent to one attempt at a time, such as the state and operation of
+/// `retry_async_with_state`.
+///
+/// The value is put back into the slot when the attempt finishes or is dropped, for example by
+/// the attempt timeout, so the next attempt can take it again.
+pub(crate) struct Lent<'a, T> {
+    slot: &'a Mutex<Option<T>>,
+    value: Option<T>,
+}
+
+impl<'a, T> Lent<'a, T> {
+    pub(crate) fn take(slot: &'a Mutex<Option<T>>) -> Self {
+        let value = slot
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .take();
+        Lent { slot, value }
+    }
+
+    pub(crate) fn get(&mut self) -> &mut T {
+        self.value
+            .as_mut()
+            .expect("attempts of a call are made one at a time")
+    }
+}
+
+impl<T> Drop for Lent<'_, T> {
+    fn drop(&mut self) {
+        *self
+            .slot
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = self.value.take();
+    }
+}
+
+/// Retry the given asynchronous operation until it succeeds, until the given `Duration` iterator
+/// ends, or until the `shutdown` future resolves.
+///
+/// The `shutdown` future is raced against both the operation and the delays between tries, so the
+/// loop ends with `Error::Cancelled` as soon as it resolves, even in the middle of a long delay.
+/// An attempt that is interrupted this way is dropped.
+pub async fn retry_until<I, O, R, E, OR, F, S>(
+    iterable: I,
+    mut operation: O,
+    shutdown: S,
+) -> Result<R, Error<E>>
+where
+    I: IntoIterator<Item = Duration>,
+    I::IntoIter: FiniteDelay,
+    O: FnMut() -> F,
+    OR: Into<OperationResult<R, E>>,
+    F: Future<Output = OR>,
+    S: Future,
+{
+    let mut iterator = iterable.into_iter();
+    let mut current_try = 1;
+    let mut total_delay = Duration::default();
+    let mut shutdown = std::pin::pin!(shutdown);
+
+    loop {
+        let cancelled = Error::Cancelled {
+            total_delay,
+            tries: current_try,
+        };
+
+        let result = match race(operation(), shutdown.as_mut()).await {
+            Some(result) => result,
+            None => return Err(cancelled),
+        };
+
+        match result.into() {
+            OperationResult::Ok(value) => return Ok(value),
+            OperationResult::Retry(error) => {
+                if let Some(delay) = iterator.next() {
+                    if race(DefaultSleeper::default().sleep(delay), shutdown.as_mut())
+                        .await
+                        .is_none()
+                    {
+                        return Err(cancelled);
+                    }
+                    current_try += 1;
+                    total_delay += delay;
+                } else {
+                    return Err(Error::Operation {
+                        error,
+                        total_delay,
+                        tries: current_try,
+                    });
+                }
+            }
+            OperationResult::Err(error) => {
+                return Err(Error::Operation {
+                    error,
+                    total_delay,
+                    tries: current_try,
+                });
+            }
+        }
+    }
+}
+
+/// Retry the given asynchronous operation until it succeeds, or until the given `Duration`
+/// iterator ends, running the `on_cancel` cleanup hook if the retry future is dropped first.
+///
+/// This is useful when the retry future may be abandoned by a caller, such as a timeout wrapper,
+/// while an attempt has half-finished work that has to be released. Since `Drop` cannot await, the
+/// future returned by `on_cancel` is spawned onto the current Tokio runtime; if the retry future is
+/// dropped outside of a runtime, the hook is not run. The hook is not run if the retry loop
+/// completes, whether it succeeds or fails.
+///
+/// This function is enabled with the `"tokio"` feature.
+#[cfg(feature = "tokio")]
+pub async fn retry_with_cleanup<I, O, R, E, OR, F, C, CF>(
+    iterable: I,
+    operation: O,
+    on_cancel: C,
+) -> Result<R, Error<E>>
+where
+    I: IntoIterator<Item = Duration>,
+    I::IntoIter: FiniteDelay,
+    O: FnMut() -> F,
+    OR: Into<OperationResult<R, E>>,
+    F: Future<Output = OR>,
+    C: FnOnce() -> CF,
+    CF: Future<Output = ()> + Send + 'static,
+{
+    let mut guard = CancelGuard {
+        on_cancel: Some(on_cancel),
+    };
+    let result = retry(iterable, operation).await;
+    guard.on_cancel = None;
+    result
+}
+
+/// Spawns the cleanup hook if it is dropped while still armed.
+#[cfg(feature = "tokio")]
+struct CancelGuard<C, CF>
+where
+    C: FnOnce() -> CF,
+    CF: Future<Output = ()> + Send + 'static,
+{
+    on_cancel: Option<C>,
+}
+
+#[cfg(feature = "tokio")]
+impl<C, CF> Drop for CancelGuard<C, CF>
+where
+    C: FnOnce() -> CF,
+    CF: Future<Output = ()> + Send + 'static,
+{
+    fn drop(&mut self) {
+        if let (Some(on_cancel), Ok(handle)) = (self.on_cancel.take(), Handle::try_current()) {
+            handle.spawn(on_cancel());
+        }
+    }
+}
+
+/// Retry each of the given asynchronous operations with its own copy of the delay strategy,
+/// running at most `concurrency` of them at a time.
+///
+/// The returned stream yields the index of each operation in `operations` along with its final
+/// result, in the order in which the operations finish.
+///
+/// # Panics
+///
+/// The returned stream panics if `concurrency` is zero.
+pub fn retry_all<P, T, O, R, E, OR, F>(
+    policy: P,
+    concurrency: usize,
+    operations: T,
+) -> impl Stream<Item = (usize, Result<R, Error<E>>)>
+where
+    P: IntoIterator<Item = Duration> + Clone,
+    P::IntoIter: FiniteDelay,
+    T: IntoIterator<Item = O>,
+    O: FnMut() -> F,
+    OR: Into<OperationResult<R, E>>,
+    F: Future<Output = OR>,
+{
+    assert!(concurrency > 0, "concurrency must be greater than zero");
+
+    stream::iter(operations.into_iter().enumerate())
+        .map(move |(index, operation)| {
+            let delays = policy.clone();
+            async move { (index, retry(delays, operation).await) }
+        })
+        .buffer_unordered(concurrency)
+}
+
+/// Turns a delay strategy into a stream that yields each delay once it has elapsed, for custom
+/// loops that wait for the next backoff tick alongside other events, such as in `select!`.
+///
+/// This is implemented for every `IntoIterator<Item = Duration>`.
+///
+/// ```rust
+/// # use std::time::Duration;
+/// use futures::StreamExt;
+/// use retry::{asynchronous::IntoStream, delay::Exponential};
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let mut ticks = Exponential::from_millis(2).take(3).into_stream();
+/// let mut waited = Vec::new();
+///
+/// while let Some(delay) = ticks.next().await {
+///     waited.push(delay);
+/// }
+///
+/// assert_eq!(waited, [2, 4, 8].map(Duration::from_millis));
+/// # }
+/// ```
+pub trait IntoStream: IntoIterator<Item = Duration> + Sized {
+    /// Returns a stream that waits each delay with the `DefaultSleeper`.
+    fn into_stream(self) -> DelayStream<Self::IntoIter, DefaultSleeper> {
+        self.into_stream_with_sleeper(DefaultSleeper::default())
+    }
+
+    /// Returns a stream that waits each delay with the given `AsyncSleeper`.
+    fn into_stream_with_sleeper<S>(self, sleeper: S) -> DelayStream<Self::IntoIter, S>
+    where
+        S: AsyncSleeper,
+    {
+        DelayStream {
+            delays: self.into_iter(),
+            sleeper,
+            sleep: None,
+        }
+    }
+}
+
+impl<T> IntoStream for T where T: IntoIterator<Item = Duration> {}
+
+/// A stream that yields each delay of a strategy once it has elapsed, created with
+/// `IntoStream::into_stream`.
+///
+/// The stream ends when the strategy does. Each delay starts when the stream is polled after the
+/// previous delay was yielded, so time spent handling a tick does not shorten the next one.
+pub struct DelayStream<I, S>
+where
+    S: AsyncSleeper,
+{
+    delays: I,
+    sleeper: S,
+    sleep: Option<(Duration, Pin<Box<S::Sleep>>)>,
+}
+
+impl<I, S> Stream for DelayStream<I, S>
+where
+    I: Iterator<Item = Duration> + Unpin,
+    S: AsyncSleeper + Unpin,
+{
+    type Item = Duration;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Duration>> {
+        let this = self.get_mut();
+        loop {
+            if let Some((delay, sleep)) = &mut this.sleep {
+                if sleep.as_mut().poll(cx).is_pending() {
+                    return Poll::Pending;
+                }
+                let delay = *delay;
+                this.sleep = None;
+                return Poll::Ready(Some(delay));
+            }
+            match this.delays.next() {
+                Some(delay) => this.sleep = Some((delay, Box::pin(this.sleeper.sleep(delay)))),
+                None => return Poll::Ready(None),
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let (lower, upper) = self.delays.size_hint();
+        let waiting = usize::from(self.sleep.is_some());
+        (
+            lower.saturating_add(waiting),
+            upper.and_then(|upper| upper.checked_add(waiting)),
+        )
+    }
+}
+
+impl<I, S> Debug for DelayStream<I, S>
+where
+    I: Debug,
+    S: AsyncSleeper + Debug,
+{
+    fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        formatter
+            .debug_struct("DelayStream")
+            .field("delays", &self.delays)
+            .field("sleeper", &self.sleeper)
+            .field("waiting", &self.sleep.as_ref().map(|(delay, _)| delay))
+            .finish()
+    }
+}
+
+/// Drive `future` to completion, unless `shutdown` resolves first.
+async fn race<F, S>(future: F, mut shutdown: Pin<&mut S>) -> Option<F::Output>
+where
+    F: Future,
+    S: Future,
+{
+    let mut future = std::pin::pin!(future);
+
+    std::future::poll_fn(|cx: &mut Context<'_>| {
+        if shutdown.as_mut().poll(cx).is_ready() {
+            return Poll::Ready(None);
+        }
+
+        future.as_mut().poll(cx).map(Some)
+    })
+    .await
+}
+
 /// Retry a future with the syntax
 /// `retry_future!(IntoIterator<Item = Duration>, Future<Output = Into<OperationResult<R, E>>>)`
 ///
@@ -81,7 +926,11 @@ macro_rules! retry_future {
                     $crate::OperationResult::Ok(value) => return Ok(value),
                     $crate::OperationResult::Retry(error) => {
                         if let Some(delay) = iterator.next() {
-                            ::tokio::time::delay_for(delay).await;
+                            $crate::asynchronous::AsyncSleeper::sleep(
+                                &<$crate::asynchronous::DefaultSleeper>::default(),
+                                delay,
+                            )
+                            .await;
                             current_try += 1;
                             total_delay += delay;
                         } else {
@@ -107,23 +956,141 @@ macro_rules! retry_future {
 
 #[cfg(test)]
 mod tests {
-    use futures::future;
+    use futures::{future, StreamExt};
     use rand::Rng;
     use std::{sync::Arc, time::Duration};
     use tokio;
+    use tokio::{sync::oneshot, time};
 
-    use super::{retry, retry_with_index};
+    use super::{
+        await_condition, retry, retry_all, retry_forever, retry_notify, retry_until,
+        retry_until_some, retry_with_index, retry_with_sleeper, AsyncSleeper, FuturesTimerSleeper,
+        IntoStream,
+    };
     use crate::{
-        delay::{Exponential, Fixed, NoDelay, Range},
+        delay::{Exponential, Fixed, NoDelay},
         opresult::OperationResult,
-        retry_future, Error,
+        Classified, CorrelationId, Error, Permanent, Policy, Transient,
     };
 
+    #[cfg(feature = "tokio")]
+    #[tokio::test(start_paused = true)]
+    async fn health_check_ends_delays_early() {
+        let healthy = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let probe = Arc::clone(&healthy);
+        let policy = Policy::new(Fixed::from_millis(60_000).take(1))
+            .with_health_check(Duration::from_secs(1), move || {
+                probe.load(std::sync::atomic::Ordering::Relaxed)
+            });
+        tokio::spawn(async move {
+            time::sleep(Duration::from_millis(2500)).await;
+            healthy.store(true, std::sync::atomic::Ordering::Relaxed);
+        });
+
+        let started = time::Instant::now();
+        let mut attempts = 0;
+        let result = policy
+            .retry_async(|| {
+                attempts += 1;
+                future::ready(if attempts == 1 {
+                    Err("unavailable")
+                } else {
+                    Ok(())
+                })
+            })
+            .await;
+
+        assert_eq!(result, Ok(()));
+        assert_eq!(started.elapsed(), Duration::from_secs(3));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn awaits_conditions() {
+        let (sender, mut receiver) = oneshot::channel();
+        tokio::spawn(async move {
+            time::sleep(Duration::from_millis(25)).await;
+            sender.send(()).unwrap();
+        });
+
+        let result = await_condition(Fixed::from_millis(10).take(5), || {
+            future::ready(receiver.try_recv().is_ok())
+        })
+        .await;
+        assert_eq!(result, Ok(()));
+
+        let timeout = await_condition(Fixed::from_millis(10).take(2), || future::ready(false))
+            .await
+            .unwrap_err();
+        assert_eq!(timeout.checks(), 3);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test(flavor = "current_thread")]
+    async fn retries_blocking_operations_on_the_blocking_pool() {
+        let caller = std::thread::current().id();
+        let attempts = Arc::new(std::sync::atomic::AtomicU64::new(0));
+        let counter = Arc::clone(&attempts);
+        let policy = Policy::new(NoDelay).with_max_attempts(3);
+
+        let result = policy
+            .retry_blocking(move || {
+                assert_ne!(std::thread::current().id(), caller);
+                std::thread::sleep(Duration::from_millis(1));
+                match counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst) {
+                    0 | 1 => Err("busy"),
+                    attempt => Ok(attempt),
+                }
+            })
+            .await;
+
+        assert_eq!(result, Ok(2));
+        assert_eq!(attempts.load(std::sync::atomic::Ordering::SeqCst), 3);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    #[should_panic(expected = "driver crashed")]
+    async fn resumes_panics_of_blocking_operations() {
+        let _ = Policy::new(NoDelay)
+            .retry_blocking(|| -> Result<(), &str> { panic!("driver crashed") })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn passes_the_same_idempotency_key_to_every_attempt() {
+        let mut keys = Vec::new();
+        let policy = Policy::new(NoDelay.take(2));
+
+        let _ = policy
+            .retry_async_idempotent(|key| {
+                keys.push(key);
+                future::ready(Err::<(), _>("down"))
+            })
+            .await;
+
+        assert_eq!(keys.len(), 3);
+        assert!(keys.iter().all(|key| *key == keys[0]));
+    }
+
+    #[tokio::test]
+    async fn retries_until_some() {
+        let mut polls = vec![None, None, Some(3)].into_iter();
+        let value =
+            retry_until_some(NoDelay.take(5), || future::ready(polls.next().flatten())).await;
+        assert_eq!(value, Ok(3));
+
+        let policy = Policy::new(NoDelay).with_max_attempts(3);
+        let result = policy
+            .retry_async_until_some(|| future::ready(None::<()>))
+            .await;
+        assert_eq!(result.unwrap_err().tries(), 3);
+    }
+
     #[tokio::test]
     async fn succeeds_with_infinite_retries() {
         let mut collection = vec![1, 2, 3, 4, 5].into_iter();
 
-        let value = retry(NoDelay, || match collection.next() {
+        let value = retry_forever(NoDelay, || match collection.next() {
             Some(n) if n == 5 => future::ready(Ok(n)),
             Some(_) => future::ready(Err("not 5")),
             None => future::ready(Err("not 5")),
@@ -195,7 +1162,7 @@ mod tests {
     async fn succeeds_with_fixed_delay() {
         let mut collection = vec![1, 2].into_iter();
 
-        let value = retry(Fixed::from_millis(1), || match collection.next() {
+        let value = retry(Fixed::from_millis(1).take(5), || match collection.next() {
             Some(n) if n == 2 => future::ready(Ok(n)),
             Some(_) => future::ready(Err("not 2")),
             None => future::ready(Err("not 2")),
@@ -210,10 +1177,12 @@ mod tests {
     async fn succeeds_with_exponential_delay() {
         let mut collection = vec![1, 2].into_iter();
 
-        let value = retry(Exponential::from_millis(1), || match collection.next() {
-            Some(n) if n == 2 => future::ready(Ok(n)),
-            Some(_) => future::ready(Err("not 2")),
-            None => future::ready(Err("not 2")),
+        let value = retry(Exponential::from_millis(1).take(5), || {
+            match collection.next() {
+                Some(n) if n == 2 => future::ready(Ok(n)),
+                Some(_) => future::ready(Err("not 2")),
+                None => future::ready(Err("not 2")),
+            }
         })
         .await
         .unwrap();
@@ -221,17 +1190,21 @@ mod tests {
         assert_eq!(value, 2);
     }
 
+    #[cfg(feature = "random")]
     #[tokio::test]
     async fn succeeds_with_ranged_delay() {
+        use crate::delay::Range;
+
         let mut collection = vec![1, 2].into_iter();
 
-        let value = retry(Range::from_millis_exclusive(1, 10), || {
-            match collection.next() {
+        let value = retry(
+            Range::from_millis_exclusive(1, 10).take(5),
+            || match collection.next() {
                 Some(n) if n == 2 => future::ready(Ok(n)),
                 Some(_) => future::ready(Err("not 2")),
                 None => future::ready(Err("not 2")),
-            }
-        })
+            },
+        )
         .await
         .unwrap();
 
@@ -242,7 +1215,7 @@ mod tests {
     async fn succeeds_with_index() {
         let mut collection = vec![1, 2, 3].into_iter();
 
-        let value = retry_with_index(NoDelay, |current_try| match collection.next() {
+        let value = retry_with_index(NoDelay.take(5), |current_try| match collection.next() {
             Some(n) if n == current_try => future::ready(Ok(n)),
             Some(_) => future::ready(Err("not current_try")),
             None => future::ready(Err("not current_try")),
@@ -257,7 +1230,7 @@ mod tests {
     async fn succeeds_with_index_async_closure() {
         let collection = Arc::new(vec![0, 2, 3]);
         let mut i = 0;
-        let value = retry_with_index(NoDelay, |current_try| {
+        let value = retry_with_index(NoDelay.take(5), |current_try| {
             let collection_copy = Arc::clone(&collection);
             let f = async move {
                 match collection_copy.get(i).copied() {
@@ -284,7 +1257,7 @@ mod tests {
                 Err("not 2")
             }
         }
-        let value = retry_with_index(NoDelay, op).await;
+        let value = retry_with_index(NoDelay.take(5), op).await;
 
         assert!(value.is_ok());
     }
@@ -304,4 +1277,333 @@ mod tests {
 
         assert!(value < 100);
     }
+
+    #[tokio::test]
+    async fn retry_until_cancels_during_delay() {
+        let res = retry_until(
+            Fixed::from_millis(60_000).take(5),
+            || future::ready(Err::<(), _>("not yet")),
+            time::sleep(Duration::from_millis(10)),
+        )
+        .await;
+
+        assert_eq!(
+            res,
+            Err(Error::Cancelled {
+                tries: 1,
+                total_delay: Duration::from_millis(0)
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn retry_until_cancels_during_attempt() {
+        let res = retry_until(
+            NoDelay.take(5),
+            future::pending::<Result<(), &str>>,
+            future::ready(()),
+        )
+        .await;
+
+        assert_eq!(
+            res,
+            Err(Error::Cancelled {
+                tries: 1,
+                total_delay: Duration::from_millis(0)
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn retry_until_succeeds_before_shutdown() {
+        let mut collection = vec![1, 2].into_iter();
+
+        let value = retry_until(
+            NoDelay.take(5),
+            || match collection.next() {
+                Some(n) if n == 2 => future::ready(Ok(n)),
+                Some(_) => future::ready(Err("not 2")),
+                None => future::ready(Err("not 2")),
+            },
+            future::pending::<()>(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(value, 2);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn retry_with_cleanup_runs_hook_when_dropped() {
+        let (sender, receiver) = oneshot::channel();
+
+        let res = time::timeout(
+            Duration::from_millis(10),
+            super::retry_with_cleanup(
+                Fixed::from_millis(60_000).take(5),
+                || future::ready(Err::<(), _>("not yet")),
+                || async move {
+                    sender.send(()).unwrap();
+                },
+            ),
+        )
+        .await;
+
+        assert!(res.is_err());
+        assert_eq!(receiver.await, Ok(()));
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn retry_with_cleanup_skips_hook_on_completion() {
+        let (sender, receiver) = oneshot::channel();
+
+        let res = super::retry_with_cleanup(
+            NoDelay.take(1),
+            || future::ready(Err::<(), _>("not 2")),
+            || async move {
+                sender.send(()).unwrap();
+            },
+        )
+        .await;
+
+        assert!(res.is_err());
+        assert!(receiver.await.is_err());
+    }
+
+    #[tokio::test]
+    async fn retry_notify_awaits_notification_before_delay() {
+        let notifications = Arc::new(tokio::sync::Mutex::new(Vec::new()));
+        let mut collection = vec![1, 2, 3].into_iter();
+
+        let value = retry_notify(
+            Fixed::from_millis(1).take(5),
+            || match collection.next() {
+                Some(n) if n == 3 => future::ready(Ok(n)),
+                Some(_) => future::ready(Err("not 3")),
+                None => future::ready(Err("not 3")),
+            },
+            |error: &&str, delay| {
+                let notifications = Arc::clone(&notifications);
+                let message = format!("{} (retrying in {:?})", error, delay);
+                async move {
+                    time::sleep(Duration::from_millis(1)).await;
+                    notifications.lock().await.push(message);
+                }
+            },
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(value, 3);
+        assert_eq!(
+            *notifications.lock().await,
+            vec!["not 3 (retrying in 1ms)", "not 3 (retrying in 1ms)"]
+        );
+    }
+
+    #[tokio::test]
+    async fn retry_with_custom_sleeper() {
+        let slept = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let recorder = Arc::clone(&slept);
+        let mut collection = vec![1, 2, 3].into_iter();
+
+        let value = retry_with_sleeper(
+            move |delay| {
+                recorder.lock().unwrap().push(delay);
+                future::ready(())
+            },
+            Exponential::from_millis(10).take(5),
+            || match collection.next() {
+                Some(n) if n == 3 => future::ready(Ok(n)),
+                Some(_) => future::ready(Err("not 3")),
+                None => future::ready(Err("not 3")),
+            },
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(value, 3);
+        assert_eq!(
+            *slept.lock().unwrap(),
+            vec![Duration::from_millis(10), Duration::from_millis(100)]
+        );
+    }
+
+    #[test]
+    fn futures_timer_sleeper_needs_no_runtime() {
+        let started = std::time::Instant::now();
+        let mut tries = 0;
+
+        let result = futures::executor::block_on(retry_with_sleeper(
+            FuturesTimerSleeper,
+            Fixed::from_millis(5).take(1),
+            || {
+                tries += 1;
+                future::ready(if tries < 2 { Err("busy") } else { Ok(tries) })
+            },
+        ));
+
+        assert_eq!(result, Ok(2));
+        assert!(started.elapsed() >= Duration::from_millis(5));
+        futures::executor::block_on(FuturesTimerSleeper.sleep(Duration::from_millis(1)));
+    }
+
+    #[test]
+    fn streams_each_delay_once_it_elapses() {
+        let slept = std::sync::Mutex::new(Vec::new());
+        let delays = [Duration::from_millis(10), Duration::from_millis(100)];
+        let mut ticks = delays.into_stream_with_sleeper(|delay| {
+            slept.lock().unwrap().push(delay);
+            future::ready(())
+        });
+        assert_eq!(futures::Stream::size_hint(&ticks), (2, Some(2)));
+
+        let first = futures::executor::block_on(ticks.next());
+        assert_eq!(first, Some(Duration::from_millis(10)));
+        assert_eq!(*slept.lock().unwrap(), [Duration::from_millis(10)]);
+
+        let rest: Vec<_> = futures::executor::block_on(ticks.collect());
+        assert_eq!(rest, [Duration::from_millis(100)]);
+    }
+
+    #[tokio::test]
+    async fn retry_all_limits_concurrency() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let running = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+
+        let operations = (0..10).map(|n| {
+            let running = Arc::clone(&running);
+            let peak = Arc::clone(&peak);
+            let mut tries = 0;
+            move || {
+                tries += 1;
+                let running = Arc::clone(&running);
+                let peak = Arc::clone(&peak);
+                async move {
+                    let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                    peak.fetch_max(now, Ordering::SeqCst);
+                    time::sleep(Duration::from_millis(1)).await;
+                    running.fetch_sub(1, Ordering::SeqCst);
+                    if tries < 2 {
+                        Err("first try")
+                    } else {
+                        Ok(n * 2)
+                    }
+                }
+            }
+        });
+
+        let mut results: Vec<_> = retry_all(NoDelay.take(1), 3, operations).collect().await;
+        results.sort_by_key(|(index, _)| *index);
+
+        assert_eq!(peak.load(Ordering::SeqCst), 3);
+        assert_eq!(
+            results,
+            (0..10).map(|n| (n, Ok(n as u64 * 2))).collect::<Vec<_>>()
+        );
+    }
+
+    #[tokio::test]
+    async fn policy_retries_timed_out_attempts() {
+        let mut collection = vec![1, 2].into_iter();
+
+        let value = Policy::new(NoDelay.take(1))
+            .with_attempt_timeout(Duration::from_millis(10))
+            .retry_async(|| {
+                let n = collection.next();
+                async move {
+                    if n == Some(1) {
+                        future::pending::<()>().await;
+                    }
+                    Ok::<_, &str>(n)
+                }
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(value, Some(2));
+    }
+
+    #[tokio::test]
+    async fn policy_lends_the_state_to_every_attempt() {
+        let mut attempts = Vec::new();
+
+        let value = Policy::new(NoDelay.take(2))
+            .with_attempt_timeout(Duration::from_millis(10))
+            .retry_async_with_state(&mut attempts, async |attempts: &mut Vec<u64>| {
+                attempts.push(attempts.len() as u64 + 1);
+                if attempts.len() == 1 {
+                    future::pending::<()>().await;
+                }
+                tokio::task::yield_now().await;
+                if attempts.len() < 3 {
+                    Err("busy")
+                } else {
+                    Ok(attempts.len())
+                }
+            })
+            .await;
+
+        assert_eq!(value, Ok(3));
+        assert_eq!(attempts, [1, 2, 3]);
+    }
+
+    #[tokio::test]
+    async fn policy_times_out_last_attempt() {
+        let res = Policy::new(NoDelay.take(1))
+            .with_attempt_timeout(Duration::from_millis(10))
+            .retry_async(future::pending::<Result<(), &str>>)
+            .await;
+
+        assert_eq!(
+            res,
+            Err(Error::TimedOut {
+                timeout: Duration::from_millis(10),
+                tries: 2,
+                total_delay: Duration::from_millis(0)
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn policy_passes_correlation_id_to_attempts() {
+        let mut seen = Vec::new();
+
+        let _ = Policy::new(NoDelay.take(1))
+            .retry_async_with_correlation_id("req-1".into(), |_, id| {
+                seen.push(id);
+                future::ready(Err::<(), _>("down"))
+            })
+            .await;
+
+        assert_eq!(seen, vec![CorrelationId::from("req-1"); 2]);
+    }
+
+    #[tokio::test]
+    async fn policy_retries_transient_errors() {
+        let mut attempts = 0;
+
+        let result = Policy::new(NoDelay.take(5))
+            .retry_async_classified(|| {
+                attempts += 1;
+                future::ready(match attempts {
+                    1 => Err(Transient::new("unavailable").into()),
+                    _ => Err::<(), Classified<_>>(Permanent("gone").into()),
+                })
+            })
+            .await;
+
+        assert_eq!(
+            result,
+            Err(Error::Operation {
+                error: "gone",
+                total_delay: Duration::default(),
+                tries: 2,
+            })
+        );
+    }
 }
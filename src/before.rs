@@ -0,0 +1,199 @@
+use std::{fmt, time::Duration};
+
+use crate::{Error, OperationResult, Policy};
+
+/// A policy that runs a hook before every retry, created with `Policy::before_attempt`, to repair
+/// what the previous attempt may have broken, such as reconnecting a dropped connection, before the
+/// operation is tried again.
+///
+/// The hook is given the number of the attempt about to be made. If it fails, that attempt is not
+/// made and the hook's error counts as a retryable failure of the attempt, so the hook runs again
+/// before the next one. Unlike listeners, which are only notified, the hook decides whether an
+/// attempt is made.
+///
+/// ```rust
+/// # use std::cell::RefCell;
+/// # use retry::delay::NoDelay;
+/// use retry::Policy;
+///
+/// let connection = RefCell::new(None);
+/// let policy = Policy::new(NoDelay.take(2)).before_attempt(|_| {
+///     *connection.borrow_mut() = Some("reconnected");
+///     Ok(())
+/// });
+///
+/// let result = policy.retry(|| match connection.borrow_mut().take() {
+///     Some(connection) => Ok(connection),
+///     None => Err("connection lost"),
+/// });
+///
+/// assert_eq!(result, Ok("reconnected"));
+/// ```
+#[derive(Clone)]
+pub struct BeforeAttempt<D, H> {
+    policy: Policy<D>,
+    hook: H,
+}
+
+impl<D> Policy<D> {
+    /// Run `hook` before every attempt but the first, treating its failure as a retryable failure
+    /// of the attempt.
+    pub fn before_attempt<H>(self, hook: H) -> BeforeAttempt<D, H> {
+        BeforeAttempt { policy: self, hook }
+    }
+}
+
+impl<D, H> BeforeAttempt<D, H> {
+    /// The policy the attempts are retried with.
+    pub fn policy(&self) -> &Policy<D> {
+        &self.policy
+    }
+}
+
+impl<D, H> BeforeAttempt<D, H>
+where
+    D: IntoIterator<Item = Duration> + Clone,
+{
+    /// Retry the given operation synchronously according to the policy, running the hook before
+    /// every retry.
+    pub fn retry<O, R, E, OR>(&self, mut operation: O) -> Result<R, Error<E>>
+    where
+        O: FnMut() -> OR,
+        OR: Into<OperationResult<R, E>>,
+        H: Fn(u64) -> Result<(), E>,
+        E: fmt::Debug,
+    {
+        self.policy.retry_with_index(|current_try| {
+            if current_try > 1 {
+                if let Err(error) = (self.hook)(current_try) {
+                    return OperationResult::Retry(error);
+                }
+            }
+            operation().into()
+        })
+    }
+
+    /// Retry the given asynchronous operation according to the policy, running the asynchronous
+    /// hook before every retry. An attempt timeout bounds the hook and the operation together.
+    ///
+    /// This method is enabled with the `"asynchronous"` feature.
+    #[cfg(feature = "asynchronous")]
+    pub async fn retry_async<O, R, E, OR, F, HF>(&self, operation: O) -> Result<R, Error<E>>
+    where
+        O: FnMut() -> F,
+        OR: Into<OperationResult<R, E>>,
+        F: std::future::Future<Output = OR>,
+        H: Fn(u64) -> HF,
+        HF: std::future::Future<Output = Result<(), E>>,
+        E: fmt::Debug,
+    {
+        // The operation is only called once the hook is done, so it sees what the hook repaired.
+        let operation = std::cell::RefCell::new(operation);
+        let operation = &operation;
+        self.policy
+            .retry_async_with_index(|current_try| {
+                let repair = (current_try > 1).then(|| (self.hook)(current_try));
+                async move {
+                    if let Some(repair) = repair {
+                        if let Err(error) = repair.await {
+                            return OperationResult::Retry(error);
+                        }
+                    }
+                    let attempt = (operation.borrow_mut())();
+                    attempt.await.into()
+                }
+            })
+            .await
+    }
+}
+
+impl<D, H> fmt::Debug for BeforeAttempt<D, H>
+where
+    D: fmt::Debug,
+{
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter
+            .debug_struct("BeforeAttempt")
+            .field("policy", &self.policy)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::{Cell, RefCell};
+
+    use crate::{delay::NoDelay, Error, Policy};
+
+    #[test]
+    fn repairs_before_every_retry() {
+        let repairs = RefCell::new(Vec::new());
+        let policy = Policy::new(NoDelay)
+            .with_max_attempts(4)
+            .before_attempt(|current_try| {
+                repairs.borrow_mut().push(current_try);
+                if current_try == 2 {
+                    Err("reconnect failed")
+                } else {
+                    Ok(())
+                }
+            });
+        let attempts = Cell::new(0);
+
+        let result = policy.retry(|| {
+            attempts.set(attempts.get() + 1);
+            if attempts.get() < 2 {
+                Err("connection lost")
+            } else {
+                Ok(attempts.get())
+            }
+        });
+
+        assert_eq!(result, Ok(2));
+        assert_eq!(*repairs.borrow(), [2, 3]);
+    }
+
+    #[test]
+    fn gives_up_with_the_error_of_the_hook() {
+        let policy = Policy::new(NoDelay)
+            .with_max_attempts(2)
+            .before_attempt(|_| Err("reconnect failed"));
+
+        let result = policy.retry(|| Err::<(), _>("connection lost"));
+
+        assert!(matches!(
+            result,
+            Err(Error::MaxAttempts {
+                error: "reconnect failed",
+                tries: 2,
+                ..
+            })
+        ));
+    }
+
+    #[cfg(feature = "asynchronous")]
+    #[tokio::test]
+    async fn repairs_asynchronously() {
+        let connected = &Cell::new(false);
+        let policy = Policy::new(NoDelay.take(1)).before_attempt(|_| async move {
+            tokio::task::yield_now().await;
+            connected.set(true);
+            Ok::<_, &str>(())
+        });
+
+        let result = policy
+            .retry_async(|| {
+                let connected = connected.get();
+                async move {
+                    if connected {
+                        Ok(())
+                    } else {
+                        Err("connection lost")
+                    }
+                }
+            })
+            .await;
+
+        assert_eq!(result, Ok(()));
+    }
+}
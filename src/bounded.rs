@@ -0,0 +1,218 @@
+use core::{fmt, time::Duration};
+
+use crate::{Error, OperationResult};
+
+/// The outcome of a call made with `retry_n`, along with the error of every failed attempt, kept
+/// in an array on the stack rather than in a heap-allocated history.
+pub struct AttemptReport<R, E, const N: usize> {
+    value: Option<R>,
+    errors: [Option<E>; N],
+    tries: usize,
+    total_delay: Duration,
+    max_attempts_reached: bool,
+}
+
+impl<R, E, const N: usize> AttemptReport<R, E, N> {
+    /// Whether the last attempt succeeded.
+    pub fn succeeded(&self) -> bool {
+        self.value.is_some()
+    }
+
+    /// The value of the last attempt, if it succeeded.
+    pub fn value(&self) -> Option<&R> {
+        self.value.as_ref()
+    }
+
+    /// The errors of the failed attempts, in the order they were made.
+    pub fn errors(&self) -> impl Iterator<Item = &E> {
+        self.errors.iter().filter_map(Option::as_ref)
+    }
+
+    /// The number of attempts made, at most `N`.
+    pub fn tries(&self) -> usize {
+        self.tries
+    }
+
+    /// The total time waited between attempts.
+    pub fn total_delay(&self) -> Duration {
+        self.total_delay
+    }
+
+    /// The value of the last attempt, or its error as `Error::MaxAttempts` if all `N` attempts
+    /// failed while the strategy still had delays left, and as `Error::Operation` otherwise.
+    pub fn into_result(mut self) -> Result<R, Error<E>> {
+        if let Some(value) = self.value {
+            return Ok(value);
+        }
+        let error = self.errors[self.tries - 1]
+            .take()
+            .expect("a failed call keeps the error of its last attempt");
+        let (total_delay, tries) = (self.total_delay, self.tries as u64);
+        Err(if self.max_attempts_reached {
+            Error::MaxAttempts {
+                error,
+                total_delay,
+                tries,
+            }
+        } else {
+            Error::Operation {
+                error,
+                total_delay,
+                tries,
+            }
+        })
+    }
+}
+
+impl<R, E, const N: usize> fmt::Debug for AttemptReport<R, E, N>
+where
+    R: fmt::Debug,
+    E: fmt::Debug,
+{
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter
+            .debug_struct("AttemptReport")
+            .field("value", &self.value)
+            .field("errors", &self.errors().collect::<alloc::vec::Vec<_>>())
+            .field("tries", &self.tries)
+            .field("total_delay", &self.total_delay)
+            .finish()
+    }
+}
+
+/// Retry the given operation synchronously, making at most `N` attempts, fixed at compile time,
+/// until it succeeds or the given `Duration` iterator ends.
+///
+/// Nothing about the call is configured at runtime, and the errors of the attempts are kept in the
+/// returned report without allocating, which suits embedded and latency-critical code. `N` is
+/// usually inferred from the type of the report, and a zero `N` fails to compile.
+///
+/// ```rust
+/// # use retry::delay::Fixed;
+/// use retry::{retry_n, AttemptReport};
+///
+/// let mut readings = vec![Err("checksum"), Err("timeout"), Ok(21.5)].into_iter();
+/// let report: AttemptReport<_, _, 5> =
+///     retry_n(Fixed::from_millis(1), || readings.next().unwrap());
+///
+/// assert_eq!(report.value(), Some(&21.5));
+/// assert_eq!(report.errors().collect::<Vec<_>>(), [&"checksum", &"timeout"]);
+/// ```
+#[cfg(feature = "std")]
+pub fn retry_n<const N: usize, I, O, R, E, OR>(iterable: I, operation: O) -> AttemptReport<R, E, N>
+where
+    I: IntoIterator<Item = Duration>,
+    O: FnMut() -> OR,
+    OR: Into<OperationResult<R, E>>,
+{
+    retry_n_with_sleep(iterable, std::thread::sleep, operation)
+}
+
+/// Retry the given operation like `retry_n`, waiting each delay with `sleep`, such as the delay of
+/// a hardware timer, so that it can be used without the standard library.
+pub fn retry_n_with_sleep<const N: usize, I, S, O, R, E, OR>(
+    iterable: I,
+    mut sleep: S,
+    mut operation: O,
+) -> AttemptReport<R, E, N>
+where
+    I: IntoIterator<Item = Duration>,
+    S: FnMut(Duration),
+    O: FnMut() -> OR,
+    OR: Into<OperationResult<R, E>>,
+{
+    const { assert!(N > 0, "retry_n needs to make at least one attempt") };
+
+    let mut report = AttemptReport {
+        value: None,
+        errors: core::array::from_fn(|_| None),
+        tries: 0,
+        total_delay: Duration::default(),
+        max_attempts_reached: false,
+    };
+    let mut delays = iterable.into_iter();
+    loop {
+        report.tries += 1;
+        match operation().into() {
+            OperationResult::Ok(value) => {
+                report.value = Some(value);
+                return report;
+            }
+            OperationResult::Retry(error) => report.errors[report.tries - 1] = Some(error),
+            OperationResult::Err(error) => {
+                report.errors[report.tries - 1] = Some(error);
+                return report;
+            }
+        }
+        match delays.next() {
+            Some(_) if report.tries == N => {
+                report.max_attempts_reached = true;
+                return report;
+            }
+            Some(delay) => {
+                sleep(delay);
+                report.total_delay += delay;
+            }
+            None => return report,
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use std::time::Duration;
+
+    use super::{retry_n, retry_n_with_sleep, AttemptReport};
+    use crate::{delay::Fixed, Error, OperationResult};
+
+    #[test]
+    fn keeps_the_errors_of_at_most_n_attempts() {
+        let mut attempts = 0;
+        let report = retry_n::<3, _, _, (), _, _>(Fixed::from_millis(1), || {
+            attempts += 1;
+            Err(attempts)
+        });
+
+        assert!(!report.succeeded());
+        assert_eq!(report.errors().copied().collect::<Vec<_>>(), [1, 2, 3]);
+        assert_eq!(report.total_delay(), Duration::from_millis(2));
+        assert!(matches!(
+            report.into_result(),
+            Err(Error::MaxAttempts {
+                error: 3,
+                tries: 3,
+                ..
+            })
+        ));
+
+        let report = retry_n::<3, _, _, (), _, _>(Fixed::from_millis(1).take(1), || Err("down"));
+        assert_eq!(report.tries(), 2);
+        assert!(matches!(
+            report.into_result(),
+            Err(Error::Operation { tries: 2, .. })
+        ));
+    }
+
+    #[test]
+    fn waits_with_the_given_sleep() {
+        let mut slept = Vec::new();
+        let mut attempts = 0;
+        let report: AttemptReport<_, _, 4> = retry_n_with_sleep(
+            Fixed::from_millis(5),
+            |delay| slept.push(delay),
+            || {
+                attempts += 1;
+                match attempts {
+                    1 => OperationResult::Retry("busy"),
+                    2 => OperationResult::Err("corrupt"),
+                    _ => OperationResult::Ok(()),
+                }
+            },
+        );
+
+        assert_eq!(slept, [Duration::from_millis(5)]);
+        assert_eq!(report.tries(), 2);
+        assert_eq!(report.errors().collect::<Vec<_>>(), [&"busy", &"corrupt"]);
+        assert!(format!("{:?}", report).starts_with("AttemptReport { value: None"));
+    }
+}
@@ -0,0 +1,324 @@
+use std::{
+    convert::TryFrom,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    clock::{Clock, SystemClock},
+    sync::{Arc, AtomicU64, Ordering},
+};
+
+/// The bit of the state word set while a call tests a dependency whose breaker was open.
+const PROBING: u64 = 1;
+
+/// Stops the calls made through the policies it is attached to for a while, once enough calls in
+/// a row have given up, so that a dependency that is down is not kept busy with retries.
+///
+/// After `failure_threshold` calls in a row give up, the breaker opens, and calls fail fast with
+/// `Error::CircuitOpen` without making any attempt. Once it has been open for `open_for`, it lets a
+/// single call through to test the dependency: the breaker closes if that call succeeds, and opens
+/// again if it gives up. Clones share the same state, so the same breaker can be attached to
+/// several policies that call the same dependency.
+///
+/// The state is kept in atomics, so checking a breaker on every call takes no lock.
+///
+/// ```rust
+/// # use std::time::Duration;
+/// # use retry::delay::NoDelay;
+/// use retry::{CircuitBreaker, CircuitState, Error, Policy};
+///
+/// let breaker = CircuitBreaker::new(2, Duration::from_secs(30));
+/// let policy = Policy::new(NoDelay.take(1)).with_circuit_breaker(breaker.clone());
+///
+/// let _ = policy.retry(|| Err::<(), _>("unavailable"));
+/// let _ = policy.retry(|| Err::<(), _>("unavailable"));
+/// assert_eq!(breaker.state(), CircuitState::Open);
+///
+/// let mut attempts = 0;
+/// let result = policy.retry(|| {
+///     attempts += 1;
+///     Ok::<_, &str>(())
+/// });
+/// assert!(matches!(result, Err(Error::CircuitOpen { .. })));
+/// assert_eq!(attempts, 0);
+/// ```
+#[derive(Clone, Debug)]
+pub struct CircuitBreaker {
+    failure_threshold: u64,
+    open_for: Duration,
+    clock: Option<std::sync::Arc<dyn Clock>>,
+    epoch: Instant,
+    state: Arc<State>,
+}
+
+/// The state shared by the clones of a breaker.
+///
+/// `word` is zero while the breaker is closed. Once it opens, it holds one more than the time it
+/// opened, in nanoseconds since the breaker's epoch, shifted left by one, with the low bit set
+/// while a call tests the dependency. Every transition is a single store or compare-and-swap of
+/// this word. `failures` counts the calls that gave up in a row while the breaker was closed.
+#[derive(Debug, Default)]
+struct State {
+    word: AtomicU64,
+    failures: AtomicU64,
+}
+
+/// Whether a circuit breaker lets calls through.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum CircuitState {
+    /// Calls go through.
+    Closed,
+    /// Calls are rejected.
+    Open,
+    /// The breaker has been open for long enough to let the next call test the dependency, or a
+    /// call is testing it.
+    HalfOpen,
+}
+
+impl CircuitBreaker {
+    /// Open after `failure_threshold` calls in a row give up, and stay open for `open_for` before
+    /// testing the dependency again.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `failure_threshold` is zero.
+    pub fn new(failure_threshold: u64, open_for: Duration) -> Self {
+        assert!(
+            failure_threshold > 0,
+            "a circuit breaker must allow at least one failure"
+        );
+        CircuitBreaker {
+            failure_threshold,
+            open_for,
+            clock: None,
+            epoch: SystemClock.now(),
+            state: Arc::default(),
+        }
+    }
+
+    /// Measure how long the breaker stays open with the given clock instead of the system clock.
+    pub fn with_clock<C>(mut self, clock: C) -> Self
+    where
+        C: Clock + 'static,
+    {
+        self.epoch = clock.now();
+        self.clock = Some(std::sync::Arc::new(clock));
+        self
+    }
+
+    /// The number of calls in a row that open the breaker.
+    pub fn failure_threshold(&self) -> u64 {
+        self.failure_threshold
+    }
+
+    /// How long the breaker stays open before testing the dependency again.
+    pub fn open_for(&self) -> Duration {
+        self.open_for
+    }
+
+    /// Whether the breaker lets calls through.
+    pub fn state(&self) -> CircuitState {
+        let word = self.state.word.load(Ordering::Acquire);
+        if word == 0 {
+            CircuitState::Closed
+        } else if word & PROBING != 0 || self.open_elapsed(word, self.now()) >= self.open_for {
+            CircuitState::HalfOpen
+        } else {
+            CircuitState::Open
+        }
+    }
+
+    /// Let a call through, if the breaker is closed or the call is the one to test the dependency,
+    /// or return how long until the breaker will let one through.
+    pub(crate) fn try_enter(&self) -> Result<Admission, Duration> {
+        let trial = self.try_enter_at(self.now())?;
+        Ok(Admission {
+            breaker: self.clone(),
+            trial,
+        })
+    }
+
+    /// Let a call through at `now`, returning whether it is the call that tests the dependency.
+    fn try_enter_at(&self, now: u64) -> Result<bool, Duration> {
+        let mut word = self.state.word.load(Ordering::Acquire);
+        loop {
+            if word == 0 {
+                return Ok(false);
+            }
+            let elapsed = self.open_elapsed(word, now);
+            if elapsed < self.open_for {
+                return Err(self.open_for - elapsed);
+            }
+            if word & PROBING != 0 {
+                return Err(Duration::default());
+            }
+            match self.state.word.compare_exchange_weak(
+                word,
+                word | PROBING,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => return Ok(true),
+                Err(current) => word = current,
+            }
+        }
+    }
+
+    fn record_success(&self, trial: bool) {
+        self.state.failures.store(0, Ordering::Relaxed);
+        if trial {
+            self.state.word.store(0, Ordering::Release);
+        }
+    }
+
+    fn record_failure_at(&self, now: u64, trial: bool) {
+        if trial {
+            self.state.word.store(opened(now), Ordering::Release);
+            return;
+        }
+        let failures = self.state.failures.fetch_add(1, Ordering::Relaxed) + 1;
+        if failures >= self.failure_threshold
+            && self
+                .state
+                .word
+                .compare_exchange(0, opened(now), Ordering::AcqRel, Ordering::Relaxed)
+                .is_ok()
+        {
+            self.state.failures.store(0, Ordering::Relaxed);
+        }
+    }
+
+    /// Give up testing the dependency without an outcome, so that the next call tests it instead.
+    fn abandon_trial(&self) {
+        self.state.word.fetch_and(!PROBING, Ordering::AcqRel);
+    }
+
+    /// How long the breaker whose state is `word` has been open at `now`.
+    fn open_elapsed(&self, word: u64, now: u64) -> Duration {
+        Duration::from_nanos(now.saturating_sub((word >> 1) - 1))
+    }
+
+    /// The time, in nanoseconds since the breaker's epoch.
+    fn now(&self) -> u64 {
+        let now = match self.clock {
+            Some(ref clock) => clock.now(),
+            None => SystemClock.now(),
+        };
+        let nanos = now.saturating_duration_since(self.epoch).as_nanos();
+        u64::try_from(nanos).unwrap_or(u64::MAX).min(MAX_NANOS)
+    }
+}
+
+/// The latest time the state word can hold.
+const MAX_NANOS: u64 = (u64::MAX >> 1) - 1;
+
+/// The state word of a breaker that opened at `now`.
+fn opened(now: u64) -> u64 {
+    (now.min(MAX_NANOS) + 1) << 1
+}
+
+/// A call let through by a circuit breaker, which reports its outcome to the breaker. A call
+/// dropped without an outcome, such as one that was cancelled, does not count.
+#[derive(Debug)]
+pub(crate) struct Admission {
+    breaker: CircuitBreaker,
+    trial: bool,
+}
+
+impl Admission {
+    pub(crate) fn succeeded(mut self) {
+        self.breaker.record_success(self.trial);
+        self.trial = false;
+    }
+
+    pub(crate) fn failed(mut self) {
+        let now = self.breaker.now();
+        self.breaker.record_failure_at(now, self.trial);
+        self.trial = false;
+    }
+}
+
+impl Drop for Admission {
+    fn drop(&mut self) {
+        if self.trial {
+            self.breaker.abandon_trial();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{CircuitBreaker, CircuitState};
+    use crate::{clock::MockClock, delay::NoDelay, Error, Policy};
+
+    #[test]
+    fn opens_and_tests_the_dependency_again() {
+        let clock = MockClock::new();
+        let breaker = CircuitBreaker::new(2, Duration::from_secs(30)).with_clock(clock.clone());
+        let policy = Policy::new(NoDelay.take(1)).with_circuit_breaker(breaker.clone());
+
+        let _ = policy.retry(|| Err::<(), _>("down"));
+        assert_eq!(policy.retry(|| Ok::<_, &str>(())), Ok(()));
+        let _ = policy.retry(|| Err::<(), _>("down"));
+        assert_eq!(breaker.state(), CircuitState::Closed);
+        let _ = policy.retry(|| Err::<(), _>("down"));
+        assert_eq!(breaker.state(), CircuitState::Open);
+
+        clock.advance(Duration::from_secs(10));
+        assert_eq!(
+            policy.retry(|| Ok::<_, &str>(())),
+            Err(Error::CircuitOpen {
+                retry_in: Duration::from_secs(20)
+            })
+        );
+
+        clock.advance(Duration::from_secs(20));
+        assert_eq!(breaker.state(), CircuitState::HalfOpen);
+        let _ = policy.retry(|| Err::<(), _>("still down"));
+        assert_eq!(breaker.state(), CircuitState::Open);
+
+        clock.advance(Duration::from_secs(30));
+        let trial = breaker.try_enter().unwrap();
+        assert_eq!(
+            breaker.try_enter().unwrap_err(),
+            Duration::default(),
+            "only one call tests the dependency"
+        );
+        drop(trial);
+        assert_eq!(policy.retry(|| Ok::<_, &str>(())), Ok(()));
+        assert_eq!(breaker.state(), CircuitState::Closed);
+    }
+
+    #[cfg(retry_loom)]
+    #[test]
+    fn loom_lets_a_single_call_test_the_dependency() {
+        loom::model(|| {
+            let breaker = CircuitBreaker::new(1, Duration::from_nanos(10));
+            breaker.record_failure_at(0, false);
+
+            let other = breaker.clone();
+            let thread = loom::thread::spawn(move || other.try_enter_at(10) == Ok(true));
+            let trial = breaker.try_enter_at(10) == Ok(true);
+
+            assert!(trial ^ thread.join().unwrap());
+        });
+    }
+
+    #[cfg(retry_loom)]
+    #[test]
+    fn loom_opens_once_when_calls_fail_together() {
+        loom::model(|| {
+            let breaker = CircuitBreaker::new(2, Duration::from_nanos(10));
+
+            let other = breaker.clone();
+            let thread = loom::thread::spawn(move || other.record_failure_at(1, false));
+            breaker.record_failure_at(2, false);
+            thread.join().unwrap();
+
+            assert!(breaker.try_enter_at(5).is_err());
+            assert_eq!(breaker.try_enter_at(12), Ok(true));
+        });
+    }
+}
@@ -0,0 +1,490 @@
+use std::{
+    fmt::Debug,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    clock::{Clock, SystemClock},
+    sync::{Arc, AtomicU64, Ordering},
+};
+
+/// A limit on the retries made through the policies it is attached to, so that retries cannot
+/// multiply the load on a dependency that is struggling.
+///
+/// Every call deposits into the budget when it makes its first attempt, and every retry must
+/// withdraw from it. A call whose retry is refused gives up with its last error, as if its
+/// schedule had ended.
+pub trait RetryBudget: Debug + Send + Sync {
+    /// Record that a call was made.
+    fn deposit(&self);
+
+    /// Ask to retry a call, returning `false` if the budget is spent.
+    fn try_withdraw(&self) -> bool;
+}
+
+impl<B> RetryBudget for std::sync::Arc<B>
+where
+    B: RetryBudget + ?Sized,
+{
+    fn deposit(&self) {
+        (**self).deposit()
+    }
+
+    fn try_withdraw(&self) -> bool {
+        (**self).try_withdraw()
+    }
+}
+
+/// The number of slots the window of a `RatioBudget` is divided into.
+const SLOTS: usize = 10;
+
+/// A retry budget that allows retries as a fraction of the calls made recently, such as at most
+/// 20% extra load measured over the last 10 seconds, as in Finagle.
+///
+/// On top of the ratio, a minimum number of retries per second is always allowed, so that a
+/// client that makes few calls can still retry some of them. It defaults to 10 per second.
+///
+/// The window slides in tenths, and the counts are kept in atomics, so a budget can be checked on
+/// every call without taking a lock. Calls made while the window slides may be missed, so the
+/// limit is approximate. Clones share the same counts, so the same budget can be attached to
+/// several policies that call the same dependency.
+///
+/// ```rust
+/// # use std::time::Duration;
+/// # use retry::delay::NoDelay;
+/// use retry::{Policy, RatioBudget};
+///
+/// let budget = RatioBudget::new(0.5, Duration::from_secs(10)).with_min_retries_per_second(0);
+/// let policy = Policy::new(NoDelay.take(3)).with_retry_budget(budget.clone());
+///
+/// let mut attempts = 0;
+/// for _ in 0..4 {
+///     let _ = policy.retry(|| {
+///         attempts += 1;
+///         Err::<(), _>("unavailable")
+///     });
+/// }
+///
+/// // Four calls allow two retries, whichever calls make them.
+/// assert_eq!(attempts, 6);
+/// assert_eq!(budget.balance(), 0);
+/// ```
+#[derive(Clone, Debug)]
+pub struct RatioBudget {
+    ratio: f64,
+    window: Duration,
+    min_retries_per_second: u64,
+    clock: Option<std::sync::Arc<dyn Clock>>,
+    epoch: Instant,
+    slots: Arc<[Slot; SLOTS]>,
+}
+
+/// The counts of a tenth of the window.
+#[derive(Debug, Default)]
+struct Slot {
+    /// One more than the index of the tenth of the window the counts are for, or zero before the
+    /// slot is first used.
+    tenth: AtomicU64,
+    deposits: AtomicU64,
+    withdrawals: AtomicU64,
+}
+
+impl RatioBudget {
+    /// Allow retries of at most `ratio` of the calls made over the last `window`, such as `0.2`
+    /// for 20%.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `ratio` is negative or not finite, or if `window` is zero.
+    pub fn new(ratio: f64, window: Duration) -> Self {
+        assert!(
+            ratio.is_finite() && ratio >= 0.0,
+            "the ratio of a retry budget must be a finite, non-negative number"
+        );
+        assert!(
+            window > Duration::default(),
+            "the window of a retry budget must not be zero"
+        );
+        RatioBudget {
+            ratio,
+            window,
+            min_retries_per_second: 10,
+            clock: None,
+            epoch: SystemClock.now(),
+            slots: Arc::default(),
+        }
+    }
+
+    /// Always allow `min_retries_per_second` retries per second over the window, on top of the
+    /// ratio.
+    pub fn with_min_retries_per_second(mut self, min_retries_per_second: u64) -> Self {
+        self.min_retries_per_second = min_retries_per_second;
+        self
+    }
+
+    /// Measure the window with the given clock instead of the system clock.
+    pub fn with_clock<C>(mut self, clock: C) -> Self
+    where
+        C: Clock + 'static,
+    {
+        self.epoch = clock.now();
+        self.clock = Some(std::sync::Arc::new(clock));
+        self
+    }
+
+    /// The fraction of the calls that can be retried.
+    pub fn ratio(&self) -> f64 {
+        self.ratio
+    }
+
+    /// The window over which calls and retries are counted.
+    pub fn window(&self) -> Duration {
+        self.window
+    }
+
+    /// The number of retries the budget allows now.
+    pub fn balance(&self) -> u64 {
+        let tenth = self.tenth();
+        let (deposits, withdrawals) = self.totals(tenth);
+        self.allowance(deposits).saturating_sub(withdrawals)
+    }
+
+    fn now(&self) -> Instant {
+        match self.clock {
+            Some(ref clock) => clock.now(),
+            None => SystemClock.now(),
+        }
+    }
+
+    /// The index of the current tenth of the window since the epoch.
+    fn tenth(&self) -> u64 {
+        let elapsed = self.now().saturating_duration_since(self.epoch).as_nanos();
+        let tenth = (self.window.as_nanos() / SLOTS as u128).max(1);
+        (elapsed / tenth) as u64
+    }
+
+    /// The slot of the current tenth, cleared if it still holds the counts of an older one.
+    fn slot(&self, tenth: u64) -> &Slot {
+        let slot = &self.slots[(tenth % SLOTS as u64) as usize];
+        let current = slot.tenth.load(Ordering::Acquire);
+        if current != tenth + 1
+            && slot
+                .tenth
+                .compare_exchange(current, tenth + 1, Ordering::AcqRel, Ordering::Acquire)
+                .is_ok()
+        {
+            slot.deposits.store(0, Ordering::Release);
+            slot.withdrawals.store(0, Ordering::Release);
+        }
+        slot
+    }
+
+    /// The deposits and withdrawals of the slots within the window ending at `tenth`.
+    fn totals(&self, tenth: u64) -> (u64, u64) {
+        let oldest = tenth.saturating_sub(SLOTS as u64 - 1);
+        self.slots
+            .iter()
+            .filter(|slot| slot.tenth.load(Ordering::Acquire) > oldest)
+            .fold((0, 0), |(deposits, withdrawals), slot| {
+                (
+                    deposits + slot.deposits.load(Ordering::Acquire),
+                    withdrawals + slot.withdrawals.load(Ordering::Acquire),
+                )
+            })
+    }
+
+    /// The number of retries allowed over the window for the given number of calls.
+    fn allowance(&self, deposits: u64) -> u64 {
+        let reserve = self.min_retries_per_second as f64 * self.window.as_secs_f64();
+        (deposits as f64 * self.ratio + reserve) as u64
+    }
+}
+
+impl RetryBudget for RatioBudget {
+    fn deposit(&self) {
+        let tenth = self.tenth();
+        self.slot(tenth).deposits.fetch_add(1, Ordering::AcqRel);
+    }
+
+    fn try_withdraw(&self) -> bool {
+        let tenth = self.tenth();
+        let slot = self.slot(tenth);
+        slot.withdrawals.fetch_add(1, Ordering::AcqRel);
+        let (deposits, withdrawals) = self.totals(tenth);
+        if withdrawals <= self.allowance(deposits) {
+            true
+        } else {
+            slot.withdrawals.fetch_sub(1, Ordering::AcqRel);
+            false
+        }
+    }
+}
+
+/// A budget of attempts, and optionally of time, for a call and every call nested in it, so that
+/// a call whose attempts retry internally cannot multiply its attempts.
+///
+/// The budget of a nested call is a `child` of the budget of the call it is made from, and every
+/// attempt made with the child also draws from its parent, and from the parent's parent, up to
+/// the root. A retry is refused once the child or any of its ancestors has no attempts left or
+/// is past its time budget, which is measured from when the budget was given one. The first
+/// attempt of a call is always made, as with any retry budget, but it is counted all the same.
+///
+/// Clones share the same counts, so a budget is best given its limits before it is attached to a
+/// policy, and children are made for each call, such as in the operation of the parent call:
+///
+/// ```rust
+/// # use retry::delay::NoDelay;
+/// use retry::{NestedBudget, Policy};
+///
+/// let budget = NestedBudget::new(6);
+/// let outer = Policy::new(NoDelay.take(3)).with_retry_budget(budget.clone());
+/// let mut inner_attempts = 0;
+///
+/// let result = outer.retry(|| {
+///     let inner = Policy::new(NoDelay.take(3)).with_retry_budget(budget.child(10));
+///     inner.retry(|| {
+///         inner_attempts += 1;
+///         Err::<(), _>("unavailable")
+///     })
+/// });
+///
+/// // Without the budget, the inner operation would be attempted 16 times.
+/// assert!(result.is_err());
+/// assert_eq!(inner_attempts, 5);
+/// assert_eq!(budget.remaining_attempts(), 0);
+/// ```
+#[derive(Clone, Debug)]
+pub struct NestedBudget {
+    node: Arc<Node>,
+}
+
+/// The limits of a budget and of its ancestors.
+#[derive(Debug)]
+struct Node {
+    parent: Option<Arc<Node>>,
+    remaining: AtomicU64,
+    deadline: Option<Instant>,
+    clock: Option<std::sync::Arc<dyn Clock>>,
+}
+
+impl Node {
+    fn now(&self) -> Instant {
+        match self.clock {
+            Some(ref clock) => clock.now(),
+            None => SystemClock.now(),
+        }
+    }
+
+    /// This node and its ancestors, from this node up.
+    fn chain(&self) -> impl Iterator<Item = &Node> {
+        std::iter::successors(Some(self), |node| node.parent.as_deref())
+    }
+}
+
+impl NestedBudget {
+    /// A root budget of `max_attempts` attempts, without a time budget.
+    pub fn new(max_attempts: u64) -> Self {
+        NestedBudget {
+            node: Arc::new(Node {
+                parent: None,
+                remaining: AtomicU64::new(max_attempts),
+                deadline: None,
+                clock: None,
+            }),
+        }
+    }
+
+    /// A budget of at most `max_attempts` attempts for a call nested in the calls of this budget,
+    /// which draws from this budget and is capped by what it has left. The child measures time
+    /// with the clock of this budget.
+    pub fn child(&self, max_attempts: u64) -> Self {
+        NestedBudget {
+            node: Arc::new(Node {
+                parent: Some(Arc::clone(&self.node)),
+                remaining: AtomicU64::new(max_attempts),
+                deadline: None,
+                clock: self.node.clock.clone(),
+            }),
+        }
+    }
+
+    /// Refuse retries once `time_budget` has passed from now, or earlier if an ancestor's time
+    /// budget runs out first. Clones made before share the limits they had.
+    pub fn with_time_budget(self, time_budget: Duration) -> Self {
+        let deadline = self.node.now().checked_add(time_budget);
+        self.rebuild(|node| node.deadline = deadline)
+    }
+
+    /// Measure the time budget with the given clock instead of the system clock. Clones made
+    /// before share the limits they had.
+    pub fn with_clock<C>(self, clock: C) -> Self
+    where
+        C: Clock + 'static,
+    {
+        self.rebuild(|node| node.clock = Some(std::sync::Arc::new(clock)))
+    }
+
+    fn rebuild<F>(self, change: F) -> Self
+    where
+        F: FnOnce(&mut Node),
+    {
+        let mut node = Node {
+            parent: self.node.parent.clone(),
+            remaining: AtomicU64::new(self.node.remaining.load(Ordering::Acquire)),
+            deadline: self.node.deadline,
+            clock: self.node.clock.clone(),
+        };
+        change(&mut node);
+        NestedBudget {
+            node: Arc::new(node),
+        }
+    }
+
+    /// The number of attempts left to this budget, at most what its ancestors have left.
+    pub fn remaining_attempts(&self) -> u64 {
+        self.node
+            .chain()
+            .map(|node| node.remaining.load(Ordering::Acquire))
+            .min()
+            .unwrap_or_default()
+    }
+
+    /// The time left to this budget, at most what its ancestors have left, if any of them has a
+    /// time budget.
+    pub fn remaining_time(&self) -> Option<Duration> {
+        let now = self.node.now();
+        self.node
+            .chain()
+            .filter_map(|node| node.deadline)
+            .map(|deadline| deadline.saturating_duration_since(now))
+            .min()
+    }
+
+    /// Whether the budget refuses any more retries.
+    pub fn is_spent(&self) -> bool {
+        self.remaining_attempts() == 0 || self.remaining_time() == Some(Duration::default())
+    }
+}
+
+impl RetryBudget for NestedBudget {
+    fn deposit(&self) {
+        for node in self.node.chain() {
+            let _ = node
+                .remaining
+                .fetch_update(Ordering::AcqRel, Ordering::Acquire, |remaining| {
+                    remaining.checked_sub(1)
+                });
+        }
+    }
+
+    fn try_withdraw(&self) -> bool {
+        if self.remaining_time() == Some(Duration::default()) {
+            return false;
+        }
+        let taken = self.node.chain().take_while(|node| {
+            node.remaining
+                .fetch_update(Ordering::AcqRel, Ordering::Acquire, |remaining| {
+                    remaining.checked_sub(1)
+                })
+                .is_ok()
+        });
+        let taken = taken.count();
+        if taken == self.node.chain().count() {
+            return true;
+        }
+        // Give back what was taken from the nodes below the one that had nothing left.
+        for node in self.node.chain().take(taken) {
+            node.remaining.fetch_add(1, Ordering::AcqRel);
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{NestedBudget, RatioBudget, RetryBudget};
+    use crate::clock::MockClock;
+
+    #[test]
+    fn allows_a_ratio_of_recent_calls() {
+        let clock = MockClock::new();
+        let budget = RatioBudget::new(0.2, Duration::from_secs(10))
+            .with_min_retries_per_second(0)
+            .with_clock(clock.clone());
+
+        for _ in 0..10 {
+            budget.deposit();
+        }
+        assert_eq!(budget.balance(), 2);
+        assert!(budget.try_withdraw());
+        assert!(budget.try_withdraw());
+        assert!(!budget.try_withdraw());
+
+        clock.advance(Duration::from_secs(5));
+        for _ in 0..5 {
+            budget.deposit();
+        }
+        assert_eq!(budget.balance(), 1);
+
+        // The first calls and retries slide out of the window.
+        clock.advance(Duration::from_secs(6));
+        assert_eq!(budget.balance(), 1);
+        assert!(budget.try_withdraw());
+        assert!(!budget.try_withdraw());
+
+        clock.advance(Duration::from_secs(10));
+        assert_eq!(budget.balance(), 0);
+    }
+
+    #[test]
+    fn always_allows_the_minimum() {
+        let budget = RatioBudget::new(0.0, Duration::from_secs(2)).with_min_retries_per_second(3);
+
+        assert_eq!(budget.balance(), 6);
+        for _ in 0..6 {
+            assert!(budget.try_withdraw());
+        }
+        assert!(!budget.try_withdraw());
+    }
+
+    #[test]
+    fn nested_budgets_draw_from_their_ancestors() {
+        let root = NestedBudget::new(4);
+        let child = root.child(2);
+        let grandchild = child.child(10);
+
+        grandchild.deposit();
+        assert_eq!(grandchild.remaining_attempts(), 1);
+        assert_eq!(root.remaining_attempts(), 3);
+        assert!(grandchild.try_withdraw());
+        assert!(!grandchild.try_withdraw());
+        assert_eq!(grandchild.remaining_attempts(), 0);
+
+        // The refused retry took nothing from the grandchild or the root.
+        assert_eq!(grandchild.node.remaining.load(super::Ordering::Acquire), 8);
+        assert_eq!(root.remaining_attempts(), 2);
+        let sibling = root.child(5);
+        assert!(sibling.try_withdraw());
+        assert!(sibling.try_withdraw());
+        assert!(!sibling.try_withdraw());
+        assert!(root.is_spent());
+    }
+
+    #[test]
+    fn nested_budgets_share_the_time_budget() {
+        let clock = MockClock::new();
+        let root = NestedBudget::new(100)
+            .with_clock(clock.clone())
+            .with_time_budget(Duration::from_secs(10));
+        let child = root.child(100).with_time_budget(Duration::from_secs(30));
+
+        assert_eq!(child.remaining_time(), Some(Duration::from_secs(10)));
+        assert!(child.try_withdraw());
+        clock.advance(Duration::from_secs(10));
+        assert!(child.is_spent());
+        assert!(!child.try_withdraw());
+        assert_eq!(NestedBudget::new(1).remaining_time(), None);
+    }
+}
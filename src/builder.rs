@@ -0,0 +1,142 @@
+//! A policy builder that only builds policies which stop retrying at some point.
+//!
+//! A `Policy` made from an infinite strategy, like `Fixed::from_millis(10)`, without a maximum
+//! number of attempts retries forever. That is sometimes intended, but more often a forgotten
+//! `take` or `with_max_attempts`. A `PolicyBuilder` can only be built once it has been told how
+//! the policy stops:
+//!
+//! ```rust
+//! # use retry::delay::Fixed;
+//! use retry::Policy;
+//!
+//! let policy = Policy::builder(Fixed::from_millis(10))
+//!     .with_max_attempts(3)
+//!     .configure(|policy| policy.with_name("db"))
+//!     .build();
+//!
+//! assert_eq!(policy.max_attempts(), Some(3));
+//! ```
+//!
+//! Forgetting to bound the policy does not compile:
+//!
+//! ```compile_fail
+//! # use retry::delay::Fixed;
+//! # use retry::Policy;
+//! let policy = Policy::builder(Fixed::from_millis(10)).build();
+//! ```
+
+use std::marker::PhantomData;
+
+use crate::Policy;
+
+/// The state of a `PolicyBuilder` that does not know yet how the policy stops.
+#[derive(Clone, Copy, Debug)]
+pub enum Incomplete {}
+
+/// The state of a `PolicyBuilder` that knows how the policy stops, or that it never does.
+#[derive(Clone, Copy, Debug)]
+pub enum Bounded {}
+
+/// Builds a `Policy`, which must first be bounded with `with_max_attempts`,
+/// `with_finite_delays` or `retry_forever`.
+#[derive(Clone, Debug)]
+pub struct PolicyBuilder<D, S> {
+    policy: Policy<D>,
+    state: PhantomData<S>,
+}
+
+impl<D> Policy<D> {
+    /// Start building a policy with the given delay strategy, that must be bounded before it can
+    /// be built.
+    pub fn builder(delays: D) -> PolicyBuilder<D, Incomplete> {
+        PolicyBuilder {
+            policy: Policy::new(delays),
+            state: PhantomData,
+        }
+    }
+}
+
+impl<D, S> PolicyBuilder<D, S> {
+    /// Set the other options of the policy, with the methods of `Policy`.
+    pub fn configure<F>(self, configure: F) -> Self
+    where
+        F: FnOnce(Policy<D>) -> Policy<D>,
+    {
+        PolicyBuilder {
+            policy: configure(self.policy),
+            state: PhantomData,
+        }
+    }
+
+    /// Make at most `max_attempts` attempts, as with `Policy::with_max_attempts`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_attempts` is zero.
+    pub fn with_max_attempts(self, max_attempts: u64) -> PolicyBuilder<D, Bounded> {
+        self.bound(|policy| policy.with_max_attempts(max_attempts))
+    }
+
+    /// Declare that the delay strategy ends by itself, like `Fixed::from_millis(10).take(3)` or a
+    /// `Backoff` with a budget.
+    pub fn with_finite_delays(self) -> PolicyBuilder<D, Bounded> {
+        self.bound(|policy| policy)
+    }
+
+    /// Declare that the policy is meant to retry until the operation succeeds or fails with a
+    /// permanent error.
+    pub fn retry_forever(self) -> PolicyBuilder<D, Bounded> {
+        self.bound(|policy| policy)
+    }
+
+    fn bound<F>(self, bound: F) -> PolicyBuilder<D, Bounded>
+    where
+        F: FnOnce(Policy<D>) -> Policy<D>,
+    {
+        PolicyBuilder {
+            policy: bound(self.policy),
+            state: PhantomData,
+        }
+    }
+}
+
+impl<D> PolicyBuilder<D, Bounded> {
+    /// Build the policy.
+    pub fn build(self) -> Policy<D> {
+        self.policy
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{delay::Fixed, Policy};
+
+    #[test]
+    fn builds_bounded_policies() {
+        let policy = Policy::builder(Fixed::from_millis(1).take(2))
+            .configure(|policy| policy.with_name("cache"))
+            .with_finite_delays()
+            .build();
+
+        assert_eq!(policy.name(), Some("cache"));
+        assert_eq!(policy.max_attempts(), None);
+        assert_eq!(
+            policy.retry(|| Err::<(), _>("miss")).unwrap_err().tries(),
+            3
+        );
+
+        let policy = Policy::builder(Fixed::from_millis(1))
+            .retry_forever()
+            .build();
+        let mut attempts = 0;
+        let result = policy.retry(|| {
+            attempts += 1;
+            if attempts < 5 {
+                Err("miss")
+            } else {
+                Ok(attempts)
+            }
+        });
+        assert_eq!(result, Ok(5));
+    }
+}
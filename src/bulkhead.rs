@@ -0,0 +1,156 @@
+use crate::sync::{Arc, AtomicUsize, Ordering};
+
+/// A limit on the number of calls in progress at once through the policies it is attached to,
+/// so that an outage cannot pile up calls stuck retrying until threads and memory run out.
+///
+/// A call holds its place in the bulkhead from its first attempt until it returns. When the
+/// bulkhead is full, calls fail fast with `Error::Rejected` without making any attempt. Clones
+/// share the same places, so the same bulkhead can be attached to several policies to limit them
+/// together.
+///
+/// ```rust
+/// # use retry::delay::NoDelay;
+/// use retry::{Bulkhead, Error, Policy};
+///
+/// let bulkhead = Bulkhead::new(1);
+/// let policy = Policy::new(NoDelay.take(1)).with_bulkhead(bulkhead.clone());
+///
+/// let mut nested = None;
+/// let result = policy.retry(|| {
+///     // This call holds the only place, so a nested call is rejected.
+///     assert_eq!(bulkhead.in_use(), 1);
+///     nested = Some(policy.retry(|| Ok::<_, ()>(())));
+///     Ok::<_, ()>(())
+/// });
+///
+/// assert_eq!(result, Ok(()));
+/// assert_eq!(nested, Some(Err(Error::Rejected { max_concurrent: 1 })));
+/// assert_eq!(bulkhead.in_use(), 0);
+/// ```
+#[derive(Clone, Debug)]
+pub struct Bulkhead {
+    max_concurrent: usize,
+    in_use: Arc<AtomicUsize>,
+}
+
+impl Bulkhead {
+    /// Allow at most `max_concurrent` calls in progress at once.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_concurrent` is zero.
+    pub fn new(max_concurrent: usize) -> Self {
+        assert!(
+            max_concurrent > 0,
+            "a bulkhead must allow at least one call"
+        );
+        Bulkhead {
+            max_concurrent,
+            in_use: Arc::default(),
+        }
+    }
+
+    /// The number of calls allowed in progress at once.
+    pub fn max_concurrent(&self) -> usize {
+        self.max_concurrent
+    }
+
+    /// The number of calls in progress.
+    pub fn in_use(&self) -> usize {
+        self.in_use.load(Ordering::Acquire)
+    }
+
+    /// Take a place for a call, if one is free.
+    pub(crate) fn try_enter(&self) -> Option<Permit> {
+        self.in_use
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |in_use| {
+                (in_use < self.max_concurrent).then(|| in_use + 1)
+            })
+            .ok()?;
+        Some(Permit(Arc::clone(&self.in_use)))
+    }
+}
+
+/// A place taken in a bulkhead, given back when dropped.
+#[derive(Debug)]
+pub(crate) struct Permit(Arc<AtomicUsize>);
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Barrier};
+
+    use super::Bulkhead;
+    use crate::{delay::NoDelay, Error, Policy};
+
+    #[test]
+    fn gives_places_back_when_calls_return() {
+        let bulkhead = Bulkhead::new(2);
+        let first = bulkhead.try_enter().unwrap();
+        let _second = bulkhead.clone().try_enter().unwrap();
+
+        assert!(bulkhead.try_enter().is_none());
+        drop(first);
+        assert_eq!(bulkhead.in_use(), 1);
+        assert!(bulkhead.try_enter().is_some());
+    }
+
+    #[test]
+    fn rejects_calls_beyond_the_limit() {
+        let bulkhead = Bulkhead::new(2);
+        let policy = Arc::new(Policy::new(NoDelay.take(1)).with_bulkhead(bulkhead.clone()));
+        let entered = Arc::new(Barrier::new(3));
+        let release = Arc::new(Barrier::new(3));
+
+        let calls: Vec<_> = (0..2)
+            .map(|_| {
+                let (policy, entered, release) = (
+                    Arc::clone(&policy),
+                    Arc::clone(&entered),
+                    Arc::clone(&release),
+                );
+                std::thread::spawn(move || {
+                    policy.retry(|| {
+                        entered.wait();
+                        release.wait();
+                        Ok::<_, ()>(())
+                    })
+                })
+            })
+            .collect();
+
+        entered.wait();
+        let mut attempts = 0;
+        let result = policy.retry(|| {
+            attempts += 1;
+            Ok::<_, ()>(())
+        });
+        assert_eq!(result, Err(Error::Rejected { max_concurrent: 2 }));
+        assert_eq!(attempts, 0);
+
+        release.wait();
+        for call in calls {
+            assert_eq!(call.join().unwrap(), Ok(()));
+        }
+        assert_eq!(policy.retry(|| Ok::<_, ()>(())), Ok(()));
+    }
+
+    #[cfg(retry_loom)]
+    #[test]
+    fn loom_never_gives_out_more_places_than_it_has() {
+        loom::model(|| {
+            let bulkhead = Bulkhead::new(1);
+
+            let other = bulkhead.clone();
+            let thread = loom::thread::spawn(move || other.try_enter());
+            let entered = bulkhead.try_enter();
+
+            assert!(entered.is_some() ^ thread.join().unwrap().is_some());
+        });
+    }
+}
@@ -0,0 +1,124 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+/// A flag that cancels the calls made through the policies it is attached to, checked before each
+/// attempt and regularly while waiting between attempts.
+///
+/// A cancelled call ends with `Error::Cancelled`, without making another attempt or waiting out
+/// the rest of its delay. The attempt in progress when the token is cancelled is not interrupted.
+/// Clones share the same flag, and a token stays cancelled once it has been.
+///
+/// The flag is a plain atomic rather than one of the crate's shared primitives, since signal
+/// handlers set it directly.
+///
+/// ```rust
+/// # use retry::delay::Fixed;
+/// use retry::{CancelToken, Error, Policy};
+///
+/// let token = CancelToken::new();
+/// let policy = Policy::new(Fixed::from_millis(60_000)).with_cancellation(token.clone());
+///
+/// let result = policy.retry(|| {
+///     token.cancel();
+///     Err::<(), _>("unavailable")
+/// });
+///
+/// assert!(matches!(result, Err(Error::Cancelled { tries: 1, .. })));
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct CancelToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancelToken {
+    /// Create a token that is not cancelled.
+    pub fn new() -> Self {
+        CancelToken::default()
+    }
+
+    /// Create a token that is cancelled when the process receives a termination signal, such as
+    /// `SIGINT` from Ctrl-C or `SIGTERM`, so that a command line tool stops retrying promptly
+    /// instead of finishing a long delay first.
+    ///
+    /// The first signal only cancels the token, so the program is expected to exit once the call
+    /// returns `Error::Cancelled`. A second signal terminates the process at once, in case the
+    /// program is stuck in an attempt. The handlers stay registered for the life of the process.
+    ///
+    /// This constructor is enabled with the `"signal"` feature.
+    #[cfg(feature = "signal")]
+    pub fn on_termination_signal() -> std::io::Result<Self> {
+        use signal_hook::{consts::TERM_SIGNALS, flag};
+
+        let token = CancelToken::new();
+        for &signal in TERM_SIGNALS {
+            // The shutdown handler runs first, so it only terminates the process if an earlier
+            // signal already cancelled the token.
+            flag::register_conditional_shutdown(signal, 1, Arc::clone(&token.cancelled))?;
+            flag::register(signal, Arc::clone(&token.cancelled))?;
+        }
+        Ok(token)
+    }
+
+    /// Cancel the calls that check this token.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Release);
+    }
+
+    /// Whether the token was cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Acquire)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::CancelToken;
+    use crate::{delay::Fixed, Error, Policy};
+
+    #[test]
+    fn cancelled_calls_stop_waiting() {
+        let token = CancelToken::new();
+        let policy = Policy::new(Fixed::from_millis(60_000)).with_cancellation(token.clone());
+        let canceller = token.clone();
+        let thread = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(50));
+            canceller.cancel();
+        });
+
+        let mut attempts = 0;
+        let result = policy.retry(|| {
+            attempts += 1;
+            Err::<(), _>("down")
+        });
+        thread.join().unwrap();
+
+        let error = result.unwrap_err();
+        assert!(matches!(error, Error::Cancelled { tries: 1, .. }));
+        assert!(error.total_delay() < Duration::from_secs(1));
+        assert_eq!(attempts, 1);
+
+        assert!(token.is_cancelled());
+        assert_eq!(
+            policy.retry(|| Ok::<_, &str>(())),
+            Err(Error::Cancelled {
+                total_delay: Duration::default(),
+                tries: 0,
+            })
+        );
+    }
+
+    #[cfg(all(unix, feature = "signal"))]
+    #[test]
+    fn termination_signals_cancel_the_token() {
+        let token = CancelToken::on_termination_signal().unwrap();
+        assert!(!token.is_cancelled());
+
+        signal_hook::low_level::raise(signal_hook::consts::SIGTERM).unwrap();
+
+        assert!(token.is_cancelled());
+    }
+}
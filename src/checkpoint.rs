@@ -0,0 +1,155 @@
+use std::{fmt::Debug, time::Duration};
+
+use crate::{Error, OperationResult, Policy};
+
+/// An operation that makes progress it can keep across attempts, such as a chunked upload, so
+/// that a retry continues from where the failed attempt got to instead of starting over.
+///
+/// After an attempt fails, `Policy::retry_resumable` takes a `checkpoint` of the progress it made,
+/// and hands it back to `resume` before the next attempt. The checkpoint should only record the
+/// progress that is known to be kept, such as the chunks the server acknowledged, so that `resume`
+/// can discard whatever the failed attempt left half done.
+///
+/// ```rust
+/// # use retry::delay::NoDelay;
+/// use retry::{OperationResult, Policy, Resumable};
+///
+/// struct Upload {
+///     chunks: usize,
+///     sent: usize,
+///     failures: usize,
+/// }
+///
+/// impl Resumable for Upload {
+///     type Output = usize;
+///     type Error = &'static str;
+///     type Checkpoint = usize;
+///
+///     fn attempt(&mut self) -> OperationResult<usize, &'static str> {
+///         while self.sent < self.chunks {
+///             if self.sent == 3 && self.failures == 0 {
+///                 self.failures += 1;
+///                 return OperationResult::Retry("connection reset");
+///             }
+///             self.sent += 1;
+///         }
+///         OperationResult::Ok(self.sent)
+///     }
+///
+///     fn checkpoint(&self) -> usize {
+///         self.sent
+///     }
+///
+///     fn resume(&mut self, sent: usize) {
+///         self.sent = sent;
+///     }
+/// }
+///
+/// let mut upload = Upload { chunks: 5, sent: 0, failures: 0 };
+/// assert_eq!(Policy::new(NoDelay.take(1)).retry_resumable(&mut upload), Ok(5));
+/// ```
+pub trait Resumable {
+    /// The value of a successful attempt.
+    type Output;
+    /// The error of a failed attempt.
+    type Error;
+    /// The progress kept across attempts.
+    type Checkpoint;
+
+    /// Make an attempt, continuing from the progress the operation was last resumed from, if any.
+    fn attempt(&mut self) -> OperationResult<Self::Output, Self::Error>;
+
+    /// The progress made so far that the next attempt can continue from.
+    fn checkpoint(&self) -> Self::Checkpoint;
+
+    /// Continue from `checkpoint` on the next attempt.
+    fn resume(&mut self, checkpoint: Self::Checkpoint);
+}
+
+impl<D> Policy<D>
+where
+    D: IntoIterator<Item = Duration> + Clone,
+{
+    /// Retry the given resumable operation synchronously according to this policy, resuming each
+    /// retry from the checkpoint taken after the attempt before it failed.
+    pub fn retry_resumable<O>(&self, operation: &mut O) -> Result<O::Output, Error<O::Error>>
+    where
+        O: Resumable,
+        O::Error: Debug,
+    {
+        let mut checkpoint = None;
+        self.retry(|| {
+            if let Some(checkpoint) = checkpoint.take() {
+                operation.resume(checkpoint);
+            }
+            let result = operation.attempt();
+            if let OperationResult::Retry(_) = result {
+                checkpoint = Some(operation.checkpoint());
+            }
+            result
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Resumable;
+    use crate::{delay::NoDelay, Error, OperationResult, Policy};
+
+    /// Writes bytes in chunks, acknowledging every other chunk, and failing halfway through.
+    struct Transfer {
+        acknowledged: usize,
+        written: usize,
+        resumed_from: Vec<usize>,
+    }
+
+    impl Resumable for Transfer {
+        type Output = usize;
+        type Error = &'static str;
+        type Checkpoint = usize;
+
+        fn attempt(&mut self) -> OperationResult<usize, &'static str> {
+            for _ in 0..3 {
+                self.written += 1;
+                if self.written.is_multiple_of(2) {
+                    self.acknowledged = self.written;
+                }
+                if self.written == 10 {
+                    return OperationResult::Ok(self.written);
+                }
+            }
+            OperationResult::Retry("timed out")
+        }
+
+        fn checkpoint(&self) -> usize {
+            self.acknowledged
+        }
+
+        fn resume(&mut self, acknowledged: usize) {
+            self.resumed_from.push(acknowledged);
+            self.written = acknowledged;
+        }
+    }
+
+    #[test]
+    fn resumes_from_the_last_checkpoint() {
+        let mut transfer = Transfer {
+            acknowledged: 0,
+            written: 0,
+            resumed_from: Vec::new(),
+        };
+
+        assert_eq!(
+            Policy::new(NoDelay.take(10)).retry_resumable(&mut transfer),
+            Ok(10)
+        );
+        assert_eq!(transfer.resumed_from, [2, 4, 6, 8]);
+
+        transfer.written = 0;
+        transfer.acknowledged = 0;
+        assert!(matches!(
+            Policy::new(NoDelay.take(1)).retry_resumable(&mut transfer),
+            Err(Error::Operation { tries: 2, .. })
+        ));
+    }
+}
@@ -0,0 +1,119 @@
+//! Command line flags that parse into retry policies. This module is enabled with the `"clap"`
+//! feature.
+//!
+//! Policies are written with the one-line settings of `RetryPolicyConfig`. `Policy<Backoff>`
+//! implements `ValueParserFactory`, so it can be used as the type of an argument directly; invalid
+//! settings are reported with the flag, the value and an example of valid settings:
+//!
+//! ```rust
+//! use clap::{Arg, Command};
+//! use retry::{clap::PolicyParser, delay::Backoff, Policy};
+//!
+//! let command =
+//!     Command::new("app").arg(Arg::new("retry").long("retry").value_parser(PolicyParser));
+//!
+//! let matches = command
+//!     .clone()
+//!     .try_get_matches_from(["app", "--retry", "fixed(2s, attempts=3)"])
+//!     .unwrap();
+//! let policy = matches.get_one::<Policy<Backoff>>("retry").unwrap();
+//! assert_eq!(policy.max_attempts(), Some(3));
+//!
+//! assert!(command.try_get_matches_from(["app", "--retry", "linear(2s)"]).is_err());
+//! ```
+
+use std::ffi::OsStr;
+
+use clap::{
+    builder::{TypedValueParser, ValueParserFactory},
+    error::ErrorKind,
+    Arg, Command, Error,
+};
+
+use crate::{delay::Backoff, Policy};
+
+/// An example of valid settings, shown when a flag cannot be parsed.
+const EXAMPLE: &str = "exponential(100ms, x2, cap=30s, attempts=5, jitter=full)";
+
+/// Parses the value of a flag into a `Policy<Backoff>`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PolicyParser;
+
+impl TypedValueParser for PolicyParser {
+    type Value = Policy<Backoff>;
+
+    fn parse_ref(
+        &self,
+        cmd: &Command,
+        arg: Option<&Arg>,
+        value: &OsStr,
+    ) -> Result<Self::Value, Error> {
+        let flag = arg
+            .map(|arg| arg.to_string())
+            .unwrap_or_else(|| "...".to_owned());
+        let value = value.to_str().ok_or_else(|| {
+            Error::raw(
+                ErrorKind::InvalidUtf8,
+                format!("invalid UTF-8 in the value of '{}'\n", flag),
+            )
+            .with_cmd(cmd)
+        })?;
+
+        value.parse().map_err(|error| {
+            Error::raw(
+                ErrorKind::ValueValidation,
+                format!(
+                    "invalid value '{}' for '{}': {}\n\n  for example: '{}'\n",
+                    value, flag, error, EXAMPLE
+                ),
+            )
+            .with_cmd(cmd)
+        })
+    }
+}
+
+impl ValueParserFactory for Policy<Backoff> {
+    type Parser = PolicyParser;
+
+    fn value_parser() -> PolicyParser {
+        PolicyParser
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::{error::ErrorKind, value_parser, Arg, Command};
+
+    use crate::{delay::Backoff, Policy};
+
+    fn command() -> Command {
+        Command::new("app").arg(
+            Arg::new("retry")
+                .long("retry")
+                .value_parser(value_parser!(Policy<Backoff>)),
+        )
+    }
+
+    #[test]
+    fn parses_flags_into_policies() {
+        let matches = command()
+            .try_get_matches_from(["app", "--retry", "fibonacci(10ms, attempts=4)"])
+            .unwrap();
+        let policy = matches.get_one::<Policy<Backoff>>("retry").unwrap();
+
+        assert_eq!(policy.to_string(), "fibonacci(10ms), max 4 attempts");
+    }
+
+    #[test]
+    fn reports_invalid_flags() {
+        let error = command()
+            .try_get_matches_from(["app", "--retry", "fixed(2s, tries=3)"])
+            .unwrap_err();
+
+        assert_eq!(error.kind(), ErrorKind::ValueValidation);
+        assert!(error.to_string().contains(
+            "invalid value 'fixed(2s, tries=3)' for '--retry <retry>': \
+             invalid retry policy: unknown setting `tries`"
+        ));
+    }
+}
@@ -0,0 +1,244 @@
+//! Errors classified as transient or permanent where they happen, and errors that carry a hint
+//! about when to retry them.
+//!
+//! # Examples
+//!
+//! ```rust
+//! # use std::time::Duration;
+//! # use retry::delay::Fixed;
+//! use retry::{Classified, Permanent, Policy, Transient};
+//!
+//! fn fetch(attempt: u32) -> Result<u32, Classified<&'static str>> {
+//!     match attempt {
+//!         0 => Err(Transient::with_retry_after("throttled", Duration::from_millis(5)).into()),
+//!         1 => Err(Transient::new("unavailable").into()),
+//!         2 => Ok(2),
+//!         _ => Err(Permanent("gone").into()),
+//!     }
+//! }
+//!
+//! let policy = Policy::new(Fixed::from_millis(1).take(5));
+//! let mut attempt = 0;
+//!
+//! let value = policy.retry_classified(|| {
+//!     let result = fetch(attempt);
+//!     attempt += 1;
+//!     result
+//! });
+//!
+//! assert_eq!(value, Ok(2));
+//! ```
+
+use std::time::Duration;
+
+use crate::OperationResult;
+
+/// An error that is worth retrying, optionally after a specific delay.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Transient<E> {
+    error: E,
+    retry_after: Option<Duration>,
+}
+
+impl<E> Transient<E> {
+    /// Wrap an error that is retried after the next delay of the strategy.
+    pub fn new(error: E) -> Self {
+        Transient {
+            error,
+            retry_after: None,
+        }
+    }
+
+    /// Wrap an error that is retried after `delay` instead of the next delay of the strategy.
+    pub fn with_retry_after(error: E, delay: Duration) -> Self {
+        Transient {
+            error,
+            retry_after: Some(delay),
+        }
+    }
+
+    /// The delay requested before the next attempt, if any.
+    pub fn retry_after(&self) -> Option<Duration> {
+        self.retry_after
+    }
+
+    /// Consume the wrapper, returning the error.
+    pub fn into_inner(self) -> E {
+        self.error
+    }
+}
+
+/// An error that is not worth retrying.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Permanent<E>(pub E);
+
+/// An error classified as either `Transient` or `Permanent`, as returned by operations passed to
+/// `Policy::retry_classified`.
+///
+/// Both wrappers convert into `Classified`, so an operation can classify errors with `?`, for
+/// example `connect().map_err(Transient::new)?`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Classified<E> {
+    /// The error is retried.
+    Transient(Transient<E>),
+    /// The error is returned immediately.
+    Permanent(Permanent<E>),
+}
+
+impl<E> From<Transient<E>> for Classified<E> {
+    fn from(transient: Transient<E>) -> Self {
+        Classified::Transient(transient)
+    }
+}
+
+impl<E> From<Permanent<E>> for Classified<E> {
+    fn from(permanent: Permanent<E>) -> Self {
+        Classified::Permanent(permanent)
+    }
+}
+
+/// An error that may know how long to wait before retrying, for example from a `Retry-After`
+/// header or a server's pushback.
+///
+/// Errors of operations passed to `Policy::retry_hinted` are asked for their hint whenever they
+/// are retried. A hint replaces the next delay of the strategy, but it is still bounded by the
+/// policy's maximum delay, if any.
+pub trait RetryAfterHint {
+    /// The delay to wait before the next attempt, or `None` to use the strategy's delay.
+    fn retry_after(&self) -> Option<Duration>;
+}
+
+impl<E> RetryAfterHint for Transient<E> {
+    fn retry_after(&self) -> Option<Duration> {
+        self.retry_after
+    }
+}
+
+impl<E> RetryAfterHint for Classified<E> {
+    fn retry_after(&self) -> Option<Duration> {
+        match *self {
+            Classified::Transient(ref transient) => transient.retry_after,
+            Classified::Permanent(_) => None,
+        }
+    }
+}
+
+/// The outcome of one attempt, as seen by the policy executors.
+pub(crate) enum Outcome<T, E> {
+    Ok(T),
+    Retry(E, Option<Duration>),
+    Err(E),
+}
+
+impl<T, E> Outcome<T, E>
+where
+    E: RetryAfterHint,
+{
+    /// The outcome of an attempt whose retried errors give their own hint.
+    pub(crate) fn hinted(result: OperationResult<T, E>) -> Self {
+        match result {
+            OperationResult::Retry(error) => {
+                let retry_after = error.retry_after();
+                Outcome::Retry(error, retry_after)
+            }
+            result => result.into(),
+        }
+    }
+}
+
+impl<T, E> From<OperationResult<T, E>> for Outcome<T, E> {
+    fn from(result: OperationResult<T, E>) -> Self {
+        match result {
+            OperationResult::Ok(value) => Outcome::Ok(value),
+            OperationResult::Retry(error) => Outcome::Retry(error, None),
+            OperationResult::Err(error) => Outcome::Err(error),
+        }
+    }
+}
+
+impl<T, E> From<Result<T, Classified<E>>> for Outcome<T, E> {
+    fn from(result: Result<T, Classified<E>>) -> Self {
+        match result {
+            Ok(value) => Outcome::Ok(value),
+            Err(Classified::Transient(transient)) => {
+                Outcome::Retry(transient.error, transient.retry_after)
+            }
+            Err(Classified::Permanent(Permanent(error))) => Outcome::Err(error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::{Classified, Permanent, RetryAfterHint, Transient};
+    use crate::{delay::Fixed, Error, Policy};
+
+    #[test]
+    fn permanent_errors_are_not_retried() {
+        let mut attempts = 0;
+
+        let result = Policy::new(Fixed::from_millis(1)).retry_classified(|| {
+            attempts += 1;
+            Err::<(), Classified<_>>(Permanent("gone").into())
+        });
+
+        assert_eq!(
+            result,
+            Err(Error::Operation {
+                error: "gone",
+                total_delay: Duration::default(),
+                tries: 1,
+            })
+        );
+    }
+
+    #[test]
+    fn transient_errors_use_their_retry_after() {
+        let start = Instant::now();
+
+        let result = Policy::new(Fixed::from_millis(1000).take(1)).retry_classified(|| {
+            Err::<(), Classified<_>>(
+                Transient::with_retry_after("throttled", Duration::from_millis(1)).into(),
+            )
+        });
+
+        assert_eq!(
+            result,
+            Err(Error::Operation {
+                error: "throttled",
+                total_delay: Duration::from_millis(1),
+                tries: 2,
+            })
+        );
+        assert!(start.elapsed() < Duration::from_millis(1000));
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct Throttled(u64);
+
+    impl RetryAfterHint for Throttled {
+        fn retry_after(&self) -> Option<Duration> {
+            Some(Duration::from_millis(self.0))
+        }
+    }
+
+    #[test]
+    fn hints_replace_strategy_delays_up_to_the_max_delay() {
+        let mut hints = vec![1, 5000].into_iter();
+
+        let result = Policy::new(Fixed::from_millis(1000).take(2))
+            .with_max_delay(Duration::from_millis(2))
+            .retry_hinted(|| Err::<(), _>(Throttled(hints.next().unwrap_or(0))));
+
+        assert_eq!(
+            result,
+            Err(Error::Operation {
+                error: Throttled(0),
+                total_delay: Duration::from_millis(3),
+                tries: 3,
+            })
+        );
+    }
+}
@@ -0,0 +1,478 @@
+//! Sources of the current time for policies, and ways to wait.
+//!
+//! A policy measures the latency of attempts and the time elapsed since a call started, for its
+//! listeners, watchdog and give-up summaries. It uses the system clock unless another `Clock` is
+//! given with `Policy::with_clock`, such as a `MockClock` that tests advance by hand:
+//!
+//! ```rust
+//! # use std::time::Duration;
+//! # use retry::delay::NoDelay;
+//! use retry::{clock::MockClock, Policy};
+//!
+//! let clock = MockClock::new();
+//! let policy = Policy::new(NoDelay)
+//!     .with_clock(clock.clone())
+//!     .with_watchdog(Duration::from_secs(60), |slow| {
+//!         assert_eq!(slow.elapsed(), Duration::from_secs(90));
+//!     });
+//!
+//! let value = policy.retry(|| {
+//!     clock.advance(Duration::from_secs(90));
+//!     if clock.elapsed() < Duration::from_secs(180) {
+//!         Err("not yet")
+//!     } else {
+//!         Ok("done")
+//!     }
+//! });
+//!
+//! assert_eq!(value, Ok("done"));
+//! ```
+//!
+//! With the `"tokio"` feature, asynchronous calls read Tokio's clock by default, so they follow
+//! `tokio::time::pause` in tests.
+//!
+//! The default clocks are monotonic: they never go back, but on some platforms, such as Linux,
+//! they stand still while the machine is suspended, so a laptop that sleeps through a delay does
+//! not count the time it slept. A policy whose calls should follow the calendar instead, such as
+//! one that schedules jobs hours apart, can read the wall clock with
+//! `Policy::with_time_source(TimeSource::WallClock)`. The `WallClock` keeps counting while the
+//! machine is suspended, and guards against the system time being set back, so the time it
+//! measures never goes back either:
+//!
+//! ```rust
+//! # use retry::delay::NoDelay;
+//! use retry::{clock::TimeSource, Policy};
+//!
+//! let policy = Policy::new(NoDelay.take(1)).with_time_source(TimeSource::WallClock);
+//! assert_eq!(policy.time_source(), Some(TimeSource::WallClock));
+//! ```
+//!
+//! Sleeping threads and timers wait the monotonic time whatever the time source, so a delay that a
+//! suspension interrupts goes on for the rest of its time once the machine wakes up, unless the
+//! policy checks for suspensions with `Policy::with_suspend_detection`.
+//!
+//! Synchronous calls wait between attempts with `thread::sleep`, unless another `Sleeper` is given
+//! with `Policy::with_sleeper`. `thread::sleep` often overshoots by a millisecond or more; a
+//! `SpinSleeper` waits sub-millisecond delays precisely, at the cost of busy-waiting:
+//!
+//! ```rust
+//! # use std::time::Duration;
+//! # use retry::delay::Fixed;
+//! use retry::{clock::SpinSleeper, Policy};
+//!
+//! let policy = Policy::new(Fixed::from_duration(Duration::from_micros(50)).take(3))
+//!     .with_sleeper(SpinSleeper::new());
+//! # let _ = policy.retry(|| Err::<(), _>("busy"));
+//! ```
+
+use std::{
+    convert::TryFrom,
+    fmt::Debug,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime},
+};
+
+use crate::sync::{AtomicU64, Ordering};
+
+/// A source of the current time.
+pub trait Clock: Debug + Send + Sync {
+    /// The current time.
+    fn now(&self) -> Instant;
+}
+
+impl<C> Clock for Arc<C>
+where
+    C: Clock + ?Sized,
+{
+    fn now(&self) -> Instant {
+        (**self).now()
+    }
+}
+
+/// The system clock, as read by `Instant::now`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// The time a policy measures, when it is not given a clock of its own.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TimeSource {
+    /// The monotonic clock of the executor: the `SystemClock` for synchronous calls, and Tokio's
+    /// clock for asynchronous ones with the `"tokio"` feature. It is the default.
+    ///
+    /// The time never jumps, which suits budgets and timeouts, but on some platforms it does not
+    /// pass while the machine is suspended.
+    Monotonic,
+    /// The system time, read through a `WallClock`. It passes while the machine is suspended, and
+    /// follows the calendar, which suits schedules, but jumps whenever the system time is set.
+    WallClock,
+}
+
+/// The system time, as read by `SystemTime::now`, converted to instants so that policies can
+/// measure it.
+///
+/// Unlike the monotonic clocks, the system time passes while the machine is suspended, but it can
+/// also be set back, by hand or by clock synchronization. The time this clock reads never goes
+/// back: once the system time has been set back, the clock stands still until the system time
+/// catches up with the latest time it read. A system time set forward can not be told apart from
+/// a suspended machine, so it is counted as time passing. Clones share the latest time.
+#[derive(Clone, Debug)]
+pub struct WallClock {
+    instant: Instant,
+    system_time: SystemTime,
+    latest: Arc<AtomicU64>,
+}
+
+impl WallClock {
+    /// Create a clock that starts at the current system time.
+    pub fn new() -> Self {
+        WallClock {
+            instant: Instant::now(),
+            system_time: SystemTime::now(),
+            latest: Arc::default(),
+        }
+    }
+
+    /// The current system time, never earlier than a time this clock read before.
+    pub fn system_time(&self) -> SystemTime {
+        self.system_time + self.elapsed_at(SystemTime::now())
+    }
+
+    /// The time elapsed since the clock was created, if the system time is `now`, and never less
+    /// than the time elapsed the last time the clock was read.
+    fn elapsed_at(&self, now: SystemTime) -> Duration {
+        let elapsed = now.duration_since(self.system_time).unwrap_or_default();
+        let nanos = u64::try_from(elapsed.as_nanos()).unwrap_or(u64::MAX);
+        let latest = self.latest.fetch_max(nanos, Ordering::Relaxed);
+        Duration::from_nanos(latest.max(nanos))
+    }
+}
+
+impl Default for WallClock {
+    fn default() -> Self {
+        WallClock::new()
+    }
+}
+
+impl Clock for WallClock {
+    fn now(&self) -> Instant {
+        let elapsed = self.elapsed_at(SystemTime::now());
+        self.instant.checked_add(elapsed).unwrap_or(self.instant)
+    }
+}
+
+/// Tokio's clock, as read by `tokio::time::Instant::now`. It stands still while the clock is
+/// paused with `tokio::time::pause`, and moves forward as sleeps are auto-advanced, so the time
+/// measured by asynchronous calls matches the time they waited.
+///
+/// Asynchronous calls use this clock unless the policy has another one. This clock is enabled
+/// with the `"tokio"` feature.
+#[cfg(feature = "tokio")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TokioClock;
+
+#[cfg(feature = "tokio")]
+impl Clock for TokioClock {
+    fn now(&self) -> Instant {
+        tokio::time::Instant::now().into_std()
+    }
+}
+
+/// A way to block the current thread while waiting between synchronous attempts.
+pub trait Sleeper: Debug + Send + Sync {
+    /// Block for `duration`.
+    fn sleep(&self, duration: Duration);
+}
+
+impl<S> Sleeper for Arc<S>
+where
+    S: Sleeper + ?Sized,
+{
+    fn sleep(&self, duration: Duration) {
+        (**self).sleep(duration)
+    }
+}
+
+/// Blocks with `thread::sleep`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ThreadSleeper;
+
+impl Sleeper for ThreadSleeper {
+    fn sleep(&self, duration: Duration) {
+        std::thread::sleep(duration)
+    }
+}
+
+/// Waits with `thread::sleep` for all but the end of each delay, then spins until the delay is
+/// over, so that delays end on time instead of whenever the thread is woken up.
+///
+/// Delays no longer than the spin threshold, 1ms by default, are spun entirely. Spinning keeps a
+/// core busy, so the threshold should cover the usual oversleep of the platform and no more.
+#[derive(Clone, Copy, Debug)]
+pub struct SpinSleeper {
+    spin_threshold: Duration,
+}
+
+impl SpinSleeper {
+    /// Create a sleeper that spins for the last millisecond of each delay.
+    pub const fn new() -> Self {
+        SpinSleeper {
+            spin_threshold: Duration::from_millis(1),
+        }
+    }
+
+    /// Spin for the last `spin_threshold` of each delay instead.
+    pub const fn with_spin_threshold(spin_threshold: Duration) -> Self {
+        SpinSleeper { spin_threshold }
+    }
+}
+
+impl Default for SpinSleeper {
+    fn default() -> Self {
+        SpinSleeper::new()
+    }
+}
+
+impl Sleeper for SpinSleeper {
+    fn sleep(&self, duration: Duration) {
+        // A delay too long to add to an `Instant`, such as `Duration::MAX`, cannot end with a spin;
+        // sleep through it like `ThreadSleeper` does.
+        let deadline = match Instant::now().checked_add(duration) {
+            Some(deadline) => deadline,
+            None => return std::thread::sleep(duration),
+        };
+        if let Some(sleep) = duration.checked_sub(self.spin_threshold) {
+            std::thread::sleep(sleep);
+        }
+        while Instant::now() < deadline {
+            std::hint::spin_loop();
+        }
+    }
+}
+
+/// A clock that only moves forward when told to. Clones share the same time.
+#[derive(Clone, Debug)]
+pub struct MockClock {
+    start: Instant,
+    elapsed: Arc<Mutex<Duration>>,
+}
+
+impl MockClock {
+    /// Create a clock stopped at the current time.
+    pub fn new() -> Self {
+        MockClock {
+            start: Instant::now(),
+            elapsed: Arc::default(),
+        }
+    }
+
+    /// Move the clock forward by `duration`.
+    pub fn advance(&self, duration: Duration) {
+        *self.lock() += duration;
+    }
+
+    /// The time the clock has been moved forward by since it was created.
+    pub fn elapsed(&self) -> Duration {
+        *self.lock()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Duration> {
+        self.elapsed
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl Default for MockClock {
+    fn default() -> Self {
+        MockClock::new()
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> Instant {
+        self.start + self.elapsed()
+    }
+}
+
+/// `time + delay`, or the latest time that can be represented if the sum overflows, so that a
+/// delay as long as `Duration::MAX` still ends after every shorter one instead of wrapping to
+/// `time`. `checked_add` is `Instant::checked_add` or `SystemTime::checked_add`.
+pub(crate) fn saturating_add<T, F>(time: T, delay: Duration, checked_add: F) -> T
+where
+    T: Copy,
+    F: Fn(&T, Duration) -> Option<T>,
+{
+    let (mut sum, mut left, mut step) = (time, delay, delay);
+    while !left.is_zero() && !step.is_zero() {
+        step = step.min(left);
+        match checked_add(&sum, step) {
+            Some(next) => {
+                sum = next;
+                left -= step;
+            }
+            None => step /= 2,
+        }
+    }
+    sum
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use super::{Clock, MockClock, Sleeper, SpinSleeper, TimeSource, WallClock};
+    use crate::{
+        delay::NoDelay,
+        listener::{AttemptOutcome, GiveUpSummary, RetryListener},
+        CorrelationId, Policy,
+    };
+
+    #[derive(Default)]
+    struct Latencies {
+        attempts: Mutex<Vec<Duration>>,
+        elapsed: Mutex<Option<Duration>>,
+    }
+
+    impl RetryListener for Latencies {
+        fn on_attempt_end(
+            &self,
+            _: &CorrelationId,
+            _: u64,
+            _: AttemptOutcome<'_>,
+            latency: Duration,
+        ) {
+            self.attempts.lock().unwrap().push(latency);
+        }
+
+        fn on_give_up(&self, summary: &GiveUpSummary) {
+            *self.elapsed.lock().unwrap() = Some(summary.elapsed());
+        }
+    }
+
+    #[test]
+    fn mock_clocks_only_move_when_advanced() {
+        let clock = MockClock::new();
+        let now = clock.now();
+
+        clock.clone().advance(Duration::from_secs(1));
+
+        assert_eq!(clock.now() - now, Duration::from_secs(1));
+        assert_eq!(clock.elapsed(), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn wall_clocks_never_go_back() {
+        let clock = WallClock::new();
+        let start = clock.system_time;
+        let elapsed = |secs| clock.elapsed_at(start + Duration::from_secs(secs));
+
+        assert_eq!(elapsed(10), Duration::from_secs(10));
+        assert_eq!(elapsed(5), Duration::from_secs(10), "set back");
+        assert_eq!(
+            clock.elapsed_at(start - Duration::from_secs(3600)),
+            Duration::from_secs(10),
+            "set back before the clock was created"
+        );
+        assert_eq!(elapsed(12), Duration::from_secs(12));
+        assert!(clock.clone().now() >= clock.instant + Duration::from_secs(12));
+    }
+
+    #[test]
+    fn policies_expose_their_time_source() {
+        let policy = Policy::new(NoDelay);
+        assert_eq!(policy.time_source(), Some(TimeSource::Monotonic));
+
+        let policy = policy.with_time_source(TimeSource::WallClock);
+        assert_eq!(policy.time_source(), Some(TimeSource::WallClock));
+        assert_eq!(policy.clone().retry(|| Ok::<_, ()>(1)), Ok(1));
+
+        let policy = policy.with_clock(MockClock::new());
+        assert_eq!(policy.time_source(), None);
+        let policy = policy.with_time_source(TimeSource::Monotonic);
+        assert_eq!(policy.time_source(), Some(TimeSource::Monotonic));
+    }
+
+    #[test]
+    fn spin_sleepers_wait_at_least_the_delay() {
+        for &(sleeper, delay) in &[
+            (SpinSleeper::new(), Duration::from_micros(200)),
+            (
+                SpinSleeper::with_spin_threshold(Duration::from_micros(100)),
+                Duration::from_millis(2),
+            ),
+        ] {
+            let started = std::time::Instant::now();
+            sleeper.sleep(delay);
+            assert!(started.elapsed() >= delay);
+        }
+
+        // A delay past any `Instant` is slept through instead of panicking.
+        let sleeping = std::thread::spawn(|| SpinSleeper::new().sleep(Duration::MAX));
+        std::thread::sleep(Duration::from_millis(50));
+        assert!(!sleeping.is_finished());
+    }
+
+    #[test]
+    fn policies_measure_time_with_their_clock() {
+        let clock = MockClock::new();
+        let latencies = Arc::new(Latencies::default());
+        let policy = Policy::new(NoDelay.take(2))
+            .with_clock(clock.clone())
+            .with_listener(Arc::clone(&latencies));
+        let mut attempt = 0;
+
+        let _ = policy.retry(|| {
+            attempt += 1;
+            clock.advance(Duration::from_millis(10 * attempt));
+            Err::<(), _>("down")
+        });
+
+        assert_eq!(
+            *latencies.attempts.lock().unwrap(),
+            [10, 20, 30]
+                .iter()
+                .map(|&millis| Duration::from_millis(millis))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            *latencies.elapsed.lock().unwrap(),
+            Some(Duration::from_millis(60))
+        );
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test(start_paused = true)]
+    async fn asynchronous_calls_follow_the_paused_tokio_clock() {
+        let started = std::time::Instant::now();
+        let latencies = Arc::new(Latencies::default());
+        let policy = Policy::new(crate::delay::Fixed::from_millis(60_000).take(2))
+            .with_attempt_timeout(Duration::from_secs(10))
+            .with_listener(Arc::clone(&latencies));
+
+        let _ = policy
+            .retry_async(|| async {
+                tokio::time::sleep(Duration::from_secs(1)).await;
+                Err::<(), _>("down")
+            })
+            .await;
+
+        assert_eq!(
+            *latencies.attempts.lock().unwrap(),
+            vec![Duration::from_secs(1); 3]
+        );
+        assert_eq!(
+            *latencies.elapsed.lock().unwrap(),
+            Some(Duration::from_secs(123))
+        );
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,23 @@
+//! Adapters that mirror the APIs of other retry crates, backed by the strategies of this crate, so
+//! that code written against them can be migrated one call at a time.
+//!
+//! `compat::backoff` mirrors `backoff::retry` and its `Error`, and `compat::tokio_retry`, enabled
+//! with the `"asynchronous"` feature flag, mirrors `tokio_retry::Retry` and `RetryIf`. Changing
+//! the imports of a call is usually enough, along with spelling the strategy with the strategies of
+//! this crate:
+//!
+//! ```rust
+//! # #[derive(Debug)] struct Refused;
+//! # fn connect() -> Result<(), Refused> { Ok(()) }
+//! // use backoff::{retry, Error, ExponentialBackoff};
+//! use retry::{compat::backoff::{retry, Error}, delay::Exponential};
+//!
+//! let result = retry(Exponential::from_millis(10).take(3), || {
+//!     connect().map_err(Error::transient)
+//! });
+//! assert!(result.is_ok());
+//! ```
+
+pub mod backoff;
+#[cfg(feature = "asynchronous")]
+pub mod tokio_retry;
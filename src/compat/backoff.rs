@@ -0,0 +1,170 @@
+//! The API of the `backoff` crate's `retry`, which retries an operation whose errors say whether
+//! they are permanent or transient.
+//!
+//! Unlike `backoff`, the delays come from any strategy of this crate rather than from a `Backoff`,
+//! and there is no `retry_notify`: listeners attached to a `Policy` observe the retries instead.
+
+use std::{cell::Cell, error::Error as StdError, fmt, time::Duration};
+
+use crate::OperationResult;
+
+/// An error of an operation retried with `retry`, telling whether to retry it, as
+/// `backoff::Error` does.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Error<E> {
+    /// The error is returned immediately.
+    Permanent(E),
+    /// The error is retried, after `retry_after` if given, instead of the strategy's next delay.
+    Transient {
+        /// The error of the operation.
+        err: E,
+        /// The delay to wait before the next attempt, if not the strategy's.
+        retry_after: Option<Duration>,
+    },
+}
+
+impl<E> Error<E> {
+    /// An error that is returned immediately.
+    pub fn permanent(err: E) -> Self {
+        Error::Permanent(err)
+    }
+
+    /// An error that is retried after the strategy's next delay.
+    pub fn transient(err: E) -> Self {
+        Error::Transient {
+            err,
+            retry_after: None,
+        }
+    }
+
+    /// An error that is retried after `duration` instead of the strategy's next delay.
+    pub fn retry_after(err: E, duration: Duration) -> Self {
+        Error::Transient {
+            err,
+            retry_after: Some(duration),
+        }
+    }
+}
+
+/// Errors converted with `?` are transient, as in `backoff`.
+impl<E> From<E> for Error<E> {
+    fn from(err: E) -> Self {
+        Error::transient(err)
+    }
+}
+
+impl<E> fmt::Display for Error<E>
+where
+    E: fmt::Display,
+{
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Error::Permanent(ref err) | Error::Transient { ref err, .. } => err.fmt(formatter),
+        }
+    }
+}
+
+impl<E> StdError for Error<E>
+where
+    E: StdError + 'static,
+{
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match *self {
+            Error::Permanent(ref err) | Error::Transient { ref err, .. } => Some(err),
+        }
+    }
+}
+
+/// The delays of a strategy, each replaced by the delay asked for by the error before it, if any.
+struct Hinted<'a, I> {
+    delays: I,
+    retry_after: &'a Cell<Option<Duration>>,
+}
+
+impl<I> Iterator for Hinted<'_, I>
+where
+    I: Iterator<Item = Duration>,
+{
+    type Item = Duration;
+
+    fn next(&mut self) -> Option<Duration> {
+        let delay = self.delays.next()?;
+        Some(self.retry_after.take().unwrap_or(delay))
+    }
+}
+
+/// Retry `op` until it succeeds, fails with a permanent error or the strategy ends, as
+/// `backoff::retry` does, returning the error of the last attempt.
+///
+/// An error that asks to be retried after a delay is, and the strategy's next delay is skipped,
+/// so that the strategy still bounds the number of attempts. Like `backoff`, this does not check
+/// that the strategy ends.
+pub fn retry<I, F, T, E>(strategy: I, mut op: F) -> Result<T, Error<E>>
+where
+    I: IntoIterator<Item = Duration>,
+    F: FnMut() -> Result<T, Error<E>>,
+{
+    let retry_after = Cell::new(None);
+    let delays = Hinted {
+        delays: strategy.into_iter(),
+        retry_after: &retry_after,
+    };
+    crate::retry_forever(delays, || match op() {
+        Ok(value) => OperationResult::Ok(value),
+        Err(error @ Error::Permanent(_)) => OperationResult::Err(error),
+        Err(error) => {
+            if let Error::Transient {
+                retry_after: Some(delay),
+                ..
+            } = error
+            {
+                retry_after.set(Some(delay));
+            }
+            OperationResult::Retry(error)
+        }
+    })
+    .map_err(|error| {
+        error
+            .into_last_error()
+            .expect("a call without a policy ends with the error of its last attempt")
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::{retry, Error};
+    use crate::delay::Fixed;
+
+    #[test]
+    fn retries_transient_errors_only() {
+        let mut attempts = 0;
+        let result = retry(Fixed::from_millis(1).take(5), || {
+            attempts += 1;
+            match attempts {
+                1 => Err(Error::transient("busy"))?,
+                2 => Err("reset")?,
+                _ => Err(Error::permanent("denied")),
+            }
+        });
+        assert_eq!(result, Err::<(), _>(Error::Permanent("denied")));
+        assert_eq!(attempts, 3);
+
+        let result = retry(Fixed::from_millis(1).take(1), || {
+            Err::<(), _>("busy".into())
+        });
+        assert_eq!(result, Err(Error::transient("busy")));
+    }
+
+    #[test]
+    fn waits_the_delay_the_error_asks_for() {
+        let start = Instant::now();
+        let result = retry(Fixed::from_millis(1).take(1), || {
+            Err::<(), _>(Error::retry_after("throttled", Duration::from_millis(30)))
+        });
+
+        assert!(start.elapsed() >= Duration::from_millis(30));
+        assert_eq!(result.unwrap_err().to_string(), "throttled");
+    }
+}
@@ -0,0 +1,178 @@
+//! The API of the `tokio-retry` crate, which retries asynchronous actions with the delays of a
+//! strategy.
+//!
+//! `Retry` and `RetryIf` are not futures themselves here: their `spawn` functions are `async`,
+//! and their futures, which are `Send` whenever the action is, so they can be awaited or spawned
+//! the same way. The strategies of `tokio_retry::strategy` are found in `strategy` under the same
+//! names.
+//!
+//! ```rust
+//! # futures::executor::block_on(async {
+//! // use tokio_retry::{strategy::{jitter, ExponentialBackoff}, Retry};
+//! use retry::compat::tokio_retry::{strategy::{jitter, ExponentialBackoff}, Retry};
+//!
+//! let strategy = ExponentialBackoff::from_millis(10).map(jitter).take(3);
+//! let result = Retry::spawn(strategy, || async { Ok::<_, ()>(42) }).await;
+//! assert_eq!(result, Ok(42));
+//! # });
+//! ```
+
+use std::{future::Future, sync::Mutex, time::Duration};
+
+use crate::{asynchronous, OperationResult};
+
+/// The strategies of `tokio_retry::strategy`.
+pub mod strategy {
+    #[cfg(feature = "random")]
+    pub use crate::delay::jitter;
+    pub use crate::delay::{
+        Exponential as ExponentialBackoff, Fibonacci as FibonacciBackoff, Fixed as FixedInterval,
+    };
+}
+
+/// An asynchronous action that can be run again, as `tokio_retry::Action`.
+pub trait Action {
+    /// The future of a run of the action.
+    type Future: Future<Output = Result<Self::Item, Self::Error>>;
+    /// The value of a successful run.
+    type Item;
+    /// The error of a failed run.
+    type Error;
+
+    /// Run the action once.
+    fn run(&mut self) -> Self::Future;
+}
+
+impl<R, E, T, F> Action for F
+where
+    T: Future<Output = Result<R, E>>,
+    F: FnMut() -> T,
+{
+    type Future = T;
+    type Item = R;
+    type Error = E;
+
+    fn run(&mut self) -> T {
+        self()
+    }
+}
+
+/// Whether an error of an action is retried by `RetryIf`, as `tokio_retry::Condition`.
+pub trait Condition<E> {
+    /// Whether to retry after `error`.
+    fn should_retry(&mut self, error: &E) -> bool;
+}
+
+impl<E, F> Condition<E> for F
+where
+    F: FnMut(&E) -> bool,
+{
+    fn should_retry(&mut self, error: &E) -> bool {
+        self(error)
+    }
+}
+
+/// Retries every error of an action, as `tokio_retry::Retry`.
+#[derive(Debug)]
+pub enum Retry {}
+
+impl Retry {
+    /// Run `action` until it succeeds or `strategy` ends, returning the error of the last run.
+    pub async fn spawn<T, A>(strategy: T, mut action: A) -> Result<A::Item, A::Error>
+    where
+        T: IntoIterator<Item = Duration>,
+        A: Action,
+    {
+        asynchronous::retry_forever(strategy, || action.run())
+            .await
+            .map_err(last_error)
+    }
+}
+
+/// Retries the errors of an action that a condition accepts, as `tokio_retry::RetryIf`.
+#[derive(Debug)]
+pub enum RetryIf {}
+
+impl RetryIf {
+    /// Run `action` until it succeeds, `strategy` ends or `condition` rejects its error, returning
+    /// the error of the last run.
+    pub async fn spawn<T, A, C>(
+        strategy: T,
+        mut action: A,
+        condition: C,
+    ) -> Result<A::Item, A::Error>
+    where
+        T: IntoIterator<Item = Duration>,
+        A: Action,
+        C: Condition<A::Error>,
+    {
+        let condition = Mutex::new(condition);
+        let condition = &condition;
+        asynchronous::retry_forever(strategy, || {
+            let run = action.run();
+            async move {
+                match run.await {
+                    Ok(value) => OperationResult::Ok(value),
+                    Err(error) => {
+                        let mut condition = condition
+                            .lock()
+                            .unwrap_or_else(|poisoned| poisoned.into_inner());
+                        if condition.should_retry(&error) {
+                            OperationResult::Retry(error)
+                        } else {
+                            OperationResult::Err(error)
+                        }
+                    }
+                }
+            }
+        })
+        .await
+        .map_err(last_error)
+    }
+}
+
+fn last_error<E>(error: crate::Error<E>) -> E {
+    error
+        .into_last_error()
+        .expect("a call without a policy ends with the error of its last attempt")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{strategy::FixedInterval, Retry, RetryIf};
+
+    fn assert_send<T: Send>(value: T) -> T {
+        value
+    }
+
+    #[tokio::test]
+    async fn retries_until_the_strategy_ends() {
+        let mut attempts = 0;
+        let result = assert_send(Retry::spawn(FixedInterval::from_millis(1).take(2), || {
+            attempts += 1;
+            async { Err::<(), _>("down") }
+        }))
+        .await;
+
+        assert_eq!(result, Err("down"));
+        assert_eq!(attempts, 3);
+    }
+
+    #[tokio::test]
+    async fn stops_on_errors_the_condition_rejects() {
+        let mut attempts = 0;
+        let result = assert_send(RetryIf::spawn(
+            FixedInterval::from_millis(1).take(5),
+            || {
+                attempts += 1;
+                let error = if attempts < 2 { "busy" } else { "denied" };
+                async move { Err::<(), _>(error) }
+            },
+            |error: &&str| *error == "busy",
+        ))
+        .await;
+
+        assert_eq!(result, Err("denied"));
+        assert_eq!(attempts, 2);
+    }
+}
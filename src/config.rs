@@ -0,0 +1,512 @@
+//! Retry policies described by plain settings, for example loaded from a configuration file.
+//!
+//! With the `"serde"` feature, `RetryPolicyConfig` can be deserialized from any format supported
+//! by serde, such as TOML, JSON or YAML. Durations are written as strings with a unit, like
+//! `"100ms"`, `"1.5s"` or `"2m"`, or as a number of milliseconds. Every setting is optional.
+//!
+//! ```rust
+//! # #[cfg(feature = "serde")]
+//! # {
+//! use retry::config::RetryPolicyConfig;
+//!
+//! let config: RetryPolicyConfig = serde_json::from_str(
+//!     r#"{ "strategy": "exponential", "base": "10ms", "cap": "1s", "max_attempts": 3 }"#,
+//! )
+//! .unwrap();
+//! let policy = config.build().unwrap();
+//!
+//! assert_eq!(policy.max_attempts(), Some(3));
+//! assert_eq!(policy.retry(|| Err::<(), _>("down")).unwrap_err().tries(), 3);
+//! # }
+//! ```
+//!
+//! Settings can also be written on one line, which suits environment variables and command line
+//! flags. The schedule kind comes first, optionally followed by the base delay, the factor and
+//! the other settings in parentheses:
+//!
+//! ```rust
+//! # use retry::{delay::Backoff, Policy};
+//! let policy: Policy<Backoff> = "exponential(100ms, x2, cap=30s, attempts=5, jitter=full)"
+//!     .parse()
+//!     .unwrap();
+//!
+//! assert_eq!(policy.max_attempts(), Some(5));
+//! ```
+//!
+//! `Policy::from_env` reads such settings from an environment variable, so that operators can
+//! tune retries at deploy time. With the `"config"` feature, `RetryPolicyConfig::load` and
+//! `RetryPolicyConfig::from_config` read them from the layered sources of the `config` crate.
+
+use std::{convert::TryFrom, env, error::Error as StdError, fmt, str::FromStr, time::Duration};
+
+use crate::{
+    delay::{self, Backoff, BackoffKind, Jitter},
+    Policy,
+};
+
+#[cfg(feature = "config")]
+mod layered;
+
+/// The settings of a retry policy.
+///
+/// The defaults are an exponential schedule starting at 100ms and doubling every time, without
+/// cap, jitter, budget or maximum number of attempts.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize),
+    serde(default, deny_unknown_fields)
+)]
+pub struct RetryPolicyConfig {
+    /// The kind of schedule.
+    pub strategy: BackoffKind,
+    /// The first delay, and every delay of a fixed schedule.
+    #[cfg_attr(feature = "serde", serde(deserialize_with = "de::duration"))]
+    pub base: Duration,
+    /// The factor by which an exponential schedule grows.
+    pub factor: f64,
+    /// The maximum delay between attempts.
+    #[cfg_attr(feature = "serde", serde(deserialize_with = "de::optional_duration"))]
+    pub cap: Option<Duration>,
+    /// The maximum number of attempts, including the first one.
+    pub max_attempts: Option<u64>,
+    /// The jitter applied to each delay.
+    pub jitter: Jitter,
+    /// The maximum total delay of one call.
+    #[cfg_attr(feature = "serde", serde(deserialize_with = "de::optional_duration"))]
+    pub budget: Option<Duration>,
+}
+
+impl RetryPolicyConfig {
+    /// Build the policy described by these settings.
+    ///
+    /// Fails if the settings describe a degenerate policy, such as one with a factor less than
+    /// one, no attempts or a zero base delay, listing every problem found.
+    pub fn build(&self) -> Result<Policy<Backoff>, ConfigError> {
+        let mut problems = Vec::new();
+        if self.strategy == BackoffKind::Exponential {
+            problems.extend(delay::factor_problem(self.factor));
+        }
+        if self.max_attempts == Some(0) {
+            problems.push("max_attempts must be at least 1".to_owned());
+        }
+        if !problems.is_empty() {
+            return Err(ConfigError::new(problems.join("; ")));
+        }
+
+        let mut backoff = match self.strategy {
+            BackoffKind::None => Backoff::none(),
+            BackoffKind::Fixed => Backoff::fixed(self.base),
+            BackoffKind::Exponential => Backoff::exponential(self.base, self.factor),
+            BackoffKind::Fibonacci => Backoff::fibonacci(self.base),
+        };
+        if let Some(cap) = self.cap {
+            backoff = backoff.with_cap(cap);
+        }
+        if let Some(budget) = self.budget {
+            backoff = backoff.with_budget(budget);
+        }
+        let mut policy = Policy::new(backoff.with_jitter(self.jitter));
+        if let Some(max_attempts) = self.max_attempts {
+            policy = policy.with_max_attempts(max_attempts);
+        }
+
+        policy
+            .validate()
+            .map_err(|error| ConfigError::new(error.problems().join("; ")))?;
+        Ok(policy)
+    }
+}
+
+impl Default for RetryPolicyConfig {
+    fn default() -> Self {
+        RetryPolicyConfig {
+            strategy: BackoffKind::Exponential,
+            base: Duration::from_millis(100),
+            factor: 2.0,
+            cap: None,
+            max_attempts: None,
+            jitter: Jitter::None,
+            budget: None,
+        }
+    }
+}
+
+/// Parses settings written as `kind(base, xfactor, key=value, ...)`, where the kind is one of
+/// `none`, `fixed`, `exponential` and `fibonacci`, and the keys are `base`, `factor`, `cap`,
+/// `attempts`, `jitter` and `budget`. Everything but the kind is optional, including the
+/// parentheses.
+impl FromStr for RetryPolicyConfig {
+    type Err = ConfigError;
+
+    fn from_str(text: &str) -> Result<Self, ConfigError> {
+        let text = text.trim();
+        let (kind, settings) = match text.find('(') {
+            Some(open) if text.ends_with(')') => (&text[..open], &text[open + 1..text.len() - 1]),
+            None => (text, ""),
+            Some(_) => return Err(ConfigError::new(format!("unclosed `(` in `{}`", text))),
+        };
+
+        let mut config = RetryPolicyConfig {
+            strategy: match kind.trim() {
+                "none" => BackoffKind::None,
+                "fixed" => BackoffKind::Fixed,
+                "exponential" => BackoffKind::Exponential,
+                "fibonacci" => BackoffKind::Fibonacci,
+                kind => return Err(ConfigError::new(format!("unknown strategy `{}`", kind))),
+            },
+            ..RetryPolicyConfig::default()
+        };
+
+        let settings = settings
+            .split(',')
+            .map(str::trim)
+            .filter(|setting| !setting.is_empty());
+        for (index, setting) in settings.enumerate() {
+            let (key, value) = match setting.split_once('=') {
+                Some((key, value)) => (key.trim(), value.trim()),
+                None if setting.starts_with('x') => ("factor", &setting[1..]),
+                None if index == 0 => ("base", setting),
+                None => {
+                    return Err(ConfigError::new(format!(
+                        "expected `key=value`, got `{}`",
+                        setting
+                    )))
+                }
+            };
+            let invalid = || ConfigError::new(format!("invalid {} `{}`", key, value));
+
+            match key {
+                "base" => config.base = parse_duration(value)?,
+                "factor" => config.factor = value.parse().map_err(|_| invalid())?,
+                "cap" => config.cap = Some(parse_duration(value)?),
+                "attempts" | "max_attempts" => {
+                    config.max_attempts = Some(value.parse().map_err(|_| invalid())?)
+                }
+                "jitter" => {
+                    config.jitter = match value {
+                        "none" => Jitter::None,
+                        #[cfg(feature = "random")]
+                        "full" => Jitter::Full,
+                        _ => return Err(invalid()),
+                    }
+                }
+                "budget" => config.budget = Some(parse_duration(value)?),
+                key => return Err(ConfigError::new(format!("unknown setting `{}`", key))),
+            }
+        }
+
+        Ok(config)
+    }
+}
+
+/// Parses settings written as for `RetryPolicyConfig`, and builds the policy they describe.
+impl FromStr for Policy<Backoff> {
+    type Err = ConfigError;
+
+    fn from_str(text: &str) -> Result<Self, ConfigError> {
+        text.parse::<RetryPolicyConfig>()?.build()
+    }
+}
+
+impl Policy<Backoff> {
+    /// Build the policy described by the environment variable `name`, or return `default` if the
+    /// variable is not set or empty.
+    ///
+    /// The variable holds one-line settings, like `fixed(1s, attempts=3)`. With the `"serde"`
+    /// feature, it can also hold the settings as a JSON object. Invalid settings are an error
+    /// rather than silently replaced by the default.
+    pub fn from_env(name: &str, default: Policy<Backoff>) -> Result<Self, ConfigError> {
+        let value = match env::var(name) {
+            Ok(value) if !value.trim().is_empty() => value,
+            Ok(_) | Err(env::VarError::NotPresent) => return Ok(default),
+            Err(env::VarError::NotUnicode(_)) => {
+                return Err(ConfigError::new(format!("{} is not valid unicode", name)))
+            }
+        };
+
+        #[cfg(feature = "serde")]
+        {
+            if value.trim_start().starts_with('{') {
+                return serde_json::from_str::<RetryPolicyConfig>(&value)
+                    .map_err(|error| ConfigError::new(format!("{}: {}", name, error)))?
+                    .build();
+            }
+        }
+
+        value
+            .parse()
+            .map_err(|error: ConfigError| ConfigError::new(format!("{}: {}", name, error.message)))
+    }
+}
+
+/// An error in the settings of a retry policy.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConfigError {
+    message: String,
+}
+
+impl ConfigError {
+    pub(crate) fn new(message: impl Into<String>) -> Self {
+        ConfigError {
+            message: message.into(),
+        }
+    }
+
+    pub(crate) fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(formatter, "invalid retry policy: {}", self.message)
+    }
+}
+
+impl StdError for ConfigError {}
+
+/// Parse a duration written as a number followed by one of the units `ns`, `us` (or `µs`),
+/// `ms`, `s`, `m` and `h`, like `"100ms"` or `"1.5s"`. A bare `0` is also accepted.
+///
+/// This is the format of durations in configuration files.
+pub fn parse_duration(text: &str) -> Result<Duration, ConfigError> {
+    let text = text.trim();
+    let invalid = || ConfigError::new(format!("invalid duration `{}`", text));
+
+    let split = text
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let unit_nanos: u128 = match unit {
+        "ns" => 1,
+        "us" | "µs" => 1_000,
+        "ms" => 1_000_000,
+        "s" => 1_000_000_000,
+        "m" => 60_000_000_000,
+        "h" => 3_600_000_000_000,
+        "" if number.chars().all(|c| c == '0') && !number.is_empty() => 1,
+        _ => return Err(invalid()),
+    };
+
+    let (whole, fraction) = match number.find('.') {
+        Some(dot) => (&number[..dot], &number[dot + 1..]),
+        None => (number, ""),
+    };
+    if whole.is_empty() || fraction.contains('.') {
+        return Err(invalid());
+    }
+    let whole: u128 = whole.parse().map_err(|_| invalid())?;
+    let mut nanos = whole.checked_mul(unit_nanos).ok_or_else(invalid)?;
+    let mut scale = unit_nanos;
+    for digit in fraction.chars() {
+        scale /= 10;
+        nanos += u128::from(digit.to_digit(10).ok_or_else(invalid)?) * scale;
+    }
+
+    let secs = u64::try_from(nanos / 1_000_000_000).map_err(|_| invalid())?;
+    Ok(Duration::new(secs, (nanos % 1_000_000_000) as u32))
+}
+
+#[cfg(feature = "serde")]
+mod de {
+    use std::{convert::TryFrom, fmt, time::Duration};
+
+    use serde::de::{Deserializer, Error, Visitor};
+
+    struct DurationVisitor;
+
+    impl<'de> Visitor<'de> for DurationVisitor {
+        type Value = Duration;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+            formatter.write_str("a duration like \"100ms\" or a number of milliseconds")
+        }
+
+        fn visit_u64<E: Error>(self, millis: u64) -> Result<Duration, E> {
+            Ok(Duration::from_millis(millis))
+        }
+
+        fn visit_i64<E: Error>(self, millis: i64) -> Result<Duration, E> {
+            u64::try_from(millis)
+                .map(Duration::from_millis)
+                .map_err(|_| E::custom("durations cannot be negative"))
+        }
+
+        fn visit_str<E: Error>(self, text: &str) -> Result<Duration, E> {
+            super::parse_duration(text).map_err(E::custom)
+        }
+    }
+
+    pub(super) fn duration<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Duration, D::Error> {
+        deserializer.deserialize_any(DurationVisitor)
+    }
+
+    pub(super) fn optional_duration<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Duration>, D::Error> {
+        duration(deserializer).map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{parse_duration, RetryPolicyConfig};
+    use crate::{delay::Backoff, Policy};
+
+    #[test]
+    fn parses_durations() {
+        assert_eq!(parse_duration("100ms"), Ok(Duration::from_millis(100)));
+        assert_eq!(parse_duration("1.5s"), Ok(Duration::from_millis(1500)));
+        assert_eq!(parse_duration("2m"), Ok(Duration::from_secs(120)));
+        assert_eq!(parse_duration("250µs"), Ok(Duration::from_micros(250)));
+        assert_eq!(parse_duration("0"), Ok(Duration::default()));
+        assert!(parse_duration("10").is_err());
+        assert!(parse_duration("1.2.3s").is_err());
+        assert!(parse_duration("ms").is_err());
+    }
+
+    #[test]
+    fn rejects_invalid_settings() {
+        let config = RetryPolicyConfig {
+            factor: 0.5,
+            ..RetryPolicyConfig::default()
+        };
+        assert_eq!(
+            config.build().unwrap_err().to_string(),
+            "invalid retry policy: factor must be at least 1, got 0.5"
+        );
+
+        let config = RetryPolicyConfig {
+            factor: 0.5,
+            max_attempts: Some(0),
+            ..RetryPolicyConfig::default()
+        };
+        assert_eq!(
+            config.build().unwrap_err().to_string(),
+            "invalid retry policy: factor must be at least 1, got 0.5; \
+             max_attempts must be at least 1"
+        );
+
+        let config = RetryPolicyConfig {
+            base: Duration::default(),
+            ..RetryPolicyConfig::default()
+        };
+        assert_eq!(
+            config.build().unwrap_err().to_string(),
+            "invalid retry policy: base delay is zero, so every delay is zero"
+        );
+    }
+
+    #[cfg(feature = "random")]
+    #[test]
+    fn parses_one_line_settings() {
+        use crate::delay::{BackoffKind, Jitter};
+
+        let config: RetryPolicyConfig =
+            "exponential(100ms, x1.5, cap=30s, attempts=5, jitter=full)"
+                .parse()
+                .unwrap();
+        assert_eq!(
+            config,
+            RetryPolicyConfig {
+                strategy: BackoffKind::Exponential,
+                base: Duration::from_millis(100),
+                factor: 1.5,
+                cap: Some(Duration::from_secs(30)),
+                max_attempts: Some(5),
+                jitter: Jitter::Full,
+                budget: None,
+            }
+        );
+
+        let policy: Policy<Backoff> = " fixed ( 10ms , budget=1s ) ".parse().unwrap();
+        assert_eq!(policy.to_string(), "fixed(10ms, budget=1s)");
+        assert_eq!(
+            "none".parse::<RetryPolicyConfig>().unwrap().strategy,
+            BackoffKind::None
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_one_line_settings() {
+        let error = |text: &str| text.parse::<Policy<Backoff>>().unwrap_err().to_string();
+
+        assert_eq!(
+            error("linear(1s)"),
+            "invalid retry policy: unknown strategy `linear`"
+        );
+        assert_eq!(
+            error("fixed(1s, 2s)"),
+            "invalid retry policy: expected `key=value`, got `2s`"
+        );
+        assert_eq!(
+            error("fixed(1s, retries=2)"),
+            "invalid retry policy: unknown setting `retries`"
+        );
+        assert_eq!(
+            error("fixed(1s, attempts=0)"),
+            "invalid retry policy: max_attempts must be at least 1"
+        );
+        assert_eq!(
+            error("fixed(1s"),
+            "invalid retry policy: unclosed `(` in `fixed(1s`"
+        );
+    }
+
+    #[test]
+    fn reads_settings_from_the_environment() {
+        use std::env;
+
+        let default = || Policy::new(Backoff::none()).with_max_attempts(2);
+
+        let policy = Policy::from_env("RETRY_TEST_UNSET_POLICY", default()).unwrap();
+        assert_eq!(policy.to_string(), "no delay, max 2 attempts");
+
+        env::set_var("RETRY_TEST_POLICY", "fixed(10ms, attempts=3)");
+        let policy = Policy::from_env("RETRY_TEST_POLICY", default()).unwrap();
+        assert_eq!(policy.to_string(), "fixed(10ms), max 3 attempts");
+
+        env::set_var("RETRY_TEST_INVALID_POLICY", "fixed(10ms, attempts=none)");
+        assert_eq!(
+            Policy::from_env("RETRY_TEST_INVALID_POLICY", default())
+                .unwrap_err()
+                .to_string(),
+            "invalid retry policy: RETRY_TEST_INVALID_POLICY: invalid attempts `none`"
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn reads_json_settings_from_the_environment() {
+        std::env::set_var(
+            "RETRY_TEST_JSON_POLICY",
+            r#"{ "strategy": "fibonacci", "base": "1ms" }"#,
+        );
+        let policy =
+            Policy::from_env("RETRY_TEST_JSON_POLICY", Policy::new(Backoff::none())).unwrap();
+
+        assert_eq!(policy.to_string(), "fibonacci(1ms)");
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn deserializes_settings() {
+        let config: RetryPolicyConfig = serde_json::from_str(
+            r#"{ "strategy": "fixed", "base": 5, "jitter": "full", "budget": "1s" }"#,
+        )
+        .unwrap();
+
+        assert_eq!(config.base, Duration::from_millis(5));
+        assert_eq!(config.budget, Some(Duration::from_secs(1)));
+        assert_eq!(
+            config.build().unwrap().to_string(),
+            "fixed(5ms, jitter=full, budget=1s)"
+        );
+        assert!(serde_json::from_str::<RetryPolicyConfig>(r#"{ "delay": "1s" }"#).is_err());
+    }
+}
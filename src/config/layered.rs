@@ -0,0 +1,92 @@
+use std::path::Path;
+
+use ::config::{Config, Environment, File};
+
+use super::{ConfigError, RetryPolicyConfig};
+
+impl RetryPolicyConfig {
+    /// Read the settings stored under `key` in a layered configuration from the `config` crate,
+    /// for example `"retry.db"` for the `[retry.db]` table of a TOML file. The keys are the names
+    /// of the fields of `RetryPolicyConfig`, and missing keys keep their default values.
+    ///
+    /// This method is enabled with the `"config"` feature.
+    pub fn from_config(config: &Config, key: &str) -> Result<Self, ConfigError> {
+        match config.get(key) {
+            Ok(settings) => Ok(settings),
+            Err(::config::ConfigError::NotFound(_)) => Ok(RetryPolicyConfig::default()),
+            Err(error) => Err(ConfigError::new(format!("{}: {}", key, error))),
+        }
+    }
+
+    /// Load the settings of one policy from the defaults, then the file at `path` if it exists,
+    /// then the environment variables starting with `env_prefix` and an underscore.
+    ///
+    /// The format of the file is guessed from its extension, such as `.toml`, `.json` or `.yaml`.
+    /// With the prefix `MYAPP_RETRY`, the maximum number of attempts is overridden by
+    /// `MYAPP_RETRY_MAX_ATTEMPTS`, for example.
+    ///
+    /// This method is enabled with the `"config"` feature.
+    pub fn load<P>(path: P, env_prefix: &str) -> Result<Self, ConfigError>
+    where
+        P: AsRef<Path>,
+    {
+        Config::builder()
+            .add_source(File::from(path.as_ref()).required(false))
+            .add_source(Environment::with_prefix(env_prefix).try_parsing(true))
+            .build()
+            .and_then(Config::try_deserialize)
+            .map_err(|error| ConfigError::new(error.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, env, fs, time::Duration};
+
+    use ::config::{Config, Environment, File, FileFormat};
+
+    use crate::{config::RetryPolicyConfig, delay::BackoffKind};
+
+    #[test]
+    fn reads_settings_under_a_key() {
+        let variables: HashMap<_, _> =
+            vec![("APP_RETRY__DB__MAX_ATTEMPTS".to_string(), "4".into())]
+                .into_iter()
+                .collect();
+        let config = Config::builder()
+            .add_source(File::from_str(
+                "[retry.db]\nstrategy = \"fixed\"\nbase = \"250ms\"\n",
+                FileFormat::Toml,
+            ))
+            .add_source(
+                Environment::with_prefix("APP")
+                    .prefix_separator("_")
+                    .separator("__")
+                    .source(Some(variables)),
+            )
+            .build()
+            .unwrap();
+
+        let settings = RetryPolicyConfig::from_config(&config, "retry.db").unwrap();
+        assert_eq!(settings.strategy, BackoffKind::Fixed);
+        assert_eq!(settings.base, Duration::from_millis(250));
+        assert_eq!(settings.max_attempts, Some(4));
+
+        let settings = RetryPolicyConfig::from_config(&config, "retry.cache").unwrap();
+        assert_eq!(settings, RetryPolicyConfig::default());
+    }
+
+    #[test]
+    fn loads_settings_from_a_file_and_the_environment() {
+        let path = env::temp_dir().join(format!("retry-config-test-{}.json", std::process::id()));
+        fs::write(&path, r#"{ "strategy": "fibonacci", "max_attempts": 2 }"#).unwrap();
+        env::set_var("RETRY_LOAD_TEST_MAX_ATTEMPTS", "6");
+
+        let settings = RetryPolicyConfig::load(&path, "RETRY_LOAD_TEST");
+        fs::remove_file(&path).unwrap();
+
+        let settings = settings.unwrap();
+        assert_eq!(settings.strategy, BackoffKind::Fibonacci);
+        assert_eq!(settings.max_attempts, Some(6));
+    }
+}
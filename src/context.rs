@@ -0,0 +1,305 @@
+use std::{cell::Cell, error::Error as StdError, fmt, time::Duration};
+
+#[cfg(feature = "asynchronous")]
+use std::{future::Future, sync::Mutex};
+
+use crate::{classified::Outcome, CorrelationId, Error, OperationResult, Policy};
+
+/// Where an attempt of a call made with `Policy::retry_with_attempt_context` stands in the call,
+/// as of the start of the attempt.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AttemptContext {
+    attempt: u64,
+    elapsed: Duration,
+    remaining_budget: Option<Duration>,
+    next_planned_delay: Option<Duration>,
+}
+
+impl AttemptContext {
+    pub(crate) fn new(
+        attempt: u64,
+        elapsed: Duration,
+        remaining_budget: Option<Duration>,
+        next_planned_delay: Option<Duration>,
+    ) -> Self {
+        AttemptContext {
+            attempt,
+            elapsed,
+            remaining_budget,
+            next_planned_delay,
+        }
+    }
+
+    /// The number of the attempt, starting at 1.
+    pub fn attempt(&self) -> u64 {
+        self.attempt
+    }
+
+    /// The time since the first attempt of the call started.
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /// The time left of the policy's time budget, if it has one.
+    pub fn remaining_budget(&self) -> Option<Duration> {
+        self.remaining_budget
+    }
+
+    /// The delay the strategy plans after the attempt if it fails with a retryable error, bounded
+    /// by the policy's maximum delay, or `None` if the call would give up instead. The delay
+    /// actually waited may differ, such as when the error hints at a delay, the policy paces its
+    /// attempts, or its retry budget refuses the retry.
+    pub fn next_planned_delay(&self) -> Option<Duration> {
+        self.next_planned_delay
+    }
+}
+
+/// The error of a call made with `Policy::retry_with_context`, together with the context its
+/// attempts left behind.
+#[derive(Debug, PartialEq, Eq)]
+pub struct ContextError<C, E> {
+    error: Error<E>,
+    context: C,
+}
+
+impl<C, E> ContextError<C, E> {
+    /// Why the call gave up.
+    pub fn error(&self) -> &Error<E> {
+        &self.error
+    }
+
+    /// The context as the last attempt left it.
+    pub fn context(&self) -> &C {
+        &self.context
+    }
+
+    /// Consume the error, returning why the call gave up and the context.
+    pub fn into_parts(self) -> (Error<E>, C) {
+        (self.error, self.context)
+    }
+}
+
+impl<C, E> fmt::Display for ContextError<C, E>
+where
+    E: fmt::Display,
+{
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.error.fmt(formatter)
+    }
+}
+
+impl<C, E> StdError for ContextError<C, E>
+where
+    C: fmt::Debug,
+    E: StdError + 'static,
+{
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        self.error.source()
+    }
+}
+
+impl<D> Policy<D>
+where
+    D: IntoIterator<Item = Duration> + Clone,
+{
+    /// Retry the given operation synchronously according to this policy, passing every attempt
+    /// the same context, so that an attempt can leave behind what the next one needs, such as a
+    /// redirect to follow or an offset to resume from. If the call gives up, the context is
+    /// returned with the error.
+    ///
+    /// ```rust
+    /// # use retry::delay::NoDelay;
+    /// use retry::Policy;
+    ///
+    /// let policy = Policy::new(NoDelay.take(2));
+    /// let chunks = ["abc", "def", "ghi", "jkl"];
+    ///
+    /// // Every attempt uploads one more chunk before the connection drops, resuming from the
+    /// // chunks the previous attempts uploaded.
+    /// let result = policy.retry_with_context(Vec::new(), |uploaded: &mut Vec<&str>| {
+    ///     uploaded.push(chunks[uploaded.len()]);
+    ///     Err::<(), _>("connection reset")
+    /// });
+    ///
+    /// assert_eq!(*result.unwrap_err().context(), ["abc", "def", "ghi"]);
+    /// ```
+    pub fn retry_with_context<C, O, R, E, OR>(
+        &self,
+        mut context: C,
+        mut operation: O,
+    ) -> Result<R, ContextError<C, E>>
+    where
+        O: FnMut(&mut C) -> OR,
+        OR: Into<OperationResult<R, E>>,
+        E: fmt::Debug,
+    {
+        self.retry(|| operation(&mut context))
+            .map_err(|error| ContextError { error, context })
+    }
+
+    /// Retry the given operation synchronously according to this policy, passing each attempt its
+    /// `AttemptContext`, so that an attempt can fit its own timeout to the time the call has left.
+    ///
+    /// ```rust
+    /// # use std::time::Duration;
+    /// # use retry::delay::Fixed;
+    /// use retry::Policy;
+    ///
+    /// let policy = Policy::new(Fixed::from_millis(10).take(5))
+    ///     .with_time_budget(Duration::from_secs(2));
+    /// let mut timeouts = Vec::new();
+    ///
+    /// let _ = policy.retry_with_attempt_context(|context| {
+    ///     let timeout = context.remaining_budget().unwrap().min(Duration::from_millis(500));
+    ///     timeouts.push((context.attempt(), timeout));
+    ///     Err::<(), _>("timed out")
+    /// });
+    ///
+    /// assert_eq!(timeouts.len(), 6);
+    /// assert!(timeouts.iter().all(|&(_, timeout)| timeout <= Duration::from_millis(500)));
+    /// ```
+    pub fn retry_with_attempt_context<O, R, E, OR>(&self, mut operation: O) -> Result<R, Error<E>>
+    where
+        O: FnMut(&AttemptContext) -> OR,
+        OR: Into<OperationResult<R, E>>,
+        E: fmt::Debug,
+    {
+        let context = Cell::new(AttemptContext::default());
+        self.execute_aggregated(
+            CorrelationId::generate(),
+            crate::aggregate::Last,
+            Some(&context),
+            |_, _| {
+                let result: OperationResult<R, E> = operation(&context.get()).into();
+                Outcome::from(result)
+            },
+        )
+    }
+
+    /// Retry the given asynchronous operation according to this policy, passing each attempt its
+    /// `AttemptContext`, like `retry_with_attempt_context`.
+    #[cfg(feature = "asynchronous")]
+    pub async fn retry_async_with_attempt_context<O, R, E, OR, F>(
+        &self,
+        mut operation: O,
+    ) -> Result<R, Error<E>>
+    where
+        O: FnMut(AttemptContext) -> F,
+        OR: Into<OperationResult<R, E>>,
+        F: Future<Output = OR>,
+        E: fmt::Debug,
+    {
+        let context = Mutex::new(AttemptContext::default());
+        let context = &context;
+        self.execute_async(CorrelationId::generate(), Some(context), |_, _| {
+            let current = *context
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+            let attempt = operation(current);
+            async move {
+                let result: OperationResult<R, E> = attempt.await.into();
+                Outcome::from(result)
+            }
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::AttemptContext;
+    use crate::{
+        clock::{MockClock, Sleeper},
+        delay::{Fixed, NoDelay},
+        Error, Policy,
+    };
+
+    /// Sleeps by advancing a mock clock.
+    #[derive(Debug)]
+    struct Advancing(MockClock);
+
+    impl Sleeper for Advancing {
+        fn sleep(&self, duration: Duration) {
+            self.0.advance(duration);
+        }
+    }
+
+    #[test]
+    fn attempts_share_the_context() {
+        let policy = Policy::new(NoDelay).with_max_attempts(3);
+
+        let result = policy.retry_with_context(Vec::new(), |redirects: &mut Vec<u64>| {
+            match redirects.last() {
+                Some(&location) if location == 2 => Ok(location),
+                last => {
+                    redirects.push(last.map_or(1, |location| location + 1));
+                    Err("moved")
+                }
+            }
+        });
+        assert_eq!(result, Ok(2));
+
+        let error = policy
+            .retry_with_context(0, |attempts: &mut u64| {
+                *attempts += 1;
+                Err::<(), _>("down")
+            })
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "gave up after all 3 attempts allowed over 0s of delays (last error: down)"
+        );
+        let (error, attempts) = error.into_parts();
+        assert!(matches!(error, Error::MaxAttempts { tries: 3, .. }));
+        assert_eq!(attempts, 3);
+    }
+
+    #[test]
+    fn passes_each_attempt_its_context() {
+        let clock = MockClock::new();
+        let policy = Policy::new(Fixed::from_millis(100).take(5))
+            .with_max_delay(Duration::from_millis(60))
+            .with_time_budget(Duration::from_millis(150))
+            .with_clock(clock.clone())
+            .with_sleeper(Advancing(clock.clone()));
+
+        let mut contexts = Vec::new();
+        let error = policy
+            .retry_with_attempt_context(|context| {
+                contexts.push(*context);
+                clock.advance(Duration::from_millis(10));
+                Err::<(), _>("down")
+            })
+            .unwrap_err();
+        assert!(matches!(error, Error::Operation { tries: 3, .. }));
+
+        let millis = Duration::from_millis;
+        assert_eq!(
+            contexts,
+            [
+                AttemptContext::new(1, millis(0), Some(millis(150)), Some(millis(60))),
+                AttemptContext::new(2, millis(70), Some(millis(80)), Some(millis(60))),
+                AttemptContext::new(3, millis(140), Some(millis(10)), None),
+            ]
+        );
+    }
+
+    #[cfg(feature = "asynchronous")]
+    #[tokio::test]
+    async fn passes_asynchronous_attempts_their_context() {
+        let policy = Policy::new(NoDelay).with_max_attempts(2);
+
+        let mut attempts = Vec::new();
+        let result = policy
+            .retry_async_with_attempt_context(|context| {
+                attempts.push((context.attempt(), context.next_planned_delay()));
+                async move { Err::<(), _>("down") }
+            })
+            .await;
+
+        assert!(result.is_err());
+        assert_eq!(attempts, [(1, Some(Duration::default())), (2, None)]);
+    }
+}
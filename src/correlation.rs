@@ -0,0 +1,144 @@
+use std::{
+    cmp::Ordering,
+    fmt,
+    hash::{Hash, Hasher},
+    sync::Arc,
+};
+
+/// Identifies all the attempts of one call made through a `Policy`.
+///
+/// A new random ID is generated for every call, unless one is given to
+/// `Policy::retry_with_correlation_id`, for example to reuse the ID of an incoming request. The
+/// ID is passed to every attempt and every listener, and included in logs and traces.
+///
+/// Cloning an ID is cheap, and generating one does not allocate.
+#[derive(Clone)]
+pub struct CorrelationId(Repr);
+
+#[derive(Clone)]
+enum Repr {
+    /// The hexadecimal digits of a generated ID, kept inline.
+    Generated([u8; 16]),
+    /// An ID given by the caller.
+    Given(Arc<str>),
+}
+
+impl CorrelationId {
+    /// Generate a new random ID, made of 16 hexadecimal digits.
+    pub fn generate() -> Self {
+        const DIGITS: &[u8; 16] = b"0123456789abcdef";
+
+        let value = random_u64();
+        let mut digits = [0; 16];
+        for (i, digit) in digits.iter_mut().enumerate() {
+            *digit = DIGITS[(value >> (60 - 4 * i)) as usize & 0xf];
+        }
+        CorrelationId(Repr::Generated(digits))
+    }
+
+    /// The ID as a string.
+    pub fn as_str(&self) -> &str {
+        match self.0 {
+            Repr::Generated(ref digits) => std::str::from_utf8(digits).unwrap_or_default(),
+            Repr::Given(ref id) => id,
+        }
+    }
+}
+
+#[cfg(feature = "random")]
+pub(crate) fn random_u64() -> u64 {
+    rand::random()
+}
+
+/// Without `rand`, the random keys of the standard library's hasher are mixed with a counter.
+#[cfg(not(feature = "random"))]
+pub(crate) fn random_u64() -> u64 {
+    use std::{
+        collections::hash_map::RandomState,
+        hash::{BuildHasher, Hasher},
+        sync::atomic::{AtomicU64, Ordering},
+    };
+
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(COUNTER.fetch_add(1, Ordering::Relaxed));
+    hasher.finish()
+}
+
+/// IDs are compared as strings, whether they were generated or given.
+impl PartialEq for CorrelationId {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl Eq for CorrelationId {}
+
+impl Hash for CorrelationId {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_str().hash(state)
+    }
+}
+
+impl PartialOrd for CorrelationId {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for CorrelationId {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.as_str().cmp(other.as_str())
+    }
+}
+
+impl fmt::Debug for CorrelationId {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), formatter)
+    }
+}
+
+impl fmt::Display for CorrelationId {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str(self.as_str())
+    }
+}
+
+/// IDs are serialized as strings, whether they were generated or given.
+#[cfg(feature = "serde")]
+impl serde::Serialize for CorrelationId {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl From<String> for CorrelationId {
+    fn from(id: String) -> Self {
+        CorrelationId(Repr::Given(id.into()))
+    }
+}
+
+impl From<&str> for CorrelationId {
+    fn from(id: &str) -> Self {
+        CorrelationId(Repr::Given(id.into()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CorrelationId;
+
+    #[test]
+    fn generated_ids_are_distinct_hex_strings() {
+        let first = CorrelationId::generate();
+        let second = CorrelationId::generate();
+
+        assert_ne!(first, second);
+        assert_eq!(first.as_str().len(), 16);
+        assert!(first.as_str().chars().all(|c| c.is_ascii_hexdigit()));
+        assert_eq!(CorrelationId::from(first.as_str()), first);
+    }
+}
@@ -0,0 +1,130 @@
+use std::{fmt, time::Duration};
+
+use crate::{Error, OperationResult, Policy};
+
+/// What the attempts of a call made with `Policy::retry_with_cost` have spent of the policy's cost
+/// budget, passed to every attempt so that it can declare its cost.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CostMeter {
+    budget: Option<u64>,
+    spent: u64,
+}
+
+impl CostMeter {
+    /// Declare that the current attempt cost `cost`, on top of what it has already declared.
+    pub fn charge(&mut self, cost: u64) {
+        self.spent = self.spent.saturating_add(cost);
+    }
+
+    /// The total cost declared by the attempts of the call so far.
+    pub fn spent(&self) -> u64 {
+        self.spent
+    }
+
+    /// What is left of the budget, or `None` if the policy has no cost budget.
+    pub fn remaining(&self) -> Option<u64> {
+        self.budget.map(|budget| budget.saturating_sub(self.spent))
+    }
+
+    /// Whether the budget has been spent, so that the call gives up instead of retrying.
+    pub fn is_spent(&self) -> bool {
+        self.remaining() == Some(0)
+    }
+}
+
+impl fmt::Display for CostMeter {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.budget {
+            Some(budget) => write!(formatter, "spent {} of {}", self.spent, budget),
+            None => write!(formatter, "spent {}", self.spent),
+        }
+    }
+}
+
+impl<D> Policy<D>
+where
+    D: IntoIterator<Item = Duration> + Clone,
+{
+    /// Retry the given operation synchronously according to this policy, letting every attempt
+    /// declare what it cost on the given meter, and giving up with the last error once the
+    /// attempts have spent the policy's cost budget. An attempt can also read what is left of the
+    /// budget, such as to fall back to a cheaper model before it runs out.
+    ///
+    /// ```rust
+    /// # use retry::delay::NoDelay;
+    /// use retry::{CostMeter, Policy};
+    ///
+    /// let policy = Policy::new(NoDelay.take(10)).with_cost_budget(100);
+    ///
+    /// // Each attempt at summarizing a long document is billed for 40 tokens.
+    /// let result = policy.retry_with_cost(|meter: &mut CostMeter| {
+    ///     meter.charge(40);
+    ///     Err::<(), _>("rate limited")
+    /// });
+    ///
+    /// assert_eq!(result.unwrap_err().tries(), 3);
+    /// ```
+    pub fn retry_with_cost<O, R, E, OR>(&self, mut operation: O) -> Result<R, Error<E>>
+    where
+        O: FnMut(&mut CostMeter) -> OR,
+        OR: Into<OperationResult<R, E>>,
+        E: fmt::Debug,
+    {
+        let mut meter = CostMeter {
+            budget: self.cost_budget(),
+            spent: 0,
+        };
+        self.retry(|| match operation(&mut meter).into() {
+            OperationResult::Retry(error) if meter.is_spent() => OperationResult::Err(error),
+            result => result,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CostMeter;
+    use crate::{delay::NoDelay, Error, Policy};
+
+    #[test]
+    fn gives_up_once_the_budget_is_spent() {
+        let policy = Policy::new(NoDelay)
+            .with_max_attempts(10)
+            .with_cost_budget(10);
+        let mut remaining = Vec::new();
+
+        let result = policy.retry_with_cost(|meter: &mut CostMeter| {
+            remaining.push(meter.remaining());
+            meter.charge(if meter.spent() == 0 { 7 } else { 2 });
+            Err::<(), _>("quota")
+        });
+
+        assert_eq!(remaining, [Some(10), Some(3), Some(1)]);
+        assert!(matches!(
+            result,
+            Err(Error::Operation {
+                error: "quota",
+                tries: 3,
+                ..
+            })
+        ));
+        assert_eq!(
+            policy.to_string(),
+            "no delay, max 10 attempts, cost budget 10"
+        );
+    }
+
+    #[test]
+    fn meters_without_a_budget() {
+        let policy = Policy::new(NoDelay.take(2));
+        let mut meters = Vec::new();
+
+        let _ = policy.retry_with_cost(|meter: &mut CostMeter| {
+            meter.charge(1_000);
+            meters.push(meter.to_string());
+            Err::<(), _>("quota")
+        });
+
+        assert_eq!(meters, ["spent 1000", "spent 2000", "spent 3000"]);
+    }
+}
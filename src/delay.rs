@@ -1,52 +1,164 @@
 //! Different types of delay for retryable operations.
-
-use std::ops::{Range as StdRange, RangeInclusive};
-use std::time::Duration;
-use std::u64::MAX as U64_MAX;
-
-use rand::{
-    distributions::{Distribution, Uniform},
-    random,
-    rngs::ThreadRng,
-    thread_rng,
-};
+//!
+//! Every strategy implements `Display`, summarizing the schedule it will produce from its current
+//! state, for example `exponential(10ms, x10)`.
+//!
+//! With the `"chrono"` and `"time"` features, the strategies built from a single duration can also
+//! be built from the durations of those crates, with `TryFrom`.
+//!
+//! The constructors of the deterministic strategies are `const fn`, so strategies can be kept in
+//! constants and statics, and cloned into policies where they are used:
+//!
+//! ```rust
+//! use retry::{delay::Exponential, retry};
+//!
+//! static BACKOFF: Exponential = Exponential::from_millis(10);
+//!
+//! let result = retry(BACKOFF.clone().take(2), || Err::<(), _>("down"));
+//! assert_eq!(result.unwrap_err().tries(), 3);
+//! ```
+//!
+//! The randomized parts, `Range`, `jitter`, `splay`, `Jittered`, `GrowingJitter`,
+//! `LatencyJitter` and the `Latency` it reads, `SharedRng` and the full jitter of a `Backoff`, are
+//! enabled with the default `"random"` feature, which is the only one that depends on `rand`.
+//!
+//! Every strategy is `Send` and `Sync`, so policies can be kept in shared structures and moved
+//! across threads and tasks. Randomized strategies hold no generator of their own unless given a
+//! `SharedRng`: they borrow the thread-local one each time they draw a delay.
+//!
+//! Without the default `"std"` feature, only the deterministic strategies are available:
+//! `Exponential`, `Fibonacci`, `Fixed` and `NoDelay`, along with `CheckedDelay`. `Backoff`,
+//! `DeadlineFraction`, `Adaptive` and the `FailureRate` it reads, `TimeOfDay`, and the validation,
+//! introspection and conversion traits need the standard library.
+
+use core::fmt;
+#[cfg(feature = "random")]
+use core::ops::{Range as StdRange, RangeInclusive};
+use core::time::Duration;
+
+#[cfg(feature = "std")]
+mod adaptive;
+mod checked;
+#[cfg(feature = "std")]
+mod convert;
+#[cfg(feature = "std")]
+mod deadline;
+#[cfg(feature = "rand_distr")]
+mod distributed;
+mod finite;
+#[cfg(feature = "std")]
+mod introspect;
+#[cfg(feature = "random")]
+mod latency;
+#[cfg(feature = "random")]
+mod random;
+#[cfg(feature = "std")]
+mod time_of_day;
+#[cfg(feature = "std")]
+mod validate;
+
+#[cfg(feature = "std")]
+pub use adaptive::{Adaptive, FailureRate};
+pub use checked::{CheckedDelay, Violation};
+#[cfg(feature = "std")]
+pub use convert::{NegativeDuration, TryIntoDuration};
+#[cfg(feature = "std")]
+pub use deadline::DeadlineFraction;
+#[cfg(feature = "rand_distr")]
+pub use distributed::Distributed;
+pub use finite::FiniteDelay;
+#[cfg(feature = "std")]
+pub use introspect::{Introspect, Param};
+#[cfg(feature = "random")]
+pub use latency::{Latency, LatencyJitter};
+#[cfg(feature = "random")]
+pub use random::{GrowingJitter, Jittered, Randomized, SharedRng};
+#[cfg(feature = "std")]
+pub use time_of_day::TimeOfDay;
+#[cfg(feature = "std")]
+pub(crate) use validate::factor_problem;
+#[cfg(feature = "std")]
+pub use validate::{Validate, ValidationError};
+
+#[cfg(feature = "random")]
+use rand::distributions::{Distribution, Uniform};
 
 /// Each retry increases the delay since the last exponentially.
-#[derive(Debug)]
+///
+/// Each delay is the previous one multiplied by the number of milliseconds in the initial delay,
+/// or by the factor given to `with_factor`, saturating at `Duration::MAX`.
+#[derive(Clone, Debug)]
 pub struct Exponential {
-    base: u64,
-    current: u64,
+    factor: u64,
+    current: Duration,
 }
 
 impl Exponential {
     /// Create a new `Exponential` using the given millisecond duration as the initial delay.
-    pub fn from_millis(base: u64) -> Self {
+    pub const fn from_millis(base: u64) -> Self {
         Exponential {
-            base,
-            current: base,
+            factor: base,
+            current: Duration::from_millis(base),
         }
     }
+
+    /// Create a new `Exponential` using the given duration as the initial delay. Delays are
+    /// multiplied by its number of whole milliseconds, or by 1 for a base below a millisecond, so
+    /// that the delays never shrink; use `with_factor` to make such a base grow.
+    pub const fn from_duration(duration: Duration) -> Self {
+        let millis = duration.as_millis();
+        Exponential {
+            factor: if millis > u64::MAX as u128 {
+                u64::MAX
+            } else if millis == 0 {
+                1
+            } else {
+                millis as u64
+            },
+            current: duration,
+        }
+    }
+
+    /// Multiply each delay by `factor` instead of the number of milliseconds in the initial delay.
+    pub const fn with_factor(mut self, factor: u64) -> Self {
+        self.factor = factor;
+        self
+    }
+
+    /// Create a new `Exponential` like `from_millis`, failing if the delays would be zero or never
+    /// grow.
+    #[cfg(feature = "std")]
+    pub fn try_from_millis(base: u64) -> Result<Self, ValidationError> {
+        let exponential = Self::from_millis(base);
+        ValidationError::check(exponential.problems())?;
+        Ok(exponential)
+    }
 }
 
 impl Iterator for Exponential {
     type Item = Duration;
 
     fn next(&mut self) -> Option<Duration> {
-        let duration = Duration::from_millis(self.current);
-
-        if let Some(next) = self.current.checked_mul(self.base) {
-            self.current = next;
-        } else {
-            self.current = U64_MAX;
-        }
-
+        let duration = self.current;
+        self.current =
+            saturating_from_nanos(self.current.as_nanos().saturating_mul(self.factor as u128));
         Some(duration)
     }
 }
 
+impl fmt::Display for Exponential {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            formatter,
+            "exponential({:?}, x{})",
+            self.current, self.factor
+        )
+    }
+}
+
 impl From<Duration> for Exponential {
     fn from(duration: Duration) -> Self {
-        Self::from_millis(duration.as_millis() as u64)
+        Self::from_duration(duration)
     }
 }
 
@@ -58,46 +170,93 @@ impl From<Duration> for Exponential {
 ///
 /// See ["A Performance Comparison of Different Backoff Algorithms under Different Rebroadcast Probabilities for MANETs."](http://www.comp.leeds.ac.uk/ukpew09/papers/12.pdf)
 /// for more details.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Fibonacci {
-    curr: u64,
-    next: u64,
+    curr: Duration,
+    next: Duration,
 }
 
 impl Fibonacci {
     /// Create a new `Fibonacci` using the given duration in milliseconds.
-    pub fn from_millis(millis: u64) -> Fibonacci {
+    pub const fn from_millis(millis: u64) -> Fibonacci {
+        Self::from_duration(Duration::from_millis(millis))
+    }
+
+    /// Create a new `Fibonacci` using the given duration.
+    pub const fn from_duration(duration: Duration) -> Fibonacci {
         Fibonacci {
-            curr: millis,
-            next: millis,
+            curr: duration,
+            next: duration,
         }
     }
+
+    /// Create a new `Fibonacci` like `from_millis`, failing if the delays would be zero.
+    #[cfg(feature = "std")]
+    pub fn try_from_millis(millis: u64) -> Result<Fibonacci, ValidationError> {
+        let fibonacci = Self::from_millis(millis);
+        ValidationError::check(fibonacci.problems())?;
+        Ok(fibonacci)
+    }
 }
 
 impl Iterator for Fibonacci {
     type Item = Duration;
 
     fn next(&mut self) -> Option<Duration> {
-        let duration = Duration::from_millis(self.curr);
-
-        if let Some(next_next) = self.curr.checked_add(self.next) {
-            self.curr = self.next;
-            self.next = next_next;
-        } else {
-            self.curr = self.next;
-            self.next = U64_MAX;
-        }
-
+        let duration = self.curr;
+        let next_next = self.curr.saturating_add(self.next);
+        self.curr = self.next;
+        self.next = next_next;
         Some(duration)
     }
 }
 
+impl fmt::Display for Fibonacci {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(formatter, "fibonacci({:?})", self.curr)
+    }
+}
+
 impl From<Duration> for Fibonacci {
     fn from(duration: Duration) -> Self {
-        Self::from_millis(duration.as_millis() as u64)
+        Self::from_duration(duration)
     }
 }
 
+#[test]
+fn const_constructors() {
+    const EXPONENTIAL: Exponential = Exponential::from_duration(Duration::from_secs(1));
+    const FIBONACCI: Fibonacci = Fibonacci::from_millis(10);
+    const FIXED: Fixed = Fixed::from_duration(Duration::from_millis(1500));
+    const NO_DELAY: NoDelay = NoDelay;
+
+    assert_eq!(EXPONENTIAL.to_string(), "exponential(1s, x1000)");
+    assert_eq!(FIBONACCI.to_string(), "fibonacci(10ms)");
+    assert_eq!(FIXED.to_string(), "fixed(1.5s)");
+    assert_eq!(NO_DELAY.to_string(), "no delay");
+}
+
+#[test]
+fn sub_millisecond_and_saturated_delays() {
+    let mut fibonacci = Fibonacci::from_duration(Duration::from_micros(250));
+    assert_eq!(fibonacci.nth(2), Some(Duration::from_micros(500)));
+
+    let mut exponential = Exponential::from_duration(Duration::from_micros(2500));
+    assert_eq!(exponential.nth(1), Some(Duration::from_micros(5000)));
+
+    let mut exponential = Exponential::from_duration(Duration::from_micros(500));
+    assert_eq!(exponential.nth(2), Some(Duration::from_micros(500)));
+    let mut exponential = Exponential::from_duration(Duration::from_micros(500)).with_factor(2);
+    assert_eq!(exponential.nth(2), Some(Duration::from_millis(2)));
+
+    let mut exponential = Exponential::from_millis(1000);
+    assert_eq!(exponential.nth(10), Some(Duration::MAX));
+    assert_eq!(exponential.next(), Some(Duration::MAX));
+
+    let mut fibonacci = Fibonacci::from_duration(Duration::MAX);
+    assert_eq!(fibonacci.nth(2), Some(Duration::MAX));
+}
+
 #[test]
 fn fibonacci() {
     let mut iter = Fibonacci::from_millis(10);
@@ -111,23 +270,26 @@ fn fibonacci() {
 
 #[test]
 fn fibonacci_saturated() {
-    let mut iter = Fibonacci::from_millis(U64_MAX);
-    assert_eq!(iter.next(), Some(Duration::from_millis(U64_MAX)));
-    assert_eq!(iter.next(), Some(Duration::from_millis(U64_MAX)));
+    let mut iter = Fibonacci::from_millis(u64::MAX);
+    assert_eq!(iter.next(), Some(Duration::from_millis(u64::MAX)));
+    assert_eq!(iter.next(), Some(Duration::from_millis(u64::MAX)));
 }
 
 /// Each retry uses a fixed delay.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Fixed {
     duration: Duration,
 }
 
 impl Fixed {
     /// Create a new `Fixed` using the given duration in milliseconds.
-    pub fn from_millis(millis: u64) -> Self {
-        Fixed {
-            duration: Duration::from_millis(millis),
-        }
+    pub const fn from_millis(millis: u64) -> Self {
+        Self::from_duration(Duration::from_millis(millis))
+    }
+
+    /// Create a new `Fixed` using the given duration.
+    pub const fn from_duration(duration: Duration) -> Self {
+        Fixed { duration }
     }
 }
 
@@ -139,16 +301,20 @@ impl Iterator for Fixed {
     }
 }
 
+impl fmt::Display for Fixed {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(formatter, "fixed({:?})", self.duration)
+    }
+}
+
 impl From<Duration> for Fixed {
     fn from(delay: Duration) -> Self {
-        Self {
-            duration: delay.into(),
-        }
+        Self::from_duration(delay)
     }
 }
 
 /// Each retry happens immediately without any delay.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct NoDelay;
 
 impl Iterator for NoDelay {
@@ -159,13 +325,27 @@ impl Iterator for NoDelay {
     }
 }
 
+impl fmt::Display for NoDelay {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str("no delay")
+    }
+}
+
 /// Each retry uses a duration randomly chosen from a range.
-#[derive(Debug)]
+///
+/// Durations are drawn from the thread-local generator, unless another is given with
+/// `Randomized::with_rng`.
+#[cfg(feature = "random")]
+#[derive(Clone, Debug)]
 pub struct Range {
-    distribution: Uniform<u64>,
-    rng: ThreadRng,
+    distribution: Uniform<u128>,
+    rng: Option<SharedRng>,
+    minimum: Duration,
+    maximum: Duration,
+    inclusive: bool,
 }
 
+#[cfg(feature = "random")]
 impl Range {
     /// Create a new `Range` between the given millisecond durations, excluding the maximum value.
     ///
@@ -173,10 +353,7 @@ impl Range {
     ///
     /// Panics if the minimum is greater than or equal to the maximum.
     pub fn from_millis_exclusive(minimum: u64, maximum: u64) -> Self {
-        Range {
-            distribution: Uniform::new(minimum, maximum),
-            rng: thread_rng(),
-        }
+        Self::from(Duration::from_millis(minimum)..Duration::from_millis(maximum))
     }
 
     /// Create a new `Range` between the given millisecond durations, including the maximum value.
@@ -185,42 +362,562 @@ impl Range {
     ///
     /// Panics if the minimum is greater than or equal to the maximum.
     pub fn from_millis_inclusive(minimum: u64, maximum: u64) -> Self {
-        Range {
-            distribution: Uniform::new_inclusive(minimum, maximum),
-            rng: thread_rng(),
-        }
+        Self::from(Duration::from_millis(minimum)..=Duration::from_millis(maximum))
+    }
+
+    /// Create a new `Range` like `from_millis_exclusive`, failing instead of panicking if the
+    /// range is empty.
+    pub fn try_from_millis_exclusive(minimum: u64, maximum: u64) -> Result<Self, ValidationError> {
+        ValidationError::check(validate::range_problems(
+            Duration::from_millis(minimum),
+            Duration::from_millis(maximum),
+            false,
+        ))?;
+        Ok(Self::from_millis_exclusive(minimum, maximum))
+    }
+
+    /// Create a new `Range` like `from_millis_inclusive`, failing instead of panicking if the
+    /// range is empty.
+    pub fn try_from_millis_inclusive(minimum: u64, maximum: u64) -> Result<Self, ValidationError> {
+        ValidationError::check(validate::range_problems(
+            Duration::from_millis(minimum),
+            Duration::from_millis(maximum),
+            true,
+        ))?;
+        Ok(Self::from_millis_inclusive(minimum, maximum))
     }
 }
 
+#[cfg(feature = "random")]
 impl Iterator for Range {
     type Item = Duration;
 
     fn next(&mut self) -> Option<Duration> {
-        Some(Duration::from_millis(
-            self.distribution.sample(&mut self.rng),
-        ))
+        let distribution = &self.distribution;
+        Some(saturating_from_nanos(random::draw(
+            self.rng.as_ref(),
+            |rng| distribution.sample(rng),
+        )))
     }
 }
 
+#[cfg(feature = "random")]
+impl fmt::Display for Range {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            formatter,
+            "range({:?}..{}{:?})",
+            self.minimum,
+            if self.inclusive { "=" } else { "" },
+            self.maximum
+        )
+    }
+}
+
+#[cfg(feature = "random")]
 impl From<StdRange<Duration>> for Range {
+    /// # Panics
+    ///
+    /// Panics if the start of the range is greater than or equal to its end.
     fn from(range: StdRange<Duration>) -> Self {
-        Self::from_millis_exclusive(range.start.as_millis() as u64, range.end.as_millis() as u64)
+        Range {
+            distribution: Uniform::new(range.start.as_nanos(), range.end.as_nanos()),
+            rng: None,
+            minimum: range.start,
+            maximum: range.end,
+            inclusive: false,
+        }
     }
 }
 
+#[cfg(feature = "random")]
 impl From<RangeInclusive<Duration>> for Range {
+    /// # Panics
+    ///
+    /// Panics if the start of the range is greater than its end.
     fn from(range: RangeInclusive<Duration>) -> Self {
-        Self::from_millis_inclusive(
-            range.start().as_millis() as u64,
-            range.end().as_millis() as u64,
-        )
+        let (minimum, maximum) = range.into_inner();
+        Range {
+            distribution: Uniform::new_inclusive(minimum.as_nanos(), maximum.as_nanos()),
+            rng: None,
+            minimum,
+            maximum,
+            inclusive: true,
+        }
+    }
+}
+
+/// The shape of the schedule produced by a `Backoff`.
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
+pub enum BackoffKind {
+    /// Every delay is zero.
+    None,
+    /// Every delay is the base delay.
+    Fixed,
+    /// Every delay is the previous one multiplied by the factor.
+    Exponential,
+    /// Every delay is the sum of the two previous ones.
+    Fibonacci,
+}
+
+/// The random jitter applied to each delay of a `Backoff`.
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
+pub enum Jitter {
+    /// Delays are used as they are.
+    None,
+    /// Each delay is replaced by a random one between zero and itself, as with `jitter`.
+    ///
+    /// This is enabled with the default `"random"` feature.
+    #[cfg(feature = "random")]
+    Full,
+}
+
+/// A delay strategy assembled from settings rather than from iterator adapters, as built from a
+/// `RetryPolicyConfig`.
+///
+/// Delays follow a schedule of the given kind, are capped at a maximum and jittered, in that
+/// order. With a budget, the schedule ends as soon as the next delay would make the total delay
+/// exceed it.
+#[cfg(feature = "std")]
+#[derive(Clone, Debug, PartialEq)]
+pub struct Backoff {
+    kind: BackoffKind,
+    factor: f64,
+    cap: Option<Duration>,
+    jitter: Jitter,
+    budget: Option<Duration>,
+    #[cfg(feature = "random")]
+    rng: Option<SharedRng>,
+    #[cfg(feature = "random")]
+    spread: Option<u32>,
+    current: Duration,
+    next: Duration,
+}
+
+#[cfg(feature = "std")]
+impl Backoff {
+    /// Create a `Backoff` that never waits.
+    pub fn none() -> Self {
+        Self::new(BackoffKind::None, Duration::default(), 1.0)
+    }
+
+    /// Create a `Backoff` that always waits `base`.
+    pub fn fixed(base: Duration) -> Self {
+        Self::new(BackoffKind::Fixed, base, 1.0)
+    }
+
+    /// Create a `Backoff` that first waits `base`, multiplying the delay by `factor` each time.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `factor` is less than one or not finite.
+    pub fn exponential(base: Duration, factor: f64) -> Self {
+        assert!(
+            factor_problem(factor).is_none(),
+            "the factor of an exponential backoff must be at least 1"
+        );
+        Self::new(BackoffKind::Exponential, base, factor)
+    }
+
+    /// Create a `Backoff` like `exponential`, failing instead of panicking if `factor` is less than
+    /// one, and failing if `base` is zero.
+    pub fn try_exponential(base: Duration, factor: f64) -> Result<Self, ValidationError> {
+        let backoff = Self::new(BackoffKind::Exponential, base, factor);
+        ValidationError::check(backoff.problems())?;
+        Ok(backoff)
+    }
+
+    /// Create a `Backoff` that first waits `base` twice, then the sum of the two previous delays.
+    pub fn fibonacci(base: Duration) -> Self {
+        Self::new(BackoffKind::Fibonacci, base, 1.0)
+    }
+
+    /// Create a `Backoff` of the given kind whose delays between `attempts` attempts add up to
+    /// `deadline`, so that the base delay does not have to be tuned by hand. Exponential schedules
+    /// double each delay.
+    ///
+    /// The schedule has a budget of the sum of its delays, which ends it after the last attempt.
+    /// Shortening the delays with a cap or jitter leaves room in the budget for more attempts.
+    /// `BackoffKind::None` has no delays to fit, and gives `Backoff::none()`.
+    ///
+    /// ```rust
+    /// # use std::time::Duration;
+    /// use retry::delay::{Backoff, BackoffKind};
+    ///
+    /// let delays: Vec<_> = Backoff::fit(Duration::from_secs(63), 7, BackoffKind::Exponential).collect();
+    ///
+    /// assert_eq!(delays.len(), 6);
+    /// assert_eq!(delays[0], Duration::from_secs(1));
+    /// assert_eq!(delays.iter().sum::<Duration>(), Duration::from_secs(63));
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if `attempts` is zero.
+    pub fn fit(deadline: Duration, attempts: u64, kind: BackoffKind) -> Self {
+        assert!(attempts > 0, "a schedule must fit at least one attempt");
+        let delays = attempts - 1;
+        // The sum of the delays of the schedule, in multiples of its base delay.
+        let units = match kind {
+            BackoffKind::None => return Self::none(),
+            BackoffKind::Fixed => u128::from(delays),
+            BackoffKind::Exponential if delays < 128 => (1 << delays) - 1,
+            BackoffKind::Exponential => u128::MAX,
+            BackoffKind::Fibonacci => {
+                let (mut sum, mut current, mut next) = (0u128, 1u128, 1u128);
+                for _ in 0..delays {
+                    sum = sum.saturating_add(current);
+                    let following = current.saturating_add(next);
+                    current = next;
+                    next = following;
+                }
+                sum
+            }
+        };
+        // A zero base would never use up the budget, so the schedule would not end.
+        let nanos = deadline.as_nanos().checked_div(units).unwrap_or(u128::MAX);
+        let base = saturating_from_nanos(nanos.max(1));
+        let factor = if kind == BackoffKind::Exponential {
+            2.0
+        } else {
+            1.0
+        };
+        let budget = Self::new(kind, base, factor)
+            .take(delays as usize)
+            .fold(Duration::default(), Duration::saturating_add);
+        Self::new(kind, base, factor).with_budget(budget)
+    }
+
+    fn new(kind: BackoffKind, base: Duration, factor: f64) -> Self {
+        Backoff {
+            kind,
+            factor,
+            cap: None,
+            jitter: Jitter::None,
+            budget: None,
+            #[cfg(feature = "random")]
+            rng: None,
+            #[cfg(feature = "random")]
+            spread: None,
+            current: base,
+            next: base,
+        }
+    }
+
+    /// Never wait longer than `cap`.
+    pub fn with_cap(mut self, cap: Duration) -> Self {
+        self.cap = Some(cap);
+        self
+    }
+
+    /// Apply the given jitter to each delay.
+    pub fn with_jitter(mut self, jitter: Jitter) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// With full jitter, wait a fixed fraction of each delay derived from a hash of `key`, such as
+    /// the ID of the client, instead of a random one, as with `Jittered::spread_by_key`.
+    ///
+    /// This is enabled with the default `"random"` feature.
+    #[cfg(feature = "random")]
+    pub fn spread_by_key<K>(mut self, key: K) -> Self
+    where
+        K: AsRef<[u8]>,
+    {
+        self.spread = Some(random::spread(key.as_ref()));
+        self
+    }
+
+    /// End the schedule before the total delay exceeds `budget`.
+    pub fn with_budget(mut self, budget: Duration) -> Self {
+        self.budget = Some(budget);
+        self
+    }
+
+    /// The kind of schedule.
+    pub fn kind(&self) -> BackoffKind {
+        self.kind
+    }
+}
+
+#[cfg(feature = "std")]
+impl Iterator for Backoff {
+    type Item = Duration;
+
+    fn next(&mut self) -> Option<Duration> {
+        let mut delay = self.current;
+
+        match self.kind {
+            BackoffKind::None | BackoffKind::Fixed => {}
+            BackoffKind::Exponential => {
+                let nanos = self.current.as_nanos() as f64 * self.factor;
+                self.current = if nanos < u128::MAX as f64 {
+                    saturating_from_nanos(nanos as u128)
+                } else {
+                    Duration::MAX
+                };
+            }
+            BackoffKind::Fibonacci => {
+                let next = self.current.saturating_add(self.next);
+                self.current = self.next;
+                self.next = next;
+            }
+        }
+
+        if let Some(cap) = self.cap {
+            delay = delay.min(cap);
+        }
+        #[cfg(feature = "random")]
+        if self.jitter == Jitter::Full {
+            delay = match self.spread {
+                Some(spread) => jitter_scaled(delay, spread),
+                None => random::draw(self.rng.as_ref(), |rng| jitter_from(delay, rng)),
+            };
+        }
+        if let Some(budget) = self.budget {
+            self.budget = Some(budget.checked_sub(delay)?);
+        }
+
+        Some(delay)
     }
 }
 
+#[cfg(feature = "std")]
+impl fmt::Display for Backoff {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            BackoffKind::None => return formatter.write_str("no delay"),
+            BackoffKind::Fixed => write!(formatter, "fixed({:?}", self.current)?,
+            BackoffKind::Exponential => write!(
+                formatter,
+                "exponential({:?}, x{}",
+                self.current, self.factor
+            )?,
+            BackoffKind::Fibonacci => write!(formatter, "fibonacci({:?}", self.current)?,
+        }
+        if let Some(cap) = self.cap {
+            write!(formatter, ", cap={:?}", cap)?;
+        }
+        #[cfg(feature = "random")]
+        if self.jitter == Jitter::Full {
+            formatter.write_str(", jitter=full")?;
+            if self.spread.is_some() {
+                formatter.write_str(", spread by key")?;
+            }
+        }
+        if let Some(budget) = self.budget {
+            write!(formatter, ", budget={:?}", budget)?;
+        }
+        formatter.write_str(")")
+    }
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn fit() {
+    let minute = Duration::from_secs(60);
+    for &kind in &[
+        BackoffKind::Fixed,
+        BackoffKind::Exponential,
+        BackoffKind::Fibonacci,
+    ] {
+        let delays: Vec<_> = Backoff::fit(minute, 6, kind).collect();
+        assert_eq!(delays.len(), 5, "{:?}", kind);
+        let total: Duration = delays.iter().sum();
+        assert!(total <= minute && total > minute - Duration::from_micros(1));
+    }
+
+    assert_eq!(
+        Backoff::fit(minute, 6, BackoffKind::Fibonacci)
+            .map(|delay| delay.as_secs())
+            .collect::<Vec<_>>(),
+        vec![5, 5, 10, 15, 25]
+    );
+    assert_eq!(Backoff::fit(minute, 1, BackoffKind::Fixed).count(), 0);
+    assert_eq!(
+        Backoff::fit(Duration::default(), 1, BackoffKind::Fixed).count(),
+        0
+    );
+    assert_eq!(
+        Backoff::fit(Duration::from_secs(1), 200, BackoffKind::Exponential).next(),
+        Some(Duration::from_nanos(1))
+    );
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn backoff() {
+    let delays: Vec<_> = Backoff::exponential(Duration::from_millis(100), 2.0)
+        .with_cap(Duration::from_millis(300))
+        .with_budget(Duration::from_millis(700))
+        .collect();
+    assert_eq!(
+        delays,
+        [100, 200, 300]
+            .iter()
+            .map(|&millis| Duration::from_millis(millis))
+            .collect::<Vec<_>>()
+    );
+
+    let mut fibonacci = Backoff::fibonacci(Duration::from_millis(10));
+    assert_eq!(fibonacci.nth(3), Some(Duration::from_millis(30)));
+
+    #[cfg(feature = "random")]
+    let jittered = Backoff::fixed(Duration::from_millis(10)).with_jitter(Jitter::Full);
+    #[cfg(feature = "random")]
+    assert!(jittered
+        .take(10)
+        .all(|delay| delay <= Duration::from_millis(10)));
+}
+
 /// Apply full random jitter to a duration.
+///
+/// With the `"integer-jitter"` feature, this and every other jitter in this module is computed
+/// with `jitter_scaled` instead of floating-point arithmetic.
+#[cfg(feature = "random")]
 pub fn jitter(duration: Duration) -> Duration {
-    let jitter = random::<f64>();
+    random::draw(None, |rng| jitter_from(duration, rng))
+}
+
+/// A random offset between zero and `max` that stays the same for the life of the process, to
+/// desynchronize the replicas of a fleet that restart together, such as by waiting it before the
+/// first attempt, or adding it to the first delay with `Policy::with_splay`.
+///
+/// The offset is a fraction of `max` drawn the first time it is asked for, so every call in the
+/// process gets the same fraction, while other processes get their own.
+///
+/// ```rust
+/// # use std::time::Duration;
+/// use retry::delay::splay;
+///
+/// let offset = splay(Duration::from_secs(30));
+/// assert!(offset <= Duration::from_secs(30));
+/// assert_eq!(splay(Duration::from_secs(30)), offset);
+/// ```
+#[cfg(feature = "random")]
+pub fn splay(max: Duration) -> Duration {
+    static FRACTION: std::sync::OnceLock<u32> = std::sync::OnceLock::new();
+    let fraction = *FRACTION.get_or_init(|| random::draw(None, |rng| rand::RngCore::next_u32(rng)));
+    jitter_scaled(max, fraction)
+}
+
+/// Scale a duration by `random / 2^32`, using only integer arithmetic, to jitter it with a random
+/// number from any source. The result is between zero and the duration, and only reaches the
+/// duration when it is zero.
+///
+/// This is available without the `"random"` and `"std"` features, for targets that bring their
+/// own random numbers and handle floating-point numbers poorly.
+///
+/// ```rust
+/// # use std::time::Duration;
+/// use retry::delay::jitter_scaled;
+///
+/// assert_eq!(jitter_scaled(Duration::from_secs(10), u32::MAX / 2), Duration::from_nanos(4_999_999_997));
+/// assert_eq!(jitter_scaled(Duration::from_secs(10), 0), Duration::default());
+/// ```
+pub const fn jitter_scaled(duration: Duration, random: u32) -> Duration {
+    saturating_from_nanos((duration.as_nanos() * random as u128) >> 32)
+}
+
+/// Convert a number of nanoseconds into a duration, saturating at `Duration::MAX`.
+const fn saturating_from_nanos(nanos: u128) -> Duration {
+    const NANOS_PER_SEC: u128 = 1_000_000_000;
+
+    let secs = nanos / NANOS_PER_SEC;
+    if secs > u64::MAX as u128 {
+        Duration::MAX
+    } else {
+        Duration::new(secs as u64, (nanos % NANOS_PER_SEC) as u32)
+    }
+}
+
+/// Jitter a duration with a random number drawn from `rng`.
+#[cfg(all(feature = "random", feature = "integer-jitter"))]
+fn jitter_from(duration: Duration, rng: &mut dyn rand::RngCore) -> Duration {
+    jitter_scaled(duration, rng.next_u32())
+}
+
+/// Jitter a duration with a random number drawn from `rng`, scaling it by a random number between
+/// zero and one.
+#[cfg(all(feature = "random", not(feature = "integer-jitter")))]
+fn jitter_from(duration: Duration, rng: &mut dyn rand::RngCore) -> Duration {
+    use rand::Rng;
+
+    let jitter: f64 = rng.gen();
     let secs = ((duration.as_secs() as f64) * jitter).ceil() as u64;
     let nanos = ((f64::from(duration.subsec_nanos())) * jitter).ceil() as u32;
     Duration::new(secs, nanos)
 }
+
+#[cfg(feature = "random")]
+#[test]
+fn display() {
+    assert_eq!(
+        Exponential::from_millis(10).to_string(),
+        "exponential(10ms, x10)"
+    );
+    assert_eq!(Fibonacci::from_millis(10).to_string(), "fibonacci(10ms)");
+    assert_eq!(Fixed::from_millis(1500).to_string(), "fixed(1.5s)");
+    assert_eq!(NoDelay.to_string(), "no delay");
+    assert_eq!(
+        Range::from_millis_exclusive(10, 20).to_string(),
+        "range(10ms..20ms)"
+    );
+    assert_eq!(
+        Range::from_millis_inclusive(10, 20).to_string(),
+        "range(10ms..=20ms)"
+    );
+    assert_eq!(
+        Backoff::exponential(Duration::from_millis(100), 2.0)
+            .with_cap(Duration::from_secs(30))
+            .with_jitter(Jitter::Full)
+            .to_string(),
+        "exponential(100ms, x2, cap=30s, jitter=full)"
+    );
+    assert_eq!(Backoff::none().to_string(), "no delay");
+}
+
+#[test]
+fn integer_jitter() {
+    assert_eq!(
+        jitter_scaled(Duration::from_secs(1), 1 << 31),
+        Duration::from_millis(500)
+    );
+    assert!(jitter_scaled(Duration::MAX, u32::MAX) < Duration::MAX);
+    assert!(jitter_scaled(Duration::from_nanos(1), u32::MAX) < Duration::from_nanos(1));
+}
+
+#[cfg(feature = "random")]
+#[test]
+fn strategies_are_send_and_sync() {
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    assert_send_sync::<Exponential>();
+    assert_send_sync::<Fibonacci>();
+    assert_send_sync::<Fixed>();
+    assert_send_sync::<NoDelay>();
+    assert_send_sync::<Range>();
+    assert_send_sync::<Backoff>();
+    assert_send_sync::<Adaptive<Fixed>>();
+    assert_send_sync::<CheckedDelay<Fixed>>();
+    assert_send_sync::<DeadlineFraction>();
+    assert_send_sync::<TimeOfDay<Fixed>>();
+    assert_send_sync::<GrowingJitter<Range>>();
+    assert_send_sync::<Jittered<Backoff>>();
+    #[cfg(feature = "rand_distr")]
+    assert_send_sync::<Distributed<rand_distr::Exp<f64>>>();
+    assert_send_sync::<crate::Policy<core::iter::Take<Range>>>();
+}
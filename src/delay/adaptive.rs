@@ -0,0 +1,268 @@
+use std::{
+    collections::VecDeque,
+    fmt,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use crate::{
+    listener::{AttemptOutcome, RetryListener},
+    CorrelationId,
+};
+
+/// The failure rate of the last attempts made through the policies it listens to.
+///
+/// The tracker is a listener: adding it to a policy with `Policy::with_listener` makes every call
+/// through the policy record the outcome of its attempts in it. Clones share the same window, so
+/// a tracker can be shared by several policies, and by the `Adaptive` strategies that read it.
+#[derive(Clone, Debug)]
+pub struct FailureRate {
+    window: Arc<Mutex<Window>>,
+}
+
+#[derive(Debug)]
+struct Window {
+    outcomes: VecDeque<bool>,
+    size: usize,
+    failures: usize,
+}
+
+impl FailureRate {
+    /// Track the outcomes of the last `window` attempts.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `window` is zero.
+    pub fn new(window: usize) -> Self {
+        assert!(window > 0, "a failure rate must track at least one attempt");
+        FailureRate {
+            window: Arc::new(Mutex::new(Window {
+                outcomes: VecDeque::with_capacity(window),
+                size: window,
+                failures: 0,
+            })),
+        }
+    }
+
+    /// Record the outcome of an attempt, forgetting the oldest one if the window is full.
+    pub fn record(&self, failed: bool) {
+        let mut window = self.lock();
+        if window.outcomes.len() == window.size && window.outcomes.pop_front() == Some(true) {
+            window.failures -= 1;
+        }
+        window.outcomes.push_back(failed);
+        if failed {
+            window.failures += 1;
+        }
+    }
+
+    /// The fraction of the attempts in the window that failed, or zero if none were recorded.
+    pub fn rate(&self) -> f64 {
+        let window = self.lock();
+        if window.outcomes.is_empty() {
+            0.0
+        } else {
+            window.failures as f64 / window.outcomes.len() as f64
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Window> {
+        self.window
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl RetryListener for FailureRate {
+    fn on_attempt_end(&self, _: &CorrelationId, _: u64, outcome: AttemptOutcome<'_>, _: Duration) {
+        self.record(!outcome.is_ok());
+    }
+}
+
+/// Lengthens the delays of a strategy while the failure rate tracked by a `FailureRate` is above
+/// a threshold, so that every call slows down together when a dependency is struggling.
+///
+/// At or below the threshold, 50% by default, delays are unchanged. Above it, they are scaled by a
+/// factor that grows linearly with the failure rate, up to the maximum factor, 10 by default, when
+/// every attempt in the window failed.
+///
+/// ```rust
+/// # use std::time::Duration;
+/// use retry::delay::{Adaptive, FailureRate, Fixed};
+/// use retry::Policy;
+///
+/// let failures = FailureRate::new(100);
+/// let policy = Policy::new(Adaptive::new(Fixed::from_millis(10), failures.clone()))
+///     .with_listener(failures.clone())
+///     .with_max_attempts(3);
+///
+/// assert_eq!(policy.delays().next(), Some(Duration::from_millis(10)));
+/// let _ = policy.retry(|| Err::<(), _>("down"));
+/// assert_eq!(failures.rate(), 1.0);
+/// assert_eq!(policy.delays().next(), Some(Duration::from_millis(100)));
+/// ```
+#[derive(Clone, Debug)]
+pub struct Adaptive<I> {
+    delays: I,
+    failures: FailureRate,
+    threshold: f64,
+    max_factor: f64,
+}
+
+impl<I> Adaptive<I> {
+    /// Scale the delays of the given strategy by the failure rate tracked by `failures`.
+    pub fn new(delays: I, failures: FailureRate) -> Self {
+        Adaptive {
+            delays,
+            failures,
+            threshold: 0.5,
+            max_factor: 10.0,
+        }
+    }
+
+    /// Only lengthen delays while the failure rate is above `threshold`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `threshold` is not between 0 and 1.
+    pub fn with_threshold(mut self, threshold: f64) -> Self {
+        assert!(
+            (0.0..=1.0).contains(&threshold),
+            "failure rate threshold {} is not between 0 and 1",
+            threshold
+        );
+        self.threshold = threshold;
+        self
+    }
+
+    /// Never lengthen delays by more than `max_factor` times.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_factor` is less than 1.
+    pub fn with_max_factor(mut self, max_factor: f64) -> Self {
+        assert!(
+            max_factor >= 1.0,
+            "maximum factor {} would shorten delays",
+            max_factor
+        );
+        self.max_factor = max_factor;
+        self
+    }
+
+    /// The factor delays are currently scaled by.
+    pub fn factor(&self) -> f64 {
+        let rate = self.failures.rate();
+        if rate <= self.threshold {
+            1.0
+        } else {
+            1.0 + (rate - self.threshold) / (1.0 - self.threshold) * (self.max_factor - 1.0)
+        }
+    }
+}
+
+impl<I> Iterator for Adaptive<I>
+where
+    I: Iterator<Item = Duration>,
+{
+    type Item = Duration;
+
+    fn next(&mut self) -> Option<Duration> {
+        let delay = self.delays.next()?;
+        Some(
+            Duration::try_from_secs_f64(delay.as_secs_f64() * self.factor())
+                .unwrap_or(Duration::MAX),
+        )
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.delays.size_hint()
+    }
+}
+
+impl<I> fmt::Display for Adaptive<I>
+where
+    I: fmt::Display,
+{
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            formatter,
+            "{} scaled up to x{} above {}% failures",
+            self.delays,
+            self.max_factor,
+            self.threshold * 100.0
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{Adaptive, FailureRate};
+    use crate::{delay::Fixed, Policy};
+
+    #[test]
+    fn tracks_a_sliding_window() {
+        let failures = FailureRate::new(4);
+        assert_eq!(failures.rate(), 0.0);
+
+        for &failed in &[true, true, false, true] {
+            failures.record(failed);
+        }
+        assert_eq!(failures.rate(), 0.75);
+
+        failures.record(false);
+        failures.record(false);
+        assert_eq!(failures.rate(), 0.25);
+    }
+
+    #[test]
+    fn scales_delays_above_the_threshold() {
+        let failures = FailureRate::new(4);
+        let delays = Adaptive::new(Fixed::from_millis(100), failures.clone())
+            .with_threshold(0.5)
+            .with_max_factor(5.0);
+        let next = || delays.clone().next().unwrap();
+
+        failures.record(true);
+        failures.record(false);
+        assert_eq!(next(), Duration::from_millis(100));
+
+        failures.record(true);
+        failures.record(true);
+        assert_eq!(next(), Duration::from_millis(300));
+
+        failures.record(true);
+        failures.record(true);
+        assert_eq!(next(), Duration::from_millis(500));
+    }
+
+    #[test]
+    fn policies_record_attempts_through_the_listener() {
+        let failures = FailureRate::new(10);
+        let policy = Policy::new(Fixed::from_millis(0).take(3)).with_listener(failures.clone());
+        let mut attempts = 0;
+
+        let result = policy.retry(|| {
+            attempts += 1;
+            if attempts < 4 {
+                Err("down")
+            } else {
+                Ok(())
+            }
+        });
+
+        assert_eq!(result, Ok(()));
+        assert_eq!(failures.rate(), 0.75);
+    }
+
+    #[test]
+    fn display() {
+        let delays = Adaptive::new(Fixed::from_millis(10), FailureRate::new(1));
+        assert_eq!(
+            delays.to_string(),
+            "fixed(10ms) scaled up to x10 above 50% failures"
+        );
+    }
+}
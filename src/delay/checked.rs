@@ -0,0 +1,257 @@
+use core::{fmt, time::Duration};
+
+/// Checks that a delay strategy, usually one written by a user, keeps the invariants it is
+/// expected to keep.
+///
+/// Each invariant is opted into: delays that never decrease, delays bounded by a cap, and a
+/// bounded number of delays. As an iterator, the wrapper panics on the first violation; frameworks
+/// that prefer to report it call `try_next` instead.
+///
+/// ```rust
+/// # use std::time::Duration;
+/// use retry::delay::{CheckedDelay, Exponential};
+///
+/// let mut delays = CheckedDelay::new(Exponential::from_millis(10))
+///     .non_decreasing()
+///     .with_cap(Duration::from_millis(500))
+///     .debug_only();
+///
+/// assert_eq!(delays.try_next(), Ok(Some(Duration::from_millis(10))));
+/// assert_eq!(delays.try_next(), Ok(Some(Duration::from_millis(100))));
+/// # if cfg!(debug_assertions) {
+/// assert_eq!(
+///     delays.try_next().unwrap_err().to_string(),
+///     "delay 1s is above the cap of 500ms"
+/// );
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct CheckedDelay<I> {
+    delays: I,
+    enabled: bool,
+    non_decreasing: bool,
+    cap: Option<Duration>,
+    max_delays: Option<usize>,
+    previous: Option<Duration>,
+    count: usize,
+    violation: Option<Violation>,
+}
+
+impl<I> CheckedDelay<I> {
+    /// Check the delays of the given strategy. No invariant is checked until one is opted into.
+    pub fn new(delays: I) -> Self {
+        CheckedDelay {
+            delays,
+            enabled: true,
+            non_decreasing: false,
+            cap: None,
+            max_delays: None,
+            previous: None,
+            count: 0,
+            violation: None,
+        }
+    }
+
+    /// Check that no delay is shorter than the one before it.
+    pub fn non_decreasing(mut self) -> Self {
+        self.non_decreasing = true;
+        self
+    }
+
+    /// Check that no delay is longer than `cap`.
+    pub fn with_cap(mut self, cap: Duration) -> Self {
+        self.cap = Some(cap);
+        self
+    }
+
+    /// Check that the strategy ends after at most `max_delays` delays.
+    pub fn with_max_delays(mut self, max_delays: usize) -> Self {
+        self.max_delays = Some(max_delays);
+        self
+    }
+
+    /// Only check the invariants in builds with debug assertions, passing the delays through
+    /// unchecked otherwise.
+    pub fn debug_only(mut self) -> Self {
+        self.enabled = cfg!(debug_assertions);
+        self
+    }
+
+    /// The wrapped strategy.
+    pub fn into_inner(self) -> I {
+        self.delays
+    }
+}
+
+impl<I> CheckedDelay<I>
+where
+    I: Iterator<Item = Duration>,
+{
+    /// The next delay, or the invariant it violates.
+    ///
+    /// After a violation, the strategy is not advanced any further by the following calls, which
+    /// keep returning the same error.
+    pub fn try_next(&mut self) -> Result<Option<Duration>, Violation> {
+        if !self.enabled {
+            return Ok(self.delays.next());
+        }
+        if let Some(violation) = self.violation {
+            return Err(violation);
+        }
+
+        let delay = match self.delays.next() {
+            Some(delay) => delay,
+            None => return Ok(None),
+        };
+        let violation = match (self.previous, self.cap, self.max_delays) {
+            (_, _, Some(max)) if self.count >= max => Violation::TooManyDelays { max },
+            (_, Some(cap), _) if delay > cap => Violation::AboveCap { delay, cap },
+            (Some(previous), _, _) if self.non_decreasing && delay < previous => {
+                Violation::Decreasing { previous, delay }
+            }
+            _ => {
+                self.previous = Some(delay);
+                self.count += 1;
+                return Ok(Some(delay));
+            }
+        };
+        self.violation = Some(violation);
+        Err(violation)
+    }
+}
+
+impl<I> Iterator for CheckedDelay<I>
+where
+    I: Iterator<Item = Duration>,
+{
+    type Item = Duration;
+
+    /// # Panics
+    ///
+    /// Panics if the delay violates one of the invariants checked.
+    fn next(&mut self) -> Option<Duration> {
+        match self.try_next() {
+            Ok(delay) => delay,
+            Err(violation) => panic!("invalid delay strategy: {}", violation),
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.delays.size_hint()
+    }
+}
+
+impl<I> fmt::Display for CheckedDelay<I>
+where
+    I: fmt::Display,
+{
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.delays.fmt(formatter)
+    }
+}
+
+/// An invariant broken by a strategy wrapped in a `CheckedDelay`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Violation {
+    /// A delay was shorter than the one before it.
+    Decreasing {
+        /// The delay before.
+        previous: Duration,
+        /// The shorter delay.
+        delay: Duration,
+    },
+    /// A delay was longer than the cap.
+    AboveCap {
+        /// The longer delay.
+        delay: Duration,
+        /// The cap.
+        cap: Duration,
+    },
+    /// The strategy produced more delays than allowed.
+    TooManyDelays {
+        /// The number of delays allowed.
+        max: usize,
+    },
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Violation::Decreasing { previous, delay } => write!(
+                formatter,
+                "delay {:?} is shorter than the previous delay of {:?}",
+                delay, previous
+            ),
+            Violation::AboveCap { delay, cap } => {
+                write!(formatter, "delay {:?} is above the cap of {:?}", delay, cap)
+            }
+            Violation::TooManyDelays { max } => {
+                write!(formatter, "strategy did not end after {} delays", max)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Violation {}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{CheckedDelay, Violation};
+    use crate::delay::{Fixed, NoDelay};
+
+    fn millis(delays: &[u64]) -> impl Iterator<Item = Duration> + Clone {
+        delays
+            .iter()
+            .map(|&millis| Duration::from_millis(millis))
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+
+    #[test]
+    fn passes_schedules_that_keep_the_invariants() {
+        let delays = CheckedDelay::new(millis(&[10, 10, 20]))
+            .non_decreasing()
+            .with_cap(Duration::from_millis(20))
+            .with_max_delays(3);
+
+        assert_eq!(
+            delays.collect::<Vec<_>>(),
+            millis(&[10, 10, 20]).collect::<Vec<_>>()
+        );
+        assert_eq!(CheckedDelay::new(millis(&[20, 10])).count(), 2);
+    }
+
+    #[test]
+    fn reports_violations() {
+        let mut delays = CheckedDelay::new(millis(&[20, 10])).non_decreasing();
+        delays.try_next().unwrap();
+        assert_eq!(
+            delays.try_next(),
+            Err(Violation::Decreasing {
+                previous: Duration::from_millis(20),
+                delay: Duration::from_millis(10),
+            })
+        );
+
+        let mut delays = CheckedDelay::new(NoDelay).with_max_delays(2);
+        assert_eq!(delays.try_next(), Ok(Some(Duration::default())));
+        assert_eq!(delays.try_next(), Ok(Some(Duration::default())));
+        assert_eq!(delays.try_next(), Err(Violation::TooManyDelays { max: 2 }));
+        assert_eq!(delays.try_next(), Err(Violation::TooManyDelays { max: 2 }));
+
+        let mut delays = CheckedDelay::new(millis(&[1])).with_max_delays(1);
+        delays.try_next().unwrap();
+        assert_eq!(delays.try_next(), Ok(None));
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid delay strategy: delay 2s is above the cap of 1s")]
+    fn panics_as_an_iterator() {
+        let _ = CheckedDelay::new(Fixed::from_millis(2000))
+            .with_cap(Duration::from_secs(1))
+            .next();
+    }
+}
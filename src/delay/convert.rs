@@ -0,0 +1,115 @@
+use std::{error::Error as StdError, fmt, time::Duration};
+
+/// A duration that may not fit in a `std::time::Duration`, such as a `chrono::Duration` or a
+/// `time::Duration`, which can be negative.
+///
+/// The conversions from those types are enabled with the `"chrono"` and `"time"` features. They
+/// let the settings of policies, such as budgets and timeouts, be given in those types:
+///
+/// ```rust
+/// # #[cfg(feature = "chrono")]
+/// # {
+/// use std::time::Duration;
+/// use retry::delay::{Backoff, TryIntoDuration};
+///
+/// let budget = chrono::Duration::seconds(5).try_into_duration().unwrap();
+/// let backoff = Backoff::fixed(Duration::from_millis(10)).with_budget(budget);
+/// # }
+/// ```
+pub trait TryIntoDuration {
+    /// Convert into a `std::time::Duration`, failing if the duration is negative.
+    fn try_into_duration(self) -> Result<Duration, NegativeDuration>;
+}
+
+impl TryIntoDuration for Duration {
+    fn try_into_duration(self) -> Result<Duration, NegativeDuration> {
+        Ok(self)
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl TryIntoDuration for chrono::Duration {
+    fn try_into_duration(self) -> Result<Duration, NegativeDuration> {
+        self.to_std().map_err(|_| NegativeDuration)
+    }
+}
+
+#[cfg(feature = "time")]
+impl TryIntoDuration for ::time::Duration {
+    fn try_into_duration(self) -> Result<Duration, NegativeDuration> {
+        use std::convert::TryFrom;
+
+        Duration::try_from(self).map_err(|_| NegativeDuration)
+    }
+}
+
+/// The error returned when converting a negative duration into a `std::time::Duration`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NegativeDuration;
+
+impl fmt::Display for NegativeDuration {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str("delays cannot be negative")
+    }
+}
+
+impl StdError for NegativeDuration {}
+
+/// Implement `TryFrom` of a foreign duration type for strategies built from a single duration.
+#[cfg(any(feature = "chrono", feature = "time"))]
+macro_rules! try_from_duration {
+    ($duration:ty => $($strategy:ty),+) => {
+        $(
+            impl std::convert::TryFrom<$duration> for $strategy {
+                type Error = NegativeDuration;
+
+                fn try_from(duration: $duration) -> Result<Self, NegativeDuration> {
+                    duration.try_into_duration().map(Self::from)
+                }
+            }
+        )+
+    };
+}
+
+#[cfg(feature = "chrono")]
+try_from_duration!(chrono::Duration => super::Exponential, super::Fibonacci, super::Fixed);
+#[cfg(feature = "time")]
+try_from_duration!(::time::Duration => super::Exponential, super::Fibonacci, super::Fixed);
+
+#[cfg(test)]
+mod tests {
+    #[cfg(any(feature = "chrono", feature = "time"))]
+    use std::{convert::TryFrom, time::Duration};
+
+    #[cfg(any(feature = "chrono", feature = "time"))]
+    use super::{super::Fixed, NegativeDuration, TryIntoDuration};
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn converts_chrono_durations() {
+        assert_eq!(
+            chrono::Duration::milliseconds(1500).try_into_duration(),
+            Ok(Duration::from_millis(1500))
+        );
+        assert_eq!(
+            chrono::Duration::milliseconds(-1).try_into_duration(),
+            Err(NegativeDuration)
+        );
+
+        let mut fixed = Fixed::try_from(chrono::Duration::milliseconds(10)).unwrap();
+        assert_eq!(fixed.next(), Some(Duration::from_millis(10)));
+    }
+
+    #[cfg(feature = "time")]
+    #[test]
+    fn converts_time_durations() {
+        assert_eq!(
+            ::time::Duration::milliseconds(1500).try_into_duration(),
+            Ok(Duration::from_millis(1500))
+        );
+        assert_eq!(
+            Fixed::try_from(::time::Duration::milliseconds(-1)).unwrap_err(),
+            NegativeDuration
+        );
+    }
+}
@@ -0,0 +1,162 @@
+use std::{
+    fmt,
+    sync::Arc,
+    time::{Duration, Instant, SystemTime},
+};
+
+use crate::clock::{Clock, SystemClock};
+
+/// Each retry waits a fraction of the time left before a deadline, such as the deadline
+/// propagated with an RPC, and the schedule ends when the deadline has passed.
+///
+/// Delays shrink as the deadline approaches, so attempts get more frequent towards the end. A floor
+/// keeps them from getting too short, though no delay ever goes past the deadline.
+///
+/// The deadline is kept on the wall clock as well as the monotonic one, and the time left is the
+/// shorter of the two, so that a machine that was suspended past the deadline, which the monotonic
+/// clock of some platforms does not count, ends the schedule when it wakes up. A wall clock set
+/// back does not extend the deadline.
+///
+/// ```rust
+/// # use std::time::{Duration, Instant};
+/// use retry::delay::DeadlineFraction;
+///
+/// let deadline = Instant::now() + Duration::from_secs(10);
+/// let mut delays = DeadlineFraction::new(deadline, 0.1).with_floor(Duration::from_millis(50));
+///
+/// let first = delays.next().unwrap();
+/// assert!(first <= Duration::from_secs(1) && first > Duration::from_millis(900));
+/// ```
+#[derive(Clone, Debug)]
+pub struct DeadlineFraction {
+    deadline: Instant,
+    wall_deadline: Option<SystemTime>,
+    fraction: f64,
+    floor: Duration,
+    clock: Option<Arc<dyn Clock>>,
+}
+
+impl DeadlineFraction {
+    /// Wait `fraction` of the time left before `deadline` before each retry.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `fraction` is not between 0 and 1.
+    pub fn new(deadline: Instant, fraction: f64) -> Self {
+        assert!(
+            (0.0..=1.0).contains(&fraction),
+            "fraction {} of the remaining time is not between 0 and 1",
+            fraction
+        );
+        let wall_deadline =
+            SystemTime::now().checked_add(deadline.saturating_duration_since(Instant::now()));
+        DeadlineFraction {
+            deadline,
+            wall_deadline,
+            fraction,
+            floor: Duration::default(),
+            clock: None,
+        }
+    }
+
+    /// Never wait less than `floor`, unless the deadline is closer than that.
+    pub fn with_floor(mut self, floor: Duration) -> Self {
+        self.floor = floor;
+        self
+    }
+
+    /// Read the current time from the given clock instead of the system clock, and only from it.
+    pub fn with_clock<C>(mut self, clock: C) -> Self
+    where
+        C: Clock + 'static,
+    {
+        self.clock = Some(Arc::new(clock));
+        self.wall_deadline = None;
+        self
+    }
+
+    /// The time left before the deadline.
+    pub fn remaining(&self) -> Duration {
+        let now = match self.clock {
+            Some(ref clock) => clock.now(),
+            None => SystemClock.now(),
+        };
+        let remaining = self.deadline.saturating_duration_since(now);
+        match self.wall_deadline {
+            Some(wall_deadline) => remaining.min(
+                wall_deadline
+                    .duration_since(SystemTime::now())
+                    .unwrap_or_default(),
+            ),
+            None => remaining,
+        }
+    }
+}
+
+impl Iterator for DeadlineFraction {
+    type Item = Duration;
+
+    fn next(&mut self) -> Option<Duration> {
+        let remaining = self.remaining();
+        if remaining == Duration::default() {
+            return None;
+        }
+        Some(
+            remaining
+                .mul_f64(self.fraction)
+                .max(self.floor)
+                .min(remaining),
+        )
+    }
+}
+
+impl fmt::Display for DeadlineFraction {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            formatter,
+            "{}% of {:?} remaining",
+            self.fraction * 100.0,
+            self.remaining()
+        )?;
+        if self.floor > Duration::default() {
+            write!(formatter, ", floor={:?}", self.floor)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant, SystemTime};
+
+    use super::DeadlineFraction;
+    use crate::clock::{Clock, MockClock};
+
+    #[test]
+    fn waits_a_fraction_of_the_remaining_time() {
+        let clock = MockClock::new();
+        let mut delays = DeadlineFraction::new(clock.now() + Duration::from_secs(10), 0.5)
+            .with_floor(Duration::from_secs(2))
+            .with_clock(clock.clone());
+
+        assert_eq!(delays.next(), Some(Duration::from_secs(5)));
+        clock.advance(Duration::from_secs(5));
+        assert_eq!(delays.next(), Some(Duration::from_millis(2500)));
+        clock.advance(Duration::from_secs(4));
+        assert_eq!(delays.next(), Some(Duration::from_secs(1)));
+        assert_eq!(delays.to_string(), "50% of 1s remaining, floor=2s");
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(delays.next(), None);
+    }
+
+    #[test]
+    fn ends_when_the_deadline_passed_on_the_wall_clock() {
+        let mut delays = DeadlineFraction::new(Instant::now() + Duration::from_secs(3600), 0.5);
+        assert!(delays.next().unwrap() > Duration::from_secs(1700));
+
+        // As if the machine had been suspended for two hours.
+        delays.wall_deadline = Some(SystemTime::now() - Duration::from_secs(3600));
+        assert_eq!(delays.remaining(), Duration::default());
+        assert_eq!(delays.next(), None);
+    }
+}
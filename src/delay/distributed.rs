@@ -0,0 +1,150 @@
+use std::{fmt, time::Duration};
+
+use rand::distributions::Distribution;
+
+use super::random::{draw, Randomized, SharedRng};
+
+/// Each retry uses a duration drawn from a probability distribution, such as the heavy-tailed
+/// distributions of `rand_distr`, in milliseconds. This strategy is enabled with the
+/// `"rand_distr"` feature.
+///
+/// Samples are clamped between a floor, zero by default, and an optional cap. Samples that are not
+/// numbers are replaced by the floor.
+///
+/// ```rust
+/// # use std::time::Duration;
+/// use rand_distr::Pareto;
+/// use retry::delay::Distributed;
+///
+/// let delays = Distributed::new(Pareto::new(10.0, 1.5).unwrap())
+///     .with_floor(Duration::from_millis(10))
+///     .with_cap(Duration::from_secs(5));
+///
+/// assert!(delays
+///     .take(100)
+///     .all(|delay| delay >= Duration::from_millis(10) && delay <= Duration::from_secs(5)));
+/// ```
+#[derive(Clone, Debug)]
+pub struct Distributed<D> {
+    distribution: D,
+    floor: Duration,
+    cap: Option<Duration>,
+    rng: Option<SharedRng>,
+}
+
+impl<D> Distributed<D> {
+    /// Draw delays in milliseconds from the given distribution.
+    pub fn new(distribution: D) -> Self {
+        Distributed {
+            distribution,
+            floor: Duration::default(),
+            cap: None,
+            rng: None,
+        }
+    }
+
+    /// Never wait less than `floor`.
+    pub fn with_floor(mut self, floor: Duration) -> Self {
+        self.floor = floor;
+        self
+    }
+
+    /// Never wait longer than `cap`.
+    pub fn with_cap(mut self, cap: Duration) -> Self {
+        self.cap = Some(cap);
+        self
+    }
+}
+
+impl<D> Randomized for Distributed<D> {
+    fn with_rng(mut self, rng: SharedRng) -> Self {
+        self.rng = Some(rng);
+        self
+    }
+}
+
+impl<D> Iterator for Distributed<D>
+where
+    D: Distribution<f64>,
+{
+    type Item = Duration;
+
+    fn next(&mut self) -> Option<Duration> {
+        let distribution = &self.distribution;
+        let millis = draw(self.rng.as_ref(), |rng| distribution.sample(rng));
+        let delay = if millis.is_nan() {
+            self.floor
+        } else {
+            Duration::try_from_secs_f64(millis.max(0.0) / 1000.0)
+                .unwrap_or(Duration::MAX)
+                .max(self.floor)
+        };
+        Some(match self.cap {
+            Some(cap) => delay.min(cap),
+            None => delay,
+        })
+    }
+}
+
+impl<D> fmt::Display for Distributed<D>
+where
+    D: fmt::Debug,
+{
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(formatter, "distributed({:?}", self.distribution)?;
+        if self.floor > Duration::default() {
+            write!(formatter, ", floor={:?}", self.floor)?;
+        }
+        if let Some(cap) = self.cap {
+            write!(formatter, ", cap={:?}", cap)?;
+        }
+        formatter.write_str(")")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use rand_distr::Weibull;
+
+    use super::Distributed;
+    use crate::delay::{Randomized, SharedRng};
+
+    #[test]
+    fn clamps_samples() {
+        let delays = Distributed::new(Weibull::new(100.0, 0.5).unwrap())
+            .with_floor(Duration::from_millis(5))
+            .with_cap(Duration::from_millis(200))
+            .with_rng(SharedRng::seeded(3));
+
+        let delays: Vec<_> = delays.take(1000).collect();
+        assert!(delays.contains(&Duration::from_millis(5)));
+        assert!(delays.contains(&Duration::from_millis(200)));
+        assert!(
+            delays
+                .iter()
+                .all(|&delay| delay >= Duration::from_millis(5)
+                    && delay <= Duration::from_millis(200))
+        );
+    }
+
+    #[test]
+    fn saturates_long_samples() {
+        let mut delays =
+            Distributed::new(rand::distributions::Uniform::new(f64::MAX / 2.0, f64::MAX))
+                .with_floor(Duration::from_millis(1));
+        assert_eq!(delays.next(), Some(Duration::MAX));
+    }
+
+    #[test]
+    fn display() {
+        let delays = Distributed::new(Weibull::new(1.0, 1.0).unwrap())
+            .with_floor(Duration::from_millis(1))
+            .with_cap(Duration::from_secs(1));
+        assert_eq!(
+            delays.to_string(),
+            "distributed(Weibull { inv_shape: 1.0, scale: 1.0 }, floor=1ms, cap=1s)"
+        );
+    }
+}
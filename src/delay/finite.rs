@@ -0,0 +1,108 @@
+use core::{
+    array, iter,
+    ops::{Range as StdRange, RangeInclusive},
+    option, slice,
+};
+
+use alloc::{collections::vec_deque, vec};
+
+use super::CheckedDelay;
+#[cfg(feature = "std")]
+use super::{Adaptive, TimeOfDay};
+#[cfg(feature = "random")]
+use super::{GrowingJitter, Jittered, LatencyJitter};
+
+/// A delay strategy that is known to end, so that a call retried with it always gives up.
+///
+/// `retry` only accepts strategies that implement it, since a strategy such as
+/// `Fixed::from_millis(10)` never ends, and a call retried with it while its operation keeps
+/// failing never returns. Strategies are made finite with `take`, or by collecting their delays,
+/// and adapters that keep a strategy finite, such as `map` and `chain`, keep implementing it.
+/// A call that should retry until it succeeds, however long it takes, says so with
+/// `retry_forever`.
+///
+/// ```compile_fail
+/// # use retry::{delay::Fixed, retry};
+/// let _ = retry(Fixed::from_millis(10), || Err::<(), _>("down"));
+/// ```
+///
+/// ```rust
+/// # use retry::{delay::Fixed, retry};
+/// let error = retry(Fixed::from_millis(10).take(3), || Err::<(), _>("down")).unwrap_err();
+/// assert_eq!(error.tries(), 4);
+/// ```
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` is not known to end, so retrying with it may never give up",
+    note = "limit the delays with `.take(n)`, or retry without end with `retry_forever`"
+)]
+pub trait FiniteDelay: Iterator {}
+
+impl<I> FiniteDelay for iter::Take<I> where I: Iterator {}
+impl<T> FiniteDelay for iter::Empty<T> {}
+impl<T> FiniteDelay for iter::Once<T> {}
+impl<T> FiniteDelay for option::IntoIter<T> {}
+impl<T> FiniteDelay for vec::IntoIter<T> {}
+impl<T> FiniteDelay for vec_deque::IntoIter<T> {}
+impl<T, const N: usize> FiniteDelay for array::IntoIter<T, N> {}
+impl<T> FiniteDelay for slice::Iter<'_, T> {}
+impl<T> FiniteDelay for StdRange<T> where StdRange<T>: Iterator {}
+impl<T> FiniteDelay for RangeInclusive<T> where RangeInclusive<T>: Iterator {}
+
+impl<A, B> FiniteDelay for iter::Chain<A, B>
+where
+    A: FiniteDelay,
+    B: FiniteDelay<Item = A::Item>,
+{
+}
+impl<'a, I, T> FiniteDelay for iter::Cloned<I>
+where
+    I: FiniteDelay<Item = &'a T>,
+    T: Clone + 'a,
+{
+}
+impl<'a, I, T> FiniteDelay for iter::Copied<I>
+where
+    I: FiniteDelay<Item = &'a T>,
+    T: Copy + 'a,
+{
+}
+impl<I, P> FiniteDelay for iter::Filter<I, P>
+where
+    I: FiniteDelay,
+    P: FnMut(&I::Item) -> bool,
+{
+}
+impl<I> FiniteDelay for iter::Fuse<I> where I: FiniteDelay {}
+impl<I, F> FiniteDelay for iter::Inspect<I, F>
+where
+    I: FiniteDelay,
+    F: FnMut(&I::Item),
+{
+}
+impl<B, I, F> FiniteDelay for iter::Map<I, F>
+where
+    I: FiniteDelay,
+    F: FnMut(I::Item) -> B,
+{
+}
+impl<I> FiniteDelay for iter::Peekable<I> where I: FiniteDelay {}
+impl<I> FiniteDelay for iter::Skip<I> where I: FiniteDelay {}
+impl<I> FiniteDelay for iter::StepBy<I> where I: FiniteDelay {}
+impl<I, P> FiniteDelay for iter::TakeWhile<I, P>
+where
+    I: FiniteDelay,
+    P: FnMut(&I::Item) -> bool,
+{
+}
+
+impl<I> FiniteDelay for CheckedDelay<I> where I: FiniteDelay<Item = core::time::Duration> {}
+#[cfg(feature = "std")]
+impl<I> FiniteDelay for Adaptive<I> where I: FiniteDelay<Item = std::time::Duration> {}
+#[cfg(feature = "std")]
+impl<I> FiniteDelay for TimeOfDay<I> where I: FiniteDelay<Item = std::time::Duration> {}
+#[cfg(feature = "random")]
+impl<I> FiniteDelay for Jittered<I> where I: FiniteDelay<Item = std::time::Duration> {}
+#[cfg(feature = "random")]
+impl<I> FiniteDelay for GrowingJitter<I> where I: FiniteDelay<Item = std::time::Duration> {}
+#[cfg(feature = "random")]
+impl<I> FiniteDelay for LatencyJitter<I> where I: FiniteDelay<Item = std::time::Duration> {}
@@ -0,0 +1,217 @@
+use std::{fmt, time::Duration};
+
+#[cfg(feature = "random")]
+use super::Range;
+use super::{Backoff, BackoffKind, Exponential, Fibonacci, Fixed, Jitter, NoDelay};
+
+/// A delay strategy that can tell what schedule it produces, so that metrics and traces can be
+/// tagged with the backoff in effect without parsing its `Debug` or `Display` output.
+///
+/// The kind is a stable lowercase name, such as `"exponential"`, and the parameters are the
+/// settings of the schedule as typed values, named the same across strategies: `base`, `factor`,
+/// `cap` and so on. Adapters such as `take` hide the strategy they wrap, so a policy should be
+/// introspected through `Policy::strategy_kind` and `Policy::strategy_params` with its strategy
+/// before adapting it.
+///
+/// ```rust
+/// # use std::time::Duration;
+/// use retry::delay::{Backoff, Introspect, Param};
+///
+/// let backoff =
+///     Backoff::exponential(Duration::from_millis(10), 2.0).with_cap(Duration::from_secs(1));
+///
+/// assert_eq!(Introspect::kind(&backoff), "exponential");
+/// assert_eq!(
+///     backoff.params(),
+///     vec![
+///         ("base", Param::Duration(Duration::from_millis(10))),
+///         ("factor", Param::Factor(2.0)),
+///         ("cap", Param::Duration(Duration::from_secs(1))),
+///     ]
+/// );
+/// ```
+pub trait Introspect {
+    /// The name of the kind of schedule, which stays the same from one release to the next.
+    fn kind(&self) -> &'static str;
+
+    /// The settings of the schedule, in a fixed order, leaving out the options that are not set.
+    fn params(&self) -> Vec<(&'static str, Param)>;
+}
+
+/// The value of a setting of a delay strategy.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Param {
+    /// A delay, such as the base delay or the cap.
+    Duration(Duration),
+    /// A multiplier, such as the growth factor of an exponential schedule.
+    Factor(f64),
+    /// A switch, such as whether the maximum of a range can be drawn.
+    Flag(bool),
+    /// One of a fixed set of names, such as the kind of jitter.
+    Name(&'static str),
+}
+
+/// Formats the value as it would be written in a tag: durations as with `Debug`, such as `10ms`.
+impl fmt::Display for Param {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Param::Duration(duration) => write!(formatter, "{:?}", duration),
+            Param::Factor(factor) => write!(formatter, "{}", factor),
+            Param::Flag(flag) => write!(formatter, "{}", flag),
+            Param::Name(name) => formatter.write_str(name),
+        }
+    }
+}
+
+impl Introspect for Exponential {
+    fn kind(&self) -> &'static str {
+        "exponential"
+    }
+
+    fn params(&self) -> Vec<(&'static str, Param)> {
+        vec![
+            ("base", Param::Duration(self.current)),
+            ("factor", Param::Factor(self.factor as f64)),
+        ]
+    }
+}
+
+impl Introspect for Fibonacci {
+    fn kind(&self) -> &'static str {
+        "fibonacci"
+    }
+
+    fn params(&self) -> Vec<(&'static str, Param)> {
+        vec![("base", Param::Duration(self.curr))]
+    }
+}
+
+impl Introspect for Fixed {
+    fn kind(&self) -> &'static str {
+        "fixed"
+    }
+
+    fn params(&self) -> Vec<(&'static str, Param)> {
+        vec![("base", Param::Duration(self.duration))]
+    }
+}
+
+impl Introspect for NoDelay {
+    fn kind(&self) -> &'static str {
+        "none"
+    }
+
+    fn params(&self) -> Vec<(&'static str, Param)> {
+        Vec::new()
+    }
+}
+
+#[cfg(feature = "random")]
+impl Introspect for Range {
+    fn kind(&self) -> &'static str {
+        "range"
+    }
+
+    fn params(&self) -> Vec<(&'static str, Param)> {
+        vec![
+            ("minimum", Param::Duration(self.minimum)),
+            ("maximum", Param::Duration(self.maximum)),
+            ("inclusive", Param::Flag(self.inclusive)),
+        ]
+    }
+}
+
+impl Introspect for Backoff {
+    fn kind(&self) -> &'static str {
+        match self.kind {
+            BackoffKind::None => "none",
+            BackoffKind::Fixed => "fixed",
+            BackoffKind::Exponential => "exponential",
+            BackoffKind::Fibonacci => "fibonacci",
+        }
+    }
+
+    fn params(&self) -> Vec<(&'static str, Param)> {
+        let mut params = Vec::new();
+        if self.kind != BackoffKind::None {
+            params.push(("base", Param::Duration(self.current)));
+        }
+        if self.kind == BackoffKind::Exponential {
+            params.push(("factor", Param::Factor(self.factor)));
+        }
+        if let Some(cap) = self.cap {
+            params.push(("cap", Param::Duration(cap)));
+        }
+        if self.jitter != Jitter::None {
+            params.push(("jitter", Param::Name("full")));
+        }
+        if let Some(budget) = self.budget {
+            params.push(("budget", Param::Duration(budget)));
+        }
+        params
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{Introspect, Param};
+    use crate::{
+        delay::{Backoff, Exponential, Fibonacci, Fixed, NoDelay},
+        Policy,
+    };
+
+    #[test]
+    fn describes_each_strategy() {
+        let millis = |millis| Param::Duration(Duration::from_millis(millis));
+
+        assert_eq!(Exponential::from_millis(10).kind(), "exponential");
+        assert_eq!(
+            Exponential::from_millis(10).params(),
+            vec![("base", millis(10)), ("factor", Param::Factor(10.0))]
+        );
+        assert_eq!(
+            Fibonacci::from_millis(5).params(),
+            vec![("base", millis(5))]
+        );
+        assert_eq!(Fixed::from_millis(7).kind(), "fixed");
+        assert_eq!(NoDelay.kind(), "none");
+        assert_eq!(NoDelay.params(), vec![]);
+
+        let backoff = Backoff::fixed(Duration::from_millis(20)).with_budget(Duration::from_secs(1));
+        assert_eq!(Introspect::kind(&backoff), "fixed");
+        assert_eq!(
+            backoff.params(),
+            vec![("base", millis(20)), ("budget", millis(1000))]
+        );
+        assert_eq!(Introspect::kind(&Backoff::none()), "none");
+        assert_eq!(Backoff::none().params(), vec![]);
+    }
+
+    #[cfg(feature = "random")]
+    #[test]
+    fn describes_randomized_strategies() {
+        use crate::delay::{Jitter, Range};
+
+        let range = Range::from_millis_inclusive(10, 20);
+        assert_eq!(range.kind(), "range");
+        assert_eq!(range.params()[2], ("inclusive", Param::Flag(true)));
+
+        let backoff = Backoff::fibonacci(Duration::from_millis(1)).with_jitter(Jitter::Full);
+        assert_eq!(backoff.params()[1], ("jitter", Param::Name("full")));
+    }
+
+    #[test]
+    fn policies_describe_their_strategy() {
+        let policy = Policy::new(Exponential::from_millis(10)).with_max_attempts(3);
+
+        assert_eq!(policy.strategy_kind(), "exponential");
+        let tags: Vec<_> = policy
+            .strategy_params()
+            .into_iter()
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect();
+        assert_eq!(tags, ["base=10ms", "factor=10"]);
+    }
+}
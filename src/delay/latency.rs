@@ -0,0 +1,263 @@
+use std::{convert::TryFrom, fmt, time::Duration};
+
+use super::{
+    jitter_from,
+    random::{draw, Randomized, SharedRng},
+};
+use crate::{
+    listener::{AttemptOutcome, RetryListener},
+    sync::{Arc, AtomicU64, Ordering},
+    CorrelationId,
+};
+
+/// The value of the average before the first attempt was recorded.
+const UNSET: u64 = u64::MAX;
+
+/// A moving average of the latency of the last attempts made through the policies it listens to.
+///
+/// The tracker is a listener: adding it to a policy with `Policy::with_listener` makes every call
+/// through the policy record how long its attempts took in it. Each attempt moves the average
+/// towards its latency by a fixed weight, 0.2 by default, so that the average follows how slow the
+/// dependency currently is. Clones share the same average, so a tracker can be shared by several
+/// policies, and by the `LatencyJitter` strategies that read it.
+#[derive(Clone, Debug)]
+pub struct Latency {
+    weight: f64,
+    average: Arc<AtomicU64>,
+}
+
+impl Latency {
+    /// Track the average latency of attempts, weighing each new one by 0.2.
+    pub fn new() -> Self {
+        Latency {
+            weight: 0.2,
+            average: Arc::new(AtomicU64::new(UNSET)),
+        }
+    }
+
+    /// Weigh each new attempt by `weight`: the higher it is, the faster the average follows
+    /// changes of the latency.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `weight` is not greater than 0 and at most 1.
+    pub fn with_weight(mut self, weight: f64) -> Self {
+        assert!(
+            weight > 0.0 && weight <= 1.0,
+            "latency weight {} is not greater than 0 and at most 1",
+            weight
+        );
+        self.weight = weight;
+        self
+    }
+
+    /// Record the latency of an attempt. The first one recorded becomes the average.
+    pub fn record(&self, latency: Duration) {
+        let sample = u64::try_from(latency.as_nanos()).unwrap_or(UNSET - 1);
+        let _ = self
+            .average
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |average| {
+                Some(if average == UNSET {
+                    sample
+                } else {
+                    let moved = average as f64 + (sample as f64 - average as f64) * self.weight;
+                    (moved as u64).min(UNSET - 1)
+                })
+            });
+    }
+
+    /// The average latency, or `None` if no attempt was recorded.
+    pub fn average(&self) -> Option<Duration> {
+        match self.average.load(Ordering::Acquire) {
+            UNSET => None,
+            nanos => Some(Duration::from_nanos(nanos)),
+        }
+    }
+}
+
+impl Default for Latency {
+    fn default() -> Self {
+        Latency::new()
+    }
+}
+
+impl RetryListener for Latency {
+    fn on_attempt_end(&self, _: &CorrelationId, _: u64, _: AttemptOutcome<'_>, latency: Duration) {
+        self.record(latency);
+    }
+}
+
+/// Applies random jitter to each delay of a strategy whose magnitude follows the average latency
+/// tracked by a `Latency`, rather than the delay itself.
+///
+/// Each delay is moved by a random amount of up to `scale` times the average latency, 1 by
+/// default, either way, without going below zero. A fixed amount of jitter is too little to
+/// spread clients over a long delay, and too much on a short one: jitter proportional to the
+/// latency spreads the retries of clients by about as much as the attempts of a slow dependency
+/// are spread anyway. Until a latency is recorded, delays are unchanged.
+///
+/// ```rust
+/// # use std::time::Duration;
+/// use retry::delay::{Fixed, Latency, LatencyJitter};
+///
+/// let latency = Latency::new();
+/// let delays = LatencyJitter::new(Fixed::from_millis(1000), latency.clone());
+///
+/// latency.record(Duration::from_millis(20));
+/// let delays: Vec<_> = delays.take(10).collect();
+///
+/// assert!(delays.iter().all(|&delay| {
+///     delay >= Duration::from_millis(980) && delay <= Duration::from_millis(1020)
+/// }));
+/// ```
+#[derive(Clone, Debug)]
+pub struct LatencyJitter<I> {
+    delays: I,
+    latency: Latency,
+    scale: f64,
+    rng: Option<SharedRng>,
+}
+
+impl<I> LatencyJitter<I> {
+    /// Jitter the delays of the given strategy by the average latency tracked by `latency`.
+    pub fn new(delays: I, latency: Latency) -> Self {
+        LatencyJitter {
+            delays,
+            latency,
+            scale: 1.0,
+            rng: None,
+        }
+    }
+
+    /// Move each delay by up to `scale` times the average latency.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `scale` is negative or not finite.
+    pub fn with_scale(mut self, scale: f64) -> Self {
+        assert!(
+            scale.is_finite() && scale >= 0.0,
+            "latency jitter scale {} is not a finite, non-negative number",
+            scale
+        );
+        self.scale = scale;
+        self
+    }
+}
+
+impl<I> Randomized for LatencyJitter<I> {
+    fn with_rng(mut self, rng: SharedRng) -> Self {
+        self.rng = Some(rng);
+        self
+    }
+}
+
+impl<I> Iterator for LatencyJitter<I>
+where
+    I: Iterator<Item = Duration>,
+{
+    type Item = Duration;
+
+    fn next(&mut self) -> Option<Duration> {
+        let delay = self.delays.next()?;
+        let spread = match self.latency.average() {
+            Some(average) => Duration::try_from_secs_f64(average.as_secs_f64() * self.scale)
+                .unwrap_or(Duration::MAX),
+            None => return Some(delay),
+        };
+        let offset = draw(self.rng.as_ref(), |rng| {
+            jitter_from(spread.saturating_mul(2), rng)
+        });
+        Some(delay.saturating_add(offset).saturating_sub(spread))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.delays.size_hint()
+    }
+}
+
+impl<I> fmt::Display for LatencyJitter<I>
+where
+    I: fmt::Display,
+{
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            formatter,
+            "{} with jitter of x{} latency",
+            self.delays, self.scale
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{Latency, LatencyJitter};
+    use crate::{
+        delay::{Fixed, Randomized, SharedRng},
+        Policy,
+    };
+
+    #[test]
+    fn averages_recent_latencies() {
+        let latency = Latency::new().with_weight(0.5);
+        assert_eq!(latency.average(), None);
+
+        latency.record(Duration::from_millis(100));
+        assert_eq!(latency.average(), Some(Duration::from_millis(100)));
+        latency.record(Duration::from_millis(20));
+        assert_eq!(latency.average(), Some(Duration::from_millis(60)));
+        latency.record(Duration::from_millis(20));
+        assert_eq!(latency.average(), Some(Duration::from_millis(40)));
+    }
+
+    #[test]
+    fn jitters_by_the_average_latency() {
+        let latency = Latency::new();
+        let delays = LatencyJitter::new(Fixed::from_millis(100), latency.clone())
+            .with_scale(2.0)
+            .with_rng(SharedRng::seeded(3));
+        assert_eq!(
+            delays.clone().take(3).collect::<Vec<_>>(),
+            vec![Duration::from_millis(100); 3]
+        );
+
+        latency.record(Duration::from_millis(10));
+        let jittered: Vec<_> = delays.clone().take(200).collect();
+        assert!(jittered.iter().all(
+            |&delay| delay >= Duration::from_millis(80) && delay <= Duration::from_millis(120)
+        ));
+        assert!(jittered
+            .iter()
+            .any(|&delay| delay < Duration::from_millis(90)));
+        assert!(jittered
+            .iter()
+            .any(|&delay| delay > Duration::from_millis(110)));
+
+        latency.record(Duration::from_secs(10));
+        assert!(delays.take(200).any(|delay| delay == Duration::default()));
+    }
+
+    #[test]
+    fn policies_record_latencies_through_the_listener() {
+        let latency = Latency::new();
+        let policy = Policy::new(Fixed::from_millis(0).take(2)).with_listener(latency.clone());
+
+        let _ = policy.retry(|| {
+            std::thread::sleep(Duration::from_millis(5));
+            Err::<(), _>("slow")
+        });
+
+        assert!(latency.average() >= Some(Duration::from_millis(5)));
+    }
+
+    #[test]
+    fn display() {
+        let delays = LatencyJitter::new(Fixed::from_millis(10), Latency::new()).with_scale(0.5);
+        assert_eq!(
+            delays.to_string(),
+            "fixed(10ms) with jitter of x0.5 latency"
+        );
+    }
+}
@@ -0,0 +1,432 @@
+use std::{
+    fmt,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use rand::{rngs::StdRng, RngCore, SeedableRng};
+
+use super::{jitter_from, jitter_scaled, Backoff, Range};
+
+/// A random number generator shared by the randomized parts of a schedule, such as `Range`,
+/// `Jittered` and the jitter of a `Backoff`.
+///
+/// Randomized strategies draw from the thread-local generator by default. Giving them the same
+/// seeded generator makes every delay, and so every call made through a policy, reproducible.
+/// Clones share the same generator.
+///
+/// With the `"small-rng"` feature, the thread-local generator is a `SmallRng` seeded once per
+/// thread, which is cheaper to draw from than `thread_rng` in tight retry loops, but is not
+/// cryptographically secure.
+#[derive(Clone)]
+pub struct SharedRng(Arc<Mutex<dyn RngCore + Send>>);
+
+impl SharedRng {
+    /// Share the given generator.
+    pub fn new<R>(rng: R) -> Self
+    where
+        R: RngCore + Send + 'static,
+    {
+        SharedRng(Arc::new(Mutex::new(rng)))
+    }
+
+    /// Share a generator seeded with `seed`, which always produces the same numbers.
+    pub fn seeded(seed: u64) -> Self {
+        SharedRng::new(StdRng::seed_from_u64(seed))
+    }
+
+    /// Call `f` with exclusive access to the generator.
+    pub(crate) fn with<T>(&self, f: impl FnOnce(&mut dyn RngCore) -> T) -> T {
+        let mut rng = self
+            .0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        f(&mut *rng)
+    }
+}
+
+impl fmt::Debug for SharedRng {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.debug_struct("SharedRng").finish()
+    }
+}
+
+/// Generators are equal if they are clones of each other.
+impl PartialEq for SharedRng {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+/// Draw from the given generator, or from the thread-local one.
+pub(super) fn draw<T>(rng: Option<&SharedRng>, f: impl FnOnce(&mut dyn RngCore) -> T) -> T {
+    match rng {
+        Some(rng) => rng.with(f),
+        None => draw_thread_local(f),
+    }
+}
+
+#[cfg(not(feature = "small-rng"))]
+fn draw_thread_local<T>(f: impl FnOnce(&mut dyn RngCore) -> T) -> T {
+    f(&mut rand::thread_rng())
+}
+
+#[cfg(feature = "small-rng")]
+fn draw_thread_local<T>(f: impl FnOnce(&mut dyn RngCore) -> T) -> T {
+    use rand::rngs::SmallRng;
+    use std::cell::RefCell;
+
+    thread_local! {
+        static SMALL_RNG: RefCell<SmallRng> = RefCell::new(SmallRng::from_entropy());
+    }
+    SMALL_RNG.with(|rng| f(&mut *rng.borrow_mut()))
+}
+
+/// The fixed fraction of each delay, out of `2^32`, that a client with the given key waits instead
+/// of a random one. It is the same in every process and on every platform: the key is hashed with
+/// 64-bit FNV-1a, whose hashes of similar keys are mixed with the finalizer of MurmurHash3 so that
+/// keys such as "client-1" and "client-2" land far apart.
+pub(super) fn spread(key: &[u8]) -> u32 {
+    let mut hash = key.iter().fold(0xcbf2_9ce4_8422_2325_u64, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0000_0100_0000_01b3)
+    });
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xff51_afd7_ed55_8ccd);
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+    hash ^= hash >> 33;
+    (hash >> 32) as u32
+}
+
+/// A delay strategy whose randomness can be drawn from a given generator.
+///
+/// `Policy::with_rng` gives the generator to the strategy of a policy. Adapters such as `take`
+/// hide the strategy they wrap, so the generator must be given before adapting it:
+/// `Range::from_millis_exclusive(10, 20).with_rng(rng).take(3)`.
+pub trait Randomized {
+    /// Draw the random delays from `rng`.
+    fn with_rng(self, rng: SharedRng) -> Self;
+}
+
+impl Randomized for Range {
+    fn with_rng(mut self, rng: SharedRng) -> Self {
+        self.rng = Some(rng);
+        self
+    }
+}
+
+impl Randomized for Backoff {
+    fn with_rng(mut self, rng: SharedRng) -> Self {
+        self.rng = Some(rng);
+        self
+    }
+}
+
+/// Applies full random jitter to each delay of a strategy, like mapping it with `jitter`, but
+/// with a generator that can be seeded.
+///
+/// Clients can also be spread without any randomness with `spread_by_key`: each client then waits
+/// the same fraction of every delay, derived from a stable ID of its own.
+///
+/// ```rust
+/// # use std::time::Duration;
+/// use retry::delay::{Fixed, Jittered};
+///
+/// let delays = |client: &str| -> Vec<_> {
+///     Jittered::new(Fixed::from_millis(1000)).spread_by_key(client).take(2).collect()
+/// };
+///
+/// assert_eq!(delays("replica-1"), delays("replica-1"));
+/// assert_eq!(delays("replica-1")[0], delays("replica-1")[1]);
+/// assert_ne!(delays("replica-1"), delays("replica-2"));
+/// ```
+#[derive(Clone, Debug)]
+pub struct Jittered<I> {
+    delays: I,
+    rng: Option<SharedRng>,
+    spread: Option<u32>,
+}
+
+impl<I> Jittered<I> {
+    /// Jitter the delays of the given strategy.
+    pub fn new(delays: I) -> Self {
+        Jittered {
+            delays,
+            rng: None,
+            spread: None,
+        }
+    }
+
+    /// Instead of a random fraction of each delay, wait a fixed fraction derived from a hash of
+    /// `key`, such as the ID of the client, so that clients with different keys are spread over
+    /// the delays without drawing any random number.
+    pub fn spread_by_key<K>(mut self, key: K) -> Self
+    where
+        K: AsRef<[u8]>,
+    {
+        self.spread = Some(spread(key.as_ref()));
+        self
+    }
+}
+
+impl<I> Randomized for Jittered<I> {
+    fn with_rng(mut self, rng: SharedRng) -> Self {
+        self.rng = Some(rng);
+        self
+    }
+}
+
+impl<I> Iterator for Jittered<I>
+where
+    I: Iterator<Item = Duration>,
+{
+    type Item = Duration;
+
+    fn next(&mut self) -> Option<Duration> {
+        let delay = self.delays.next()?;
+        Some(match self.spread {
+            Some(spread) => jitter_scaled(delay, spread),
+            None => draw(self.rng.as_ref(), |rng| jitter_from(delay, rng)),
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.delays.size_hint()
+    }
+}
+
+impl<I> fmt::Display for Jittered<I>
+where
+    I: fmt::Display,
+{
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(formatter, "{} with jitter", self.delays)?;
+        if self.spread.is_some() {
+            formatter.write_str(" spread by key")?;
+        }
+        Ok(())
+    }
+}
+
+/// Applies random jitter to each delay of a strategy that widens with each attempt, so that the
+/// first retries stay close to the schedule and later ones spread out to break the
+/// synchronization of clients that failed together.
+///
+/// The `n`th delay is replaced by a random one between `1 - n * step` times itself and itself,
+/// until the fraction of the delay that is jittered reaches the maximum, full jitter by default.
+///
+/// ```rust
+/// # use std::time::Duration;
+/// use retry::delay::{Fixed, GrowingJitter};
+///
+/// let delays: Vec<_> = GrowingJitter::new(Fixed::from_millis(1000), 0.1)
+///     .with_max(0.5)
+///     .take(10)
+///     .collect();
+///
+/// assert!(delays[0] >= Duration::from_millis(900));
+/// assert!(delays.iter().all(|&delay| delay >= Duration::from_millis(500)));
+/// ```
+#[derive(Clone, Debug)]
+pub struct GrowingJitter<I> {
+    delays: I,
+    step: f64,
+    max: f64,
+    fraction: f64,
+    rng: Option<SharedRng>,
+}
+
+impl<I> GrowingJitter<I> {
+    /// Jitter the delays of the given strategy, widening the jittered fraction of each delay by
+    /// `step` after every attempt.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `step` is not between 0 and 1.
+    pub fn new(delays: I, step: f64) -> Self {
+        assert!(
+            (0.0..=1.0).contains(&step),
+            "jitter step {} is not between 0 and 1",
+            step
+        );
+        GrowingJitter {
+            delays,
+            step,
+            max: 1.0,
+            fraction: 0.0,
+            rng: None,
+        }
+    }
+
+    /// Never jitter more than the fraction `max` of a delay.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max` is not between 0 and 1.
+    pub fn with_max(mut self, max: f64) -> Self {
+        assert!(
+            (0.0..=1.0).contains(&max),
+            "maximum jitter {} is not between 0 and 1",
+            max
+        );
+        self.max = max;
+        self
+    }
+}
+
+impl<I> Randomized for GrowingJitter<I> {
+    fn with_rng(mut self, rng: SharedRng) -> Self {
+        self.rng = Some(rng);
+        self
+    }
+}
+
+impl<I> Iterator for GrowingJitter<I>
+where
+    I: Iterator<Item = Duration>,
+{
+    type Item = Duration;
+
+    fn next(&mut self) -> Option<Duration> {
+        let delay = self.delays.next()?;
+        self.fraction = (self.fraction + self.step).min(self.max);
+        let spread = delay.mul_f64(self.fraction);
+        Some(delay - draw(self.rng.as_ref(), |rng| jitter_from(spread, rng)))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.delays.size_hint()
+    }
+}
+
+impl<I> fmt::Display for GrowingJitter<I>
+where
+    I: fmt::Display,
+{
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            formatter,
+            "{} with jitter growing by {} up to {}",
+            self.delays, self.step, self.max
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{GrowingJitter, Jittered, Randomized, SharedRng};
+    use crate::{
+        delay::{Backoff, Fixed, Jitter, Range},
+        Policy,
+    };
+
+    fn schedule(seed: u64) -> Vec<Duration> {
+        let rng = SharedRng::seeded(seed);
+        let range = Range::from_millis_exclusive(10, 1000).with_rng(rng.clone());
+        let jittered = Jittered::new(Fixed::from_millis(1000)).with_rng(rng.clone());
+        let backoff = Backoff::fixed(Duration::from_secs(1))
+            .with_jitter(Jitter::Full)
+            .with_rng(rng);
+        range
+            .take(3)
+            .chain(jittered.take(3))
+            .chain(backoff.take(3))
+            .collect()
+    }
+
+    #[test]
+    fn seeded_schedules_are_reproducible() {
+        assert_eq!(schedule(7), schedule(7));
+        assert_ne!(schedule(7), schedule(8));
+    }
+
+    #[test]
+    fn draws_from_the_thread_local_generator_by_default() {
+        let delays: Vec<_> = Jittered::new(Fixed::from_millis(100))
+            .take(100)
+            .chain(Range::from_millis_exclusive(10, 20).take(100))
+            .collect();
+
+        assert!(delays
+            .iter()
+            .all(|&delay| delay <= Duration::from_millis(100)));
+        assert!(delays.windows(2).any(|pair| pair[0] != pair[1]));
+    }
+
+    #[test]
+    fn policies_share_their_generator_between_calls() {
+        let delays = |seed| {
+            let policy = Policy::new(Range::from_millis_exclusive(0, 1_000_000))
+                .with_rng(SharedRng::seeded(seed));
+            let first: Vec<_> = policy.delays().take(2).collect();
+            let second: Vec<_> = policy.delays().take(2).collect();
+            (first, second)
+        };
+
+        let (first, second) = delays(1);
+        assert_ne!(first, second);
+        assert_eq!(delays(1), (first, second));
+    }
+
+    #[test]
+    fn growing_jitter_widens_up_to_the_maximum() {
+        let delays: Vec<_> = GrowingJitter::new(Fixed::from_millis(1000), 0.25)
+            .with_max(0.5)
+            .with_rng(SharedRng::seeded(5))
+            .take(200)
+            .collect();
+
+        assert!(delays[0] >= Duration::from_millis(750));
+        assert!(delays[1] >= Duration::from_millis(500));
+        assert!(delays
+            .iter()
+            .all(|&delay| delay >= Duration::from_millis(500) && delay <= Duration::from_secs(1)));
+        assert!(delays[2..]
+            .iter()
+            .any(|&delay| delay < Duration::from_millis(600)));
+
+        let unjittered: Vec<_> = GrowingJitter::new(Fixed::from_millis(10), 0.0)
+            .take(3)
+            .collect();
+        assert_eq!(unjittered, vec![Duration::from_millis(10); 3]);
+    }
+
+    #[test]
+    fn spreads_delays_by_key() {
+        let jittered = Jittered::new(Fixed::from_millis(1000)).spread_by_key("client-7");
+        let backoff = Backoff::fixed(Duration::from_secs(1))
+            .with_jitter(Jitter::Full)
+            .spread_by_key(b"client-7");
+        assert_eq!(jittered.to_string(), "fixed(1s) with jitter spread by key");
+        assert_eq!(backoff.to_string(), "fixed(1s, jitter=full, spread by key)");
+
+        let delays: Vec<_> = jittered.take(3).chain(backoff.take(3)).collect();
+        assert_eq!(delays, vec![Duration::from_nanos(492_458_257); 6]);
+
+        let spread: Vec<_> = (0..100)
+            .map(|client| {
+                Jittered::new(Fixed::from_millis(1000))
+                    .spread_by_key(format!("client-{}", client))
+                    .next()
+                    .unwrap()
+            })
+            .collect();
+        assert!(spread
+            .iter()
+            .any(|&delay| delay < Duration::from_millis(250)));
+        assert!(spread
+            .iter()
+            .any(|&delay| delay > Duration::from_millis(750)));
+    }
+
+    #[test]
+    fn growing_jitter_display() {
+        let delays = GrowingJitter::new(Fixed::from_millis(10), 0.1).with_max(0.5);
+        assert_eq!(
+            delays.to_string(),
+            "fixed(10ms) with jitter growing by 0.1 up to 0.5"
+        );
+    }
+}
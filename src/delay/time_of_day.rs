@@ -0,0 +1,193 @@
+use std::{
+    fmt,
+    time::{Duration, SystemTime},
+};
+
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+/// Scales the delays of a strategy by a factor chosen by the time of day, so that retries can be
+/// gentle on a dependency during business hours and aggressive off hours.
+///
+/// Each window spans whole hours, from its first hour up to but not including its last, and wraps
+/// around midnight if it ends before it starts. The factor of the first window containing the
+/// time of the delay applies, and delays outside every window are left as they are. Hours are
+/// read from the system time, in UTC unless given another offset.
+///
+/// ```rust
+/// # use std::time::{Duration, SystemTime};
+/// use retry::delay::{Fixed, TimeOfDay};
+///
+/// let delays = TimeOfDay::new(Fixed::from_millis(100))
+///     .with_factor(9, 17, 4.0)
+///     .with_factor(22, 6, 0.5)
+///     .with_utc_offset(2 * 60 * 60);
+///
+/// let noon = SystemTime::UNIX_EPOCH + Duration::from_secs(10 * 60 * 60);
+/// assert_eq!(delays.factor_at(noon), 4.0);
+/// assert_eq!(
+///     delays.to_string(),
+///     "fixed(100ms) x4 from 09:00 to 17:00, x0.5 from 22:00 to 06:00, UTC+02:00"
+/// );
+/// ```
+#[derive(Clone, Debug)]
+pub struct TimeOfDay<I> {
+    delays: I,
+    windows: Vec<(u8, u8, f64)>,
+    utc_offset: i32,
+}
+
+impl<I> TimeOfDay<I> {
+    /// Scale the delays of `delays`, which are left as they are until windows are added.
+    pub fn new(delays: I) -> Self {
+        TimeOfDay {
+            delays,
+            windows: Vec::new(),
+            utc_offset: 0,
+        }
+    }
+
+    /// Scale the delays drawn from hour `from` until hour `until` by `factor`, unless an earlier
+    /// window contains them.
+    ///
+    /// # Panics
+    ///
+    /// Panics if an hour is over 24 or the factor is negative or not finite.
+    pub fn with_factor(mut self, from: u8, until: u8, factor: f64) -> Self {
+        assert!(
+            from <= 24 && until <= 24,
+            "hours {} to {} are not hours of a day",
+            from,
+            until
+        );
+        assert!(
+            factor.is_finite() && factor >= 0.0,
+            "factor {} is negative or not finite",
+            factor
+        );
+        self.windows.push((from, until, factor));
+        self
+    }
+
+    /// Read the hours at `utc_offset` seconds east of UTC, such as `-5 * 60 * 60` for Eastern
+    /// Standard Time.
+    pub fn with_utc_offset(mut self, utc_offset: i32) -> Self {
+        self.utc_offset = utc_offset;
+        self
+    }
+
+    /// The factor applied to a delay drawn at `time`.
+    pub fn factor_at(&self, time: SystemTime) -> f64 {
+        let since_epoch = match time.duration_since(SystemTime::UNIX_EPOCH) {
+            Ok(since_epoch) => since_epoch.as_secs() as i64,
+            Err(error) => -(error.duration().as_secs() as i64),
+        };
+        let hour = (since_epoch + i64::from(self.utc_offset)).rem_euclid(SECONDS_PER_DAY) / 3600;
+        let hour = hour as u8;
+        self.windows
+            .iter()
+            .find(|&&(from, until, _)| {
+                if from <= until {
+                    (from..until).contains(&hour)
+                } else {
+                    hour >= from || hour < until
+                }
+            })
+            .map_or(1.0, |&(_, _, factor)| factor)
+    }
+}
+
+impl<I> Iterator for TimeOfDay<I>
+where
+    I: Iterator<Item = Duration>,
+{
+    type Item = Duration;
+
+    fn next(&mut self) -> Option<Duration> {
+        let delay = self.delays.next()?;
+        let factor = self.factor_at(SystemTime::now());
+        Some(Duration::try_from_secs_f64(delay.as_secs_f64() * factor).unwrap_or(Duration::MAX))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.delays.size_hint()
+    }
+}
+
+impl<I> fmt::Display for TimeOfDay<I>
+where
+    I: fmt::Display,
+{
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(formatter, "{}", self.delays)?;
+        for (index, (from, until, factor)) in self.windows.iter().enumerate() {
+            let separator = if index == 0 { " " } else { ", " };
+            write!(
+                formatter,
+                "{}x{} from {:02}:00 to {:02}:00",
+                separator, factor, from, until
+            )?;
+        }
+        if self.utc_offset != 0 {
+            let sign = if self.utc_offset < 0 { '-' } else { '+' };
+            let minutes = self.utc_offset.unsigned_abs() / 60;
+            write!(
+                formatter,
+                ", UTC{}{:02}:{:02}",
+                sign,
+                minutes / 60,
+                minutes % 60
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime};
+
+    use super::TimeOfDay;
+    use crate::delay::Fixed;
+
+    fn at(hour: u64) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(hour * 60 * 60 + 59)
+    }
+
+    #[test]
+    fn picks_the_factor_of_the_first_window_of_the_hour() {
+        let delays = TimeOfDay::new(Fixed::from_millis(100))
+            .with_factor(9, 17, 4.0)
+            .with_factor(12, 13, 8.0)
+            .with_factor(22, 6, 0.5);
+
+        let factors: Vec<_> = (0..24).map(|hour| delays.factor_at(at(hour))).collect();
+        let mut expected = vec![1.0; 24];
+        expected[..6].fill(0.5);
+        expected[9..17].fill(4.0);
+        expected[22..].fill(0.5);
+        assert_eq!(factors, expected);
+        assert_eq!(delays.factor_at(at(24 * 365 + 23)), 0.5);
+        assert_eq!(
+            delays.factor_at(SystemTime::UNIX_EPOCH - Duration::from_secs(60)),
+            0.5
+        );
+    }
+
+    #[test]
+    fn reads_hours_at_the_utc_offset() {
+        let delays = TimeOfDay::new(Fixed::from_millis(100))
+            .with_factor(9, 17, 4.0)
+            .with_utc_offset(-5 * 60 * 60 - 30 * 60);
+
+        assert_eq!(delays.factor_at(at(14)), 1.0);
+        assert_eq!(delays.factor_at(at(15)), 4.0);
+        assert_eq!(
+            delays.to_string(),
+            "fixed(100ms) x4 from 09:00 to 17:00, UTC-05:30"
+        );
+
+        let mut delays = TimeOfDay::new(Fixed::from_millis(100).take(1)).with_factor(0, 24, 0.5);
+        assert_eq!(delays.next(), Some(Duration::from_millis(50)));
+        assert_eq!(delays.next(), None);
+    }
+}
@@ -0,0 +1,190 @@
+use std::{error::Error as StdError, fmt, time::Duration};
+
+#[cfg(feature = "random")]
+use super::Range;
+use super::{Backoff, BackoffKind, Exponential, Fibonacci, Fixed, NoDelay};
+
+/// A delay strategy that can tell whether its settings produce a useful schedule.
+///
+/// Strategies built from configuration can be checked with `Policy::validate`, which lists every
+/// problem found instead of stopping at the first one.
+pub trait Validate {
+    /// Describe each problem with the settings of the strategy, if any.
+    fn problems(&self) -> Vec<String>;
+}
+
+impl Validate for Exponential {
+    fn problems(&self) -> Vec<String> {
+        if self.current == Duration::default() {
+            return vec![zero_base()];
+        }
+        match self.factor {
+            0 => vec!["factor is zero, so every delay after the first is zero".to_owned()],
+            1 => vec![format!(
+                "base delay is {:?}, so delays never grow",
+                self.current
+            )],
+            _ => Vec::new(),
+        }
+    }
+}
+
+impl Validate for Fibonacci {
+    fn problems(&self) -> Vec<String> {
+        if self.curr == Duration::default() && self.next == Duration::default() {
+            vec![zero_base()]
+        } else {
+            Vec::new()
+        }
+    }
+}
+
+impl Validate for Fixed {
+    fn problems(&self) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+impl Validate for NoDelay {
+    fn problems(&self) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+#[cfg(feature = "random")]
+impl Validate for Range {
+    fn problems(&self) -> Vec<String> {
+        range_problems(self.minimum, self.maximum, self.inclusive)
+    }
+}
+
+impl Validate for Backoff {
+    fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        if self.kind != BackoffKind::None && self.current == Duration::default() {
+            problems.push(zero_base());
+        }
+        if self.kind == BackoffKind::Exponential {
+            problems.extend(factor_problem(self.factor));
+        }
+        if self.cap == Some(Duration::default()) {
+            problems.push("cap is zero, so every delay is zero".to_owned());
+        }
+        if self.budget == Some(Duration::default()) {
+            problems.push("budget is zero, so no attempt is retried".to_owned());
+        }
+        problems
+    }
+}
+
+fn zero_base() -> String {
+    "base delay is zero, so every delay is zero".to_owned()
+}
+
+pub(crate) fn factor_problem(factor: f64) -> Option<String> {
+    if factor.is_finite() && factor >= 1.0 {
+        None
+    } else {
+        Some(format!("factor must be at least 1, got {}", factor))
+    }
+}
+
+#[cfg(feature = "random")]
+pub(super) fn range_problems(minimum: Duration, maximum: Duration, inclusive: bool) -> Vec<String> {
+    if minimum < maximum || (inclusive && minimum == maximum) {
+        Vec::new()
+    } else {
+        vec![format!(
+            "range minimum must be less than {}its maximum, got {:?}..{}{:?}",
+            if inclusive { "or equal to " } else { "" },
+            minimum,
+            if inclusive { "=" } else { "" },
+            maximum
+        )]
+    }
+}
+
+/// The problems found with the settings of a strategy or a policy.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ValidationError {
+    problems: Vec<String>,
+}
+
+impl ValidationError {
+    /// Fail with the given problems, if any.
+    pub(crate) fn check(problems: Vec<String>) -> Result<(), Self> {
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(ValidationError { problems })
+        }
+    }
+
+    /// Each problem found, in the order they were found.
+    pub fn problems(&self) -> &[String] {
+        &self.problems
+    }
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            formatter,
+            "invalid retry policy: {}",
+            self.problems.join("; ")
+        )
+    }
+}
+
+impl StdError for ValidationError {}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::Validate;
+    use crate::delay::{Backoff, Exponential, Fibonacci};
+
+    #[test]
+    fn fallible_constructors() {
+        assert_eq!(
+            Exponential::try_from_millis(1).unwrap_err().problems(),
+            ["base delay is 1ms, so delays never grow"]
+        );
+        assert!(Exponential::try_from_millis(10).is_ok());
+        assert!(Fibonacci::try_from_millis(0).is_err());
+        assert_eq!(
+            Backoff::try_exponential(Duration::default(), 0.0)
+                .unwrap_err()
+                .problems(),
+            [
+                "base delay is zero, so every delay is zero",
+                "factor must be at least 1, got 0"
+            ]
+        );
+    }
+
+    #[cfg(feature = "random")]
+    #[test]
+    fn fallible_range_constructors() {
+        use crate::delay::Range;
+
+        assert_eq!(
+            Range::try_from_millis_exclusive(20, 10)
+                .unwrap_err()
+                .to_string(),
+            "invalid retry policy: range minimum must be less than its maximum, got 20ms..10ms"
+        );
+        assert!(Range::try_from_millis_inclusive(10, 10).is_ok());
+    }
+
+    #[test]
+    fn lists_backoff_problems() {
+        let backoff = Backoff::fixed(Duration::from_millis(10))
+            .with_cap(Duration::default())
+            .with_budget(Duration::default());
+
+        assert_eq!(backoff.problems().len(), 2);
+        assert!(Backoff::none().problems().is_empty());
+    }
+}
@@ -0,0 +1,159 @@
+//! Asynchronous retries for Embassy, the async runtime for microcontrollers. This module is enabled
+//! with the `"embassy"` feature, which does not need the standard library.
+//!
+//! Delays between tries are awaited with `embassy_time::Timer`, so the delay strategies of the
+//! `delay` module, which are `no_std` as well, can drive retries on firmware:
+//!
+//! ```rust
+//! # use retry::delay::Exponential;
+//! use retry::embassy;
+//!
+//! # futures::executor::block_on(async {
+//! let mut readings = vec![Err("i2c busy"), Err("i2c busy"), Ok(21)].into_iter();
+//! let delays = Exponential::from_millis(1).take(3);
+//!
+//! let result = embassy::retry(delays, || {
+//!     let reading = readings.next().unwrap();
+//!     async move { reading }
+//! })
+//! .await;
+//!
+//! assert_eq!(result, Ok(21));
+//! # });
+//! ```
+//!
+//! An Embassy time driver must be linked into the program, as for any use of `embassy_time`. With
+//! the `"asynchronous"` feature as well, `EmbassySleeper` is an `AsyncSleeper`, so it can be given
+//! to `asynchronous::retry_with_sleeper`.
+
+use core::{convert::TryFrom, future::Future, time::Duration};
+
+use embassy_time::Timer;
+
+use crate::{Error, OperationResult};
+
+/// Waits between tries with `embassy_time::Timer`.
+///
+/// Delays are rounded up to the tick rate of the time driver, and delays too long for the driver
+/// saturate at its longest one.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct EmbassySleeper;
+
+impl EmbassySleeper {
+    /// Returns a timer that expires after `duration` has elapsed.
+    pub fn sleep(&self, duration: Duration) -> Timer {
+        Timer::after(to_embassy(duration))
+    }
+}
+
+#[cfg(feature = "asynchronous")]
+impl crate::asynchronous::AsyncSleeper for EmbassySleeper {
+    type Sleep = Timer;
+
+    fn sleep(&self, duration: Duration) -> Timer {
+        EmbassySleeper::sleep(self, duration)
+    }
+}
+
+fn to_embassy(duration: Duration) -> embassy_time::Duration {
+    u64::try_from(duration.as_nanos())
+        .ok()
+        .and_then(embassy_time::Duration::try_from_nanos)
+        .unwrap_or(embassy_time::Duration::MAX)
+}
+
+/// Retry the given asynchronous operation until it succeeds, or until the given `Duration`
+/// iterator ends, waiting between tries with `embassy_time::Timer`.
+pub async fn retry<I, O, R, E, OR, F>(iterable: I, mut operation: O) -> Result<R, Error<E>>
+where
+    I: IntoIterator<Item = Duration>,
+    O: FnMut() -> F,
+    OR: Into<OperationResult<R, E>>,
+    F: Future<Output = OR>,
+{
+    retry_with_index(iterable, |_| operation()).await
+}
+
+/// Retry the given asynchronous operation until it succeeds, or until the given `Duration`
+/// iterator ends, with each iteration of the operation receiving the number of the attempt as an
+/// argument.
+pub async fn retry_with_index<I, O, R, E, OR, F>(
+    iterable: I,
+    mut operation: O,
+) -> Result<R, Error<E>>
+where
+    I: IntoIterator<Item = Duration>,
+    O: FnMut(u64) -> F,
+    OR: Into<OperationResult<R, E>>,
+    F: Future<Output = OR>,
+{
+    let mut iterator = iterable.into_iter();
+    let mut current_try = 1;
+    let mut total_delay = Duration::default();
+
+    loop {
+        match operation(current_try).await.into() {
+            OperationResult::Ok(value) => return Ok(value),
+            OperationResult::Retry(error) => {
+                if let Some(delay) = iterator.next() {
+                    EmbassySleeper.sleep(delay).await;
+                    current_try += 1;
+                    total_delay += delay;
+                } else {
+                    return Err(Error::Operation {
+                        error,
+                        total_delay,
+                        tries: current_try,
+                    });
+                }
+            }
+            OperationResult::Err(error) => {
+                return Err(Error::Operation {
+                    error,
+                    total_delay,
+                    tries: current_try,
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+
+    use super::{retry_with_index, to_embassy};
+    use crate::{Error, OperationResult};
+
+    #[test]
+    fn rounds_delays_up_to_the_tick() {
+        assert_eq!(to_embassy(Duration::from_nanos(1)).as_micros(), 1);
+        assert_eq!(to_embassy(Duration::from_millis(3)).as_micros(), 3000);
+        assert_eq!(to_embassy(Duration::MAX).as_ticks(), u64::MAX);
+    }
+
+    #[test]
+    fn waits_with_the_embassy_timer() {
+        let start = std::time::Instant::now();
+        let result = futures::executor::block_on(retry_with_index(
+            [Duration::from_millis(5), Duration::from_millis(5)],
+            |current_try| async move {
+                if current_try == 2 {
+                    OperationResult::<(), _>::Err("rejected")
+                } else {
+                    OperationResult::Retry("busy")
+                }
+            },
+        ));
+
+        assert_eq!(
+            result,
+            Err(Error::Operation {
+                error: "rejected",
+                total_delay: Duration::from_millis(5),
+                tries: 2,
+            })
+        );
+        assert!(start.elapsed() >= Duration::from_millis(5));
+    }
+}
@@ -0,0 +1,331 @@
+//! Structured events describing every step of the calls made through a `Policy`, for log pipelines
+//! that ingest records rather than lines of text.
+//!
+//! An `EventSink` given to `Policy::with_event_sink` receives one `RetryEvent` when each attempt
+//! ends, before each delay and when a call gives up. With the `"serde"` feature, events can be
+//! serialized, and `JsonLines` writes each of them as a line of JSON:
+//!
+//! ```rust
+//! # #[cfg(feature = "serde")]
+//! # {
+//! # use std::sync::Arc;
+//! # use retry::delay::NoDelay;
+//! use retry::{events::JsonLines, Policy};
+//!
+//! let sink = Arc::new(JsonLines::new(Vec::new()));
+//! let policy = Policy::new(NoDelay.take(1)).with_event_sink(Arc::clone(&sink));
+//!
+//! let _ = policy.retry_with_correlation_id("order-7".into(), |_, _| Err::<(), _>("timeout"));
+//! drop(policy);
+//!
+//! let output = String::from_utf8(Arc::try_unwrap(sink).unwrap().into_inner()).unwrap();
+//! let lines: Vec<_> = output.lines().collect();
+//! assert!(lines[0].starts_with(
+//!     r#"{"event":"attempt","correlation_id":"order-7","attempt":1,"outcome":"retry","error":"\"timeout\"","#
+//! ));
+//! assert_eq!(lines[1], r#"{"event":"backoff","correlation_id":"order-7","delay_ms":0.0}"#);
+//! # }
+//! ```
+//!
+//! The field names are stable: `event` is one of `attempt`, `backoff` and `give_up`, and durations
+//! are given in milliseconds, in fields ending with `_ms`.
+
+use std::{sync::Arc, time::Duration};
+
+use crate::{
+    listener::{AttemptOutcome, GiveUpSummary, RetryListener},
+    CorrelationId, Policy,
+};
+
+/// A step of a call made through a policy.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize),
+    serde(tag = "event", rename_all = "snake_case")
+)]
+pub enum RetryEvent {
+    /// An attempt ended.
+    Attempt {
+        /// The correlation ID of the call.
+        correlation_id: CorrelationId,
+        /// The number of the attempt, starting at 1.
+        attempt: u64,
+        /// How the attempt ended: `ok`, `retry`, `err` or `timed_out`.
+        outcome: &'static str,
+        /// The error of a failed attempt, formatted with `Debug`.
+        #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+        error: Option<String>,
+        /// How long the attempt took.
+        #[cfg_attr(
+            feature = "serde",
+            serde(rename = "latency_ms", serialize_with = "millis")
+        )]
+        latency: Duration,
+    },
+    /// The call is about to wait before its next attempt.
+    Backoff {
+        /// The correlation ID of the call.
+        correlation_id: CorrelationId,
+        /// The delay before the next attempt.
+        #[cfg_attr(
+            feature = "serde",
+            serde(rename = "delay_ms", serialize_with = "millis")
+        )]
+        delay: Duration,
+    },
+    /// The call gave up.
+    GiveUp {
+        /// The correlation ID of the call.
+        correlation_id: CorrelationId,
+        /// The number of attempts that were made.
+        attempts: u64,
+        /// The sum of the delays waited between attempts.
+        #[cfg_attr(
+            feature = "serde",
+            serde(rename = "total_delay_ms", serialize_with = "millis")
+        )]
+        total_delay: Duration,
+        /// The time from the start of the first attempt to the end of the last one.
+        #[cfg_attr(
+            feature = "serde",
+            serde(rename = "elapsed_ms", serialize_with = "millis")
+        )]
+        elapsed: Duration,
+    },
+}
+
+#[cfg(feature = "serde")]
+fn millis<S>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    serializer.serialize_f64(duration.as_secs_f64() * 1000.0)
+}
+
+/// Receives the structured events of the calls made through a policy, possibly from several
+/// threads at once.
+pub trait EventSink: Send + Sync {
+    /// Handle one event.
+    fn emit(&self, event: &RetryEvent);
+}
+
+impl<S> EventSink for Arc<S>
+where
+    S: EventSink + ?Sized,
+{
+    fn emit(&self, event: &RetryEvent) {
+        (**self).emit(event)
+    }
+}
+
+/// Writes every event as a line of JSON.
+///
+/// Errors writing to the writer are ignored, so that a broken log pipeline does not fail the
+/// calls it observes. This sink is enabled with the `"serde"` feature.
+#[cfg(feature = "serde")]
+#[derive(Debug)]
+pub struct JsonLines<W> {
+    writer: std::sync::Mutex<W>,
+}
+
+#[cfg(feature = "serde")]
+impl<W> JsonLines<W> {
+    /// Write the events to `writer`, such as `std::io::stdout()` or a `BufWriter` around a file.
+    pub fn new(writer: W) -> Self {
+        JsonLines {
+            writer: std::sync::Mutex::new(writer),
+        }
+    }
+
+    /// Consume the sink, returning the writer.
+    pub fn into_inner(self) -> W {
+        self.writer
+            .into_inner()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<W> EventSink for JsonLines<W>
+where
+    W: std::io::Write + Send,
+{
+    fn emit(&self, event: &RetryEvent) {
+        let mut line = match serde_json::to_vec(event) {
+            Ok(line) => line,
+            Err(_) => return,
+        };
+        line.push(b'\n');
+        let mut writer = self
+            .writer
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let _ = writer.write_all(&line);
+    }
+}
+
+/// Turns the notifications of a call into events for a sink.
+struct SinkListener<S>(S);
+
+impl<S> RetryListener for SinkListener<S>
+where
+    S: EventSink,
+{
+    fn on_attempt_end(
+        &self,
+        id: &CorrelationId,
+        attempt: u64,
+        outcome: AttemptOutcome<'_>,
+        latency: Duration,
+    ) {
+        let (outcome, error) = match outcome {
+            AttemptOutcome::Ok => ("ok", None),
+            AttemptOutcome::Retry(error) => ("retry", Some(format!("{:?}", error))),
+            AttemptOutcome::Err(error) => ("err", Some(format!("{:?}", error))),
+            AttemptOutcome::TimedOut(timeout) => {
+                ("timed_out", Some(format!("timed out after {:?}", timeout)))
+            }
+        };
+        self.0.emit(&RetryEvent::Attempt {
+            correlation_id: id.clone(),
+            attempt,
+            outcome,
+            error,
+            latency,
+        });
+    }
+
+    fn on_backoff(&self, id: &CorrelationId, delay: Duration) {
+        self.0.emit(&RetryEvent::Backoff {
+            correlation_id: id.clone(),
+            delay,
+        });
+    }
+
+    fn on_give_up(&self, summary: &GiveUpSummary) {
+        self.0.emit(&RetryEvent::GiveUp {
+            correlation_id: summary.correlation_id().clone(),
+            attempts: summary.attempts(),
+            total_delay: summary.total_delay(),
+            elapsed: summary.elapsed(),
+        });
+    }
+}
+
+impl<D> Policy<D> {
+    /// Send a structured event to `sink` when each attempt ends, before each delay and when a call
+    /// gives up.
+    pub fn with_event_sink<S>(self, sink: S) -> Self
+    where
+        S: EventSink + 'static,
+    {
+        self.with_listener(SinkListener(sink))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use super::{EventSink, RetryEvent};
+    use crate::{delay::Fixed, CorrelationId, OperationResult, Policy};
+
+    #[derive(Default)]
+    struct Recorder(Mutex<Vec<RetryEvent>>);
+
+    impl EventSink for Recorder {
+        fn emit(&self, event: &RetryEvent) {
+            self.0.lock().unwrap().push(event.clone());
+        }
+    }
+
+    /// The events recorded so far, with their latencies and elapsed times zeroed.
+    fn events(recorder: &Recorder) -> Vec<RetryEvent> {
+        let mut events = recorder.0.lock().unwrap().clone();
+        for event in &mut events {
+            match event {
+                RetryEvent::Attempt { latency, .. } => *latency = Duration::default(),
+                RetryEvent::GiveUp { elapsed, .. } => *elapsed = Duration::default(),
+                RetryEvent::Backoff { .. } => {}
+            }
+        }
+        events
+    }
+
+    #[test]
+    fn emits_an_event_per_step() {
+        let recorder = Arc::new(Recorder::default());
+        let policy =
+            Policy::new(Fixed::from_millis(1).take(1)).with_event_sink(Arc::clone(&recorder));
+        let id = CorrelationId::from("order-7");
+
+        let _ = policy.retry_with_correlation_id(id.clone(), |attempt, _| {
+            if attempt == 1 {
+                OperationResult::<(), _>::Retry("busy")
+            } else {
+                OperationResult::Err("rejected")
+            }
+        });
+
+        assert_eq!(
+            events(&recorder),
+            [
+                RetryEvent::Attempt {
+                    correlation_id: id.clone(),
+                    attempt: 1,
+                    outcome: "retry",
+                    error: Some("\"busy\"".to_owned()),
+                    latency: Duration::default(),
+                },
+                RetryEvent::Backoff {
+                    correlation_id: id.clone(),
+                    delay: Duration::from_millis(1),
+                },
+                RetryEvent::Attempt {
+                    correlation_id: id.clone(),
+                    attempt: 2,
+                    outcome: "err",
+                    error: Some("\"rejected\"".to_owned()),
+                    latency: Duration::default(),
+                },
+                RetryEvent::GiveUp {
+                    correlation_id: id,
+                    attempts: 2,
+                    total_delay: Duration::from_millis(1),
+                    elapsed: Duration::default(),
+                },
+            ]
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn writes_json_lines() {
+        let sink = super::JsonLines::new(Vec::new());
+        sink.emit(&RetryEvent::Backoff {
+            correlation_id: "order-7".into(),
+            delay: Duration::from_micros(1500),
+        });
+        sink.emit(&RetryEvent::GiveUp {
+            correlation_id: "order-7".into(),
+            attempts: 3,
+            total_delay: Duration::from_millis(3),
+            elapsed: Duration::from_millis(4),
+        });
+
+        assert_eq!(
+            String::from_utf8(sink.into_inner()).unwrap(),
+            concat!(
+                r#"{"event":"backoff","correlation_id":"order-7","delay_ms":1.5}"#,
+                "\n",
+                r#"{"event":"give_up","correlation_id":"order-7","attempts":3,"#,
+                r#""total_delay_ms":3.0,"elapsed_ms":4.0}"#,
+                "\n",
+            )
+        );
+    }
+}
@@ -0,0 +1,362 @@
+use std::{fmt::Debug, time::Duration};
+
+#[cfg(feature = "asynchronous")]
+use std::future::Future;
+#[cfg(feature = "dns")]
+use std::{
+    io,
+    net::{SocketAddr, ToSocketAddrs},
+};
+
+use crate::{Error, OperationResult, Policy};
+
+/// Chooses the target of each attempt of a call made with `Policy::retry_with_targets`, such as
+/// the replica or address to send it to.
+///
+/// The provider is asked for the target of the first attempt before it is made, and for the
+/// target of each later attempt as soon as the attempt before it fails with a retryable error,
+/// which it is given, so that it can steer away from a target that reported, say, that it is not
+/// the leader. A target that cannot be chosen, such as when service discovery is down, fails the
+/// attempt with the returned error, which is retried like any other.
+///
+/// Closures taking the number of the attempt and the last error implement it, so that targets can
+/// come from any service discovery:
+///
+/// ```rust
+/// # use retry::delay::NoDelay;
+/// use retry::Policy;
+///
+/// let leader = |_: u64, last_error: Option<&String>| match last_error {
+///     Some(error) => Ok(error.trim_start_matches("not the leader, try ").to_owned()),
+///     None => Ok("node-1".to_owned()),
+/// };
+///
+/// let result = Policy::new(NoDelay.take(3)).retry_with_targets(leader, |node| match &*node {
+///     "node-3" => Ok(node),
+///     _ => Err("not the leader, try node-3".to_owned()),
+/// });
+///
+/// assert_eq!(result.unwrap(), "node-3");
+/// ```
+pub trait TargetProvider<E> {
+    /// What an attempt is made against.
+    type Target;
+
+    /// The target of attempt number `attempt`, given the error of the attempt before it, if any.
+    fn next_target(&mut self, attempt: u64, last_error: Option<&E>) -> Result<Self::Target, E>;
+}
+
+impl<F, T, E> TargetProvider<E> for F
+where
+    F: FnMut(u64, Option<&E>) -> Result<T, E>,
+{
+    type Target = T;
+
+    fn next_target(&mut self, attempt: u64, last_error: Option<&E>) -> Result<T, E> {
+        self(attempt, last_error)
+    }
+}
+
+/// Targets taken in turn from a fixed list, starting over from the first after the last, as with
+/// `Policy::retry_failover`.
+#[derive(Clone, Debug)]
+pub struct StaticTargets<T> {
+    targets: Vec<T>,
+}
+
+impl<T> StaticTargets<T> {
+    /// Take the targets in the order given.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there are no targets.
+    pub fn new<I>(targets: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+    {
+        let targets: Vec<_> = targets.into_iter().collect();
+        assert_targets(&targets);
+        StaticTargets { targets }
+    }
+}
+
+impl<T, E> TargetProvider<E> for StaticTargets<T>
+where
+    T: Clone,
+{
+    type Target = T;
+
+    fn next_target(&mut self, attempt: u64, _: Option<&E>) -> Result<T, E> {
+        Ok(target(&self.targets, attempt).clone())
+    }
+}
+
+/// The addresses a host name resolves to, taken in turn, and resolved again once each of them has
+/// been tried, so that the rotation follows changes to the DNS records between rounds.
+///
+/// A failed resolution fails the attempt with the I/O error it returned, so the error of the
+/// operation must convert from `io::Error`.
+///
+/// ```rust
+/// # use std::{io, net::{SocketAddr, TcpStream}};
+/// # use retry::delay::NoDelay;
+/// use retry::{DnsTargets, Policy};
+///
+/// let policy = Policy::new(NoDelay.take(2));
+/// let mut tried = Vec::new();
+/// let _ = policy.retry_with_targets(DnsTargets::new("127.0.0.1:1"), |address: SocketAddr| {
+///     tried.push(address);
+///     TcpStream::connect(address)
+/// });
+///
+/// assert_eq!(tried.len(), 3);
+/// ```
+#[cfg(feature = "dns")]
+#[derive(Clone, Debug)]
+pub struct DnsTargets {
+    host: String,
+    addresses: Vec<SocketAddr>,
+    next: usize,
+}
+
+#[cfg(feature = "dns")]
+impl DnsTargets {
+    /// Resolve `host`, a host name and a port such as `"db.internal:5432"`, when the first target
+    /// is asked for.
+    pub fn new<H>(host: H) -> Self
+    where
+        H: Into<String>,
+    {
+        DnsTargets {
+            host: host.into(),
+            addresses: Vec::new(),
+            next: 0,
+        }
+    }
+}
+
+#[cfg(feature = "dns")]
+impl<E> TargetProvider<E> for DnsTargets
+where
+    E: From<io::Error>,
+{
+    type Target = SocketAddr;
+
+    fn next_target(&mut self, _: u64, _: Option<&E>) -> Result<SocketAddr, E> {
+        if self.next >= self.addresses.len() {
+            self.addresses = self.host.to_socket_addrs()?.collect();
+            self.next = 0;
+        }
+        let address = self.addresses.get(self.next).copied().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{} did not resolve to any address", self.host),
+            )
+        })?;
+        self.next += 1;
+        Ok(address)
+    }
+}
+
+/// The target of the given attempt, starting over from the first target after the last one.
+fn target<T>(targets: &[T], current_try: u64) -> &T {
+    &targets[((current_try - 1) % targets.len() as u64) as usize]
+}
+
+fn assert_targets<T>(targets: &[T]) {
+    assert!(
+        !targets.is_empty(),
+        "failing over needs at least one target"
+    );
+}
+
+impl<D> Policy<D>
+where
+    D: IntoIterator<Item = Duration> + Clone,
+{
+    /// Retry the given operation synchronously according to this policy, passing each attempt the
+    /// next of `targets`, such as the replicas of a service: the first attempt goes to the first
+    /// target, the second to the second, and so on, starting over from the first after the last.
+    ///
+    /// Every call starts from the first target, with a schedule of its own, so the targets are
+    /// best listed from the most to the least preferred.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `targets` is empty.
+    ///
+    /// ```rust
+    /// # use retry::delay::NoDelay;
+    /// use retry::Policy;
+    ///
+    /// let replicas = ["db-primary:5432", "db-secondary:5432", "db-tertiary:5432"];
+    /// let mut tried = Vec::new();
+    ///
+    /// let result = Policy::new(NoDelay.take(5)).retry_failover(&replicas, |&replica| {
+    ///     tried.push(replica);
+    ///     match replica {
+    ///         "db-tertiary:5432" => Ok(replica),
+    ///         _ => Err("connection refused"),
+    ///     }
+    /// });
+    ///
+    /// assert_eq!(result, Ok("db-tertiary:5432"));
+    /// assert_eq!(tried, replicas);
+    /// ```
+    pub fn retry_failover<T, O, R, E, OR>(
+        &self,
+        targets: &[T],
+        mut operation: O,
+    ) -> Result<R, Error<E>>
+    where
+        O: FnMut(&T) -> OR,
+        OR: Into<OperationResult<R, E>>,
+        E: Debug,
+    {
+        assert_targets(targets);
+        self.retry_with_index(|current_try| operation(target(targets, current_try)))
+    }
+
+    /// Retry the given asynchronous operation according to this policy, passing each attempt the
+    /// next of `targets`, like `retry_failover`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `targets` is empty.
+    #[cfg(feature = "asynchronous")]
+    pub async fn retry_failover_async<T, O, R, E, OR, F>(
+        &self,
+        targets: &[T],
+        mut operation: O,
+    ) -> Result<R, Error<E>>
+    where
+        O: FnMut(&T) -> F,
+        OR: Into<OperationResult<R, E>>,
+        F: Future<Output = OR>,
+        E: Debug,
+    {
+        assert_targets(targets);
+        self.retry_async_with_index(|current_try| operation(target(targets, current_try)))
+            .await
+    }
+
+    /// Retry the given operation synchronously according to this policy, passing each attempt the
+    /// target `provider` chooses for it.
+    pub fn retry_with_targets<P, O, R, E, OR>(
+        &self,
+        mut provider: P,
+        mut operation: O,
+    ) -> Result<R, Error<E>>
+    where
+        P: TargetProvider<E>,
+        O: FnMut(P::Target) -> OR,
+        OR: Into<OperationResult<R, E>>,
+        E: Debug,
+    {
+        let mut next = None;
+        self.retry_with_index(|current_try| {
+            let target = match next.take() {
+                Some(target) => target,
+                None => provider.next_target(current_try, None),
+            };
+            let result = match target {
+                Ok(target) => operation(target).into(),
+                Err(error) => OperationResult::Retry(error),
+            };
+            if let OperationResult::Retry(ref error) = result {
+                next = Some(provider.next_target(current_try + 1, Some(error)));
+            }
+            result
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::StaticTargets;
+    use crate::{delay::NoDelay, Error, Policy};
+
+    #[test]
+    fn rotates_through_the_targets_in_every_call() {
+        let policy = Policy::new(NoDelay.take(4));
+
+        for _ in 0..2 {
+            let mut tried = Vec::new();
+            let result = policy.retry_failover(&["primary", "secondary"], |&target| {
+                tried.push(target);
+                Err::<(), _>("unavailable")
+            });
+
+            assert_eq!(
+                tried,
+                ["primary", "secondary", "primary", "secondary", "primary"]
+            );
+            assert!(matches!(result, Err(Error::Operation { tries: 5, .. })));
+        }
+    }
+
+    #[test]
+    fn asks_the_provider_for_each_target() {
+        let policy = Policy::new(NoDelay.take(3));
+        let mut asked = Vec::new();
+
+        let result = policy.retry_with_targets(
+            |attempt: u64, last_error: Option<&&'static str>| {
+                asked.push((attempt, last_error.copied()));
+                match attempt {
+                    2 => Err("discovery down"),
+                    _ => Ok(attempt * 10),
+                }
+            },
+            |target| match target {
+                30 => Ok(target),
+                _ => Err("unavailable"),
+            },
+        );
+
+        assert_eq!(result, Ok(30));
+        assert_eq!(
+            asked,
+            [
+                (1, None),
+                (2, Some("unavailable")),
+                (3, Some("discovery down"))
+            ]
+        );
+
+        let mut tried = Vec::new();
+        let _ = policy.retry_with_targets(StaticTargets::new(vec!['a', 'b']), |target| {
+            tried.push(target);
+            Err::<(), _>(())
+        });
+        assert_eq!(tried, ['a', 'b', 'a', 'b']);
+    }
+
+    #[test]
+    #[should_panic(expected = "at least one target")]
+    fn needs_a_target() {
+        let _ = Policy::new(NoDelay.take(1)).retry_failover(&[] as &[&str], |_| Ok::<_, ()>(()));
+    }
+
+    #[cfg(feature = "asynchronous")]
+    #[test]
+    fn rotates_asynchronous_attempts() {
+        let policy = Policy::new(NoDelay.take(2));
+        let mut tried = Vec::new();
+
+        let result =
+            futures::executor::block_on(policy.retry_failover_async(&[1, 2, 3], |&target| {
+                tried.push(target);
+                async move {
+                    if target == 3 {
+                        Ok(target)
+                    } else {
+                        Err("unavailable")
+                    }
+                }
+            }));
+
+        assert_eq!(result, Ok(3));
+        assert_eq!(tried, [1, 2, 3]);
+    }
+}
@@ -0,0 +1,128 @@
+//! HTTP helpers shared by the HTTP client integrations.
+
+#[cfg(any(feature = "reqwest", feature = "ureq"))]
+use std::time::{Duration, SystemTime};
+
+#[cfg(any(feature = "reqwest", feature = "ureq"))]
+use http::header::{HeaderMap, RETRY_AFTER};
+#[cfg(any(feature = "hyper", feature = "reqwest"))]
+use http::{Method, StatusCode};
+
+/// Whether a response with the given status is worth retrying, as decided by
+/// `predicates::http::is_retryable`.
+#[cfg(any(feature = "hyper", feature = "reqwest"))]
+pub(crate) fn is_retryable_status(status: StatusCode) -> bool {
+    crate::predicates::http::is_retryable(status.as_u16())
+}
+
+/// Whether a request with the given method can be sent again without changing its effect:
+/// `GET`, `HEAD`, `PUT`, `DELETE`, `OPTIONS` and `TRACE`.
+#[cfg(any(feature = "hyper", feature = "reqwest"))]
+pub(crate) fn is_idempotent(method: &Method) -> bool {
+    matches!(
+        *method,
+        Method::GET | Method::HEAD | Method::PUT | Method::DELETE | Method::OPTIONS | Method::TRACE
+    )
+}
+
+/// The longest `Retry-After` delay that is honored unless configured otherwise.
+#[cfg(any(feature = "reqwest", feature = "ureq"))]
+pub(crate) const DEFAULT_MAX_RETRY_AFTER: Duration = Duration::from_secs(60);
+
+/// The delay requested by a `Retry-After` header, either as a number of seconds or as an HTTP
+/// date.
+#[cfg(any(feature = "reqwest", feature = "ureq"))]
+pub(crate) fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+
+    match value.parse::<u64>() {
+        Ok(seconds) => Some(Duration::from_secs(seconds)),
+        Err(_) => {
+            let date = httpdate::parse_http_date(value).ok()?;
+            Some(date.duration_since(SystemTime::now()).unwrap_or_default())
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    };
+
+    #[cfg(any(feature = "hyper", feature = "reqwest"))]
+    use http::StatusCode;
+    #[cfg(any(feature = "hyper", feature = "reqwest"))]
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    #[cfg(any(feature = "hyper", feature = "reqwest"))]
+    use super::is_retryable_status;
+
+    pub(crate) const UNAVAILABLE: &str =
+        "HTTP/1.1 503 Service Unavailable\r\nconnection: close\r\ncontent-length: 0\r\n\r\n";
+    pub(crate) const OK: &str = "HTTP/1.1 200 OK\r\nconnection: close\r\ncontent-length: 0\r\n\r\n";
+
+    /// Serve one canned response per connection, in order, returning the address and a counter of
+    /// the requests received.
+    #[cfg(any(feature = "hyper", feature = "reqwest"))]
+    pub(crate) async fn serve(responses: Vec<&'static str>) -> (String, Arc<AtomicU64>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(AtomicU64::new(0));
+        let counter = Arc::clone(&requests);
+
+        tokio::spawn(async move {
+            for response in responses {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut buffer = [0; 4096];
+                let _ = stream.read(&mut buffer).await.unwrap();
+                counter.fetch_add(1, Ordering::SeqCst);
+                stream.write_all(response.as_bytes()).await.unwrap();
+                stream.shutdown().await.unwrap();
+            }
+        });
+
+        (address, requests)
+    }
+
+    /// Like `serve`, but serves the responses from a thread, for blocking clients.
+    #[cfg(feature = "ureq")]
+    pub(crate) fn serve_blocking(responses: Vec<&'static str>) -> (String, Arc<AtomicU64>) {
+        use std::{
+            io::{Read, Write},
+            net::TcpListener,
+        };
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(AtomicU64::new(0));
+        let counter = Arc::clone(&requests);
+
+        std::thread::spawn(move || {
+            for response in responses {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut buffer = [0; 4096];
+                let _ = stream.read(&mut buffer).unwrap();
+                counter.fetch_add(1, Ordering::SeqCst);
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+        });
+
+        (address, requests)
+    }
+
+    #[cfg(any(feature = "hyper", feature = "reqwest"))]
+    #[test]
+    fn retryable_statuses() {
+        assert!(is_retryable_status(StatusCode::REQUEST_TIMEOUT));
+        assert!(is_retryable_status(StatusCode::TOO_MANY_REQUESTS));
+        assert!(is_retryable_status(StatusCode::BAD_GATEWAY));
+        assert!(!is_retryable_status(StatusCode::NOT_IMPLEMENTED));
+        assert!(!is_retryable_status(StatusCode::NOT_FOUND));
+        assert!(!is_retryable_status(StatusCode::OK));
+    }
+}
@@ -0,0 +1,344 @@
+//! A `hyper` client wrapper that re-sends requests according to a retry policy. This module is
+//! enabled with the `"hyper"` feature.
+//!
+//! Requests are sent with a buffered `Full<Bytes>` body, so that they can be rebuilt for every
+//! attempt.
+//!
+//! ```
+//! # use http_body_util::Full;
+//! # use hyper::body::Bytes;
+//! # use hyper_util::{client::legacy::Client, rt::TokioExecutor};
+//! # use retry::delay::Exponential;
+//! # use retry::hyper::RetryClient;
+//! # use retry::Policy;
+//! let client = Client::builder(TokioExecutor::new()).build_http::<Full<Bytes>>();
+//! let policy = Policy::new(Exponential::from_millis(10).take(3));
+//! let client = RetryClient::new(client, policy).retry_on_status(|status| status.is_server_error());
+//! # let _ = client;
+//! ```
+//!
+//! By default, only requests with an idempotent method (`GET`, `HEAD`, `PUT`, `DELETE`, `OPTIONS`
+//! and `TRACE`) are retried, as by the `reqwest` middleware, and other requests are sent exactly
+//! once unless `retry_on_method` allows them. Responses with a status of 408, 429 or 5xx (except
+//! 501 and 505) and errors that happened while connecting are retried.
+//!
+//! When the policy gives up on a response, the last response is returned unchanged. A client error
+//! is returned as the last error of a `retry::Error`, whose other variants tell why the policy
+//! refused to make an attempt, for example because its circuit breaker is open.
+
+use std::time::Duration;
+
+use http_body_util::Full;
+use hyper::{
+    body::{Bytes, Incoming},
+    Method, Request, Response, StatusCode,
+};
+use hyper_util::client::legacy::{connect::Connect, Client, Error};
+
+use crate::{
+    classified::Outcome,
+    http::{is_idempotent, is_retryable_status},
+    CorrelationId, Error as RetryError, Policy,
+};
+
+/// A `hyper_util` client that retries requests.
+///
+/// The policy's delays are cloned for every request, so each request gets its own independent
+/// schedule, while its budgets, circuit breaker and bulkhead are shared by all requests.
+#[derive(Clone, Debug)]
+pub struct RetryClient<
+    C,
+    D,
+    S = fn(StatusCode) -> bool,
+    E = fn(&Error) -> bool,
+    M = fn(&Method) -> bool,
+> {
+    client: Client<C, Full<Bytes>>,
+    policy: Policy<D>,
+    retry_on_status: S,
+    retry_on_error: E,
+    retry_on_method: M,
+}
+
+impl<C, D> RetryClient<C, D> {
+    /// Create a new `RetryClient` that sends requests with `client`, retrying them using the
+    /// given policy.
+    pub fn new(client: Client<C, Full<Bytes>>, policy: Policy<D>) -> Self {
+        RetryClient {
+            client,
+            policy,
+            retry_on_status: is_retryable_status,
+            retry_on_error: Error::is_connect,
+            retry_on_method: is_idempotent,
+        }
+    }
+}
+impl<C, D, S, E, M> RetryClient<C, D, S, E, M> {
+    /// Use the given function to decide which response statuses are retried.
+    pub fn retry_on_status<S2>(self, retry_on_status: S2) -> RetryClient<C, D, S2, E, M>
+    where
+        S2: Fn(StatusCode) -> bool,
+    {
+        RetryClient {
+            client: self.client,
+            policy: self.policy,
+            retry_on_status,
+            retry_on_error: self.retry_on_error,
+            retry_on_method: self.retry_on_method,
+        }
+    }
+
+    /// Use the given function to decide which client errors are retried.
+    pub fn retry_on_error<E2>(self, retry_on_error: E2) -> RetryClient<C, D, S, E2, M>
+    where
+        E2: Fn(&Error) -> bool,
+    {
+        RetryClient {
+            client: self.client,
+            policy: self.policy,
+            retry_on_status: self.retry_on_status,
+            retry_on_error,
+            retry_on_method: self.retry_on_method,
+        }
+    }
+
+    /// Use the given function to decide which request methods are retried, such as to retry `POST`
+    /// requests that carry an idempotency key. Requests with other methods are sent exactly once.
+    pub fn retry_on_method<M2>(self, retry_on_method: M2) -> RetryClient<C, D, S, E, M2>
+    where
+        M2: Fn(&Method) -> bool,
+    {
+        RetryClient {
+            client: self.client,
+            policy: self.policy,
+            retry_on_status: self.retry_on_status,
+            retry_on_error: self.retry_on_error,
+            retry_on_method,
+        }
+    }
+
+    /// Get a reference to the inner client.
+    pub fn get_ref(&self) -> &Client<C, Full<Bytes>> {
+        &self.client
+    }
+}
+
+impl<C, D, S, E, M> RetryClient<C, D, S, E, M>
+where
+    C: Connect + Clone + Send + Sync + 'static,
+    D: IntoIterator<Item = Duration> + Clone,
+    S: Fn(StatusCode) -> bool,
+    E: Fn(&Error) -> bool,
+    M: Fn(&Method) -> bool,
+{
+    /// Send the request, retrying it according to the policy while the response or error is
+    /// retryable, unless its method is not retried.
+    ///
+    /// Request extensions are not carried over to retried requests, since they cannot be cloned.
+    pub async fn request(
+        &self,
+        request: Request<Full<Bytes>>,
+    ) -> Result<Response<Incoming>, RetryError<Error>> {
+        if !(self.retry_on_method)(request.method()) {
+            return self
+                .client
+                .request(request)
+                .await
+                .map_err(|error| RetryError::Operation {
+                    error,
+                    total_delay: Duration::default(),
+                    tries: 1,
+                });
+        }
+
+        let request = &request;
+        let result = self
+            .policy
+            .execute_async(CorrelationId::generate(), None, |_, _| async move {
+                let result = self.client.request(clone_request(request)).await;
+                self.classify(result)
+            })
+            .await;
+
+        match result {
+            Ok(response) => Ok(response),
+            Err(RetryError::Operation {
+                error: Failure::Response(response),
+                ..
+            })
+            | Err(RetryError::MaxAttempts {
+                error: Failure::Response(response),
+                ..
+            }) => Ok(response),
+            Err(error) => Err(error.map_error(|failure| match failure {
+                Failure::Error(error) => error,
+                Failure::Response(_) => {
+                    unreachable!("a call that gave up on a response returned it above")
+                }
+            })),
+        }
+    }
+
+    fn classify(
+        &self,
+        result: Result<Response<Incoming>, Error>,
+    ) -> Outcome<Response<Incoming>, Failure> {
+        match result {
+            Ok(response) if (self.retry_on_status)(response.status()) => {
+                Outcome::Retry(Failure::Response(response), None)
+            }
+            Ok(response) => Outcome::Ok(response),
+            Err(error) if (self.retry_on_error)(&error) => {
+                Outcome::Retry(Failure::Error(error), None)
+            }
+            Err(error) => Outcome::Err(Failure::Error(error)),
+        }
+    }
+}
+
+/// The last response or error of a request that was not a success.
+#[derive(Debug)]
+enum Failure {
+    Response(Response<Incoming>),
+    Error(Error),
+}
+
+fn clone_request(request: &Request<Full<Bytes>>) -> Request<Full<Bytes>> {
+    let mut clone = Request::new(request.body().clone());
+    *clone.method_mut() = request.method().clone();
+    *clone.uri_mut() = request.uri().clone();
+    *clone.version_mut() = request.version();
+    *clone.headers_mut() = request.headers().clone();
+    clone
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::atomic::Ordering, time::Duration};
+
+    use http_body_util::Full;
+    use hyper::{body::Bytes, Request};
+    use hyper_util::{client::legacy::Client, rt::TokioExecutor};
+
+    use super::RetryClient;
+    use crate::{
+        delay::NoDelay,
+        http::tests::{serve, OK, UNAVAILABLE},
+        CircuitBreaker, Error, Policy,
+    };
+
+    fn get(address: &str) -> Request<Full<Bytes>> {
+        Request::get(address).body(Full::default()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn retries_server_errors() {
+        let (address, requests) = serve(vec![UNAVAILABLE, UNAVAILABLE, OK]).await;
+        let client = Client::builder(TokioExecutor::new()).build_http();
+
+        let response = RetryClient::new(client, Policy::new(NoDelay.take(5)))
+            .request(get(&address))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), 200);
+        assert_eq!(requests.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn returns_last_response_when_delays_end() {
+        let (address, requests) = serve(vec![UNAVAILABLE, UNAVAILABLE, OK]).await;
+        let client = Client::builder(TokioExecutor::new()).build_http();
+
+        let response = RetryClient::new(client, Policy::new(NoDelay.take(1)))
+            .request(get(&address))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), 503);
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn retries_statuses_chosen_by_hook() {
+        let (address, requests) = serve(vec![UNAVAILABLE, OK]).await;
+        let client = Client::builder(TokioExecutor::new()).build_http();
+
+        let response = RetryClient::new(client, Policy::new(NoDelay.take(5)))
+            .retry_on_status(|_| false)
+            .request(get(&address))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), 503);
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn retries_only_idempotent_methods_by_default() {
+        let (address, requests) = serve(vec![UNAVAILABLE, UNAVAILABLE, OK]).await;
+        let client = RetryClient::new(
+            Client::builder(TokioExecutor::new()).build_http(),
+            Policy::new(NoDelay.take(5)),
+        );
+        let post = || {
+            Request::post(address.as_str())
+                .body(Full::default())
+                .unwrap()
+        };
+
+        let response = client.request(post()).await.unwrap();
+        assert_eq!(response.status(), 503);
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+
+        let response = client
+            .retry_on_method(|_| true)
+            .request(post())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(requests.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn returns_last_connection_error() {
+        let client = Client::builder(TokioExecutor::new()).build_http();
+
+        let error = RetryClient::new(client, Policy::new(NoDelay.take(2)))
+            .request(get("http://127.0.0.1:1"))
+            .await
+            .unwrap_err();
+
+        assert!(matches!(error, Error::Operation { tries: 3, .. }));
+        assert!(error.last_error().unwrap().is_connect());
+    }
+
+    #[tokio::test]
+    async fn stops_at_the_policy_max_attempts() {
+        let (address, requests) = serve(vec![UNAVAILABLE, UNAVAILABLE, OK]).await;
+        let client = Client::builder(TokioExecutor::new()).build_http();
+        let policy = Policy::new(NoDelay.take(5)).with_max_attempts(2);
+
+        let response = RetryClient::new(client, policy)
+            .request(get(&address))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), 503);
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn returns_refusals_of_the_policy() {
+        let client = Client::builder(TokioExecutor::new()).build_http();
+        let policy = Policy::new(NoDelay.take(1))
+            .with_circuit_breaker(CircuitBreaker::new(1, Duration::from_secs(60)));
+        let client = RetryClient::new(client, policy);
+
+        let error = client.request(get("http://127.0.0.1:1")).await.unwrap_err();
+        assert!(error.last_error().unwrap().is_connect());
+
+        let error = client.request(get("http://127.0.0.1:1")).await.unwrap_err();
+        assert!(matches!(error, Error::CircuitOpen { .. }));
+    }
+}
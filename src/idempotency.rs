@@ -0,0 +1,108 @@
+use std::{fmt, sync::Arc};
+
+use crate::correlation::random_u64;
+
+/// A key that stays the same across all the attempts of one call, so that a server can tell the
+/// retries of a non-idempotent request, such as a `POST`, from new requests and apply it once.
+///
+/// `Policy::retry_idempotent` generates a new random key for every call and passes it to every
+/// attempt; `Policy::retry_with_idempotency_key` uses a given key instead, for example one stored
+/// with the request so that it survives a restart.
+///
+/// ```rust
+/// # use retry::delay::NoDelay;
+/// use retry::Policy;
+///
+/// let policy = Policy::new(NoDelay.take(2));
+/// let mut keys = Vec::new();
+///
+/// let _ = policy.retry_idempotent(|key| {
+///     keys.push(key.to_string());
+///     Err::<(), _>("connection reset")
+/// });
+///
+/// assert_eq!(keys.len(), 3);
+/// assert!(keys.iter().all(|key| *key == keys[0]));
+/// ```
+#[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct IdempotencyKey(Arc<str>);
+
+impl IdempotencyKey {
+    /// Generate a new random key, formatted like a version 4 UUID.
+    pub fn generate() -> Self {
+        let value = u128::from(random_u64()) << 64 | u128::from(random_u64());
+        // Set the version and variant bits of a random UUID.
+        let value = value & !(0xf << 76) | 0x4 << 76;
+        let value = value & !(0x3 << 62) | 0x2 << 62;
+        IdempotencyKey(
+            format!(
+                "{:08x}-{:04x}-{:04x}-{:04x}-{:012x}",
+                value >> 96,
+                value >> 80 & 0xffff,
+                value >> 64 & 0xffff,
+                value >> 48 & 0xffff,
+                value & 0xffff_ffff_ffff
+            )
+            .into(),
+        )
+    }
+
+    /// The key as a string.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Debug for IdempotencyKey {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), formatter)
+    }
+}
+
+impl fmt::Display for IdempotencyKey {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str(self.as_str())
+    }
+}
+
+impl From<String> for IdempotencyKey {
+    fn from(key: String) -> Self {
+        IdempotencyKey(key.into())
+    }
+}
+
+impl From<&str> for IdempotencyKey {
+    fn from(key: &str) -> Self {
+        IdempotencyKey(key.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::IdempotencyKey;
+    use crate::{delay::NoDelay, Policy};
+
+    #[test]
+    fn generated_keys_look_like_random_uuids() {
+        let key = IdempotencyKey::generate();
+        let groups: Vec<_> = key.as_str().split('-').map(str::len).collect();
+
+        assert_eq!(groups, [8, 4, 4, 4, 12]);
+        assert_eq!(&key.as_str()[14..15], "4");
+        assert!("89ab".contains(&key.as_str()[19..20]));
+        assert_ne!(key, IdempotencyKey::generate());
+    }
+
+    #[test]
+    fn given_keys_are_passed_to_every_attempt() {
+        let policy = Policy::new(NoDelay.take(1));
+        let mut keys = Vec::new();
+
+        let _ = policy.retry_with_idempotency_key(IdempotencyKey::from("order-7"), |key| {
+            keys.push(key.clone());
+            Err::<(), _>("down")
+        });
+
+        assert_eq!(keys, vec![IdempotencyKey::from("order-7"); 2]);
+    }
+}
@@ -0,0 +1,124 @@
+//! Retries of the I/O errors that only ask for the call to be made again: `Interrupted`, when a
+//! signal arrived during a system call, and `WouldBlock`, when a non-blocking call is not ready.
+//!
+//! ```rust
+//! # use std::io::{self, Read};
+//! use retry::io::{micro_delays, retry_interrupted, retry_would_block};
+//!
+//! let mut source: &[u8] = b"ready";
+//! let mut buffer = [0; 5];
+//! let read = retry_interrupted(|| source.read(&mut buffer))?;
+//! assert_eq!(read, 5);
+//!
+//! let mut polls = 0;
+//! let value = retry_would_block(micro_delays(), || {
+//!     polls += 1;
+//!     if polls < 3 { Err(io::ErrorKind::WouldBlock.into()) } else { Ok(polls) }
+//! })?;
+//! assert_eq!(value, 3);
+//! # Ok::<(), io::Error>(())
+//! ```
+//!
+//! Unlike the policies, these wrappers return the error of the operation itself, so they can
+//! replace the loops of low-level code without changing its signatures.
+
+use std::{io, thread::sleep, time::Duration};
+
+use crate::delay::Backoff;
+
+/// Call `operation` until it returns anything but an `Interrupted` error, without waiting.
+///
+/// An interrupted call can be made again straight away, and does not happen again unless another
+/// signal arrives, so there is no limit on the number of calls.
+pub fn retry_interrupted<T, O>(mut operation: O) -> io::Result<T>
+where
+    O: FnMut() -> io::Result<T>,
+{
+    loop {
+        match operation() {
+            Err(error) if error.kind() == io::ErrorKind::Interrupted => {}
+            result => return result,
+        }
+    }
+}
+
+/// Call `operation` until it returns anything but a `WouldBlock` error, waiting the delays of
+/// `delays` in between, and returning the last `WouldBlock` error once they run out.
+///
+/// `Interrupted` errors are retried straight away, as with `retry_interrupted`, without taking a
+/// delay.
+pub fn retry_would_block<T, D, O>(delays: D, mut operation: O) -> io::Result<T>
+where
+    D: IntoIterator<Item = Duration>,
+    O: FnMut() -> io::Result<T>,
+{
+    let mut delays = delays.into_iter();
+    loop {
+        match retry_interrupted(&mut operation) {
+            Err(error) if error.kind() == io::ErrorKind::WouldBlock => match delays.next() {
+                Some(delay) => sleep(delay),
+                None => return Err(error),
+            },
+            result => return result,
+        }
+    }
+}
+
+/// A strategy of short delays for `retry_would_block`, starting at a microsecond and doubling up
+/// to a millisecond, which ends once it has waited 100 milliseconds.
+pub fn micro_delays() -> Backoff {
+    Backoff::exponential(Duration::from_micros(1), 2.0)
+        .with_cap(Duration::from_millis(1))
+        .with_budget(Duration::from_millis(100))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io, time::Duration};
+
+    use super::{micro_delays, retry_interrupted, retry_would_block};
+    use crate::delay::Fixed;
+
+    #[test]
+    fn retries_interrupted_calls() {
+        let mut calls = 0;
+        let result = retry_interrupted(|| {
+            calls += 1;
+            match calls {
+                1 | 2 => Err(io::ErrorKind::Interrupted.into()),
+                3 => Err(io::Error::from(io::ErrorKind::UnexpectedEof)),
+                _ => Ok(()),
+            }
+        });
+
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+        assert_eq!(calls, 3);
+    }
+
+    #[test]
+    fn gives_up_on_would_block_when_the_delays_run_out() {
+        let mut calls = 0;
+        let result = retry_would_block(Fixed::from_millis(1).take(2), || {
+            calls += 1;
+            if calls % 2 == 0 {
+                Err::<(), _>(io::ErrorKind::Interrupted.into())
+            } else {
+                Err(io::ErrorKind::WouldBlock.into())
+            }
+        });
+
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::WouldBlock);
+        assert_eq!(calls, 5, "interrupted calls do not take a delay");
+    }
+
+    #[test]
+    fn micro_delays_are_short_and_end() {
+        let delays: Vec<_> = micro_delays().collect();
+
+        assert_eq!(delays[0], Duration::from_micros(1));
+        assert!(delays
+            .iter()
+            .all(|&delay| delay <= Duration::from_millis(1)));
+        assert!(delays.iter().sum::<Duration>() <= Duration::from_millis(100));
+    }
+}
@@ -0,0 +1,255 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    hash::Hash,
+    sync::{Arc, Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    clock::{Clock, SystemClock},
+    delay::Backoff,
+};
+
+/// Independent backoff state for each of many targets, such as the hosts a client calls, so that
+/// a host that keeps failing is backed off from without holding up the others.
+///
+/// The state of a key is created the first time a failure is recorded for it, with a fresh copy
+/// of the delay strategy, and dropped when a success is recorded, so healthy targets hold no
+/// state. Keys whose last failure was recorded `idle_after` ago or more are evicted, so that a
+/// client that calls ever-changing targets does not keep the state of every one it has seen.
+/// Eviction happens as the map is used, at most once every `idle_after`, or with `evict_idle`.
+///
+/// The map is used through shared references, so it can be shared between threads, usually in an
+/// `Arc`.
+///
+/// ```rust
+/// # use std::time::Duration;
+/// use retry::{delay::Exponential, BackoffMap};
+///
+/// let backoff = BackoffMap::new(Exponential::from_millis(10), Duration::from_secs(300));
+///
+/// assert_eq!(backoff.record_failure("host-a"), Some(Duration::from_millis(10)));
+/// assert_eq!(backoff.record_failure("host-a"), Some(Duration::from_millis(100)));
+/// assert_eq!(backoff.failures(&"host-a"), 2);
+/// assert_eq!(backoff.failures(&"host-b"), 0);
+/// assert_eq!(backoff.backoff_remaining(&"host-b"), None);
+///
+/// backoff.record_success(&"host-a");
+/// assert_eq!(backoff.failures(&"host-a"), 0);
+/// ```
+pub struct BackoffMap<K, D = Backoff>
+where
+    D: IntoIterator<Item = Duration>,
+{
+    strategy: D,
+    idle_after: Duration,
+    clock: Option<Arc<dyn Clock>>,
+    state: Mutex<State<K, D::IntoIter>>,
+}
+
+struct State<K, I> {
+    entries: HashMap<K, Entry<I>>,
+    last_sweep: Instant,
+}
+
+/// The backoff state of a key that is failing.
+struct Entry<I> {
+    delays: I,
+    failures: u64,
+    /// When the key can be called again, or `None` if its strategy has ended.
+    retry_at: Option<Instant>,
+    last_used: Instant,
+}
+
+impl<K, D> BackoffMap<K, D>
+where
+    K: Eq + Hash,
+    D: IntoIterator<Item = Duration> + Clone,
+{
+    /// Back off from each key according to its own copy of `strategy`, and forget the keys that
+    /// have not failed for `idle_after`.
+    pub fn new(strategy: D, idle_after: Duration) -> Self {
+        BackoffMap {
+            strategy,
+            idle_after,
+            clock: None,
+            state: Mutex::new(State {
+                entries: HashMap::new(),
+                last_sweep: SystemClock.now(),
+            }),
+        }
+    }
+
+    /// Measure delays and idleness with the given clock instead of the system clock.
+    pub fn with_clock<C>(mut self, clock: C) -> Self
+    where
+        C: Clock + 'static,
+    {
+        self.lock().last_sweep = clock.now();
+        self.clock = Some(Arc::new(clock));
+        self
+    }
+
+    fn now(&self) -> Instant {
+        match self.clock {
+            Some(ref clock) => clock.now(),
+            None => SystemClock.now(),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State<K, D::IntoIter>> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Lock the state, evicting the idle keys first if it is time to.
+    fn lock_at(&self, now: Instant) -> MutexGuard<'_, State<K, D::IntoIter>> {
+        let mut state = self.lock();
+        if now.saturating_duration_since(state.last_sweep) >= self.idle_after {
+            self.sweep(&mut state, now);
+        }
+        state
+    }
+
+    fn sweep(&self, state: &mut State<K, D::IntoIter>, now: Instant) -> usize {
+        let before = state.entries.len();
+        let idle_after = self.idle_after;
+        state
+            .entries
+            .retain(|_, entry| now.saturating_duration_since(entry.last_used) < idle_after);
+        state.last_sweep = now;
+        before - state.entries.len()
+    }
+
+    /// Record that a call to `key` failed, and return how long to back off from it, or `None` if
+    /// its strategy has ended. The backoff of a key starts over once a success is recorded.
+    pub fn record_failure(&self, key: K) -> Option<Duration> {
+        let now = self.now();
+        let mut state = self.lock_at(now);
+        let strategy = &self.strategy;
+        let entry = state.entries.entry(key).or_insert_with(|| Entry {
+            delays: strategy.clone().into_iter(),
+            failures: 0,
+            retry_at: Some(now),
+            last_used: now,
+        });
+        entry.failures += 1;
+        entry.last_used = now;
+        let delay = entry.delays.next();
+        entry.retry_at = delay.map(|delay| now.checked_add(delay).unwrap_or(now));
+        delay
+    }
+
+    /// Record that a call to `key` succeeded, forgetting its backoff.
+    pub fn record_success(&self, key: &K) {
+        let now = self.now();
+        self.lock_at(now).entries.remove(key);
+    }
+
+    /// The number of failures recorded in a row for `key`, zero if it is healthy.
+    pub fn failures(&self, key: &K) -> u64 {
+        let now = self.now();
+        self.lock_at(now)
+            .entries
+            .get(key)
+            .map_or(0, |entry| entry.failures)
+    }
+
+    /// How long until `key` can be called again, or `None` if it can be called now. A key whose
+    /// strategy has ended is backed off from for `Duration::MAX`, until a success is recorded or
+    /// it is evicted.
+    pub fn backoff_remaining(&self, key: &K) -> Option<Duration> {
+        let now = self.now();
+        let state = self.lock_at(now);
+        let entry = state.entries.get(key)?;
+        match entry.retry_at {
+            Some(retry_at) if retry_at <= now => None,
+            Some(retry_at) => Some(retry_at - now),
+            None => Some(Duration::MAX),
+        }
+    }
+
+    /// Forget the keys that have not failed for the idle time, returning how many there were.
+    pub fn evict_idle(&self) -> usize {
+        let now = self.now();
+        let mut state = self.lock();
+        self.sweep(&mut state, now)
+    }
+
+    /// The number of keys with backoff state.
+    pub fn len(&self) -> usize {
+        self.lock().entries.len()
+    }
+
+    /// Returns `true` if no key has backoff state.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<K, D> fmt::Debug for BackoffMap<K, D>
+where
+    D: IntoIterator<Item = Duration> + fmt::Debug,
+{
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let keys = self
+            .state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .entries
+            .len();
+        formatter
+            .debug_struct("BackoffMap")
+            .field("strategy", &self.strategy)
+            .field("idle_after", &self.idle_after)
+            .field("keys", &keys)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::BackoffMap;
+    use crate::{clock::MockClock, delay::Fixed};
+
+    #[test]
+    fn backs_off_from_each_key_and_evicts_idle_ones() {
+        let clock = MockClock::new();
+        let backoff = BackoffMap::new(Fixed::from_millis(100).take(2), Duration::from_secs(60))
+            .with_clock(clock.clone());
+
+        assert_eq!(
+            backoff.record_failure("a"),
+            Some(Duration::from_millis(100))
+        );
+        clock.advance(Duration::from_millis(40));
+        assert_eq!(
+            backoff.backoff_remaining(&"a"),
+            Some(Duration::from_millis(60))
+        );
+        clock.advance(Duration::from_millis(60));
+        assert_eq!(backoff.backoff_remaining(&"a"), None);
+
+        assert_eq!(
+            backoff.record_failure("a"),
+            Some(Duration::from_millis(100))
+        );
+        assert_eq!(backoff.record_failure("a"), None);
+        assert_eq!(backoff.backoff_remaining(&"a"), Some(Duration::MAX));
+        assert_eq!(backoff.failures(&"a"), 3);
+
+        clock.advance(Duration::from_secs(30));
+        backoff.record_failure("b");
+        assert_eq!(backoff.len(), 2);
+        clock.advance(Duration::from_secs(30));
+        assert_eq!(backoff.failures(&"b"), 1, "a is evicted, b is not idle yet");
+        assert_eq!(backoff.len(), 1);
+
+        backoff.record_success(&"b");
+        assert!(backoff.is_empty());
+    }
+}
@@ -1,6 +1,37 @@
 //! Crate `retry` provides utilities for retrying operations that can fail.
 //!
-//! Asynchronous versions of these utilities can be enabled with the `"asynchronous"` feature flag.
+//! Asynchronous versions of these utilities, which run on any executor, can be enabled with the
+//! `"asynchronous"` feature flag, and wait with Tokio's timer with the `"tokio"` feature flag,
+//! which also enables a Tokio stream wrapper that dials its connection again when it fails. A
+//! `Sink` combinator that retries each send is enabled with the `"sink"` feature flag.
+//! Retrying middleware for `tower` services and `reqwest` clients, a retrying `hyper` client, and
+//! retrying wrappers for `tonic` calls and channels can be enabled with the `"tower"`, `"reqwest"`,
+//! `"hyper"` and `"tonic"` feature flags respectively. Retry statistics can be collected in a `prometheus`
+//! registry with the `"prometheus"` feature flag, or reported to OpenTelemetry with the `"otel"`
+//! feature flag. Policies can be loaded from configuration files
+//! with the `"serde"` and `"config"` feature flags, and parsed from command line flags with the
+//! `"clap"` feature flag. Command line tools can stop retrying on Ctrl-C with the `"signal"`
+//! feature flag, and retry blocking `ureq` calls with the `"ureq"` feature flag. Connection pools
+//! can retry establishing connections with the `"r2d2"` feature flag, and `postgres` transactions
+//! can be retried on serialization failures with the `"postgres"` feature flag. Functions can be
+//! retried by annotating them with the `#[retry]` attribute of the `"macros"` feature flag, and
+//! the locks of `parking_lot` tried with backoff with the `"parking_lot"` feature flag. Attempts
+//! can be spread over the addresses a host name resolves to with the `"dns"` feature flag.
+//! Transient Windows errors can be recognized by their OS error codes with the `"windows"`
+//! feature flag.
+//!
+//! Randomized delays are behind the default `"random"` feature, so builds that only need
+//! deterministic strategies do not compile `rand`. Everything that needs the standard library,
+//! such as sleeping and `Instant`, is behind the default `"std"` feature. Without it, the crate is
+//! `no_std`, needs only `alloc`, and provides the deterministic delay strategies, `OperationResult`
+//! and `Error`, for firmware that drives its own retry loop with a `Schedule`, or retries with
+//! `retry_n_with_sleep`, along with the spinning `Backoff` of the `spin` module.
+//! The `"embassy"` feature flag adds asynchronous retries that wait with the Embassy timer, which
+//! need neither `std` nor Tokio.
+//!
+//! The minimum supported Rust version is 1.89, declared as `rust-version` in `Cargo.toml`.
+//! `Policy::retry_async_with_state` takes an async closure, which needs Rust 1.85, and the `lock`
+//! module retries `File::try_lock`, which was stabilized in Rust 1.89.
 //!
 //! # Usage
 //!
@@ -13,14 +44,17 @@
 //!
 //! Any type that implements `Iterator<Item = Duration>` can be used to determine retry behavior,
 //! though a few useful implementations are provided in the `delay` module, including a fixed delay
-//! and exponential back-off.
+//! and exponential back-off. So that a call always gives up, `retry` only accepts iterators that
+//! are known to end, as told by `delay::FiniteDelay`, such as a strategy limited with `take`.
+//! `retry_forever` accepts any iterator, and retries until the operation succeeds if it never
+//! ends.
 //!
 //! ```
 //! # use retry::retry;
 //! # use retry::delay::Fixed;
 //! let mut collection = vec![1, 2, 3].into_iter();
 //!
-//! let result = retry(Fixed::from_millis(100), || {
+//! let result = retry(Fixed::from_millis(100).take(5), || {
 //!     match collection.next() {
 //!         Some(n) if n == 3 => Ok("n is 3!"),
 //!         Some(_) => Err("n must be 3!"),
@@ -80,7 +114,7 @@
 //! # use retry::delay::Fixed;
 //! use retry::OperationResult;
 //! let mut collection = vec![1, 2].into_iter();
-//! let value = retry(Fixed::from_millis(1), || {
+//! let value = retry(Fixed::from_millis(1).take(5), || {
 //!     match collection.next() {
 //!         Some(n) if n == 2 => OperationResult::Ok(n),
 //!         Some(_) => OperationResult::Retry("not 2"),
@@ -101,7 +135,7 @@
 //! # use retry::OperationResult;
 //! let mut collection = vec![1, 2, 3, 4, 5].into_iter();
 //!
-//! let result = retry_with_index(Fixed::from_millis(100), |current_try| {
+//! let result = retry_with_index(Fixed::from_millis(100).take(5), |current_try| {
 //!     if current_try > 3 {
 //!         return OperationResult::Err("did not succeed within 3 tries");
 //!     }
@@ -115,39 +149,304 @@
 //!
 //! assert!(result.is_err());
 //! ```
+//!
+//! Polling operations that return `Option`, with `None` for "not ready yet", can be retried with
+//! `retry_until_some`, which returns the value of the first `Some`:
+//!
+//! ```
+//! # use retry::retry_until_some;
+//! # use retry::delay::Fixed;
+//! let mut polls = vec![None, None, Some("ready")].into_iter();
+//!
+//! let result = retry_until_some(Fixed::from_millis(10).take(5), || polls.next().flatten());
+//!
+//! assert_eq!(result, Ok("ready"));
+//! ```
+//!
+//! To reuse the same retry behavior for many operations, wrap the delay strategy in a `Policy`.
+//! A policy clones its strategy for every call, and carries additional options such as a timeout
+//! for each asynchronous try.
 
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
 #![deny(missing_debug_implementations, missing_docs, warnings)]
 
-use std::{
-    error::Error as StdError,
+extern crate alloc;
+// The code generated by `#[retry]` refers to `::retry`, including in the tests of this crate.
+#[cfg(all(test, feature = "macros"))]
+extern crate self as retry;
+
+use alloc::string::String;
+use core::{
     fmt::{Display, Error as FmtError, Formatter},
-    thread::sleep,
     time::Duration,
 };
+#[cfg(feature = "std")]
+use std::{error::Error as StdError, thread::sleep};
 
+#[cfg(feature = "std")]
+use crate::delay::FiniteDelay;
+
+#[cfg(feature = "std")]
+pub mod aggregate;
 #[cfg(feature = "asynchronous")]
 pub mod asynchronous;
+#[cfg(feature = "std")]
+mod before;
+mod bounded;
+#[cfg(feature = "std")]
+mod breaker;
+#[cfg(feature = "std")]
+mod budget;
+#[cfg(feature = "std")]
+pub mod builder;
+#[cfg(feature = "std")]
+mod bulkhead;
+#[cfg(feature = "std")]
+mod cancel;
+#[cfg(feature = "std")]
+mod checkpoint;
+#[cfg(feature = "clap")]
+pub mod clap;
+#[cfg(feature = "std")]
+mod classified;
+#[cfg(feature = "std")]
+pub mod clock;
+#[cfg(feature = "std")]
+pub mod compat;
+#[cfg(feature = "std")]
+pub mod config;
+#[cfg(feature = "std")]
+mod context;
+#[cfg(feature = "std")]
+mod correlation;
+#[cfg(feature = "std")]
+mod cost;
 pub mod delay;
+#[cfg(feature = "embassy")]
+pub mod embassy;
+#[cfg(feature = "std")]
+pub mod events;
+#[cfg(feature = "std")]
+mod failover;
+#[cfg(any(feature = "hyper", feature = "reqwest", feature = "ureq"))]
+mod http;
+#[cfg(feature = "hyper")]
+pub mod hyper;
+#[cfg(feature = "std")]
+mod idempotency;
+#[cfg(feature = "std")]
+pub mod io;
+#[cfg(feature = "std")]
+mod keyed;
+#[cfg(feature = "std")]
+pub mod listener;
+#[cfg(feature = "std")]
+pub mod lock;
+#[cfg(feature = "std")]
+pub mod net;
 mod opresult;
+#[cfg(feature = "otel")]
+mod otel;
+#[cfg(feature = "std")]
+mod pipeline;
+#[cfg(feature = "std")]
+mod policy;
+#[cfg(feature = "std")]
+pub mod predicates;
+#[cfg(feature = "random")]
+pub mod presets;
+#[cfg(feature = "std")]
+pub mod process;
+#[cfg(feature = "prometheus")]
+pub mod prometheus;
+#[cfg(feature = "std")]
+mod queue;
+#[cfg(feature = "r2d2")]
+pub mod r2d2;
+#[cfg(feature = "std")]
+mod reconnect;
+#[cfg(feature = "tokio")]
+pub mod reconnecting;
+#[cfg(feature = "std")]
+mod registry;
+#[cfg(feature = "std")]
+mod reload;
+#[cfg(feature = "std")]
+pub mod replay;
+#[cfg(feature = "reqwest")]
+pub mod reqwest;
+#[cfg(feature = "std")]
+mod resume;
+mod schedule;
+#[cfg(feature = "std")]
+mod session;
+#[cfg(feature = "std")]
+pub mod shared;
+#[cfg(feature = "std")]
+pub mod simulation;
+#[cfg(feature = "sink")]
+pub mod sink;
+pub mod spin;
+#[cfg(feature = "std")]
+mod stats;
+#[cfg(feature = "std")]
+mod supervisor;
+#[cfg(feature = "std")]
+mod sync;
+#[cfg(feature = "test-util")]
+pub mod testing;
+#[cfg(feature = "tonic")]
+pub mod tonic;
+#[cfg(feature = "tower")]
+pub mod tower;
+#[cfg(feature = "std")]
+pub mod transaction;
+#[cfg(feature = "std")]
+mod until;
+#[cfg(feature = "ureq")]
+pub mod ureq;
 
+#[cfg(feature = "std")]
+#[doc(inline)]
+pub use before::BeforeAttempt;
+#[cfg(feature = "std")]
+#[doc(inline)]
+pub use bounded::retry_n;
+#[doc(inline)]
+pub use bounded::{retry_n_with_sleep, AttemptReport};
+#[cfg(feature = "std")]
+#[doc(inline)]
+pub use breaker::{CircuitBreaker, CircuitState};
+#[cfg(feature = "std")]
+#[doc(inline)]
+pub use budget::{NestedBudget, RatioBudget, RetryBudget};
+#[cfg(feature = "std")]
+#[doc(inline)]
+pub use builder::PolicyBuilder;
+#[cfg(feature = "std")]
+#[doc(inline)]
+pub use bulkhead::Bulkhead;
+#[cfg(feature = "std")]
+#[doc(inline)]
+pub use cancel::CancelToken;
+#[cfg(feature = "std")]
+#[doc(inline)]
+pub use checkpoint::Resumable;
+#[cfg(feature = "std")]
+#[doc(inline)]
+pub use classified::{Classified, Permanent, RetryAfterHint, Transient};
+#[cfg(feature = "std")]
+#[doc(inline)]
+pub use context::{AttemptContext, ContextError};
+#[cfg(feature = "std")]
+#[doc(inline)]
+pub use correlation::CorrelationId;
+#[cfg(feature = "std")]
+#[doc(inline)]
+pub use cost::CostMeter;
+#[cfg(feature = "dns")]
+#[doc(inline)]
+pub use failover::DnsTargets;
+#[cfg(feature = "std")]
+#[doc(inline)]
+pub use failover::{StaticTargets, TargetProvider};
+#[cfg(feature = "std")]
+#[doc(inline)]
+pub use idempotency::IdempotencyKey;
+#[cfg(feature = "std")]
+#[doc(inline)]
+pub use keyed::BackoffMap;
 #[doc(inline)]
 pub use opresult::OperationResult;
+#[cfg(feature = "std")]
+#[doc(inline)]
+pub use pipeline::{Pipeline, StepError};
+#[cfg(feature = "std")]
+#[doc(inline)]
+pub use policy::{Policy, Scheduling};
+#[cfg(feature = "std")]
+#[doc(inline)]
+pub use queue::{DrainOrder, RetryQueue};
+#[cfg(feature = "std")]
+#[doc(inline)]
+pub use reconnect::Reconnector;
+#[cfg(feature = "std")]
+#[doc(inline)]
+pub use registry::PolicyRegistry;
+#[cfg(feature = "std")]
+#[doc(inline)]
+pub use reload::ReloadablePolicy;
+#[cfg(feature = "std")]
+#[doc(inline)]
+pub use resume::{RetrySession, RetryState};
+#[cfg(feature = "macros")]
+#[doc(inline)]
+pub use retry_macros::retry;
+#[doc(inline)]
+pub use schedule::Schedule;
+#[cfg(feature = "std")]
+#[doc(inline)]
+pub use stats::PolicyStats;
+#[cfg(feature = "std")]
+#[doc(inline)]
+pub use supervisor::{ThreadSupervisor, WorkerError};
+#[cfg(feature = "std")]
+#[doc(inline)]
+pub use until::{Unsatisfied, Until};
 
 /// Retry the given operation synchronously until it succeeds, or until the given `Duration`
 /// iterator ends.
-pub fn retry<I, O, R, E, OR>(iterable: I, mut operation: O) -> Result<R, Error<E>>
+///
+/// The iterator must be known to end, as told by `FiniteDelay`, so that the call always gives up.
+/// `retry_forever` retries with any iterator.
+#[cfg(feature = "std")]
+pub fn retry<I, O, R, E, OR>(iterable: I, operation: O) -> Result<R, Error<E>>
 where
     I: IntoIterator<Item = Duration>,
+    I::IntoIter: FiniteDelay,
     O: FnMut() -> OR,
     OR: Into<OperationResult<R, E>>,
 {
-    retry_with_index(iterable, |_| operation())
+    retry_forever(iterable, operation)
+}
+
+/// Retry the given operation synchronously like `retry`, with an iterator that may never end,
+/// such as `Fixed::from_millis(10)`, in which case the call only returns once the operation
+/// succeeds or returns `OperationResult::Err`.
+#[cfg(feature = "std")]
+pub fn retry_forever<I, O, R, E, OR>(iterable: I, mut operation: O) -> Result<R, Error<E>>
+where
+    I: IntoIterator<Item = Duration>,
+    O: FnMut() -> OR,
+    OR: Into<OperationResult<R, E>>,
+{
+    retry_with_index_forever(iterable, |_| operation())
 }
 
 /// Retry the given operation synchronously until it succeeds, or until the given `Duration`
 /// iterator ends, with each iteration of the operation receiving the number of the attempt as an
 /// argument.
-pub fn retry_with_index<I, O, R, E, OR>(iterable: I, mut operation: O) -> Result<R, Error<E>>
+///
+/// Like `retry`, this only accepts iterators that are known to end, and
+/// `retry_with_index_forever` accepts any iterator.
+#[cfg(feature = "std")]
+pub fn retry_with_index<I, O, R, E, OR>(iterable: I, operation: O) -> Result<R, Error<E>>
+where
+    I: IntoIterator<Item = Duration>,
+    I::IntoIter: FiniteDelay,
+    O: FnMut(u64) -> OR,
+    OR: Into<OperationResult<R, E>>,
+{
+    retry_with_index_forever(iterable, operation)
+}
+
+/// Retry the given operation synchronously like `retry_with_index`, with an iterator that may
+/// never end.
+#[cfg(feature = "std")]
+pub fn retry_with_index_forever<I, O, R, E, OR>(
+    iterable: I,
+    mut operation: O,
+) -> Result<R, Error<E>>
 where
     I: IntoIterator<Item = Duration>,
     O: FnMut(u64) -> OR,
@@ -184,11 +483,101 @@ where
     }
 }
 
+/// Retry the given operation synchronously while it returns `None`, until it returns `Some` or
+/// until the given `Duration` iterator ends, returning the value it was `Some` of.
+///
+/// There is no error to return when the iterator ends, so the error of the operation is `()`.
+#[cfg(feature = "std")]
+pub fn retry_until_some<I, O, T>(iterable: I, mut operation: O) -> Result<T, Error<()>>
+where
+    I: IntoIterator<Item = Duration>,
+    I::IntoIter: FiniteDelay,
+    O: FnMut() -> Option<T>,
+{
+    retry(iterable, || operation().ok_or(()))
+}
+
+/// Check the given probe synchronously until it returns `true`, waiting between checks according to
+/// the given `Duration` iterator, for example to wait for a port to open or a file to exist.
+///
+/// ```
+/// # use retry::await_condition;
+/// # use retry::delay::Fixed;
+/// let mut checks = 0;
+/// let result = await_condition(Fixed::from_millis(10).take(5), || {
+///     checks += 1;
+///     checks == 3
+/// });
+///
+/// assert_eq!(result, Ok(()));
+/// assert_eq!(
+///     await_condition(Fixed::from_millis(10).take(2), || false).unwrap_err().to_string(),
+///     "condition was not met after 3 checks and 20ms of delays"
+/// );
+/// ```
+#[cfg(feature = "std")]
+pub fn await_condition<I, P>(iterable: I, mut probe: P) -> Result<(), ConditionTimeout>
+where
+    I: IntoIterator<Item = Duration>,
+    I::IntoIter: FiniteDelay,
+    P: FnMut() -> bool,
+{
+    retry(iterable, || if probe() { Ok(()) } else { Err(()) }).map_err(ConditionTimeout::from)
+}
+
+/// The error returned when a condition awaited with `await_condition` was still not met when the
+/// schedule ended.
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ConditionTimeout {
+    checks: u64,
+    total_delay: Duration,
+}
+
+#[cfg(feature = "std")]
+impl ConditionTimeout {
+    /// The number of times the condition was checked.
+    pub fn checks(&self) -> u64 {
+        self.checks
+    }
+
+    /// The duration spent waiting between checks.
+    pub fn total_delay(&self) -> Duration {
+        self.total_delay
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<Error<()>> for ConditionTimeout {
+    fn from(error: Error<()>) -> Self {
+        ConditionTimeout {
+            checks: error.tries(),
+            total_delay: error.total_delay(),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Display for ConditionTimeout {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> Result<(), FmtError> {
+        write!(
+            formatter,
+            "condition was not met after {} checks and {:?} of delays",
+            self.checks, self.total_delay
+        )
+    }
+}
+
+#[cfg(feature = "std")]
+impl StdError for ConditionTimeout {}
+
 /// An error with a retryable operation.
 #[derive(Debug, PartialEq, Eq)]
 pub enum Error<E> {
     /// The operation's last error, plus the number of times the operation was tried and the
     /// duration spent waiting between tries.
+    ///
+    /// This is returned when the last error was not retryable, or when the delay schedule ended.
     Operation {
         /// The error returned by the operation on the last try.
         error: E,
@@ -199,51 +588,336 @@ pub enum Error<E> {
         /// The total number of times the operation was tried.
         tries: u64,
     },
+    /// The operation's last error, returned when the policy's maximum number of attempts was
+    /// reached while the delay schedule still had delays left.
+    MaxAttempts {
+        /// The error returned by the operation on the last try.
+        error: E,
+        /// The duration spent waiting between retries of the operation.
+        total_delay: Duration,
+        /// The total number of times the operation was tried.
+        tries: u64,
+    },
+    /// The retry loop was cancelled before the operation succeeded, plus the number of times the
+    /// operation was tried and the duration spent waiting between tries.
+    Cancelled {
+        /// The duration spent waiting between retries of the operation before cancellation.
+        total_delay: Duration,
+        /// The number of times the operation was tried, including an attempt that was
+        /// interrupted by the cancellation.
+        tries: u64,
+    },
+    /// The last try of the operation did not finish within the policy's attempt timeout, plus
+    /// the number of times the operation was tried and the duration spent waiting between tries.
+    TimedOut {
+        /// The attempt timeout that the last try exceeded.
+        timeout: Duration,
+        /// The duration spent waiting between retries of the operation.
+        total_delay: Duration,
+        /// The total number of times the operation was tried.
+        tries: u64,
+    },
+    /// The call was rejected without making any attempt, because the policy's bulkhead was full.
+    Rejected {
+        /// The number of calls the bulkhead allows in progress at once.
+        max_concurrent: usize,
+    },
+    /// The call was rejected without making any attempt, because the policy's circuit breaker was
+    /// open after too many calls in a row gave up.
+    CircuitOpen {
+        /// How long until the breaker lets a call through to test the dependency again, which is
+        /// zero while another call is testing it.
+        retry_in: Duration,
+    },
     /// Something went wrong in the internal logic.
     Internal(String),
 }
 
+impl<E> Error<E> {
+    /// The error returned by the operation on the last try, if the retry loop ended because of
+    /// it.
+    pub fn last_error(&self) -> Option<&E> {
+        match *self {
+            Error::Operation { ref error, .. } | Error::MaxAttempts { ref error, .. } => {
+                Some(error)
+            }
+            Error::Cancelled { .. }
+            | Error::TimedOut { .. }
+            | Error::Rejected { .. }
+            | Error::CircuitOpen { .. }
+            | Error::Internal(_) => None,
+        }
+    }
+
+    /// Consume the error, returning the error of the operation's last try, if the retry loop
+    /// ended because of it.
+    pub fn into_last_error(self) -> Option<E> {
+        match self {
+            Error::Operation { error, .. } | Error::MaxAttempts { error, .. } => Some(error),
+            Error::Cancelled { .. }
+            | Error::TimedOut { .. }
+            | Error::Rejected { .. }
+            | Error::CircuitOpen { .. }
+            | Error::Internal(_) => None,
+        }
+    }
+
+    /// The duration spent waiting between tries, which is zero for `Error::Rejected`,
+    /// `Error::CircuitOpen` and `Error::Internal`.
+    pub fn total_delay(&self) -> Duration {
+        match *self {
+            Error::Operation { total_delay, .. }
+            | Error::MaxAttempts { total_delay, .. }
+            | Error::Cancelled { total_delay, .. }
+            | Error::TimedOut { total_delay, .. } => total_delay,
+            Error::Rejected { .. } | Error::CircuitOpen { .. } | Error::Internal(_) => {
+                Duration::default()
+            }
+        }
+    }
+
+    /// The number of times the operation was tried, which is zero for `Error::Rejected`,
+    /// `Error::CircuitOpen` and `Error::Internal`.
+    pub fn tries(&self) -> u64 {
+        match *self {
+            Error::Operation { tries, .. }
+            | Error::MaxAttempts { tries, .. }
+            | Error::Cancelled { tries, .. }
+            | Error::TimedOut { tries, .. } => tries,
+            Error::Rejected { .. } | Error::CircuitOpen { .. } | Error::Internal(_) => 0,
+        }
+    }
+
+    /// Convert the error of the last try, if any, with `f`.
+    #[cfg(feature = "std")]
+    pub(crate) fn map_error<T, F>(self, f: F) -> Error<T>
+    where
+        F: FnOnce(E) -> T,
+    {
+        match self {
+            Error::Operation {
+                error,
+                total_delay,
+                tries,
+            } => Error::Operation {
+                error: f(error),
+                total_delay,
+                tries,
+            },
+            Error::MaxAttempts {
+                error,
+                total_delay,
+                tries,
+            } => Error::MaxAttempts {
+                error: f(error),
+                total_delay,
+                tries,
+            },
+            Error::Cancelled { total_delay, tries } => Error::Cancelled { total_delay, tries },
+            Error::TimedOut {
+                timeout,
+                total_delay,
+                tries,
+            } => Error::TimedOut {
+                timeout,
+                total_delay,
+                tries,
+            },
+            Error::Rejected { max_concurrent } => Error::Rejected { max_concurrent },
+            Error::CircuitOpen { retry_in } => Error::CircuitOpen { retry_in },
+            Error::Internal(message) => Error::Internal(message),
+        }
+    }
+}
+
+/// Summarizes how the retry loop ended on one line, with the number of tries, the time spent
+/// waiting between them rounded to a tenth of a unit, and the error of the last try, such as
+/// `gave up after 5 attempts over 36.2s of delays (last error: connection refused)`.
+///
+/// The alternate form, `{:#}`, gives the exact time spent waiting and says why the loop ended in
+/// full, followed by the last error in its own alternate form. The error of the last try, if any,
+/// is also available as the `source` of the error.
 impl<E> Display for Error<E>
 where
-    E: StdError,
+    E: Display,
 {
-    fn fmt(&self, formatter: &mut Formatter) -> Result<(), FmtError> {
-        write!(formatter, "{}", self.description())
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> Result<(), FmtError> {
+        if formatter.alternate() {
+            return self.fmt_details(formatter);
+        }
+        match *self {
+            Error::Operation {
+                ref error,
+                total_delay,
+                tries,
+            } => write!(
+                formatter,
+                "gave up after {} over {} of delays (last error: {})",
+                Attempts(tries),
+                Rounded(total_delay),
+                error
+            ),
+            Error::MaxAttempts {
+                ref error,
+                total_delay,
+                tries,
+            } => write!(
+                formatter,
+                "gave up after all {} allowed over {} of delays (last error: {})",
+                Attempts(tries),
+                Rounded(total_delay),
+                error
+            ),
+            Error::Cancelled { total_delay, tries } => write!(
+                formatter,
+                "cancelled after {} over {} of delays",
+                Attempts(tries),
+                Rounded(total_delay)
+            ),
+            Error::TimedOut {
+                timeout,
+                total_delay,
+                tries,
+            } => write!(
+                formatter,
+                "gave up after {} over {} of delays (last attempt timed out after {})",
+                Attempts(tries),
+                Rounded(total_delay),
+                Rounded(timeout)
+            ),
+            Error::Rejected { max_concurrent } => write!(
+                formatter,
+                "call was rejected because {} calls were already in progress",
+                max_concurrent
+            ),
+            Error::CircuitOpen { retry_in } if retry_in.is_zero() => formatter.write_str(
+                "call was rejected because the circuit breaker is testing the dependency",
+            ),
+            Error::CircuitOpen { retry_in } => write!(
+                formatter,
+                "call was rejected because the circuit breaker is open for another {}",
+                Rounded(retry_in)
+            ),
+            Error::Internal(ref description) => formatter.write_str(description),
+        }
     }
 }
 
-impl<E> StdError for Error<E>
+impl<E> Error<E>
 where
-    E: StdError,
+    E: Display,
 {
-    fn description(&self) -> &str {
+    fn fmt_details(&self, formatter: &mut Formatter<'_>) -> Result<(), FmtError> {
         match *self {
-            Error::Operation { ref error, .. } => error.description(),
-            Error::Internal(ref description) => description,
+            Error::Operation {
+                ref error,
+                total_delay,
+                tries,
+            } => write!(
+                formatter,
+                "operation failed after {} tries and {:?} of delays: {:#}",
+                tries, total_delay, error
+            ),
+            Error::MaxAttempts {
+                ref error,
+                total_delay,
+                tries,
+            } => write!(
+                formatter,
+                "operation failed on all {} allowed tries, after {:?} of delays: {:#}",
+                tries, total_delay, error
+            ),
+            Error::Cancelled { total_delay, tries } => write!(
+                formatter,
+                "retry loop was cancelled after {} tries and {:?} of delays",
+                tries, total_delay
+            ),
+            Error::TimedOut {
+                timeout,
+                total_delay,
+                tries,
+            } => write!(
+                formatter,
+                "operation timed out after {:?} on the last of {} tries, after {:?} of delays",
+                timeout, tries, total_delay
+            ),
+            Error::Rejected { .. } | Error::CircuitOpen { .. } | Error::Internal(_) => {
+                write!(formatter, "{}", self)
+            }
         }
     }
+}
 
-    fn cause(&self) -> Option<&dyn StdError> {
-        match *self {
-            Error::Operation { ref error, .. } => Some(error),
-            Error::Internal(_) => None,
+/// A duration rounded to a tenth of its unit, such as "36.2s", or "0s".
+struct Rounded(Duration);
+
+impl Display for Rounded {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> Result<(), FmtError> {
+        if self.0.is_zero() {
+            formatter.write_str("0s")
+        } else {
+            write!(formatter, "{:.1?}", self.0)
         }
     }
 }
 
-#[cfg(test)]
+/// A number of attempts, as "1 attempt" or "N attempts".
+struct Attempts(u64);
+
+impl Display for Attempts {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> Result<(), FmtError> {
+        match self.0 {
+            1 => formatter.write_str("1 attempt"),
+            tries => write!(formatter, "{} attempts", tries),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<E> StdError for Error<E>
+where
+    E: StdError + 'static,
+{
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        self.last_error()
+            .map(|error| error as &(dyn StdError + 'static))
+    }
+}
+
+/// Converts into an I/O error of the same kind as the last error, so retried operations can be
+/// used where only I/O errors are expected. The retry error becomes the payload, keeping the
+/// summary in the message and the last error as its source.
+#[cfg(feature = "std")]
+impl From<Error<std::io::Error>> for std::io::Error {
+    fn from(error: Error<std::io::Error>) -> Self {
+        let kind = match error {
+            Error::Operation { ref error, .. } | Error::MaxAttempts { ref error, .. } => {
+                error.kind()
+            }
+            Error::Cancelled { .. } => std::io::ErrorKind::Interrupted,
+            Error::TimedOut { .. } => std::io::ErrorKind::TimedOut,
+            Error::Rejected { .. } | Error::CircuitOpen { .. } => std::io::ErrorKind::WouldBlock,
+            Error::Internal(_) => std::io::ErrorKind::Other,
+        };
+        std::io::Error::new(kind, error)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
 mod tests {
     use std::time::Duration;
 
-    use super::delay::{Exponential, Fixed, NoDelay, Range};
+    #[cfg(feature = "random")]
+    use super::delay::Range;
+    use super::delay::{Exponential, Fixed, NoDelay};
     use super::opresult::OperationResult;
-    use super::{retry, retry_with_index, Error};
+    use super::{await_condition, retry, retry_forever, retry_until_some, retry_with_index, Error};
 
     #[test]
     fn succeeds_with_infinite_retries() {
         let mut collection = vec![1, 2, 3, 4, 5].into_iter();
 
-        let value = retry(NoDelay, || match collection.next() {
+        let value = retry_forever(NoDelay, || match collection.next() {
             Some(n) if n == 5 => Ok(n),
             Some(_) => Err("not 5"),
             None => Err("not 5"),
@@ -311,7 +985,7 @@ mod tests {
     fn succeeds_with_fixed_delay() {
         let mut collection = vec![1, 2].into_iter();
 
-        let value = retry(Fixed::from_millis(1), || match collection.next() {
+        let value = retry_forever(Fixed::from_millis(1), || match collection.next() {
             Some(n) if n == 2 => Ok(n),
             Some(_) => Err("not 2"),
             None => Err("not 2"),
@@ -333,7 +1007,7 @@ mod tests {
     fn succeeds_with_exponential_delay() {
         let mut collection = vec![1, 2].into_iter();
 
-        let value = retry(Exponential::from_millis(1), || match collection.next() {
+        let value = retry_forever(Exponential::from_millis(1), || match collection.next() {
             Some(n) if n == 2 => Ok(n),
             Some(_) => Err("not 2"),
             None => Err("not 2"),
@@ -343,11 +1017,12 @@ mod tests {
         assert_eq!(value, 2);
     }
 
+    #[cfg(feature = "random")]
     #[test]
     fn succeeds_with_ranged_delay() {
         let mut collection = vec![1, 2].into_iter();
 
-        let value = retry(Range::from_millis_exclusive(1, 10), || {
+        let value = retry_forever(Range::from_millis_exclusive(1, 10), || {
             match collection.next() {
                 Some(n) if n == 2 => Ok(n),
                 Some(_) => Err("not 2"),
@@ -363,7 +1038,7 @@ mod tests {
     fn succeeds_with_index() {
         let mut collection = vec![1, 2, 3].into_iter();
 
-        let value = retry_with_index(NoDelay, |current_try| match collection.next() {
+        let value = retry_with_index(NoDelay.take(5), |current_try| match collection.next() {
             Some(n) if n == current_try => Ok(n),
             Some(_) => Err("not current_try"),
             None => Err("not current_try"),
@@ -372,4 +1047,195 @@ mod tests {
 
         assert_eq!(value, 1);
     }
+
+    #[test]
+    fn displays_context_and_exposes_source() {
+        use std::error::Error as StdError;
+        use std::io;
+
+        let error = retry(Fixed::from_millis(1).take(2), || {
+            Err::<(), _>(io::Error::other("connection reset"))
+        })
+        .unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "gave up after 3 attempts over 2.0ms of delays (last error: connection reset)"
+        );
+        assert_eq!(
+            format!("{:#}", error),
+            "operation failed after 3 tries and 2ms of delays: connection reset"
+        );
+        assert_eq!(error.source().unwrap().to_string(), "connection reset");
+
+        let cancelled: Error<io::Error> = Error::Cancelled {
+            total_delay: Duration::from_millis(5),
+            tries: 1,
+        };
+        assert_eq!(
+            cancelled.to_string(),
+            "cancelled after 1 attempt over 5.0ms of delays"
+        );
+        assert_eq!(
+            format!("{:#}", cancelled),
+            "retry loop was cancelled after 1 tries and 5ms of delays"
+        );
+
+        let timed_out: Error<io::Error> = Error::TimedOut {
+            timeout: Duration::from_millis(1500),
+            total_delay: Duration::default(),
+            tries: 2,
+        };
+        assert_eq!(
+            timed_out.to_string(),
+            "gave up after 2 attempts over 0s of delays (last attempt timed out after 1.5s)"
+        );
+        assert!(cancelled.source().is_none());
+
+        let open: Error<io::Error> = Error::CircuitOpen {
+            retry_in: Duration::from_millis(4250),
+        };
+        assert_eq!(
+            open.to_string(),
+            "call was rejected because the circuit breaker is open for another 4.2s"
+        );
+    }
+
+    #[test]
+    fn exposes_last_error_and_totals() {
+        let error = retry(Fixed::from_millis(1).take(2), || Err::<(), _>("boom")).unwrap_err();
+
+        assert_eq!(error.last_error(), Some(&"boom"));
+        assert_eq!(error.tries(), 3);
+        assert_eq!(error.total_delay(), Duration::from_millis(2));
+        assert_eq!(error.into_last_error(), Some("boom"));
+
+        let cancelled: Error<&str> = Error::Cancelled {
+            total_delay: Duration::from_millis(5),
+            tries: 1,
+        };
+        assert_eq!(cancelled.last_error(), None);
+        assert_eq!(cancelled.total_delay(), Duration::from_millis(5));
+    }
+
+    #[test]
+    fn converts_into_io_errors() {
+        use std::io;
+
+        let error: io::Error = retry(Fixed::from_millis(1).take(1), || {
+            Err::<(), _>(io::Error::from(io::ErrorKind::ConnectionReset))
+        })
+        .unwrap_err()
+        .into();
+
+        assert_eq!(error.kind(), io::ErrorKind::ConnectionReset);
+        assert_eq!(
+            error.to_string(),
+            "gave up after 2 attempts over 1.0ms of delays (last error: connection reset)"
+        );
+        let source = error.get_ref().unwrap().source().unwrap();
+        assert_eq!(source.to_string(), "connection reset");
+
+        let cancelled: io::Error = Error::<io::Error>::Cancelled {
+            total_delay: Duration::from_millis(5),
+            tries: 1,
+        }
+        .into();
+        assert_eq!(cancelled.kind(), io::ErrorKind::Interrupted);
+    }
+
+    #[test]
+    fn retries_until_some() {
+        let mut polls = vec![None, Some(1), Some(2)].into_iter();
+        assert_eq!(
+            retry_until_some(NoDelay.take(5), || polls.next().flatten()),
+            Ok(1)
+        );
+
+        let result = retry_until_some(Fixed::from_millis(1).take(2), || None::<()>);
+        assert_eq!(
+            result,
+            Err(Error::Operation {
+                error: (),
+                total_delay: Duration::from_millis(2),
+                tries: 3,
+            })
+        );
+
+        let policy = crate::Policy::new(NoDelay).with_max_attempts(2);
+        let mut polls = vec![None, Some("ready")].into_iter();
+        assert_eq!(
+            policy.retry_until_some(|| polls.next().flatten()),
+            Ok("ready")
+        );
+        assert_eq!(
+            policy.retry_until_some(|| None::<()>).unwrap_err().tries(),
+            2
+        );
+    }
+
+    #[test]
+    fn awaits_conditions() {
+        let mut checks = 0;
+        assert_eq!(
+            await_condition(NoDelay.take(5), || {
+                checks += 1;
+                checks > 2
+            }),
+            Ok(())
+        );
+        assert_eq!(checks, 3);
+
+        let timeout = await_condition(Fixed::from_millis(1).take(3), || false).unwrap_err();
+        assert_eq!(timeout.checks(), 4);
+        assert_eq!(timeout.total_delay(), Duration::from_millis(3));
+    }
+
+    #[cfg(feature = "macros")]
+    #[test]
+    fn retries_annotated_functions() {
+        use std::cell::Cell;
+
+        #[crate::retry(policy = "crate::Policy::new(NoDelay.take(5))", max_attempts = 3)]
+        fn flaky(attempts: &Cell<u64>, failures: u64) -> Result<u64, &'static str> {
+            attempts.set(attempts.get() + 1);
+            if attempts.get() <= failures {
+                return Err("unavailable");
+            }
+            Ok(attempts.get())
+        }
+
+        let attempts = Cell::new(0);
+        assert_eq!(flaky(&attempts, 2), Ok(3));
+
+        attempts.set(0);
+        assert!(matches!(
+            flaky(&attempts, 5),
+            Err(Error::MaxAttempts { tries: 3, .. })
+        ));
+    }
+
+    #[cfg(all(feature = "macros", feature = "asynchronous"))]
+    #[tokio::test]
+    async fn retries_annotated_async_functions() {
+        use std::sync::{
+            atomic::{AtomicU64, Ordering},
+            Arc,
+        };
+
+        #[crate::retry(policy = "crate::Policy::new(NoDelay.take(5))")]
+        async fn flaky(attempts: Arc<AtomicU64>, name: String) -> Result<String, String> {
+            if attempts.fetch_add(1, Ordering::SeqCst) < 2 {
+                return Err(format!("{} is unavailable", name));
+            }
+            Ok(name)
+        }
+
+        let attempts = Arc::new(AtomicU64::new(0));
+        assert_eq!(
+            flaky(attempts.clone(), "search".to_owned()).await,
+            Ok("search".to_owned())
+        );
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+    }
 }
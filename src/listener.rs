@@ -0,0 +1,896 @@
+//! Listeners notified of every step of the calls made through a `Policy`, and the events passed to
+//! them.
+//!
+//! # Examples
+//!
+//! ```rust
+//! # use std::sync::atomic::{AtomicU64, Ordering};
+//! # use std::sync::Arc;
+//! # use std::time::Duration;
+//! # use retry::delay::NoDelay;
+//! use retry::listener::{AttemptOutcome, RetryListener};
+//! use retry::{CorrelationId, Policy};
+//!
+//! #[derive(Default)]
+//! struct Failures(AtomicU64);
+//!
+//! impl RetryListener for Failures {
+//!     fn on_attempt_end(&self, _: &CorrelationId, _: u64, outcome: AttemptOutcome<'_>, _: Duration) {
+//!         if !outcome.is_ok() {
+//!             self.0.fetch_add(1, Ordering::Relaxed);
+//!         }
+//!     }
+//! }
+//!
+//! let failures = Arc::new(Failures::default());
+//! let policy = Policy::new(NoDelay.take(3)).with_listener(failures.clone());
+//! let mut remaining = 2;
+//!
+//! let result = policy.retry(|| {
+//!     remaining -= 1;
+//!     if remaining > 0 { Err("not yet") } else { Ok(()) }
+//! });
+//!
+//! assert_eq!(result, Ok(()));
+//! assert_eq!(failures.0.load(Ordering::Relaxed), 1);
+//! ```
+
+use std::{
+    collections::VecDeque,
+    fmt,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime},
+};
+
+use crate::CorrelationId;
+
+/// Receives the events of the calls made through a policy.
+///
+/// Every method has an empty default implementation, so a listener only needs to implement the
+/// events it is interested in. Each event comes with the correlation ID of the call it belongs to.
+/// Listeners are shared by every call made through the policy, and possibly by several threads at
+/// once.
+pub trait RetryListener: Send + Sync {
+    /// Called before each attempt, with its number, starting at 1.
+    fn on_attempt_start(&self, id: &CorrelationId, attempt: u64) {
+        let _ = (id, attempt);
+    }
+
+    /// Called after each attempt, with its number, its outcome and how long it took.
+    fn on_attempt_end(
+        &self,
+        id: &CorrelationId,
+        attempt: u64,
+        outcome: AttemptOutcome<'_>,
+        latency: Duration,
+    ) {
+        let _ = (id, attempt, outcome, latency);
+    }
+
+    /// Called before waiting `delay` ahead of the next attempt.
+    fn on_backoff(&self, id: &CorrelationId, delay: Duration) {
+        let _ = (id, delay);
+    }
+
+    /// Called once when a call gives up, after the last attempt has ended.
+    fn on_give_up(&self, summary: &GiveUpSummary) {
+        let _ = summary;
+    }
+}
+
+impl<L> RetryListener for Arc<L>
+where
+    L: RetryListener + ?Sized,
+{
+    fn on_attempt_start(&self, id: &CorrelationId, attempt: u64) {
+        (**self).on_attempt_start(id, attempt)
+    }
+
+    fn on_attempt_end(
+        &self,
+        id: &CorrelationId,
+        attempt: u64,
+        outcome: AttemptOutcome<'_>,
+        latency: Duration,
+    ) {
+        (**self).on_attempt_end(id, attempt, outcome, latency)
+    }
+
+    fn on_backoff(&self, id: &CorrelationId, delay: Duration) {
+        (**self).on_backoff(id, delay)
+    }
+
+    fn on_give_up(&self, summary: &GiveUpSummary) {
+        (**self).on_give_up(summary)
+    }
+}
+
+/// How an attempt ended, mirroring `OperationResult`.
+#[derive(Clone, Copy, Debug)]
+pub enum AttemptOutcome<'a> {
+    /// The attempt succeeded.
+    Ok,
+    /// The attempt failed with an error that is retried if the schedule allows it.
+    Retry(&'a dyn fmt::Debug),
+    /// The attempt failed with an error that is not retried.
+    Err(&'a dyn fmt::Debug),
+    /// The attempt did not finish within the policy's attempt timeout.
+    TimedOut(Duration),
+}
+
+impl AttemptOutcome<'_> {
+    /// Returns `true` if the attempt succeeded.
+    pub fn is_ok(&self) -> bool {
+        matches!(self, AttemptOutcome::Ok)
+    }
+}
+
+/// What happened during a call that gave up.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GiveUpSummary {
+    pub(crate) correlation_id: CorrelationId,
+    pub(crate) attempts: u64,
+    pub(crate) total_delay: Duration,
+    pub(crate) delays: Vec<Duration>,
+    pub(crate) elapsed: Duration,
+}
+
+impl GiveUpSummary {
+    /// The correlation ID of the call.
+    pub fn correlation_id(&self) -> &CorrelationId {
+        &self.correlation_id
+    }
+
+    /// The number of attempts that were made.
+    pub fn attempts(&self) -> u64 {
+        self.attempts
+    }
+
+    /// The sum of the delays waited between attempts.
+    pub fn total_delay(&self) -> Duration {
+        self.total_delay
+    }
+
+    /// The delays waited between attempts, in order, exactly as the strategy produced them,
+    /// including any jitter.
+    pub fn delays(&self) -> &[Duration] {
+        &self.delays
+    }
+
+    /// The time from the start of the first attempt to the end of the last one.
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+}
+
+/// A call that gave up, with the errors of all its attempts, for the dead-letter hook of a policy
+/// to route the item it was processing to a dead-letter queue.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DeadLetter {
+    pub(crate) summary: GiveUpSummary,
+    pub(crate) errors: Vec<String>,
+    pub(crate) error_times: Vec<SystemTime>,
+    pub(crate) omitted_errors: u64,
+}
+
+impl DeadLetter {
+    /// The correlation ID of the call, such as the ID of the message it was processing when given
+    /// with `Policy::retry_with_correlation_id`.
+    pub fn correlation_id(&self) -> &CorrelationId {
+        &self.summary.correlation_id
+    }
+
+    /// The error of each attempt, in order, formatted with `Debug`. An attempt that timed out is
+    /// described as such.
+    ///
+    /// Only the errors kept by the policy's `ErrorRetention` are given; the errors of the attempts
+    /// in between are counted by `omitted_errors`.
+    pub fn errors(&self) -> &[String] {
+        &self.errors
+    }
+
+    /// The time at which each error of `errors` occurred, when its attempt ended, as read from the
+    /// system clock, for postmortems to line the failures up with the logs of the dependency.
+    pub fn error_times(&self) -> &[SystemTime] {
+        &self.error_times
+    }
+
+    /// The number of errors that were not kept, between the first and the last errors kept.
+    pub fn omitted_errors(&self) -> u64 {
+        self.omitted_errors
+    }
+
+    /// The attempts, delays and time taken by the call.
+    pub fn summary(&self) -> &GiveUpSummary {
+        &self.summary
+    }
+}
+
+/// Which errors of a call are kept for its dead letter, as set with `Policy::with_error_retention`,
+/// so that calls retrying thousands of times do not hold on to every error.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum ErrorRetention {
+    /// Keep every error.
+    #[default]
+    All,
+    /// Keep the errors of the last attempts, up to the given number.
+    Last(usize),
+    /// Keep the errors of the first attempts and of the last attempts, up to the given numbers.
+    FirstAndLast(usize, usize),
+}
+
+/// The errors of a call kept according to an `ErrorRetention`, with the time each occurred.
+#[derive(Debug)]
+pub(crate) struct RetainedErrors {
+    first: Vec<(SystemTime, String)>,
+    last: VecDeque<(SystemTime, String)>,
+    keep_first: usize,
+    keep_last: usize,
+    omitted: u64,
+}
+
+impl RetainedErrors {
+    pub(crate) fn new(retention: ErrorRetention) -> Self {
+        let (keep_first, keep_last) = match retention {
+            ErrorRetention::All => (usize::MAX, 0),
+            ErrorRetention::Last(last) => (0, last),
+            ErrorRetention::FirstAndLast(first, last) => (first, last),
+        };
+        RetainedErrors {
+            first: Vec::new(),
+            last: VecDeque::new(),
+            keep_first,
+            keep_last,
+            omitted: 0,
+        }
+    }
+
+    pub(crate) fn push(&mut self, error: (SystemTime, String)) {
+        if self.first.len() < self.keep_first {
+            self.first.push(error);
+        } else if self.keep_last == 0 {
+            self.omitted += 1;
+        } else {
+            if self.last.len() == self.keep_last {
+                self.last.pop_front();
+                self.omitted += 1;
+            }
+            self.last.push_back(error);
+        }
+    }
+
+    /// Take the errors kept, in order, and the number of errors omitted.
+    pub(crate) fn take(&mut self) -> (Vec<(SystemTime, String)>, u64) {
+        let mut errors = std::mem::take(&mut self.first);
+        errors.extend(self.last.drain(..));
+        (errors, std::mem::take(&mut self.omitted))
+    }
+}
+
+/// A call that has been retrying for longer than the policy's watchdog threshold.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SlowRetry {
+    pub(crate) correlation_id: CorrelationId,
+    pub(crate) attempts: u64,
+    pub(crate) elapsed: Duration,
+    pub(crate) threshold: Duration,
+}
+
+impl SlowRetry {
+    /// The correlation ID of the call.
+    pub fn correlation_id(&self) -> &CorrelationId {
+        &self.correlation_id
+    }
+
+    /// The number of attempts made so far.
+    pub fn attempts(&self) -> u64 {
+        self.attempts
+    }
+
+    /// The time since the start of the first attempt.
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /// The threshold that was exceeded.
+    pub fn threshold(&self) -> Duration {
+        self.threshold
+    }
+}
+
+/// A policy whose calls have kept retrying faster than its storm threshold, amplifying the load on
+/// a dependency that is probably already struggling.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RetryStorm {
+    pub(crate) retries_per_second: u64,
+    pub(crate) sustained: Duration,
+}
+
+impl RetryStorm {
+    /// The rate of retries, across every call made through the policy, that was exceeded.
+    pub fn retries_per_second(&self) -> u64 {
+        self.retries_per_second
+    }
+
+    /// How long the rate has been exceeded for.
+    pub fn sustained(&self) -> Duration {
+        self.sustained
+    }
+}
+
+/// The progress of a call that is about to wait before retrying, for example to render
+/// "retrying (3/10), next attempt in 4s, giving up in ~38s".
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Progress {
+    pub(crate) correlation_id: CorrelationId,
+    pub(crate) attempt: u64,
+    pub(crate) max_attempts: Option<u64>,
+    pub(crate) next_delay: Duration,
+    pub(crate) remaining_delay: Option<Duration>,
+}
+
+impl Progress {
+    /// The correlation ID of the call.
+    pub fn correlation_id(&self) -> &CorrelationId {
+        &self.correlation_id
+    }
+
+    /// The number of the attempt that just failed.
+    pub fn attempt(&self) -> u64 {
+        self.attempt
+    }
+
+    /// The largest number of attempts the call can make, or `None` if the delay strategy does not
+    /// report an upper bound on its length through `Iterator::size_hint`.
+    pub fn max_attempts(&self) -> Option<u64> {
+        self.max_attempts
+    }
+
+    /// The delay before the next attempt.
+    pub fn next_delay(&self) -> Duration {
+        self.next_delay
+    }
+
+    /// An estimate of the delays left before the call gives up, including `next_delay`, or
+    /// `None` if the delay strategy is not known to be finite.
+    ///
+    /// The estimate assumes that the delays match those of a fresh copy of the strategy, so it is
+    /// exact for deterministic strategies and approximate for randomized ones.
+    pub fn remaining_delay(&self) -> Option<Duration> {
+        self.remaining_delay
+    }
+}
+
+/// A threshold and the hook called once per call when it is exceeded.
+#[derive(Clone)]
+pub(crate) struct Watchdog {
+    pub(crate) threshold: Duration,
+    pub(crate) hook: Arc<dyn Fn(&SlowRetry) + Send + Sync>,
+}
+
+impl fmt::Debug for Watchdog {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter
+            .debug_struct("Watchdog")
+            .field("threshold", &self.threshold)
+            .finish()
+    }
+}
+
+/// Counts the retries of every call made through a policy, one second at a time, and calls its
+/// hook once when the count of every second has reached the threshold for long enough. Once a
+/// second falls below the threshold, the storm is over and the hook can be called again.
+#[derive(Clone)]
+pub(crate) struct StormDetector {
+    pub(crate) retries_per_second: u64,
+    pub(crate) sustained: Duration,
+    pub(crate) hook: Arc<dyn Fn(&RetryStorm) + Send + Sync>,
+    pub(crate) state: Arc<Mutex<StormState>>,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct StormState {
+    second: Option<Instant>,
+    retries: u64,
+    storming_since: Option<Instant>,
+    warned: bool,
+}
+
+impl StormDetector {
+    /// Count a retry made at `now`.
+    pub(crate) fn record_retry(&self, now: Instant) {
+        let second = Duration::from_secs(1);
+        let storm = {
+            let mut state = self
+                .state
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+            match state.second {
+                Some(start) if now.saturating_duration_since(start) < second => {}
+                start => {
+                    // The storm goes on only if the last second reached the threshold and the
+                    // retries of this one immediately follow it.
+                    let continues = start.is_some_and(|start| {
+                        state.retries >= self.retries_per_second
+                            && now.saturating_duration_since(start) < 2 * second
+                    });
+                    if !continues {
+                        state.storming_since = None;
+                        state.warned = false;
+                    }
+                    state.second = Some(now);
+                    state.retries = 0;
+                }
+            }
+            state.retries += 1;
+            if state.retries < self.retries_per_second {
+                None
+            } else {
+                let start = state.second.unwrap_or(now);
+                let since = *state.storming_since.get_or_insert(start);
+                let sustained = now.saturating_duration_since(since);
+                if !state.warned && sustained >= self.sustained {
+                    state.warned = true;
+                    Some(RetryStorm {
+                        retries_per_second: self.retries_per_second,
+                        sustained,
+                    })
+                } else {
+                    None
+                }
+            }
+        };
+        // The hook is called without the lock, so that it can make calls through the policy.
+        if let Some(storm) = storm {
+            (self.hook)(&storm);
+        }
+    }
+}
+
+impl fmt::Debug for StormDetector {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter
+            .debug_struct("StormDetector")
+            .field("retries_per_second", &self.retries_per_second)
+            .field("sustained", &self.sustained)
+            .finish()
+    }
+}
+
+/// A hook called with the progress of a call before every delay.
+#[derive(Clone)]
+pub(crate) struct ProgressHook(pub(crate) Arc<dyn Fn(&Progress) + Send + Sync>);
+
+impl fmt::Debug for ProgressHook {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.debug_struct("ProgressHook").finish()
+    }
+}
+
+/// A hook called with the errors of a call when it gives up.
+#[derive(Clone)]
+pub(crate) struct DeadLetterHook(pub(crate) Arc<dyn Fn(&DeadLetter) + Send + Sync>);
+
+impl fmt::Debug for DeadLetterHook {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.debug_struct("DeadLetterHook").finish()
+    }
+}
+
+/// An action undoing the side effects of a call that gave up.
+pub(crate) type Compensation = Arc<dyn Fn(&GiveUpSummary) + Send + Sync>;
+
+/// The compensating actions carried by a policy, in the order they were added.
+#[derive(Clone, Default)]
+pub(crate) struct Compensations(Vec<Compensation>);
+
+impl Compensations {
+    pub(crate) fn push(&mut self, compensation: Compensation) {
+        self.0.push(compensation);
+    }
+
+    /// The actions in the order they run, the last one added first.
+    pub(crate) fn in_order(&self) -> impl Iterator<Item = &Compensation> {
+        self.0.iter().rev()
+    }
+}
+
+impl fmt::Debug for Compensations {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter
+            .debug_struct("Compensations")
+            .field("len", &self.0.len())
+            .finish()
+    }
+}
+
+/// The listeners carried by a policy.
+#[derive(Clone, Default)]
+pub(crate) struct Listeners(Vec<Arc<dyn RetryListener>>);
+
+impl Listeners {
+    pub(crate) fn push(&mut self, listener: Arc<dyn RetryListener>) {
+        self.0.push(listener);
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = &dyn RetryListener> {
+        self.0.iter().map(|listener| &**listener)
+    }
+}
+
+impl fmt::Debug for Listeners {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter
+            .debug_struct("Listeners")
+            .field("len", &self.0.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{Arc, Mutex},
+        time::{Duration, SystemTime},
+    };
+
+    use super::{
+        AttemptOutcome, ErrorRetention, GiveUpSummary, Progress, RetainedErrors, RetryListener,
+        RetryStorm, SlowRetry,
+    };
+    use crate::{
+        clock::MockClock,
+        delay::{Fixed, NoDelay},
+        CorrelationId, OperationResult, Policy,
+    };
+
+    /// A listener that records every event as a string.
+    #[derive(Default)]
+    struct Recorder(Mutex<Vec<String>>);
+
+    impl Recorder {
+        fn events(&self) -> Vec<String> {
+            self.0.lock().unwrap().clone()
+        }
+    }
+
+    impl RetryListener for Recorder {
+        fn on_attempt_start(&self, _: &CorrelationId, attempt: u64) {
+            self.0.lock().unwrap().push(format!("start {}", attempt));
+        }
+
+        fn on_attempt_end(
+            &self,
+            _: &CorrelationId,
+            attempt: u64,
+            outcome: AttemptOutcome<'_>,
+            _: Duration,
+        ) {
+            self.0
+                .lock()
+                .unwrap()
+                .push(format!("end {} {:?}", attempt, outcome));
+        }
+
+        fn on_backoff(&self, _: &CorrelationId, delay: Duration) {
+            self.0.lock().unwrap().push(format!("backoff {:?}", delay));
+        }
+
+        fn on_give_up(&self, summary: &GiveUpSummary) {
+            self.0
+                .lock()
+                .unwrap()
+                .push(format!("give up after {}", summary.attempts()));
+        }
+    }
+
+    #[test]
+    fn reports_every_step_of_a_call() {
+        let recorder = Arc::new(Recorder::default());
+        let policy = Policy::new(NoDelay.take(1)).with_listener(recorder.clone());
+
+        let _ = policy.retry(|| Err::<(), _>("down"));
+
+        assert_eq!(
+            recorder.events(),
+            vec![
+                "start 1",
+                "end 1 Retry(\"down\")",
+                "backoff 0ns",
+                "start 2",
+                "end 2 Retry(\"down\")",
+                "give up after 2",
+            ]
+        );
+    }
+
+    #[test]
+    fn passes_the_correlation_id_to_every_event() {
+        #[derive(Default)]
+        struct Ids(Mutex<Vec<CorrelationId>>);
+
+        impl RetryListener for Ids {
+            fn on_attempt_start(&self, id: &CorrelationId, _: u64) {
+                self.0.lock().unwrap().push(id.clone());
+            }
+
+            fn on_give_up(&self, summary: &GiveUpSummary) {
+                self.0
+                    .lock()
+                    .unwrap()
+                    .push(summary.correlation_id().clone());
+            }
+        }
+
+        let ids = Arc::new(Ids::default());
+        let policy = Policy::new(NoDelay.take(1)).with_listener(ids.clone());
+        let mut seen = Vec::new();
+
+        let _ = policy.retry_with_correlation_id("req-1".into(), |_, id| {
+            seen.push(id.clone());
+            Err::<(), _>("down")
+        });
+
+        let expected = vec![CorrelationId::from("req-1"); 2];
+        assert_eq!(seen, expected);
+        assert_eq!(
+            *ids.0.lock().unwrap(),
+            vec![CorrelationId::from("req-1"); 3]
+        );
+    }
+
+    #[test]
+    fn give_up_summary_lists_the_delays_waited() {
+        #[derive(Default)]
+        struct Summaries(Mutex<Vec<GiveUpSummary>>);
+
+        impl RetryListener for Summaries {
+            fn on_give_up(&self, summary: &GiveUpSummary) {
+                self.0.lock().unwrap().push(summary.clone());
+            }
+        }
+
+        let summaries = Arc::new(Summaries::default());
+        let policy = Policy::new(vec![Duration::from_millis(1), Duration::from_millis(2)])
+            .with_listener(summaries.clone());
+
+        let _ = policy.retry(|| Err::<(), _>("down"));
+
+        let summaries = summaries.0.lock().unwrap();
+        assert_eq!(summaries.len(), 1);
+        assert_eq!(summaries[0].attempts(), 3);
+        assert_eq!(
+            summaries[0].delays(),
+            &[Duration::from_millis(1), Duration::from_millis(2)]
+        );
+        assert_eq!(summaries[0].total_delay(), Duration::from_millis(3));
+    }
+
+    #[test]
+    fn reports_progress_before_every_delay() {
+        let progress = Arc::new(Mutex::new(Vec::new()));
+        let policy = Policy::new(Fixed::from_millis(1).take(2)).with_progress({
+            let progress = progress.clone();
+            move |event: &Progress| {
+                progress.lock().unwrap().push((
+                    event.attempt(),
+                    event.max_attempts(),
+                    event.next_delay(),
+                    event.remaining_delay(),
+                ))
+            }
+        });
+
+        let _ = policy.retry(|| Err::<(), _>("down"));
+
+        let millis = Duration::from_millis;
+        assert_eq!(
+            *progress.lock().unwrap(),
+            vec![
+                (1, Some(3), millis(1), Some(millis(2))),
+                (2, Some(3), millis(1), Some(millis(1))),
+            ]
+        );
+    }
+
+    #[test]
+    fn progress_of_infinite_strategies_has_no_bounds() {
+        let progress = Arc::new(Mutex::new(Vec::new()));
+        let policy = Policy::new(NoDelay).with_progress({
+            let progress = progress.clone();
+            move |event: &Progress| {
+                progress
+                    .lock()
+                    .unwrap()
+                    .push((event.max_attempts(), event.remaining_delay()))
+            }
+        });
+        let mut remaining = 2;
+
+        let _ = policy.retry(|| {
+            remaining -= 1;
+            if remaining > 0 {
+                Err("down")
+            } else {
+                Ok(())
+            }
+        });
+
+        assert_eq!(*progress.lock().unwrap(), vec![(None, None)]);
+    }
+
+    #[test]
+    fn watchdog_fires_once_after_threshold() {
+        let slow = Arc::new(Mutex::new(Vec::new()));
+        let policy =
+            Policy::new(Fixed::from_millis(5).take(4)).with_watchdog(Duration::from_millis(8), {
+                let slow = slow.clone();
+                move |event: &SlowRetry| slow.lock().unwrap().push(event.attempts())
+            });
+
+        let _ = policy.retry(|| Err::<(), _>("down"));
+
+        assert_eq!(*slow.lock().unwrap(), vec![3]);
+    }
+
+    #[test]
+    fn watchdog_stays_quiet_for_fast_calls() {
+        let slow = Arc::new(Mutex::new(0));
+        let policy = Policy::new(NoDelay.take(2)).with_watchdog(Duration::from_secs(60), {
+            let slow = slow.clone();
+            move |_: &SlowRetry| *slow.lock().unwrap() += 1
+        });
+
+        let _ = policy.retry(|| Err::<(), _>("down"));
+
+        assert_eq!(*slow.lock().unwrap(), 0);
+    }
+
+    #[test]
+    fn reports_success_and_fatal_errors() {
+        let recorder = Arc::new(Recorder::default());
+        let policy = Policy::new(NoDelay).with_listener(recorder.clone());
+
+        let _ = policy.retry(|| Ok::<_, ()>(()));
+        let _ = policy.retry(|| OperationResult::<(), _>::Err("fatal"));
+
+        assert_eq!(
+            recorder.events(),
+            vec![
+                "start 1",
+                "end 1 Ok",
+                "start 1",
+                "end 1 Err(\"fatal\")",
+                "give up after 1",
+            ]
+        );
+    }
+
+    #[test]
+    fn retains_a_bounded_number_of_errors() {
+        let retain = |retention, count: u32| {
+            let mut errors = RetainedErrors::new(retention);
+            for error in 1..=count {
+                errors.push((SystemTime::UNIX_EPOCH, error.to_string()));
+            }
+            let (errors, omitted) = errors.take();
+            let errors: Vec<_> = errors.into_iter().map(|(_, error)| error).collect();
+            (errors, omitted)
+        };
+        let strings = |errors: &[&str]| errors.iter().map(|error| error.to_string()).collect();
+
+        assert_eq!(
+            retain(ErrorRetention::All, 3),
+            (strings(&["1", "2", "3"]), 0)
+        );
+        assert_eq!(
+            retain(ErrorRetention::Last(2), 5),
+            (strings(&["4", "5"]), 3)
+        );
+        assert_eq!(retain(ErrorRetention::Last(0), 2), (Vec::new(), 2));
+        assert_eq!(
+            retain(ErrorRetention::FirstAndLast(2, 1), 6),
+            (strings(&["1", "2", "6"]), 3)
+        );
+        assert_eq!(
+            retain(ErrorRetention::FirstAndLast(2, 2), 3),
+            (strings(&["1", "2", "3"]), 0)
+        );
+    }
+
+    #[test]
+    fn reports_dead_letters_with_their_errors() {
+        let dead_letters = Arc::new(Mutex::new(Vec::new()));
+        let queue = Arc::clone(&dead_letters);
+        let policy = Policy::new(Fixed::from_millis(1).take(3)).with_on_exhausted(move |dead| {
+            queue.lock().unwrap().push(dead.clone());
+        });
+        let mut attempts = 0;
+        let started = SystemTime::now();
+
+        let _ = policy.retry(|| Ok::<_, ()>(()));
+        let _ = policy.retry(|| {
+            attempts += 1;
+            if attempts < 2 {
+                OperationResult::<(), _>::Retry("busy")
+            } else {
+                OperationResult::Err("fatal")
+            }
+        });
+
+        let dead_letters = dead_letters.lock().unwrap();
+        assert_eq!(dead_letters.len(), 1);
+        assert_eq!(dead_letters[0].errors(), ["\"busy\"", "\"fatal\""]);
+        let times = dead_letters[0].error_times();
+        assert_eq!(times.len(), 2);
+        assert!(started <= times[0] && times[0] <= times[1]);
+        assert!(times[1].duration_since(times[0]).unwrap() >= Duration::from_millis(1));
+        assert_eq!(dead_letters[0].summary().attempts(), 2);
+        assert_eq!(
+            dead_letters[0].summary().delays(),
+            [Duration::from_millis(1)]
+        );
+    }
+
+    #[test]
+    fn compensates_before_reporting_dead_letters() {
+        let steps = Arc::new(Mutex::new(Vec::new()));
+        let (compensation, dead_letter) = (Arc::clone(&steps), Arc::clone(&steps));
+        let policy = Policy::new(NoDelay.take(1))
+            .with_compensation(move |summary| {
+                compensation
+                    .lock()
+                    .unwrap()
+                    .push(format!("compensate after {}", summary.attempts()));
+            })
+            .with_on_exhausted(move |_| dead_letter.lock().unwrap().push("dead letter".to_owned()));
+
+        let _ = policy.retry(|| Ok::<_, ()>(()));
+        let _ = policy.retry(|| Err::<(), _>("down"));
+
+        assert_eq!(
+            *steps.lock().unwrap(),
+            ["compensate after 2", "dead letter"]
+        );
+    }
+
+    #[test]
+    fn warns_once_per_retry_storm() {
+        let clock = MockClock::new();
+        let storms = Arc::new(Mutex::new(Vec::new()));
+        let warned = Arc::clone(&storms);
+        let policy = Policy::new(NoDelay)
+            .with_max_attempts(5)
+            .with_clock(clock.clone())
+            .with_storm_warning(4, Duration::from_secs(2), move |storm| {
+                warned.lock().unwrap().push(storm.clone());
+            });
+        // Every call retries 4 times, then the clock moves on by `step`.
+        let calls = |count: u32, step: Duration| {
+            for _ in 0..count {
+                let _ = policy.clone().retry(|| Err::<(), _>("down"));
+                clock.advance(step);
+            }
+        };
+
+        calls(3, Duration::from_secs(1));
+        assert_eq!(
+            *storms.lock().unwrap(),
+            [RetryStorm {
+                retries_per_second: 4,
+                sustained: Duration::from_secs(2)
+            }]
+        );
+        calls(2, Duration::from_secs(1));
+        assert_eq!(storms.lock().unwrap().len(), 1);
+
+        // A second without retries ends the storm, so the next one is reported too.
+        clock.advance(Duration::from_secs(1));
+        calls(3, Duration::from_secs(1));
+        assert_eq!(storms.lock().unwrap().len(), 2);
+    }
+}
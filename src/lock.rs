@@ -0,0 +1,348 @@
+//! Acquiring a lock held by someone else, such as an advisory file lock held by another process
+//! or a `Mutex` contended by other threads, by trying it with backoff until it is free instead of
+//! blocking on it indefinitely. The locks of `parking_lot` are tried the same way with the
+//! `"parking_lot"` feature flag.
+//!
+//! ```rust,no_run
+//! # use std::{fs::File, time::Duration};
+//! # use retry::delay::Exponential;
+//! use retry::lock::{acquire_with_retry, LockError};
+//!
+//! let file = File::create("/tmp/job.lock")?;
+//! match acquire_with_retry(Exponential::from_millis(10), Duration::from_secs(5), || {
+//!     file.try_lock()
+//! }) {
+//!     Ok(()) => println!("locked"),
+//!     Err(LockError::Busy { waited, .. }) => println!("still locked after {:?}", waited),
+//!     Err(LockError::Io(error)) => return Err(error.into()),
+//!     Err(LockError::Poisoned) => unreachable!("file locks are not poisoned"),
+//! }
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+//!
+//! ```rust
+//! # use std::{sync::Mutex, time::Duration};
+//! # use retry::delay::Fixed;
+//! use retry::lock::lock_with_retry;
+//!
+//! let jobs = Mutex::new(vec!["reindex"]);
+//! let mut jobs = lock_with_retry(&jobs, Fixed::from_millis(1), Duration::from_millis(100))?;
+//! jobs.push("vacuum");
+//! # Ok::<(), retry::lock::LockError>(())
+//! ```
+
+use std::{
+    error::Error as StdError,
+    fmt,
+    fs::TryLockError,
+    io,
+    sync::{self, Mutex, MutexGuard, RwLock, RwLockReadGuard, RwLockWriteGuard},
+    thread::sleep,
+    time::{Duration, Instant},
+};
+
+/// Why a lock could not be acquired.
+#[derive(Debug)]
+pub enum LockError {
+    /// The lock was still held when the strategy ended or the deadline passed.
+    Busy {
+        /// The time spent waiting for the lock.
+        waited: Duration,
+        /// The number of times the lock was tried.
+        tries: u64,
+    },
+    /// Trying the lock failed for another reason than it being held, so it was not tried again.
+    Io(io::Error),
+    /// The lock was poisoned by a thread that panicked while holding it, so it was not tried
+    /// again.
+    Poisoned,
+}
+
+impl fmt::Display for LockError {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            LockError::Busy { waited, tries } => write!(
+                formatter,
+                "the lock was still busy after {} tries over {:?}",
+                tries, waited
+            ),
+            LockError::Io(ref error) => write!(formatter, "failed to try the lock: {}", error),
+            LockError::Poisoned => formatter.write_str("the lock was poisoned"),
+        }
+    }
+}
+
+impl StdError for LockError {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match *self {
+            LockError::Busy { .. } | LockError::Poisoned => None,
+            LockError::Io(ref error) => Some(error),
+        }
+    }
+}
+
+/// Converts into an I/O error, of kind `WouldBlock` for a busy lock.
+impl From<LockError> for io::Error {
+    fn from(error: LockError) -> Self {
+        match error {
+            LockError::Busy { .. } => io::Error::new(io::ErrorKind::WouldBlock, error),
+            LockError::Io(error) => error,
+            LockError::Poisoned => io::Error::other(error),
+        }
+    }
+}
+
+/// Try a lock with `try_lock`, such as `File::try_lock`, until it is acquired, waiting the delays
+/// of `delays` while it is held, and giving up with `LockError::Busy` once they run out or
+/// `deadline` has passed since the first try.
+///
+/// The last delay is shortened so that the last try happens at the deadline.
+pub fn acquire_with_retry<T, D, O>(
+    delays: D,
+    deadline: Duration,
+    mut try_lock: O,
+) -> Result<T, LockError>
+where
+    D: IntoIterator<Item = Duration>,
+    O: FnMut() -> Result<T, TryLockError>,
+{
+    acquire(delays, deadline, || match try_lock() {
+        Ok(guard) => Ok(Some(guard)),
+        Err(TryLockError::Error(error)) => Err(LockError::Io(error)),
+        Err(TryLockError::WouldBlock) => Ok(None),
+    })
+}
+
+/// Lock `mutex` with `Mutex::try_lock` until it is free, like `acquire_with_retry`, giving up
+/// with `LockError::Poisoned` if it is poisoned.
+pub fn lock_with_retry<T, D>(
+    mutex: &Mutex<T>,
+    delays: D,
+    deadline: Duration,
+) -> Result<MutexGuard<'_, T>, LockError>
+where
+    T: ?Sized,
+    D: IntoIterator<Item = Duration>,
+{
+    acquire(delays, deadline, || poisonable(mutex.try_lock()))
+}
+
+/// Lock `lock` for reading with `RwLock::try_read` until no writer holds it, like
+/// `acquire_with_retry`, giving up with `LockError::Poisoned` if it is poisoned.
+pub fn read_with_retry<T, D>(
+    lock: &RwLock<T>,
+    delays: D,
+    deadline: Duration,
+) -> Result<RwLockReadGuard<'_, T>, LockError>
+where
+    T: ?Sized,
+    D: IntoIterator<Item = Duration>,
+{
+    acquire(delays, deadline, || poisonable(lock.try_read()))
+}
+
+/// Lock `lock` for writing with `RwLock::try_write` until nobody holds it, like
+/// `acquire_with_retry`, giving up with `LockError::Poisoned` if it is poisoned.
+pub fn write_with_retry<T, D>(
+    lock: &RwLock<T>,
+    delays: D,
+    deadline: Duration,
+) -> Result<RwLockWriteGuard<'_, T>, LockError>
+where
+    T: ?Sized,
+    D: IntoIterator<Item = Duration>,
+{
+    acquire(delays, deadline, || poisonable(lock.try_write()))
+}
+
+/// Trying the locks of `parking_lot`, which are never poisoned.
+#[cfg(feature = "parking_lot")]
+pub mod parking_lot {
+    use std::time::Duration;
+
+    use ::parking_lot::{Mutex, MutexGuard, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+    use super::{acquire, LockError};
+
+    /// Lock `mutex` with `Mutex::try_lock` until it is free, like `acquire_with_retry`.
+    pub fn lock_with_retry<T, D>(
+        mutex: &Mutex<T>,
+        delays: D,
+        deadline: Duration,
+    ) -> Result<MutexGuard<'_, T>, LockError>
+    where
+        T: ?Sized,
+        D: IntoIterator<Item = Duration>,
+    {
+        acquire(delays, deadline, || Ok(mutex.try_lock()))
+    }
+
+    /// Lock `lock` for reading with `RwLock::try_read` until no writer holds it, like
+    /// `acquire_with_retry`.
+    pub fn read_with_retry<T, D>(
+        lock: &RwLock<T>,
+        delays: D,
+        deadline: Duration,
+    ) -> Result<RwLockReadGuard<'_, T>, LockError>
+    where
+        T: ?Sized,
+        D: IntoIterator<Item = Duration>,
+    {
+        acquire(delays, deadline, || Ok(lock.try_read()))
+    }
+
+    /// Lock `lock` for writing with `RwLock::try_write` until nobody holds it, like
+    /// `acquire_with_retry`.
+    pub fn write_with_retry<T, D>(
+        lock: &RwLock<T>,
+        delays: D,
+        deadline: Duration,
+    ) -> Result<RwLockWriteGuard<'_, T>, LockError>
+    where
+        T: ?Sized,
+        D: IntoIterator<Item = Duration>,
+    {
+        acquire(delays, deadline, || Ok(lock.try_write()))
+    }
+}
+
+/// The guard of a try of a standard library lock, or `None` if it is held.
+fn poisonable<G>(result: sync::TryLockResult<G>) -> Result<Option<G>, LockError> {
+    match result {
+        Ok(guard) => Ok(Some(guard)),
+        Err(sync::TryLockError::WouldBlock) => Ok(None),
+        Err(sync::TryLockError::Poisoned(_)) => Err(LockError::Poisoned),
+    }
+}
+
+/// Try a lock until `try_lock` gives its guard, sleeping while it returns `None`.
+fn acquire<T, D, O>(delays: D, deadline: Duration, mut try_lock: O) -> Result<T, LockError>
+where
+    D: IntoIterator<Item = Duration>,
+    O: FnMut() -> Result<Option<T>, LockError>,
+{
+    let start = Instant::now();
+    let mut delays = delays.into_iter();
+    let mut tries = 0;
+    loop {
+        tries += 1;
+        if let Some(guard) = try_lock()? {
+            return Ok(guard);
+        }
+        let waited = start.elapsed();
+        let remaining = deadline.saturating_sub(waited);
+        match delays.next() {
+            Some(delay) if remaining > Duration::default() => sleep(delay.min(remaining)),
+            _ => return Err(LockError::Busy { waited, tries }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs::{self, File, TryLockError},
+        io,
+        sync::{Arc, Mutex, RwLock},
+        thread,
+        time::Duration,
+    };
+
+    use super::{
+        acquire_with_retry, lock_with_retry, read_with_retry, write_with_retry, LockError,
+    };
+    use crate::delay::Fixed;
+
+    #[test]
+    fn waits_for_a_file_lock_to_be_released() {
+        let path = std::env::temp_dir().join(format!("retry-lock-{}", std::process::id()));
+        let holder = File::create(&path).unwrap();
+        let waiter = File::open(&path).unwrap();
+        holder.lock().unwrap();
+
+        let result = acquire_with_retry(Fixed::from_millis(5), Duration::from_millis(50), || {
+            waiter.try_lock()
+        });
+        assert!(matches!(result, Err(LockError::Busy { waited, tries })
+            if waited >= Duration::from_millis(50) && tries > 1));
+
+        let mut tries = 0;
+        let result = acquire_with_retry(Fixed::from_millis(1), Duration::from_secs(5), || {
+            tries += 1;
+            if tries == 3 {
+                holder.unlock().unwrap();
+            }
+            waiter.try_lock()
+        });
+        assert!(result.is_ok());
+        assert_eq!(tries, 3);
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn returns_other_errors_at_once() {
+        let mut tries = 0;
+        let result = acquire_with_retry(Fixed::from_millis(1), Duration::from_secs(5), || {
+            tries += 1;
+            Err::<(), _>(TryLockError::Error(io::ErrorKind::PermissionDenied.into()))
+        });
+
+        let error = io::Error::from(result.unwrap_err());
+        assert_eq!(error.kind(), io::ErrorKind::PermissionDenied);
+        assert_eq!(tries, 1);
+    }
+
+    #[test]
+    fn waits_for_contended_mutexes_and_rw_locks() {
+        let mutex = Arc::new(Mutex::new(0));
+        let held = mutex.lock().unwrap();
+        let contender = Arc::clone(&mutex);
+        let waiter = thread::spawn(move || {
+            *lock_with_retry(&*contender, Fixed::from_millis(1), Duration::from_secs(5))
+                .unwrap() += 1;
+        });
+        thread::sleep(Duration::from_millis(10));
+        drop(held);
+        waiter.join().unwrap();
+        assert_eq!(*mutex.lock().unwrap(), 1);
+
+        let lock = RwLock::new(0);
+        let reader = lock.read().unwrap();
+        assert!(read_with_retry(&lock, Fixed::from_millis(1), Duration::from_millis(5)).is_ok());
+        let result = write_with_retry(&lock, Fixed::from_millis(1), Duration::from_millis(5));
+        assert!(matches!(result, Err(LockError::Busy { tries, .. }) if tries > 1));
+        drop(reader);
+        assert!(write_with_retry(&lock, Fixed::from_millis(1), Duration::from_millis(5)).is_ok());
+    }
+
+    #[test]
+    fn gives_up_on_poisoned_locks() {
+        let mutex = Arc::new(Mutex::new(()));
+        let poisoner = Arc::clone(&mutex);
+        let _ = thread::spawn(move || {
+            let _guard = poisoner.lock().unwrap();
+            panic!("poisoning the lock");
+        })
+        .join();
+
+        let result = lock_with_retry(&*mutex, Fixed::from_millis(1), Duration::from_secs(5));
+        assert!(matches!(result, Err(LockError::Poisoned)));
+    }
+
+    #[cfg(feature = "parking_lot")]
+    #[test]
+    fn waits_for_parking_lot_locks() {
+        let lock = ::parking_lot::RwLock::new(0);
+        let writer = lock.write();
+        let result = super::parking_lot::read_with_retry(
+            &lock,
+            Fixed::from_millis(1),
+            Duration::from_millis(5),
+        );
+        assert!(matches!(result, Err(LockError::Busy { .. })));
+        drop(writer);
+        *super::parking_lot::write_with_retry(&lock, Fixed::from_millis(1), Duration::ZERO)
+            .unwrap() += 1;
+        assert_eq!(*lock.read(), 1);
+    }
+}
@@ -0,0 +1,163 @@
+//! Connecting to a server with retries, waiting for it to come up.
+//!
+//! ```rust,no_run
+//! # use std::time::Duration;
+//! # use retry::delay::Exponential;
+//! use retry::Policy;
+//!
+//! let policy = Policy::new(Exponential::from_millis(10).take(8))
+//!     .with_attempt_timeout(Duration::from_secs(1));
+//! let stream = retry::net::connect_with_retry("localhost:5432", &policy)?;
+//! # Ok::<(), retry::Error<std::io::Error>>(())
+//! ```
+//!
+//! Each attempt resolves the address again, since the name of a server that is starting may not
+//! be registered yet, and tries every address it resolves to in turn. The errors of a server that
+//! is not up yet, such as a refused connection or an unreachable host, are retried, as are failed
+//! lookups; other errors, such as a malformed address or a denied permission, are returned at once.
+
+use std::{
+    io,
+    net::{SocketAddr, TcpStream, ToSocketAddrs},
+    time::Duration,
+};
+#[cfg(unix)]
+use std::{os::unix::net::UnixStream, path::Path};
+
+use crate::{predicates::IoPredicate, Error, OperationResult, Policy};
+
+/// Connect a TCP stream to `addr`, retrying according to `policy` until the server accepts the
+/// connection.
+///
+/// The policy's attempt timeout, if any, bounds each connection to an address, so that a server
+/// that does not answer at all is retried rather than waited for as long as the system allows.
+pub fn connect_with_retry<A, D>(addr: A, policy: &Policy<D>) -> Result<TcpStream, Error<io::Error>>
+where
+    A: ToSocketAddrs,
+    D: IntoIterator<Item = Duration> + Clone,
+{
+    let is_retryable = is_retryable();
+    policy.retry(|| {
+        let addrs = match addr.to_socket_addrs() {
+            Ok(addrs) => addrs,
+            Err(error) if error.kind() == io::ErrorKind::InvalidInput => {
+                return OperationResult::Err(error)
+            }
+            Err(error) => return OperationResult::Retry(error),
+        };
+        OperationResult::retry_if(connect_any(addrs, policy.attempt_timeout()), &is_retryable)
+    })
+}
+
+fn connect_any<I>(addrs: I, timeout: Option<Duration>) -> io::Result<TcpStream>
+where
+    I: Iterator<Item = SocketAddr>,
+{
+    let mut last_error = None;
+    for addr in addrs {
+        let result = match timeout {
+            Some(timeout) => TcpStream::connect_timeout(&addr, timeout),
+            None => TcpStream::connect(addr),
+        };
+        match result {
+            Ok(stream) => return Ok(stream),
+            Err(error) => last_error = Some(error),
+        }
+    }
+    Err(last_error.unwrap_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "the address resolved to no addresses",
+        )
+    }))
+}
+
+/// Connect a Unix domain socket to `path`, retrying according to `policy` until the server
+/// listens on it. A socket that does not exist yet, or that nothing listens on, is retried.
+///
+/// Unix sockets connect at once or fail, so the policy's attempt timeout does not apply.
+#[cfg(unix)]
+pub fn connect_unix_with_retry<P, D>(
+    path: P,
+    policy: &Policy<D>,
+) -> Result<UnixStream, Error<io::Error>>
+where
+    P: AsRef<Path>,
+    D: IntoIterator<Item = Duration> + Clone,
+{
+    let is_retryable = is_retryable();
+    policy.retry(|| OperationResult::retry_if(UnixStream::connect(path.as_ref()), &is_retryable))
+}
+
+/// The errors of a connection to a server that is not up yet.
+fn is_retryable() -> impl Fn(&io::Error) -> bool {
+    IoPredicate::transient()
+        .retry(io::ErrorKind::NotFound)
+        .retry(io::ErrorKind::AddrNotAvailable)
+        .retry(io::ErrorKind::HostUnreachable)
+        .retry(io::ErrorKind::NetworkUnreachable)
+        .build()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io, net::TcpListener, thread, time::Duration};
+
+    use super::connect_with_retry;
+    use crate::{delay::Fixed, Error, Policy};
+
+    #[test]
+    fn waits_for_the_server_to_listen() {
+        let addr = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let server = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            let listener = TcpListener::bind(addr).unwrap();
+            listener.accept().unwrap();
+        });
+        let policy = Policy::new(Fixed::from_millis(10).take(100))
+            .with_attempt_timeout(Duration::from_secs(1));
+
+        let stream = connect_with_retry(addr, &policy).unwrap();
+
+        assert_eq!(stream.peer_addr().unwrap(), addr);
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn returns_malformed_addresses_at_once() {
+        let policy = Policy::new(Fixed::from_millis(10).take(100));
+
+        let error = connect_with_retry("no port here", &policy).unwrap_err();
+
+        assert!(matches!(error, Error::Operation { tries: 1, .. }));
+        assert_eq!(
+            error.into_last_error().unwrap().kind(),
+            io::ErrorKind::InvalidInput
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn waits_for_the_unix_socket_to_exist() {
+        use std::os::unix::net::UnixListener;
+
+        let path = std::env::temp_dir().join(format!("retry-net-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let server_path = path.clone();
+        let server = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            let listener = UnixListener::bind(server_path).unwrap();
+            listener.accept().unwrap();
+        });
+        let policy = Policy::new(Fixed::from_millis(10).take(100));
+
+        let result = super::connect_unix_with_retry(&path, &policy);
+        server.join().unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        assert!(result.is_ok());
+    }
+}
@@ -7,7 +7,7 @@
 //! # use retry::delay::Fixed;
 //! use retry::OperationResult;
 //! let mut collection = vec![1, 2].into_iter();
-//! let value = retry(Fixed::from_millis(1), || {
+//! let value = retry(Fixed::from_millis(1).take(5), || {
 //!     match collection.next() {
 //!         Some(n) if n == 2 => OperationResult::Ok(n),
 //!         Some(_) => OperationResult::Retry("not 2"),
@@ -29,6 +29,27 @@ pub enum OperationResult<T, E> {
     Err(E),
 }
 
+impl<T, E> OperationResult<T, E> {
+    /// Convert a result, retrying its error only if `predicate` returns `true` for it.
+    ///
+    /// ```rust
+    /// # use retry::OperationResult;
+    /// let result = OperationResult::retry_if(Err::<(), _>(503), |status| *status >= 500);
+    ///
+    /// assert_eq!(result, OperationResult::Retry(503));
+    /// ```
+    pub fn retry_if<F>(result: Result<T, E>, predicate: F) -> Self
+    where
+        F: FnOnce(&E) -> bool,
+    {
+        match result {
+            Ok(value) => OperationResult::Ok(value),
+            Err(error) if predicate(&error) => OperationResult::Retry(error),
+            Err(error) => OperationResult::Err(error),
+        }
+    }
+}
+
 impl<T, E> From<Result<T, E>> for OperationResult<T, E> {
     fn from(item: Result<T, E>) -> Self {
         match item {
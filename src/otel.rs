@@ -0,0 +1,217 @@
+//! The OpenTelemetry instruments that policies report their calls to, with the `"otel"` feature.
+
+use std::{borrow::Cow, convert::TryFrom, fmt, time::Duration};
+
+use opentelemetry::{
+    global,
+    metrics::{Counter, Histogram},
+    trace::TraceContextExt,
+    Context, KeyValue,
+};
+
+/// The instruments of a policy, created from the global meter provider when the policy is created.
+#[derive(Clone)]
+pub(crate) struct Instruments {
+    retries: Counter<u64>,
+    outcomes: Counter<u64>,
+    backoff: Histogram<f64>,
+}
+
+impl Instruments {
+    pub(crate) fn new() -> Self {
+        let meter = global::meter("retry");
+        Instruments {
+            retries: meter
+                .u64_counter("retry.count")
+                .with_description("Attempts retried after a failure")
+                .build(),
+            outcomes: meter
+                .u64_counter("retry.outcome")
+                .with_description("Calls that succeeded or gave up, labeled with the outcome")
+                .build(),
+            backoff: meter
+                .f64_histogram("retry.backoff")
+                .with_description("Delays waited before retrying")
+                .with_unit("s")
+                .build(),
+        }
+    }
+
+    /// Record that a call is about to wait `delay` before retrying.
+    pub(crate) fn record_retry(&self, name: Option<&Cow<'static, str>>, delay: Duration) {
+        let attributes = operation(name);
+        let attributes = attributes.as_slice();
+        self.retries.add(1, attributes);
+        self.backoff.record(delay.as_secs_f64(), attributes);
+    }
+
+    /// Record how a call that made `tries` attempts ended, in the metrics and as attributes of the
+    /// current span.
+    pub(crate) fn record_outcome(
+        &self,
+        name: Option<&Cow<'static, str>>,
+        outcome: &'static str,
+        tries: u64,
+    ) {
+        let outcome = KeyValue::new("retry.outcome", outcome);
+        match operation(name) {
+            Some(operation) => self.outcomes.add(1, &[operation, outcome.clone()]),
+            None => self.outcomes.add(1, std::slice::from_ref(&outcome)),
+        }
+        let retries = i64::try_from(tries.saturating_sub(1)).unwrap_or(i64::MAX);
+        Context::map_current(|context| {
+            let span = context.span();
+            span.set_attribute(KeyValue::new("retry.count", retries));
+            span.set_attribute(outcome);
+        });
+    }
+}
+
+fn operation(name: Option<&Cow<'static, str>>) -> Option<KeyValue> {
+    name.map(|name| KeyValue::new("operation", name.clone()))
+}
+
+impl fmt::Debug for Instruments {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.debug_struct("Instruments").finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        borrow::Cow,
+        sync::{Arc, Mutex},
+        time::SystemTime,
+    };
+
+    use opentelemetry::{
+        global,
+        metrics::{
+            Counter, Histogram, HistogramBuilder, InstrumentBuilder, InstrumentProvider, Meter,
+            MeterProvider, SyncInstrument,
+        },
+        trace::{Span, SpanContext, Status, TraceContextExt},
+        Context, InstrumentationScope, KeyValue,
+    };
+
+    use crate::{delay::Fixed, Policy};
+
+    type Measurements = Arc<Mutex<Vec<(Cow<'static, str>, f64, Vec<KeyValue>)>>>;
+
+    /// Records the measurements of the calls made through the test's policy, with the name of the
+    /// instrument. Other tests may be making calls at the same time, and must not see allocations.
+    #[derive(Clone, Default)]
+    struct Recorder(Measurements);
+
+    struct Instrument(Cow<'static, str>, Measurements);
+
+    impl Instrument {
+        fn record(&self, value: f64, attributes: &[KeyValue]) {
+            if attributes.contains(&KeyValue::new("operation", "otel-test")) {
+                let measurement = (self.0.clone(), value, attributes.to_vec());
+                self.1.lock().unwrap().push(measurement);
+            }
+        }
+    }
+
+    impl SyncInstrument<u64> for Instrument {
+        fn measure(&self, value: u64, attributes: &[KeyValue]) {
+            self.record(value as f64, attributes);
+        }
+    }
+
+    impl SyncInstrument<f64> for Instrument {
+        fn measure(&self, value: f64, attributes: &[KeyValue]) {
+            self.record(value, attributes);
+        }
+    }
+
+    impl InstrumentProvider for Recorder {
+        fn u64_counter(&self, builder: InstrumentBuilder<'_, Counter<u64>>) -> Counter<u64> {
+            Counter::new(Arc::new(Instrument(builder.name, Arc::clone(&self.0))))
+        }
+
+        fn f64_histogram(&self, builder: HistogramBuilder<'_, Histogram<f64>>) -> Histogram<f64> {
+            Histogram::new(Arc::new(Instrument(builder.name, Arc::clone(&self.0))))
+        }
+    }
+
+    impl MeterProvider for Recorder {
+        fn meter_with_scope(&self, _: InstrumentationScope) -> Meter {
+            Meter::new(Arc::new(self.clone()))
+        }
+    }
+
+    /// A span that records the attributes set on it.
+    #[derive(Debug)]
+    struct RecordingSpan(Arc<Mutex<Vec<KeyValue>>>);
+
+    impl Span for RecordingSpan {
+        fn add_event_with_timestamp<T>(&mut self, _: T, _: SystemTime, _: Vec<KeyValue>)
+        where
+            T: Into<Cow<'static, str>>,
+        {
+        }
+
+        fn span_context(&self) -> &SpanContext {
+            &SpanContext::NONE
+        }
+
+        fn is_recording(&self) -> bool {
+            true
+        }
+
+        fn set_attribute(&mut self, attribute: KeyValue) {
+            self.0.lock().unwrap().push(attribute);
+        }
+
+        fn set_status(&mut self, _: Status) {}
+
+        fn update_name<T>(&mut self, _: T)
+        where
+            T: Into<Cow<'static, str>>,
+        {
+        }
+
+        fn add_link(&mut self, _: SpanContext, _: Vec<KeyValue>) {}
+
+        fn end_with_timestamp(&mut self, _: SystemTime) {}
+    }
+
+    #[test]
+    fn reports_retries_and_outcomes() {
+        let recorder = Recorder::default();
+        global::set_meter_provider(recorder.clone());
+        let policy = Policy::new(Fixed::from_millis(1).take(2)).with_name("otel-test");
+        let attributes = Arc::new(Mutex::new(Vec::new()));
+        let _span = Context::current_with_span(RecordingSpan(Arc::clone(&attributes))).attach();
+
+        let _ = policy.retry(|| Err::<(), _>("down"));
+
+        let measurements: Vec<_> = recorder
+            .0
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(name, value, attributes)| (name.clone(), *value, attributes.len()))
+            .collect();
+        assert_eq!(
+            measurements,
+            [
+                ("retry.count".into(), 1.0, 1),
+                ("retry.backoff".into(), 0.001, 1),
+                ("retry.count".into(), 1.0, 1),
+                ("retry.backoff".into(), 0.001, 1),
+                ("retry.outcome".into(), 1.0, 2),
+            ]
+        );
+        assert_eq!(
+            *attributes.lock().unwrap(),
+            [
+                KeyValue::new("retry.count", 2),
+                KeyValue::new("retry.outcome", "gave_up"),
+            ]
+        );
+    }
+}
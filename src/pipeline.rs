@@ -0,0 +1,235 @@
+use std::{borrow::Cow, error::Error as StdError, fmt, time::Duration};
+
+use crate::{Error, OperationResult, Policy};
+
+/// A sequence of steps, each retried on its own, that resumes from the step that failed rather
+/// than starting over, for workflows such as "upload, register, activate" whose first steps are
+/// expensive or have side effects.
+///
+/// The steps share a state, which earlier steps fill in for later ones. Running the pipeline
+/// retries each incomplete step in turn with a fresh schedule from the policy. If a step gives up,
+/// the pipeline stops there, and running it again starts with that step.
+///
+/// ```rust
+/// # use retry::delay::NoDelay;
+/// use retry::{Pipeline, Policy};
+///
+/// #[derive(Default)]
+/// struct Deployment {
+///     object: Option<String>,
+///     uploads: u32,
+///     activations: u32,
+/// }
+///
+/// let mut pipeline = Pipeline::new(Deployment::default())
+///     .step("upload", |deployment: &mut Deployment| {
+///         deployment.uploads += 1;
+///         deployment.object = Some("artifact-1".to_owned());
+///         Ok(())
+///     })
+///     .step("activate", |deployment: &mut Deployment| {
+///         deployment.activations += 1;
+///         if deployment.activations < 3 { Err("unavailable") } else { Ok(()) }
+///     });
+///
+/// let policy = Policy::new(NoDelay).with_max_attempts(2);
+/// let error = pipeline.run(&policy).unwrap_err();
+/// assert_eq!(error.name(), "activate");
+///
+/// assert_eq!(pipeline.run(&policy), Ok(()));
+/// assert_eq!(pipeline.state().uploads, 1);
+/// ```
+pub struct Pipeline<'a, S, E> {
+    state: S,
+    steps: Vec<Step<'a, S, E>>,
+    completed: usize,
+}
+
+type StepFn<'a, S, E> = Box<dyn FnMut(&mut S) -> OperationResult<(), E> + 'a>;
+
+struct Step<'a, S, E> {
+    name: Cow<'static, str>,
+    run: StepFn<'a, S, E>,
+}
+
+impl<'a, S, E> Pipeline<'a, S, E> {
+    /// Create a pipeline without any step, whose steps share the given state.
+    pub fn new(state: S) -> Self {
+        Pipeline {
+            state,
+            steps: Vec::new(),
+            completed: 0,
+        }
+    }
+
+    /// Add a step to run after the ones added before it.
+    pub fn step<N, F, OR>(mut self, name: N, mut step: F) -> Self
+    where
+        N: Into<Cow<'static, str>>,
+        F: FnMut(&mut S) -> OR + 'a,
+        OR: Into<OperationResult<(), E>>,
+    {
+        self.steps.push(Step {
+            name: name.into(),
+            run: Box::new(move |state| step(state).into()),
+        });
+        self
+    }
+
+    /// The number of steps that have completed.
+    pub fn completed(&self) -> usize {
+        self.completed
+    }
+
+    /// Returns `true` if every step has completed.
+    pub fn is_complete(&self) -> bool {
+        self.completed == self.steps.len()
+    }
+
+    /// The state shared by the steps.
+    pub fn state(&self) -> &S {
+        &self.state
+    }
+
+    /// Consume the pipeline, returning the state shared by the steps.
+    pub fn into_state(self) -> S {
+        self.state
+    }
+
+    /// Run the incomplete steps in order, retrying each of them according to `policy`, and stop at
+    /// the first one that gives up.
+    pub fn run<D>(&mut self, policy: &Policy<D>) -> Result<(), StepError<E>>
+    where
+        D: IntoIterator<Item = Duration> + Clone,
+        E: fmt::Debug,
+    {
+        let state = &mut self.state;
+        while let Some(step) = self.steps.get_mut(self.completed) {
+            let run = &mut step.run;
+            if let Err(error) = policy.retry(|| run(state)) {
+                return Err(StepError {
+                    step: self.completed,
+                    name: step.name.clone(),
+                    error,
+                });
+            }
+            self.completed += 1;
+        }
+        Ok(())
+    }
+}
+
+impl<S, E> fmt::Debug for Pipeline<'_, S, E>
+where
+    S: fmt::Debug,
+{
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter
+            .debug_struct("Pipeline")
+            .field("state", &self.state)
+            .field(
+                "steps",
+                &self.steps.iter().map(|step| &step.name).collect::<Vec<_>>(),
+            )
+            .field("completed", &self.completed)
+            .finish()
+    }
+}
+
+/// The step of a `Pipeline` that gave up, and why.
+#[derive(Debug, PartialEq, Eq)]
+pub struct StepError<E> {
+    step: usize,
+    name: Cow<'static, str>,
+    error: Error<E>,
+}
+
+impl<E> StepError<E> {
+    /// The index of the step, starting at 0.
+    pub fn step(&self) -> usize {
+        self.step
+    }
+
+    /// The name of the step.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Why the step gave up.
+    pub fn error(&self) -> &Error<E> {
+        &self.error
+    }
+
+    /// Consume the error, returning why the step gave up.
+    pub fn into_error(self) -> Error<E> {
+        self.error
+    }
+}
+
+impl<E> fmt::Display for StepError<E>
+where
+    E: fmt::Display,
+{
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if formatter.alternate() {
+            write!(formatter, "step {:?} failed: {:#}", self.name, self.error)
+        } else {
+            write!(formatter, "step {:?} failed: {}", self.name, self.error)
+        }
+    }
+}
+
+impl<E> StdError for StepError<E>
+where
+    E: StdError + 'static,
+{
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        Some(&self.error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Pipeline;
+    use crate::{delay::NoDelay, OperationResult, Policy};
+
+    #[test]
+    fn resumes_from_the_failed_step() {
+        let mut runs = Vec::new();
+        let mut registrations = 0;
+        let policy = Policy::new(NoDelay).with_max_attempts(2);
+
+        let mut pipeline = Pipeline::new(())
+            .step("upload", |_: &mut ()| {
+                runs.push("upload");
+                Ok::<_, &str>(())
+            })
+            .step("register", |_: &mut ()| {
+                registrations += 1;
+                if registrations == 1 {
+                    OperationResult::Retry("conflict")
+                } else if registrations == 2 {
+                    OperationResult::Err("rejected")
+                } else {
+                    OperationResult::Ok(())
+                }
+            });
+
+        let error = pipeline.run(&policy).unwrap_err();
+        assert_eq!(error.step(), 1);
+        assert_eq!(error.error().tries(), 2);
+        assert_eq!(
+            error.to_string(),
+            "step \"register\" failed: gave up after 2 attempts over 0s of delays (last error: \
+             rejected)"
+        );
+        assert_eq!(pipeline.completed(), 1);
+
+        assert_eq!(pipeline.run(&policy), Ok(()));
+        assert!(pipeline.is_complete());
+        assert_eq!(pipeline.run(&policy), Ok(()));
+        drop(pipeline);
+        assert_eq!(runs, ["upload"]);
+        assert_eq!(registrations, 3);
+    }
+}
@@ -0,0 +1,1540 @@
+//! A reusable retry configuration.
+//!
+//! # Examples
+//!
+//! ```rust
+//! # use retry::delay::Exponential;
+//! use retry::Policy;
+//! let policy = Policy::new(Exponential::from_millis(10).take(3));
+//! let mut collection = vec![1, 2].into_iter();
+//!
+//! let value = policy.retry(|| match collection.next() {
+//!     Some(n) if n == 2 => Ok(n),
+//!     Some(_) => Err("not 2"),
+//!     None => Err("not found"),
+//! });
+//!
+//! assert_eq!(value, Ok(2));
+//! ```
+
+use std::{
+    borrow::Cow,
+    cell::Cell,
+    fmt::{self, Debug, Display},
+    sync::Arc,
+    thread::sleep,
+    time::{Duration, Instant},
+};
+
+#[cfg(feature = "random")]
+use crate::delay::{Randomized, SharedRng};
+use crate::{
+    aggregate::{ErrorAggregator, Last},
+    breaker::CircuitBreaker,
+    budget::RetryBudget,
+    bulkhead::Bulkhead,
+    cancel::CancelToken,
+    classified::{Classified, Outcome, RetryAfterHint},
+    clock::{Clock, Sleeper, SystemClock, TimeSource, WallClock},
+    context::AttemptContext,
+    delay::{Introspect, Param, Validate, ValidationError},
+    listener::{
+        Compensations, DeadLetter, DeadLetterHook, ErrorRetention, GiveUpSummary, Listeners,
+        Progress, ProgressHook, RetryListener, RetryStorm, SlowRetry, StormDetector, Watchdog,
+    },
+    session::Session,
+    stats::{Counters, PolicyStats},
+    CorrelationId, Error, IdempotencyKey, OperationResult,
+};
+
+/// A delay strategy together with options that control how operations are retried.
+///
+/// The strategy is cloned for every call, so a single policy can be shared by many operations,
+/// each getting its own independent schedule.
+///
+/// A synchronous call does not allocate, unless cloning the strategy does, or listeners or a
+/// dead-letter hook are registered, which are given the list of delays when the call gives up.
+/// The integrations enabled by features, such as logs and metrics, may allocate when they are
+/// active.
+///
+/// With the `"tracing"` feature, every call made through a policy runs in a `retry` span. Each
+/// retried attempt is recorded as an event with its number, the delay before the next attempt and
+/// the error, and giving up is recorded as a warning with the total number of attempts.
+///
+/// With the `"log"` feature, every retried attempt is logged at the debug level and giving up is
+/// logged at the warn level, under the `retry` target. The target and levels can be changed with
+/// `with_log_target` and `with_log_levels`.
+///
+/// With the `"metrics"` feature, every call made through a policy increments the `retry.attempts`
+/// counter for each attempt and the `retry.giveups` counter when it gives up, and records each
+/// delay in seconds in the `retry.delay` histogram. When the policy has a name, the metrics are
+/// labeled with it as `operation`.
+///
+/// With the `"prometheus"` feature, a policy can also carry a `PolicyMetrics` handle, set with
+/// `with_prometheus`, whose collectors are updated in the same way.
+///
+/// With the `"otel"` feature, every call made through a policy is reported to OpenTelemetry: the
+/// `retry.count` counter is incremented for each retry and each delay is recorded in seconds in
+/// the `retry.backoff` histogram, while the `retry.outcome` counter counts the calls that ended,
+/// labeled with `retry.outcome`, `success` or `gave_up`. The same outcome and the number of
+/// retries are set as the `retry.outcome` and `retry.count` attributes of the current span. The
+/// instruments are created from the global meter provider when the policy is created, so the
+/// provider must be installed first.
+#[derive(Clone, Debug)]
+pub struct Policy<D> {
+    delays: D,
+    options: Options,
+}
+
+/// The options of a policy that do not depend on its delay strategy.
+#[derive(Clone, Debug)]
+pub(crate) struct Options {
+    pub(crate) attempt_timeout: Option<Duration>,
+    pub(crate) bulkhead: Option<Bulkhead>,
+    pub(crate) retry_budget: Option<Arc<dyn RetryBudget>>,
+    pub(crate) cost_budget: Option<u64>,
+    pub(crate) cancellation: Option<CancelToken>,
+    pub(crate) circuit_breaker: Option<CircuitBreaker>,
+    pub(crate) clock: Option<Arc<dyn Clock>>,
+    pub(crate) time_source: Option<TimeSource>,
+    pub(crate) sleeper: Option<Arc<dyn Sleeper>>,
+    pub(crate) max_attempts: Option<u64>,
+    pub(crate) max_delay: Option<Duration>,
+    pub(crate) min_interval: Option<Duration>,
+    pub(crate) time_budget: Option<Duration>,
+    pub(crate) name: Option<Cow<'static, str>>,
+    pub(crate) listeners: Listeners,
+    pub(crate) watchdog: Option<Watchdog>,
+    pub(crate) progress: Option<ProgressHook>,
+    pub(crate) dead_letter: Option<DeadLetterHook>,
+    pub(crate) error_retention: ErrorRetention,
+    pub(crate) compensations: Compensations,
+    pub(crate) health_check: Option<HealthCheck>,
+    pub(crate) suspend_detection: Option<SuspendDetection>,
+    pub(crate) storm: Option<StormDetector>,
+    pub(crate) splay: Option<Duration>,
+    pub(crate) scheduling: Scheduling,
+    pub(crate) stats: Arc<Counters>,
+    #[cfg(feature = "log")]
+    pub(crate) log: LogOptions,
+    #[cfg(feature = "prometheus")]
+    pub(crate) prometheus: Option<crate::prometheus::PolicyMetrics>,
+    #[cfg(feature = "otel")]
+    pub(crate) otel: crate::otel::Instruments,
+}
+
+impl Options {
+    /// The current time, as read from the policy's clock, or from the default clock of the
+    /// executor if the policy has none.
+    pub(crate) fn now(&self, default: &dyn Clock) -> Instant {
+        match self.clock {
+            Some(ref clock) => clock.now(),
+            None => default.now(),
+        }
+    }
+
+    /// Wait between synchronous attempts, with the policy's sleeper, returning how long was
+    /// waited, which is less than `duration` if the health check passed first, and more if the
+    /// machine was suspended.
+    pub(crate) fn sleep(&self, duration: Duration) -> Duration {
+        let mut waiting = self.waiting(duration);
+        while let Some(interval) = waiting.next_interval() {
+            self.sleep_for(interval);
+            waiting.slept(interval);
+        }
+        waiting.waited()
+    }
+
+    /// Start waiting `duration` between attempts.
+    pub(crate) fn waiting(&self, duration: Duration) -> Waiting<'_> {
+        let suspend = self.suspend_detection.as_ref().map(|detection| {
+            // A policy that measures the monotonic time can not see a suspension with its own
+            // clock, so it is detected with the wall clock instead.
+            let clock: &dyn Clock = match (self.time_source, self.clock.as_ref()) {
+                (Some(TimeSource::Monotonic), _) | (_, None) => &detection.wall_clock,
+                (_, Some(clock)) => &**clock,
+            };
+            (detection.interval, clock, clock.now())
+        });
+        Waiting {
+            duration,
+            waited: Duration::default(),
+            since_probe: Duration::default(),
+            health_check: self.health_check.as_ref(),
+            suspend,
+            cancellation: self.cancellation.as_ref(),
+            recovered: false,
+        }
+    }
+
+    /// Whether the policy's cancellation token was cancelled.
+    pub(crate) fn cancelled(&self) -> bool {
+        self.cancellation
+            .as_ref()
+            .is_some_and(|token| token.is_cancelled())
+    }
+
+    fn sleep_for(&self, duration: Duration) {
+        match self.sleeper {
+            Some(ref sleeper) => sleeper.sleep(duration),
+            None => sleep(duration),
+        }
+    }
+}
+
+/// When the delays between the attempts of a call start.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Scheduling {
+    /// Each delay starts when the attempt before it ends, so attempts are spread further apart the
+    /// longer they take. It is the default.
+    #[default]
+    FixedDelay,
+    /// Each delay starts when the attempt before it starts, so attempts keep to the cadence of
+    /// the schedule however long they take, as pollers expect. The latency of each attempt is
+    /// taken off the delay after it, down to no delay at all for an attempt that took longer than
+    /// the delay. A delay asked for by the dependency, such as with `Retry-After`, is waited in
+    /// full.
+    FixedRate,
+}
+
+/// A delay between attempts being waited in intervals, so that the health check can be probed
+/// and a suspension of the machine detected between them.
+pub(crate) struct Waiting<'a> {
+    duration: Duration,
+    waited: Duration,
+    since_probe: Duration,
+    health_check: Option<&'a HealthCheck>,
+    suspend: Option<(Duration, &'a dyn Clock, Instant)>,
+    cancellation: Option<&'a CancelToken>,
+    recovered: bool,
+}
+
+/// How often a policy with a cancellation token checks it while waiting between attempts.
+const CANCELLATION_INTERVAL: Duration = Duration::from_millis(100);
+
+impl Waiting<'_> {
+    /// How long to sleep next, or `None` if the wait is over.
+    pub(crate) fn next_interval(&self) -> Option<Duration> {
+        if self.recovered
+            || self.waited >= self.duration
+            || self.cancellation.is_some_and(CancelToken::is_cancelled)
+        {
+            return None;
+        }
+        let mut interval = self.duration - self.waited;
+        if let Some(health_check) = self.health_check {
+            interval = interval.min(health_check.interval - self.since_probe);
+        }
+        if let Some((check_interval, _, _)) = self.suspend {
+            interval = interval.min(check_interval);
+        }
+        if self.cancellation.is_some() {
+            interval = interval.min(CANCELLATION_INTERVAL);
+        }
+        Some(interval)
+    }
+
+    /// Record that the sleeper slept for `interval`.
+    pub(crate) fn slept(&mut self, interval: Duration) {
+        self.waited += interval;
+        if let Some((_, clock, started)) = self.suspend {
+            // The sleeper does not count the time the machine was suspended, while the wall clock
+            // does, so the time that passed is whichever is longer. A wall clock set back while
+            // waiting only makes it shorter, so it is ignored.
+            self.waited = self
+                .waited
+                .max(clock.now().saturating_duration_since(started));
+        }
+        if let Some(health_check) = self.health_check {
+            self.since_probe += interval;
+            if self.since_probe >= health_check.interval {
+                self.since_probe = Duration::default();
+                self.recovered = self.waited < self.duration && (health_check.probe)();
+            }
+        }
+    }
+
+    /// How long was waited.
+    pub(crate) fn waited(&self) -> Duration {
+        self.waited
+    }
+}
+
+/// Detects that the machine was suspended while waiting between attempts.
+#[derive(Clone, Debug)]
+pub(crate) struct SuspendDetection {
+    interval: Duration,
+    wall_clock: WallClock,
+}
+
+/// A probe of the health of the dependency, checked at every interval of a delay.
+#[derive(Clone)]
+pub(crate) struct HealthCheck {
+    pub(crate) interval: Duration,
+    pub(crate) probe: Arc<dyn Fn() -> bool + Send + Sync>,
+}
+
+impl Debug for HealthCheck {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter
+            .debug_struct("HealthCheck")
+            .field("interval", &self.interval)
+            .finish()
+    }
+}
+
+#[cfg(feature = "log")]
+#[derive(Clone, Debug)]
+pub(crate) struct LogOptions {
+    pub(crate) target: &'static str,
+    pub(crate) retry_level: log::Level,
+    pub(crate) give_up_level: log::Level,
+}
+
+impl<D> Policy<D> {
+    /// Create a new `Policy` using the given delay strategy.
+    pub fn new(delays: D) -> Self {
+        Policy {
+            delays,
+            options: Options {
+                attempt_timeout: None,
+                bulkhead: None,
+                retry_budget: None,
+                cost_budget: None,
+                cancellation: None,
+                circuit_breaker: None,
+                clock: None,
+                time_source: Some(TimeSource::Monotonic),
+                sleeper: None,
+                max_attempts: None,
+                max_delay: None,
+                min_interval: None,
+                time_budget: None,
+                name: None,
+                listeners: Listeners::default(),
+                watchdog: None,
+                progress: None,
+                dead_letter: None,
+                error_retention: ErrorRetention::All,
+                compensations: Compensations::default(),
+                health_check: None,
+                suspend_detection: None,
+                storm: None,
+                splay: None,
+                scheduling: Scheduling::FixedDelay,
+                stats: Arc::default(),
+                #[cfg(feature = "log")]
+                log: LogOptions {
+                    target: "retry",
+                    retry_level: log::Level::Debug,
+                    give_up_level: log::Level::Warn,
+                },
+                #[cfg(feature = "prometheus")]
+                prometheus: None,
+                #[cfg(feature = "otel")]
+                otel: crate::otel::Instruments::new(),
+            },
+        }
+    }
+
+    /// Name the operations retried with this policy, to identify them in logs, traces and
+    /// metrics.
+    pub fn with_name<N>(mut self, name: N) -> Self
+    where
+        N: Into<Cow<'static, str>>,
+    {
+        self.options.name = Some(name.into());
+        self
+    }
+
+    /// The name of the operations retried with this policy, if any.
+    pub fn name(&self) -> Option<&str> {
+        self.options.name.as_deref()
+    }
+
+    /// Notify the given listener of every attempt, delay and give-up of the calls made through this
+    /// policy. Several listeners can be added; they are notified in the order they were added.
+    pub fn with_listener<L>(mut self, listener: L) -> Self
+    where
+        L: RetryListener + 'static,
+    {
+        self.options.listeners.push(Arc::new(listener));
+        self
+    }
+
+    /// Call `on_slow` once for every call that is still retrying after `threshold`, to warn about
+    /// it before it gives up.
+    ///
+    /// The elapsed time is checked when an attempt starts and when it fails, so the hook runs at
+    /// the first of these points after the threshold has passed.
+    pub fn with_watchdog<F>(mut self, threshold: Duration, on_slow: F) -> Self
+    where
+        F: Fn(&SlowRetry) + Send + Sync + 'static,
+    {
+        self.options.watchdog = Some(Watchdog {
+            threshold,
+            hook: Arc::new(on_slow),
+        });
+        self
+    }
+
+    /// Call `on_storm` once when the calls made through this policy, and its clones, have retried
+    /// at least `retries_per_second` times in every second for `sustained`, so that operators learn
+    /// that the retries are amplifying an outage. The hook is called again only after a second
+    /// with fewer retries has ended the storm.
+    ///
+    /// ```rust
+    /// # use std::sync::atomic::{AtomicU32, Ordering};
+    /// # use std::sync::Arc;
+    /// # use std::time::Duration;
+    /// # use retry::delay::NoDelay;
+    /// use retry::Policy;
+    ///
+    /// let storms = Arc::new(AtomicU32::new(0));
+    /// let warned = Arc::clone(&storms);
+    /// let policy = Policy::new(NoDelay.take(100)).with_storm_warning(
+    ///     50,
+    ///     Duration::default(),
+    ///     move |storm| {
+    ///         assert_eq!(storm.retries_per_second(), 50);
+    ///         warned.fetch_add(1, Ordering::Relaxed);
+    ///     },
+    /// );
+    ///
+    /// let _ = policy.retry(|| Err::<(), _>("unavailable"));
+    /// assert_eq!(storms.load(Ordering::Relaxed), 1);
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if `retries_per_second` is zero.
+    pub fn with_storm_warning<F>(
+        mut self,
+        retries_per_second: u64,
+        sustained: Duration,
+        on_storm: F,
+    ) -> Self
+    where
+        F: Fn(&RetryStorm) + Send + Sync + 'static,
+    {
+        assert!(
+            retries_per_second > 0,
+            "a retry storm must be at least one retry per second"
+        );
+        self.options.storm = Some(StormDetector {
+            retries_per_second,
+            sustained,
+            hook: Arc::new(on_storm),
+            state: Arc::default(),
+        });
+        self
+    }
+
+    /// Call `on_progress` before every delay with the number of attempts made and left, the next
+    /// delay and an estimate of the delays left, for example to show the progress of a call in a
+    /// command-line tool.
+    pub fn with_progress<F>(mut self, on_progress: F) -> Self
+    where
+        F: Fn(&Progress) + Send + Sync + 'static,
+    {
+        self.options.progress = Some(ProgressHook(Arc::new(on_progress)));
+        self
+    }
+
+    /// Call `on_exhausted` once for every call that gives up after making attempts, with the error
+    /// of each attempt, for example to route the message a consumer failed to process to a
+    /// dead-letter queue with its failure history.
+    ///
+    /// Calls that are cancelled or rejected by a bulkhead do not give up on their item, so they are
+    /// not reported. Formatting the errors allocates, so calls through a policy with this hook do.
+    ///
+    /// ```rust
+    /// # use std::sync::{Arc, Mutex};
+    /// # use retry::delay::NoDelay;
+    /// use retry::{CorrelationId, Policy};
+    ///
+    /// let dead_letters = Arc::new(Mutex::new(Vec::new()));
+    /// let queue = Arc::clone(&dead_letters);
+    /// let policy = Policy::new(NoDelay.take(1)).with_on_exhausted(move |dead_letter| {
+    ///     let message = dead_letter.correlation_id().to_string();
+    ///     queue.lock().unwrap().push((message, dead_letter.errors().to_vec()));
+    /// });
+    ///
+    /// let message_id = CorrelationId::from("message-42");
+    /// let _ = policy.retry_with_correlation_id(message_id, |attempt, _| Err::<(), _>(attempt));
+    ///
+    /// assert_eq!(
+    ///     *dead_letters.lock().unwrap(),
+    ///     vec![("message-42".to_owned(), vec!["1".to_owned(), "2".to_owned()])]
+    /// );
+    /// ```
+    pub fn with_on_exhausted<F>(mut self, on_exhausted: F) -> Self
+    where
+        F: Fn(&DeadLetter) + Send + Sync + 'static,
+    {
+        self.options.dead_letter = Some(DeadLetterHook(Arc::new(on_exhausted)));
+        self
+    }
+
+    /// Keep only the errors selected by `retention` for the dead letters given to the
+    /// `with_on_exhausted` hook, instead of the error of every attempt, so that long-running calls
+    /// do not hold on to thousands of errors.
+    ///
+    /// ```rust
+    /// # use std::sync::{Arc, Mutex};
+    /// # use retry::delay::NoDelay;
+    /// use retry::{listener::ErrorRetention, Policy};
+    ///
+    /// let errors = Arc::new(Mutex::new(Vec::new()));
+    /// let report = Arc::clone(&errors);
+    /// let policy = Policy::new(NoDelay.take(999))
+    ///     .with_error_retention(ErrorRetention::FirstAndLast(1, 2))
+    ///     .with_on_exhausted(move |dead_letter| {
+    ///         let omitted = dead_letter.omitted_errors();
+    ///         report.lock().unwrap().push((dead_letter.errors().to_vec(), omitted));
+    ///     });
+    ///
+    /// let _ = policy.retry_with_index(|attempt| Err::<(), _>(attempt));
+    ///
+    /// let kept = vec!["1".to_owned(), "999".to_owned(), "1000".to_owned()];
+    /// assert_eq!(*errors.lock().unwrap(), vec![(kept, 997)]);
+    /// ```
+    pub fn with_error_retention(mut self, retention: ErrorRetention) -> Self {
+        self.options.error_retention = retention;
+        self
+    }
+
+    /// Run `compensate` for every call that gives up after making attempts, to undo the side
+    /// effects its attempts may have left behind, such as a reservation to release or a temporary
+    /// object to delete.
+    ///
+    /// Several compensating actions can be added. Like the steps of a saga, they are undone in
+    /// reverse: the last action added runs first. They run after the listeners are notified and
+    /// before the dead-letter hook is called. Cancelled and rejected calls are not compensated.
+    ///
+    /// ```rust
+    /// # use std::sync::{Arc, Mutex};
+    /// # use retry::delay::NoDelay;
+    /// use retry::Policy;
+    ///
+    /// let undone = Arc::new(Mutex::new(Vec::new()));
+    /// let (reservation, object) = (Arc::clone(&undone), Arc::clone(&undone));
+    /// let policy = Policy::new(NoDelay.take(1))
+    ///     .with_compensation(move |_| reservation.lock().unwrap().push("release reservation"))
+    ///     .with_compensation(move |_| object.lock().unwrap().push("delete temporary object"));
+    ///
+    /// let _ = policy.retry(|| Err::<(), _>("down"));
+    ///
+    /// assert_eq!(
+    ///     *undone.lock().unwrap(),
+    ///     ["delete temporary object", "release reservation"]
+    /// );
+    /// ```
+    pub fn with_compensation<F>(mut self, compensate: F) -> Self
+    where
+        F: Fn(&GiveUpSummary) + Send + Sync + 'static,
+    {
+        self.options.compensations.push(Arc::new(compensate));
+        self
+    }
+
+    /// Log retries and give-ups under the given target instead of `retry`.
+    #[cfg(feature = "log")]
+    pub fn with_log_target(mut self, target: &'static str) -> Self {
+        self.options.log.target = target;
+        self
+    }
+
+    /// Log retried attempts at `retry` level and giving up at `give_up` level, instead of debug
+    /// and warn.
+    #[cfg(feature = "log")]
+    pub fn with_log_levels(mut self, retry: log::Level, give_up: log::Level) -> Self {
+        self.options.log.retry_level = retry;
+        self.options.log.give_up_level = give_up;
+        self
+    }
+
+    /// Record the attempts, delays and give-ups of this policy in the given collectors.
+    #[cfg(feature = "prometheus")]
+    pub fn with_prometheus(mut self, metrics: crate::prometheus::PolicyMetrics) -> Self {
+        self.options.prometheus = Some(metrics);
+        self
+    }
+
+    /// Read the current time from the given clock instead of the default one, to measure the
+    /// latency of attempts and the time elapsed since a call started.
+    ///
+    /// By default, synchronous calls read the system clock and asynchronous calls read Tokio's
+    /// clock, which stands still while it is paused in tests.
+    pub fn with_clock<C>(mut self, clock: C) -> Self
+    where
+        C: Clock + 'static,
+    {
+        self.options.clock = Some(Arc::new(clock));
+        self.options.time_source = None;
+        self
+    }
+
+    /// Measure time with the given source, replacing any clock given with `with_clock`.
+    ///
+    /// The default, `TimeSource::Monotonic`, suits budgets and timeouts, while
+    /// `TimeSource::WallClock` keeps counting while the machine is suspended, for schedules that
+    /// follow the calendar.
+    pub fn with_time_source(mut self, time_source: TimeSource) -> Self {
+        self.options.clock = match time_source {
+            TimeSource::Monotonic => None,
+            TimeSource::WallClock => Some(Arc::new(WallClock::new())),
+        };
+        self.options.time_source = Some(time_source);
+        self
+    }
+
+    /// The source of the time this policy measures, or `None` if it was given a clock of its own
+    /// with `with_clock`.
+    pub fn time_source(&self) -> Option<TimeSource> {
+        self.options.time_source
+    }
+
+    /// Wait between synchronous attempts with the given sleeper instead of `thread::sleep`.
+    /// Asynchronous calls are not affected.
+    pub fn with_sleeper<S>(mut self, sleeper: S) -> Self
+    where
+        S: Sleeper + 'static,
+    {
+        self.options.sleeper = Some(Arc::new(sleeper));
+        self
+    }
+
+    /// Make at most `max_attempts` attempts, including the first, even if the delay strategy has
+    /// delays left. A call that fails on its last allowed attempt returns `Error::MaxAttempts`
+    /// rather than `Error::Operation`, so that the two causes of giving up can be told apart.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_attempts` is zero.
+    pub fn with_max_attempts(mut self, max_attempts: u64) -> Self {
+        assert!(max_attempts > 0, "a policy must make at least one attempt");
+        self.options.max_attempts = Some(max_attempts);
+        self
+    }
+
+    /// The maximum number of attempts, if any.
+    pub fn max_attempts(&self) -> Option<u64> {
+        self.options.max_attempts
+    }
+
+    /// Never wait longer than `max_delay` between attempts, whether the delay comes from the
+    /// strategy or from a hint given by the error.
+    pub fn with_max_delay(mut self, max_delay: Duration) -> Self {
+        self.options.max_delay = Some(max_delay);
+        self
+    }
+
+    /// The maximum delay between attempts, if any.
+    pub fn max_delay(&self) -> Option<Duration> {
+        self.options.max_delay
+    }
+
+    /// Start each attempt at least `min_interval` after the start of the attempt before it,
+    /// lengthening whatever delay the strategy, a hint given by the error or the scheduling comes
+    /// up with, so that a call never hits its dependency more often than once per interval, even
+    /// with `NoDelay`. The interval wins over the maximum delay if it is longer.
+    ///
+    /// ```rust
+    /// # use std::time::{Duration, Instant};
+    /// # use retry::{delay::NoDelay, Policy};
+    /// let policy = Policy::new(NoDelay.take(3)).with_min_interval(Duration::from_millis(20));
+    /// let start = Instant::now();
+    /// let _ = policy.retry(|| Err::<(), _>("overloaded"));
+    ///
+    /// assert!(start.elapsed() >= Duration::from_millis(60));
+    /// ```
+    pub fn with_min_interval(mut self, min_interval: Duration) -> Self {
+        self.options.min_interval = Some(min_interval);
+        self
+    }
+
+    /// The minimum interval between the starts of attempts, if any.
+    pub fn min_interval(&self) -> Option<Duration> {
+        self.options.min_interval
+    }
+
+    /// Give up instead of retrying when the next attempt would start more than `time_budget`
+    /// after the first one, however many attempts the strategy has left. The time left is passed
+    /// to the attempts of `retry_with_attempt_context`, such as to bound their own timeouts.
+    pub fn with_time_budget(mut self, time_budget: Duration) -> Self {
+        self.options.time_budget = Some(time_budget);
+        self
+    }
+
+    /// The time budget of each call, if any.
+    pub fn time_budget(&self) -> Option<Duration> {
+        self.options.time_budget
+    }
+
+    /// Bound each asynchronous try of the operation by the given timeout. A try that does not
+    /// finish in time is counted as a retryable failure.
+    ///
+    /// Synchronous operations cannot be interrupted, so this option only applies to
+    /// `retry_async` and `retry_async_with_index`, and to the connections made by
+    /// `net::connect_with_retry`.
+    pub fn with_attempt_timeout(mut self, timeout: Duration) -> Self {
+        self.options.attempt_timeout = Some(timeout);
+        self
+    }
+
+    /// The timeout applied to each asynchronous try, if any.
+    pub fn attempt_timeout(&self) -> Option<Duration> {
+        self.options.attempt_timeout
+    }
+
+    /// The number of calls, attempts and give-ups made through this policy so far. The counts
+    /// are shared with the clones of the policy, and with the policies derived from it by methods
+    /// such as `with_name`.
+    pub fn stats(&self) -> PolicyStats {
+        self.options.stats.snapshot()
+    }
+
+    /// Limit the calls in progress at once through this policy, and any other policy with the same
+    /// bulkhead, rejecting the calls beyond the limit with `Error::Rejected`.
+    pub fn with_bulkhead(mut self, bulkhead: Bulkhead) -> Self {
+        self.options.bulkhead = Some(bulkhead);
+        self
+    }
+
+    /// Limit the retries of the calls made through this policy, and any other policy with the same
+    /// budget, so that retries cannot multiply the load on a dependency that is struggling. A call
+    /// whose retry the budget refuses gives up with its last error.
+    pub fn with_retry_budget<B>(mut self, budget: B) -> Self
+    where
+        B: RetryBudget + 'static,
+    {
+        self.options.retry_budget = Some(Arc::new(budget));
+        self
+    }
+
+    /// Give each call made with `retry_with_cost` a budget of `budget`, in units of the caller's
+    /// choosing such as cents or tokens, to spend on the costs its attempts declare. A call gives
+    /// up with its last error once its attempts have spent the budget, whatever the strategy has
+    /// left. Calls made with the other methods declare no costs, so the budget never stops them.
+    pub fn with_cost_budget(mut self, budget: u64) -> Self {
+        self.options.cost_budget = Some(budget);
+        self
+    }
+
+    /// The cost budget of each call, if any.
+    pub fn cost_budget(&self) -> Option<u64> {
+        self.options.cost_budget
+    }
+
+    /// Start the delay before each attempt when the previous attempt starts, or when it ends, the
+    /// default.
+    ///
+    /// ```rust
+    /// # use std::time::Duration;
+    /// use retry::{delay::Fixed, Policy, Scheduling};
+    ///
+    /// // Poll every 100ms, even when an attempt takes 30ms.
+    /// let policy = Policy::new(Fixed::from_millis(100).take(2)).with_scheduling(Scheduling::FixedRate);
+    /// let result = policy.retry(|| {
+    ///     std::thread::sleep(Duration::from_millis(30));
+    ///     Err::<(), _>("not ready")
+    /// });
+    ///
+    /// assert!(result.unwrap_err().total_delay() <= Duration::from_millis(140));
+    /// ```
+    pub fn with_scheduling(mut self, scheduling: Scheduling) -> Self {
+        self.options.scheduling = scheduling;
+        self
+    }
+
+    /// Add a random offset between zero and `max`, drawn once for the process with `delay::splay`,
+    /// to the first delay of every call, so that the replicas of a fleet that restarted together
+    /// do not retry in step. The offset stays the same for every call, and is bounded by the
+    /// maximum delay like the delay it is added to.
+    #[cfg(feature = "random")]
+    pub fn with_splay(mut self, max: Duration) -> Self {
+        self.options.splay = Some(crate::delay::splay(max));
+        self
+    }
+
+    /// Cancel the calls made through this policy once `token` is cancelled, ending them with
+    /// `Error::Cancelled`. The token is checked before each attempt, and every 100ms while waiting
+    /// between attempts, so a cancelled call does not wait out the rest of a long delay.
+    ///
+    /// Cancelled calls are not given up on: listeners, the dead-letter hook and the circuit breaker
+    /// are not told about them.
+    pub fn with_cancellation(mut self, token: CancelToken) -> Self {
+        self.options.cancellation = Some(token);
+        self
+    }
+
+    /// Stop the calls made through this policy, and any other policy with the same breaker, while
+    /// the breaker is open, rejecting them with `Error::CircuitOpen`.
+    ///
+    /// Each call counts once towards opening the breaker, when it gives up after its retries.
+    /// Calls that are cancelled or rejected do not count.
+    pub fn with_circuit_breaker(mut self, breaker: CircuitBreaker) -> Self {
+        self.options.circuit_breaker = Some(breaker);
+        self
+    }
+
+    /// Check `probe` every `interval` while waiting between attempts, and make the next attempt as
+    /// soon as it returns `true` rather than waiting out the rest of the delay, for example when a
+    /// cheap health endpoint reports that the dependency is back. The probe should be fast, since
+    /// it is called on the thread or task that is waiting.
+    ///
+    /// ```rust
+    /// # use std::time::{Duration, Instant};
+    /// use retry::{delay::Fixed, Policy};
+    ///
+    /// let policy = Policy::new(Fixed::from_millis(60_000).take(1))
+    ///     .with_health_check(Duration::from_millis(10), || true);
+    ///
+    /// let started = Instant::now();
+    /// let mut attempts = 0;
+    /// let result = policy.retry(|| {
+    ///     attempts += 1;
+    ///     if attempts == 1 { Err("unavailable") } else { Ok(()) }
+    /// });
+    ///
+    /// assert_eq!(result, Ok(()));
+    /// assert!(started.elapsed() < Duration::from_secs(1));
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if `interval` is zero.
+    pub fn with_health_check<F>(mut self, interval: Duration, probe: F) -> Self
+    where
+        F: Fn() -> bool + Send + Sync + 'static,
+    {
+        assert!(
+            interval > Duration::default(),
+            "the health check interval must not be zero"
+        );
+        self.options.health_check = Some(HealthCheck {
+            interval,
+            probe: Arc::new(probe),
+        });
+        self
+    }
+
+    /// Check every `interval` while waiting between attempts whether the machine was suspended,
+    /// such as a laptop put to sleep, and count the time it was suspended towards the delay.
+    ///
+    /// Sleeping threads and timers measure the monotonic time, which on some platforms stands
+    /// still while the machine is suspended, so a machine that sleeps for two hours through a
+    /// delay of a minute would otherwise wait most of the minute again once it wakes up. With
+    /// suspend detection, the wait ends at the first check after the delay has passed on the wall
+    /// clock, and the time waited, which counts towards the total delay of the call, includes the
+    /// time the machine was suspended. A policy that measures the time with a clock of its own, or
+    /// with `TimeSource::WallClock`, checks with that clock instead.
+    ///
+    /// ```rust
+    /// # use std::time::Duration;
+    /// use retry::{clock::TimeSource, delay::Fixed, Policy};
+    ///
+    /// let policy = Policy::new(Fixed::from_millis(60_000).take(3))
+    ///     .with_time_source(TimeSource::WallClock)
+    ///     .with_suspend_detection(Duration::from_secs(5));
+    /// # let _ = policy;
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if `interval` is zero.
+    pub fn with_suspend_detection(mut self, interval: Duration) -> Self {
+        assert!(
+            interval > Duration::default(),
+            "the suspend detection interval must not be zero"
+        );
+        self.options.suspend_detection = Some(SuspendDetection {
+            interval,
+            wall_clock: WallClock::new(),
+        });
+        self
+    }
+}
+
+impl<D> Policy<D>
+where
+    D: IntoIterator<Item = Duration> + Clone,
+{
+    /// A fresh copy of the delay strategy for one call.
+    pub fn delays(&self) -> D::IntoIter {
+        self.delays.clone().into_iter()
+    }
+
+    /// Retry the given operation synchronously according to this policy.
+    pub fn retry<O, R, E, OR>(&self, mut operation: O) -> Result<R, Error<E>>
+    where
+        O: FnMut() -> OR,
+        OR: Into<OperationResult<R, E>>,
+        E: Debug,
+    {
+        self.retry_with_index(|_| operation())
+    }
+
+    /// Retry the given operation synchronously according to this policy, with each iteration of
+    /// the operation receiving the number of the attempt as an argument.
+    pub fn retry_with_index<O, R, E, OR>(&self, mut operation: O) -> Result<R, Error<E>>
+    where
+        O: FnMut(u64) -> OR,
+        OR: Into<OperationResult<R, E>>,
+        E: Debug,
+    {
+        self.retry_with_correlation_id(CorrelationId::generate(), |current_try, _| {
+            operation(current_try)
+        })
+    }
+
+    /// Retry the given operation synchronously according to this policy, identifying the call
+    /// with the given correlation ID instead of a generated one. Each iteration of the operation
+    /// receives the number of the attempt and the ID as arguments.
+    pub fn retry_with_correlation_id<O, R, E, OR>(
+        &self,
+        id: CorrelationId,
+        mut operation: O,
+    ) -> Result<R, Error<E>>
+    where
+        O: FnMut(u64, &CorrelationId) -> OR,
+        OR: Into<OperationResult<R, E>>,
+        E: Debug,
+    {
+        self.execute(id, |current_try, id| {
+            let result: OperationResult<R, E> = operation(current_try, id).into();
+            result.into()
+        })
+    }
+
+    /// Retry the given operation synchronously according to this policy, passing every attempt the
+    /// same newly generated idempotency key.
+    pub fn retry_idempotent<O, R, E, OR>(&self, operation: O) -> Result<R, Error<E>>
+    where
+        O: FnMut(&IdempotencyKey) -> OR,
+        OR: Into<OperationResult<R, E>>,
+        E: Debug,
+    {
+        self.retry_with_idempotency_key(IdempotencyKey::generate(), operation)
+    }
+
+    /// Retry the given operation synchronously according to this policy, passing every attempt the
+    /// given idempotency key.
+    pub fn retry_with_idempotency_key<O, R, E, OR>(
+        &self,
+        key: IdempotencyKey,
+        mut operation: O,
+    ) -> Result<R, Error<E>>
+    where
+        O: FnMut(&IdempotencyKey) -> OR,
+        OR: Into<OperationResult<R, E>>,
+        E: Debug,
+    {
+        self.retry(|| operation(&key))
+    }
+
+    /// Retry the given operation synchronously according to this policy, retrying the errors it
+    /// classifies as `Transient` and returning those it classifies as `Permanent` immediately.
+    pub fn retry_classified<O, R, E>(&self, mut operation: O) -> Result<R, Error<E>>
+    where
+        O: FnMut() -> Result<R, Classified<E>>,
+        E: Debug,
+    {
+        self.execute(CorrelationId::generate(), |_, _| operation().into())
+    }
+
+    /// Retry the given operation synchronously according to this policy, waiting the delay hinted
+    /// by each retried error, if any, instead of the strategy's next delay.
+    pub fn retry_hinted<O, R, E, OR>(&self, mut operation: O) -> Result<R, Error<E>>
+    where
+        O: FnMut() -> OR,
+        OR: Into<OperationResult<R, E>>,
+        E: Debug + RetryAfterHint,
+    {
+        self.execute(CorrelationId::generate(), |_, _| {
+            Outcome::hinted(operation().into())
+        })
+    }
+
+    /// Retry the given operation synchronously according to this policy while it returns `None`,
+    /// returning the value of the first `Some`. The error of the operation is `()`.
+    pub fn retry_until_some<O, T>(&self, mut operation: O) -> Result<T, Error<()>>
+    where
+        O: FnMut() -> Option<T>,
+    {
+        self.retry(|| operation().ok_or(()))
+    }
+
+    fn execute<O, R, E>(&self, id: CorrelationId, operation: O) -> Result<R, Error<E>>
+    where
+        O: FnMut(u64, &CorrelationId) -> Outcome<R, E>,
+        E: Debug,
+    {
+        self.execute_aggregated(id, Last, None, operation)
+    }
+
+    /// Run the attempts of a synchronous call, giving the errors of the attempts that are retried
+    /// to `aggregator`, and the error of the last one to its `finish`. If `context` is given, it
+    /// is set to the context of each attempt before the attempt is made.
+    pub(crate) fn execute_aggregated<O, R, E, A>(
+        &self,
+        id: CorrelationId,
+        mut aggregator: A,
+        context: Option<&Cell<AttemptContext>>,
+        mut operation: O,
+    ) -> Result<R, Error<A::Output>>
+    where
+        O: FnMut(u64, &CorrelationId) -> Outcome<R, E>,
+        E: Debug,
+        A: ErrorAggregator<E>,
+    {
+        let mut session = self.session(id, &SystemClock)?;
+        #[cfg(feature = "tracing")]
+        let _entered = session.span().clone().entered();
+
+        loop {
+            if self.options.cancelled() {
+                return Err(session.cancel());
+            }
+            let current_try = session.start_attempt();
+            if let Some(context) = context {
+                context.set(session.context());
+            }
+
+            match operation(current_try, session.correlation_id()) {
+                Outcome::Ok(value) => {
+                    session.succeed();
+                    return Ok(value);
+                }
+                Outcome::Retry(error, retry_after) => match session.retry(&error, retry_after) {
+                    Some(delay) => {
+                        aggregator.aggregate(error);
+                        let waited = self.options.sleep(delay);
+                        session.waited(waited);
+                    }
+                    None => {
+                        return Err(session
+                            .give_up(error)
+                            .map_error(|last| aggregator.finish(last)))
+                    }
+                },
+                Outcome::Err(error) => {
+                    return Err(session
+                        .give_up(error)
+                        .map_error(|last| aggregator.finish(last)))
+                }
+            }
+        }
+    }
+
+    /// Start waiting `duration` between asynchronous attempts.
+    #[cfg(feature = "asynchronous")]
+    pub(crate) fn waiting(&self, duration: Duration) -> Waiting<'_> {
+        self.options.waiting(duration)
+    }
+
+    /// Whether the policy's cancellation token was cancelled.
+    #[cfg(feature = "asynchronous")]
+    pub(crate) fn cancelled(&self) -> bool {
+        self.options.cancelled()
+    }
+
+    /// Start a session, measuring time with `default_clock` unless the policy has a clock, or
+    /// reject it if the policy's circuit breaker is open or its bulkhead is full.
+    pub(crate) fn session<E>(
+        &self,
+        id: CorrelationId,
+        default_clock: &'static dyn Clock,
+    ) -> Result<Session<'_, D::IntoIter>, Error<E>> {
+        let admission = match self.options.circuit_breaker {
+            Some(ref breaker) => Some(
+                breaker
+                    .try_enter()
+                    .map_err(|retry_in| Error::CircuitOpen { retry_in })?,
+            ),
+            None => None,
+        };
+        let permit = match self.options.bulkhead {
+            Some(ref bulkhead) => Some(bulkhead.try_enter().ok_or(Error::Rejected {
+                max_concurrent: bulkhead.max_concurrent(),
+            })?),
+            None => None,
+        };
+        let mut session = Session::new(
+            &self.options,
+            default_clock,
+            id,
+            self.delays(),
+            admission,
+            permit,
+        );
+        if self.options.progress.is_some() {
+            session.plan(self.delays());
+        }
+        Ok(session)
+    }
+}
+
+#[cfg(feature = "random")]
+impl<D> Policy<D>
+where
+    D: Randomized,
+{
+    /// Draw the random delays of the strategy from `rng`, so that the schedules of the calls made
+    /// through this policy are reproducible. All calls share the same generator.
+    pub fn with_rng(mut self, rng: SharedRng) -> Self {
+        self.delays = self.delays.with_rng(rng);
+        self
+    }
+}
+
+impl<D> Policy<D>
+where
+    D: Validate,
+{
+    /// Check that the strategy and the options produce a useful schedule, listing every problem
+    /// found. This is meant for policies built from configuration.
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        let mut problems = self.delays.problems();
+        if self.options.max_delay == Some(Duration::default()) {
+            problems.push("maximum delay is zero, so every delay is zero".to_owned());
+        }
+        if self.options.attempt_timeout == Some(Duration::default()) {
+            problems.push(
+                "attempt timeout is zero, so every asynchronous attempt times out".to_owned(),
+            );
+        }
+        ValidationError::check(problems)
+    }
+}
+
+impl<D> Policy<D>
+where
+    D: Introspect,
+{
+    /// The kind of the delay strategy, such as `"exponential"`, to tag metrics and traces with.
+    pub fn strategy_kind(&self) -> &'static str {
+        self.delays.kind()
+    }
+
+    /// The settings of the delay strategy, to tag metrics and traces with.
+    pub fn strategy_params(&self) -> Vec<(&'static str, Param)> {
+        self.delays.params()
+    }
+}
+
+/// Summarizes the policy as its name, its delay strategy and the options that are set, for example
+/// `search: exponential(10ms, x10), max 5 attempts, attempt timeout 1s`.
+impl<D> Display for Policy<D>
+where
+    D: Display,
+{
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(ref name) = self.options.name {
+            write!(formatter, "{}: ", name)?;
+        }
+        write!(formatter, "{}", self.delays)?;
+        if let Some(max_attempts) = self.options.max_attempts {
+            write!(formatter, ", max {} attempts", max_attempts)?;
+        }
+        if let Some(max_delay) = self.options.max_delay {
+            write!(formatter, ", max delay {:?}", max_delay)?;
+        }
+        if let Some(min_interval) = self.options.min_interval {
+            write!(formatter, ", min interval {:?}", min_interval)?;
+        }
+        if let Some(time_budget) = self.options.time_budget {
+            write!(formatter, ", time budget {:?}", time_budget)?;
+        }
+        if let Some(timeout) = self.options.attempt_timeout {
+            write!(formatter, ", attempt timeout {:?}", timeout)?;
+        }
+        if let Some(ref watchdog) = self.options.watchdog {
+            write!(formatter, ", watchdog {:?}", watchdog.threshold)?;
+        }
+        if let Some(ref bulkhead) = self.options.bulkhead {
+            write!(formatter, ", bulkhead {}", bulkhead.max_concurrent())?;
+        }
+        if self.options.retry_budget.is_some() {
+            formatter.write_str(", retry budget")?;
+        }
+        if let Some(cost_budget) = self.options.cost_budget {
+            write!(formatter, ", cost budget {}", cost_budget)?;
+        }
+        if let Some(ref storm) = self.options.storm {
+            write!(
+                formatter,
+                ", storm warning {}/s for {:?}",
+                storm.retries_per_second, storm.sustained
+            )?;
+        }
+        if let Some(ref health_check) = self.options.health_check {
+            write!(
+                formatter,
+                ", health check every {:?}",
+                health_check.interval
+            )?;
+        }
+        if self.options.scheduling == Scheduling::FixedRate {
+            formatter.write_str(", fixed rate")?;
+        }
+        if let Some(splay) = self.options.splay {
+            write!(formatter, ", splay {:?}", splay)?;
+        }
+        if self.options.cancellation.is_some() {
+            formatter.write_str(", cancellable")?;
+        }
+        if let Some(ref detection) = self.options.suspend_detection {
+            write!(formatter, ", suspend checks every {:?}", detection.interval)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        alloc::{GlobalAlloc, Layout, System},
+        cell::Cell,
+        sync::{
+            atomic::{AtomicU32, Ordering},
+            Arc, Mutex,
+        },
+        time::Duration,
+    };
+
+    use super::{Policy, Scheduling};
+    use crate::{
+        clock::{MockClock, Sleeper},
+        delay::{Exponential, Fixed, NoDelay},
+        Error,
+    };
+
+    /// Counts the allocations made by each thread, so that tests running in parallel do not
+    /// disturb each other's counts.
+    struct CountingAllocator;
+
+    thread_local! {
+        static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+    }
+
+    unsafe impl GlobalAlloc for CountingAllocator {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+            System.alloc(layout)
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            System.dealloc(ptr, layout)
+        }
+
+        unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+            let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+            System.realloc(ptr, layout, new_size)
+        }
+    }
+
+    #[global_allocator]
+    static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+    /// The number of allocations made by the current thread while running `f`.
+    fn allocations<T>(f: impl FnOnce() -> T) -> (T, usize) {
+        let before = ALLOCATIONS.with(Cell::get);
+        let value = f();
+        (value, ALLOCATIONS.with(Cell::get) - before)
+    }
+
+    #[test]
+    fn each_call_gets_a_fresh_schedule() {
+        let policy = Policy::new(NoDelay.take(1));
+
+        for _ in 0..2 {
+            assert_eq!(
+                policy.retry(|| Err::<(), _>("fail")),
+                Err(Error::Operation {
+                    error: "fail",
+                    tries: 2,
+                    total_delay: Duration::from_millis(0)
+                })
+            );
+        }
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!(
+            Policy::new(Exponential::from_millis(10)).to_string(),
+            "exponential(10ms, x10)"
+        );
+        assert_eq!(
+            Policy::new(Exponential::from_millis(10))
+                .with_name("search")
+                .with_max_attempts(5)
+                .with_attempt_timeout(Duration::from_secs(1))
+                .with_watchdog(Duration::from_secs(30), |_| ())
+                .to_string(),
+            "search: exponential(10ms, x10), max 5 attempts, attempt timeout 1s, watchdog 30s"
+        );
+    }
+
+    #[derive(Debug, Default, Clone)]
+    struct RecordingSleeper(Arc<Mutex<Vec<Duration>>>);
+
+    impl Sleeper for RecordingSleeper {
+        fn sleep(&self, duration: Duration) {
+            self.0.lock().unwrap().push(duration);
+        }
+    }
+
+    #[test]
+    fn reports_the_strategy_ending_when_it_ties_with_max_attempts() {
+        let policy = Policy::new(Fixed::from_millis(1).take(2)).with_max_attempts(3);
+
+        let result = policy.retry(|| Err::<(), _>("down"));
+
+        assert!(matches!(result, Err(Error::Operation { tries: 3, .. })));
+    }
+
+    #[test]
+    fn health_check_ends_delays_early() {
+        let sleeper = RecordingSleeper::default();
+        let checks = Arc::new(AtomicU32::new(0));
+        let probe = Arc::clone(&checks);
+        let policy = Policy::new(Fixed::from_millis(2500))
+            .with_max_attempts(3)
+            .with_sleeper(sleeper.clone())
+            .with_health_check(Duration::from_secs(1), move || {
+                probe.fetch_add(1, Ordering::Relaxed) == 2
+            });
+
+        let result = policy.retry(|| Err::<(), _>("down"));
+
+        // The probe is not checked once a delay is over, so it only passes on its third check,
+        // a second into the second delay.
+        assert_eq!(
+            *sleeper.0.lock().unwrap(),
+            [1000, 1000, 500, 1000].map(Duration::from_millis)
+        );
+        assert_eq!(checks.load(Ordering::Relaxed), 3);
+        assert_eq!(
+            result.unwrap_err().total_delay(),
+            Duration::from_millis(3500)
+        );
+        assert_eq!(
+            policy.to_string(),
+            "fixed(2.5s), max 3 attempts, health check every 1s"
+        );
+    }
+
+    #[test]
+    fn counts_the_time_the_machine_was_suspended_towards_delays() {
+        #[derive(Debug)]
+        struct Suspending(MockClock, RecordingSleeper);
+
+        impl Sleeper for Suspending {
+            fn sleep(&self, duration: Duration) {
+                self.1.sleep(duration);
+                let suspended = if self.1 .0.lock().unwrap().len() == 2 {
+                    Duration::from_secs(7200)
+                } else {
+                    Duration::default()
+                };
+                self.0.advance(duration + suspended);
+            }
+        }
+
+        let clock = MockClock::new();
+        let sleeper = RecordingSleeper::default();
+        let policy = Policy::new(Fixed::from_millis(60_000))
+            .with_max_attempts(3)
+            .with_clock(clock.clone())
+            .with_sleeper(Suspending(clock, sleeper.clone()))
+            .with_suspend_detection(Duration::from_secs(20));
+
+        let result = policy.retry(|| Err::<(), _>("down"));
+
+        // The machine is suspended during the second interval of the first delay, which ends
+        // as soon as the machine wakes up.
+        assert_eq!(
+            *sleeper.0.lock().unwrap(),
+            [20, 20, 20, 20, 20].map(Duration::from_secs)
+        );
+        assert_eq!(
+            result.unwrap_err().total_delay(),
+            Duration::from_secs(7200 + 40 + 60)
+        );
+        assert_eq!(
+            policy.to_string(),
+            "fixed(60s), max 3 attempts, suspend checks every 20s"
+        );
+    }
+
+    #[cfg(feature = "random")]
+    #[test]
+    fn splays_the_first_delay() {
+        let policy = Policy::new(Fixed::from_millis(10).take(2))
+            .with_splay(Duration::from_secs(1))
+            .with_max_delay(Duration::from_millis(1010));
+        let splay = crate::delay::splay(Duration::from_secs(1));
+
+        for _ in 0..2 {
+            let sleeper = RecordingSleeper::default();
+            let _ = policy
+                .clone()
+                .with_sleeper(sleeper.clone())
+                .retry(|| Err::<(), _>("down"));
+            assert_eq!(
+                *sleeper.0.lock().unwrap(),
+                [Duration::from_millis(10) + splay, Duration::from_millis(10)]
+            );
+        }
+    }
+
+    #[test]
+    fn fixed_rate_scheduling_takes_latency_off_delays() {
+        #[derive(Debug)]
+        struct Advancing(MockClock, RecordingSleeper);
+
+        impl Sleeper for Advancing {
+            fn sleep(&self, duration: Duration) {
+                self.1.sleep(duration);
+                self.0.advance(duration);
+            }
+        }
+
+        let clock = MockClock::new();
+        let sleeper = RecordingSleeper::default();
+        let policy = Policy::new(Fixed::from_millis(100))
+            .with_max_attempts(4)
+            .with_clock(clock.clone())
+            .with_sleeper(Advancing(clock.clone(), sleeper.clone()))
+            .with_scheduling(Scheduling::FixedRate);
+        let mut latencies = [30, 150, 0].iter();
+
+        let result = policy.retry(|| {
+            if let Some(&latency) = latencies.next() {
+                clock.advance(Duration::from_millis(latency));
+            }
+            Err::<(), _>("not ready")
+        });
+
+        assert_eq!(
+            *sleeper.0.lock().unwrap(),
+            [70, 100].map(Duration::from_millis)
+        );
+        assert_eq!(
+            result.unwrap_err().total_delay(),
+            Duration::from_millis(170)
+        );
+        assert_eq!(
+            policy.to_string(),
+            "fixed(100ms), max 4 attempts, fixed rate"
+        );
+    }
+
+    #[test]
+    fn spaces_attempts_by_the_min_interval() {
+        #[derive(Debug)]
+        struct Advancing(MockClock, RecordingSleeper);
+
+        impl Sleeper for Advancing {
+            fn sleep(&self, duration: Duration) {
+                self.1.sleep(duration);
+                self.0.advance(duration);
+            }
+        }
+
+        let clock = MockClock::new();
+        let sleeper = RecordingSleeper::default();
+        let policy = Policy::new(Fixed::from_millis(10))
+            .with_max_attempts(4)
+            .with_min_interval(Duration::from_millis(100))
+            .with_clock(clock.clone())
+            .with_sleeper(Advancing(clock.clone(), sleeper.clone()));
+        let mut latencies = [30, 150, 0].iter();
+
+        let _ = policy.retry(|| {
+            if let Some(&latency) = latencies.next() {
+                clock.advance(Duration::from_millis(latency));
+            }
+            Err::<(), _>("overloaded")
+        });
+
+        // The attempt that took longer than the interval is followed by the strategy's delay.
+        assert_eq!(
+            *sleeper.0.lock().unwrap(),
+            [70, 10, 100].map(Duration::from_millis)
+        );
+        assert_eq!(
+            policy.to_string(),
+            "fixed(10ms), max 4 attempts, min interval 100ms"
+        );
+    }
+
+    #[test]
+    fn tells_max_attempts_from_exhausted_schedules() {
+        assert_eq!(
+            Policy::new(NoDelay)
+                .with_max_attempts(3)
+                .retry(|| Err::<(), _>("fail")),
+            Err(Error::MaxAttempts {
+                error: "fail",
+                tries: 3,
+                total_delay: Duration::from_millis(0)
+            })
+        );
+        assert_eq!(
+            Policy::new(NoDelay.take(1))
+                .with_max_attempts(3)
+                .retry(|| Err::<(), _>("fail")),
+            Err(Error::Operation {
+                error: "fail",
+                tries: 2,
+                total_delay: Duration::from_millis(0)
+            })
+        );
+    }
+
+    #[test]
+    fn lists_every_problem() {
+        let policy = Policy::new(Exponential::from_millis(0))
+            .with_max_delay(Duration::default())
+            .with_attempt_timeout(Duration::default());
+
+        assert_eq!(
+            policy.validate().unwrap_err().problems(),
+            [
+                "base delay is zero, so every delay is zero",
+                "maximum delay is zero, so every delay is zero",
+                "attempt timeout is zero, so every asynchronous attempt times out",
+            ]
+        );
+        assert_eq!(Policy::new(NoDelay).with_max_attempts(2).validate(), Ok(()));
+    }
+
+    #[test]
+    fn calls_do_not_allocate() {
+        let policy = Policy::new(Exponential::from_millis(0).take(4)).with_max_attempts(3);
+        // The thread-local random number generator allocates once, when first used.
+        let _ = policy.retry(|| Ok::<_, ()>(()));
+
+        let (result, count) = allocations(|| policy.retry(|| Err::<(), _>("down")));
+        assert_eq!(result.unwrap_err().tries(), 3);
+        assert_eq!(count, 0);
+
+        let mut attempts = 0;
+        let (result, count) = allocations(|| {
+            policy.retry(|| {
+                attempts += 1;
+                if attempts < 2 {
+                    Err("down")
+                } else {
+                    Ok(attempts)
+                }
+            })
+        });
+        assert_eq!(result, Ok(2));
+        assert_eq!(count, 0);
+    }
+}
@@ -0,0 +1,135 @@
+//! Ready-made predicates that decide which errors are worth retrying.
+//!
+//! Predicates are plain functions of the error, so they can be used with `retry_if` methods or
+//! with `OperationResult::retry_if` in the operation passed to `retry`:
+//!
+//! ```rust
+//! # use std::io;
+//! # use retry::delay::Fixed;
+//! # use retry::{predicates, retry, OperationResult};
+//! let is_transient = predicates::io_transient();
+//! let mut attempts = 0;
+//!
+//! let result = retry(Fixed::from_millis(1).take(3), || {
+//!     attempts += 1;
+//!     OperationResult::retry_if(
+//!         Err::<(), _>(io::Error::from(io::ErrorKind::NotFound)),
+//!         &is_transient,
+//!     )
+//! });
+//!
+//! assert!(result.is_err());
+//! assert_eq!(attempts, 1);
+//! ```
+//!
+//! Predicates for other kinds of errors are grouped in submodules.
+
+use std::io;
+
+#[cfg(feature = "anyhow")]
+pub mod anyhow;
+pub mod db;
+pub mod http;
+#[cfg(feature = "windows")]
+pub mod windows;
+
+/// The kinds of I/O errors that usually indicate a transient condition.
+pub const TRANSIENT_IO_KINDS: &[io::ErrorKind] = &[
+    io::ErrorKind::WouldBlock,
+    io::ErrorKind::TimedOut,
+    io::ErrorKind::Interrupted,
+    io::ErrorKind::ConnectionRefused,
+    io::ErrorKind::ConnectionReset,
+    io::ErrorKind::ConnectionAborted,
+    io::ErrorKind::BrokenPipe,
+];
+
+/// Build a predicate that retries I/O errors with one of `TRANSIENT_IO_KINDS`.
+pub fn io_transient() -> impl Fn(&io::Error) -> bool + Clone {
+    IoPredicate::transient().build()
+}
+
+/// A customizable set of I/O error kinds to retry.
+///
+/// ```rust
+/// # use std::io;
+/// # use retry::predicates::IoPredicate;
+/// let predicate = IoPredicate::transient()
+///     .retry(io::ErrorKind::UnexpectedEof)
+///     .except(io::ErrorKind::WouldBlock)
+///     .build();
+///
+/// assert!(predicate(&io::ErrorKind::UnexpectedEof.into()));
+/// assert!(!predicate(&io::ErrorKind::WouldBlock.into()));
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct IoPredicate {
+    kinds: Vec<io::ErrorKind>,
+}
+
+impl IoPredicate {
+    /// Create a predicate that retries no errors.
+    pub fn new() -> Self {
+        IoPredicate::default()
+    }
+
+    /// Create a predicate that retries the errors with one of `TRANSIENT_IO_KINDS`.
+    pub fn transient() -> Self {
+        IoPredicate {
+            kinds: TRANSIENT_IO_KINDS.to_vec(),
+        }
+    }
+
+    /// Also retry errors of the given kind.
+    pub fn retry(mut self, kind: io::ErrorKind) -> Self {
+        if !self.kinds.contains(&kind) {
+            self.kinds.push(kind);
+        }
+        self
+    }
+
+    /// Stop retrying errors of the given kind.
+    pub fn except(mut self, kind: io::ErrorKind) -> Self {
+        self.kinds.retain(|retried| *retried != kind);
+        self
+    }
+
+    /// Returns `true` if the error should be retried.
+    pub fn matches(&self, error: &io::Error) -> bool {
+        self.kinds.contains(&error.kind())
+    }
+
+    /// Turn the set into a predicate function.
+    pub fn build(self) -> impl Fn(&io::Error) -> bool + Clone {
+        move |error| self.matches(error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Error, ErrorKind};
+
+    use super::{io_transient, IoPredicate};
+
+    #[test]
+    fn transient_io_errors() {
+        let predicate = io_transient();
+
+        assert!(predicate(&Error::from(ErrorKind::ConnectionReset)));
+        assert!(predicate(&Error::from(ErrorKind::Interrupted)));
+        assert!(!predicate(&Error::from(ErrorKind::NotFound)));
+        assert!(!predicate(&Error::from(ErrorKind::PermissionDenied)));
+    }
+
+    #[test]
+    fn customized_io_kinds() {
+        let predicate = IoPredicate::new().retry(ErrorKind::NotFound);
+
+        assert!(predicate.matches(&Error::from(ErrorKind::NotFound)));
+        assert!(!predicate.matches(&Error::from(ErrorKind::TimedOut)));
+        assert_eq!(
+            IoPredicate::transient().retry(ErrorKind::TimedOut),
+            IoPredicate::transient()
+        );
+    }
+}
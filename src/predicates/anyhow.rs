@@ -0,0 +1,77 @@
+//! Predicates for `anyhow::Error`, based on the errors in its chain. This module is enabled with
+//! the `"anyhow"` feature.
+//!
+//! ```rust
+//! # use std::io;
+//! # use retry::predicates::anyhow::{retry_if_downcast, retry_if_downcast_matches};
+//! let error = anyhow::Error::new(io::Error::from(io::ErrorKind::TimedOut)).context("fetching");
+//!
+//! assert!(retry_if_downcast::<io::Error>()(&error));
+//! assert!(retry_if_downcast_matches(|error: &io::Error| {
+//!     error.kind() == io::ErrorKind::TimedOut
+//! })(&error));
+//! ```
+
+use std::error::Error as StdError;
+
+use ::anyhow::Error;
+
+/// Build a predicate that retries errors whose chain contains an error of type `E`.
+pub fn retry_if_downcast<E>() -> impl Fn(&Error) -> bool + Clone
+where
+    E: StdError + 'static,
+{
+    retry_if_downcast_matches(|_: &E| true)
+}
+
+/// Build a predicate that retries errors whose chain contains an error of type `E` for which
+/// `predicate` returns `true`.
+pub fn retry_if_downcast_matches<E, F>(predicate: F) -> impl Fn(&Error) -> bool + Clone
+where
+    E: StdError + 'static,
+    F: Fn(&E) -> bool + Clone,
+{
+    move |error| {
+        error
+            .chain()
+            .filter_map(|cause| cause.downcast_ref::<E>())
+            .any(&predicate)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fmt, io};
+
+    use super::{retry_if_downcast, retry_if_downcast_matches};
+    use crate::predicates::io_transient;
+
+    #[derive(Debug)]
+    struct Fatal;
+
+    impl fmt::Display for Fatal {
+        fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+            formatter.write_str("fatal")
+        }
+    }
+
+    impl std::error::Error for Fatal {}
+
+    #[test]
+    fn finds_errors_anywhere_in_the_chain() {
+        let error = anyhow::Error::new(io::Error::from(io::ErrorKind::ConnectionReset))
+            .context("reading")
+            .context("syncing");
+
+        assert!(retry_if_downcast::<io::Error>()(&error));
+        assert!(!retry_if_downcast::<Fatal>()(&error));
+        assert!(retry_if_downcast_matches(io_transient())(&error));
+    }
+
+    #[test]
+    fn checks_the_downcast_error() {
+        let error = anyhow::Error::new(io::Error::from(io::ErrorKind::NotFound));
+
+        assert!(!retry_if_downcast_matches(io_transient())(&error));
+    }
+}
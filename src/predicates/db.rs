@@ -0,0 +1,145 @@
+//! Predicates for transient database errors, keyed on SQLSTATE codes so that they can be used with
+//! any driver.
+//!
+//! Drivers expose errors in different shapes, so the predicates work with any error type that
+//! implements `SqlState`:
+//!
+//! ```rust
+//! # use retry::predicates::db::{self, SqlState};
+//! struct DriverError {
+//!     code: Option<String>,
+//! }
+//!
+//! impl SqlState for DriverError {
+//!     fn sqlstate(&self) -> Option<&str> {
+//!         self.code.as_deref()
+//!     }
+//! }
+//!
+//! let deadlock = DriverError { code: Some("40P01".to_string()) };
+//! let syntax = DriverError { code: Some("42601".to_string()) };
+//!
+//! assert!(db::is_transient(&deadlock));
+//! assert!(!db::is_transient(&syntax));
+//! ```
+
+/// The SQLSTATE codes of errors that usually succeed when the transaction or statement is
+/// retried: serialization failures, deadlocks, locks that could not be acquired and servers that
+/// are shutting down, starting up or out of connections.
+pub const TRANSIENT_SQLSTATES: &[&str] = &[
+    "40001", // serialization_failure
+    "40P01", // deadlock_detected
+    "55P03", // lock_not_available
+    "53300", // too_many_connections
+    "57P01", // admin_shutdown
+    "57P02", // crash_shutdown
+    "57P03", // cannot_connect_now
+];
+
+/// The SQLSTATE class of connection exceptions, all of which are treated as transient.
+pub const CONNECTION_EXCEPTION_CLASS: &str = "08";
+
+/// An error reported by a database driver.
+pub trait SqlState {
+    /// The SQLSTATE code of the error, if the server reported one.
+    fn sqlstate(&self) -> Option<&str>;
+
+    /// Returns `true` if the connection to the server was lost or could not be established,
+    /// which drivers usually report without a SQLSTATE code.
+    fn is_connection_error(&self) -> bool {
+        false
+    }
+}
+
+impl SqlState for str {
+    fn sqlstate(&self) -> Option<&str> {
+        Some(self)
+    }
+}
+
+impl SqlState for String {
+    fn sqlstate(&self) -> Option<&str> {
+        Some(self)
+    }
+}
+
+impl<T> SqlState for &T
+where
+    T: SqlState + ?Sized,
+{
+    fn sqlstate(&self) -> Option<&str> {
+        (**self).sqlstate()
+    }
+
+    fn is_connection_error(&self) -> bool {
+        (**self).is_connection_error()
+    }
+}
+
+/// The SQLSTATE code of a `postgres` error, and whether its connection was closed. This is
+/// enabled with the `"postgres"` feature.
+#[cfg(feature = "postgres")]
+impl SqlState for postgres::Error {
+    fn sqlstate(&self) -> Option<&str> {
+        self.code().map(|state| state.code())
+    }
+
+    fn is_connection_error(&self) -> bool {
+        self.is_closed()
+    }
+}
+
+/// Returns `true` if the code is one of `TRANSIENT_SQLSTATES` or a connection exception.
+pub fn is_transient_sqlstate(code: &str) -> bool {
+    TRANSIENT_SQLSTATES.contains(&code) || code.starts_with(CONNECTION_EXCEPTION_CLASS)
+}
+
+/// Returns `true` if the error has a transient SQLSTATE code or is a connection error.
+pub fn is_transient<E>(error: &E) -> bool
+where
+    E: SqlState + ?Sized,
+{
+    error.is_connection_error() || error.sqlstate().is_some_and(is_transient_sqlstate)
+}
+
+/// Build a predicate that retries errors with any of the given SQLSTATE codes.
+pub fn retry_on_sqlstates<E>(codes: &'static [&'static str]) -> impl Fn(&E) -> bool + Clone
+where
+    E: SqlState + ?Sized,
+{
+    move |error| error.sqlstate().is_some_and(|code| codes.contains(&code))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_transient, is_transient_sqlstate, retry_on_sqlstates, SqlState};
+
+    struct Disconnected;
+
+    impl SqlState for Disconnected {
+        fn sqlstate(&self) -> Option<&str> {
+            None
+        }
+
+        fn is_connection_error(&self) -> bool {
+            true
+        }
+    }
+
+    #[test]
+    fn transient_sqlstates() {
+        assert!(is_transient_sqlstate("40001"));
+        assert!(is_transient_sqlstate("40P01"));
+        assert!(is_transient_sqlstate("08006"));
+        assert!(!is_transient_sqlstate("23505"));
+        assert!(!is_transient_sqlstate("42P01"));
+    }
+
+    #[test]
+    fn transient_driver_errors() {
+        assert!(is_transient(&Disconnected));
+        assert!(is_transient("40001"));
+        assert!(!is_transient(&"23505".to_string()));
+        assert!(retry_on_sqlstates::<str>(&["23505"])("23505"));
+    }
+}
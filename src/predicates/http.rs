@@ -0,0 +1,129 @@
+//! Predicates for HTTP response statuses, keyed on the plain numeric status so that they can be
+//! used with any HTTP client.
+//!
+//! ```rust
+//! # use retry::predicates::http;
+//! let retry_on = http::retry_on_5xx_and_429();
+//!
+//! assert!(retry_on(503));
+//! assert!(retry_on(429));
+//! assert!(!retry_on(404));
+//! ```
+
+use std::ops::RangeInclusive;
+
+/// Returns `true` for statuses that are usually worth retrying: 408, 429 and server errors other
+/// than 501 and 505, which will not change on a retry.
+pub fn is_retryable(status: u16) -> bool {
+    match status {
+        408 | 429 => true,
+        501 | 505 => false,
+        status => is_server_error(status),
+    }
+}
+
+/// Returns `true` for server errors, in the 5xx class.
+pub fn is_server_error(status: u16) -> bool {
+    (500..600).contains(&status)
+}
+
+/// Build a predicate that retries every server error.
+pub fn retry_on_5xx() -> impl Fn(u16) -> bool + Clone {
+    is_server_error
+}
+
+/// Build a predicate that retries every server error and 429 Too Many Requests.
+pub fn retry_on_5xx_and_429() -> impl Fn(u16) -> bool + Clone {
+    |status| status == 429 || is_server_error(status)
+}
+
+/// A customizable set of statuses to retry, made of allowed ranges and denied statuses.
+///
+/// A status is retried if it is in one of the allowed ranges and is not denied.
+///
+/// ```rust
+/// # use retry::predicates::http::StatusPredicate;
+/// let predicate = StatusPredicate::new()
+///     .allow_range(500..=599)
+///     .allow(409)
+///     .deny(501)
+///     .build();
+///
+/// assert!(predicate(409));
+/// assert!(predicate(502));
+/// assert!(!predicate(501));
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct StatusPredicate {
+    allowed: Vec<RangeInclusive<u16>>,
+    denied: Vec<u16>,
+}
+
+impl StatusPredicate {
+    /// Create a predicate that retries no statuses.
+    pub fn new() -> Self {
+        StatusPredicate::default()
+    }
+
+    /// Create a predicate that retries the same statuses as `is_retryable`.
+    pub fn retryable() -> Self {
+        StatusPredicate::new()
+            .allow(408)
+            .allow(429)
+            .allow_range(500..=599)
+            .deny(501)
+            .deny(505)
+    }
+
+    /// Retry the given status, unless it is denied.
+    pub fn allow(self, status: u16) -> Self {
+        self.allow_range(status..=status)
+    }
+
+    /// Retry every status in the given range, except those that are denied.
+    pub fn allow_range(mut self, statuses: RangeInclusive<u16>) -> Self {
+        self.allowed.push(statuses);
+        self
+    }
+
+    /// Never retry the given status, even if it is in an allowed range.
+    pub fn deny(mut self, status: u16) -> Self {
+        self.denied.push(status);
+        self
+    }
+
+    /// Returns `true` if the status should be retried.
+    pub fn matches(&self, status: u16) -> bool {
+        !self.denied.contains(&status) && self.allowed.iter().any(|range| range.contains(&status))
+    }
+
+    /// Turn the set into a predicate function.
+    pub fn build(self) -> impl Fn(u16) -> bool + Clone {
+        move |status| self.matches(status)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_retryable, retry_on_5xx, StatusPredicate};
+
+    #[test]
+    fn retryable_statuses() {
+        for status in [408, 429, 500, 502, 503, 504] {
+            assert!(is_retryable(status), "{} should be retried", status);
+        }
+        for status in [200, 400, 404, 501, 505] {
+            assert!(!is_retryable(status), "{} should not be retried", status);
+        }
+        assert!(retry_on_5xx()(501));
+    }
+
+    #[test]
+    fn builder_matches_is_retryable() {
+        let predicate = StatusPredicate::retryable();
+
+        for status in 100..600 {
+            assert_eq!(predicate.matches(status), is_retryable(status));
+        }
+    }
+}
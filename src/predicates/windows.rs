@@ -0,0 +1,119 @@
+//! Predicates for transient Windows errors, keyed on the raw OS error codes of `io::Error`, which
+//! tell apart conditions that `io::ErrorKind` maps to `Other` or `Uncategorized`, such as a file
+//! that another process holds open.
+//!
+//! The codes are Winsock and Win32 error codes, so `is_transient` only matches errors of the
+//! operating system on Windows, where `raw_os_error` returns them. Elsewhere, the same numbers are
+//! `errno` values with other meanings, and no error matches. Codes read from elsewhere, such as a
+//! log or a remote agent, can be checked on any platform with `is_transient_code`:
+//!
+//! ```rust
+//! use retry::predicates::windows;
+//!
+//! assert!(windows::is_transient_code(windows::WSAECONNRESET));
+//! assert!(windows::is_transient_code(windows::ERROR_SHARING_VIOLATION));
+//! assert!(!windows::is_transient_code(5)); // ERROR_ACCESS_DENIED
+//! ```
+
+use std::io;
+
+/// An established connection was reset by the remote host.
+pub const WSAECONNRESET: i32 = 10054;
+/// A connection attempt, or an established connection, timed out.
+pub const WSAETIMEDOUT: i32 = 10060;
+/// The file is open in another process without sharing the access asked for.
+pub const ERROR_SHARING_VIOLATION: i32 = 32;
+/// Part of the file is locked by another process.
+pub const ERROR_LOCK_VIOLATION: i32 = 33;
+
+/// The Winsock error codes that usually indicate a transient network condition: interrupted or
+/// non-blocking calls, networks and hosts that are down or unreachable, connections that were
+/// reset, aborted, refused or timed out, exhausted buffers, and name lookups to try again.
+pub const TRANSIENT_WSA_ERRORS: &[i32] = &[
+    10004, // WSAEINTR
+    10035, // WSAEWOULDBLOCK
+    10050, // WSAENETDOWN
+    10051, // WSAENETUNREACH
+    10052, // WSAENETRESET
+    10053, // WSAECONNABORTED
+    WSAECONNRESET,
+    10055, // WSAENOBUFS
+    WSAETIMEDOUT,
+    10061, // WSAECONNREFUSED
+    10064, // WSAEHOSTDOWN
+    10065, // WSAEHOSTUNREACH
+    10067, // WSAEPROCLIM
+    10091, // WSASYSNOTREADY
+    11002, // WSATRY_AGAIN
+];
+
+/// The Win32 error codes that usually indicate a transient condition: files locked or held open
+/// by another process, such as an antivirus scanner or an indexer, busy devices and pipes, lost
+/// network shares, timeouts, and system resources that ran out.
+pub const TRANSIENT_WIN32_ERRORS: &[i32] = &[
+    21, // ERROR_NOT_READY
+    ERROR_SHARING_VIOLATION,
+    ERROR_LOCK_VIOLATION,
+    64,   // ERROR_NETNAME_DELETED
+    121,  // ERROR_SEM_TIMEOUT
+    170,  // ERROR_BUSY
+    231,  // ERROR_PIPE_BUSY
+    1231, // ERROR_NETWORK_UNREACHABLE
+    1236, // ERROR_CONNECTION_ABORTED
+    1237, // ERROR_RETRY
+    1450, // ERROR_NO_SYSTEM_RESOURCES
+    1453, // ERROR_WORKING_SET_QUOTA
+    1460, // ERROR_TIMEOUT
+];
+
+/// Returns `true` if the code is one of `TRANSIENT_WSA_ERRORS` or `TRANSIENT_WIN32_ERRORS`.
+pub fn is_transient_code(code: i32) -> bool {
+    TRANSIENT_WSA_ERRORS.contains(&code) || TRANSIENT_WIN32_ERRORS.contains(&code)
+}
+
+/// Returns `true` if the error is a transient Windows error. Always `false` on other platforms.
+pub fn is_transient(error: &io::Error) -> bool {
+    cfg!(windows) && error.raw_os_error().is_some_and(is_transient_code)
+}
+
+/// Build a predicate that retries Windows errors with any of the given codes. It never matches on
+/// other platforms.
+pub fn retry_on_codes(codes: &'static [i32]) -> impl Fn(&io::Error) -> bool + Clone {
+    move |error| {
+        cfg!(windows)
+            && error
+                .raw_os_error()
+                .is_some_and(|code| codes.contains(&code))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use super::{
+        is_transient, is_transient_code, retry_on_codes, ERROR_SHARING_VIOLATION, WSAETIMEDOUT,
+    };
+
+    #[test]
+    fn transient_codes() {
+        assert!(is_transient_code(WSAETIMEDOUT));
+        assert!(is_transient_code(10053));
+        assert!(is_transient_code(ERROR_SHARING_VIOLATION));
+        assert!(is_transient_code(1460));
+        assert!(!is_transient_code(2)); // ERROR_FILE_NOT_FOUND
+        assert!(!is_transient_code(10013)); // WSAEACCES
+    }
+
+    #[test]
+    fn matches_os_errors_only_on_windows() {
+        let error = io::Error::from_raw_os_error(ERROR_SHARING_VIOLATION);
+        assert_eq!(is_transient(&error), cfg!(windows));
+        assert_eq!(
+            retry_on_codes(&[ERROR_SHARING_VIOLATION])(&error),
+            cfg!(windows)
+        );
+        assert!(!is_transient(&io::Error::from_raw_os_error(5)));
+        assert!(!is_transient(&io::ErrorKind::TimedOut.into()));
+    }
+}
@@ -0,0 +1,77 @@
+//! Ready-made policies that follow the retry defaults of well-known clients, for callers who want a
+//! sane default rather than a schedule of their own.
+//!
+//! Every preset uses capped exponential backoff with full jitter and a bounded number of attempts,
+//! and can be adjusted further like any other policy:
+//!
+//! ```rust
+//! use retry::presets;
+//!
+//! let policy = presets::grpc_default().with_name("list-users");
+//! let result = policy.retry(|| Err::<(), _>("unavailable"));
+//!
+//! assert_eq!(result.unwrap_err().tries(), 5);
+//! ```
+//!
+//! Presets only decide when to retry, not what: which errors are worth retrying is still up to the
+//! operation, for example with the `predicates` module. This module is enabled with the default
+//! `"random"` feature, which the jitter needs.
+
+use std::time::Duration;
+
+use crate::{
+    delay::{Backoff, Jitter},
+    Policy,
+};
+
+/// The standard retry mode of the AWS SDKs: at most 3 attempts, with delays drawn between zero
+/// and a base of 1s that doubles after every attempt, up to 20s.
+pub fn aws_standard() -> Policy<Backoff> {
+    jittered(Duration::from_secs(1), Duration::from_secs(20), 3)
+}
+
+/// The retry policy recommended by gRPC: at most 5 attempts, the most a service config may ask
+/// for, with delays drawn between zero and a base of 100ms that doubles after every attempt, up to
+/// 1s.
+pub fn grpc_default() -> Policy<Backoff> {
+    jittered(Duration::from_millis(100), Duration::from_secs(1), 5)
+}
+
+/// For database transactions aborted by serialization failures or deadlocks: at most 5 attempts,
+/// with short delays drawn between zero and a base of 10ms that doubles after every attempt, up to
+/// 250ms, since conflicting transactions usually clear quickly and locks should not be held up.
+///
+/// `predicates::db::is_transient` tells these errors apart from the ones not worth retrying.
+pub fn db_transaction() -> Policy<Backoff> {
+    jittered(Duration::from_millis(10), Duration::from_millis(250), 5)
+}
+
+fn jittered(base: Duration, cap: Duration, max_attempts: u64) -> Policy<Backoff> {
+    Policy::new(
+        Backoff::exponential(base, 2.0)
+            .with_cap(cap)
+            .with_jitter(Jitter::Full),
+    )
+    .with_max_attempts(max_attempts)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{aws_standard, db_transaction, grpc_default};
+
+    #[test]
+    fn presets_are_bounded_and_valid() {
+        for &(policy, max_attempts, cap) in &[
+            (aws_standard as fn() -> _, 3, Duration::from_secs(20)),
+            (grpc_default, 5, Duration::from_secs(1)),
+            (db_transaction, 5, Duration::from_millis(250)),
+        ] {
+            let policy = policy();
+            assert_eq!(policy.max_attempts(), Some(max_attempts));
+            assert_eq!(policy.validate(), Ok(()));
+            assert!(policy.delays().take(20).all(|delay| delay <= cap));
+        }
+    }
+}
@@ -0,0 +1,375 @@
+//! Running external commands with retries, such as the flaky steps of a deploy script.
+//!
+//! ```rust,no_run
+//! # use std::process::Command;
+//! # use retry::delay::Exponential;
+//! use retry::{process::run_with_retry, Policy};
+//!
+//! let policy = Policy::new(Exponential::from_millis(500).take(4));
+//! let output = run_with_retry(
+//!     Command::new("kubectl").args(["rollout", "status", "deploy/web"]),
+//!     &policy,
+//!     |error| error.stderr().windows(7).any(|word| word == b"timeout"),
+//! )?;
+//! println!("{}", String::from_utf8_lossy(&output.stdout));
+//! # Ok::<(), retry::Error<retry::process::CommandError>>(())
+//! ```
+//!
+//! Each attempt runs the command with `Command::output`, so its standard output and error are
+//! captured unless they were redirected, and the command must not read its standard input. A
+//! command that cannot be started, or that exits with a failure, fails the attempt with a
+//! `CommandError`, and the predicate given tells whether it is worth running again. `is_transient`
+//! retries every failure except a program that does not exist or may not be run.
+//!
+//! A `Supervisor` keeps a long-running child process, such as a worker or a tunnel, running
+//! instead, restarting it with backoff whenever it exits.
+
+use std::{
+    error::Error as StdError,
+    fmt, io,
+    process::{Command, ExitStatus, Output},
+    time::Duration,
+};
+
+use crate::{clock::Clock, Error, OperationResult, Policy, Reconnector};
+
+type GiveUpHook = Box<dyn FnMut(&Error<CommandError>) + Send>;
+
+/// Why an attempt to run a command failed.
+#[derive(Debug)]
+pub enum CommandError {
+    /// The command could not be started, or a supervised child could not be waited for.
+    Spawn(io::Error),
+    /// The command ran and exited with a failure.
+    Failed(Output),
+}
+
+impl CommandError {
+    /// The status the command exited with, if it ran.
+    pub fn status(&self) -> Option<ExitStatus> {
+        match *self {
+            CommandError::Spawn(_) => None,
+            CommandError::Failed(ref output) => Some(output.status),
+        }
+    }
+
+    /// What the command wrote to its standard error, if it ran and it was captured.
+    pub fn stderr(&self) -> &[u8] {
+        match *self {
+            CommandError::Spawn(_) => &[],
+            CommandError::Failed(ref output) => &output.stderr,
+        }
+    }
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            CommandError::Spawn(ref error) => {
+                write!(formatter, "failed to start the command: {}", error)
+            }
+            CommandError::Failed(ref output) => {
+                write!(formatter, "the command exited with {}", output.status)?;
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                match stderr.trim().lines().last() {
+                    Some(line) => write!(formatter, ": {}", line),
+                    None => Ok(()),
+                }
+            }
+        }
+    }
+}
+
+impl StdError for CommandError {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match *self {
+            CommandError::Spawn(ref error) => Some(error),
+            CommandError::Failed(_) => None,
+        }
+    }
+}
+
+/// Retry every failure of a command, except a program that does not exist or may not be run,
+/// which no retry will fix.
+pub fn is_transient(error: &CommandError) -> bool {
+    match *error {
+        CommandError::Spawn(ref error) => !matches!(
+            error.kind(),
+            io::ErrorKind::NotFound | io::ErrorKind::PermissionDenied
+        ),
+        CommandError::Failed(_) => true,
+    }
+}
+
+/// Run `command` until it exits successfully, retrying according to `policy` while it fails with
+/// an error that `is_retryable` accepts, and returning its output.
+pub fn run_with_retry<D, P>(
+    command: &mut Command,
+    policy: &Policy<D>,
+    mut is_retryable: P,
+) -> Result<Output, Error<CommandError>>
+where
+    D: IntoIterator<Item = Duration> + Clone,
+    P: FnMut(&CommandError) -> bool,
+{
+    policy.retry(|| OperationResult::retry_if(run(command), &mut is_retryable))
+}
+
+/// Run the command built by `build` for each attempt, such as one whose arguments depend on the
+/// attempt before, like `run_with_retry`.
+pub fn run_built_with_retry<B, D, P>(
+    mut build: B,
+    policy: &Policy<D>,
+    mut is_retryable: P,
+) -> Result<Output, Error<CommandError>>
+where
+    B: FnMut() -> Command,
+    D: IntoIterator<Item = Duration> + Clone,
+    P: FnMut(&CommandError) -> bool,
+{
+    policy.retry(|| OperationResult::retry_if(run(&mut build()), &mut is_retryable))
+}
+
+fn run(command: &mut Command) -> Result<Output, CommandError> {
+    match command.output() {
+        Ok(output) if output.status.success() => Ok(output),
+        Ok(output) => Err(CommandError::Failed(output)),
+        Err(error) => Err(CommandError::Spawn(error)),
+    }
+}
+
+/// Keeps a child process running, restarting it with the delays of a strategy whenever it exits
+/// with a failure, and giving up once the strategy ends.
+///
+/// A child that stays up for `stable_after` is taken to have recovered, so the next restart
+/// starts the strategy over from its first delay, as with a `Reconnector`. Restarts that keep
+/// failing straight away go further down the strategy until it ends, at which point the hook
+/// given to `on_give_up` is called, for example to page someone, and `run` returns the last
+/// failure.
+///
+/// Supervised children inherit the standard streams, so the output of a failed run is not kept
+/// in its `CommandError`.
+///
+/// ```rust,no_run
+/// # use std::{process::Command, time::Duration};
+/// # use retry::delay::Exponential;
+/// use retry::process::Supervisor;
+///
+/// let backoff = Exponential::from_millis(100).take(10);
+/// let mut supervisor = Supervisor::new(backoff, Duration::from_secs(60))
+///     .on_give_up(|error| eprintln!("worker keeps crashing, giving up: {}", error));
+/// let status = supervisor.run(|| {
+///     let mut command = Command::new("worker");
+///     command.arg("--queue").arg("emails");
+///     command
+/// });
+/// ```
+pub struct Supervisor<D>
+where
+    D: IntoIterator<Item = Duration>,
+{
+    reconnector: Reconnector<D>,
+    restart_on_success: bool,
+    on_give_up: Option<GiveUpHook>,
+}
+
+impl<D> Supervisor<D>
+where
+    D: IntoIterator<Item = Duration> + Clone,
+{
+    /// Create a supervisor that restarts children according to `strategy`, and starts it over
+    /// after a child has stayed up for `stable_after`.
+    pub fn new(strategy: D, stable_after: Duration) -> Self {
+        Supervisor {
+            reconnector: Reconnector::new(strategy, stable_after),
+            restart_on_success: false,
+            on_give_up: None,
+        }
+    }
+
+    /// Restart children that exit successfully too, so that `run` only returns when the
+    /// strategy ends.
+    pub fn restart_on_success(mut self) -> Self {
+        self.restart_on_success = true;
+        self
+    }
+
+    /// Call `hook` with the last failure when the strategy ends and the supervisor gives up.
+    pub fn on_give_up<F>(mut self, hook: F) -> Self
+    where
+        F: FnMut(&Error<CommandError>) + Send + 'static,
+    {
+        self.on_give_up = Some(Box::new(hook));
+        self
+    }
+
+    /// Measure the uptime of children with the given clock instead of the system clock.
+    pub fn with_clock<C>(mut self, clock: C) -> Self
+    where
+        C: Clock + 'static,
+    {
+        self.reconnector = self.reconnector.with_clock(clock);
+        self
+    }
+
+    /// Spawn the command built by `build`, and build and spawn it again whenever the child exits
+    /// with a failure, until a child exits successfully or the strategy ends.
+    pub fn run<B>(&mut self, mut build: B) -> Result<ExitStatus, Error<CommandError>>
+    where
+        B: FnMut() -> Command,
+    {
+        let restart_on_success = self.restart_on_success;
+        let result = self.reconnector.run(
+            || build().spawn().map_err(CommandError::Spawn),
+            |mut child| match child.wait() {
+                Ok(status) if status.success() && !restart_on_success => Ok(status),
+                Ok(status) => Err(CommandError::Failed(Output {
+                    status,
+                    stdout: Vec::new(),
+                    stderr: Vec::new(),
+                })),
+                Err(error) => Err(CommandError::Spawn(error)),
+            },
+        );
+        if let (Err(ref error), Some(hook)) = (&result, self.on_give_up.as_mut()) {
+            hook(error);
+        }
+        result
+    }
+}
+
+impl<D> fmt::Debug for Supervisor<D>
+where
+    D: IntoIterator<Item = Duration> + fmt::Debug,
+{
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter
+            .debug_struct("Supervisor")
+            .field("reconnector", &self.reconnector)
+            .field("restart_on_success", &self.restart_on_success)
+            .finish()
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::{
+        fs,
+        process::Command,
+        sync::{
+            atomic::{AtomicU64, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+
+    use super::{is_transient, run_built_with_retry, run_with_retry, CommandError, Supervisor};
+    use crate::{delay::NoDelay, Error, Policy};
+
+    /// A shell command that fails with `busy` until it has run three times.
+    fn flaky(counter: &std::path::Path) -> Command {
+        let mut command = Command::new("sh");
+        command.arg("-c").arg(format!(
+            "n=$(cat {0} 2>/dev/null || echo 0); n=$((n + 1)); echo $n > {0}; \
+             if [ $n -lt 3 ]; then echo busy >&2; exit 1; fi; echo done",
+            counter.display()
+        ));
+        command
+    }
+
+    #[test]
+    fn reruns_failed_commands() {
+        let counter = std::env::temp_dir().join(format!("retry-process-{}", std::process::id()));
+        let _ = fs::remove_file(&counter);
+        let policy = Policy::new(NoDelay.take(5));
+
+        let mut stderrs = Vec::new();
+        let output = run_with_retry(&mut flaky(&counter), &policy, |error| {
+            stderrs.push(String::from_utf8_lossy(error.stderr()).into_owned());
+            is_transient(error)
+        })
+        .unwrap();
+        assert_eq!(output.stdout, b"done\n");
+        assert_eq!(stderrs, ["busy\n", "busy\n"]);
+
+        fs::remove_file(&counter).unwrap();
+        let error = run_built_with_retry(
+            || flaky(&counter),
+            &policy,
+            |error| error.stderr() != b"busy\n",
+        )
+        .unwrap_err();
+        assert!(matches!(error, Error::Operation { tries: 1, .. }));
+        assert_eq!(
+            error.into_last_error().unwrap().to_string(),
+            "the command exited with exit status: 1: busy"
+        );
+        let _ = fs::remove_file(&counter);
+    }
+
+    #[test]
+    fn supervisor_restarts_children_until_it_gives_up() {
+        let counter = std::env::temp_dir().join(format!("retry-supervisor-{}", std::process::id()));
+        let _ = fs::remove_file(&counter);
+
+        let status = Supervisor::new(NoDelay.take(5), Duration::from_secs(60))
+            .run(|| flaky(&counter))
+            .unwrap();
+        assert!(status.success());
+        assert_eq!(fs::read_to_string(&counter).unwrap(), "3\n");
+
+        let given_up = Arc::new(AtomicU64::new(0));
+        let tries = Arc::clone(&given_up);
+        let mut supervisor = Supervisor::new(NoDelay.take(2), Duration::from_secs(60))
+            .restart_on_success()
+            .on_give_up(move |error| tries.store(error.tries(), Ordering::SeqCst));
+        fs::remove_file(&counter).unwrap();
+        let error = supervisor.run(|| flaky(&counter)).unwrap_err();
+        assert!(matches!(error, Error::Operation { tries: 3, .. }));
+        assert_eq!(given_up.load(Ordering::SeqCst), 3);
+        let _ = fs::remove_file(&counter);
+    }
+
+    #[test]
+    fn supervisor_starts_over_after_sustained_uptime() {
+        let mut supervisor = Supervisor::new(NoDelay.take(1), Duration::from_millis(50));
+        let mut runs = 0;
+
+        let status = supervisor.run(|| {
+            runs += 1;
+            let mut command = Command::new("sh");
+            command.arg("-c").arg(if runs < 4 {
+                "sleep 0.1; exit 1"
+            } else {
+                "exit 1"
+            });
+            command
+        });
+
+        assert!(status.is_err());
+        assert_eq!(runs, 4, "the strategy starts over after each long run");
+    }
+
+    #[test]
+    fn does_not_retry_missing_programs() {
+        let mut tries = 0;
+        let result = run_with_retry(
+            &mut Command::new("retry-no-such-program"),
+            &Policy::new(NoDelay.take(5)),
+            |error| {
+                tries += 1;
+                is_transient(error)
+            },
+        );
+
+        assert!(matches!(
+            result,
+            Err(Error::Operation {
+                error: CommandError::Spawn(_),
+                tries: 1,
+                ..
+            })
+        ));
+        assert_eq!(tries, 1);
+    }
+}
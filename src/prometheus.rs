@@ -0,0 +1,148 @@
+//! Retry statistics collected in a `prometheus` registry. This module is enabled with the
+//! `"prometheus"` feature.
+//!
+//! ```
+//! # use prometheus::Registry;
+//! # use retry::delay::Fixed;
+//! # use retry::prometheus::PolicyMetrics;
+//! # use retry::Policy;
+//! let registry = Registry::new();
+//! let metrics = PolicyMetrics::register(&registry).unwrap();
+//!
+//! let policy = Policy::new(Fixed::from_millis(10).take(3))
+//!     .with_name("search")
+//!     .with_prometheus(metrics);
+//!
+//! let _ = policy.retry(|| Ok::<_, ()>(()));
+//! # assert_eq!(registry.gather().len(), 1);
+//! ```
+
+use std::time::Duration;
+
+use prometheus::{HistogramOpts, HistogramVec, IntCounterVec, Opts, Registry};
+
+/// The label that holds the name of the policy, or an empty string for unnamed policies.
+pub const OPERATION_LABEL: &str = "operation";
+
+/// The collectors updated by the policies that carry this handle.
+///
+/// The handle is cheap to clone, and every clone updates the same collectors, so one handle can be
+/// shared by all the policies of an application. The collectors are:
+///
+/// - `retry_attempts_total`, counting every attempt,
+/// - `retry_giveups_total`, counting the calls that gave up,
+/// - `retry_delay_seconds`, a histogram of the delays waited before retrying.
+///
+/// All of them are labeled by the name of the policy as `operation`.
+#[derive(Clone, Debug)]
+pub struct PolicyMetrics {
+    attempts: IntCounterVec,
+    give_ups: IntCounterVec,
+    delays: HistogramVec,
+}
+
+impl PolicyMetrics {
+    /// Create the collectors without registering them.
+    pub fn new() -> prometheus::Result<Self> {
+        Ok(PolicyMetrics {
+            attempts: IntCounterVec::new(
+                Opts::new("retry_attempts_total", "Attempts made by retry policies."),
+                &[OPERATION_LABEL],
+            )?,
+            give_ups: IntCounterVec::new(
+                Opts::new(
+                    "retry_giveups_total",
+                    "Calls that retry policies gave up on.",
+                ),
+                &[OPERATION_LABEL],
+            )?,
+            delays: HistogramVec::new(
+                HistogramOpts::new(
+                    "retry_delay_seconds",
+                    "Delays waited by retry policies before retrying.",
+                ),
+                &[OPERATION_LABEL],
+            )?,
+        })
+    }
+
+    /// Create the collectors and register them with `registry`.
+    pub fn register(registry: &Registry) -> prometheus::Result<Self> {
+        let metrics = PolicyMetrics::new()?;
+        registry.register(Box::new(metrics.attempts.clone()))?;
+        registry.register(Box::new(metrics.give_ups.clone()))?;
+        registry.register(Box::new(metrics.delays.clone()))?;
+        Ok(metrics)
+    }
+
+    /// The counter of attempts.
+    pub fn attempts(&self) -> &IntCounterVec {
+        &self.attempts
+    }
+
+    /// The counter of give-ups.
+    pub fn give_ups(&self) -> &IntCounterVec {
+        &self.give_ups
+    }
+
+    /// The histogram of delays, in seconds.
+    pub fn delays(&self) -> &HistogramVec {
+        &self.delays
+    }
+
+    pub(crate) fn record_attempt(&self, operation: &str) {
+        self.attempts.with_label_values(&[operation]).inc();
+    }
+
+    pub(crate) fn record_delay(&self, operation: &str, delay: Duration) {
+        self.delays
+            .with_label_values(&[operation])
+            .observe(delay.as_secs_f64());
+    }
+
+    pub(crate) fn record_give_up(&self, operation: &str) {
+        self.give_ups.with_label_values(&[operation]).inc();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use prometheus::Registry;
+
+    use super::PolicyMetrics;
+    use crate::{delay::NoDelay, Policy};
+
+    #[test]
+    fn policies_update_registered_collectors() {
+        let registry = Registry::new();
+        let metrics = PolicyMetrics::register(&registry).unwrap();
+        let named = Policy::new(NoDelay.take(1))
+            .with_name("db")
+            .with_prometheus(metrics.clone());
+        let unnamed = Policy::new(NoDelay).with_prometheus(metrics.clone());
+
+        let _ = named.retry(|| Err::<(), _>("down"));
+        let _ = unnamed.retry(|| Ok::<_, ()>(()));
+
+        assert_eq!(metrics.attempts().with_label_values(&["db"]).get(), 2);
+        assert_eq!(metrics.give_ups().with_label_values(&["db"]).get(), 1);
+        assert_eq!(
+            metrics
+                .delays()
+                .with_label_values(&["db"])
+                .get_sample_count(),
+            1
+        );
+        assert_eq!(metrics.attempts().with_label_values(&[""]).get(), 1);
+        assert_eq!(metrics.give_ups().with_label_values(&[""]).get(), 0);
+        assert_eq!(registry.gather().len(), 3);
+    }
+
+    #[test]
+    fn registering_twice_fails() {
+        let registry = Registry::new();
+        PolicyMetrics::register(&registry).unwrap();
+
+        assert!(PolicyMetrics::register(&registry).is_err());
+    }
+}
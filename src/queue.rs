@@ -0,0 +1,445 @@
+use std::{
+    cmp::{Ordering, Reverse},
+    collections::BinaryHeap,
+    convert::TryFrom,
+    fmt,
+    sync::{Arc, Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+use crate::clock::{saturating_add, Clock, SystemClock};
+
+/// A queue of items waiting to be retried, such as failed jobs, that hands each one out once its
+/// delay is over. Clones share the same items, so producers and workers on several threads can
+/// use the same queue.
+///
+/// Each item has a priority, higher first. Items whose delays are over wait in a ready queue ordered
+/// by priority, so when the timers of several items collide, as they do after an outage, the items
+/// of critical jobs or paid customers are retried ahead of the others. Among items of the same
+/// priority, the one that has been due the longest goes first, and then the one scheduled first.
+/// Another `DrainOrder` can be chosen with `with_order`, and `with_dispatch_rate` spreads the items
+/// out rather than handing thousands of them out at once to retry a dependency that just recovered.
+/// With many items waiting, `with_coalescing` trades the precision of their delays for fewer
+/// wakeups.
+///
+/// ```rust
+/// # use std::time::Duration;
+/// use retry::RetryQueue;
+///
+/// let queue = RetryQueue::new();
+/// queue.schedule("nightly report", Duration::default(), 0);
+/// queue.schedule("checkout", Duration::default(), 10);
+/// queue.schedule("reminder email", Duration::from_secs(60), 100);
+///
+/// assert_eq!(queue.pop_ready(), Some("checkout"));
+/// assert_eq!(queue.pop_ready(), Some("nightly report"));
+/// assert_eq!(queue.pop_ready(), None);
+/// assert_eq!(queue.len(), 1);
+/// ```
+pub struct RetryQueue<T> {
+    inner: Arc<Mutex<Inner<T>>>,
+    clock: Option<Arc<dyn Clock>>,
+}
+
+struct Inner<T> {
+    /// The items whose delays are not over yet, earliest due first.
+    waiting: BinaryHeap<Reverse<Entry<T>>>,
+    /// The items whose delays are over, first to be handed out first.
+    ready: BinaryHeap<Ready<T>>,
+    scheduled: u64,
+    order: DrainOrder,
+    /// The time that the ready items' positions are measured from.
+    epoch: Instant,
+    /// The time between two items handed out, if the queue has a dispatch rate.
+    dispatch_interval: Option<Duration>,
+    /// The time the next item can be handed out, under the dispatch rate.
+    next_dispatch: Option<Instant>,
+    /// The width of the buckets that due times are rounded up to, if they are coalesced.
+    bucket: Option<Duration>,
+}
+
+impl<T> Inner<T> {
+    /// Round `due` up to the end of its bucket, if due times are coalesced.
+    fn coalesce(&self, due: Instant) -> Instant {
+        let bucket = match self.bucket {
+            Some(bucket) => bucket.as_nanos(),
+            None => return due,
+        };
+        let since_epoch = due.saturating_duration_since(self.epoch).as_nanos();
+        let rounded = since_epoch.div_ceil(bucket).saturating_mul(bucket);
+        let rounded = Duration::from_nanos(u64::try_from(rounded).unwrap_or(u64::MAX));
+        self.epoch.checked_add(rounded).unwrap_or(due).max(due)
+    }
+}
+
+/// The order in which a `RetryQueue` hands out the items whose delays are over.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum DrainOrder {
+    /// Hand out the items of highest priority first, and among items of the same priority, the one
+    /// that has been due the longest. It is the default.
+    ///
+    /// Items of low priority wait for as long as items of higher priority keep becoming due.
+    #[default]
+    Priority,
+    /// Hand out the items that have been due the longest first, whatever their priority.
+    Due,
+    /// Hand out the items that have been due the longest first, counting each point of priority
+    /// as having been due for the given time longer. Items of high priority go ahead of those
+    /// that became due shortly before them, without starving the items of low priority.
+    Aging(Duration),
+}
+
+/// An item of the queue, ordered by when it is due, and then by when it was scheduled.
+struct Entry<T> {
+    due: Instant,
+    priority: u32,
+    sequence: u64,
+    item: T,
+}
+
+impl<T> Entry<T> {
+    fn key(&self) -> (Instant, u64) {
+        (self.due, self.sequence)
+    }
+}
+
+impl<T> PartialEq for Entry<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl<T> Eq for Entry<T> {}
+
+impl<T> PartialOrd for Entry<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> Ord for Entry<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key().cmp(&other.key())
+    }
+}
+
+/// An item whose delay is over, ordered so that the greatest is handed out first.
+struct Ready<T> {
+    /// The priority that comes first under the queue's order, if any.
+    rank: u32,
+    /// The time the item counts as due from under the queue's order, in nanoseconds since the
+    /// queue's epoch.
+    due: i128,
+    entry: Entry<T>,
+}
+
+impl<T> Ready<T> {
+    fn new(entry: Entry<T>, order: DrainOrder, epoch: Instant) -> Self {
+        let due = entry.due.saturating_duration_since(epoch).as_nanos() as i128;
+        let (rank, due) = match order {
+            DrainOrder::Priority => (entry.priority, due),
+            DrainOrder::Due => (0, due),
+            DrainOrder::Aging(step) => (
+                0,
+                due - step.as_nanos() as i128 * i128::from(entry.priority),
+            ),
+        };
+        Ready { rank, due, entry }
+    }
+
+    fn key(&self) -> (u32, Reverse<i128>, Reverse<u64>) {
+        (self.rank, Reverse(self.due), Reverse(self.entry.sequence))
+    }
+}
+
+impl<T> PartialEq for Ready<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl<T> Eq for Ready<T> {}
+
+impl<T> PartialOrd for Ready<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> Ord for Ready<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key().cmp(&other.key())
+    }
+}
+
+impl<T> RetryQueue<T> {
+    /// Create an empty queue.
+    pub fn new() -> Self {
+        RetryQueue {
+            inner: Arc::new(Mutex::new(Inner {
+                waiting: BinaryHeap::new(),
+                ready: BinaryHeap::new(),
+                scheduled: 0,
+                order: DrainOrder::default(),
+                epoch: SystemClock.now(),
+                dispatch_interval: None,
+                next_dispatch: None,
+                bucket: None,
+            })),
+            clock: None,
+        }
+    }
+
+    /// Measure delays with the given clock instead of the system clock.
+    pub fn with_clock<C>(mut self, clock: C) -> Self
+    where
+        C: Clock + 'static,
+    {
+        self.lock().epoch = clock.now();
+        self.clock = Some(Arc::new(clock));
+        self
+    }
+
+    /// Hand out the items whose delays are over in the given order instead of by priority.
+    ///
+    /// Items that are already ready keep their place.
+    pub fn with_order(self, order: DrainOrder) -> Self {
+        self.lock().order = order;
+        self
+    }
+
+    /// Hand out at most `count` items every `per`, evenly spaced, however many are ready, so that
+    /// a dependency that recovers is not flooded with every retry that was waiting for it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `count` is zero.
+    pub fn with_dispatch_rate(self, count: u32, per: Duration) -> Self {
+        assert!(count > 0, "the dispatch rate must allow at least one item");
+        self.lock().dispatch_interval = Some(per / count);
+        self
+    }
+
+    /// Round the due time of every item scheduled from now on up to the next multiple of `bucket`,
+    /// so that items due around the same time become due together, and a worker that sleeps until
+    /// the next item is due wakes up once per bucket rather than once per item. Items are handed
+    /// out up to `bucket` late, and never early.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bucket` is zero.
+    pub fn with_coalescing(self, bucket: Duration) -> Self {
+        assert!(
+            bucket > Duration::default(),
+            "the coalescing bucket must not be empty"
+        );
+        self.lock().bucket = Some(bucket);
+        self
+    }
+
+    fn now(&self) -> Instant {
+        match self.clock {
+            Some(ref clock) => clock.now(),
+            None => SystemClock.now(),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Inner<T>> {
+        self.inner
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Add `item` to the queue, to be retried with the given priority once `delay` is over.
+    ///
+    /// A delay too long to add to an `Instant`, such as `Duration::MAX`, is due at the latest
+    /// instant that can be represented, after every other item.
+    pub fn schedule(&self, item: T, delay: Duration, priority: u32) {
+        let due = saturating_add(self.now(), delay, Instant::checked_add);
+        self.schedule_at(item, due, priority);
+    }
+
+    /// Add `item` to the queue, to be retried with the given priority once `due` has passed.
+    pub fn schedule_at(&self, item: T, due: Instant, priority: u32) {
+        let mut inner = self.lock();
+        let due = inner.coalesce(due);
+        let sequence = inner.scheduled;
+        inner.scheduled += 1;
+        inner.waiting.push(Reverse(Entry {
+            due,
+            priority,
+            sequence,
+            item,
+        }));
+    }
+
+    /// Take the first item, in the queue's order, among those whose delay is over, if any and if
+    /// the dispatch rate allows it.
+    pub fn pop_ready(&self) -> Option<T> {
+        let now = self.now();
+        let mut inner = self.lock();
+        if inner.next_dispatch.is_some_and(|next| next > now) {
+            return None;
+        }
+        let (order, epoch) = (inner.order, inner.epoch);
+        while inner
+            .waiting
+            .peek()
+            .is_some_and(|Reverse(entry)| entry.due <= now)
+        {
+            if let Some(Reverse(entry)) = inner.waiting.pop() {
+                inner.ready.push(Ready::new(entry, order, epoch));
+            }
+        }
+        let ready = inner.ready.pop()?;
+        if let Some(interval) = inner.dispatch_interval {
+            inner.next_dispatch = now.checked_add(interval);
+        }
+        Some(ready.entry.item)
+    }
+
+    /// When `pop_ready` can next hand out an item, or `None` if the queue is empty. The time is in
+    /// the past if an item can be handed out now.
+    pub fn next_due(&self) -> Option<Instant> {
+        let inner = self.lock();
+        let ready = inner.ready.iter().map(|ready| ready.entry.due).min();
+        let waiting = inner.waiting.peek().map(|Reverse(entry)| entry.due);
+        let due = ready.into_iter().chain(waiting).min()?;
+        Some(inner.next_dispatch.map_or(due, |next| next.max(due)))
+    }
+
+    /// The number of items in the queue, ready or not.
+    pub fn len(&self) -> usize {
+        let inner = self.lock();
+        inner.waiting.len() + inner.ready.len()
+    }
+
+    /// Whether the queue is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<T> Clone for RetryQueue<T> {
+    fn clone(&self) -> Self {
+        RetryQueue {
+            inner: Arc::clone(&self.inner),
+            clock: self.clock.clone(),
+        }
+    }
+}
+
+impl<T> Default for RetryQueue<T> {
+    fn default() -> Self {
+        RetryQueue::new()
+    }
+}
+
+impl<T> fmt::Debug for RetryQueue<T> {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let inner = self.lock();
+        formatter
+            .debug_struct("RetryQueue")
+            .field("waiting", &inner.waiting.len())
+            .field("ready", &inner.ready.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{DrainOrder, RetryQueue};
+    use crate::clock::{Clock, MockClock};
+
+    #[test]
+    fn hands_out_ready_items_by_priority() {
+        let clock = MockClock::new();
+        let queue = RetryQueue::new().with_clock(clock.clone());
+        let start = clock.now();
+        queue.schedule("low, due first", Duration::from_secs(1), 1);
+        queue.schedule("high", Duration::from_secs(3), 5);
+        queue.schedule("low, due second", Duration::from_secs(2), 1);
+        queue.schedule("later", Duration::from_secs(10), 9);
+
+        assert_eq!(queue.pop_ready(), None);
+        assert_eq!(queue.next_due(), Some(start + Duration::from_secs(1)));
+
+        clock.advance(Duration::from_secs(5));
+        let other = queue.clone();
+        assert_eq!(other.pop_ready(), Some("high"));
+        assert_eq!(queue.pop_ready(), Some("low, due first"));
+        assert_eq!(queue.next_due(), Some(start + Duration::from_secs(2)));
+        assert_eq!(queue.pop_ready(), Some("low, due second"));
+        assert_eq!(queue.pop_ready(), None);
+        assert_eq!(queue.len(), 1);
+
+        clock.advance(Duration::from_secs(5));
+        assert_eq!(queue.pop_ready(), Some("later"));
+        assert!(queue.is_empty());
+        assert_eq!(queue.next_due(), None);
+    }
+
+    #[test]
+    fn delays_past_any_instant_are_due_last() {
+        let clock = MockClock::new();
+        let queue = RetryQueue::new().with_clock(clock.clone());
+        queue.schedule("forever", Duration::MAX, 9);
+        queue.schedule("soon", Duration::from_secs(1), 0);
+
+        assert_eq!(queue.pop_ready(), None);
+        clock.advance(Duration::from_secs(3600));
+        assert_eq!(queue.pop_ready(), Some("soon"));
+        assert_eq!(queue.pop_ready(), None);
+        assert!(queue.next_due() > Some(clock.now() + Duration::from_secs(1 << 40)));
+    }
+
+    #[test]
+    fn coalesces_due_times_into_buckets() {
+        let clock = MockClock::new();
+        let start = clock.now();
+        let queue = RetryQueue::new()
+            .with_clock(clock.clone())
+            .with_coalescing(Duration::from_secs(1));
+        for millis in [1, 300, 999, 1000] {
+            queue.schedule(millis, Duration::from_millis(millis), 0);
+        }
+        queue.schedule(1001, Duration::from_millis(1001), 1);
+
+        assert_eq!(queue.next_due(), Some(start + Duration::from_secs(1)));
+        clock.advance(Duration::from_millis(999));
+        assert_eq!(queue.pop_ready(), None, "never early");
+
+        clock.advance(Duration::from_millis(1));
+        let ready: Vec<_> = std::iter::from_fn(|| queue.pop_ready()).collect();
+        assert_eq!(ready, [1, 300, 999, 1000]);
+        assert_eq!(queue.next_due(), Some(start + Duration::from_secs(2)));
+    }
+
+    #[test]
+    fn drains_in_order_at_the_dispatch_rate() {
+        let clock = MockClock::new();
+        let start = clock.now();
+        let queue = RetryQueue::new()
+            .with_clock(clock.clone())
+            .with_order(DrainOrder::Aging(Duration::from_secs(10)))
+            .with_dispatch_rate(2, Duration::from_secs(1));
+        queue.schedule("low, due long ago", Duration::from_secs(0), 0);
+        queue.schedule("high", Duration::from_secs(25), 2);
+        queue.schedule("low", Duration::from_secs(20), 0);
+
+        clock.advance(Duration::from_secs(30));
+        assert_eq!(queue.pop_ready(), Some("low, due long ago"));
+        assert_eq!(queue.pop_ready(), None, "limited by the dispatch rate");
+        assert_eq!(
+            queue.next_due(),
+            Some(start + Duration::from_millis(30_500))
+        );
+
+        clock.advance(Duration::from_millis(500));
+        assert_eq!(queue.pop_ready(), Some("high"));
+        clock.advance(Duration::from_millis(500));
+        assert_eq!(queue.pop_ready(), Some("low"));
+    }
+}
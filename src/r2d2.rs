@@ -0,0 +1,184 @@
+//! An `r2d2` connection manager that retries establishing connections according to a policy. This
+//! module is enabled with the `"r2d2"` feature.
+//!
+//! Wrapping the manager of a pool in a `RetryManager` makes the pool retry a failed connection,
+//! for example while the database restarts, before it reports the error to its error handler and
+//! counts the connection as missing:
+//!
+//! ```rust
+//! # #[derive(Debug)]
+//! # struct Database;
+//! # impl r2d2::ManageConnection for Database {
+//! #     type Connection = ();
+//! #     type Error = std::io::Error;
+//! #     fn connect(&self) -> std::io::Result<()> { Ok(()) }
+//! #     fn is_valid(&self, _: &mut ()) -> std::io::Result<()> { Ok(()) }
+//! #     fn has_broken(&self, _: &mut ()) -> bool { false }
+//! # }
+//! # let manager = Database;
+//! use retry::{delay::Exponential, r2d2::RetryManager, Policy};
+//!
+//! let policy = Policy::new(Exponential::from_millis(50).take(5));
+//! let pool = r2d2::Pool::new(RetryManager::new(manager, policy))?;
+//! # Ok::<(), r2d2::Error>(())
+//! ```
+//!
+//! Every error of `connect` is retried. Checking a connection with `is_valid` and `has_broken` is
+//! passed through as it is, without retries, since the pool discards the connections that fail
+//! them and establishes new ones.
+
+use std::time::Duration;
+
+use r2d2::ManageConnection;
+
+use crate::{Error, Policy};
+
+/// A `r2d2::ManageConnection` that establishes connections with another manager, retrying them
+/// according to a policy.
+///
+/// The errors of the manager are wrapped in `Error`, which tells how long the connection was
+/// retried for. An error of `is_valid` is returned as an `Error::Operation` of a single try.
+#[derive(Clone, Debug)]
+pub struct RetryManager<M, D> {
+    manager: M,
+    policy: Policy<D>,
+}
+
+impl<M, D> RetryManager<M, D> {
+    /// Establish connections with `manager`, retrying them according to `policy`.
+    pub fn new(manager: M, policy: Policy<D>) -> Self {
+        RetryManager { manager, policy }
+    }
+
+    /// The wrapped manager.
+    pub fn manager(&self) -> &M {
+        &self.manager
+    }
+
+    /// The policy connections are retried with.
+    pub fn policy(&self) -> &Policy<D> {
+        &self.policy
+    }
+}
+
+impl<M, D> ManageConnection for RetryManager<M, D>
+where
+    M: ManageConnection,
+    M::Error: Send,
+    D: IntoIterator<Item = Duration> + Clone + Send + Sync + 'static,
+{
+    type Connection = M::Connection;
+    type Error = Error<M::Error>;
+
+    fn connect(&self) -> Result<Self::Connection, Self::Error> {
+        self.policy.retry(|| self.manager.connect())
+    }
+
+    fn is_valid(&self, connection: &mut Self::Connection) -> Result<(), Self::Error> {
+        self.manager
+            .is_valid(connection)
+            .map_err(|error| Error::Operation {
+                error,
+                total_delay: Duration::default(),
+                tries: 1,
+            })
+    }
+
+    fn has_broken(&self, connection: &mut Self::Connection) -> bool {
+        self.manager.has_broken(connection)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fmt,
+        sync::{
+            atomic::{AtomicU64, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+
+    use r2d2::{ManageConnection, Pool};
+
+    use super::RetryManager;
+    use crate::{delay::Fixed, Error, Policy};
+
+    /// Fails to connect until the given number of connections was tried.
+    #[derive(Debug)]
+    struct Flaky {
+        failures: u64,
+        tries: Arc<AtomicU64>,
+    }
+
+    #[derive(Debug)]
+    struct Connection;
+
+    #[derive(Debug)]
+    struct Refused;
+
+    impl fmt::Display for Refused {
+        fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+            formatter.write_str("connection refused")
+        }
+    }
+
+    impl std::error::Error for Refused {}
+
+    impl ManageConnection for Flaky {
+        type Connection = Connection;
+        type Error = Refused;
+
+        fn connect(&self) -> Result<Connection, Refused> {
+            if self.tries.fetch_add(1, Ordering::SeqCst) < self.failures {
+                Err(Refused)
+            } else {
+                Ok(Connection)
+            }
+        }
+
+        fn is_valid(&self, _: &mut Connection) -> Result<(), Refused> {
+            Ok(())
+        }
+
+        fn has_broken(&self, _: &mut Connection) -> bool {
+            false
+        }
+    }
+
+    fn flaky(failures: u64) -> Flaky {
+        Flaky {
+            failures,
+            tries: Arc::default(),
+        }
+    }
+
+    #[test]
+    fn retries_establishing_connections() {
+        let flaky = flaky(2);
+        let tries = Arc::clone(&flaky.tries);
+        let manager = RetryManager::new(flaky, Policy::new(Fixed::from_millis(1).take(3)));
+        let pool = Pool::builder()
+            .max_size(1)
+            .connection_timeout(Duration::from_secs(5))
+            .build(manager)
+            .unwrap();
+
+        let _connection = pool.get().unwrap();
+        assert_eq!(tries.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn gives_up_when_the_policy_ends() {
+        let manager = RetryManager::new(flaky(5), Policy::new(Fixed::from_millis(1).take(2)));
+
+        let error = manager.connect().unwrap_err();
+
+        assert!(matches!(error, Error::Operation { tries: 3, .. }));
+        assert_eq!(
+            error.to_string(),
+            "gave up after 3 attempts over 2.0ms of delays (last error: connection refused)"
+        );
+    }
+}
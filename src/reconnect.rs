@@ -0,0 +1,328 @@
+use std::{
+    fmt,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    clock::{Clock, SystemClock},
+    Error,
+};
+
+type ConnectHook = Arc<dyn Fn() + Send + Sync>;
+type DisconnectHook = Arc<dyn Fn(Duration) + Send + Sync>;
+
+/// Supervises a long-lived connection, such as a WebSocket, reconnecting it with escalating delays
+/// when it drops, and starting the schedule over once a connection has stayed up long enough.
+///
+/// Rapid reconnects that fail again straight away go further down the delay strategy, while a
+/// connection that stays up for `stable_after` is taken as a sign of recovery, so the next drop
+/// starts again from the first delay. Whether or not it stayed up, the strategy ending means the
+/// supervisor gives up.
+///
+/// `run` drives a whole supervision loop. A loop of one's own can report to `connected`,
+/// `disconnected` and `failed` instead, and wait the delays they return.
+///
+/// ```rust
+/// # use std::time::Duration;
+/// # use retry::delay::NoDelay;
+/// use retry::Reconnector;
+///
+/// let mut sessions = 0;
+/// let mut reconnector = Reconnector::new(NoDelay.take(3), Duration::from_secs(60))
+///     .on_disconnect(|uptime| println!("connection dropped after {:?}", uptime));
+///
+/// let result = reconnector.run(
+///     || Ok::<_, &str>("socket"),
+///     |_socket| {
+///         sessions += 1;
+///         if sessions < 3 { Err("connection reset") } else { Ok("closed by server") }
+///     },
+/// );
+///
+/// assert_eq!(result, Ok("closed by server"));
+/// ```
+pub struct Reconnector<D>
+where
+    D: IntoIterator<Item = Duration>,
+{
+    strategy: D,
+    delays: D::IntoIter,
+    stable_after: Duration,
+    connected_at: Option<Instant>,
+    tries: u64,
+    total_delay: Duration,
+    on_connect: Option<ConnectHook>,
+    on_disconnect: Option<DisconnectHook>,
+    clock: Option<Arc<dyn Clock>>,
+}
+
+impl<D> Reconnector<D>
+where
+    D: IntoIterator<Item = Duration> + Clone,
+{
+    /// Create a supervisor that reconnects according to `strategy`, and starts it over after a
+    /// connection has stayed up for `stable_after`.
+    pub fn new(strategy: D, stable_after: Duration) -> Self {
+        Reconnector {
+            delays: strategy.clone().into_iter(),
+            strategy,
+            stable_after,
+            connected_at: None,
+            tries: 0,
+            total_delay: Duration::default(),
+            on_connect: None,
+            on_disconnect: None,
+            clock: None,
+        }
+    }
+
+    /// Call `hook` every time a connection is established.
+    pub fn on_connect<F>(mut self, hook: F) -> Self
+    where
+        F: Fn() + Send + Sync + 'static,
+    {
+        self.on_connect = Some(Arc::new(hook));
+        self
+    }
+
+    /// Call `hook` with the uptime of the connection every time one drops.
+    pub fn on_disconnect<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(Duration) + Send + Sync + 'static,
+    {
+        self.on_disconnect = Some(Arc::new(hook));
+        self
+    }
+
+    /// Measure the uptime of connections with the given clock instead of the system clock.
+    pub fn with_clock<C>(mut self, clock: C) -> Self
+    where
+        C: Clock + 'static,
+    {
+        self.clock = Some(Arc::new(clock));
+        self
+    }
+
+    fn now(&self) -> Instant {
+        match self.clock {
+            Some(ref clock) => clock.now(),
+            None => SystemClock.now(),
+        }
+    }
+
+    /// Record that a connection was established.
+    pub fn connected(&mut self) {
+        self.tries += 1;
+        self.connected_at = Some(self.now());
+        if let Some(ref hook) = self.on_connect {
+            hook();
+        }
+    }
+
+    /// Record that the connection dropped, and return how long to wait before reconnecting, or
+    /// `None` if the strategy has ended. If the connection stayed up for the stability threshold,
+    /// the strategy starts over first.
+    pub fn disconnected(&mut self) -> Option<Duration> {
+        let uptime = match self.connected_at.take() {
+            Some(connected_at) => self.now().saturating_duration_since(connected_at),
+            None => Duration::default(),
+        };
+        if let Some(ref hook) = self.on_disconnect {
+            hook(uptime);
+        }
+        if uptime >= self.stable_after {
+            self.reset();
+        }
+        self.next_delay()
+    }
+
+    /// Record that connecting failed, and return how long to wait before trying again, or `None`
+    /// if the strategy has ended.
+    pub fn failed(&mut self) -> Option<Duration> {
+        self.tries += 1;
+        self.next_delay()
+    }
+
+    /// Start the strategy over, as if the supervisor had just been created.
+    pub fn reset(&mut self) {
+        self.delays = self.strategy.clone().into_iter();
+        self.tries = 0;
+        self.total_delay = Duration::default();
+    }
+
+    fn next_delay(&mut self) -> Option<Duration> {
+        let delay = self.delays.next()?;
+        self.total_delay += delay;
+        Some(delay)
+    }
+
+    fn give_up<E>(&self, error: E) -> Error<E> {
+        Error::Operation {
+            error,
+            total_delay: self.total_delay,
+            tries: self.tries,
+        }
+    }
+
+    /// Connect with `connect` and hand every connection to `serve`, reconnecting when connecting
+    /// fails or `serve` returns an error, until `serve` returns a value or the strategy ends.
+    ///
+    /// The error returned when the strategy ends counts the connection attempts and delays since
+    /// the strategy last started over.
+    pub fn run<C, R, E, F, S>(&mut self, mut connect: F, mut serve: S) -> Result<R, Error<E>>
+    where
+        F: FnMut() -> Result<C, E>,
+        S: FnMut(C) -> Result<R, E>,
+    {
+        loop {
+            let (error, delay) = match connect() {
+                Ok(connection) => {
+                    self.connected();
+                    match serve(connection) {
+                        Ok(value) => {
+                            self.connected_at = None;
+                            return Ok(value);
+                        }
+                        Err(error) => (error, self.disconnected()),
+                    }
+                }
+                Err(error) => (error, self.failed()),
+            };
+            match delay {
+                Some(delay) => std::thread::sleep(delay),
+                None => return Err(self.give_up(error)),
+            }
+        }
+    }
+
+    /// Connect asynchronously with `connect` and hand every connection to `serve`, reconnecting
+    /// when connecting fails or `serve` returns an error, until `serve` returns a value or the
+    /// strategy ends. Delays are awaited with the `DefaultSleeper`.
+    ///
+    /// This method is enabled with the `"asynchronous"` feature.
+    #[cfg(feature = "asynchronous")]
+    pub async fn run_async<C, R, E, F, FF, S, SF>(
+        &mut self,
+        mut connect: F,
+        mut serve: S,
+    ) -> Result<R, Error<E>>
+    where
+        F: FnMut() -> FF,
+        FF: std::future::Future<Output = Result<C, E>>,
+        S: FnMut(C) -> SF,
+        SF: std::future::Future<Output = Result<R, E>>,
+    {
+        use crate::asynchronous::{AsyncSleeper, DefaultSleeper};
+
+        loop {
+            let (error, delay) = match connect().await {
+                Ok(connection) => {
+                    self.connected();
+                    match serve(connection).await {
+                        Ok(value) => {
+                            self.connected_at = None;
+                            return Ok(value);
+                        }
+                        Err(error) => (error, self.disconnected()),
+                    }
+                }
+                Err(error) => (error, self.failed()),
+            };
+            match delay {
+                Some(delay) => DefaultSleeper::default().sleep(delay).await,
+                None => return Err(self.give_up(error)),
+            }
+        }
+    }
+}
+
+impl<D> fmt::Debug for Reconnector<D>
+where
+    D: IntoIterator<Item = Duration> + fmt::Debug,
+{
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter
+            .debug_struct("Reconnector")
+            .field("strategy", &self.strategy)
+            .field("stable_after", &self.stable_after)
+            .field("connected", &self.connected_at.is_some())
+            .field("tries", &self.tries)
+            .field("total_delay", &self.total_delay)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        cell::RefCell,
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use super::Reconnector;
+    use crate::{clock::MockClock, delay::Exponential, Error};
+
+    #[test]
+    fn resets_the_backoff_after_a_stable_connection() {
+        let clock = MockClock::new();
+        let uptimes = Arc::new(Mutex::new(Vec::new()));
+        let recorder = Arc::clone(&uptimes);
+        let mut reconnector =
+            Reconnector::new(Exponential::from_millis(2).take(3), Duration::from_secs(60))
+                .with_clock(clock.clone())
+                .on_disconnect(move |uptime| recorder.lock().unwrap().push(uptime));
+
+        reconnector.connected();
+        assert_eq!(reconnector.disconnected(), Some(Duration::from_millis(2)));
+        assert_eq!(reconnector.failed(), Some(Duration::from_millis(4)));
+        reconnector.connected();
+        clock.advance(Duration::from_secs(5));
+        assert_eq!(reconnector.disconnected(), Some(Duration::from_millis(8)));
+
+        reconnector.connected();
+        clock.advance(Duration::from_secs(60));
+        assert_eq!(reconnector.disconnected(), Some(Duration::from_millis(2)));
+        assert_eq!(
+            *uptimes.lock().unwrap(),
+            [
+                Duration::default(),
+                Duration::from_secs(5),
+                Duration::from_secs(60)
+            ]
+        );
+    }
+
+    #[test]
+    fn gives_up_when_the_strategy_ends() {
+        let events = RefCell::new(Vec::new());
+        let mut reconnector =
+            Reconnector::new(Exponential::from_millis(1).take(2), Duration::from_secs(60));
+
+        let result = reconnector.run(
+            || {
+                events.borrow_mut().push("connect");
+                if events.borrow().len() == 1 {
+                    Ok(())
+                } else {
+                    Err("refused")
+                }
+            },
+            |()| {
+                events.borrow_mut().push("serve");
+                Err::<(), _>("reset")
+            },
+        );
+
+        assert_eq!(
+            result,
+            Err(Error::Operation {
+                error: "refused",
+                total_delay: Duration::from_millis(2),
+                tries: 3,
+            })
+        );
+        assert_eq!(*events.borrow(), ["connect", "serve", "connect", "connect"]);
+    }
+}
@@ -0,0 +1,535 @@
+//! An `AsyncRead` and `AsyncWrite` wrapper that dials its connection again whenever it fails. This
+//! module is enabled with the `"tokio"` feature.
+//!
+//! ```
+//! # use retry::delay::Exponential;
+//! use retry::reconnecting::{ReconnectEvent, ReconnectingStream};
+//! use tokio::{io::AsyncWriteExt, net::TcpStream};
+//!
+//! # #[tokio::main] async fn main() -> std::io::Result<()> {
+//! # let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+//! # let address = listener.local_addr()?;
+//! let mut stream = ReconnectingStream::new(
+//!     move || TcpStream::connect(address),
+//!     Exponential::from_millis(10).take(5),
+//! )
+//! .on_event(|event| {
+//!     if let ReconnectEvent::Retrying { delay, error, .. } = event {
+//!         eprintln!("reconnecting in {:?}: {}", delay, error);
+//!     }
+//! });
+//!
+//! stream.write_all(b"PING\r\n").await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{
+    fmt,
+    future::Future,
+    io,
+    pin::Pin,
+    sync::Arc,
+    task::{ready, Context, Poll},
+    time::Duration,
+};
+
+use tokio::{
+    io::{AsyncRead, AsyncWrite, ReadBuf},
+    time::{self, Sleep},
+};
+
+type EventHook = Arc<dyn Fn(ReconnectEvent<'_>) + Send + Sync>;
+
+/// What happened to the connection of a `ReconnectingStream`, as passed to its `on_event` hook.
+#[derive(Debug)]
+pub enum ReconnectEvent<'a> {
+    /// A connection was established, after the given number of attempts to dial it.
+    Connected {
+        /// The number of attempts, starting at 1.
+        attempts: u64,
+    },
+    /// Reading from or writing to the connection failed, so it was dropped to be dialed again.
+    Disconnected(&'a io::Error),
+    /// Dialing failed, or the connection it dialed failed before a read or write on it succeeded,
+    /// and a connection will be dialed again after `delay`.
+    Retrying {
+        /// The number of the attempt that failed.
+        attempt: u64,
+        /// The delay before the next attempt.
+        delay: Duration,
+        /// Why the attempt failed.
+        error: &'a io::Error,
+    },
+    /// Dialing failed and the strategy has ended, so the error is returned to the caller.
+    GaveUp {
+        /// The number of attempts made.
+        attempts: u64,
+        /// Why the last attempt failed.
+        error: &'a io::Error,
+    },
+}
+
+/// A stream that dials its connection with a factory, and dials it again with the delays of a
+/// strategy whenever reading from, writing to or flushing it fails.
+///
+/// The first connection is dialed when the stream is first used. After a failure, the failed
+/// connection is dropped, a new one is dialed after the next delay of the strategy, and the read
+/// or write that failed is made again on it, so that retrying is transparent to the caller.
+///
+/// A round of dialing lasts until a read or write on a new connection succeeds, so a connection
+/// that fails before then counts as a failed attempt, and a peer that accepts connections only to
+/// drop them makes the stream give up. Once the strategy, which is cloned for every round, ends,
+/// the error of the last attempt is returned, and the next read or write starts a new round.
+///
+/// Data written to a connection that failed may or may not have reached the other end, and data
+/// it buffered is lost, so this suits protocols that can resume from where they were, such as
+/// ones whose messages are idempotent. The end of a stream is not a failure: a read that returns
+/// no bytes still means that the other end closed the connection.
+pub struct ReconnectingStream<S, F, Fut, P>
+where
+    P: IntoIterator<Item = Duration>,
+{
+    connect: F,
+    policy: P,
+    state: State<S, Fut, P::IntoIter>,
+    on_event: Option<EventHook>,
+}
+
+enum State<S, Fut, I> {
+    Disconnected,
+    Connected {
+        stream: S,
+        /// The round of dialing that made the connection, until a read or write on it succeeds.
+        round: Option<(I, u64)>,
+    },
+    Connecting {
+        connection: Pin<Box<Fut>>,
+        delays: I,
+        tries: u64,
+    },
+    Sleeping {
+        sleep: Pin<Box<Sleep>>,
+        delays: I,
+        tries: u64,
+    },
+}
+
+impl<S, F, Fut, P> ReconnectingStream<S, F, Fut, P>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = io::Result<S>>,
+    P: IntoIterator<Item = Duration> + Clone,
+{
+    /// Create a stream that dials its connections with `connect`, retrying according to `policy`.
+    pub fn new(connect: F, policy: P) -> Self {
+        ReconnectingStream {
+            connect,
+            policy,
+            state: State::Disconnected,
+            on_event: None,
+        }
+    }
+
+    /// Call `hook` every time the connection is established, fails or is dialed again.
+    pub fn on_event<H>(mut self, hook: H) -> Self
+    where
+        H: Fn(ReconnectEvent<'_>) + Send + Sync + 'static,
+    {
+        self.on_event = Some(Arc::new(hook));
+        self
+    }
+
+    /// Get a reference to the current connection, if the stream is connected.
+    pub fn get_ref(&self) -> Option<&S> {
+        match self.state {
+            State::Connected { ref stream, .. } => Some(stream),
+            _ => None,
+        }
+    }
+
+    /// Whether the stream is connected.
+    pub fn is_connected(&self) -> bool {
+        self.get_ref().is_some()
+    }
+
+    fn emit(&self, event: ReconnectEvent<'_>) {
+        if let Some(ref hook) = self.on_event {
+            hook(event);
+        }
+    }
+
+    fn dial(&mut self, delays: P::IntoIter, tries: u64) {
+        self.state = State::Connecting {
+            connection: Box::pin((self.connect)()),
+            delays,
+            tries,
+        };
+    }
+
+    /// Wait the next delay of the round before dialing again after attempt `tries` failed with
+    /// `error`, or return the error if the round has ended.
+    fn retry(
+        &mut self,
+        mut delays: P::IntoIter,
+        tries: u64,
+        error: io::Error,
+    ) -> Option<io::Error> {
+        match delays.next() {
+            Some(delay) => {
+                self.emit(ReconnectEvent::Retrying {
+                    attempt: tries,
+                    delay,
+                    error: &error,
+                });
+                self.state = State::Sleeping {
+                    sleep: Box::pin(time::sleep(delay)),
+                    delays,
+                    tries,
+                };
+                None
+            }
+            None => {
+                self.emit(ReconnectEvent::GaveUp {
+                    attempts: tries,
+                    error: &error,
+                });
+                self.state = State::Disconnected;
+                Some(error)
+            }
+        }
+    }
+
+    /// Run `operation` on the connection, dialing it first if needed, and again whenever the
+    /// operation fails.
+    fn poll_io<T, O>(&mut self, cx: &mut Context<'_>, mut operation: O) -> Poll<io::Result<T>>
+    where
+        S: Unpin,
+        O: FnMut(Pin<&mut S>, &mut Context<'_>) -> Poll<io::Result<T>>,
+    {
+        loop {
+            match self.state {
+                State::Disconnected => self.dial(self.policy.clone().into_iter(), 1),
+                State::Connected {
+                    ref mut stream,
+                    ref mut round,
+                } => match operation(Pin::new(stream), cx) {
+                    Poll::Ready(Err(error)) => {
+                        let (delays, tries) = round
+                            .take()
+                            .unwrap_or_else(|| (self.policy.clone().into_iter(), 1));
+                        self.emit(ReconnectEvent::Disconnected(&error));
+                        if let Some(error) = self.retry(delays, tries, error) {
+                            return Poll::Ready(Err(error));
+                        }
+                    }
+                    Poll::Ready(Ok(value)) => {
+                        *round = None;
+                        return Poll::Ready(Ok(value));
+                    }
+                    Poll::Pending => return Poll::Pending,
+                },
+                State::Connecting {
+                    ref mut connection, ..
+                } => {
+                    let result = ready!(connection.as_mut().poll(cx));
+                    let (delays, tries) =
+                        match std::mem::replace(&mut self.state, State::Disconnected) {
+                            State::Connecting { delays, tries, .. } => (delays, tries),
+                            _ => unreachable!("the state was just matched"),
+                        };
+                    match result {
+                        Ok(stream) => {
+                            self.emit(ReconnectEvent::Connected { attempts: tries });
+                            self.state = State::Connected {
+                                stream,
+                                round: Some((delays, tries)),
+                            };
+                        }
+                        Err(error) => {
+                            if let Some(error) = self.retry(delays, tries, error) {
+                                return Poll::Ready(Err(error));
+                            }
+                        }
+                    }
+                }
+                State::Sleeping { ref mut sleep, .. } => {
+                    ready!(sleep.as_mut().poll(cx));
+                    if let State::Sleeping { delays, tries, .. } =
+                        std::mem::replace(&mut self.state, State::Disconnected)
+                    {
+                        self.dial(delays, tries + 1);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl<S, F, Fut, P> AsyncRead for ReconnectingStream<S, F, Fut, P>
+where
+    S: AsyncRead + Unpin,
+    F: FnMut() -> Fut,
+    Fut: Future<Output = io::Result<S>>,
+    P: IntoIterator<Item = Duration> + Clone,
+{
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        self.get_mut()
+            .poll_io(cx, |stream, cx| stream.poll_read(cx, buf))
+    }
+}
+
+impl<S, F, Fut, P> AsyncWrite for ReconnectingStream<S, F, Fut, P>
+where
+    S: AsyncWrite + Unpin,
+    F: FnMut() -> Fut,
+    Fut: Future<Output = io::Result<S>>,
+    P: IntoIterator<Item = Duration> + Clone,
+{
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.get_mut()
+            .poll_io(cx, |stream, cx| stream.poll_write(cx, buf))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.get_mut()
+            .poll_io(cx, |stream, cx| stream.poll_flush(cx))
+    }
+
+    /// Shut down the current connection, if any, without dialing one to shut down.
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut().state {
+            State::Connected { ref mut stream, .. } => Pin::new(stream).poll_shutdown(cx),
+            _ => Poll::Ready(Ok(())),
+        }
+    }
+}
+
+// The connection being dialed and the sleep are boxed, and the connection is required to be
+// `Unpin` wherever it is polled.
+impl<S, F, Fut, P> Unpin for ReconnectingStream<S, F, Fut, P>
+where
+    S: Unpin,
+    P: IntoIterator<Item = Duration>,
+{
+}
+
+impl<S, F, Fut, P> fmt::Debug for ReconnectingStream<S, F, Fut, P>
+where
+    S: fmt::Debug,
+    P: IntoIterator<Item = Duration> + fmt::Debug,
+{
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut debug = formatter.debug_struct("ReconnectingStream");
+        debug.field("policy", &self.policy);
+        match self.state {
+            State::Disconnected => debug.field("state", &"disconnected"),
+            State::Connected { ref stream, .. } => debug.field("connection", stream),
+            State::Connecting { tries, .. } | State::Sleeping { tries, .. } => {
+                debug.field("state", &"connecting").field("tries", &tries)
+            }
+        };
+        debug.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io,
+        pin::Pin,
+        sync::{Arc, Mutex},
+        task::{Context, Poll},
+    };
+
+    use futures::future;
+    use tokio::{
+        io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadBuf},
+        net::{TcpListener, TcpStream},
+    };
+
+    use super::{ReconnectEvent, ReconnectingStream};
+    use crate::delay::{Fixed, NoDelay};
+
+    /// A connection that serves `data`, or fails every read and write if it is broken.
+    #[derive(Debug)]
+    struct Connection {
+        data: &'static [u8],
+        broken: bool,
+        written: Arc<Mutex<Vec<u8>>>,
+    }
+
+    impl Connection {
+        fn check(&self) -> io::Result<()> {
+            if self.broken {
+                Err(io::ErrorKind::ConnectionReset.into())
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    impl AsyncRead for Connection {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            _: &mut Context<'_>,
+            buf: &mut ReadBuf<'_>,
+        ) -> Poll<io::Result<()>> {
+            self.check()?;
+            buf.put_slice(self.data);
+            self.data = &[];
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    impl AsyncWrite for Connection {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            _: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            self.check()?;
+            self.written.lock().unwrap().extend_from_slice(buf);
+            Poll::Ready(Ok(buf.len()))
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(self.check())
+        }
+
+        fn poll_shutdown(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    fn recorder() -> (
+        Arc<Mutex<Vec<String>>>,
+        impl Fn(ReconnectEvent<'_>) + Send + Sync + 'static,
+    ) {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let recorded = Arc::clone(&events);
+        (events, move |event: ReconnectEvent<'_>| {
+            let event = match event {
+                ReconnectEvent::Connected { attempts } => format!("connected after {}", attempts),
+                ReconnectEvent::Disconnected(error) => format!("disconnected: {}", error),
+                ReconnectEvent::Retrying { attempt, delay, .. } => {
+                    format!("attempt {} failed, retrying in {:?}", attempt, delay)
+                }
+                ReconnectEvent::GaveUp { attempts, .. } => format!("gave up after {}", attempts),
+            };
+            recorded.lock().unwrap().push(event);
+        })
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn reconnects_transparently() {
+        let written = Arc::new(Mutex::new(Vec::new()));
+        let mut dials = 0;
+        let sink = Arc::clone(&written);
+        let (events, hook) = recorder();
+
+        let mut stream = ReconnectingStream::new(
+            move || {
+                dials += 1;
+                future::ready(match dials {
+                    2 => Err(io::ErrorKind::ConnectionRefused.into()),
+                    _ => Ok(Connection {
+                        data: b"hello",
+                        broken: dials == 1,
+                        written: Arc::clone(&sink),
+                    }),
+                })
+            },
+            Fixed::from_millis(10).take(3),
+        )
+        .on_event(hook);
+
+        let mut read = [0; 5];
+        stream.read_exact(&mut read).await.unwrap();
+        stream.write_all(b"world").await.unwrap();
+        assert_eq!(&read, b"hello");
+        assert_eq!(*written.lock().unwrap(), b"world");
+        assert!(stream.is_connected());
+        assert_eq!(
+            *events.lock().unwrap(),
+            [
+                "connected after 1",
+                "disconnected: connection reset",
+                "attempt 1 failed, retrying in 10ms",
+                "attempt 2 failed, retrying in 10ms",
+                "connected after 3",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn gives_up_when_the_strategy_ends() {
+        let (events, hook) = recorder();
+        let mut stream = ReconnectingStream::new(
+            || {
+                future::ready(Err::<Connection, _>(
+                    io::ErrorKind::ConnectionRefused.into(),
+                ))
+            },
+            NoDelay.take(2),
+        )
+        .on_event(hook);
+
+        let error = stream.write_all(b"lost").await.unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::ConnectionRefused);
+        assert!(!stream.is_connected());
+        assert_eq!(events.lock().unwrap().last().unwrap(), "gave up after 3");
+
+        // The next write starts a new round of dialing.
+        assert!(stream.flush().await.is_err());
+        assert_eq!(events.lock().unwrap().len(), 6);
+        assert!(stream.shutdown().await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn gives_up_on_peers_that_drop_every_connection() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let accepted = tokio::spawn(async move {
+            let mut accepted = 0;
+            while let Ok((connection, _)) = listener.accept().await {
+                // Reset the connection instead of closing it, so that reads fail.
+                connection.set_zero_linger().unwrap();
+                drop(connection);
+                accepted += 1;
+                if accepted == 4 {
+                    break;
+                }
+            }
+            accepted
+        });
+
+        let (events, hook) = recorder();
+        let mut stream = ReconnectingStream::new(
+            move || TcpStream::connect(address),
+            Fixed::from_millis(1).take(3),
+        )
+        .on_event(hook);
+
+        let error = stream.read(&mut [0; 16]).await.unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::ConnectionReset);
+        assert_eq!(accepted.await.unwrap(), 4);
+        let events = events.lock().unwrap();
+        assert_eq!(events.last().unwrap(), "gave up after 4");
+        assert_eq!(
+            events
+                .iter()
+                .filter(|event| event.starts_with("connected"))
+                .count(),
+            4
+        );
+    }
+}
@@ -0,0 +1,171 @@
+use std::{borrow::Cow, collections::HashMap, iter::FromIterator};
+
+use crate::{
+    config::{ConfigError, RetryPolicyConfig},
+    delay::Backoff,
+    Policy,
+};
+
+/// Policies looked up by name, for applications that retry many kinds of operations.
+///
+/// An application fills the registry once, for example from configuration, and shares it, usually
+/// in an `Arc`. Code that makes the calls then only needs to know the name of its policy:
+///
+/// ```rust
+/// # use std::sync::Arc;
+/// use retry::{config::RetryPolicyConfig, PolicyRegistry};
+///
+/// let registry = PolicyRegistry::from_configs(vec![
+///     ("db", "exponential(10ms, x2, attempts=3)".parse::<RetryPolicyConfig>().unwrap()),
+///     ("cache", "none(attempts=2)".parse().unwrap()),
+/// ])
+/// .unwrap();
+/// let registry = Arc::new(registry);
+///
+/// let db = registry.get("db").unwrap();
+/// assert_eq!(db.name(), Some("db"));
+/// assert_eq!(db.retry(|| Err::<(), _>("down")).unwrap_err().tries(), 3);
+/// ```
+///
+/// Policies that have no name when they are inserted are named after their key, so that their
+/// logs, traces and metrics can be told apart.
+#[derive(Clone, Debug)]
+pub struct PolicyRegistry<D = Backoff> {
+    policies: HashMap<Cow<'static, str>, Policy<D>>,
+}
+
+impl<D> PolicyRegistry<D> {
+    /// Create an empty registry.
+    pub fn new() -> Self {
+        PolicyRegistry {
+            policies: HashMap::new(),
+        }
+    }
+
+    /// Register a policy under the given name, returning the policy it replaces, if any.
+    pub fn insert<N>(&mut self, name: N, policy: Policy<D>) -> Option<Policy<D>>
+    where
+        N: Into<Cow<'static, str>>,
+    {
+        let name = name.into();
+        let policy = match policy.name() {
+            Some(_) => policy,
+            None => policy.with_name(name.clone()),
+        };
+        self.policies.insert(name, policy)
+    }
+
+    /// The policy registered under the given name, if any.
+    pub fn get(&self, name: &str) -> Option<&Policy<D>> {
+        self.policies.get(name)
+    }
+
+    /// The names of the registered policies, in no particular order.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.policies.keys().map(|name| name.as_ref())
+    }
+
+    /// The number of registered policies.
+    pub fn len(&self) -> usize {
+        self.policies.len()
+    }
+
+    /// Returns `true` if no policy is registered.
+    pub fn is_empty(&self) -> bool {
+        self.policies.is_empty()
+    }
+}
+
+impl PolicyRegistry<Backoff> {
+    /// Create a registry with the policies described by the given settings, keyed by name.
+    ///
+    /// Fails on the first invalid settings, naming the policy they describe.
+    pub fn from_configs<I, N>(configs: I) -> Result<Self, ConfigError>
+    where
+        I: IntoIterator<Item = (N, RetryPolicyConfig)>,
+        N: Into<Cow<'static, str>>,
+    {
+        let mut registry = PolicyRegistry::new();
+        for (name, config) in configs {
+            let name = name.into();
+            let policy = config
+                .build()
+                .map_err(|error| ConfigError::new(format!("{}: {}", name, error.message())))?;
+            registry.insert(name, policy);
+        }
+        Ok(registry)
+    }
+}
+
+impl<D> Default for PolicyRegistry<D> {
+    fn default() -> Self {
+        PolicyRegistry::new()
+    }
+}
+
+impl<D, N> FromIterator<(N, Policy<D>)> for PolicyRegistry<D>
+where
+    N: Into<Cow<'static, str>>,
+{
+    fn from_iter<I>(policies: I) -> Self
+    where
+        I: IntoIterator<Item = (N, Policy<D>)>,
+    {
+        let mut registry = PolicyRegistry::new();
+        for (name, policy) in policies {
+            registry.insert(name, policy);
+        }
+        registry
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::PolicyRegistry;
+    use crate::{
+        config::RetryPolicyConfig,
+        delay::{Backoff, Fixed},
+        Policy,
+    };
+
+    #[test]
+    fn names_policies_after_their_keys() {
+        let mut registry = PolicyRegistry::new();
+        registry.insert("payments-api", Policy::new(Fixed::from_millis(1)));
+        registry.insert(
+            String::from("db"),
+            Policy::new(Fixed::from_millis(1)).with_name("postgres"),
+        );
+
+        assert_eq!(registry.len(), 2);
+        assert_eq!(
+            registry.get("payments-api").and_then(Policy::name),
+            Some("payments-api")
+        );
+        assert_eq!(registry.get("db").and_then(Policy::name), Some("postgres"));
+        assert!(registry.get("cache").is_none());
+    }
+
+    #[test]
+    fn builds_policies_from_configs() {
+        let config = RetryPolicyConfig {
+            base: Duration::from_millis(1),
+            max_attempts: Some(0),
+            ..RetryPolicyConfig::default()
+        };
+
+        assert_eq!(
+            PolicyRegistry::from_configs(vec![("db", config)])
+                .unwrap_err()
+                .to_string(),
+            "invalid retry policy: db: max_attempts must be at least 1"
+        );
+
+        let registry: PolicyRegistry<Backoff> = vec![("cache", Policy::new(Backoff::none()))]
+            .into_iter()
+            .collect();
+        assert_eq!(registry.names().collect::<Vec<_>>(), ["cache"]);
+    }
+}
@@ -0,0 +1,133 @@
+use std::{
+    fmt::Debug,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use crate::{Error, OperationResult, Policy};
+
+/// A shared policy that can be replaced while calls are made through it, for example by a
+/// configuration watcher during an incident.
+///
+/// Every call reads the current policy once, when it starts, and keeps it until it returns; a
+/// policy stored in the meantime only applies to the calls that start afterwards. Clones share the
+/// same policy.
+///
+/// ```rust
+/// # use retry::delay::NoDelay;
+/// use retry::{Policy, ReloadablePolicy};
+///
+/// let policy = ReloadablePolicy::new(Policy::new(NoDelay).with_max_attempts(2));
+/// let watcher = policy.clone();
+///
+/// assert_eq!(policy.retry(|| Err::<(), _>("down")).unwrap_err().tries(), 2);
+/// watcher.store(Policy::new(NoDelay).with_max_attempts(5));
+/// assert_eq!(policy.retry(|| Err::<(), _>("down")).unwrap_err().tries(), 5);
+/// ```
+#[derive(Debug)]
+pub struct ReloadablePolicy<D> {
+    current: Arc<RwLock<Arc<Policy<D>>>>,
+}
+
+impl<D> ReloadablePolicy<D> {
+    /// Share the given policy.
+    pub fn new(policy: Policy<D>) -> Self {
+        ReloadablePolicy {
+            current: Arc::new(RwLock::new(Arc::new(policy))),
+        }
+    }
+
+    /// The current policy.
+    pub fn load(&self) -> Arc<Policy<D>> {
+        match self.current.read() {
+            Ok(current) => Arc::clone(&current),
+            Err(poisoned) => Arc::clone(&poisoned.into_inner()),
+        }
+    }
+
+    /// Replace the current policy, for the calls that start from now on.
+    pub fn store(&self, policy: Policy<D>) {
+        let policy = Arc::new(policy);
+        match self.current.write() {
+            Ok(mut current) => *current = policy,
+            Err(poisoned) => *poisoned.into_inner() = policy,
+        }
+    }
+}
+
+impl<D> ReloadablePolicy<D>
+where
+    D: IntoIterator<Item = Duration> + Clone,
+{
+    /// Retry the given operation synchronously according to the current policy.
+    pub fn retry<O, R, E, OR>(&self, operation: O) -> Result<R, Error<E>>
+    where
+        O: FnMut() -> OR,
+        OR: Into<OperationResult<R, E>>,
+        E: Debug,
+    {
+        self.load().retry(operation)
+    }
+
+    /// Retry the given asynchronous operation according to the current policy.
+    ///
+    /// This method is enabled with the `"asynchronous"` feature.
+    #[cfg(feature = "asynchronous")]
+    pub async fn retry_async<O, R, E, OR, F>(&self, operation: O) -> Result<R, Error<E>>
+    where
+        O: FnMut() -> F,
+        OR: Into<OperationResult<R, E>>,
+        F: std::future::Future<Output = OR>,
+        E: Debug,
+    {
+        let policy = self.load();
+        policy.retry_async(operation).await
+    }
+}
+
+impl<D> Clone for ReloadablePolicy<D> {
+    fn clone(&self) -> Self {
+        ReloadablePolicy {
+            current: Arc::clone(&self.current),
+        }
+    }
+}
+
+impl<D> From<Policy<D>> for ReloadablePolicy<D> {
+    fn from(policy: Policy<D>) -> Self {
+        ReloadablePolicy::new(policy)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ReloadablePolicy;
+    use crate::{delay::NoDelay, Policy};
+
+    #[test]
+    fn calls_keep_the_policy_they_started_with() {
+        let policy = ReloadablePolicy::new(Policy::new(NoDelay).with_max_attempts(3));
+        let watcher = policy.clone();
+        let mut attempts = 0;
+
+        let result = policy.retry(|| {
+            attempts += 1;
+            watcher.store(Policy::new(NoDelay).with_max_attempts(1));
+            Err::<(), _>("down")
+        });
+
+        assert_eq!(result.unwrap_err().tries(), 3);
+        assert_eq!(attempts, 3);
+        assert_eq!(policy.load().max_attempts(), Some(1));
+    }
+
+    #[cfg(feature = "asynchronous")]
+    #[tokio::test]
+    async fn retries_asynchronously_with_the_current_policy() {
+        let policy = ReloadablePolicy::from(Policy::new(NoDelay).with_max_attempts(2));
+
+        let result = policy.retry_async(|| async { Err::<(), _>("down") }).await;
+
+        assert_eq!(result.unwrap_err().tries(), 2);
+    }
+}
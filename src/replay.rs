@@ -0,0 +1,435 @@
+//! Recording of the calls made through a policy, to replay them deterministically in a test.
+//!
+//! A `Recorder` wraps a policy and returns, along with the result of each call, a `SessionTrace`
+//! of how the call went: the outcome of every attempt and the delay chosen before each retry. With
+//! the `"serde"` feature, traces can be serialized, for example to capture a flaky call in
+//! production and load it in a test.
+//!
+//! Traces also record how long each attempt took, and can be exported for analysis like a
+//! `Simulation`, one `ScheduleRow` per attempt.
+//!
+//! A `Replayer` plays a trace back. Its policy waits for nothing and follows the recorded delays
+//! rather than the original strategy, so the random draws of a jittered strategy are reproduced,
+//! and its operation returns the recorded outcomes:
+//!
+//! ```rust
+//! # use retry::delay::{jitter, Exponential};
+//! use retry::{
+//!     replay::{Recorder, Replayer},
+//!     OperationResult, Policy,
+//! };
+//!
+//! let recorder = Recorder::new(Policy::new(Exponential::from_millis(1).map(jitter).take(3)));
+//! let mut attempts = 0;
+//! let (result, trace) = recorder.retry(|| {
+//!     attempts += 1;
+//!     match attempts {
+//!         1 | 2 => OperationResult::<(), _>::Retry("connection reset"),
+//!         _ => OperationResult::Err("permission denied"),
+//!     }
+//! });
+//! assert!(result.is_err());
+//!
+//! let replayer = Replayer::new(trace.clone());
+//! let recorder = Recorder::new(replayer.policy());
+//! let (_, replayed) = recorder.retry(replayer.operation());
+//! assert_eq!(replayed, trace);
+//! ```
+
+use std::{
+    fmt,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use crate::{
+    clock::Sleeper,
+    listener::{AttemptOutcome, RetryListener},
+    simulation::{self, ScheduleRow},
+    CorrelationId, Error, OperationResult, Policy,
+};
+
+/// How a call made through a policy went, as recorded by a `Recorder`.
+///
+/// Traces are equal when their steps are, whatever the latencies of their attempts, which differ
+/// from one run to the next.
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SessionTrace {
+    steps: Vec<Step>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    latencies: Vec<Duration>,
+}
+
+impl PartialEq for SessionTrace {
+    fn eq(&self, other: &Self) -> bool {
+        self.steps == other.steps
+    }
+}
+
+impl Eq for SessionTrace {}
+
+impl SessionTrace {
+    /// The attempts and delays of the call, in order.
+    pub fn steps(&self) -> &[Step] {
+        &self.steps
+    }
+
+    /// The delays chosen before each retry, in order.
+    pub fn delays(&self) -> Vec<Duration> {
+        self.steps
+            .iter()
+            .filter_map(|step| match *step {
+                Step::Delay { delay } => Some(delay),
+                Step::Attempt { .. } => None,
+            })
+            .collect()
+    }
+
+    /// How long each attempt took, in order.
+    pub fn latencies(&self) -> &[Duration] {
+        &self.latencies
+    }
+
+    /// One row per attempt, with its outcome, its latency and the delay after it.
+    pub fn rows(&self) -> Vec<ScheduleRow> {
+        let mut rows: Vec<ScheduleRow> = Vec::new();
+        for step in &self.steps {
+            match *step {
+                Step::Attempt { outcome, .. } => {
+                    let attempt = rows.len() as u64 + 1;
+                    let latency = self.latencies.get(rows.len()).copied();
+                    rows.push(ScheduleRow::new(attempt, outcome.name(), latency, None));
+                }
+                Step::Delay { delay } => {
+                    if let Some(row) = rows.last_mut() {
+                        row.set_delay(delay);
+                    }
+                }
+            }
+        }
+        rows
+    }
+
+    /// The rows of the trace as CSV, with a header.
+    pub fn to_csv(&self) -> String {
+        simulation::to_csv(&self.rows())
+    }
+
+    /// The rows of the trace as a JSON array.
+    #[cfg(feature = "serde")]
+    pub fn to_json(&self) -> String {
+        simulation::to_json(&self.rows())
+    }
+}
+
+/// A step of a recorded call.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(tag = "step", rename_all = "snake_case")
+)]
+pub enum Step {
+    /// An attempt ended.
+    Attempt {
+        /// How the attempt ended.
+        outcome: Recorded,
+        /// The error of a failed attempt, formatted with `Debug`.
+        #[cfg_attr(
+            feature = "serde",
+            serde(default, skip_serializing_if = "Option::is_none")
+        )]
+        error: Option<String>,
+    },
+    /// The policy chose to wait `delay` before the next attempt.
+    Delay {
+        /// The delay before the next attempt.
+        delay: Duration,
+    },
+}
+
+/// How a recorded attempt ended, mirroring `OperationResult`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum Recorded {
+    /// The attempt succeeded.
+    Ok,
+    /// The attempt failed with an error that is retried if the schedule allows it.
+    Retry,
+    /// The attempt failed with an error that is not retried.
+    Err,
+    /// The attempt did not finish within the policy's attempt timeout.
+    TimedOut,
+}
+
+impl Recorded {
+    fn name(self) -> &'static str {
+        match self {
+            Recorded::Ok => "ok",
+            Recorded::Retry => "retry",
+            Recorded::Err => "err",
+            Recorded::TimedOut => "timed_out",
+        }
+    }
+}
+
+/// Wraps a policy to record a `SessionTrace` of every call made through it.
+#[derive(Clone, Debug)]
+pub struct Recorder<D> {
+    policy: Policy<D>,
+}
+
+impl<D> Recorder<D>
+where
+    D: IntoIterator<Item = Duration> + Clone,
+{
+    /// Record the calls made through `policy`.
+    pub fn new(policy: Policy<D>) -> Self {
+        Recorder { policy }
+    }
+
+    /// Retry the given operation synchronously according to the policy, returning its result
+    /// together with the trace of the call.
+    pub fn retry<O, R, E, OR>(&self, operation: O) -> (Result<R, Error<E>>, SessionTrace)
+    where
+        O: FnMut() -> OR,
+        OR: Into<OperationResult<R, E>>,
+        E: fmt::Debug,
+    {
+        let trace = Arc::new(Mutex::new(SessionTrace::default()));
+        let policy = self
+            .policy
+            .clone()
+            .with_listener(StepListener(Arc::clone(&trace)));
+
+        let result = policy.retry(operation);
+        let trace = std::mem::take(
+            &mut *trace
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner()),
+        );
+        (result, trace)
+    }
+}
+
+/// Records the steps of a single call.
+struct StepListener(Arc<Mutex<SessionTrace>>);
+
+impl StepListener {
+    fn push(&self, step: Step) {
+        self.trace().steps.push(step);
+    }
+
+    fn trace(&self) -> std::sync::MutexGuard<'_, SessionTrace> {
+        self.0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl RetryListener for StepListener {
+    fn on_attempt_end(
+        &self,
+        _: &CorrelationId,
+        _: u64,
+        outcome: AttemptOutcome<'_>,
+        latency: Duration,
+    ) {
+        let (outcome, error) = match outcome {
+            AttemptOutcome::Ok => (Recorded::Ok, None),
+            AttemptOutcome::Retry(error) => (Recorded::Retry, Some(format!("{:?}", error))),
+            AttemptOutcome::Err(error) => (Recorded::Err, Some(format!("{:?}", error))),
+            AttemptOutcome::TimedOut(timeout) => (
+                Recorded::TimedOut,
+                Some(format!("timed out after {:?}", timeout)),
+            ),
+        };
+        let mut trace = self.trace();
+        trace.steps.push(Step::Attempt { outcome, error });
+        trace.latencies.push(latency);
+    }
+
+    fn on_backoff(&self, _: &CorrelationId, delay: Duration) {
+        self.push(Step::Delay { delay });
+    }
+}
+
+/// Plays a `SessionTrace` back.
+#[derive(Clone, Debug)]
+pub struct Replayer {
+    trace: SessionTrace,
+}
+
+impl Replayer {
+    /// Play `trace` back.
+    pub fn new(trace: SessionTrace) -> Self {
+        Replayer { trace }
+    }
+
+    /// The trace being played back.
+    pub fn trace(&self) -> &SessionTrace {
+        &self.trace
+    }
+
+    /// A policy whose strategy is the recorded delays, and which does not actually wait them.
+    pub fn policy(&self) -> Policy<Vec<Duration>> {
+        Policy::new(self.trace.delays()).with_sleeper(NoSleep)
+    }
+
+    /// An operation that returns the recorded outcome of each attempt in turn, with the recorded
+    /// errors. Attempts beyond the trace fail with an error that is not retried.
+    ///
+    /// An attempt that timed out is replayed as a retried error, since the synchronous executor
+    /// has no attempt timeout.
+    pub fn operation(&self) -> impl FnMut() -> OperationResult<(), ReplayedError> {
+        let mut attempts = self
+            .trace
+            .steps
+            .clone()
+            .into_iter()
+            .filter_map(|step| match step {
+                Step::Attempt { outcome, error } => Some((outcome, error.unwrap_or_default())),
+                Step::Delay { .. } => None,
+            });
+        move || match attempts.next() {
+            Some((Recorded::Ok, _)) => OperationResult::Ok(()),
+            Some((Recorded::Retry, error)) | Some((Recorded::TimedOut, error)) => {
+                OperationResult::Retry(ReplayedError(error))
+            }
+            Some((Recorded::Err, error)) => OperationResult::Err(ReplayedError(error)),
+            None => OperationResult::Err(ReplayedError("the trace has no more attempts".into())),
+        }
+    }
+}
+
+/// A recorded error, played back by a `Replayer`.
+///
+/// Both `Debug` and `Display` write the error as it was recorded, so that recording a replay gives
+/// the same trace as the original call.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct ReplayedError(String);
+
+impl ReplayedError {
+    /// The recorded error, as formatted with `Debug` by the original call.
+    pub fn message(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Debug for ReplayedError {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str(&self.0)
+    }
+}
+
+impl fmt::Display for ReplayedError {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for ReplayedError {}
+
+/// Returns at once instead of waiting, for replays.
+#[derive(Debug)]
+struct NoSleep;
+
+impl Sleeper for NoSleep {
+    fn sleep(&self, _: Duration) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{Recorded, Recorder, Replayer, Step};
+    use crate::{delay::Fixed, Error, Policy};
+
+    #[test]
+    fn records_and_replays_a_call() {
+        let recorder = Recorder::new(Policy::new(Fixed::from_millis(1).take(2)));
+        let mut attempts = 0;
+        let (result, trace) = recorder.retry(|| {
+            attempts += 1;
+            if attempts < 2 {
+                Err("busy")
+            } else {
+                Ok(attempts)
+            }
+        });
+
+        assert_eq!(result, Ok(2));
+        assert_eq!(
+            trace.steps(),
+            [
+                Step::Attempt {
+                    outcome: Recorded::Retry,
+                    error: Some("\"busy\"".to_owned()),
+                },
+                Step::Delay {
+                    delay: Duration::from_millis(1),
+                },
+                Step::Attempt {
+                    outcome: Recorded::Ok,
+                    error: None,
+                },
+            ]
+        );
+
+        let replayer = Replayer::new(trace);
+        let mut operation = replayer.operation();
+        assert_eq!(replayer.policy().retry(&mut operation), Ok(()));
+        let error = replayer.policy().retry(operation).unwrap_err();
+        assert!(matches!(error, Error::Operation { tries: 1, .. }));
+        assert_eq!(
+            error.into_last_error().unwrap().message(),
+            "the trace has no more attempts"
+        );
+    }
+
+    #[test]
+    fn exports_the_rows_of_a_trace() {
+        let recorder = Recorder::new(Policy::new(Fixed::from_millis(2).take(1)));
+        let (_, trace) = recorder.retry(|| {
+            std::thread::sleep(Duration::from_millis(1));
+            Err::<(), _>("down")
+        });
+
+        let rows = trace.rows();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(trace.latencies().len(), 2);
+        assert_eq!(rows[0].outcome(), "retry");
+        assert_eq!(rows[0].delay(), Some(Duration::from_millis(2)));
+        assert_eq!(rows[1].delay(), None);
+        assert!(rows[1].latency() >= Some(Duration::from_millis(1)));
+
+        let csv = trace.to_csv();
+        let lines: Vec<_> = csv.lines().collect();
+        assert_eq!(lines[0], "attempt,outcome,latency_ms,delay_ms");
+        assert!(lines[1].starts_with("1,retry,") && lines[1].ends_with(",2"));
+        assert!(lines[2].starts_with("2,retry,") && lines[2].ends_with(','));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serializes_traces() {
+        let recorder = Recorder::new(Policy::new(Fixed::from_millis(3).take(1)));
+        let (_, trace) = recorder.retry(|| Err::<(), _>("down"));
+
+        let json = serde_json::to_string(&trace).unwrap();
+        assert!(json.starts_with(concat!(
+            r#"{"steps":[{"step":"attempt","outcome":"retry","error":"\"down\""},"#,
+            r#"{"step":"delay","delay":{"secs":0,"nanos":3000000}},"#,
+            r#"{"step":"attempt","outcome":"retry","error":"\"down\""}],"latencies":[{"#
+        )));
+        assert_eq!(
+            serde_json::from_str::<super::SessionTrace>(&json).unwrap(),
+            trace
+        );
+    }
+}
@@ -0,0 +1,274 @@
+//! `reqwest-middleware` integration that retries idempotent requests according to a retry
+//! policy. This module is enabled with the `"reqwest"` feature.
+//!
+//! ```
+//! # use retry::delay::{jitter, Exponential};
+//! # use retry::reqwest::RetryMiddleware;
+//! # use retry::Policy;
+//! # use reqwest_middleware::{reqwest::Client, ClientBuilder};
+//! let policy = Policy::new(Exponential::from_millis(100).map(jitter).take(3));
+//! let client = ClientBuilder::new(Client::new())
+//!     .with(RetryMiddleware::new(policy))
+//!     .build();
+//! # let _ = client;
+//! ```
+//!
+//! Only requests with an idempotent method (`GET`, `HEAD`, `PUT`, `DELETE`, `OPTIONS` and
+//! `TRACE`) and a body that can be cloned are retried; all other requests are sent exactly once.
+//! Responses with a status of 408, 429 or 5xx (except 501 and 505) and connection or timeout errors
+//! are retried. When a retried response carries a `Retry-After` header, the delay it asks for is
+//! used instead of the strategy's delay for that retry, limited to
+//! [`with_max_retry_after`](RetryMiddleware::with_max_retry_after) and to the policy's
+//! `max_delay`.
+//!
+//! When the policy gives up, the last response or error is returned unchanged. When it refuses to
+//! make an attempt at all, for example because its circuit breaker is open, the refusal is
+//! returned as a `reqwest_middleware::Error::Middleware`.
+
+use std::{
+    error::Error as StdError,
+    fmt::{Display, Formatter, Result as FmtResult},
+    sync::Mutex,
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use http::Extensions;
+use reqwest_middleware::{
+    reqwest::{Request, Response},
+    Error, Middleware, Next, Result,
+};
+
+use crate::{
+    asynchronous::Lent,
+    classified::Outcome,
+    http::{is_idempotent, is_retryable_status, retry_after, DEFAULT_MAX_RETRY_AFTER},
+    CorrelationId, Error as RetryError, Policy,
+};
+
+/// A `reqwest_middleware::Middleware` that retries requests.
+///
+/// The policy's delays are cloned for every request, so each request gets its own independent
+/// schedule, while its budgets, circuit breaker and bulkhead are shared by all requests.
+#[derive(Clone, Debug)]
+pub struct RetryMiddleware<D> {
+    policy: Policy<D>,
+    max_retry_after: Duration,
+}
+
+impl<D> RetryMiddleware<D> {
+    /// Create a new `RetryMiddleware` that retries requests using the given policy.
+    pub fn new(policy: Policy<D>) -> Self {
+        RetryMiddleware {
+            policy,
+            max_retry_after: DEFAULT_MAX_RETRY_AFTER,
+        }
+    }
+
+    /// Limit the delay a `Retry-After` header can ask for, which defaults to 60 seconds.
+    pub fn with_max_retry_after(mut self, max_retry_after: Duration) -> Self {
+        self.max_retry_after = max_retry_after;
+        self
+    }
+}
+
+#[async_trait]
+impl<D> Middleware for RetryMiddleware<D>
+where
+    D: IntoIterator<Item = Duration> + Clone + Send + Sync + 'static,
+    D::IntoIter: Send,
+{
+    async fn handle(
+        &self,
+        request: Request,
+        extensions: &mut Extensions,
+        next: Next<'_>,
+    ) -> Result<Response> {
+        if !is_idempotent(request.method()) || request.try_clone().is_none() {
+            return next.run(request, extensions).await;
+        }
+
+        let (request, next) = (&request, &next);
+        let slot = &Mutex::new(Some(extensions));
+        let result = self
+            .policy
+            .execute_async(CorrelationId::generate(), None, |_, _| async move {
+                let attempt = request
+                    .try_clone()
+                    .expect("the request body was cloned before");
+                let mut extensions = Lent::take(slot);
+                let result = next.clone().run(attempt, extensions.get()).await;
+                self.classify(result)
+            })
+            .await;
+
+        match result {
+            Ok(response) => Ok(response),
+            Err(RetryError::Operation { error, .. })
+            | Err(RetryError::MaxAttempts { error, .. }) => error.into_result(),
+            Err(error) => Err(Error::middleware(error)),
+        }
+    }
+}
+
+impl<D> RetryMiddleware<D> {
+    fn classify(&self, result: Result<Response>) -> Outcome<Response, Failure> {
+        match result {
+            Ok(response) if is_retryable_status(response.status()) => {
+                let hint =
+                    retry_after(response.headers()).map(|hint| hint.min(self.max_retry_after));
+                Outcome::Retry(Failure::Response(response), hint)
+            }
+            Ok(response) => Outcome::Ok(response),
+            Err(Error::Reqwest(error)) if error.is_connect() || error.is_timeout() => {
+                Outcome::Retry(Failure::Error(Error::Reqwest(error)), None)
+            }
+            Err(error) => Outcome::Err(Failure::Error(error)),
+        }
+    }
+}
+
+/// The last response or error of a request that was not a success.
+#[derive(Debug)]
+enum Failure {
+    Response(Response),
+    Error(Error),
+}
+
+impl Failure {
+    fn into_result(self) -> Result<Response> {
+        match self {
+            Failure::Response(response) => Ok(response),
+            Failure::Error(error) => Err(error),
+        }
+    }
+}
+
+impl Display for Failure {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Failure::Response(response) => write!(formatter, "status {}", response.status()),
+            Failure::Error(error) => Display::fmt(error, formatter),
+        }
+    }
+}
+
+impl StdError for Failure {}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::atomic::Ordering, time::Duration};
+
+    use reqwest_middleware::{reqwest::Client, ClientBuilder};
+
+    use super::RetryMiddleware;
+    use crate::{
+        delay::{Fixed, NoDelay},
+        http::tests::{serve, OK, UNAVAILABLE},
+        Policy,
+    };
+
+    const RETRY_IN_AN_HOUR: &str = "HTTP/1.1 503 Service Unavailable\r\nretry-after: 3600\r\nconnection: close\r\ncontent-length: 0\r\n\r\n";
+
+    #[tokio::test]
+    async fn retries_server_errors() {
+        let (address, requests) = serve(vec![UNAVAILABLE, UNAVAILABLE, OK]).await;
+        let client = ClientBuilder::new(Client::new())
+            .with(RetryMiddleware::new(Policy::new(NoDelay.take(5))))
+            .build();
+
+        let response = client.get(&address).send().await.unwrap();
+
+        assert_eq!(response.status(), 200);
+        assert_eq!(requests.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn returns_last_response_when_delays_end() {
+        let (address, requests) = serve(vec![UNAVAILABLE, UNAVAILABLE, OK]).await;
+        let client = ClientBuilder::new(Client::new())
+            .with(RetryMiddleware::new(Policy::new(NoDelay.take(1))))
+            .build();
+
+        let response = client.get(&address).send().await.unwrap();
+
+        assert_eq!(response.status(), 503);
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn does_not_retry_non_idempotent_requests() {
+        let (address, requests) = serve(vec![UNAVAILABLE, OK]).await;
+        let client = ClientBuilder::new(Client::new())
+            .with(RetryMiddleware::new(Policy::new(NoDelay.take(5))))
+            .build();
+
+        let response = client.post(&address).send().await.unwrap();
+
+        assert_eq!(response.status(), 503);
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn honors_retry_after() {
+        let (address, requests) = serve(vec![
+            "HTTP/1.1 429 Too Many Requests\r\nretry-after: 0\r\nconnection: close\r\ncontent-length: 0\r\n\r\n",
+            OK,
+        ])
+        .await;
+        let client = ClientBuilder::new(Client::new())
+            .with(RetryMiddleware::new(Policy::new(
+                Fixed::from_millis(60_000).take(1),
+            )))
+            .build();
+
+        let response = client.get(&address).send().await.unwrap();
+
+        assert_eq!(response.status(), 200);
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn limits_retry_after_to_max_retry_after() {
+        let (address, requests) = serve(vec![RETRY_IN_AN_HOUR, OK]).await;
+        let client = ClientBuilder::new(Client::new())
+            .with(
+                RetryMiddleware::new(Policy::new(NoDelay.take(1)))
+                    .with_max_retry_after(Duration::from_millis(1)),
+            )
+            .build();
+
+        let response = client.get(&address).send().await.unwrap();
+
+        assert_eq!(response.status(), 200);
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn limits_retry_after_to_the_policy_max_delay() {
+        let (address, requests) = serve(vec![RETRY_IN_AN_HOUR, OK]).await;
+        let policy = Policy::new(NoDelay.take(1)).with_max_delay(Duration::from_millis(1));
+        let client = ClientBuilder::new(Client::new())
+            .with(RetryMiddleware::new(policy))
+            .build();
+
+        let response = client.get(&address).send().await.unwrap();
+
+        assert_eq!(response.status(), 200);
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn stops_at_the_policy_max_attempts() {
+        let (address, requests) = serve(vec![UNAVAILABLE, UNAVAILABLE, OK]).await;
+        let policy = Policy::new(NoDelay.take(5)).with_max_attempts(2);
+        let client = ClientBuilder::new(Client::new())
+            .with(RetryMiddleware::new(policy))
+            .build();
+
+        let response = client.get(&address).send().await.unwrap();
+
+        assert_eq!(response.status(), 503);
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+    }
+}
@@ -0,0 +1,248 @@
+use std::time::{Duration, SystemTime};
+
+use crate::{clock::saturating_add, Error, Policy};
+
+/// The progress of a `RetrySession`, which can be saved and used to resume the session after the
+/// process restarts. With the `"serde"` feature, the state can be serialized.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RetryState {
+    tries: u64,
+    total_delay: Duration,
+    delays_taken: u64,
+    next_due: Option<SystemTime>,
+}
+
+impl RetryState {
+    /// The number of attempts made so far.
+    pub fn tries(&self) -> u64 {
+        self.tries
+    }
+
+    /// The sum of the delays chosen so far.
+    pub fn total_delay(&self) -> Duration {
+        self.total_delay
+    }
+
+    /// The number of delays taken from the strategy so far.
+    pub fn delays_taken(&self) -> u64 {
+        self.delays_taken
+    }
+
+    /// When the next attempt is due, on the wall clock, or `None` if it is due at once.
+    pub fn next_due(&self) -> Option<SystemTime> {
+        self.next_due
+    }
+}
+
+/// A retry schedule driven by the caller one attempt at a time, whose state survives a restart of
+/// the process, for job runners that make each attempt of a job in a separate run.
+///
+/// The session does not make attempts or wait; the runner makes an attempt once it is due, and
+/// reports failures with `record_failure`, which returns when the next attempt is due. It can save
+/// the `state` between runs, and carry on with `resume`, which starts the policy's strategy over
+/// and skips the delays that were already taken. A randomized strategy draws the delays it skips
+/// again, so the delays that follow are drawn afresh rather than being those the original session
+/// would have chosen.
+///
+/// Due times are kept on the wall clock, since the monotonic time does not carry over to another
+/// process.
+///
+/// ```rust
+/// # use retry::delay::Fixed;
+/// use retry::{Policy, RetrySession};
+///
+/// let policy = Policy::new(Fixed::from_millis(60_000)).with_max_attempts(3);
+/// let mut session = RetrySession::new(policy.clone());
+/// let due = session.record_failure("unavailable").unwrap();
+///
+/// // Saved before the process restarts, and loaded when it starts again.
+/// let state = session.state().clone();
+///
+/// let mut session = RetrySession::resume(policy, state);
+/// assert_eq!(session.state().next_due(), Some(due));
+/// assert!(!session.is_due());
+/// assert!(session.record_failure("unavailable").is_ok());
+/// assert!(session.record_failure("unavailable").is_err());
+/// ```
+#[derive(Debug)]
+pub struct RetrySession<D>
+where
+    D: IntoIterator<Item = Duration>,
+{
+    policy: Policy<D>,
+    delays: D::IntoIter,
+    state: RetryState,
+}
+
+impl<D> RetrySession<D>
+where
+    D: IntoIterator<Item = Duration> + Clone,
+{
+    /// Start a session that retries according to `policy`.
+    pub fn new(policy: Policy<D>) -> Self {
+        RetrySession::resume(policy, RetryState::default())
+    }
+
+    /// Carry on with a session that retries according to `policy`, from a state it saved.
+    ///
+    /// The policy should be the one the session was started with, since only the position in its
+    /// strategy is saved.
+    pub fn resume(policy: Policy<D>, state: RetryState) -> Self {
+        let mut delays = policy.delays();
+        for _ in 0..state.delays_taken {
+            if delays.next().is_none() {
+                break;
+            }
+        }
+        RetrySession {
+            policy,
+            delays,
+            state,
+        }
+    }
+
+    /// The state of the session, to save before the process stops.
+    pub fn state(&self) -> &RetryState {
+        &self.state
+    }
+
+    /// Whether the next attempt is due.
+    pub fn is_due(&self) -> bool {
+        self.is_due_at(SystemTime::now())
+    }
+
+    /// Whether the next attempt is due at `now`.
+    pub fn is_due_at(&self, now: SystemTime) -> bool {
+        self.state.next_due.is_none_or(|next_due| next_due <= now)
+    }
+
+    /// Record that an attempt failed with `error`, and return when the next attempt is due, or
+    /// give up with `error` if the schedule has ended.
+    ///
+    /// A delay too long to add to the current time, such as `Duration::MAX`, is due at the latest
+    /// time that can be represented.
+    pub fn record_failure<E>(&mut self, error: E) -> Result<SystemTime, Error<E>> {
+        self.record_failure_at(error, SystemTime::now())
+    }
+
+    /// Record that an attempt failed with `error` at `now`, and return when the next attempt is
+    /// due, or give up with `error` if the schedule has ended.
+    pub fn record_failure_at<E>(
+        &mut self,
+        error: E,
+        now: SystemTime,
+    ) -> Result<SystemTime, Error<E>> {
+        self.state.tries += 1;
+        let state = &mut self.state;
+        if let Some(max_attempts) = self.policy.max_attempts() {
+            if state.tries >= max_attempts {
+                state.next_due = None;
+                return Err(Error::MaxAttempts {
+                    error,
+                    total_delay: state.total_delay,
+                    tries: state.tries,
+                });
+            }
+        }
+        let delay = match self.delays.next() {
+            Some(delay) => match self.policy.max_delay() {
+                Some(max_delay) => delay.min(max_delay),
+                None => delay,
+            }
+            .max(self.policy.min_interval().unwrap_or_default()),
+            None => {
+                state.next_due = None;
+                return Err(Error::Operation {
+                    error,
+                    total_delay: state.total_delay,
+                    tries: state.tries,
+                });
+            }
+        };
+        state.delays_taken += 1;
+        state.total_delay = state.total_delay.saturating_add(delay);
+        let next_due = saturating_add(now, delay, SystemTime::checked_add);
+        state.next_due = Some(next_due);
+        Ok(next_due)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime};
+
+    use super::RetrySession;
+    use crate::{
+        delay::{Exponential, Fixed},
+        Error, Policy,
+    };
+
+    #[test]
+    fn saturates_delays_past_any_time() {
+        let policy = Policy::new(Fixed::from_duration(Duration::MAX).take(3));
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000);
+        let mut session = RetrySession::new(policy);
+
+        let first = session.record_failure_at("down", now).unwrap();
+        assert!(first > now + Duration::from_secs(1 << 40));
+        assert!(!session.is_due_at(now));
+        assert_eq!(session.record_failure_at("down", now), Ok(first));
+        assert_eq!(session.state().total_delay(), Duration::MAX);
+    }
+
+    #[test]
+    fn resumes_the_schedule_where_it_left_off() {
+        let policy = Policy::new(Exponential::from_millis(10).take(3));
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000);
+        let mut session = RetrySession::new(policy.clone());
+        assert!(session.is_due_at(now));
+
+        assert_eq!(
+            session.record_failure_at("down", now),
+            Ok(now + Duration::from_millis(10))
+        );
+        assert_eq!(
+            session.record_failure_at("down", now),
+            Ok(now + Duration::from_millis(100))
+        );
+        assert!(!session.is_due_at(now));
+        assert!(session.is_due_at(now + Duration::from_millis(100)));
+
+        let mut session = RetrySession::resume(policy, session.state().clone());
+        assert_eq!(session.state().delays_taken(), 2);
+        assert_eq!(
+            session.record_failure_at("down", now),
+            Ok(now + Duration::from_millis(1000))
+        );
+        assert_eq!(
+            session.record_failure_at("down", now),
+            Err(Error::Operation {
+                error: "down",
+                total_delay: Duration::from_millis(1110),
+                tries: 4,
+            })
+        );
+        assert_eq!(session.state().next_due(), None);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serializes_the_state() {
+        let mut session = RetrySession::new(Policy::new(Exponential::from_millis(10)));
+        let _ = session.record_failure_at("down", SystemTime::UNIX_EPOCH);
+
+        let json = serde_json::to_string(session.state()).unwrap();
+        assert_eq!(
+            json,
+            concat!(
+                r#"{"tries":1,"total_delay":{"secs":0,"nanos":10000000},"delays_taken":1,"#,
+                r#""next_due":{"secs_since_epoch":0,"nanos_since_epoch":10000000}}"#
+            )
+        );
+        assert_eq!(
+            &serde_json::from_str::<super::RetryState>(&json).unwrap(),
+            session.state()
+        );
+    }
+}
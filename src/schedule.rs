@@ -0,0 +1,293 @@
+use core::time::Duration;
+
+use crate::Error;
+
+/// The schedule of one retried call, driven by the caller one attempt at a time: which attempt is
+/// being made, how long to wait before the next one, and when to give up.
+///
+/// A `Schedule` needs neither a clock nor a way to sleep, so it is available without the `"std"`
+/// feature, for firmware that waits with its own timer or polls the schedule from an event loop.
+/// It applies the options of `Policy` that do not need the time of day: a maximum number of
+/// attempts, a maximum delay and a splay added to the first delay. `Policy` drives its calls with
+/// a `Schedule`, adding the options that need a clock on top.
+///
+/// ```rust
+/// use core::time::Duration;
+/// use retry::{delay::Exponential, Error, Schedule};
+///
+/// let mut schedule = Schedule::new(Exponential::from_millis(10))
+///     .with_max_attempts(4)
+///     .with_max_delay(Duration::from_millis(500));
+/// let mut waited = Vec::new();
+///
+/// let result = loop {
+///     schedule.start_attempt();
+///     let error = "sensor not ready";
+///     match schedule.next_delay() {
+///         Some(delay) => waited.push(delay), // wait with the board's timer
+///         None => break schedule.give_up(error),
+///     }
+/// };
+///
+/// assert_eq!(waited, [10, 100, 500].map(Duration::from_millis));
+/// assert!(matches!(result, Error::MaxAttempts { tries: 4, .. }));
+/// ```
+#[derive(Clone, Debug)]
+pub struct Schedule<I> {
+    delays: I,
+    tries: u64,
+    total_delay: Duration,
+    max_attempts: Option<u64>,
+    max_delay: Option<Duration>,
+    splay: Option<Duration>,
+    /// The next delay of the strategy, if it was drawn early to tell it before it is needed.
+    peeked_delay: Option<Option<Duration>>,
+    reached_max_attempts: bool,
+}
+
+impl<I> Schedule<I>
+where
+    I: Iterator<Item = Duration>,
+{
+    /// Create a new `Schedule` that waits the delays of the given strategy between attempts.
+    pub fn new<D>(delays: D) -> Self
+    where
+        D: IntoIterator<IntoIter = I>,
+    {
+        Schedule {
+            delays: delays.into_iter(),
+            tries: 0,
+            total_delay: Duration::default(),
+            max_attempts: None,
+            max_delay: None,
+            splay: None,
+            peeked_delay: None,
+            reached_max_attempts: false,
+        }
+    }
+
+    /// Create a new `Schedule` with the options of a policy.
+    #[cfg(feature = "std")]
+    pub(crate) fn with_options(
+        delays: I,
+        max_attempts: Option<u64>,
+        max_delay: Option<Duration>,
+        splay: Option<Duration>,
+    ) -> Self {
+        Schedule {
+            max_attempts,
+            max_delay,
+            splay,
+            ..Schedule::new(delays)
+        }
+    }
+
+    /// Give up after the given number of attempts, even if the strategy has delays left.
+    pub fn with_max_attempts(mut self, max_attempts: u64) -> Self {
+        self.max_attempts = Some(max_attempts);
+        self
+    }
+
+    /// Bound every delay by the given maximum.
+    pub fn with_max_delay(mut self, max_delay: Duration) -> Self {
+        self.max_delay = Some(max_delay);
+        self
+    }
+
+    /// Add the given offset to the first delay, so that devices that start together do not all
+    /// retry at the same time.
+    pub fn with_splay(mut self, splay: Duration) -> Self {
+        self.splay = Some(splay);
+        self
+    }
+
+    /// Start the next attempt, returning its number.
+    pub fn start_attempt(&mut self) -> u64 {
+        self.tries += 1;
+        self.tries
+    }
+
+    /// Record that the current attempt failed, returning the delay to wait before the next
+    /// attempt, or `None` if the schedule has ended and the call should give up.
+    pub fn next_delay(&mut self) -> Option<Duration> {
+        let delay = self.draw().map(|delay| self.shape(delay))?;
+        self.total_delay = self.total_delay.saturating_add(delay);
+        Some(delay)
+    }
+
+    /// The delay that `next_delay` will return if the current attempt fails, without using it up.
+    pub fn peek_delay(&mut self) -> Option<Duration> {
+        self.peek().map(|delay| self.shape(delay))
+    }
+
+    /// Give up after the current attempt failed with `error`, producing the terminal error:
+    /// `Error::MaxAttempts` if the maximum number of attempts was reached, and `Error::Operation`
+    /// otherwise.
+    pub fn give_up<E>(&self, error: E) -> Error<E> {
+        if self.reached_max_attempts {
+            Error::MaxAttempts {
+                error,
+                total_delay: self.total_delay,
+                tries: self.tries,
+            }
+        } else {
+            Error::Operation {
+                error,
+                total_delay: self.total_delay,
+                tries: self.tries,
+            }
+        }
+    }
+
+    /// The number of attempts started so far.
+    pub fn tries(&self) -> u64 {
+        self.tries
+    }
+
+    /// The sum of the delays returned by `next_delay` so far.
+    pub fn total_delay(&self) -> Duration {
+        self.total_delay
+    }
+
+    /// The maximum number of attempts, if any.
+    pub fn max_attempts(&self) -> Option<u64> {
+        self.max_attempts
+    }
+
+    /// Whether the schedule ended because the maximum number of attempts was reached.
+    pub fn reached_max_attempts(&self) -> bool {
+        self.reached_max_attempts
+    }
+
+    /// The delays of the strategy that have not been used yet.
+    #[cfg(feature = "std")]
+    pub(crate) fn delays(&self) -> &I {
+        &self.delays
+    }
+
+    /// The strategy's next delay, before it is bounded or splayed, or `None` if the strategy has
+    /// ended or the maximum number of attempts has been made.
+    ///
+    /// When both happen at once, the strategy ending wins, so that `give_up` only reports
+    /// `Error::MaxAttempts` if the strategy had delays left.
+    pub(crate) fn draw(&mut self) -> Option<Duration> {
+        let delays = &mut self.delays;
+        let delay = *self.peeked_delay.get_or_insert_with(|| delays.next());
+        if self
+            .max_attempts
+            .is_some_and(|max_attempts| self.tries >= max_attempts)
+        {
+            self.reached_max_attempts = delay.is_some();
+            return None;
+        }
+        self.peeked_delay = None;
+        delay
+    }
+
+    /// The delay that `draw` will return next, if the maximum number of attempts has not been made.
+    pub(crate) fn peek(&mut self) -> Option<Duration> {
+        if self
+            .max_attempts
+            .is_some_and(|max_attempts| self.tries >= max_attempts)
+        {
+            return None;
+        }
+        let delays = &mut self.delays;
+        *self.peeked_delay.get_or_insert_with(|| delays.next())
+    }
+
+    /// Add the splay to the first delay and bound the delay by the maximum delay.
+    pub(crate) fn shape(&self, delay: Duration) -> Duration {
+        let delay = match self.splay {
+            Some(splay) if self.tries == 1 => delay.saturating_add(splay),
+            _ => delay,
+        };
+        match self.max_delay {
+            Some(max_delay) => delay.min(max_delay),
+            None => delay,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+
+    use super::Schedule;
+    use crate::{
+        delay::{Fibonacci, Fixed},
+        Error,
+    };
+
+    #[test]
+    fn counts_attempts_until_the_strategy_ends() {
+        let mut schedule = Schedule::new(Fibonacci::from_millis(10).take(3));
+        let mut delays = [Duration::default(); 3];
+
+        for delay in delays.iter_mut() {
+            schedule.start_attempt();
+            *delay = schedule.next_delay().unwrap();
+        }
+        assert_eq!(schedule.start_attempt(), 4);
+        assert_eq!(schedule.next_delay(), None);
+
+        assert_eq!(delays, [10, 10, 20].map(Duration::from_millis));
+        assert_eq!(
+            schedule.give_up("boom"),
+            Error::Operation {
+                error: "boom",
+                total_delay: Duration::from_millis(40),
+                tries: 4,
+            }
+        );
+    }
+
+    #[test]
+    fn splays_the_first_delay_and_bounds_every_delay() {
+        let mut schedule = Schedule::new(Fixed::from_millis(100))
+            .with_splay(Duration::from_millis(5))
+            .with_max_delay(Duration::from_millis(102))
+            .with_max_attempts(3);
+
+        schedule.start_attempt();
+        assert_eq!(schedule.peek_delay(), Some(Duration::from_millis(102)));
+        assert_eq!(schedule.next_delay(), Some(Duration::from_millis(102)));
+        schedule.start_attempt();
+        assert_eq!(schedule.next_delay(), Some(Duration::from_millis(100)));
+        schedule.start_attempt();
+        assert_eq!(schedule.peek_delay(), None);
+        assert_eq!(schedule.next_delay(), None);
+
+        assert!(schedule.reached_max_attempts());
+        assert_eq!(
+            schedule.give_up(()),
+            Error::MaxAttempts {
+                error: (),
+                total_delay: Duration::from_millis(202),
+                tries: 3,
+            }
+        );
+    }
+
+    #[test]
+    fn reports_the_strategy_ending_when_it_ties_with_max_attempts() {
+        let mut schedule = Schedule::new(Fixed::from_millis(1).take(2)).with_max_attempts(3);
+
+        for _ in 0..2 {
+            schedule.start_attempt();
+            assert_eq!(schedule.next_delay(), Some(Duration::from_millis(1)));
+        }
+        schedule.start_attempt();
+        assert_eq!(schedule.next_delay(), None);
+
+        assert!(!schedule.reached_max_attempts());
+        assert_eq!(
+            schedule.give_up("boom"),
+            Error::Operation {
+                error: "boom",
+                total_delay: Duration::from_millis(2),
+                tries: 3,
+            }
+        );
+    }
+}
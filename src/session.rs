@@ -0,0 +1,818 @@
+//! The bookkeeping for a single call made through a `Policy`, shared by the synchronous and
+//! asynchronous executors so that both report attempts the same way.
+
+use std::{
+    fmt::Debug,
+    time::{Duration, Instant, SystemTime},
+};
+
+use crate::{
+    breaker::Admission,
+    bulkhead::Permit,
+    clock::Clock,
+    context::AttemptContext,
+    listener::{AttemptOutcome, DeadLetter, GiveUpSummary, Progress, RetainedErrors, SlowRetry},
+    policy::{Options, Scheduling},
+    schedule::Schedule,
+    stats::InFlight,
+    CorrelationId, Error,
+};
+
+/// The state of one retry session: the remaining delays and what has happened so far.
+pub(crate) struct Session<'p, I> {
+    options: &'p Options,
+    default_clock: &'p dyn Clock,
+    correlation_id: CorrelationId,
+    schedule: Schedule<I>,
+    total_delay: Duration,
+    started: Instant,
+    attempt_started: Option<Instant>,
+    watchdog_fired: bool,
+    planned_delay: Option<Duration>,
+    waited: Vec<Duration>,
+    errors: RetainedErrors,
+    /// The call let through by the policy's circuit breaker, which is told how the session ended.
+    admission: Option<Admission>,
+    /// The place held in the policy's bulkhead until the session ends.
+    _permit: Option<Permit>,
+    _in_flight: InFlight,
+    #[cfg(feature = "tracing")]
+    span: tracing::Span,
+}
+
+impl<'p, I> Session<'p, I>
+where
+    I: Iterator<Item = Duration>,
+{
+    pub(crate) fn new(
+        options: &'p Options,
+        default_clock: &'p dyn Clock,
+        correlation_id: CorrelationId,
+        delays: I,
+        admission: Option<Admission>,
+        permit: Option<Permit>,
+    ) -> Self {
+        Session {
+            options,
+            default_clock,
+            #[cfg(feature = "tracing")]
+            span: tracing::info_span!(
+                "retry",
+                operation = options.name.as_deref(),
+                correlation_id = %correlation_id,
+                attempts = tracing::field::Empty,
+                total_delay_ms = tracing::field::Empty,
+            ),
+            correlation_id,
+            schedule: Schedule::with_options(
+                delays,
+                options.max_attempts,
+                options.max_delay,
+                options.splay,
+            ),
+            total_delay: Duration::default(),
+            started: options.now(default_clock),
+            attempt_started: None,
+            watchdog_fired: false,
+            planned_delay: None,
+            waited: Vec::new(),
+            errors: RetainedErrors::new(options.error_retention),
+            admission,
+            _permit: permit,
+            _in_flight: options.stats.enter(),
+        }
+    }
+
+    /// Plan the session with a fresh copy of its delays, to estimate the delays left when
+    /// reporting progress. Strategies without an upper bound on their length are only planned up
+    /// to the maximum number of attempts, if the policy has one.
+    pub(crate) fn plan(&mut self, delays: I) {
+        self.planned_delay = match self.options.max_attempts {
+            Some(max_attempts) => Some(sum(delays.take(max_attempts as usize - 1))),
+            None if delays.size_hint().1.is_some() => Some(sum(delays)),
+            None => None,
+        };
+    }
+
+    /// The context of the current attempt, drawing the strategy's next delay early to tell the
+    /// delay planned after the attempt.
+    pub(crate) fn context(&mut self) -> AttemptContext {
+        let elapsed = self.elapsed_since(self.started);
+        let remaining_budget = self
+            .options
+            .time_budget
+            .map(|time_budget| time_budget.saturating_sub(elapsed));
+        let next_planned_delay = self
+            .schedule
+            .peek_delay()
+            .filter(|&delay| self.within_time_budget(delay));
+        AttemptContext::new(
+            self.schedule.tries(),
+            elapsed,
+            remaining_budget,
+            next_planned_delay,
+        )
+    }
+
+    /// The ID of this session, passed to every attempt.
+    pub(crate) fn correlation_id(&self) -> &CorrelationId {
+        &self.correlation_id
+    }
+
+    /// The span that the whole session runs in.
+    #[cfg(feature = "tracing")]
+    pub(crate) fn span(&self) -> &tracing::Span {
+        &self.span
+    }
+
+    /// Start the next attempt, returning its number.
+    pub(crate) fn start_attempt(&mut self) -> u64 {
+        if self.schedule.start_attempt() == 1 {
+            if let Some(ref budget) = self.options.retry_budget {
+                budget.deposit();
+            }
+        }
+        self.check_watchdog();
+        #[cfg(feature = "tracing")]
+        tracing::trace!(attempt = self.schedule.tries(), "starting attempt");
+        self.record_attempt();
+        for listener in self.options.listeners.iter() {
+            listener.on_attempt_start(&self.correlation_id, self.schedule.tries());
+        }
+        self.attempt_started = Some(self.options.now(self.default_clock));
+        self.schedule.tries()
+    }
+
+    /// Record that the current attempt succeeded.
+    pub(crate) fn succeed(&mut self) {
+        self.end_attempt(AttemptOutcome::Ok);
+        self.options.stats.record_success(self.schedule.tries());
+        if let Some(admission) = self.admission.take() {
+            admission.succeeded();
+        }
+        #[cfg(feature = "otel")]
+        self.options.otel.record_outcome(
+            self.options.name.as_ref(),
+            "success",
+            self.schedule.tries(),
+        );
+        #[cfg(feature = "tracing")]
+        {
+            self.record_totals();
+            tracing::debug!(attempt = self.schedule.tries(), "attempt succeeded");
+        }
+    }
+
+    /// Record that the current attempt failed with a retryable error, returning the delay before
+    /// the next attempt, or `None` if the schedule has ended.
+    ///
+    /// If `retry_after` is given, it is waited instead of the strategy's next delay. The strategy's
+    /// delay is still consumed, so that the schedule keeps bounding the attempts. Either delay is
+    /// bounded by the policy's maximum delay, then lengthened to its minimum interval.
+    pub(crate) fn retry<E: Debug>(
+        &mut self,
+        error: &E,
+        retry_after: Option<Duration>,
+    ) -> Option<Duration> {
+        let latency = self.end_attempt(AttemptOutcome::Retry(error));
+        // A delay asked for by the dependency is waited in full, whatever the scheduling.
+        let delay = self.next_delay().map(|delay| match retry_after {
+            Some(retry_after) => self.space(self.schedule.shape(retry_after), latency),
+            None => self.space(self.pace(self.schedule.shape(delay), latency), latency),
+        });
+        let delay = delay.filter(|&delay| self.within_time_budget(delay));
+        self.record_delay(delay);
+        self.check_storm(delay);
+
+        #[cfg(feature = "tracing")]
+        if let Some(delay) = delay {
+            tracing::debug!(
+                attempt = self.schedule.tries(),
+                delay_ms = delay.as_millis() as u64,
+                error = ?error,
+                "attempt failed, retrying"
+            );
+        }
+        #[cfg(feature = "log")]
+        if let Some(delay) = delay {
+            log::log!(
+                target: self.options.log.target,
+                self.options.log.retry_level,
+                "{}attempt {} failed, retrying in {:?}: {:?}",
+                self.log_prefix(),
+                self.schedule.tries(),
+                delay,
+                error
+            );
+        }
+        delay
+    }
+
+    /// Record that the current attempt timed out, returning the delay before the next attempt, or
+    /// `None` if the schedule has ended.
+    #[cfg(feature = "asynchronous")]
+    pub(crate) fn retry_timed_out(&mut self, timeout: Duration) -> Option<Duration> {
+        let latency = self.end_attempt(AttemptOutcome::TimedOut(timeout));
+        let delay = self
+            .next_delay()
+            .map(|delay| self.space(self.pace(self.schedule.shape(delay), latency), latency))
+            .filter(|&delay| self.within_time_budget(delay));
+        self.record_delay(delay);
+        self.check_storm(delay);
+
+        #[cfg(feature = "tracing")]
+        if let Some(delay) = delay {
+            tracing::debug!(
+                attempt = self.schedule.tries(),
+                delay_ms = delay.as_millis() as u64,
+                timeout_ms = timeout.as_millis() as u64,
+                "attempt timed out, retrying"
+            );
+        }
+
+        #[cfg(feature = "log")]
+        if let Some(delay) = delay {
+            log::log!(
+                target: self.options.log.target,
+                self.options.log.retry_level,
+                "{}attempt {} timed out after {:?}, retrying in {:?}",
+                self.log_prefix(),
+                self.schedule.tries(),
+                timeout,
+                delay
+            );
+        }
+
+        delay
+    }
+
+    /// Record that the delay before the next attempt has been waited out.
+    pub(crate) fn waited(&mut self, delay: Duration) {
+        self.total_delay += delay;
+        // Only listeners and the dead-letter hook see the delays, so there is no need to keep them
+        // otherwise.
+        if !self.options.listeners.is_empty() || self.options.dead_letter.is_some() {
+            self.waited.push(delay);
+        }
+    }
+
+    /// Give up after the current attempt failed with `error`, producing the terminal error.
+    pub(crate) fn give_up<E: Debug>(&mut self, error: E) -> Error<E> {
+        self.end_attempt(AttemptOutcome::Err(&error));
+        #[cfg(feature = "tracing")]
+        {
+            self.record_totals();
+            tracing::warn!(
+                attempts = self.schedule.tries(),
+                total_delay_ms = self.total_delay.as_millis() as u64,
+                error = ?error,
+                "giving up"
+            );
+        }
+
+        #[cfg(feature = "log")]
+        log::log!(
+            target: self.options.log.target,
+            self.options.log.give_up_level,
+            "{}giving up after {} attempts and {:?} of delays: {:?}",
+            self.log_prefix(),
+            self.schedule.tries(),
+            self.total_delay,
+            error
+        );
+
+        self.record_give_up();
+
+        if self.schedule.reached_max_attempts() {
+            Error::MaxAttempts {
+                error,
+                total_delay: self.total_delay,
+                tries: self.schedule.tries(),
+            }
+        } else {
+            Error::Operation {
+                error,
+                total_delay: self.total_delay,
+                tries: self.schedule.tries(),
+            }
+        }
+    }
+
+    /// End the session because the policy's cancellation token was cancelled, producing the
+    /// terminal error. The session is not given up on, so the policy's listeners and circuit
+    /// breaker are not told about it.
+    pub(crate) fn cancel<E>(&mut self) -> Error<E> {
+        #[cfg(feature = "tracing")]
+        {
+            self.record_totals();
+            tracing::warn!(
+                attempts = self.schedule.tries(),
+                total_delay_ms = self.total_delay.as_millis() as u64,
+                "cancelled"
+            );
+        }
+
+        #[cfg(feature = "log")]
+        log::log!(
+            target: self.options.log.target,
+            self.options.log.give_up_level,
+            "{}cancelled after {} attempts and {:?} of delays",
+            self.log_prefix(),
+            self.schedule.tries(),
+            self.total_delay
+        );
+
+        Error::Cancelled {
+            total_delay: self.total_delay,
+            tries: self.schedule.tries(),
+        }
+    }
+
+    /// Give up after the current attempt timed out, producing the terminal error.
+    #[cfg(feature = "asynchronous")]
+    pub(crate) fn give_up_timed_out<E>(&mut self, timeout: Duration) -> Error<E> {
+        #[cfg(feature = "tracing")]
+        {
+            self.record_totals();
+            tracing::warn!(
+                attempts = self.schedule.tries(),
+                total_delay_ms = self.total_delay.as_millis() as u64,
+                timeout_ms = timeout.as_millis() as u64,
+                "giving up after attempt timed out"
+            );
+        }
+
+        #[cfg(feature = "log")]
+        log::log!(
+            target: self.options.log.target,
+            self.options.log.give_up_level,
+            "{}giving up after {} attempts and {:?} of delays: attempt timed out after {:?}",
+            self.log_prefix(),
+            self.schedule.tries(),
+            self.total_delay,
+            timeout
+        );
+
+        self.record_give_up();
+
+        Error::TimedOut {
+            timeout,
+            total_delay: self.total_delay,
+            tries: self.schedule.tries(),
+        }
+    }
+
+    /// Labels every metric with the policy's name, if it has one.
+    #[cfg(feature = "metrics")]
+    fn metric_labels(&self) -> Vec<metrics::Label> {
+        match self.options.name {
+            Some(ref name) => vec![metrics::Label::new("operation", name.clone())],
+            None => Vec::new(),
+        }
+    }
+
+    fn record_attempt(&self) {
+        self.options.stats.record_attempt();
+        #[cfg(feature = "metrics")]
+        metrics::counter!("retry.attempts", self.metric_labels()).increment(1);
+        #[cfg(feature = "prometheus")]
+        if let Some(ref prometheus) = self.options.prometheus {
+            prometheus.record_attempt(self.operation());
+        }
+    }
+
+    /// The delay before the next attempt, or `None` if the schedule has ended, the policy's
+    /// maximum number of attempts has been made or its retry budget is spent.
+    fn next_delay(&mut self) -> Option<Duration> {
+        let delay = self.schedule.draw()?;
+        match self.options.retry_budget {
+            Some(ref budget) if !budget.try_withdraw() => None,
+            _ => Some(delay),
+        }
+    }
+
+    /// Whether the next attempt would start within the policy's time budget after waiting
+    /// `delay`, if it has one.
+    fn within_time_budget(&self, delay: Duration) -> bool {
+        match self.options.time_budget {
+            Some(time_budget) => {
+                self.elapsed_since(self.started).saturating_add(delay) <= time_budget
+            }
+            None => true,
+        }
+    }
+
+    /// Shorten a delay by the latency of the attempt before it, if the policy makes its attempts
+    /// at a fixed rate.
+    fn pace(&self, delay: Duration, latency: Duration) -> Duration {
+        match self.options.scheduling {
+            Scheduling::FixedDelay => delay,
+            Scheduling::FixedRate => delay.saturating_sub(latency),
+        }
+    }
+
+    /// Lengthen a delay so that the next attempt starts at least the policy's minimum interval
+    /// after the start of the attempt before it, if any.
+    fn space(&self, delay: Duration, latency: Duration) -> Duration {
+        match self.options.min_interval {
+            Some(min_interval) => delay.max(min_interval.saturating_sub(latency)),
+            None => delay,
+        }
+    }
+
+    /// End the current attempt, unless it has already been ended by a failure that led to giving
+    /// up, and return its latency, or zero if it had already been ended.
+    fn end_attempt(&mut self, outcome: AttemptOutcome<'_>) -> Duration {
+        let mut latency = Duration::default();
+        if let Some(attempt_started) = self.attempt_started.take() {
+            latency = self.elapsed_since(attempt_started);
+            for listener in self.options.listeners.iter() {
+                listener.on_attempt_end(
+                    &self.correlation_id,
+                    self.schedule.tries(),
+                    outcome,
+                    latency,
+                );
+            }
+            if !outcome.is_ok() {
+                self.check_watchdog();
+            }
+            if self.options.dead_letter.is_some() {
+                match outcome {
+                    AttemptOutcome::Ok => {}
+                    AttemptOutcome::Retry(error) | AttemptOutcome::Err(error) => self
+                        .errors
+                        .push((SystemTime::now(), format!("{:?}", error))),
+                    AttemptOutcome::TimedOut(timeout) => {
+                        let error = format!("timed out after {:?}", timeout);
+                        self.errors.push((SystemTime::now(), error))
+                    }
+                }
+            }
+        }
+        latency
+    }
+
+    /// The time elapsed since `instant`, as measured by the policy's clock.
+    fn elapsed_since(&self, instant: Instant) -> Duration {
+        self.options
+            .now(self.default_clock)
+            .saturating_duration_since(instant)
+    }
+
+    /// Call the watchdog hook if the session has been running for longer than its threshold and
+    /// the hook has not been called yet.
+    fn check_watchdog(&mut self) {
+        if let Some(ref watchdog) = self.options.watchdog {
+            let elapsed = self.elapsed_since(self.started);
+            if !self.watchdog_fired && elapsed >= watchdog.threshold {
+                self.watchdog_fired = true;
+                (watchdog.hook)(&SlowRetry {
+                    correlation_id: self.correlation_id.clone(),
+                    attempts: self.schedule.tries(),
+                    elapsed,
+                    threshold: watchdog.threshold,
+                });
+            }
+        }
+    }
+
+    /// Count a retry towards the policy's storm warning, if the call is about to retry.
+    fn check_storm(&self, delay: Option<Duration>) {
+        if let (Some(ref storm), Some(_)) = (&self.options.storm, delay) {
+            storm.record_retry(self.options.now(self.default_clock));
+        }
+    }
+
+    fn record_delay(&self, delay: Option<Duration>) {
+        if let Some(delay) = delay {
+            for listener in self.options.listeners.iter() {
+                listener.on_backoff(&self.correlation_id, delay);
+            }
+            if let Some(ref progress) = self.options.progress {
+                let scheduled_attempts = self
+                    .schedule
+                    .delays()
+                    .size_hint()
+                    .1
+                    .map(|left| self.schedule.tries() + 1 + left as u64);
+                (progress.0)(&Progress {
+                    correlation_id: self.correlation_id.clone(),
+                    attempt: self.schedule.tries(),
+                    max_attempts: match (scheduled_attempts, self.options.max_attempts) {
+                        (Some(scheduled), Some(max)) => Some(scheduled.min(max)),
+                        (scheduled, max) => scheduled.or(max),
+                    },
+                    next_delay: delay,
+                    remaining_delay: self
+                        .planned_delay
+                        .map(|planned| planned.saturating_sub(self.total_delay).max(delay)),
+                });
+            }
+        }
+        #[cfg(feature = "metrics")]
+        if let Some(delay) = delay {
+            metrics::histogram!("retry.delay", self.metric_labels()).record(delay.as_secs_f64());
+        }
+        #[cfg(feature = "prometheus")]
+        if let (Some(ref prometheus), Some(delay)) = (&self.options.prometheus, delay) {
+            prometheus.record_delay(self.operation(), delay);
+        }
+        #[cfg(feature = "otel")]
+        if let Some(delay) = delay {
+            self.options
+                .otel
+                .record_retry(self.options.name.as_ref(), delay);
+        }
+    }
+
+    fn record_give_up(&mut self) {
+        let summary = GiveUpSummary {
+            correlation_id: self.correlation_id.clone(),
+            attempts: self.schedule.tries(),
+            total_delay: self.total_delay,
+            delays: std::mem::take(&mut self.waited),
+            elapsed: self.elapsed_since(self.started),
+        };
+        for listener in self.options.listeners.iter() {
+            listener.on_give_up(&summary);
+        }
+        for compensate in self.options.compensations.in_order() {
+            compensate(&summary);
+        }
+        if let Some(ref dead_letter) = self.options.dead_letter {
+            let (errors, omitted_errors) = self.errors.take();
+            let (error_times, errors) = errors.into_iter().unzip();
+            (dead_letter.0)(&DeadLetter {
+                summary,
+                errors,
+                error_times,
+                omitted_errors,
+            });
+        }
+        self.options.stats.record_give_up();
+        if let Some(admission) = self.admission.take() {
+            admission.failed();
+        }
+        #[cfg(feature = "metrics")]
+        metrics::counter!("retry.giveups", self.metric_labels()).increment(1);
+        #[cfg(feature = "prometheus")]
+        if let Some(ref prometheus) = self.options.prometheus {
+            prometheus.record_give_up(self.operation());
+        }
+        #[cfg(feature = "otel")]
+        self.options.otel.record_outcome(
+            self.options.name.as_ref(),
+            "gave_up",
+            self.schedule.tries(),
+        );
+    }
+
+    /// The name of the policy, or an empty string, as a Prometheus label value.
+    #[cfg(feature = "prometheus")]
+    fn operation(&self) -> &str {
+        self.options.name.as_deref().unwrap_or_default()
+    }
+
+    #[cfg(feature = "log")]
+    fn log_prefix(&self) -> LogPrefix<'_> {
+        LogPrefix(&self.correlation_id, self.options.name.as_deref())
+    }
+
+    #[cfg(feature = "tracing")]
+    fn record_totals(&self) {
+        self.span.record("attempts", self.schedule.tries());
+        self.span
+            .record("total_delay_ms", self.total_delay.as_millis() as u64);
+    }
+}
+
+/// The sum of the given delays, saturating instead of overflowing.
+fn sum<I: Iterator<Item = Duration>>(delays: I) -> Duration {
+    delays.fold(Duration::default(), |total, delay| {
+        total.saturating_add(delay)
+    })
+}
+
+/// The session's correlation ID in brackets and the policy's name followed by a colon, to start
+/// log messages with.
+#[cfg(feature = "log")]
+struct LogPrefix<'a>(&'a CorrelationId, Option<&'a str>);
+
+#[cfg(feature = "log")]
+impl std::fmt::Display for LogPrefix<'_> {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(formatter, "[{}] ", self.0)?;
+        match self.1 {
+            Some(name) => write!(formatter, "{}: ", name),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "metrics"))]
+mod metrics_tests {
+    use metrics_util::{
+        debugging::{DebugValue, DebuggingRecorder},
+        CompositeKey, MetricKind,
+    };
+
+    use crate::{delay::Fixed, Policy};
+
+    #[test]
+    fn records_attempts_give_ups_and_delays() {
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+
+        metrics::with_local_recorder(&recorder, || {
+            let _ = Policy::new(Fixed::from_millis(1).take(2))
+                .with_name("db")
+                .retry(|| Err::<(), _>("boom"));
+        });
+
+        let metrics: Vec<_> = snapshotter
+            .snapshot()
+            .into_vec()
+            .into_iter()
+            .map(|(key, _, _, value)| (key, value))
+            .collect();
+        let value = |kind, name: &str| {
+            metrics
+                .iter()
+                .find_map(|(key, value): &(CompositeKey, DebugValue)| {
+                    let labels: Vec<_> = key.key().labels().collect();
+                    let labeled = labels.len() == 1
+                        && labels[0].key() == "operation"
+                        && labels[0].value() == "db";
+                    if key.kind() == kind && key.key().name() == name && labeled {
+                        Some(value)
+                    } else {
+                        None
+                    }
+                })
+        };
+
+        assert_eq!(
+            value(MetricKind::Counter, "retry.attempts"),
+            Some(&DebugValue::Counter(3))
+        );
+        assert_eq!(
+            value(MetricKind::Counter, "retry.giveups"),
+            Some(&DebugValue::Counter(1))
+        );
+        match value(MetricKind::Histogram, "retry.delay") {
+            Some(DebugValue::Histogram(delays)) => assert_eq!(delays.len(), 2),
+            other => panic!("unexpected delay histogram: {:?}", other),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "log"))]
+mod log_tests {
+    use std::cell::RefCell;
+
+    use log::{Level, Log, Metadata, Record};
+
+    use crate::{delay::NoDelay, Policy};
+
+    thread_local! {
+        static RECORDS: RefCell<Vec<(String, Level, String)>> = const { RefCell::new(Vec::new()) };
+    }
+
+    /// Records log messages on the thread that produced them, so tests can run in parallel.
+    struct ThreadLogger;
+
+    impl Log for ThreadLogger {
+        fn enabled(&self, _: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn log(&self, record: &Record<'_>) {
+            RECORDS.with(|records| {
+                records.borrow_mut().push((
+                    record.target().to_string(),
+                    record.level(),
+                    record.args().to_string(),
+                ))
+            });
+        }
+
+        fn flush(&self) {}
+    }
+
+    fn records() -> Vec<(String, Level, String)> {
+        let _ = log::set_logger(&ThreadLogger);
+        log::set_max_level(log::LevelFilter::Trace);
+        RECORDS.with(|records| records.borrow_mut().drain(..).collect())
+    }
+
+    #[test]
+    fn logs_retries_and_give_up() {
+        records();
+
+        let _ = Policy::new(NoDelay.take(1))
+            .with_name("db")
+            .retry_with_correlation_id("req-1".into(), |_, _| Err::<(), _>("boom"));
+
+        assert_eq!(
+            records(),
+            vec![
+                (
+                    "retry".to_string(),
+                    Level::Debug,
+                    "[req-1] db: attempt 1 failed, retrying in 0ns: \"boom\"".to_string()
+                ),
+                (
+                    "retry".to_string(),
+                    Level::Warn,
+                    "[req-1] db: giving up after 2 attempts and 0ns of delays: \"boom\""
+                        .to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn logs_with_custom_target_and_levels() {
+        records();
+
+        let _ = Policy::new(NoDelay.take(1))
+            .with_log_target("myapp::retry")
+            .with_log_levels(Level::Info, Level::Error)
+            .retry_with_correlation_id("req-2".into(), |_, _| Err::<(), _>("boom"));
+
+        let records = records();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].0, "myapp::retry");
+        assert_eq!(records[0].1, Level::Info);
+        assert_eq!(records[1].1, Level::Error);
+        assert_eq!(
+            records[1].2,
+            "[req-2] giving up after 2 attempts and 0ns of delays: \"boom\""
+        );
+    }
+}
+
+#[cfg(all(test, feature = "tracing"))]
+mod tests {
+    use std::{
+        fmt::Debug,
+        sync::{Arc, Mutex},
+    };
+
+    use tracing::{
+        field::{Field, Visit},
+        span, Event, Subscriber,
+    };
+    use tracing_subscriber::{layer::Context, prelude::*, registry::LookupSpan, Layer};
+
+    use crate::{delay::NoDelay, Policy};
+
+    /// Records the message of every event, and the fields recorded on spans after creation.
+    #[derive(Clone, Default)]
+    struct Recorder(Arc<Mutex<Vec<String>>>);
+
+    impl Visit for Recorder {
+        fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+            let entry = if field.name() == "message" {
+                format!("{:?}", value)
+            } else {
+                format!("{}={:?}", field.name(), value)
+            };
+            self.0.lock().unwrap().push(entry);
+        }
+    }
+
+    impl<S> Layer<S> for Recorder
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+    {
+        fn on_event(&self, event: &Event<'_>, _: Context<'_, S>) {
+            if event.metadata().level() <= &tracing::Level::DEBUG {
+                let mut recorder = self.clone();
+                event.record(&mut recorder);
+            }
+        }
+
+        fn on_record(&self, _: &span::Id, values: &span::Record<'_>, _: Context<'_, S>) {
+            let mut recorder = self.clone();
+            values.record(&mut recorder);
+        }
+    }
+
+    #[test]
+    fn records_attempts_and_give_up() {
+        let recorder = Recorder::default();
+        let subscriber = tracing_subscriber::registry().with(recorder.clone());
+
+        tracing::subscriber::with_default(subscriber, || {
+            let _ = Policy::new(NoDelay.take(1)).retry(|| Err::<(), _>("boom"));
+        });
+
+        let entries = recorder.0.lock().unwrap();
+        assert!(entries.contains(&"attempt failed, retrying".to_string()));
+        assert!(entries.contains(&"attempts=2".to_string()));
+        assert!(entries.contains(&"giving up".to_string()));
+        assert!(entries.contains(&"error=\"boom\"".to_string()));
+    }
+}
@@ -0,0 +1,197 @@
+//! State shared between the replicas of a service, so that they can coordinate their retries of the
+//! same dependency instead of each deciding on its own.
+//!
+//! A `SharedState` is a store of counters that expire, the primitive that cross-process retry
+//! budgets and circuit breakers are built on. It can be backed by anything the replicas can all
+//! reach, such as Redis or shared memory; `InMemoryState` keeps the counters in the current
+//! process, for tests and for services that run as a single process.
+//!
+//! ```rust
+//! # use std::time::Duration;
+//! use retry::shared::{InMemoryState, SharedState};
+//!
+//! let state = InMemoryState::new();
+//! let window = Duration::from_secs(10);
+//!
+//! state.increment("payments:retries", 1, window).unwrap();
+//! state.increment("payments:retries", 1, window).unwrap();
+//!
+//! assert_eq!(state.get("payments:retries").unwrap(), Some(2));
+//! ```
+
+use std::{
+    collections::HashMap,
+    error::Error as StdError,
+    fmt::Debug,
+    sync::{Arc, Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+use crate::clock::{Clock, SystemClock};
+
+/// The error of a `SharedState` backend, such as a lost connection to the store.
+pub type StateError = Box<dyn StdError + Send + Sync>;
+
+/// A store of counters that expire, shared by every replica that retries the same dependency.
+///
+/// Keys are chosen by the components that use the state, and should be prefixed with the name of
+/// the dependency so that unrelated components do not collide.
+pub trait SharedState: Debug + Send + Sync {
+    /// The value of the counter at `key`, or `None` if it was never set or has expired.
+    fn get(&self, key: &str) -> Result<Option<u64>, StateError>;
+
+    /// Set the counter at `key` to `value`, expiring it after `ttl`.
+    fn set(&self, key: &str, value: u64, ttl: Duration) -> Result<(), StateError>;
+
+    /// Add `by` to the counter at `key` and return its new value. A counter that was never set or
+    /// has expired starts from zero and expires after `ttl`; otherwise it keeps its expiry, like
+    /// Redis's `INCRBY` followed by `EXPIRE NX`.
+    fn increment(&self, key: &str, by: u64, ttl: Duration) -> Result<u64, StateError>;
+}
+
+impl<S> SharedState for Arc<S>
+where
+    S: SharedState + ?Sized,
+{
+    fn get(&self, key: &str) -> Result<Option<u64>, StateError> {
+        (**self).get(key)
+    }
+
+    fn set(&self, key: &str, value: u64, ttl: Duration) -> Result<(), StateError> {
+        (**self).set(key, value, ttl)
+    }
+
+    fn increment(&self, key: &str, by: u64, ttl: Duration) -> Result<u64, StateError> {
+        (**self).increment(key, by, ttl)
+    }
+}
+
+/// Counters kept in the memory of the current process. Clones share the same counters.
+///
+/// Expired counters are removed when they are next read or written. A counter whose TTL is too
+/// long to add to an `Instant`, such as `Duration::MAX`, never expires.
+#[derive(Clone, Debug, Default)]
+pub struct InMemoryState {
+    counters: Arc<Mutex<HashMap<String, Counter>>>,
+    clock: Option<Arc<dyn Clock>>,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Counter {
+    value: u64,
+    /// When the counter expires, or `None` if its TTL reaches past any `Instant`.
+    expires: Option<Instant>,
+}
+
+impl InMemoryState {
+    /// Create a store without any counter.
+    pub fn new() -> Self {
+        InMemoryState::default()
+    }
+
+    /// Expire counters according to the given clock instead of the system clock.
+    pub fn with_clock<C>(mut self, clock: C) -> Self
+    where
+        C: Clock + 'static,
+    {
+        self.clock = Some(Arc::new(clock));
+        self
+    }
+
+    fn now(&self) -> Instant {
+        match self.clock {
+            Some(ref clock) => clock.now(),
+            None => SystemClock.now(),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<String, Counter>> {
+        self.counters
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// The live counter at `key`, removing it if it has expired.
+    fn live(counters: &mut HashMap<String, Counter>, key: &str, now: Instant) -> Option<Counter> {
+        match counters.get(key) {
+            Some(counter) if counter.expires.is_none_or(|expires| expires > now) => Some(*counter),
+            Some(_) => {
+                counters.remove(key);
+                None
+            }
+            None => None,
+        }
+    }
+}
+
+impl SharedState for InMemoryState {
+    fn get(&self, key: &str) -> Result<Option<u64>, StateError> {
+        let now = self.now();
+        Ok(Self::live(&mut self.lock(), key, now).map(|counter| counter.value))
+    }
+
+    fn set(&self, key: &str, value: u64, ttl: Duration) -> Result<(), StateError> {
+        let expires = self.now().checked_add(ttl);
+        self.lock()
+            .insert(key.to_owned(), Counter { value, expires });
+        Ok(())
+    }
+
+    fn increment(&self, key: &str, by: u64, ttl: Duration) -> Result<u64, StateError> {
+        let now = self.now();
+        let mut counters = self.lock();
+        let counter = match Self::live(&mut counters, key, now) {
+            Some(counter) => Counter {
+                value: counter.value.saturating_add(by),
+                expires: counter.expires,
+            },
+            None => Counter {
+                value: by,
+                expires: now.checked_add(ttl),
+            },
+        };
+        counters.insert(key.to_owned(), counter);
+        Ok(counter.value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{InMemoryState, SharedState};
+    use crate::clock::MockClock;
+
+    #[test]
+    fn counters_expire() {
+        let clock = MockClock::new();
+        let state = InMemoryState::new().with_clock(clock.clone());
+        let ttl = Duration::from_secs(10);
+
+        assert_eq!(state.increment("retries", 2, ttl).unwrap(), 2);
+        clock.advance(Duration::from_secs(6));
+        // Incrementing keeps the expiry of the counter.
+        assert_eq!(state.clone().increment("retries", 3, ttl).unwrap(), 5);
+        clock.advance(Duration::from_secs(4));
+        assert_eq!(state.get("retries").unwrap(), None);
+        assert_eq!(state.increment("retries", 1, ttl).unwrap(), 1);
+
+        state.set("open", 1, Duration::from_secs(1)).unwrap();
+        assert_eq!(state.get("open").unwrap(), Some(1));
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(state.get("open").unwrap(), None);
+    }
+
+    #[test]
+    fn counters_with_unrepresentable_ttls_never_expire() {
+        let clock = MockClock::new();
+        let state = InMemoryState::new().with_clock(clock.clone());
+
+        state.set("open", 1, Duration::MAX).unwrap();
+        assert_eq!(state.increment("retries", 2, Duration::MAX).unwrap(), 2);
+        clock.advance(Duration::from_secs(3600));
+
+        assert_eq!(state.get("open").unwrap(), Some(1));
+        assert_eq!(state.get("retries").unwrap(), Some(2));
+    }
+}
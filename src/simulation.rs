@@ -0,0 +1,358 @@
+//! Dry runs of policies, to see the schedule they follow without running or waiting for anything.
+//!
+//! `Policy::simulate` plays a policy against scripted attempts. Attempts beyond the script fail
+//! with retryable errors, so simulating an empty script gives the worst case of a bounded policy:
+//!
+//! ```rust
+//! # use std::time::Duration;
+//! # use retry::delay::Exponential;
+//! use retry::{simulation::Outcome, Policy};
+//!
+//! let policy = Policy::new(Exponential::from_millis(10)).with_max_attempts(4);
+//! let simulation = policy.simulate(None);
+//!
+//! assert_eq!(simulation.outcome(), Outcome::MaxAttempts);
+//! assert_eq!(simulation.attempts(), 4);
+//! assert_eq!(simulation.total_delay(), Duration::from_millis(1110));
+//! ```
+//!
+//! Simulations take the maximum number of attempts and the maximum delay of the policy into
+//! account, but not its attempt timeout, listeners or other hooks, which are never called.
+//!
+//! For capacity analysis, a simulation can be exported as one `ScheduleRow` per attempt, as CSV
+//! or, with the `"serde"` feature, as JSON, in the same format as the `SessionTrace` of a call
+//! actually made, so that what a policy would do can be compared with what it did:
+//!
+//! ```rust
+//! # use retry::delay::Fixed;
+//! use retry::Policy;
+//!
+//! let simulation = Policy::new(Fixed::from_millis(250).take(2)).simulate(None);
+//!
+//! assert_eq!(
+//!     simulation.to_csv(),
+//!     "attempt,outcome,latency_ms,delay_ms\n1,retry,,250\n2,retry,,250\n3,retry,,\n"
+//! );
+//! ```
+
+use std::{fmt::Write, time::Duration};
+
+use crate::Policy;
+
+/// The scripted result of one simulated attempt.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Attempt {
+    /// The attempt succeeds.
+    Succeeds,
+    /// The attempt fails with a retryable error.
+    Retries,
+    /// The attempt fails with an error that is not retried.
+    Fails,
+}
+
+/// How a simulated call ends.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Outcome {
+    /// An attempt succeeded.
+    Succeeded,
+    /// An attempt failed with an error that is not retried.
+    Failed,
+    /// The delay strategy ended.
+    Exhausted,
+    /// The maximum number of attempts was reached.
+    MaxAttempts,
+    /// The script ended before the policy gave up, and the policy has no bound on its number of
+    /// attempts, so the simulation stopped there.
+    Unfinished,
+}
+
+/// The schedule followed by a simulated call.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Simulation {
+    delays: Vec<Duration>,
+    outcome: Outcome,
+}
+
+impl Simulation {
+    /// The delays waited between attempts, in order.
+    pub fn delays(&self) -> &[Duration] {
+        &self.delays
+    }
+
+    /// The total delay before each attempt started, from zero for the first one.
+    pub fn starts(&self) -> Vec<Duration> {
+        let mut total = Duration::default();
+        let mut starts = vec![total];
+        for delay in &self.delays {
+            total = total.saturating_add(*delay);
+            starts.push(total);
+        }
+        starts
+    }
+
+    /// The sum of all delays.
+    pub fn total_delay(&self) -> Duration {
+        self.delays.iter().sum()
+    }
+
+    /// The number of attempts made, including the unscripted one the simulation stopped at if it
+    /// is `Outcome::Unfinished`.
+    pub fn attempts(&self) -> u64 {
+        self.delays.len() as u64 + 1
+    }
+
+    /// How the call ended.
+    pub fn outcome(&self) -> Outcome {
+        self.outcome
+    }
+
+    /// One row per attempt, without latencies, since simulated attempts take no time. The last
+    /// attempt of an `Outcome::Unfinished` simulation is `unfinished`.
+    pub fn rows(&self) -> Vec<ScheduleRow> {
+        let mut rows: Vec<_> = self
+            .delays
+            .iter()
+            .enumerate()
+            .map(|(index, &delay)| ScheduleRow::new(index as u64 + 1, "retry", None, Some(delay)))
+            .collect();
+        let outcome = match self.outcome {
+            Outcome::Succeeded => "ok",
+            Outcome::Failed => "err",
+            Outcome::Exhausted | Outcome::MaxAttempts => "retry",
+            Outcome::Unfinished => "unfinished",
+        };
+        rows.push(ScheduleRow::new(self.attempts(), outcome, None, None));
+        rows
+    }
+
+    /// The rows of the simulation as CSV, with a header.
+    pub fn to_csv(&self) -> String {
+        to_csv(&self.rows())
+    }
+
+    /// The rows of the simulation as a JSON array.
+    #[cfg(feature = "serde")]
+    pub fn to_json(&self) -> String {
+        to_json(&self.rows())
+    }
+}
+
+/// One attempt of a simulated or recorded call, as exported for analysis.
+///
+/// Durations are exported in milliseconds, in the `latency_ms` and `delay_ms` columns or fields,
+/// which are empty, or `null` in JSON, when they are not known or there is no next attempt.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ScheduleRow {
+    attempt: u64,
+    outcome: &'static str,
+    #[cfg_attr(
+        feature = "serde",
+        serde(rename = "latency_ms", serialize_with = "optional_millis")
+    )]
+    latency: Option<Duration>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(rename = "delay_ms", serialize_with = "optional_millis")
+    )]
+    delay: Option<Duration>,
+}
+
+impl ScheduleRow {
+    pub(crate) fn new(
+        attempt: u64,
+        outcome: &'static str,
+        latency: Option<Duration>,
+        delay: Option<Duration>,
+    ) -> Self {
+        ScheduleRow {
+            attempt,
+            outcome,
+            latency,
+            delay,
+        }
+    }
+
+    /// The number of the attempt, starting at 1.
+    pub fn attempt(&self) -> u64 {
+        self.attempt
+    }
+
+    /// How the attempt ended: `ok`, `retry`, `err`, `timed_out`, or `unfinished` for the attempt
+    /// an unfinished simulation stopped at.
+    pub fn outcome(&self) -> &'static str {
+        self.outcome
+    }
+
+    /// How long the attempt took, if it was recorded.
+    pub fn latency(&self) -> Option<Duration> {
+        self.latency
+    }
+
+    /// The delay before the next attempt, if there was one.
+    pub fn delay(&self) -> Option<Duration> {
+        self.delay
+    }
+
+    pub(crate) fn set_delay(&mut self, delay: Duration) {
+        self.delay = Some(delay);
+    }
+}
+
+/// Write `rows` as CSV, with a header.
+pub(crate) fn to_csv(rows: &[ScheduleRow]) -> String {
+    let mut csv = String::from("attempt,outcome,latency_ms,delay_ms\n");
+    for row in rows {
+        let _ = write!(csv, "{},{},", row.attempt, row.outcome);
+        write_millis(&mut csv, row.latency);
+        csv.push(',');
+        write_millis(&mut csv, row.delay);
+        csv.push('\n');
+    }
+    csv
+}
+
+fn write_millis(csv: &mut String, duration: Option<Duration>) {
+    if let Some(duration) = duration {
+        let _ = write!(csv, "{}", duration.as_secs_f64() * 1000.0);
+    }
+}
+
+/// Write `rows` as a JSON array.
+#[cfg(feature = "serde")]
+pub(crate) fn to_json(rows: &[ScheduleRow]) -> String {
+    serde_json::to_string(rows).expect("schedule rows always serialize")
+}
+
+#[cfg(feature = "serde")]
+fn optional_millis<S>(duration: &Option<Duration>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    match *duration {
+        Some(duration) => serializer.serialize_f64(duration.as_secs_f64() * 1000.0),
+        None => serializer.serialize_none(),
+    }
+}
+
+impl<D> Policy<D>
+where
+    D: IntoIterator<Item = Duration> + Clone,
+{
+    /// Simulate a call whose attempts have the given results, returning the schedule it would
+    /// follow. Attempts beyond the given ones fail with retryable errors.
+    ///
+    /// If the results run out and neither the policy nor its strategy bounds the number of
+    /// attempts, the simulation stops with `Outcome::Unfinished` rather than running forever.
+    pub fn simulate<A>(&self, attempts: A) -> Simulation
+    where
+        A: IntoIterator<Item = Attempt>,
+    {
+        let mut delays = self.delays();
+        let bounded = self.max_attempts().is_some() || delays.size_hint().1.is_some();
+        let mut attempts = attempts.into_iter();
+        let mut waited = Vec::new();
+
+        let outcome = loop {
+            let attempt = match attempts.next() {
+                Some(attempt) => attempt,
+                None if bounded => Attempt::Retries,
+                None => break Outcome::Unfinished,
+            };
+            match attempt {
+                Attempt::Succeeds => break Outcome::Succeeded,
+                Attempt::Fails => break Outcome::Failed,
+                Attempt::Retries => {}
+            }
+
+            if self.max_attempts() == Some(waited.len() as u64 + 1) {
+                break Outcome::MaxAttempts;
+            }
+            match delays.next() {
+                Some(delay) => {
+                    let delay = match self.max_delay() {
+                        Some(max_delay) => delay.min(max_delay),
+                        None => delay,
+                    };
+                    waited.push(delay.max(self.min_interval().unwrap_or_default()))
+                }
+                None => break Outcome::Exhausted,
+            }
+        };
+
+        Simulation {
+            delays: waited,
+            outcome,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{Attempt, Outcome};
+    use crate::{
+        delay::{Fibonacci, Fixed},
+        Policy,
+    };
+
+    fn millis(delays: &[u64]) -> Vec<Duration> {
+        delays
+            .iter()
+            .map(|&millis| Duration::from_millis(millis))
+            .collect()
+    }
+
+    #[test]
+    fn follows_the_script() {
+        let policy =
+            Policy::new(Fibonacci::from_millis(10)).with_max_delay(Duration::from_millis(15));
+
+        let simulation =
+            policy.simulate(vec![Attempt::Retries, Attempt::Retries, Attempt::Succeeds]);
+        assert_eq!(simulation.outcome(), Outcome::Succeeded);
+        assert_eq!(simulation.delays(), &millis(&[10, 10])[..]);
+        assert_eq!(simulation.starts(), millis(&[0, 10, 20]));
+
+        let simulation = policy.simulate(vec![Attempt::Retries, Attempt::Fails]);
+        assert_eq!(simulation.outcome(), Outcome::Failed);
+        assert_eq!(simulation.attempts(), 2);
+
+        let simulation = policy.simulate(vec![Attempt::Retries; 3]);
+        assert_eq!(simulation.outcome(), Outcome::Unfinished);
+        assert_eq!(simulation.delays(), &millis(&[10, 10, 15])[..]);
+    }
+
+    #[test]
+    fn exports_one_row_per_attempt() {
+        let policy =
+            Policy::new(Fibonacci::from_millis(10)).with_max_delay(Duration::from_millis(15));
+
+        let simulation = policy.simulate(vec![Attempt::Retries, Attempt::Succeeds]);
+        assert_eq!(
+            simulation.to_csv(),
+            "attempt,outcome,latency_ms,delay_ms\n1,retry,,10\n2,ok,,\n"
+        );
+        let rows = policy.simulate(vec![Attempt::Retries]).rows();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[1].outcome(), "unfinished");
+        assert_eq!(rows[0].delay(), Some(Duration::from_millis(10)));
+
+        #[cfg(feature = "serde")]
+        assert_eq!(
+            simulation.to_json(),
+            r#"[{"attempt":1,"outcome":"retry","latency_ms":null,"delay_ms":10.0},"#.to_owned()
+                + r#"{"attempt":2,"outcome":"ok","latency_ms":null,"delay_ms":null}]"#
+        );
+    }
+
+    #[test]
+    fn runs_bounded_policies_to_the_end() {
+        let simulation = Policy::new(Fixed::from_millis(5).take(2)).simulate(None);
+
+        assert_eq!(simulation.outcome(), Outcome::Exhausted);
+        assert_eq!(simulation.attempts(), 3);
+        assert_eq!(simulation.total_delay(), Duration::from_millis(10));
+    }
+}
@@ -0,0 +1,305 @@
+//! A `Sink` combinator that retries sending each item according to a delay strategy. This module
+//! is enabled with the `"sink"` feature.
+//!
+//! ```
+//! # use futures::{channel::mpsc, SinkExt, StreamExt};
+//! # use retry::delay::Fixed;
+//! # use retry::sink::RetrySink;
+//! # #[tokio::main] async fn main() {
+//! # let (output, _received) = mpsc::unbounded::<u32>();
+//! let (errors, mut failed) = mpsc::unbounded();
+//!
+//! let mut sink = RetrySink::new(output, Fixed::from_millis(10).take(3), move |item, error| {
+//!     let _ = errors.unbounded_send((item, error));
+//! });
+//!
+//! sink.send(1).await.unwrap();
+//! # drop(sink);
+//! # assert!(failed.next().await.is_none());
+//! # }
+//! ```
+
+use std::{
+    fmt,
+    future::Future,
+    pin::Pin,
+    task::{ready, Context, Poll},
+    time::Duration,
+};
+
+use futures_sink::Sink;
+use tokio::time::{self, Sleep};
+
+use crate::Error;
+
+/// A `Sink` that retries sending each item to the inner sink.
+///
+/// Each item is sent and flushed on its own, so that a failure can be attributed to the item that
+/// caused it. When an item fails to be sent or flushed, it is sent again after the next delay of
+/// the strategy, which is cloned for every item. If the strategy ends first, the item and the
+/// last error are passed to the `on_give_up` callback, for example to route the item to an error
+/// channel, and the sink moves on to the next item.
+///
+/// Errors from closing the inner sink, or from flushing it when no item is pending, are returned
+/// directly.
+pub struct RetrySink<S, P, Item, D>
+where
+    S: Sink<Item>,
+    P: IntoIterator<Item = Duration>,
+{
+    inner: S,
+    policy: P,
+    on_give_up: D,
+    pending: Option<Pending<Item, P::IntoIter>>,
+}
+
+struct Pending<Item, I> {
+    item: Item,
+    delays: I,
+    state: State,
+    tries: u64,
+    total_delay: Duration,
+}
+
+enum State {
+    Sending,
+    Flushing,
+    Sleeping(Pin<Box<Sleep>>),
+}
+
+impl<S, P, Item, D> RetrySink<S, P, Item, D>
+where
+    S: Sink<Item>,
+    P: IntoIterator<Item = Duration>,
+{
+    /// Create a new `RetrySink` that sends items to `inner`, retrying failed sends using the
+    /// given delay strategy and passing items that could not be sent to `on_give_up`.
+    pub fn new(inner: S, policy: P, on_give_up: D) -> Self {
+        RetrySink {
+            inner,
+            policy,
+            on_give_up,
+            pending: None,
+        }
+    }
+
+    /// Get a reference to the inner sink.
+    pub fn get_ref(&self) -> &S {
+        &self.inner
+    }
+
+    /// Consume the `RetrySink`, returning the inner sink.
+    ///
+    /// An item that is still being retried is dropped.
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+
+impl<S, P, Item, D> RetrySink<S, P, Item, D>
+where
+    S: Sink<Item> + Unpin,
+    P: IntoIterator<Item = Duration>,
+    Item: Clone,
+    D: FnMut(Item, Error<S::Error>),
+{
+    /// Drive the pending item, if any, until it has been sent or given up on.
+    fn poll_pending(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        while let Some(pending) = self.pending.as_mut() {
+            let result = match pending.state {
+                State::Sleeping(ref mut sleep) => {
+                    ready!(sleep.as_mut().poll(cx));
+                    pending.state = State::Sending;
+                    continue;
+                }
+                State::Sending => match Pin::new(&mut self.inner).poll_ready(cx) {
+                    Poll::Pending => return Poll::Pending,
+                    Poll::Ready(Ok(())) => Pin::new(&mut self.inner)
+                        .start_send(pending.item.clone())
+                        .map(|()| pending.state = State::Flushing),
+                    Poll::Ready(Err(error)) => Err(error),
+                },
+                State::Flushing => match Pin::new(&mut self.inner).poll_flush(cx) {
+                    Poll::Pending => return Poll::Pending,
+                    Poll::Ready(Ok(())) => {
+                        self.pending = None;
+                        continue;
+                    }
+                    Poll::Ready(Err(error)) => Err(error),
+                },
+            };
+
+            if let Err(error) = result {
+                if let Some(delay) = pending.delays.next() {
+                    pending.state = State::Sleeping(Box::pin(time::sleep(delay)));
+                    pending.tries += 1;
+                    pending.total_delay += delay;
+                } else if let Some(pending) = self.pending.take() {
+                    (self.on_give_up)(
+                        pending.item,
+                        Error::Operation {
+                            error,
+                            total_delay: pending.total_delay,
+                            tries: pending.tries,
+                        },
+                    );
+                }
+            }
+        }
+
+        Poll::Ready(())
+    }
+}
+
+impl<S, P, Item, D> Sink<Item> for RetrySink<S, P, Item, D>
+where
+    S: Sink<Item> + Unpin,
+    P: IntoIterator<Item = Duration> + Clone,
+    Item: Clone,
+    D: FnMut(Item, Error<S::Error>),
+{
+    type Error = S::Error;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), S::Error>> {
+        self.get_mut().poll_pending(cx).map(Ok)
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: Item) -> Result<(), S::Error> {
+        let this = self.get_mut();
+        this.pending = Some(Pending {
+            item,
+            delays: this.policy.clone().into_iter(),
+            state: State::Sending,
+            tries: 1,
+            total_delay: Duration::default(),
+        });
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), S::Error>> {
+        let this = self.get_mut();
+        ready!(this.poll_pending(cx));
+        Pin::new(&mut this.inner).poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), S::Error>> {
+        let this = self.get_mut();
+        ready!(this.poll_pending(cx));
+        Pin::new(&mut this.inner).poll_close(cx)
+    }
+}
+
+// The pending item and the strategy are never pinned, and the inner sink is required to be
+// `Unpin` wherever it is polled.
+impl<S, P, Item, D> Unpin for RetrySink<S, P, Item, D>
+where
+    S: Sink<Item> + Unpin,
+    P: IntoIterator<Item = Duration>,
+{
+}
+
+impl<S, P, Item, D> fmt::Debug for RetrySink<S, P, Item, D>
+where
+    S: Sink<Item> + fmt::Debug,
+    P: IntoIterator<Item = Duration> + fmt::Debug,
+{
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter
+            .debug_struct("RetrySink")
+            .field("inner", &self.inner)
+            .field("policy", &self.policy)
+            .field("pending", &self.pending.is_some())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        pin::Pin,
+        task::{Context, Poll},
+    };
+
+    use futures::{sink::Sink, SinkExt};
+
+    use super::RetrySink;
+    use crate::{delay::NoDelay, Error};
+
+    /// A sink that rejects the first `failures` sends.
+    #[derive(Debug, Default)]
+    struct FlakySink {
+        failures: u64,
+        received: Vec<u32>,
+    }
+
+    impl Sink<u32> for FlakySink {
+        type Error = &'static str;
+
+        fn poll_ready(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn start_send(mut self: Pin<&mut Self>, item: u32) -> Result<(), Self::Error> {
+            if self.failures > 0 {
+                self.failures -= 1;
+                return Err("rejected");
+            }
+            self.received.push(item);
+            Ok(())
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_close(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    #[tokio::test]
+    async fn retries_failed_sends() {
+        let inner = FlakySink {
+            failures: 2,
+            ..FlakySink::default()
+        };
+        let mut failed = Vec::new();
+        let mut sink = RetrySink::new(inner, NoDelay.take(2), |item, error| {
+            failed.push((item, error))
+        });
+
+        sink.send(1).await.unwrap();
+        sink.send(2).await.unwrap();
+
+        assert_eq!(sink.into_inner().received, vec![1, 2]);
+        assert!(failed.is_empty());
+    }
+
+    #[tokio::test]
+    async fn gives_up_on_items_after_last_try() {
+        let inner = FlakySink {
+            failures: 2,
+            ..FlakySink::default()
+        };
+        let mut failed = Vec::new();
+        let mut sink = RetrySink::new(inner, NoDelay.take(1), |item, error| {
+            failed.push((item, error))
+        });
+
+        sink.send(1).await.unwrap();
+        sink.send(2).await.unwrap();
+
+        assert_eq!(sink.into_inner().received, vec![2]);
+        assert_eq!(
+            failed,
+            vec![(
+                1,
+                Error::Operation {
+                    error: "rejected",
+                    total_delay: Default::default(),
+                    tries: 2,
+                }
+            )]
+        );
+    }
+}
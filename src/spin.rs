@@ -0,0 +1,162 @@
+//! Backing off in tight loops over shared memory, such as retrying a failed compare-and-swap,
+//! where sleeping for even a millisecond is far too long.
+//!
+//! A `Backoff` escalates from busy-waiting a few cycles with the spin loop hint, doubling them
+//! each step, to yielding the thread to the scheduler, and tells when waiting has gone on long
+//! enough that the caller should block instead, such as on a `Mutex` or with one of the delay
+//! strategies of the crate.
+//!
+//! ```rust
+//! # use std::sync::atomic::{AtomicU64, Ordering};
+//! use retry::spin::Backoff;
+//!
+//! fn add_saturating(counter: &AtomicU64, amount: u64) -> u64 {
+//!     let backoff = Backoff::new();
+//!     let mut current = counter.load(Ordering::Relaxed);
+//!     loop {
+//!         let new = current.saturating_add(amount);
+//!         match counter.compare_exchange_weak(current, new, Ordering::AcqRel, Ordering::Relaxed) {
+//!             Ok(_) => return new,
+//!             Err(actual) => current = actual,
+//!         }
+//!         backoff.spin();
+//!     }
+//! }
+//!
+//! let counter = AtomicU64::new(u64::MAX - 1);
+//! assert_eq!(add_saturating(&counter, 5), u64::MAX);
+//! ```
+
+use core::{cell::Cell, fmt, hint};
+
+/// The step after which `spin` stops doubling the busy-waiting and `snooze` starts yielding.
+const SPIN_LIMIT: u32 = 6;
+/// The step after which `snooze` considers the waiting completed.
+const YIELD_LIMIT: u32 = 10;
+
+/// Exponential backoff for spinning loops, waiting nanoseconds to microseconds at each step
+/// rather than sleeping.
+///
+/// Each step of `spin` busy-waits twice as many cycles as the last one, up to 64. `snooze` does
+/// the same at first, then yields the thread to the scheduler, and `is_completed` tells when it
+/// has yielded long enough that blocking is the better way of waiting. Without the `"std"`
+/// feature, there is no thread to yield, and `snooze` keeps busy-waiting.
+///
+/// A `Backoff` is meant to be local to one loop, so it is neither `Sync` nor shared between
+/// threads.
+///
+/// ```rust
+/// # use std::sync::atomic::{AtomicBool, Ordering};
+/// use retry::spin::Backoff;
+///
+/// let ready = AtomicBool::new(true);
+/// let backoff = Backoff::new();
+/// while !ready.load(Ordering::Acquire) {
+///     if backoff.is_completed() {
+///         std::thread::park();
+///     } else {
+///         backoff.snooze();
+///     }
+/// }
+/// ```
+pub struct Backoff {
+    step: Cell<u32>,
+}
+
+impl Backoff {
+    /// Create a backoff at its first step.
+    pub fn new() -> Self {
+        Backoff { step: Cell::new(0) }
+    }
+
+    /// Go back to the first step, such as after the loop made progress.
+    pub fn reset(&self) {
+        self.step.set(0);
+    }
+
+    /// Busy-wait after a failed attempt at an operation on shared memory that another thread keeps
+    /// changing, such as a compare-and-swap, whose next attempt may succeed at any moment.
+    pub fn spin(&self) {
+        for _ in 0..1 << self.step.get().min(SPIN_LIMIT) {
+            hint::spin_loop();
+        }
+        if self.step.get() <= SPIN_LIMIT {
+            self.step.set(self.step.get() + 1);
+        }
+    }
+
+    /// Wait for another thread to make progress, such as to release a flag, busy-waiting at first
+    /// and yielding the thread once that has gone on for a while.
+    pub fn snooze(&self) {
+        if self.step.get() <= SPIN_LIMIT {
+            for _ in 0..1 << self.step.get() {
+                hint::spin_loop();
+            }
+        } else {
+            #[cfg(feature = "std")]
+            std::thread::yield_now();
+            #[cfg(not(feature = "std"))]
+            for _ in 0..1 << SPIN_LIMIT {
+                hint::spin_loop();
+            }
+        }
+        if self.step.get() <= YIELD_LIMIT {
+            self.step.set(self.step.get() + 1);
+        }
+    }
+
+    /// Whether `snooze` has waited long enough that the caller should block instead.
+    pub fn is_completed(&self) -> bool {
+        self.step.get() > YIELD_LIMIT
+    }
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Backoff::new()
+    }
+}
+
+impl fmt::Debug for Backoff {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter
+            .debug_struct("Backoff")
+            .field("step", &self.step.get())
+            .field("is_completed", &self.is_completed())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Backoff, SPIN_LIMIT, YIELD_LIMIT};
+
+    #[test]
+    fn snoozing_completes_after_yielding() {
+        let backoff = Backoff::new();
+        for _ in 0..=YIELD_LIMIT {
+            assert!(!backoff.is_completed());
+            backoff.snooze();
+        }
+        assert!(backoff.is_completed());
+        backoff.snooze();
+        assert!(backoff.is_completed());
+
+        backoff.reset();
+        assert!(!backoff.is_completed());
+        assert_eq!(
+            format!("{:?}", backoff),
+            "Backoff { step: 0, is_completed: false }"
+        );
+    }
+
+    #[test]
+    fn spinning_never_completes() {
+        let backoff = Backoff::new();
+        for _ in 0..100 {
+            backoff.spin();
+        }
+        assert!(!backoff.is_completed());
+        assert_eq!(backoff.step.get(), SPIN_LIMIT + 1);
+    }
+}
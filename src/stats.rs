@@ -0,0 +1,155 @@
+use crate::sync::{Arc, AtomicU64, Ordering};
+
+/// A snapshot of the calls made through a policy and its clones, as returned by `Policy::stats`,
+/// for example to report from a health endpoint.
+///
+/// ```rust
+/// # use retry::delay::NoDelay;
+/// use retry::Policy;
+///
+/// let policy = Policy::new(NoDelay.take(2));
+/// let mut attempts = 0;
+/// let _ = policy.retry(|| {
+///     attempts += 1;
+///     if attempts < 2 { Err("busy") } else { Ok(()) }
+/// });
+/// let _ = policy.clone().retry(|| Err::<(), _>("down"));
+///
+/// let stats = policy.stats();
+/// assert_eq!((stats.sessions(), stats.attempts(), stats.give_ups()), (2, 5, 1));
+/// assert_eq!(stats.average_attempts_per_success(), Some(2.0));
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct PolicyStats {
+    sessions: u64,
+    attempts: u64,
+    give_ups: u64,
+    in_flight: u64,
+    successes: u64,
+    success_attempts: u64,
+}
+
+impl PolicyStats {
+    /// The number of calls started, including those still in progress.
+    pub fn sessions(&self) -> u64 {
+        self.sessions
+    }
+
+    /// The number of attempts made by all the calls.
+    pub fn attempts(&self) -> u64 {
+        self.attempts
+    }
+
+    /// The number of calls that gave up.
+    pub fn give_ups(&self) -> u64 {
+        self.give_ups
+    }
+
+    /// The number of calls in progress.
+    pub fn in_flight(&self) -> u64 {
+        self.in_flight
+    }
+
+    /// The number of calls that succeeded.
+    pub fn successes(&self) -> u64 {
+        self.successes
+    }
+
+    /// The average number of attempts made by the calls that succeeded, or `None` if none has.
+    pub fn average_attempts_per_success(&self) -> Option<f64> {
+        if self.successes == 0 {
+            None
+        } else {
+            Some(self.success_attempts as f64 / self.successes as f64)
+        }
+    }
+}
+
+/// The counters behind `PolicyStats`, shared by a policy and its clones.
+#[derive(Debug, Default)]
+pub(crate) struct Counters {
+    sessions: AtomicU64,
+    attempts: AtomicU64,
+    give_ups: AtomicU64,
+    in_flight: Arc<AtomicU64>,
+    successes: AtomicU64,
+    success_attempts: AtomicU64,
+}
+
+impl Counters {
+    /// Count a new call, which is in flight until the returned guard is dropped.
+    pub(crate) fn enter(&self) -> InFlight {
+        self.sessions.fetch_add(1, Ordering::Relaxed);
+        self.in_flight.fetch_add(1, Ordering::Relaxed);
+        InFlight(Arc::clone(&self.in_flight))
+    }
+
+    pub(crate) fn record_attempt(&self) {
+        self.attempts.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_success(&self, attempts: u64) {
+        self.successes.fetch_add(1, Ordering::Relaxed);
+        self.success_attempts.fetch_add(attempts, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_give_up(&self) {
+        self.give_ups.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Read every counter. The counters are read one after the other, so calls that end meanwhile
+    /// may be counted in some of them and not in others.
+    pub(crate) fn snapshot(&self) -> PolicyStats {
+        PolicyStats {
+            sessions: self.sessions.load(Ordering::Relaxed),
+            attempts: self.attempts.load(Ordering::Relaxed),
+            give_ups: self.give_ups.load(Ordering::Relaxed),
+            in_flight: self.in_flight.load(Ordering::Relaxed),
+            successes: self.successes.load(Ordering::Relaxed),
+            success_attempts: self.success_attempts.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// A call in flight, no longer counted as such once dropped.
+#[derive(Debug)]
+pub(crate) struct InFlight(Arc<AtomicU64>);
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+
+    use crate::{delay::NoDelay, Policy};
+
+    #[test]
+    fn counts_calls_in_flight() {
+        let policy = Policy::new(NoDelay.take(1));
+        let (entered, attempting) = mpsc::channel();
+        let (release, released) = mpsc::channel::<()>();
+
+        let call = {
+            let policy = policy.clone();
+            std::thread::spawn(move || {
+                policy.retry(|| {
+                    entered.send(()).unwrap();
+                    released.recv().unwrap();
+                    Ok::<_, ()>(())
+                })
+            })
+        };
+        attempting.recv().unwrap();
+        assert_eq!(policy.stats().in_flight(), 1);
+        assert_eq!(policy.stats().average_attempts_per_success(), None);
+
+        release.send(()).unwrap();
+        assert_eq!(call.join().unwrap(), Ok(()));
+        let stats = policy.stats();
+        assert_eq!((stats.in_flight(), stats.successes()), (0, 1));
+    }
+}
@@ -0,0 +1,250 @@
+use std::{
+    any::Any,
+    collections::VecDeque,
+    error::Error as StdError,
+    fmt, io,
+    sync::Arc,
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    clock::{Clock, SystemClock},
+    Error, OperationResult, Policy,
+};
+
+/// Keeps a worker thread running, spawning a new thread with the delays of a policy whenever the
+/// worker panics, and giving up when the policy ends or the worker restarted too often lately.
+///
+/// Every run of the worker is a thread of its own, so a panic does not leave anything of the run
+/// it interrupted behind, such as thread-local state. Panics are restarted like failed attempts
+/// of `Policy::retry`, so the policy's delays, maximum number of attempts and listeners apply,
+/// and a worker that returns ends the supervision with its value.
+///
+/// ```rust
+/// # use std::{sync::atomic::{AtomicU64, Ordering}, time::Duration};
+/// # use retry::delay::Exponential;
+/// use retry::{Policy, ThreadSupervisor};
+///
+/// static RUNS: AtomicU64 = AtomicU64::new(0);
+///
+/// let supervisor = ThreadSupervisor::new(Policy::new(Exponential::from_millis(1).take(5)))
+///     .with_name("indexer")
+///     .with_max_restarts(3, Duration::from_secs(60));
+/// let worker = supervisor.spawn(|| {
+///     if RUNS.fetch_add(1, Ordering::SeqCst) == 0 {
+///         panic!("lost the connection to the index");
+///     }
+///     "drained"
+/// });
+///
+/// assert_eq!(worker.join().unwrap().ok(), Some("drained"));
+/// ```
+#[derive(Clone, Debug)]
+pub struct ThreadSupervisor<D> {
+    policy: Policy<D>,
+    name: Option<String>,
+    max_restarts: Option<(usize, Duration)>,
+    clock: Option<Arc<dyn Clock>>,
+}
+
+impl<D> ThreadSupervisor<D>
+where
+    D: IntoIterator<Item = Duration> + Clone + Send + 'static,
+{
+    /// Restart the worker according to `policy`.
+    pub fn new(policy: Policy<D>) -> Self {
+        ThreadSupervisor {
+            policy,
+            name: None,
+            max_restarts: None,
+            clock: None,
+        }
+    }
+
+    /// Name the threads of the worker, as with `thread::Builder::name`.
+    pub fn with_name<N>(mut self, name: N) -> Self
+    where
+        N: Into<String>,
+    {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Give up instead of restarting the worker once it was restarted `max_restarts` times over
+    /// the last `window`, however many attempts the policy has left.
+    pub fn with_max_restarts(mut self, max_restarts: usize, window: Duration) -> Self {
+        self.max_restarts = Some((max_restarts, window));
+        self
+    }
+
+    /// Measure the window of restarts with the given clock instead of the system clock.
+    pub fn with_clock<C>(mut self, clock: C) -> Self
+    where
+        C: Clock + 'static,
+    {
+        self.clock = Some(Arc::new(clock));
+        self
+    }
+
+    fn now(&self) -> Instant {
+        match self.clock {
+            Some(ref clock) => clock.now(),
+            None => SystemClock.now(),
+        }
+    }
+
+    /// Spawn a supervising thread that runs `worker` in a thread of its own until it returns,
+    /// restarting it whenever it panics, and returns what the worker returned, or the error it
+    /// gave up with.
+    pub fn spawn<F, R>(self, worker: F) -> JoinHandle<Result<R, Error<WorkerError>>>
+    where
+        F: Fn() -> R + Send + Sync + 'static,
+        R: Send + 'static,
+    {
+        let worker = Arc::new(worker);
+        thread::spawn(move || {
+            let mut restarts = VecDeque::new();
+            self.policy.retry(|| {
+                let worker = Arc::clone(&worker);
+                let mut builder = thread::Builder::new();
+                if let Some(ref name) = self.name {
+                    builder = builder.name(name.clone());
+                }
+                let error = match builder.spawn(move || worker()) {
+                    Ok(handle) => match handle.join() {
+                        Ok(value) => return OperationResult::Ok(value),
+                        Err(payload) => WorkerError::Panicked(panic_message(payload)),
+                    },
+                    Err(error) => WorkerError::Spawn(error),
+                };
+                if self.may_restart(&mut restarts) {
+                    OperationResult::Retry(error)
+                } else {
+                    OperationResult::Err(error)
+                }
+            })
+        })
+    }
+
+    /// Count a restart in the window, unless the window is full.
+    fn may_restart(&self, restarts: &mut VecDeque<Instant>) -> bool {
+        let (max_restarts, window) = match self.max_restarts {
+            Some(max_restarts) => max_restarts,
+            None => return true,
+        };
+        let now = self.now();
+        while restarts
+            .front()
+            .is_some_and(|&restart| now.saturating_duration_since(restart) >= window)
+        {
+            restarts.pop_front();
+        }
+        if restarts.len() >= max_restarts {
+            return false;
+        }
+        restarts.push_back(now);
+        true
+    }
+}
+
+/// Why a run of a supervised worker failed.
+#[derive(Debug)]
+pub enum WorkerError {
+    /// The worker panicked, with the given message.
+    Panicked(String),
+    /// The thread of the worker could not be spawned.
+    Spawn(io::Error),
+}
+
+impl fmt::Display for WorkerError {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            WorkerError::Panicked(ref message) => {
+                write!(formatter, "the worker panicked: {}", message)
+            }
+            WorkerError::Spawn(ref error) => {
+                write!(formatter, "failed to spawn the worker: {}", error)
+            }
+        }
+    }
+}
+
+impl StdError for WorkerError {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match *self {
+            WorkerError::Panicked(_) => None,
+            WorkerError::Spawn(ref error) => Some(error),
+        }
+    }
+}
+
+/// The message of a panic, if it was given one.
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => match payload.downcast::<&'static str>() {
+            Ok(message) => (*message).to_owned(),
+            Err(_) => "a value that is not a message".to_owned(),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::atomic::{AtomicU64, Ordering},
+        thread,
+        time::Duration,
+    };
+
+    use super::{ThreadSupervisor, WorkerError};
+    use crate::{clock::MockClock, delay::NoDelay, Error, Policy};
+
+    #[test]
+    fn restarts_panicked_workers_in_new_threads() {
+        static RUNS: AtomicU64 = AtomicU64::new(0);
+
+        let worker = ThreadSupervisor::new(Policy::new(NoDelay.take(5)))
+            .with_name("worker")
+            .spawn(|| {
+                assert_eq!(thread::current().name(), Some("worker"));
+                if RUNS.fetch_add(1, Ordering::SeqCst) < 2 {
+                    panic!("run {} failed", RUNS.load(Ordering::SeqCst));
+                }
+                RUNS.load(Ordering::SeqCst)
+            });
+
+        assert_eq!(worker.join().unwrap().ok(), Some(3));
+    }
+
+    #[test]
+    fn caps_restarts_per_window() {
+        let clock = MockClock::new();
+        let runs = std::sync::Arc::new(AtomicU64::new(0));
+        let counted = std::sync::Arc::clone(&runs);
+        let advancing = clock.clone();
+
+        let worker = ThreadSupervisor::new(Policy::new(NoDelay.take(10)))
+            .with_max_restarts(2, Duration::from_secs(60))
+            .with_clock(clock.clone())
+            .spawn(move || {
+                // The first two restarts fall out of the window before the third one.
+                if counted.fetch_add(1, Ordering::SeqCst) == 2 {
+                    advancing.advance(Duration::from_secs(61));
+                }
+                panic!("boom")
+            });
+
+        let error = worker.join().unwrap().unwrap_err();
+        assert_eq!(runs.load(Ordering::SeqCst), 5);
+        assert!(matches!(
+            error,
+            Error::Operation {
+                error: WorkerError::Panicked(ref message),
+                tries: 5,
+                ..
+            } if message == "boom"
+        ));
+    }
+}
@@ -0,0 +1,35 @@
+//! The synchronization primitives of the state that calls share across threads, such as the places
+//! of a bulkhead, the state of a circuit breaker and the counters of a policy's statistics.
+//!
+//! That state sits on the path of every call, so it is kept in atomics rather than behind locks,
+//! and every decision is made with a single atomic operation: there is no lock to contend for and
+//! no thread that can hold up the others. The atomics are the only data the components share, so
+//! orderings are chosen as follows:
+//!
+//! - Counters that are only reported, such as the statistics, use `Relaxed`, since nothing is read
+//!   on the strength of their values.
+//! - Words whose value admits or rejects a call, such as the places of a bulkhead or the state of a
+//!   circuit breaker, are updated with `AcqRel` compare-and-swap loops and read with `Acquire`, so
+//!   that a thread that sees a transition also sees what the thread that made it did before.
+//!
+//! Built with `RUSTFLAGS="--cfg retry_loom"`, the primitives are those of `loom`, whose model
+//! checker runs the tests under `cfg(retry_loom)` in every interleaving that matters. The `cfg`
+//! is not `loom`, so that dependencies such as Tokio are built as usual:
+//!
+//! ```text
+//! RUSTFLAGS="--cfg retry_loom" cargo test --lib --release loom
+//! ```
+//!
+//! Loom's atomics can only be used inside its models, so the other tests do not run in that
+//! configuration.
+
+#[cfg(retry_loom)]
+pub(crate) use loom::sync::{
+    atomic::{AtomicU64, AtomicUsize, Ordering},
+    Arc,
+};
+#[cfg(not(retry_loom))]
+pub(crate) use std::sync::{
+    atomic::{AtomicU64, AtomicUsize, Ordering},
+    Arc,
+};
@@ -0,0 +1,636 @@
+//! Test doubles for code that retries. This module is enabled with the `"test-util"` feature.
+//!
+//! A `FakeSleeper` records the delays it is asked to wait instead of waiting, so tests can check a
+//! schedule without taking as long as it:
+//!
+//! ```rust
+//! # use std::time::Duration;
+//! # use retry::delay::Exponential;
+//! use retry::{testing::FakeSleeper, Policy};
+//!
+//! let sleeper = FakeSleeper::new();
+//! let policy = Policy::new(Exponential::from_millis(10).take(3)).with_sleeper(sleeper.clone());
+//!
+//! let _ = policy.retry(|| Err::<(), _>("down"));
+//!
+//! sleeper.assert_slept_millis(&[10, 100, 1000]);
+//! ```
+//!
+//! A `FlakyOp` fails a given number of times before succeeding, and the `assert_gives_up_after!`
+//! and `assert_succeeds_after!` macros check how many attempts a policy makes:
+//!
+//! ```rust
+//! # use retry::delay::NoDelay;
+//! use retry::{assert_gives_up_after, assert_succeeds_after, testing::FlakyOp, Policy};
+//!
+//! let policy = Policy::new(NoDelay).with_max_attempts(5);
+//!
+//! assert_gives_up_after!(policy, FlakyOp::fails_n_times(10).into_fn(), attempts = 5);
+//! assert_succeeds_after!(policy, FlakyOp::fails_n_times(2).into_fn(), attempts = 3);
+//! ```
+
+use std::{
+    error::Error as StdError,
+    fmt,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, MutexGuard,
+    },
+    time::Duration,
+};
+
+use crate::{
+    clock::{MockClock, Sleeper},
+    OperationResult,
+};
+
+/// A sleeper that returns immediately, recording every delay it was asked to wait. Clones share
+/// the same record.
+///
+/// When created with a `MockClock`, the clock is advanced by every delay, as if the time had
+/// passed.
+#[derive(Clone, Debug, Default)]
+pub struct FakeSleeper {
+    sleeps: Arc<Mutex<Vec<Duration>>>,
+    clock: Option<MockClock>,
+}
+
+impl FakeSleeper {
+    /// Create a sleeper that has not slept yet.
+    pub fn new() -> Self {
+        FakeSleeper::default()
+    }
+
+    /// Create a sleeper that advances `clock` by every delay.
+    pub fn with_clock(clock: MockClock) -> Self {
+        FakeSleeper {
+            sleeps: Arc::default(),
+            clock: Some(clock),
+        }
+    }
+
+    /// The delays waited so far, in order.
+    pub fn sleeps(&self) -> Vec<Duration> {
+        self.lock().clone()
+    }
+
+    /// The sum of the delays waited so far.
+    pub fn total(&self) -> Duration {
+        self.lock().iter().sum()
+    }
+
+    /// Forget the delays waited so far.
+    pub fn clear(&self) {
+        self.lock().clear();
+    }
+
+    /// Assert that exactly the given delays were waited, in order.
+    ///
+    /// # Panics
+    ///
+    /// Panics with both schedules if they differ.
+    #[track_caller]
+    pub fn assert_slept(&self, expected: &[Duration]) {
+        let sleeps = self.sleeps();
+        assert!(
+            sleeps == expected,
+            "expected to sleep {:?}, but slept {:?}",
+            expected,
+            sleeps
+        );
+    }
+
+    /// Assert that exactly the given delays, in milliseconds, were waited, in order.
+    ///
+    /// # Panics
+    ///
+    /// Panics with both schedules if they differ.
+    #[track_caller]
+    pub fn assert_slept_millis(&self, expected: &[u64]) {
+        let expected: Vec<_> = expected
+            .iter()
+            .map(|&millis| Duration::from_millis(millis))
+            .collect();
+        self.assert_slept(&expected);
+    }
+
+    fn record(&self, duration: Duration) {
+        self.lock().push(duration);
+        if let Some(ref clock) = self.clock {
+            clock.advance(duration);
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Vec<Duration>> {
+        self.sleeps
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl Sleeper for FakeSleeper {
+    fn sleep(&self, duration: Duration) {
+        self.record(duration);
+    }
+}
+
+/// Completes immediately, so asynchronous retries can be checked the same way with
+/// `asynchronous::retry_with_sleeper`.
+#[cfg(feature = "asynchronous")]
+impl crate::asynchronous::AsyncSleeper for FakeSleeper {
+    type Sleep = std::future::Ready<()>;
+
+    fn sleep(&self, duration: Duration) -> Self::Sleep {
+        self.record(duration);
+        std::future::ready(())
+    }
+}
+
+/// A scripted operation, to exercise policies without a real dependency.
+///
+/// The script is a sequence of steps, each run for a number of calls: retryable failures, failures
+/// with a given message, permanent failures, and successes, which return the number of the attempt.
+/// Calls beyond the script succeed, unless the script ends with `then_fail`. Latency can be added
+/// to every call, waited with a sleeper such as a `FakeSleeper` advancing a `MockClock` so that
+/// no real time passes. Clones share the same count of calls.
+///
+/// ```rust
+/// # use std::time::Duration;
+/// # use retry::delay::NoDelay;
+/// use retry::{testing::FlakyOp, Policy};
+///
+/// let operation = FlakyOp::new()
+///     .fail_times(2)
+///     .fail_with("connection reset")
+///     .then_succeed()
+///     .with_latency(Duration::from_millis(1));
+/// let policy = Policy::new(NoDelay.take(5));
+///
+/// assert_eq!(policy.retry(operation.clone().into_fn()), Ok(4));
+/// assert_eq!(operation.calls(), 4);
+/// ```
+#[derive(Clone, Debug)]
+pub struct FlakyOp {
+    script: Vec<Run>,
+    then: Then,
+    latency: Option<Duration>,
+    sleeper: Option<Arc<dyn Sleeper>>,
+    calls: Arc<AtomicU64>,
+}
+
+/// A step of a script, run for a number of calls.
+#[derive(Clone, Copy, Debug)]
+struct Run {
+    times: u64,
+    step: Step,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Step {
+    Succeed,
+    Fail {
+        message: Option<&'static str>,
+        permanent: bool,
+    },
+}
+
+/// What the calls beyond the script do.
+#[derive(Clone, Copy, Debug)]
+enum Then {
+    Succeed,
+    Fail,
+    #[cfg(feature = "random")]
+    FailRandomly(f64),
+}
+
+impl Default for FlakyOp {
+    fn default() -> Self {
+        FlakyOp::new()
+    }
+}
+
+impl FlakyOp {
+    /// Create an operation with an empty script, which always succeeds until steps are added.
+    pub fn new() -> Self {
+        FlakyOp {
+            script: Vec::new(),
+            then: Then::Succeed,
+            latency: None,
+            sleeper: None,
+            calls: Arc::default(),
+        }
+    }
+
+    /// Create an operation whose first `failures` calls fail.
+    pub fn fails_n_times(failures: u64) -> Self {
+        FlakyOp::new().fail_times(failures)
+    }
+
+    /// Create an operation that never succeeds.
+    pub fn always_fails() -> Self {
+        FlakyOp::new().then_fail()
+    }
+
+    /// Fail the next `times` calls with retryable errors.
+    pub fn fail_times(self, times: u64) -> Self {
+        self.step(
+            times,
+            Step::Fail {
+                message: None,
+                permanent: false,
+            },
+        )
+    }
+
+    /// Fail the next call with a retryable error with the given message.
+    pub fn fail_with(self, message: &'static str) -> Self {
+        self.step(
+            1,
+            Step::Fail {
+                message: Some(message),
+                permanent: false,
+            },
+        )
+    }
+
+    /// Fail the next call with a permanent error with the given message, which `call_outcome`
+    /// reports as an error that is not retried.
+    pub fn fail_permanently(self, message: &'static str) -> Self {
+        self.step(
+            1,
+            Step::Fail {
+                message: Some(message),
+                permanent: true,
+            },
+        )
+    }
+
+    /// Succeed on the next `times` calls.
+    pub fn succeed_times(self, times: u64) -> Self {
+        self.step(times, Step::Succeed)
+    }
+
+    /// Succeed on every call beyond the script, which is the default.
+    pub fn then_succeed(mut self) -> Self {
+        self.then = Then::Succeed;
+        self
+    }
+
+    /// Fail every call beyond the script with a retryable error.
+    pub fn then_fail(mut self) -> Self {
+        self.then = Then::Fail;
+        self
+    }
+
+    /// Fail every call beyond the script with a retryable error with the given probability, from
+    /// 0 to 1, for chaos experiments.
+    ///
+    /// This method is enabled with the `"random"` feature.
+    #[cfg(feature = "random")]
+    pub fn then_fail_randomly(mut self, probability: f64) -> Self {
+        self.then = Then::FailRandomly(probability);
+        self
+    }
+
+    /// Wait `latency` in every call before returning, with the system's sleep unless a sleeper is
+    /// given with `with_sleeper`.
+    pub fn with_latency(mut self, latency: Duration) -> Self {
+        self.latency = Some(latency);
+        self
+    }
+
+    /// Wait the latency of every call with the given sleeper, such as a `FakeSleeper` that
+    /// advances a `MockClock` instead of waiting.
+    pub fn with_sleeper<S>(mut self, sleeper: S) -> Self
+    where
+        S: Sleeper + 'static,
+    {
+        self.sleeper = Some(Arc::new(sleeper));
+        self
+    }
+
+    fn step(mut self, times: u64, step: Step) -> Self {
+        if times > 0 {
+            self.script.push(Run { times, step });
+        }
+        self
+    }
+
+    /// Make one attempt.
+    pub fn call(&self) -> Result<u64, FlakyError> {
+        let attempt = self.start();
+        if let Some(latency) = self.latency {
+            match self.sleeper {
+                Some(ref sleeper) => sleeper.sleep(latency),
+                None => std::thread::sleep(latency),
+            }
+        }
+        self.result(attempt)
+    }
+
+    /// Make one attempt, asynchronously. Without a sleeper, the latency is awaited with the
+    /// `DefaultSleeper` of the `"asynchronous"` feature, and slept on the thread otherwise.
+    pub async fn call_async(&self) -> Result<u64, FlakyError> {
+        let attempt = self.start();
+        if let Some(latency) = self.latency {
+            match self.sleeper {
+                Some(ref sleeper) => sleeper.sleep(latency),
+                #[cfg(feature = "asynchronous")]
+                None => {
+                    use crate::asynchronous::{AsyncSleeper, DefaultSleeper};
+                    DefaultSleeper::default().sleep(latency).await
+                }
+                #[cfg(not(feature = "asynchronous"))]
+                None => std::thread::sleep(latency),
+            }
+        }
+        self.result(attempt)
+    }
+
+    /// Make one attempt, reporting permanent failures as errors that are not retried.
+    pub fn call_outcome(&self) -> OperationResult<u64, FlakyError> {
+        match self.call() {
+            Ok(attempt) => OperationResult::Ok(attempt),
+            Err(error) if error.is_permanent() => OperationResult::Err(error),
+            Err(error) => OperationResult::Retry(error),
+        }
+    }
+
+    /// The number of attempts made so far.
+    pub fn calls(&self) -> u64 {
+        self.calls.load(Ordering::SeqCst)
+    }
+
+    /// Turn the operation into a closure that can be passed to `retry` methods, sharing its count
+    /// of calls with the clones of this operation.
+    pub fn into_fn(self) -> impl FnMut() -> Result<u64, FlakyError> {
+        move || self.call()
+    }
+
+    /// Turn the operation into a closure like `into_fn`, which makes its attempts with
+    /// `call_outcome`, so that permanent failures are not retried.
+    pub fn into_outcome_fn(self) -> impl FnMut() -> OperationResult<u64, FlakyError> {
+        move || self.call_outcome()
+    }
+
+    fn start(&self) -> u64 {
+        self.calls.fetch_add(1, Ordering::SeqCst) + 1
+    }
+
+    fn result(&self, attempt: u64) -> Result<u64, FlakyError> {
+        match self.scripted(attempt) {
+            Step::Succeed => Ok(attempt),
+            Step::Fail { message, permanent } => Err(FlakyError {
+                attempt,
+                message,
+                permanent,
+            }),
+        }
+    }
+
+    /// The step of the script for the given attempt.
+    fn scripted(&self, attempt: u64) -> Step {
+        let mut remaining = attempt;
+        for run in &self.script {
+            if remaining <= run.times {
+                return run.step;
+            }
+            remaining -= run.times;
+        }
+        let fail = Step::Fail {
+            message: None,
+            permanent: false,
+        };
+        match self.then {
+            Then::Succeed => Step::Succeed,
+            Then::Fail => fail,
+            #[cfg(feature = "random")]
+            Then::FailRandomly(probability) => {
+                if rand::Rng::gen_bool(&mut rand::thread_rng(), probability.clamp(0.0, 1.0)) {
+                    fail
+                } else {
+                    Step::Succeed
+                }
+            }
+        }
+    }
+}
+
+/// The error returned by the failing attempts of a `FlakyOp`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FlakyError {
+    attempt: u64,
+    message: Option<&'static str>,
+    permanent: bool,
+}
+
+impl FlakyError {
+    /// The number of the attempt that failed.
+    pub fn attempt(&self) -> u64 {
+        self.attempt
+    }
+
+    /// The message the attempt was scripted to fail with, if any.
+    pub fn message(&self) -> Option<&'static str> {
+        self.message
+    }
+
+    /// Whether the attempt was scripted to fail permanently.
+    pub fn is_permanent(&self) -> bool {
+        self.permanent
+    }
+}
+
+impl fmt::Display for FlakyError {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(formatter, "attempt {} failed", self.attempt)?;
+        if self.permanent {
+            formatter.write_str(" permanently")?;
+        }
+        match self.message {
+            Some(message) => write!(formatter, ": {}", message),
+            None => Ok(()),
+        }
+    }
+}
+
+impl StdError for FlakyError {}
+
+/// Assert that retrying an operation with a policy gives up after exactly the given number of
+/// attempts, evaluating to the returned `retry::Error`.
+///
+/// This macro is enabled with the `"test-util"` feature.
+#[macro_export]
+macro_rules! assert_gives_up_after {
+    ($policy:expr, $operation:expr, attempts = $attempts:expr $(,)?) => {{
+        let attempts: u64 = $attempts;
+        match $policy.retry($operation) {
+            Ok(_) => panic!(
+                "expected the policy to give up after {} attempts, but the operation succeeded",
+                attempts
+            ),
+            Err(error) => {
+                assert!(
+                    error.tries() == attempts,
+                    "expected the policy to give up after {} attempts, but it gave up after {}",
+                    attempts,
+                    error.tries()
+                );
+                error
+            }
+        }
+    }};
+}
+
+/// Assert that retrying a `FlakyOp` with a policy succeeds on exactly the given attempt,
+/// evaluating to the value returned.
+///
+/// This macro is enabled with the `"test-util"` feature.
+#[macro_export]
+macro_rules! assert_succeeds_after {
+    ($policy:expr, $operation:expr, attempts = $attempts:expr $(,)?) => {{
+        let attempts: u64 = $attempts;
+        match $policy.retry($operation) {
+            Ok(attempt) => {
+                assert!(
+                    attempt == attempts,
+                    "expected the operation to succeed after {} attempts, but it succeeded after {}",
+                    attempts,
+                    attempt
+                );
+                attempt
+            }
+            Err(error) => panic!(
+                "expected the operation to succeed after {} attempts, but the policy gave up: {}",
+                attempts, error
+            ),
+        }
+    }};
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{FakeSleeper, FlakyOp};
+    use crate::{clock::MockClock, delay::Fibonacci, Policy};
+
+    #[test]
+    fn records_sleeps_and_advances_the_clock() {
+        let clock = MockClock::new();
+        let sleeper = FakeSleeper::with_clock(clock.clone());
+        let policy = Policy::new(Fibonacci::from_millis(10).take(4))
+            .with_clock(clock.clone())
+            .with_sleeper(sleeper.clone());
+
+        let error = policy.retry(|| Err::<(), _>("down")).unwrap_err();
+
+        sleeper.assert_slept_millis(&[10, 10, 20, 30]);
+        assert_eq!(sleeper.total(), Duration::from_millis(70));
+        assert_eq!(error.total_delay(), Duration::from_millis(70));
+        assert_eq!(clock.elapsed(), Duration::from_millis(70));
+
+        sleeper.clear();
+        assert!(sleeper.sleeps().is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "expected to sleep [1ms], but slept []")]
+    fn reports_unexpected_schedules() {
+        FakeSleeper::new().assert_slept_millis(&[1]);
+    }
+
+    #[cfg(feature = "asynchronous")]
+    #[tokio::test]
+    async fn records_asynchronous_sleeps() {
+        let sleeper = FakeSleeper::new();
+
+        let _ = crate::asynchronous::retry_with_sleeper(
+            sleeper.clone(),
+            Fibonacci::from_millis(1).take(2),
+            || async { Err::<(), _>("down") },
+        )
+        .await;
+
+        sleeper.assert_slept_millis(&[1, 1]);
+    }
+
+    #[test]
+    fn flaky_operations_fail_as_scripted() {
+        let operation = FlakyOp::fails_n_times(1);
+
+        assert_eq!(
+            operation.call().unwrap_err().to_string(),
+            "attempt 1 failed"
+        );
+        assert_eq!(operation.clone().call(), Ok(2));
+        assert_eq!(operation.calls(), 2);
+        assert!(FlakyOp::always_fails().call().is_err());
+    }
+
+    #[test]
+    fn flaky_operations_follow_their_script() {
+        let clock = MockClock::new();
+        let operation = FlakyOp::new()
+            .fail_with("connection reset")
+            .succeed_times(1)
+            .fail_permanently("access denied")
+            .then_fail()
+            .with_latency(Duration::from_millis(5))
+            .with_sleeper(FakeSleeper::with_clock(clock.clone()));
+
+        assert_eq!(
+            operation.call().unwrap_err().to_string(),
+            "attempt 1 failed: connection reset"
+        );
+        assert_eq!(operation.call(), Ok(2));
+        let error = operation.call().unwrap_err();
+        assert!(error.is_permanent());
+        assert_eq!(
+            error.to_string(),
+            "attempt 3 failed permanently: access denied"
+        );
+        assert_eq!(operation.call().unwrap_err().message(), None);
+        assert_eq!(clock.elapsed(), Duration::from_millis(20));
+
+        let policy = Policy::new(Fibonacci::from_millis(1)).with_sleeper(FakeSleeper::new());
+        let operation = FlakyOp::new().fail_times(2).fail_permanently("gone");
+        let error = policy
+            .retry(operation.clone().into_outcome_fn())
+            .unwrap_err();
+        assert_eq!(error.last_error().map(|error| error.attempt()), Some(3));
+        assert_eq!(operation.calls(), 3);
+
+        #[cfg(feature = "random")]
+        {
+            assert!(FlakyOp::new().then_fail_randomly(1.0).call().is_err());
+            assert!(FlakyOp::new().then_fail_randomly(0.0).call().is_ok());
+        }
+    }
+
+    #[test]
+    fn asserts_attempt_counts() {
+        let policy =
+            Policy::new(Fibonacci::from_millis(1).take(3)).with_sleeper(FakeSleeper::new());
+        let operation = FlakyOp::always_fails();
+
+        let error = assert_gives_up_after!(policy, operation.clone().into_fn(), attempts = 4);
+        assert_eq!(error.last_error().map(|error| error.attempt()), Some(4));
+        assert_eq!(operation.calls(), 4);
+        assert_eq!(
+            assert_succeeds_after!(policy, FlakyOp::fails_n_times(1).into_fn(), attempts = 2),
+            2
+        );
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "expected the policy to give up after 2 attempts, but it gave up after 4"
+    )]
+    fn reports_unexpected_attempt_counts() {
+        let policy =
+            Policy::new(Fibonacci::from_millis(1).take(3)).with_sleeper(FakeSleeper::new());
+
+        assert_gives_up_after!(policy, FlakyOp::always_fails().into_fn(), attempts = 2);
+    }
+}
@@ -0,0 +1,919 @@
+//! gRPC status predicates and retrying wrappers for unary and server-streaming `tonic` calls. This
+//! module is enabled with the `"tonic"` feature.
+//!
+//! `tonic` requests cannot be cloned, so the wrapper takes a closure that builds and sends a fresh
+//! request for every attempt:
+//!
+//! ```
+//! # use retry::delay::Exponential;
+//! # use retry::tonic::UnaryRetry;
+//! # use retry::Policy;
+//! # use tonic::{Request, Response, Status};
+//! # #[derive(Clone)] struct Client;
+//! # impl Client { async fn say_hello(&mut self, _: Request<String>) -> Result<Response<String>, Status> { Ok(Response::new("hi".into())) } }
+//! # #[tokio::main] async fn main() {
+//! # let client = Client;
+//! let retry = UnaryRetry::new(Policy::new(Exponential::from_millis(10).take(3)));
+//!
+//! let response = retry
+//!     .call(|| {
+//!         let mut client = client.clone();
+//!         async move { client.say_hello(Request::new("world".to_string())).await }
+//!     })
+//!     .await;
+//! # assert!(response.is_ok());
+//! # }
+//! ```
+//!
+//! By default, statuses with a code in `RETRYABLE_CODES` are retried. A server may ask for a
+//! specific delay before the next attempt with the `grpc-retry-pushback-ms` metadata, or ask the
+//! client not to retry at all by sending a negative value, as described in the gRPC retry design.
+//!
+//! Server-streaming calls are retried with `StreamingRetry`, which reopens a broken stream from
+//! the last cursor the client saw.
+//!
+//! The unary calls of a generated client can also be retried by layering a `RetryLayer`, which
+//! takes a `Policy`, onto the `Channel` the client is built with:
+//!
+//! ```
+//! # use retry::delay::Exponential;
+//! # use retry::tonic::RetryLayer;
+//! # use retry::Policy;
+//! # use tower::ServiceBuilder;
+//! # let channel = tower::service_fn(|_: http::Request<tonic::body::Body>| async {
+//! #     Ok::<_, std::convert::Infallible>(http::Response::new(tonic::body::Body::empty()))
+//! # });
+//! let policy = Policy::new(Exponential::from_millis(10).take(3));
+//! let channel = ServiceBuilder::new()
+//!     .layer(RetryLayer::new(policy))
+//!     .service(channel);
+//! // let client = GreeterClient::new(channel);
+//! # let _ = channel;
+//! ```
+
+use std::{
+    fmt::{Debug, Display, Formatter, Result as FmtResult},
+    future::Future,
+    pin::Pin,
+    sync::Mutex,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use ::tower::{BoxError, Layer, Service};
+use futures_util::stream::{self, Stream, StreamExt};
+use http_body_util::{BodyExt, Full};
+use tonic::{body::Body, Code, Response, Status};
+
+use crate::{
+    asynchronous::Lent, classified::Outcome, tower::ResponseFuture, CorrelationId,
+    Error as RetryError, Policy,
+};
+
+/// The status codes that indicate a transient condition which may be resolved by retrying.
+pub const RETRYABLE_CODES: &[Code] = &[Code::Unavailable, Code::ResourceExhausted];
+
+/// Returns `true` if the code is one of `RETRYABLE_CODES`.
+pub fn is_retryable_code(code: Code) -> bool {
+    RETRYABLE_CODES.contains(&code)
+}
+
+/// Returns `true` if the status has one of `RETRYABLE_CODES`.
+pub fn is_retryable(status: &Status) -> bool {
+    is_retryable_code(status.code())
+}
+
+/// Build a predicate that retries statuses with any of the given codes.
+pub fn retry_on_codes(codes: &'static [Code]) -> impl Fn(&Status) -> bool + Clone {
+    move |status| codes.contains(&status.code())
+}
+
+const PUSHBACK_KEY: &str = "grpc-retry-pushback-ms";
+
+/// The server's pushback, if any: `Some(Some(delay))` to retry after `delay`, `Some(None)` to
+/// stop retrying.
+fn pushback(status: &Status) -> Option<Option<Duration>> {
+    let value = status.metadata().get(PUSHBACK_KEY)?.to_str().ok()?;
+
+    Some(value.trim().parse::<u64>().ok().map(Duration::from_millis))
+}
+
+/// Whether a call that failed with `status` is retried: `None` if it must not be, and otherwise
+/// the delay the server asked for before the next attempt, if any.
+fn retried<F>(predicate: &F, status: &Status) -> Option<Option<Duration>>
+where
+    F: Fn(&Status) -> bool,
+{
+    if !predicate(status) {
+        return None;
+    }
+    match pushback(status) {
+        Some(None) => None,
+        Some(Some(delay)) => Some(Some(delay)),
+        None => Some(None),
+    }
+}
+
+/// The status of a call that the policy refused to make another attempt of: `Unavailable` for an
+/// open circuit breaker or a full bulkhead, `Cancelled` for a cancelled policy and
+/// `DeadlineExceeded` for an attempt that timed out.
+fn refusal<E>(error: RetryError<E>) -> Status
+where
+    E: Display,
+{
+    let code = match error {
+        RetryError::Cancelled { .. } => Code::Cancelled,
+        RetryError::TimedOut { .. } => Code::DeadlineExceeded,
+        RetryError::Internal(_) => Code::Internal,
+        _ => Code::Unavailable,
+    };
+    Status::new(code, error.to_string())
+}
+
+/// Retries unary `tonic` calls according to a retry policy.
+///
+/// The policy's delays are cloned for every call, so each call gets its own independent schedule.
+/// When the policy gives up, the last status is returned unchanged. When it refuses to make an
+/// attempt, the refusal is returned as a status with the codes used by `RetryChannel`.
+#[derive(Clone, Debug)]
+pub struct UnaryRetry<D, F = fn(&Status) -> bool> {
+    policy: Policy<D>,
+    predicate: F,
+}
+
+impl<D> UnaryRetry<D> {
+    /// Create a new `UnaryRetry` that retries calls failing with a retryable status, using the
+    /// given policy.
+    pub fn new(policy: Policy<D>) -> Self {
+        UnaryRetry {
+            policy,
+            predicate: is_retryable,
+        }
+    }
+}
+
+impl<D, F> UnaryRetry<D, F> {
+    /// Use the given predicate to decide which statuses are retried.
+    pub fn retry_if<F2>(self, predicate: F2) -> UnaryRetry<D, F2>
+    where
+        F2: Fn(&Status) -> bool,
+    {
+        UnaryRetry {
+            policy: self.policy,
+            predicate,
+        }
+    }
+}
+
+impl<D, F> UnaryRetry<D, F>
+where
+    D: IntoIterator<Item = Duration> + Clone,
+    F: Fn(&Status) -> bool,
+{
+    /// Make the call, retrying it according to the policy while it fails with a retried status.
+    pub async fn call<C, Fut, T>(&self, mut call: C) -> Result<Response<T>, Status>
+    where
+        C: FnMut() -> Fut,
+        Fut: Future<Output = Result<Response<T>, Status>>,
+    {
+        let predicate = &self.predicate;
+        let result = self
+            .policy
+            .execute_async(CorrelationId::generate(), None, |_, _| {
+                let attempt = call();
+                async move {
+                    match attempt.await {
+                        Ok(response) => Outcome::Ok(response),
+                        Err(status) => classify_status(predicate, status),
+                    }
+                }
+            })
+            .await;
+
+        match result {
+            Ok(response) => Ok(response),
+            Err(RetryError::Operation { error, .. })
+            | Err(RetryError::MaxAttempts { error, .. }) => Err(error),
+            Err(error) => Err(refusal(error)),
+        }
+    }
+}
+
+/// A `tower::Layer` that retries the calls made through a `tonic` channel according to a retry
+/// policy.
+///
+/// The request body is read into memory before the first attempt so that it can be sent again,
+/// which suits unary calls; server-streaming calls are better resumed with `StreamingRetry`. A
+/// call is retried when the channel fails, or when the server fails it with a retried status
+/// before sending any message, in a trailers-only response. A status in the trailers of a response
+/// that has already delivered messages reaches the client unchanged.
+#[derive(Clone, Debug)]
+pub struct RetryLayer<D, F = fn(&Status) -> bool> {
+    policy: Policy<D>,
+    predicate: F,
+}
+
+impl<D> RetryLayer<D> {
+    /// Create a new `RetryLayer` that retries calls failing with a retryable status, using the
+    /// given policy.
+    pub fn new(policy: Policy<D>) -> Self {
+        RetryLayer {
+            policy,
+            predicate: is_retryable,
+        }
+    }
+}
+
+impl<D, F> RetryLayer<D, F> {
+    /// Use the given predicate to decide which statuses are retried.
+    pub fn retry_if<F2>(self, predicate: F2) -> RetryLayer<D, F2>
+    where
+        F2: Fn(&Status) -> bool,
+    {
+        RetryLayer {
+            policy: self.policy,
+            predicate,
+        }
+    }
+}
+
+impl<S, D, F> Layer<S> for RetryLayer<D, F>
+where
+    D: Clone,
+    F: Clone,
+{
+    type Service = RetryChannel<S, D, F>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RetryChannel {
+            inner,
+            policy: self.policy.clone(),
+            predicate: self.predicate.clone(),
+        }
+    }
+}
+
+/// A `tower::Service` that retries the calls made through the inner `tonic` channel.
+///
+/// When the policy gives up, the last response or error of the channel is returned unchanged. When
+/// it refuses to make an attempt, the refusal is returned as a `Status`: `Unavailable` for an open
+/// circuit breaker or a full bulkhead, `Cancelled` for a cancelled policy and `DeadlineExceeded`
+/// for an attempt that timed out.
+#[derive(Clone, Debug)]
+pub struct RetryChannel<S, D, F = fn(&Status) -> bool> {
+    inner: S,
+    policy: Policy<D>,
+    predicate: F,
+}
+
+impl<S, D, F> RetryChannel<S, D, F> {
+    /// Get a reference to the inner channel.
+    pub fn get_ref(&self) -> &S {
+        &self.inner
+    }
+
+    /// Consume the `RetryChannel`, returning the inner channel.
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+
+impl<S, D, F, B> Service<http::Request<Body>> for RetryChannel<S, D, F>
+where
+    S: Service<http::Request<Body>, Response = http::Response<B>> + Clone + Send + 'static,
+    S::Future: Send,
+    S::Error: Into<BoxError>,
+    B: Send + 'static,
+    D: IntoIterator<Item = Duration> + Clone + Send + Sync + 'static,
+    D::IntoIter: Send,
+    F: Fn(&Status) -> bool + Clone + Send + Sync + 'static,
+{
+    type Response = http::Response<B>;
+    type Error = BoxError;
+    type Future = ResponseFuture<http::Response<B>, BoxError>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, request: http::Request<Body>) -> Self::Future {
+        let clone = self.inner.clone();
+        let inner = std::mem::replace(&mut self.inner, clone);
+        let policy = self.policy.clone();
+        let predicate = self.predicate.clone();
+
+        ResponseFuture::new(Box::pin(retry_calls(inner, policy, predicate, request)))
+    }
+}
+
+async fn retry_calls<S, D, F, B>(
+    inner: S,
+    policy: Policy<D>,
+    predicate: F,
+    request: http::Request<Body>,
+) -> Result<http::Response<B>, BoxError>
+where
+    S: Service<http::Request<Body>, Response = http::Response<B>>,
+    S::Error: Into<BoxError>,
+    D: IntoIterator<Item = Duration> + Clone,
+    F: Fn(&Status) -> bool,
+{
+    let (parts, body) = request.into_parts();
+    let body = body.collect().await?.to_bytes();
+    let (parts, body, predicate) = (&parts, &body, &predicate);
+    let slot = &Mutex::new(Some(inner));
+
+    let result = policy
+        .execute_async(CorrelationId::generate(), None, |_, _| async move {
+            let mut inner = Lent::take(slot);
+            let inner = inner.get();
+            let request =
+                http::Request::from_parts(parts.clone(), Body::new(Full::new(body.clone())));
+            if let Err(error) = std::future::poll_fn(|cx| inner.poll_ready(cx)).await {
+                return classify(predicate, Err(error.into()));
+            }
+            let response = inner.call(request).await.map_err(Into::into);
+            classify(predicate, response)
+        })
+        .await;
+
+    match result {
+        Ok(response) => Ok(response),
+        Err(RetryError::Operation { error, .. }) | Err(RetryError::MaxAttempts { error, .. }) => {
+            error.into_result()
+        }
+        Err(error) => Err(refusal(error).into()),
+    }
+}
+
+/// Decide whether the outcome of an attempt is retried, reading the status of a trailers-only
+/// response from its headers.
+fn classify<F, B>(
+    predicate: &F,
+    response: Result<http::Response<B>, BoxError>,
+) -> Outcome<http::Response<B>, Failure<B>>
+where
+    F: Fn(&Status) -> bool,
+{
+    let response = match response {
+        Ok(response) => response,
+        Err(error) => return Outcome::Retry(Failure::Error(error), None),
+    };
+    let status = match Status::from_header_map(response.headers()) {
+        Some(status) if status.code() != Code::Ok => status,
+        _ => return Outcome::Ok(response),
+    };
+
+    match retried(predicate, &status) {
+        Some(delay) => Outcome::Retry(Failure::Response(response), delay),
+        None => Outcome::Err(Failure::Response(response)),
+    }
+}
+
+/// The last response or error of a call that was not a success.
+enum Failure<B> {
+    Response(http::Response<B>),
+    Error(BoxError),
+}
+
+impl<B> Failure<B> {
+    fn into_result(self) -> Result<http::Response<B>, BoxError> {
+        match self {
+            Failure::Response(response) => Ok(response),
+            Failure::Error(error) => Err(error),
+        }
+    }
+}
+
+impl<B> Debug for Failure<B> {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Failure::Response(response) => formatter
+                .debug_struct("Response")
+                .field("status", &response.status())
+                .field("headers", response.headers())
+                .finish(),
+            Failure::Error(error) => formatter.debug_tuple("Error").field(error).finish(),
+        }
+    }
+}
+
+impl<B> Display for Failure<B> {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Failure::Response(response) => match Status::from_header_map(response.headers()) {
+                Some(status) => write!(formatter, "status {:?}", status.code()),
+                None => write!(formatter, "HTTP status {}", response.status()),
+            },
+            Failure::Error(error) => Display::fmt(error, formatter),
+        }
+    }
+}
+
+/// Retries server-streaming `tonic` calls according to a retry policy, resuming a stream that
+/// breaks from the last cursor the client saw instead of starting over.
+///
+/// The caller gives an extractor that reads the resumption token, such as a cursor or an offset,
+/// from each message, and a factory that opens the stream from the last token seen, or from the
+/// start for `None`. Each message is read as one call of the policy: when opening or reading the
+/// stream fails with a retried status, the stream is reopened after the policy's delay, and the
+/// messages read so far are not repeated. The schedule starts over with every message, so only
+/// failures without progress in between use up the policy's delays and attempts, while its budgets,
+/// circuit breaker and bulkhead see every message.
+///
+/// ```
+/// # use futures::StreamExt;
+/// # use retry::delay::NoDelay;
+/// # use retry::tonic::StreamingRetry;
+/// # use retry::Policy;
+/// # use tonic::{Response, Status};
+/// # struct Event { cursor: u64 }
+/// # async fn subscribe(after: Option<u64>) -> Result<Response<futures::stream::Iter<std::vec::IntoIter<Result<Event, Status>>>>, Status> {
+/// #     let events = match after {
+/// #         None => vec![Ok(Event { cursor: 1 }), Err(Status::unavailable("reset"))],
+/// #         Some(_) => vec![Ok(Event { cursor: 2 })],
+/// #     };
+/// #     Ok(Response::new(futures::stream::iter(events)))
+/// # }
+/// # #[tokio::main] async fn main() {
+/// let retry = StreamingRetry::new(Policy::new(NoDelay.take(3)));
+///
+/// let events = retry.stream(
+///     |event: &Event| Some(event.cursor),
+///     |cursor| subscribe(cursor),
+/// );
+///
+/// let cursors: Vec<_> = events.map(|event| event.unwrap().cursor).collect().await;
+/// assert_eq!(cursors, [1, 2]);
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct StreamingRetry<D, F = fn(&Status) -> bool> {
+    policy: Policy<D>,
+    predicate: F,
+}
+
+impl<D> StreamingRetry<D> {
+    /// Create a new `StreamingRetry` that reopens streams failing with a retryable status, using
+    /// the given policy.
+    pub fn new(policy: Policy<D>) -> Self {
+        StreamingRetry {
+            policy,
+            predicate: is_retryable,
+        }
+    }
+}
+
+impl<D, F> StreamingRetry<D, F> {
+    /// Use the given predicate to decide which statuses are retried.
+    pub fn retry_if<F2>(self, predicate: F2) -> StreamingRetry<D, F2>
+    where
+        F2: Fn(&Status) -> bool,
+    {
+        StreamingRetry {
+            policy: self.policy,
+            predicate,
+        }
+    }
+}
+
+impl<D, F> StreamingRetry<D, F>
+where
+    D: IntoIterator<Item = Duration> + Clone,
+    F: Fn(&Status) -> bool + Clone,
+{
+    /// Open the stream with `open` and yield its messages, reopening it from the token that
+    /// `last_token` read from the last message whenever it fails with a retried status.
+    ///
+    /// The returned stream ends when the server ends the stream. A status that is not retried, or
+    /// that the policy gives up on, is yielded as the last item, as is the refusal of the policy
+    /// to make an attempt, as a status with the codes used by `RetryChannel`.
+    pub fn stream<T, K, X, O, Fut, S>(
+        &self,
+        last_token: X,
+        open: O,
+    ) -> impl Stream<Item = Result<T, Status>>
+    where
+        X: FnMut(&T) -> Option<K>,
+        O: FnMut(Option<K>) -> Fut,
+        Fut: Future<Output = Result<Response<S>, Status>>,
+        S: Stream<Item = Result<T, Status>>,
+        K: Clone,
+    {
+        let resume = Resume {
+            policy: self.policy.clone(),
+            predicate: self.predicate.clone(),
+            reader: Reader {
+                last_token,
+                open,
+                token: None,
+                stream: None,
+            },
+            done: false,
+        };
+        stream::unfold(resume, Resume::next)
+    }
+}
+
+/// The state of a resumable stream.
+struct Resume<D, F, X, O, K, S> {
+    policy: Policy<D>,
+    predicate: F,
+    reader: Reader<X, O, K, S>,
+    done: bool,
+}
+
+impl<D, F, X, O, K, S, T, Fut> Resume<D, F, X, O, K, S>
+where
+    D: IntoIterator<Item = Duration> + Clone,
+    F: Fn(&Status) -> bool,
+    X: FnMut(&T) -> Option<K>,
+    O: FnMut(Option<K>) -> Fut,
+    Fut: Future<Output = Result<Response<S>, Status>>,
+    S: Stream<Item = Result<T, Status>>,
+    K: Clone,
+{
+    async fn next(mut self) -> Option<(Result<T, Status>, Self)> {
+        if self.done {
+            return None;
+        }
+
+        let predicate = &self.predicate;
+        let slot = Mutex::new(Some(self.reader));
+        let result = self
+            .policy
+            .execute_async(CorrelationId::generate(), None, |_, _| {
+                let slot = &slot;
+                async move {
+                    let mut reader = Lent::take(slot);
+                    reader.get().read(predicate).await
+                }
+            })
+            .await;
+        self.reader = slot
+            .into_inner()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .expect("the reader is put back when an attempt ends");
+
+        let status = match result {
+            Ok(Some(message)) => return Some((Ok(message), self)),
+            Ok(None) => return None,
+            Err(RetryError::Operation { error, .. })
+            | Err(RetryError::MaxAttempts { error, .. }) => error,
+            Err(error) => refusal(error),
+        };
+        self.done = true;
+        Some((Err(status), self))
+    }
+}
+
+/// The stream being read and what is needed to reopen it.
+struct Reader<X, O, K, S> {
+    last_token: X,
+    open: O,
+    token: Option<K>,
+    stream: Option<Pin<Box<S>>>,
+}
+
+impl<X, O, K, S, T, Fut> Reader<X, O, K, S>
+where
+    X: FnMut(&T) -> Option<K>,
+    O: FnMut(Option<K>) -> Fut,
+    Fut: Future<Output = Result<Response<S>, Status>>,
+    S: Stream<Item = Result<T, Status>>,
+    K: Clone,
+{
+    /// Read the next message, opening the stream first if it is not open, or `None` once the
+    /// server has ended the stream.
+    async fn read<F>(&mut self, predicate: &F) -> Outcome<Option<T>, Status>
+    where
+        F: Fn(&Status) -> bool,
+    {
+        let stream = match self.stream {
+            Some(ref mut stream) => stream,
+            None => match (self.open)(self.token.clone()).await {
+                Ok(response) => self.stream.insert(Box::pin(response.into_inner())),
+                Err(status) => return classify_status(predicate, status),
+            },
+        };
+
+        match stream.next().await {
+            Some(Ok(message)) => {
+                if let Some(token) = (self.last_token)(&message) {
+                    self.token = Some(token);
+                }
+                Outcome::Ok(Some(message))
+            }
+            Some(Err(status)) => {
+                self.stream = None;
+                classify_status(predicate, status)
+            }
+            None => Outcome::Ok(None),
+        }
+    }
+}
+
+/// Decide whether a call that failed with `status` is retried.
+fn classify_status<F, T>(predicate: &F, status: Status) -> Outcome<T, Status>
+where
+    F: Fn(&Status) -> bool,
+{
+    match retried(predicate, &status) {
+        Some(delay) => Outcome::Retry(status, delay),
+        None => Outcome::Err(status),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use ::tower::{service_fn, Layer, ServiceExt};
+    use futures::{future, stream, StreamExt};
+    use http_body_util::{BodyExt, Full};
+    use tonic::{body::Body, Code, Response, Status};
+
+    use super::{is_retryable, retry_on_codes, RetryLayer, StreamingRetry, UnaryRetry};
+    use crate::{delay::NoDelay, Policy};
+
+    type Bodies = Arc<Mutex<Vec<Vec<u8>>>>;
+
+    /// A channel that answers the calls with the given gRPC status codes in turn, as trailers-only
+    /// responses, and records the body of every request.
+    fn answering(
+        codes: Vec<Code>,
+    ) -> (
+        impl ::tower::Service<
+                http::Request<Body>,
+                Response = http::Response<Body>,
+                Error = &'static str,
+                Future = impl Send,
+            > + Clone
+            + Send,
+        Bodies,
+    ) {
+        let bodies = Arc::new(Mutex::new(Vec::new()));
+        let recorded = Arc::clone(&bodies);
+        let codes = Arc::new(Mutex::new(codes.into_iter()));
+        let channel = service_fn(move |request: http::Request<Body>| {
+            let recorded = Arc::clone(&recorded);
+            let code = codes.lock().unwrap().next().unwrap_or(Code::Ok);
+            async move {
+                let body = request.into_body().collect().await.unwrap().to_bytes();
+                recorded.lock().unwrap().push(body.to_vec());
+                let mut response = http::Response::new(Body::empty());
+                response
+                    .headers_mut()
+                    .insert("grpc-status", (code as i32).into());
+                Ok(response)
+            }
+        });
+        (channel, bodies)
+    }
+
+    fn with_pushback(value: &str) -> Status {
+        let mut status = Status::unavailable("pushback");
+        status
+            .metadata_mut()
+            .insert("grpc-retry-pushback-ms", value.parse().unwrap());
+        status
+    }
+
+    #[test]
+    fn retryable_statuses() {
+        assert!(is_retryable(&Status::unavailable("down")));
+        assert!(is_retryable(&Status::resource_exhausted("quota")));
+        assert!(!is_retryable(&Status::invalid_argument("bad")));
+        assert!(!is_retryable(&Status::ok("")));
+    }
+
+    #[tokio::test]
+    async fn retries_retryable_statuses() {
+        let mut attempts = 0;
+
+        let response = UnaryRetry::new(Policy::new(NoDelay))
+            .call(|| {
+                attempts += 1;
+                future::ready(if attempts < 3 {
+                    Err(Status::unavailable("down"))
+                } else {
+                    Ok(Response::new(attempts))
+                })
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(response.into_inner(), 3);
+    }
+
+    #[tokio::test]
+    async fn does_not_retry_other_statuses() {
+        let mut attempts = 0;
+
+        let status = UnaryRetry::new(Policy::new(NoDelay))
+            .call(|| {
+                attempts += 1;
+                future::ready(Err::<Response<()>, _>(Status::not_found("missing")))
+            })
+            .await
+            .unwrap_err();
+
+        assert_eq!(status.code(), Code::NotFound);
+        assert_eq!(attempts, 1);
+    }
+
+    #[tokio::test]
+    async fn retries_custom_codes() {
+        let mut attempts = 0;
+
+        let status = UnaryRetry::new(Policy::new(NoDelay.take(2)))
+            .retry_if(retry_on_codes(&[Code::Aborted]))
+            .call(|| {
+                attempts += 1;
+                future::ready(Err::<Response<()>, _>(Status::aborted("conflict")))
+            })
+            .await
+            .unwrap_err();
+
+        assert_eq!(status.code(), Code::Aborted);
+        assert_eq!(attempts, 3);
+    }
+
+    #[tokio::test]
+    async fn stops_at_the_policy_max_attempts() {
+        let mut attempts = 0;
+
+        let status = UnaryRetry::new(Policy::new(NoDelay).with_max_attempts(2))
+            .call(|| {
+                attempts += 1;
+                future::ready(Err::<Response<()>, _>(Status::unavailable("down")))
+            })
+            .await
+            .unwrap_err();
+
+        assert_eq!(status.code(), Code::Unavailable);
+        assert_eq!(attempts, 2);
+    }
+
+    #[tokio::test]
+    async fn stops_on_negative_pushback() {
+        let mut attempts = 0;
+
+        let status = UnaryRetry::new(Policy::new(NoDelay))
+            .call(|| {
+                attempts += 1;
+                future::ready(Err::<Response<()>, _>(with_pushback("-1")))
+            })
+            .await
+            .unwrap_err();
+
+        assert_eq!(status.code(), Code::Unavailable);
+        assert_eq!(attempts, 1);
+    }
+
+    #[tokio::test]
+    async fn honors_pushback_delay() {
+        let mut attempts = 0;
+
+        let response = UnaryRetry::new(Policy::new(NoDelay))
+            .call(|| {
+                attempts += 1;
+                future::ready(if attempts < 2 {
+                    Err(with_pushback("1"))
+                } else {
+                    Ok(Response::new(()))
+                })
+            })
+            .await;
+
+        assert!(response.is_ok());
+        assert_eq!(attempts, 2);
+    }
+
+    #[tokio::test]
+    async fn resumes_streams_from_the_last_token() {
+        let mut opened = Vec::new();
+
+        let messages: Vec<_> = StreamingRetry::new(Policy::new(NoDelay.take(1)))
+            .stream(
+                |message: &u32| Some(*message),
+                |token| {
+                    opened.push(token);
+                    let messages = match token {
+                        None => vec![Ok(1), Ok(2), Err(Status::unavailable("reset"))],
+                        Some(2) => vec![Ok(3), Err(Status::unavailable("reset"))],
+                        Some(_) => vec![Ok(4)],
+                    };
+                    future::ready(Ok(Response::new(stream::iter(messages))))
+                },
+            )
+            .map(Result::unwrap)
+            .collect()
+            .await;
+
+        assert_eq!(messages, [1, 2, 3, 4]);
+        assert_eq!(opened, [None, Some(2), Some(3)]);
+    }
+
+    #[tokio::test]
+    async fn yields_the_status_it_gives_up_on() {
+        let mut opens = 0;
+
+        let items: Vec<_> = StreamingRetry::new(Policy::new(NoDelay.take(2)))
+            .stream(
+                |_: &u32| None::<u32>,
+                |_| {
+                    opens += 1;
+                    let messages = if opens == 1 {
+                        vec![Ok(1), Err(Status::unavailable("reset"))]
+                    } else {
+                        vec![Err(Status::unavailable("reset"))]
+                    };
+                    future::ready(Ok(Response::new(stream::iter(messages))))
+                },
+            )
+            .collect()
+            .await;
+
+        assert_eq!(items.len(), 2);
+        assert_eq!(*items[0].as_ref().unwrap(), 1);
+        assert_eq!(items[1].as_ref().unwrap_err().code(), Code::Unavailable);
+        assert_eq!(opens, 3);
+
+        let items: Vec<_> = StreamingRetry::new(Policy::new(NoDelay))
+            .stream(
+                |_: &u32| None::<u32>,
+                |_| {
+                    future::ready(Err::<Response<stream::Empty<_>>, _>(Status::not_found(
+                        "gone",
+                    )))
+                },
+            )
+            .collect()
+            .await;
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].as_ref().unwrap_err().code(), Code::NotFound);
+    }
+
+    #[tokio::test]
+    async fn reopens_streams_at_most_the_policy_max_attempts() {
+        let mut opens = 0;
+
+        let items: Vec<_> = StreamingRetry::new(Policy::new(NoDelay).with_max_attempts(2))
+            .stream(
+                |_: &u32| None::<u32>,
+                |_| {
+                    opens += 1;
+                    future::ready(Err::<Response<stream::Empty<_>>, _>(Status::unavailable(
+                        "down",
+                    )))
+                },
+            )
+            .collect()
+            .await;
+
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].as_ref().unwrap_err().code(), Code::Unavailable);
+        assert_eq!(opens, 2);
+    }
+
+    #[tokio::test]
+    async fn retry_channel_resends_calls_failing_with_retryable_statuses() {
+        let (channel, bodies) = answering(vec![Code::Unavailable, Code::Unavailable]);
+        let request = http::Request::new(Body::new(Full::from("hello")));
+
+        let response = RetryLayer::new(Policy::new(NoDelay.take(5)))
+            .layer(channel)
+            .oneshot(request)
+            .await
+            .unwrap();
+
+        assert_eq!(response.headers()["grpc-status"], "0");
+        assert_eq!(*bodies.lock().unwrap(), [b"hello"; 3]);
+    }
+
+    #[tokio::test]
+    async fn retry_channel_returns_the_last_status() {
+        let (channel, bodies) = answering(vec![Code::NotFound]);
+
+        let response = RetryLayer::new(Policy::new(NoDelay.take(5)))
+            .layer(channel)
+            .oneshot(http::Request::new(Body::empty()))
+            .await
+            .unwrap();
+
+        assert_eq!(response.headers()["grpc-status"], "5");
+        assert_eq!(bodies.lock().unwrap().len(), 1);
+
+        let (channel, bodies) = answering(vec![Code::Aborted; 3]);
+        let response = RetryLayer::new(Policy::new(NoDelay).with_max_attempts(2))
+            .retry_if(retry_on_codes(&[Code::Aborted]))
+            .layer(channel)
+            .oneshot(http::Request::new(Body::empty()))
+            .await
+            .unwrap();
+
+        assert_eq!(response.headers()["grpc-status"], "10");
+        assert_eq!(bodies.lock().unwrap().len(), 2);
+    }
+}
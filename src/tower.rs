@@ -0,0 +1,403 @@
+//! `tower` middleware that retries requests according to a retry policy. This module is enabled
+//! with the `"tower"` feature.
+//!
+//! Wrap any service with `RetryLayer::new(policy)`. The policy's delays are cloned for every
+//! request, so each request gets its own independent schedule, while its budgets, circuit breaker
+//! and bulkhead are shared by all the requests made through the service.
+//!
+//! ```
+//! # use retry::delay::Fixed;
+//! # use retry::tower::RetryLayer;
+//! # use retry::Policy;
+//! # use tower::{service_fn, ServiceBuilder};
+//! # async fn send(request: String) -> Result<String, std::io::Error> { Ok(request) }
+//! let service = ServiceBuilder::new()
+//!     .layer(RetryLayer::new(Policy::new(Fixed::from_millis(100).take(3))))
+//!     .service(service_fn(send));
+//! # let _ = service;
+//! ```
+//!
+//! Requests must implement `Clone`, since a retried request has to be sent again, and each attempt
+//! sends a clone of the original request.
+
+use std::{
+    convert::Infallible,
+    fmt,
+    future::Future,
+    pin::Pin,
+    sync::Mutex,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use ::tower::{BoxError, Layer, Service};
+
+use crate::{asynchronous::Lent, classified::Outcome, CorrelationId, Error as RetryError, Policy};
+
+/// Decides whether the outcome of a request should cause it to be retried.
+///
+/// This is implemented for all closures of the form `Fn(&Result<Res, E>) -> bool`.
+pub trait Classify<Res, E> {
+    /// Returns `true` if the request that produced `result` should be retried.
+    fn should_retry(&self, result: &Result<Res, E>) -> bool;
+}
+
+impl<F, Res, E> Classify<Res, E> for F
+where
+    F: Fn(&Result<Res, E>) -> bool,
+{
+    fn should_retry(&self, result: &Result<Res, E>) -> bool {
+        self(result)
+    }
+}
+
+/// The default classifier, which retries every error returned by the inner service and accepts
+/// every response.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RetryErrors;
+
+impl<Res, E> Classify<Res, E> for RetryErrors {
+    fn should_retry(&self, result: &Result<Res, E>) -> bool {
+        result.is_err()
+    }
+}
+
+/// A `tower::Layer` that wraps services in a `RetryService`.
+#[derive(Clone, Debug)]
+pub struct RetryLayer<D, C = RetryErrors> {
+    policy: Policy<D>,
+    classifier: C,
+}
+
+impl<D> RetryLayer<D> {
+    /// Create a new `RetryLayer` that retries failed requests using the given policy.
+    pub fn new(policy: Policy<D>) -> Self {
+        RetryLayer {
+            policy,
+            classifier: RetryErrors,
+        }
+    }
+}
+
+impl<D, C> RetryLayer<D, C> {
+    /// Use the given classifier to decide which outcomes are retried, instead of retrying every
+    /// error.
+    pub fn retry_if<C2>(self, classifier: C2) -> RetryLayer<D, C2> {
+        RetryLayer {
+            policy: self.policy,
+            classifier,
+        }
+    }
+}
+
+impl<S, D, C> Layer<S> for RetryLayer<D, C>
+where
+    D: Clone,
+    C: Clone,
+{
+    type Service = RetryService<S, D, C>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RetryService {
+            inner,
+            policy: self.policy.clone(),
+            classifier: self.classifier.clone(),
+        }
+    }
+}
+
+/// A `tower::Service` that retries requests to the inner service.
+///
+/// When the policy gives up, the outcome of the last attempt is returned unchanged, with its error
+/// boxed. When the policy refuses to make an attempt, for example because its circuit breaker is
+/// open, the refusal is returned as a boxed `retry::Error<Infallible>`.
+#[derive(Clone, Debug)]
+pub struct RetryService<S, D, C = RetryErrors> {
+    inner: S,
+    policy: Policy<D>,
+    classifier: C,
+}
+
+impl<S, D> RetryService<S, D> {
+    /// Create a new `RetryService` wrapping `inner`, retrying failed requests using the given
+    /// policy.
+    pub fn new(inner: S, policy: Policy<D>) -> Self {
+        RetryService {
+            inner,
+            policy,
+            classifier: RetryErrors,
+        }
+    }
+}
+
+impl<S, D, C> RetryService<S, D, C> {
+    /// Get a reference to the inner service.
+    pub fn get_ref(&self) -> &S {
+        &self.inner
+    }
+
+    /// Consume the `RetryService`, returning the inner service.
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+
+impl<S, D, C, Req> Service<Req> for RetryService<S, D, C>
+where
+    S: Service<Req> + Clone + Send + 'static,
+    S::Future: Send,
+    S::Response: Send,
+    S::Error: Into<BoxError>,
+    D: IntoIterator<Item = Duration> + Clone + Send + Sync + 'static,
+    D::IntoIter: Send,
+    C: Classify<S::Response, S::Error> + Clone + Send + Sync + 'static,
+    Req: Clone + Send + 'static,
+{
+    type Response = S::Response;
+    type Error = BoxError;
+    type Future = ResponseFuture<S::Response, BoxError>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, request: Req) -> Self::Future {
+        // The service that was driven to readiness must be the one that receives the first
+        // attempt, so keep it and leave a fresh clone in its place.
+        let clone = self.inner.clone();
+        let inner = std::mem::replace(&mut self.inner, clone);
+        let policy = self.policy.clone();
+        let classifier = self.classifier.clone();
+
+        ResponseFuture::new(Box::pin(retry_requests(inner, policy, classifier, request)))
+    }
+}
+
+async fn retry_requests<S, D, C, Req>(
+    inner: S,
+    policy: Policy<D>,
+    classifier: C,
+    request: Req,
+) -> Result<S::Response, BoxError>
+where
+    S: Service<Req>,
+    S::Error: Into<BoxError>,
+    D: IntoIterator<Item = Duration> + Clone,
+    C: Classify<S::Response, S::Error>,
+    Req: Clone,
+{
+    let (request, classifier) = (&Mutex::new(request), &classifier);
+    let slot = &Mutex::new(Some(inner));
+
+    let result = policy
+        .execute_async(CorrelationId::generate(), None, |_, _| async move {
+            let mut inner = Lent::take(slot);
+            let inner = inner.get();
+            if let Err(error) = std::future::poll_fn(|cx| inner.poll_ready(cx)).await {
+                return classify(classifier, Err(error));
+            }
+            let attempt = request
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .clone();
+            let result = inner.call(attempt).await;
+            classify(classifier, result)
+        })
+        .await;
+
+    match result {
+        Ok(response) => Ok(response),
+        Err(RetryError::Operation { error, .. }) | Err(RetryError::MaxAttempts { error, .. }) => {
+            error.into_result()
+        }
+        Err(error) => Err(Box::new(error.map_error(|_| -> Infallible {
+            unreachable!("a call that ended with the error of an attempt was returned above")
+        }))),
+    }
+}
+
+/// Decide whether the outcome of an attempt is retried with `classifier`.
+fn classify<C, Res, E>(classifier: &C, result: Result<Res, E>) -> Outcome<Res, Failure<Res>>
+where
+    C: Classify<Res, E>,
+    E: Into<BoxError>,
+{
+    let retry = classifier.should_retry(&result);
+    match result {
+        Ok(response) if retry => Outcome::Retry(Failure::Response(response), None),
+        Ok(response) => Outcome::Ok(response),
+        Err(error) if retry => Outcome::Retry(Failure::Error(error.into()), None),
+        Err(error) => Outcome::Err(Failure::Error(error.into())),
+    }
+}
+
+/// The last response or error of a request that was not a success.
+enum Failure<Res> {
+    Response(Res),
+    Error(BoxError),
+}
+
+impl<Res> Failure<Res> {
+    fn into_result(self) -> Result<Res, BoxError> {
+        match self {
+            Failure::Response(response) => Ok(response),
+            Failure::Error(error) => Err(error),
+        }
+    }
+}
+
+impl<Res> fmt::Debug for Failure<Res> {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Failure::Response(_) => formatter.write_str("Response"),
+            Failure::Error(error) => formatter.debug_tuple("Error").field(error).finish(),
+        }
+    }
+}
+
+/// The future returned by `RetryService`.
+pub struct ResponseFuture<Res, E> {
+    future: Pin<Box<dyn Future<Output = Result<Res, E>> + Send>>,
+}
+
+impl<Res, E> ResponseFuture<Res, E> {
+    pub(crate) fn new(future: Pin<Box<dyn Future<Output = Result<Res, E>> + Send>>) -> Self {
+        ResponseFuture { future }
+    }
+}
+
+impl<Res, E> Future for ResponseFuture<Res, E> {
+    type Output = Result<Res, E>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.future.as_mut().poll(cx)
+    }
+}
+
+impl<Res, E> fmt::Debug for ResponseFuture<Res, E> {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.debug_struct("ResponseFuture").finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{
+            atomic::{AtomicU64, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+
+    use ::tower::{service_fn, Layer, ServiceExt};
+    use futures::future;
+
+    use super::RetryLayer;
+    use crate::{delay::NoDelay, CircuitBreaker, Error, Policy};
+
+    #[tokio::test]
+    async fn retries_errors_until_success() {
+        let calls = Arc::new(AtomicU64::new(0));
+        let counter = Arc::clone(&calls);
+        let service = service_fn(move |request: u64| {
+            let call = counter.fetch_add(1, Ordering::SeqCst) + 1;
+            future::ready(if call == 3 {
+                Ok(request * 2)
+            } else {
+                Err("not yet")
+            })
+        });
+
+        let response = RetryLayer::new(Policy::new(NoDelay.take(5)))
+            .layer(service)
+            .oneshot(21)
+            .await
+            .unwrap();
+
+        assert_eq!(response, 42);
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn returns_last_error_when_delays_end() {
+        let calls = Arc::new(AtomicU64::new(0));
+        let counter = Arc::clone(&calls);
+        let service = service_fn(move |_: ()| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            future::ready(Err::<(), _>("always"))
+        });
+
+        let error = RetryLayer::new(Policy::new(NoDelay.take(2)))
+            .layer(service)
+            .oneshot(())
+            .await
+            .unwrap_err();
+
+        assert_eq!(error.to_string(), "always");
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn retries_responses_with_classifier() {
+        let calls = Arc::new(AtomicU64::new(0));
+        let counter = Arc::clone(&calls);
+        let service = service_fn(move |_: ()| {
+            let call = counter.fetch_add(1, Ordering::SeqCst) + 1;
+            future::ready(Ok::<_, &str>(if call < 2 { 503 } else { 200 }))
+        });
+
+        let response = RetryLayer::new(Policy::new(NoDelay.take(5)))
+            .retry_if(|result: &Result<u16, &str>| matches!(result, Ok(503)))
+            .layer(service)
+            .oneshot(())
+            .await
+            .unwrap();
+
+        assert_eq!(response, 200);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn stops_at_the_policy_max_attempts() {
+        let calls = Arc::new(AtomicU64::new(0));
+        let counter = Arc::clone(&calls);
+        let service = service_fn(move |_: ()| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            future::ready(Ok::<_, &str>(503))
+        });
+
+        let response = RetryLayer::new(Policy::new(NoDelay.take(5)).with_max_attempts(2))
+            .retry_if(|result: &Result<u16, &str>| matches!(result, Ok(503)))
+            .layer(service)
+            .oneshot(())
+            .await
+            .unwrap();
+
+        assert_eq!(response, 503);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn returns_refusals_of_the_policy() {
+        let calls = Arc::new(AtomicU64::new(0));
+        let counter = Arc::clone(&calls);
+        let service = service_fn(move |_: ()| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            future::ready(Err::<(), _>("down"))
+        });
+        let policy = Policy::new(NoDelay.take(1))
+            .with_circuit_breaker(CircuitBreaker::new(1, Duration::from_secs(60)));
+        let layer = RetryLayer::new(policy);
+
+        let error = layer.layer(service.clone()).oneshot(()).await.unwrap_err();
+        assert_eq!(error.to_string(), "down");
+
+        let error = layer.layer(service).oneshot(()).await.unwrap_err();
+        assert!(matches!(
+            error.downcast_ref(),
+            Some(Error::<std::convert::Infallible>::CircuitOpen { .. })
+        ));
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+}
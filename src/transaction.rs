@@ -0,0 +1,226 @@
+//! Retries of database transactions that failed with a transient error, such as a serialization
+//! failure or a deadlock.
+//!
+//! A failed transaction cannot be resumed: it has to be rolled back, and its statements run again
+//! in a new transaction. `retry_transaction` does this for every attempt, so the body of the
+//! transaction only runs against a transaction that has just begun, and no transaction is left
+//! open between attempts. Only the errors that `predicates::db::is_transient` classifies as
+//! transient are retried, whether they happen while beginning the transaction, in its body or
+//! while committing it.
+//!
+//! Drivers are supported through the `Transactional` and `Transaction` traits, which are
+//! implemented for `postgres::Client` and its transactions with the `"postgres"` feature:
+//!
+//! ```rust,no_run
+//! # use retry::delay::Exponential;
+//! use retry::{transaction::retry_transaction, Policy};
+//!
+//! # #[cfg(feature = "postgres")]
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let mut client = postgres::Client::connect("host=localhost", postgres::NoTls)?;
+//! let policy = Policy::new(Exponential::from_millis(10).take(5));
+//!
+//! retry_transaction(&policy, &mut client, |transaction| {
+//!     transaction.batch_execute("SET TRANSACTION ISOLATION LEVEL SERIALIZABLE")?;
+//!     transaction.execute("UPDATE accounts SET balance = balance - 10 WHERE id = 1", &[])?;
+//!     transaction.execute("UPDATE accounts SET balance = balance + 10 WHERE id = 2", &[])
+//! })?;
+//! # Ok(())
+//! # }
+//! # #[cfg(not(feature = "postgres"))]
+//! # fn main() {}
+//! ```
+
+use std::{fmt::Debug, time::Duration};
+
+use crate::{predicates::db::SqlState, Error, OperationResult, Policy};
+
+/// A database transaction that has begun.
+pub trait Transaction {
+    /// The error of the driver.
+    type Error;
+
+    /// Commit the transaction.
+    fn commit(self) -> Result<(), Self::Error>;
+
+    /// Roll the transaction back.
+    fn rollback(self) -> Result<(), Self::Error>;
+}
+
+/// A connection that transactions can be begun on.
+pub trait Transactional {
+    /// The transactions of the connection.
+    type Transaction<'a>: Transaction<Error = Self::Error>
+    where
+        Self: 'a;
+    /// The error of the driver.
+    type Error;
+
+    /// Begin a transaction.
+    fn begin(&mut self) -> Result<Self::Transaction<'_>, Self::Error>;
+}
+
+/// Run `body` in a transaction begun on `connection`, committing it if `body` succeeds, and
+/// retrying the whole transaction according to `policy` while it fails with a transient error.
+///
+/// A transaction whose body fails is rolled back before it is retried or its error returned.
+/// If the rollback fails, the state of the connection is unknown, so the error of the body is
+/// returned without retrying it.
+pub fn retry_transaction<D, C, F, R>(
+    policy: &Policy<D>,
+    connection: &mut C,
+    mut body: F,
+) -> Result<R, Error<C::Error>>
+where
+    D: IntoIterator<Item = Duration> + Clone,
+    C: Transactional + ?Sized,
+    C::Error: SqlState + Debug,
+    F: FnMut(&mut C::Transaction<'_>) -> Result<R, C::Error>,
+{
+    policy.retry(|| {
+        let mut transaction = match connection.begin() {
+            Ok(transaction) => transaction,
+            Err(error) => return classify(error),
+        };
+        match body(&mut transaction) {
+            Ok(value) => match transaction.commit() {
+                Ok(()) => OperationResult::Ok(value),
+                Err(error) => classify(error),
+            },
+            Err(error) => match transaction.rollback() {
+                Ok(()) => classify(error),
+                Err(_) => OperationResult::Err(error),
+            },
+        }
+    })
+}
+
+fn classify<R, E>(error: E) -> OperationResult<R, E>
+where
+    E: SqlState,
+{
+    OperationResult::retry_if(Err(error), crate::predicates::db::is_transient)
+}
+
+#[cfg(feature = "postgres")]
+impl Transaction for postgres::Transaction<'_> {
+    type Error = postgres::Error;
+
+    fn commit(self) -> Result<(), postgres::Error> {
+        postgres::Transaction::commit(self)
+    }
+
+    fn rollback(self) -> Result<(), postgres::Error> {
+        postgres::Transaction::rollback(self)
+    }
+}
+
+#[cfg(feature = "postgres")]
+impl Transactional for postgres::Client {
+    type Transaction<'a> = postgres::Transaction<'a>;
+    type Error = postgres::Error;
+
+    fn begin(&mut self) -> Result<postgres::Transaction<'_>, postgres::Error> {
+        self.transaction()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{retry_transaction, Transaction, Transactional};
+    use crate::{delay::NoDelay, Error, Policy};
+
+    /// Records the statements run on it, failing the ones it was told to fail.
+    #[derive(Default)]
+    struct Connection {
+        log: Vec<String>,
+        fail_commits: usize,
+        fail_rollbacks: bool,
+    }
+
+    struct Open<'a>(&'a mut Connection);
+
+    impl Transaction for Open<'_> {
+        type Error = &'static str;
+
+        fn commit(self) -> Result<(), &'static str> {
+            self.0.log.push("commit".into());
+            if self.0.fail_commits > 0 {
+                self.0.fail_commits -= 1;
+                return Err("40001");
+            }
+            Ok(())
+        }
+
+        fn rollback(self) -> Result<(), &'static str> {
+            self.0.log.push("rollback".into());
+            if self.0.fail_rollbacks {
+                return Err("08006");
+            }
+            Ok(())
+        }
+    }
+
+    impl Transactional for Connection {
+        type Transaction<'a> = Open<'a>;
+        type Error = &'static str;
+
+        fn begin(&mut self) -> Result<Open<'_>, &'static str> {
+            self.log.push("begin".into());
+            Ok(Open(self))
+        }
+    }
+
+    #[test]
+    fn retries_transactions_from_the_start() {
+        let mut connection = Connection {
+            fail_commits: 1,
+            ..Connection::default()
+        };
+        let mut attempts = 0;
+
+        let result = retry_transaction(
+            &Policy::new(NoDelay.take(5)),
+            &mut connection,
+            |transaction| {
+                attempts += 1;
+                transaction.0.log.push(format!("update {}", attempts));
+                match attempts {
+                    1 => Err("40P01"),
+                    _ => Ok(attempts),
+                }
+            },
+        );
+
+        assert_eq!(result, Ok(3));
+        assert_eq!(
+            connection.log,
+            [
+                "begin", "update 1", "rollback", "begin", "update 2", "commit", "begin",
+                "update 3", "commit",
+            ]
+        );
+    }
+
+    #[test]
+    fn returns_permanent_errors_and_failed_rollbacks() {
+        let policy = Policy::new(NoDelay.take(5));
+        let mut connection = Connection::default();
+
+        let result = retry_transaction(&policy, &mut connection, |_| Err::<(), _>("23505"));
+        assert!(matches!(result, Err(Error::Operation { tries: 1, .. })));
+        assert_eq!(connection.log, ["begin", "rollback"]);
+
+        let mut connection = Connection {
+            fail_rollbacks: true,
+            ..Connection::default()
+        };
+        let result = retry_transaction(&policy, &mut connection, |_| Err::<(), _>("40001"));
+        assert_eq!(
+            result.unwrap_err().into_last_error(),
+            Some("40001"),
+            "the transaction is not retried on a connection in an unknown state"
+        );
+        assert_eq!(connection.log, ["begin", "rollback"]);
+    }
+}
@@ -0,0 +1,212 @@
+use std::{error::Error as StdError, fmt, time::Duration};
+
+use crate::{Error, OperationResult, Policy};
+
+/// A policy that also retries the successful attempts whose value does not satisfy a predicate,
+/// created with `Policy::until`, for example to re-read eventually consistent data until it is
+/// fresh enough.
+///
+/// ```rust
+/// # use retry::delay::NoDelay;
+/// use retry::{Policy, Unsatisfied};
+///
+/// let policy = Policy::new(NoDelay.take(3)).until(|version: &u64| *version >= 2);
+/// let mut versions = vec![0, 1, 2].into_iter();
+///
+/// assert_eq!(policy.retry(|| Ok::<_, ()>(versions.next().unwrap())), Ok(2));
+/// assert_eq!(
+///     policy.retry(|| Ok::<_, ()>(1)).unwrap_err().into_last_error(),
+///     Some(Unsatisfied::Value(1))
+/// );
+/// ```
+#[derive(Clone)]
+pub struct Until<D, P> {
+    policy: Policy<D>,
+    predicate: P,
+}
+
+impl<D> Policy<D> {
+    /// Treat the values of successful attempts for which `predicate` returns `false` as retryable
+    /// failures. When the policy gives up on such an attempt, its error is the last value.
+    pub fn until<P>(self, predicate: P) -> Until<D, P> {
+        Until {
+            policy: self,
+            predicate,
+        }
+    }
+}
+
+impl<D, P> Until<D, P> {
+    /// The policy the attempts are retried with.
+    pub fn policy(&self) -> &Policy<D> {
+        &self.policy
+    }
+}
+
+impl<D, P> Until<D, P>
+where
+    D: IntoIterator<Item = Duration> + Clone,
+{
+    /// Retry the given operation synchronously according to the policy, until it succeeds with a
+    /// value that satisfies the predicate.
+    pub fn retry<O, R, E, OR>(&self, mut operation: O) -> Result<R, Error<Unsatisfied<R, E>>>
+    where
+        O: FnMut() -> OR,
+        OR: Into<OperationResult<R, E>>,
+        P: Fn(&R) -> bool,
+        R: fmt::Debug,
+        E: fmt::Debug,
+    {
+        self.policy.retry(|| self.check(operation().into()))
+    }
+
+    /// Retry the given asynchronous operation according to the policy, until it succeeds with a
+    /// value that satisfies the predicate.
+    ///
+    /// This method is enabled with the `"asynchronous"` feature.
+    #[cfg(feature = "asynchronous")]
+    pub async fn retry_async<O, R, E, OR, F>(
+        &self,
+        mut operation: O,
+    ) -> Result<R, Error<Unsatisfied<R, E>>>
+    where
+        O: FnMut() -> F,
+        OR: Into<OperationResult<R, E>>,
+        F: std::future::Future<Output = OR>,
+        P: Fn(&R) -> bool,
+        R: fmt::Debug,
+        E: fmt::Debug,
+    {
+        self.policy
+            .retry_async(|| {
+                let attempt = operation();
+                async move { self.check(attempt.await.into()) }
+            })
+            .await
+    }
+
+    fn check<R, E>(&self, result: OperationResult<R, E>) -> OperationResult<R, Unsatisfied<R, E>>
+    where
+        P: Fn(&R) -> bool,
+    {
+        match result {
+            OperationResult::Ok(value) if (self.predicate)(&value) => OperationResult::Ok(value),
+            OperationResult::Ok(value) => OperationResult::Retry(Unsatisfied::Value(value)),
+            OperationResult::Retry(error) => OperationResult::Retry(Unsatisfied::Error(error)),
+            OperationResult::Err(error) => OperationResult::Err(Unsatisfied::Error(error)),
+        }
+    }
+}
+
+impl<D, P> fmt::Debug for Until<D, P>
+where
+    D: fmt::Debug,
+{
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter
+            .debug_struct("Until")
+            .field("policy", &self.policy)
+            .finish()
+    }
+}
+
+/// Why an attempt retried with `Policy::until` failed: either it succeeded with a value that did
+/// not satisfy the predicate, or it failed with an error.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Unsatisfied<R, E> {
+    /// The value of an attempt that did not satisfy the predicate.
+    Value(R),
+    /// The error of a failed attempt.
+    Error(E),
+}
+
+impl<R, E> fmt::Display for Unsatisfied<R, E>
+where
+    R: fmt::Debug,
+    E: fmt::Display,
+{
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Unsatisfied::Value(value) => write!(formatter, "unsatisfactory value {:?}", value),
+            Unsatisfied::Error(error) => error.fmt(formatter),
+        }
+    }
+}
+
+/// The source of a failed attempt is its error, so that `Error<Unsatisfied<R, E>>` keeps the chain
+/// of the operation's errors.
+impl<R, E> StdError for Unsatisfied<R, E>
+where
+    R: fmt::Debug,
+    E: StdError + 'static,
+{
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            Unsatisfied::Value(_) => None,
+            Unsatisfied::Error(error) => Some(error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Unsatisfied;
+    use crate::{delay::NoDelay, Error, OperationResult, Policy};
+
+    #[test]
+    fn retries_unsatisfactory_values() {
+        let policy = Policy::new(NoDelay).until(|value: &u32| *value > 2);
+        let mut attempts = 0;
+
+        let result = policy.retry(|| {
+            attempts += 1;
+            if attempts == 2 {
+                Err("down")
+            } else {
+                Ok(attempts)
+            }
+        });
+
+        assert_eq!(result, Ok(3));
+    }
+
+    #[test]
+    fn gives_up_with_the_last_value_or_error() {
+        let policy = Policy::new(NoDelay)
+            .with_max_attempts(2)
+            .until(|_: &()| false);
+
+        let result = policy.retry(|| Ok::<_, &str>(()));
+        assert!(matches!(
+            result,
+            Err(Error::MaxAttempts {
+                error: Unsatisfied::Value(()),
+                tries: 2,
+                ..
+            })
+        ));
+
+        let result = policy.retry(|| OperationResult::<(), _>::Err("fatal"));
+        assert_eq!(result.unwrap_err().tries(), 1);
+        assert_eq!(
+            Unsatisfied::<u8, &str>::Value(7).to_string(),
+            "unsatisfactory value 7"
+        );
+    }
+
+    #[cfg(feature = "asynchronous")]
+    #[tokio::test]
+    async fn retries_unsatisfactory_values_asynchronously() {
+        let policy = Policy::new(NoDelay).until(|value: &u32| *value > 1);
+        let mut attempts = 0;
+
+        let result = policy
+            .retry_async(|| {
+                attempts += 1;
+                futures::future::ready(Ok::<_, ()>(attempts))
+            })
+            .await;
+
+        assert_eq!(result, Ok(2));
+    }
+}
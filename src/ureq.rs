@@ -0,0 +1,228 @@
+//! Retries of blocking `ureq` calls according to a policy. This module is enabled with the
+//! `"ureq"` feature.
+//!
+//! ```no_run
+//! # use retry::delay::Exponential;
+//! use retry::Policy;
+//!
+//! let policy = Policy::new(Exponential::from_millis(100).take(3));
+//! let response = retry::ureq::call(&policy, || ureq::get("http://example.com").call());
+//! # let _ = response;
+//! ```
+//!
+//! Responses with a status of 408, 429 or 5xx (except 501 and 505), as decided by
+//! `predicates::http::is_retryable`, and errors that happened while connecting, resolving the host
+//! or waiting for the server are retried. Other statuses can be retried with `call_with` and a
+//! predicate from `predicates::http`. A retried status ends up as `ureq::Error::StatusCode`.
+//!
+//! By default, `ureq` turns statuses of 400 and above into errors, which drops the response's
+//! headers. To wait the delay a server asks for with a `Retry-After` header instead of the
+//! policy's delay, make the calls with an agent configured to return those statuses as responses:
+//!
+//! ```no_run
+//! # use retry::delay::Exponential;
+//! # use retry::Policy;
+//! use ureq::Agent;
+//!
+//! let agent: Agent = Agent::config_builder()
+//!     .http_status_as_error(false)
+//!     .build()
+//!     .into();
+//! let policy = Policy::new(Exponential::from_millis(100).take(3));
+//! let response = retry::ureq::call(&policy, || agent.get("http://example.com").call());
+//! # let _ = response;
+//! ```
+//!
+//! The delay a `Retry-After` header asks for is limited to 60 seconds, or to the maximum given to
+//! `call_with_max_retry_after`, and to the policy's `max_delay`.
+
+use std::time::Duration;
+
+use ureq::{http::Response, Body, Error as UreqError};
+
+use crate::{
+    http::{retry_after, DEFAULT_MAX_RETRY_AFTER},
+    predicates::http::is_retryable,
+    Classified, Error, Permanent, Policy, Transient,
+};
+
+/// Make a call with `operation`, retrying it according to `policy` while it fails with an error
+/// or a status worth retrying.
+pub fn call<D, O>(policy: &Policy<D>, operation: O) -> Result<Response<Body>, Error<UreqError>>
+where
+    D: IntoIterator<Item = Duration> + Clone,
+    O: FnMut() -> Result<Response<Body>, UreqError>,
+{
+    call_with(policy, is_retryable, operation)
+}
+
+/// Make a call with `operation`, retrying it according to `policy` while it fails with an error
+/// worth retrying or a status for which `retry_on_status` returns `true`.
+pub fn call_with<D, S, O>(
+    policy: &Policy<D>,
+    retry_on_status: S,
+    operation: O,
+) -> Result<Response<Body>, Error<UreqError>>
+where
+    D: IntoIterator<Item = Duration> + Clone,
+    S: Fn(u16) -> bool,
+    O: FnMut() -> Result<Response<Body>, UreqError>,
+{
+    call_with_max_retry_after(policy, retry_on_status, DEFAULT_MAX_RETRY_AFTER, operation)
+}
+
+/// Make a call like `call_with`, waiting at most `max_retry_after` for the delay a `Retry-After`
+/// header asks for instead of 60 seconds.
+pub fn call_with_max_retry_after<D, S, O>(
+    policy: &Policy<D>,
+    retry_on_status: S,
+    max_retry_after: Duration,
+    mut operation: O,
+) -> Result<Response<Body>, Error<UreqError>>
+where
+    D: IntoIterator<Item = Duration> + Clone,
+    S: Fn(u16) -> bool,
+    O: FnMut() -> Result<Response<Body>, UreqError>,
+{
+    policy.retry_classified(|| classify(operation(), &retry_on_status, max_retry_after))
+}
+
+fn classify<S>(
+    result: Result<Response<Body>, UreqError>,
+    retry_on_status: S,
+    max_retry_after: Duration,
+) -> Result<Response<Body>, Classified<UreqError>>
+where
+    S: Fn(u16) -> bool,
+{
+    match result {
+        Ok(response) if retry_on_status(response.status().as_u16()) => {
+            let error = UreqError::StatusCode(response.status().as_u16());
+            Err(match retry_after(response.headers()) {
+                Some(delay) => Transient::with_retry_after(error, delay.min(max_retry_after)),
+                None => Transient::new(error),
+            }
+            .into())
+        }
+        Ok(response) => Ok(response),
+        Err(UreqError::StatusCode(status)) if retry_on_status(status) => {
+            Err(Transient::new(UreqError::StatusCode(status)).into())
+        }
+        Err(error) if is_transient(&error) => Err(Transient::new(error).into()),
+        Err(error) => Err(Permanent(error).into()),
+    }
+}
+
+fn is_transient(error: &UreqError) -> bool {
+    matches!(
+        error,
+        UreqError::Io(_)
+            | UreqError::Timeout(_)
+            | UreqError::HostNotFound
+            | UreqError::ConnectionFailed
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::atomic::Ordering,
+        time::{Duration, Instant},
+    };
+
+    use ureq::Agent;
+
+    use super::{call, call_with, call_with_max_retry_after};
+    use crate::predicates::http::is_retryable;
+    use crate::{
+        delay::Fixed,
+        http::tests::{serve_blocking as serve, OK, UNAVAILABLE},
+        Error, Policy,
+    };
+
+    const THROTTLED: &str = concat!(
+        "HTTP/1.1 429 Too Many Requests\r\nretry-after: 1\r\n",
+        "connection: close\r\ncontent-length: 0\r\n\r\n"
+    );
+    const RETRY_IN_AN_HOUR: &str = concat!(
+        "HTTP/1.1 503 Service Unavailable\r\nretry-after: 3600\r\n",
+        "connection: close\r\ncontent-length: 0\r\n\r\n"
+    );
+    const NOT_FOUND: &str =
+        "HTTP/1.1 404 Not Found\r\nconnection: close\r\ncontent-length: 0\r\n\r\n";
+
+    fn agent() -> Agent {
+        Agent::config_builder()
+            .http_status_as_error(false)
+            .build()
+            .into()
+    }
+
+    #[test]
+    fn retries_server_errors() {
+        let (address, requests) = serve(vec![UNAVAILABLE, UNAVAILABLE, OK]);
+        let policy = Policy::new(Fixed::from_millis(1).take(5));
+
+        let response = call(&policy, || ureq::get(&address).call()).unwrap();
+
+        assert_eq!(response.status(), 200);
+        assert_eq!(requests.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn honors_retry_after() {
+        let (address, requests) = serve(vec![THROTTLED, OK]);
+        let policy = Policy::new(Fixed::from_millis(1).take(1));
+        let agent = agent();
+        let start = Instant::now();
+
+        let response = call(&policy, || agent.get(&address).call()).unwrap();
+
+        assert_eq!(response.status(), 200);
+        assert!(start.elapsed() >= Duration::from_secs(1));
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn limits_retry_after_to_max_retry_after() {
+        let (address, requests) = serve(vec![RETRY_IN_AN_HOUR, OK]);
+        let policy = Policy::new(Fixed::from_millis(1).take(1));
+        let agent = agent();
+        let start = Instant::now();
+
+        let response =
+            call_with_max_retry_after(&policy, is_retryable, Duration::from_millis(1), || {
+                agent.get(&address).call()
+            })
+            .unwrap();
+
+        assert_eq!(response.status(), 200);
+        assert!(start.elapsed() < Duration::from_secs(60));
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn returns_other_statuses_at_once() {
+        let (address, requests) = serve(vec![NOT_FOUND, UNAVAILABLE, UNAVAILABLE]);
+        let policy = Policy::new(Fixed::from_millis(1).take(1));
+        let agent = agent();
+
+        let response = call(&policy, || agent.get(&address).call()).unwrap();
+        assert_eq!(response.status(), 404);
+
+        let result = call_with(
+            &policy,
+            |status| status == 503,
+            || agent.get(&address).call(),
+        );
+        assert!(matches!(
+            result,
+            Err(Error::Operation {
+                error: ureq::Error::StatusCode(503),
+                tries: 2,
+                ..
+            })
+        ));
+        assert_eq!(requests.load(Ordering::SeqCst), 3);
+    }
+}